@@ -2,11 +2,49 @@ use std::env;
 use std::path::{Path, PathBuf};
 
 fn main() {
-    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
-    let target_dir = get_cargo_target_dir();
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=third_party/SDL2");
 
-    link_sdl(target_arch.as_str());
-    copy_sdl_to_target(target_dir.as_str(), target_arch.as_str());
+    // Only the SDL frontend needs SDL2, the core builds for any target
+    if env::var_os("CARGO_FEATURE_SDL").is_none() {
+        return;
+    }
+    // The sdl2 crate builds SDL2 from source itself
+    if env::var_os("CARGO_FEATURE_BUNDLED").is_some() {
+        return;
+    }
+
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap();
+    match (target_os.as_str(), target_env.as_str()) {
+        // The vendored SDL2 is a DLL, there's nothing to link it statically with
+        ("windows", "msvc") if env::var_os("CARGO_FEATURE_SDL_STATIC").is_some() => {
+            panic!("Linking SDL2 statically on Windows needs the `bundled` feature to build it");
+        }
+        // There is no system SDL2 to link on Windows, the vendored one is used instead
+        ("windows", "msvc") => {
+            let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+            if let Some(sdl_dir) = get_sdl_dir(target_arch.as_str()) {
+                link_sdl(sdl_dir);
+                copy_sdl_to_target(get_cargo_target_dir().as_str(), sdl_dir);
+            } else {
+                println!(
+                    "cargo:warning=No vendored SDL2 for {target_arch}, linking SDL2.lib from the \
+                     LIB directories; build with the `bundled` feature to build it from source"
+                );
+            }
+        }
+        // Homebrew installs outside of the linker's default paths
+        ("macos", _) => {
+            for lib_dir in ["/opt/homebrew/lib", "/usr/local/lib"] {
+                if Path::new(lib_dir).exists() {
+                    println!("cargo:rustc-link-search=native={lib_dir}");
+                }
+            }
+        }
+        // Everywhere else the sdl2 crate links the system's SDL2
+        _ => {}
+    }
 }
 
 fn get_cargo_target_dir() -> String {
@@ -20,7 +58,7 @@ fn get_cargo_target_dir() -> String {
         .ancestors()
         .find(|path| {
             path.file_name()
-                .map_or(false, |name| name == profile_as_osstr)
+                .is_some_and(|name| name == profile_as_osstr)
         })
         .expect("Failed to find target directory matching PROFILE");
 
@@ -30,36 +68,29 @@ fn get_cargo_target_dir() -> String {
         .to_string()
 }
 
-fn get_sdl_dir(target_arch: &str) -> String {
+fn get_sdl_dir(target_arch: &str) -> Option<&'static str> {
     match target_arch {
-        "x86" => "third_party/SDL2/x86",
-        "x86_64" => "third_party/SDL2/x64",
-        _ => panic!("Unsupported target architecture: {}", target_arch),
+        "x86" => Some("third_party/SDL2/x86"),
+        "x86_64" => Some("third_party/SDL2/x64"),
+        _ => None,
     }
-    .to_string()
 }
 
-fn link_sdl(target_arch: &str) {
-    let sdl_dir = get_sdl_dir(target_arch);
-    let lib_path = PathBuf::from(sdl_dir.as_str()).join("SDL2.lib");
+fn link_sdl(sdl_dir: &str) {
+    let lib_path = PathBuf::from(sdl_dir).join("SDL2.lib");
     if lib_path.exists() {
-        println!("cargo:rerun-if-changed=migrations");
-        println!("cargo:rustc-link-search=native={}", sdl_dir);
+        println!("cargo:rustc-link-search=native={sdl_dir}");
         println!("cargo:rustc-link-lib=static=SDL2");
     } else {
-        panic!("SQLite library not found at {}", lib_path.display());
+        panic!("SDL2 library not found at {}", lib_path.display());
     }
 }
 
-fn copy_sdl_to_target(target_dir: &str, target_arch: &str) {
+fn copy_sdl_to_target(target_dir: &str, sdl_dir: &str) {
     if !Path::new(target_dir).exists() {
         std::fs::create_dir_all(target_dir).unwrap();
     }
 
-    let lib_path = PathBuf::from(target_dir).join("SDL2.dll");
-    let _ = match target_arch {
-        "x86" => std::fs::copy("third_party/SDL2/x86/SDL2.dll", lib_path),
-        "x86_64" => std::fs::copy("third_party/SDL2/x64/SDL2.dll", lib_path),
-        _ => panic!("Unsupported target architecture: {}", target_arch),
-    };
+    let dll_path = PathBuf::from(target_dir).join("SDL2.dll");
+    let _ = std::fs::copy(PathBuf::from(sdl_dir).join("SDL2.dll"), dll_path);
 }
@@ -0,0 +1,63 @@
+// Runs a ROM for a few seconds worth of frames through the C API and prints the screen as text.
+//
+// Build the static library and link against it:
+//
+//   cargo rustc --lib --release --no-default-features --features ffi --crate-type staticlib
+//   cc examples/c/main.c -Iinclude target/release/libchip8_emu.a -lpthread -ldl -lm -o chip8
+//   ./chip8 "path to game"
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "chip8_emu.h"
+
+#define WIDTH 64
+#define HEIGHT 32
+#define FRAMES 180
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <rom>\n", argv[0]);
+        return 1;
+    }
+
+    FILE *file = fopen(argv[1], "rb");
+    if (!file) {
+        perror("[-] Failed to open the ROM");
+        return 1;
+    }
+    uint8_t rom[4096];
+    size_t len = fread(rom, 1, sizeof(rom), file);
+    fclose(file);
+
+    Chip8 *chip8 = chip8_new();
+    if (!chip8) {
+        fprintf(stderr, "[-] Failed to create the emulator\n");
+        return 1;
+    }
+    int32_t result = chip8_load_rom(chip8, rom, len);
+    for (int frame = 0; result >= 0 && frame < FRAMES; frame++) {
+        result = chip8_frame(chip8, 0);
+    }
+    if (result < 0) {
+        fprintf(stderr, "[-] The emulator stopped with error %d\n", result);
+        chip8_free(chip8);
+        return 1;
+    }
+
+    uint8_t framebuffer[WIDTH * HEIGHT];
+    chip8_framebuffer(chip8, framebuffer, sizeof(framebuffer));
+    for (int y = 0; y < HEIGHT; y++) {
+        for (int x = 0; x < WIDTH; x++) {
+            putchar(framebuffer[y * WIDTH + x] ? '#' : ' ');
+        }
+        putchar('\n');
+    }
+
+    uint16_t pc = 0;
+    chip8_get_register(chip8, CHIP8_REG_PC, &pc);
+    printf("PC = 0x%03X\n", pc);
+
+    chip8_free(chip8);
+    return 0;
+}
@@ -0,0 +1,113 @@
+//! Drives the emulator from a loop of its own through a frontend that's nothing but memory: the
+//! frames it's shown are kept, the keys it hands over are scripted and the buzzer is counted.
+//! Any other UI (a terminal, a game engine's texture, a microcontroller's display) fits the same
+//! three traits.
+//!
+//! cargo run --example custom_frontend
+
+use std::cell::RefCell;
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::time::Instant;
+
+use chip8_emu::{AudioSink, Chip8, Frontend, Input, Keypad};
+
+const FRAMES: u32 = 10;
+
+// LD V0, K; LD F, V0; CLS; DRW V1, V1, 5; LD ST, V2; JP 0x200, showing the digit of every key
+// pressed with a beep
+const ROM: &[u8] = &[
+    0x62, 0x08, 0xF0, 0x0A, 0xF0, 0x29, 0x00, 0xE0, 0xD1, 0x15, 0xF2, 0x18, 0x12, 0x02,
+];
+
+///
+/// The `Ui` struct represents what the frontend showed and heard, shared with the loop reading it.
+///
+#[derive(Default)]
+struct Ui {
+    frame: Vec<u8>,
+    presents: usize,
+    beeps: usize,
+}
+
+///
+/// The `MemoryFrontend` struct represents a frontend keeping everything in `Ui`, pressing key 7 on
+/// the third frame and letting it go on the fifth.
+///
+struct MemoryFrontend {
+    ui: Rc<RefCell<Ui>>,
+    frame: u32,
+    inputs: Vec<Input>,
+}
+
+impl Keypad for MemoryFrontend {
+    fn poll_input(&mut self) -> Option<Input> {
+        self.inputs.pop()
+    }
+}
+
+impl AudioSink for MemoryFrontend {
+    fn set_tone(&mut self, on: bool) {
+        self.ui.borrow_mut().beeps += usize::from(on);
+    }
+}
+
+impl Frontend for MemoryFrontend {
+    fn present(&mut self, framebuffer: &[u8]) {
+        let mut ui = self.ui.borrow_mut();
+        ui.frame = framebuffer.to_vec();
+        ui.presents += 1;
+    }
+
+    fn end_frame(&mut self) {
+        self.frame += 1;
+        let key = |pressed| Input::Key {
+            hex_key: 7,
+            pressed,
+            at: Instant::now(),
+        };
+        match self.frame {
+            3 => self.inputs.push(key(true)),
+            5 => self.inputs.push(key(false)),
+            _ => {}
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let ui = Rc::new(RefCell::new(Ui::default()));
+    let frontend = MemoryFrontend {
+        ui: Rc::clone(&ui),
+        frame: 0,
+        inputs: Vec::new(),
+    };
+    let result = Chip8::builder()
+        .frontend(Box::new(frontend))
+        .build()
+        .and_then(|mut chip8| {
+            chip8.load_rom_bytes(ROM)?;
+            for _ in 0..FRAMES {
+                if chip8.handle_inputs().is_some() {
+                    break;
+                }
+                chip8.run_frame()?;
+                chip8.end_frame();
+            }
+            Ok(())
+        });
+    if let Err(err) = result {
+        eprintln!("[-] {err}");
+        return ExitCode::FAILURE;
+    }
+
+    let ui = ui.borrow();
+    for row in ui.frame.chunks(64).take(5) {
+        let line: String = row[..8]
+            .iter()
+            .map(|&pixel| if pixel == 0 { '.' } else { '#' })
+            .collect();
+        println!("{line}");
+    }
+    println!("{} frames shown, {} beeps", ui.presents, ui.beeps);
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,50 @@
+//! Debugs a ROM through the library instead of the window: stops at a conditional breakpoint,
+//! steps over the next few instructions and prints the registers after each one.
+//!
+//! cargo run --example debugger_api
+
+use std::process::ExitCode;
+
+use chip8_emu::{Breakpoint, Chip8, Chip8Error};
+
+// LD V0, 0x00; ADD V0, 0x01; LD I, 0x300; JP 0x202, counting up in V0 forever
+const ROM: &[u8] = &[0x60, 0x00, 0x70, 0x01, 0xA3, 0x00, 0x12, 0x02];
+
+const STEPS: usize = 3;
+
+fn debug() -> Result<(), Chip8Error> {
+    let mut chip8 = Chip8::headless();
+    chip8.load_rom_bytes(ROM)?;
+    let breakpoint: Breakpoint = "0x204 if v0 == 5"
+        .parse()
+        .expect("the breakpoint is well-formed");
+    chip8.add_breakpoint(breakpoint);
+
+    let summary = chip8.run_frames(60)?;
+    let Some(address) = summary.breakpoint else {
+        println!("[-] The breakpoint wasn't hit.");
+        return Ok(());
+    };
+    println!(
+        "[+] Stopped at {address:#05X} after {} instructions",
+        summary.cycles
+    );
+    println!("{}", chip8.registers());
+
+    chip8.remove_breakpoint(address);
+    for _ in 0..STEPS {
+        let pc = chip8.pc();
+        chip8.step()?;
+        println!("[+] Stepped over {pc:#05X}");
+        println!("{}", chip8.registers());
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    if let Err(err) = debug() {
+        eprintln!("[-] {err}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,96 @@
+//! Drives the bare `Cpu` the way firmware for a microcontroller with a 64×32 LED matrix would:
+//! no allocator, a fixed array for the framebuffer, a tiny xorshift generator for `RND` and one
+//! timer tick per frame. Only the printing at the end needs `std`. The caption under the logo is
+//! drawn straight into the matrix, the way firmware would show its own messages.
+//!
+//! cargo run --example embedded_sim
+
+use chip8_emu::emu::io::{Screen, GRID_HEIGHT, GRID_WIDTH};
+use chip8_emu::emu::quirks::Quirks;
+use chip8_emu::{Cpu, RandomSource};
+
+const FRAMES: usize = 60;
+const STEPS_PER_FRAME: usize = 7;
+
+// The IBM logo, the same ROM the emulator ships as its demo
+const ROM: &[u8] = include_bytes!("../assets/IBM Logo");
+
+///
+/// Stands in for the LED matrix, which would be refreshed from `draw`.
+///
+struct LedMatrix {
+    leds: [u8; GRID_WIDTH * GRID_HEIGHT],
+    refreshes: usize,
+}
+
+impl Screen for LedMatrix {
+    fn set_pixel(&mut self, x: usize, y: usize, new_pixel: u8) {
+        self.leds[y * GRID_WIDTH + x] = new_pixel;
+    }
+
+    fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        self.leds[y * GRID_WIDTH + x]
+    }
+
+    fn framebuffer(&self) -> &[u8] {
+        &self.leds
+    }
+
+    fn draw(&mut self) {
+        self.refreshes += 1;
+    }
+
+    fn clear(&mut self) {
+        self.leds.fill(0);
+    }
+}
+
+///
+/// A xorshift generator, small enough for any microcontroller.
+///
+struct XorShift(u32);
+
+impl RandomSource for XorShift {
+    fn random_byte(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as u8
+    }
+}
+
+fn main() {
+    let mut matrix = LedMatrix {
+        leds: [0; GRID_WIDTH * GRID_HEIGHT],
+        refreshes: 0,
+    };
+    let mut rng = XorShift(0x1234_5678);
+    let mut cpu = Cpu::new(Quirks::default(), 0x200);
+    cpu.load_rom(ROM).expect("the demo ROM fits in memory");
+
+    for _ in 0..FRAMES {
+        for _ in 0..STEPS_PER_FRAME {
+            if let Err(err) = cpu.step(&mut matrix, &mut rng) {
+                eprintln!("[-] The CPU stopped. Error => `{err}`");
+                return;
+            }
+        }
+        cpu.tick_timers(1);
+    }
+    // Dark on a lit bar below the logo
+    matrix.draw_text(2, 26, "60 FRAMES");
+    matrix.invert_region(0, 25, GRID_WIDTH, 7);
+
+    for row in matrix.leds.chunks(GRID_WIDTH) {
+        let line: String = row
+            .iter()
+            .map(|&led| if led == 1 { '#' } else { ' ' })
+            .collect();
+        println!("{line}");
+    }
+    println!(
+        "PC = 0x{:03X} after {} refreshes",
+        cpu.registers().pc,
+        matrix.refreshes
+    );
+}
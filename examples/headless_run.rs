@@ -0,0 +1,42 @@
+//! Runs a ROM without any window: loads it from bytes, executes 10 000 instructions and prints the
+//! screen they drew as text, the way a test or a batch job would use the emulator.
+//!
+//! cargo run --example headless_run
+
+use std::process::ExitCode;
+
+use chip8_emu::Chip8;
+
+const CYCLES: u64 = 10_000;
+
+// The IBM logo, the same ROM the emulator ships as its demo
+const ROM: &[u8] = include_bytes!("../assets/IBM Logo");
+
+fn main() -> ExitCode {
+    let mut chip8 = Chip8::headless();
+    let result = chip8
+        .load_rom_bytes(ROM)
+        .and_then(|()| chip8.run_cycles(CYCLES));
+    let summary = match result {
+        Ok(summary) => summary,
+        Err(err) => {
+            eprintln!("[-] {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for row in chip8.framebuffer_rows() {
+        let line: String = row
+            .iter()
+            .map(|&pixel| if pixel == 0 { ' ' } else { '#' })
+            .collect();
+        println!("{}", line.trim_end());
+    }
+    println!(
+        "{} instructions, PC at {:#05X}, display changed: {}",
+        summary.cycles,
+        chip8.pc(),
+        summary.display_changed
+    );
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,47 @@
+//! Shows a byte of memory over the screen as the game runs, the way a training-mode script would
+//! keep an eye on a score or a life counter.
+//!
+//! cargo run --example hud_script --features script -- "path to game" 0x1F0
+
+use std::env;
+use std::process::ExitCode;
+
+use chip8_emu::emu::script::{Script, ScriptApi, ScriptError, ScriptHost};
+use chip8_emu::Chip8;
+
+struct MemoryHud {
+    address: u16,
+    frames: u64,
+}
+
+impl Script for MemoryHud {
+    fn on_frame(&mut self, emu: &mut ScriptApi<'_>) -> Result<(), ScriptError> {
+        self.frames += 1;
+        let value = emu.read_mem(self.address)?;
+        emu.draw_text(format!("[{:03X}] = {value:02X} ({value})", self.address))?;
+        emu.draw_text(format!("Frame {}", self.frames))
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let (Some(rom), Some(address)) = (args.next(), args.next()) else {
+        eprintln!("Usage: hud_script <rom> <address>");
+        return ExitCode::FAILURE;
+    };
+    let Ok(address) = u16::from_str_radix(address.trim_start_matches("0x"), 16) else {
+        eprintln!("[-] Invalid address `{address}`, expected hexadecimal.");
+        return ExitCode::FAILURE;
+    };
+
+    let result = Chip8::new(800, 600).and_then(|mut chip8| {
+        chip8.load_rom(&rom, None)?;
+        chip8.set_script(ScriptHost::new(MemoryHud { address, frames: 0 }));
+        chip8.run()
+    });
+    if let Err(err) = result {
+        eprintln!("[-] {err}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,70 @@
+// The JavaScript half of the browser frontend: it copies the ROM into the module, ticks it once
+// per animation frame, blits the framebuffer onto the canvas and forwards the keyboard.
+
+const WIDTH = 64;
+const HEIGHT = 32;
+
+// Same layout as the desktop build: 1234/QWER/ASDF/ZXCV mirror the hex keypad
+const KEYPAD = {
+    Digit1: 0x1, Digit2: 0x2, Digit3: 0x3, Digit4: 0xC,
+    KeyQ: 0x4, KeyW: 0x5, KeyE: 0x6, KeyR: 0xD,
+    KeyA: 0x7, KeyS: 0x8, KeyD: 0x9, KeyF: 0xE,
+    KeyZ: 0xA, KeyX: 0x0, KeyC: 0xB, KeyV: 0xF,
+};
+
+const { instance } = await WebAssembly.instantiateStreaming(fetch("chip8_emu.wasm"));
+const wasm = instance.exports;
+
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+const image = context.createImageData(WIDTH, HEIGHT);
+const status = document.getElementById("status");
+let running = false;
+
+function blit() {
+    const pixels = new Uint8Array(wasm.memory.buffer, wasm.framebuffer_ptr(), WIDTH * HEIGHT);
+    for (let i = 0; i < pixels.length; i++) {
+        // Green for 'on' pixels, black for the rest
+        image.data[i * 4 + 1] = pixels[i] ? 255 : 0;
+        image.data[i * 4 + 3] = 255;
+    }
+    context.putImageData(image, 0, 0);
+}
+
+function frame() {
+    const result = wasm.tick(1);
+    if (result < 0) {
+        running = false;
+        status.textContent = "The ROM hit an invalid instruction.";
+        return;
+    }
+    if (result > 0) {
+        blit();
+    }
+    requestAnimationFrame(frame);
+}
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+    const rom = new Uint8Array(await event.target.files[0].arrayBuffer());
+    const buffer = wasm.rom_buffer(rom.length);
+    new Uint8Array(wasm.memory.buffer, buffer, rom.length).set(rom);
+
+    if (!wasm.init(Math.floor(Math.random() * 2 ** 32))) {
+        status.textContent = "That doesn't look like a CHIP-8 ROM.";
+        return;
+    }
+    status.textContent = event.target.files[0].name;
+    blit();
+    if (!running) {
+        running = true;
+        requestAnimationFrame(frame);
+    }
+});
+
+for (const [type, pressed] of [["keydown", true], ["keyup", false]]) {
+    document.addEventListener(type, (event) => {
+        if (event.code in KEYPAD) {
+            wasm.key_event(KEYPAD[event.code], pressed);
+        }
+    });
+}
@@ -0,0 +1,110 @@
+#ifndef CHIP8_EMU_H
+#define CHIP8_EMU_H
+
+/* Mirrors src/ffi.rs, regenerate with `cbindgen --config cbindgen.toml` after changing it. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The call succeeded.
+#define CHIP8_OK 0
+
+// A pointer argument was null.
+#define CHIP8_ERR_NULL -1
+
+// A length or register index was out of range.
+#define CHIP8_ERR_INVALID_ARGUMENT -2
+
+// The ROM is empty or doesn't fit in memory.
+#define CHIP8_ERR_INVALID_ROM -3
+
+// The emulator hit an instruction it can't decode or an out-of-bounds memory access.
+#define CHIP8_ERR_EXECUTION -4
+
+// The emulator panicked, its state shouldn't be trusted anymore.
+#define CHIP8_ERR_PANIC -5
+
+// `chip8_get_register` index of `I`, after `V0` to `VF`.
+#define CHIP8_REG_I 16
+
+// `chip8_get_register` index of the program counter.
+#define CHIP8_REG_PC 17
+
+// `chip8_get_register` index of the delay timer.
+#define CHIP8_REG_DT 18
+
+// `chip8_get_register` index of the sound timer.
+#define CHIP8_REG_ST 19
+
+//
+// The `Chip8` structure represents the interface for using the chip8 emulator.
+//
+typedef struct Chip8 Chip8;
+
+//
+// Creates a headless emulator. Returns null if it couldn't be created.
+//
+Chip8 *chip8_new(void);
+
+//
+// Destroys an emulator created by `chip8_new`. Null is ignored.
+//
+// # Safety
+//
+// `chip8` must be null or come from `chip8_new`, and mustn't be used afterwards.
+//
+void chip8_free(Chip8 *chip8);
+
+//
+// Loads the `len` bytes at `rom` and restarts the machine with them.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`, and `rom` must point to `len` readable bytes.
+//
+int32_t chip8_load_rom(Chip8 *chip8, const uint8_t *rom, size_t len);
+
+//
+// Executes exactly one instruction.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+//
+int32_t chip8_step(Chip8 *chip8);
+
+//
+// Runs one 60 Hz frame with the hex keys set in `keys` (bit `n` for key `n`) held down.
+// Returns `1` while the buzzer sounds, `0` otherwise, or a negative error code.
+//
+// The keypad only registers one key at a time, the lowest one held wins.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+//
+int32_t chip8_frame(Chip8 *chip8, uint16_t keys);
+
+//
+// Copies the 64×32 framebuffer, one byte (0 or 1) per pixel row after row, into `out`, which
+// must have room for at least `len` >= 2048 bytes.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`, and `out` must point to `len` writable bytes.
+//
+int32_t chip8_framebuffer(const Chip8 *chip8, uint8_t *out, size_t len);
+
+//
+// Stores the value of a register into `out`: `V0` to `VF` for indices 0 to 15, then
+// `CHIP8_REG_I`, `CHIP8_REG_PC`, `CHIP8_REG_DT` and `CHIP8_REG_ST`.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`, and `out` must point to a writable `uint16_t`.
+//
+int32_t chip8_get_register(const Chip8 *chip8, uint8_t index, uint16_t *out);
+
+#endif  /* CHIP8_EMU_H */
@@ -0,0 +1,163 @@
+//!
+//! Python bindings for the emulator core, for scripting experiments around a ROM:
+//!
+//! ```python
+//! import chip8_emu
+//!
+//! chip8 = chip8_emu.Chip8(seed=0)
+//! chip8.load_rom(open("ibm.ch8", "rb").read())
+//! chip8.run_frames(60)
+//! chip8.screenshot("ibm.png")
+//! ```
+//!
+//! Errors of the emulator are raised as `chip8_emu.Chip8Error` and panics as pyo3's
+//! `PanicException`. A `Chip8` holds the GIL while it runs and belongs to the thread that created
+//! it, using it from another thread raises `RuntimeError`.
+//!
+#![warn(clippy::all)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::needless_pass_by_value)]
+
+use std::path::PathBuf;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+create_exception!(chip8_emu, Chip8Error, PyException, "Raised when the emulator fails.");
+
+fn chip8_error(err: impl ToString) -> PyErr {
+    Chip8Error::new_err(err.to_string())
+}
+
+///
+/// The `Chip8` class wraps the emulator, drawing into an in-memory screen.
+///
+#[pyclass(name = "Chip8", module = "chip8_emu", unsendable)]
+struct PyChip8 {
+    chip8: emulator::Chip8,
+}
+
+#[pymethods]
+impl PyChip8 {
+    #[new]
+    #[pyo3(signature = (seed = None, variant = None, ips = None))]
+    fn new(seed: Option<u64>, variant: Option<&str>, ips: Option<u32>) -> PyResult<Self> {
+        // Without the `sdl` feature, the emulator draws into an in-memory screen
+        let mut builder = emulator::Chip8::builder();
+        if let Some(seed) = seed {
+            builder = builder.seed(seed);
+        }
+        if let Some(variant) = variant {
+            let variant = variant
+                .parse::<emulator::Variant>()
+                .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            builder = builder.variant(variant);
+        }
+        if let Some(ips) = ips {
+            builder = builder.ips(ips);
+        }
+        let chip8 = builder.build().map_err(chip8_error)?;
+        Ok(Self { chip8 })
+    }
+
+    ///
+    /// Loads the ROM and restarts the machine with it.
+    ///
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        self.chip8.load_rom_bytes(rom).map_err(chip8_error)
+    }
+
+    fn reset(&mut self) {
+        self.chip8.reset();
+    }
+
+    ///
+    /// Executes one instruction and returns what it did, as a dict.
+    ///
+    fn step<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let outcome = self.chip8.step().map_err(chip8_error)?;
+        let summary = PyDict::new_bound(py);
+        summary.set_item("cycles", outcome.cycles)?;
+        summary.set_item("display_changed", outcome.display_changed)?;
+        summary.set_item("sound_on", outcome.sound_on)?;
+        summary.set_item("waiting_for_key", outcome.waiting_for_key)?;
+        Ok(summary)
+    }
+
+    ///
+    /// Runs `frames` 60 Hz frames and returns what they did, as a dict.
+    ///
+    fn run_frames<'py>(&mut self, py: Python<'py>, frames: u32) -> PyResult<Bound<'py, PyDict>> {
+        let run = self.chip8.run_frames(frames).map_err(chip8_error)?;
+        let summary = PyDict::new_bound(py);
+        summary.set_item("cycles", run.cycles)?;
+        summary.set_item("display_changed", run.display_changed)?;
+        summary.set_item("sound_on", run.sound_on)?;
+        summary.set_item("waiting_for_key", run.waiting_for_key)?;
+        Ok(summary)
+    }
+
+    ///
+    /// Holds down the hex keys set in `mask` (bit `n` for key `n`) and releases the others.
+    ///
+    fn keys(&mut self, mask: u16) {
+        self.chip8.set_keys(mask);
+    }
+
+    ///
+    /// Returns the 64×32 screen, one byte (0 or 1) per pixel, row after row.
+    ///
+    fn framebuffer<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, self.chip8.framebuffer())
+    }
+
+    ///
+    /// Returns the registers as a dict: `v` (a list of 16), `i`, `pc`, `dt`, `st` and `stack`.
+    ///
+    fn registers<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let registers = self.chip8.registers();
+        let dict = PyDict::new_bound(py);
+        dict.set_item("v", registers.v.to_vec())?;
+        dict.set_item("i", registers.i)?;
+        dict.set_item("pc", registers.pc)?;
+        dict.set_item("dt", registers.dt)?;
+        dict.set_item("st", registers.st)?;
+        dict.set_item("stack", self.chip8.stack().to_vec())?;
+        Ok(dict)
+    }
+
+    fn set_v(&mut self, x: u8, value: u8) -> PyResult<()> {
+        if x > 0xF {
+            return Err(PyValueError::new_err(format!("No register V{x}!")));
+        }
+        self.chip8.set_v(x, value);
+        Ok(())
+    }
+
+    fn set_i(&mut self, i: u16) {
+        self.chip8.set_i(i);
+    }
+
+    fn set_pc(&mut self, pc: u16) {
+        self.chip8.set_pc(pc);
+    }
+
+    ///
+    /// Saves the screen as a PNG, every pixel scaled up to a `scale`×`scale` square.
+    ///
+    #[pyo3(signature = (path, scale = 8))]
+    fn screenshot(&self, path: PathBuf, scale: u32) -> PyResult<()> {
+        self.chip8
+            .screenshot(path, scale)
+            .map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+}
+
+#[pymodule]
+fn chip8_emu(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyChip8>()?;
+    m.add("Chip8Error", m.py().get_type_bound::<Chip8Error>())?;
+    Ok(())
+}
@@ -0,0 +1,77 @@
+import struct
+
+import pytest
+
+import chip8_emu
+
+# LD I, 0x000 (font sprite of 0); DRW V0, V0, 5; LD V1, 0x3C; LD DT, V1; LD V2, K; JMP 0x20A
+ROM = bytes([0xA0, 0x00, 0xD0, 0x05, 0x61, 0x3C, 0xF1, 0x15, 0xF2, 0x0A, 0x12, 0x0A])
+
+
+@pytest.fixture
+def chip8():
+    chip8 = chip8_emu.Chip8(seed=0)
+    chip8.load_rom(ROM)
+    return chip8
+
+
+def test_step_reports_the_outcome(chip8):
+    assert chip8.step() == {
+        "cycles": 1,
+        "display_changed": False,
+        "sound_on": False,
+        "waiting_for_key": False,
+    }
+    assert chip8.step()["display_changed"]
+    assert chip8.registers()["pc"] == 0x204
+
+
+def test_framebuffer_is_bytes(chip8):
+    chip8.run_frames(1)
+    framebuffer = chip8.framebuffer()
+    assert isinstance(framebuffer, bytes)
+    assert len(framebuffer) == 64 * 32
+    # The top of the font sprite of 0 is 0xF0
+    assert framebuffer[:5] == b"\x01\x01\x01\x01\x00"
+
+
+def test_frames_tick_the_timers_and_wait_for_keys(chip8):
+    summary = chip8.run_frames(30)
+    assert summary["waiting_for_key"]
+    assert chip8.registers()["dt"] == 0x3C - 30
+
+    chip8.keys(1 << 0xB | 1 << 0xE)
+    assert not chip8.run_frames(1)["waiting_for_key"]
+    assert chip8.registers()["v"][2] == 0xB
+
+
+def test_registers_can_be_poked(chip8):
+    chip8.set_v(3, 0x2A)
+    chip8.set_i(0x300)
+    chip8.set_pc(0x208)
+    registers = chip8.registers()
+    assert registers["v"][3] == 0x2A
+    assert registers["i"] == 0x300
+    assert registers["pc"] == 0x208
+    assert registers["stack"] == []
+    with pytest.raises(ValueError):
+        chip8.set_v(16, 0)
+
+
+def test_errors_become_exceptions():
+    chip8 = chip8_emu.Chip8()
+    with pytest.raises(chip8_emu.Chip8Error):
+        chip8.load_rom(b"")
+    with pytest.raises(ValueError):
+        chip8_emu.Chip8(variant="xochip")
+    with pytest.raises(chip8_emu.Chip8Error):
+        chip8_emu.Chip8(ips=0)
+
+
+def test_screenshot_writes_a_png(chip8, tmp_path):
+    chip8.run_frames(1)
+    path = tmp_path / "screen.png"
+    chip8.screenshot(str(path), scale=4)
+    png = path.read_bytes()
+    assert png[:8] == b"\x89PNG\r\n\x1a\n"
+    assert struct.unpack(">II", png[16:24]) == (256, 128)
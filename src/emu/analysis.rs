@@ -0,0 +1,913 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+
+use super::cpu::{Cpu, Instruction, WORD_SIZE};
+use super::memory::{RAM_SIZE, STACK_SIZE};
+use super::quirks::Variant;
+use super::trace::escape_json;
+
+///
+/// The `Extension` enum represents the instruction sets extending CHIP-8's.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extension {
+    SChip,
+    XoChip,
+}
+
+impl Extension {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::SChip => "SUPER-CHIP",
+            Self::XoChip => "XO-CHIP",
+        }
+    }
+
+    const fn key(self) -> &'static str {
+        match self {
+            Self::SChip => "schip",
+            Self::XoChip => "xochip",
+        }
+    }
+}
+
+///
+/// Returns the extension the opcode belongs to and what it does there, if it's an instruction
+/// the original CHIP-8 doesn't have.
+///
+/// ```
+/// use chip8_emu::emu::analysis::{extension_instruction, Extension};
+///
+/// assert_eq!(extension_instruction(0x00FF), Some((Extension::SChip, "hires")));
+/// assert_eq!(extension_instruction(0xF002), Some((Extension::XoChip, "audio")));
+/// assert_eq!(extension_instruction(0xD015), None);
+/// ```
+///
+pub const fn extension_instruction(opcode: u16) -> Option<(Extension, &'static str)> {
+    let instruction = match opcode {
+        0x00C0..=0x00CF => (Extension::SChip, "scroll down"),
+        0x00D0..=0x00DF => (Extension::XoChip, "scroll up"),
+        0x00FB => (Extension::SChip, "scroll right"),
+        0x00FC => (Extension::SChip, "scroll left"),
+        0x00FD => (Extension::SChip, "exit"),
+        0x00FE => (Extension::SChip, "lores"),
+        0x00FF => (Extension::SChip, "hires"),
+        0xF000 => (Extension::XoChip, "long i :="),
+        0xF002 => (Extension::XoChip, "audio"),
+        _ if opcode & 0xF00F == 0xD000 => (Extension::SChip, "16x16 sprite"),
+        _ if opcode & 0xF00F == 0x5002 => (Extension::XoChip, "save range"),
+        _ if opcode & 0xF00F == 0x5003 => (Extension::XoChip, "load range"),
+        _ if opcode & 0xF0FF == 0xF001 => (Extension::XoChip, "plane"),
+        _ if opcode & 0xF0FF == 0xF030 => (Extension::SChip, "big hex font"),
+        _ if opcode & 0xF0FF == 0xF03A => (Extension::XoChip, "pitch"),
+        _ if opcode & 0xF0FF == 0xF075 => (Extension::SChip, "save flags"),
+        _ if opcode & 0xF0FF == 0xF085 => (Extension::SChip, "load flags"),
+        _ => return None,
+    };
+    Some(instruction)
+}
+
+///
+/// The `Issue` enum represents something in a ROM that goes wrong when it runs.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// A ROM of `size` bytes, with room for only `room` of them
+    RomTooLarge {
+        size: usize,
+        room: usize,
+    },
+    UnknownOpcode {
+        address: u16,
+        opcode: u16,
+    },
+    OddTarget {
+        address: u16,
+        opcode: u16,
+        target: u16,
+    },
+    TargetOutsideRom {
+        address: u16,
+        opcode: u16,
+        target: u16,
+    },
+    /// An access through `I` to the bytes from `start` on, up to `end` exclusive
+    DataOutsideMemory {
+        address: u16,
+        opcode: u16,
+        start: usize,
+        end: usize,
+    },
+    StackOverflow {
+        depth: usize,
+    },
+}
+
+impl Issue {
+    ///
+    /// Returns the address of the instruction at fault, unless the issue is with the whole ROM.
+    ///
+    pub const fn address(&self) -> Option<u16> {
+        match self {
+            Self::RomTooLarge { .. } | Self::StackOverflow { .. } => None,
+            Self::UnknownOpcode { address, .. }
+            | Self::OddTarget { address, .. }
+            | Self::TargetOutsideRom { address, .. }
+            | Self::DataOutsideMemory { address, .. } => Some(*address),
+        }
+    }
+
+    const fn kind(&self) -> &'static str {
+        match self {
+            Self::RomTooLarge { .. } => "rom-too-large",
+            Self::UnknownOpcode { .. } => "unknown-opcode",
+            Self::OddTarget { .. } => "odd-target",
+            Self::TargetOutsideRom { .. } => "target-outside-rom",
+            Self::DataOutsideMemory { .. } => "data-outside-memory",
+            Self::StackOverflow { .. } => "stack-overflow",
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::RomTooLarge { size, room } => {
+                write!(f, "ROM is {size} bytes, but only {room} bytes fit in RAM")
+            }
+            Self::UnknownOpcode { address, opcode } => {
+                write!(f, "0x{address:04X}: unknown opcode {opcode:04X}")
+            }
+            Self::OddTarget {
+                address,
+                opcode,
+                target,
+            } => write!(
+                f,
+                "0x{address:04X}: {opcode:04X} targets the odd address 0x{target:04X}"
+            ),
+            Self::TargetOutsideRom {
+                address,
+                opcode,
+                target,
+            } => write!(
+                f,
+                "0x{address:04X}: {opcode:04X} targets 0x{target:04X} outside the ROM"
+            ),
+            Self::DataOutsideMemory {
+                address,
+                opcode,
+                start,
+                end,
+            } => {
+                let access = if matches!(opcode & 0xF0FF, 0xF033 | 0xF055) {
+                    "writes"
+                } else {
+                    "reads"
+                };
+                write!(
+                    f,
+                    "0x{address:04X}: {opcode:04X} {access} 0x{start:04X}-0x{:04X} past the end \
+                     of the memory",
+                    end - 1
+                )
+            }
+            Self::StackOverflow { depth } => write!(
+                f,
+                "Calls nest up to {depth} deep, but the stack only holds {STACK_SIZE}"
+            ),
+        }
+    }
+}
+
+///
+/// The `ExtensionUse` struct represents an instruction of an extension found in a ROM.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionUse {
+    pub address: u16,
+    pub opcode: u16,
+    pub extension: Extension,
+    pub name: &'static str,
+}
+
+///
+/// The `Analysis` struct represents what a static pass over a ROM found, following its control
+/// flow from the entry point without running it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// The issues, the ones of the whole ROM first and the others by address
+    pub issues: Vec<Issue>,
+    /// How many instructions the control flow reaches
+    pub reachable: usize,
+    /// How deep the calls nest at most, `None` when subroutines recurse
+    pub stack_depth: Option<usize>,
+    pub extensions: Vec<ExtensionUse>,
+}
+
+///
+/// The `ReportFormat` enum represents how an analysis is written out.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+///
+/// The `ReportFormatError` struct represents a report format that isn't supported.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportFormatError(String);
+
+impl Error for ReportFormatError {}
+
+impl fmt::Display for ReportFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown report format `{}`, expected `text` or `json`!",
+            self.0
+        )
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = ReportFormatError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(ReportFormatError(name.to_string())),
+        }
+    }
+}
+
+impl Analysis {
+    ///
+    /// Returns the variant to run the ROM as, when it uses SUPER-CHIP instructions.
+    ///
+    pub fn suggested_variant(&self) -> Option<Variant> {
+        self.extensions
+            .iter()
+            .any(|used| used.extension == Extension::SChip)
+            .then_some(Variant::SChip)
+    }
+
+    ///
+    /// Writes the issues one per line, followed by a summary of the control flow and of the
+    /// extensions used.
+    ///
+    pub fn write_text(&self, out: &mut String) {
+        for issue in &self.issues {
+            let _ = writeln!(out, "{issue}");
+        }
+        let _ = writeln!(out, "Reachable instructions: {}", self.reachable);
+        let _ = match self.stack_depth {
+            Some(depth) => writeln!(out, "Stack depth: at most {depth} call(s)"),
+            None => writeln!(out, "Stack depth: unbounded, the subroutines recurse"),
+        };
+        for extension in [Extension::SChip, Extension::XoChip] {
+            let uses: Vec<String> = self
+                .extensions
+                .iter()
+                .filter(|used| used.extension == extension)
+                .map(|used| format!("0x{:04X} {:04X} ({})", used.address, used.opcode, used.name))
+                .collect();
+            if uses.is_empty() {
+                continue;
+            }
+            let advice = match extension {
+                Extension::SChip => {
+                    "SUPER-CHIP's instructions aren't supported, --variant schip only follows its \
+                     quirks"
+                }
+                Extension::XoChip => "XO-CHIP isn't supported",
+            };
+            let _ = writeln!(
+                out,
+                "{} instructions: {}; {advice}",
+                extension.name(),
+                uses.join(", ")
+            );
+        }
+    }
+
+    ///
+    /// Writes the analysis as a JSON object, on a single line.
+    ///
+    pub fn write_json(&self, out: &mut String) {
+        out.push_str("{\"issues\":[");
+        for (n, issue) in self.issues.iter().enumerate() {
+            let separator = if n == 0 { "" } else { "," };
+            let _ = write!(
+                out,
+                "{separator}{{\"kind\":\"{}\",\"address\":",
+                issue.kind()
+            );
+            let _ = match issue.address() {
+                Some(address) => write!(out, "{address}"),
+                None => write!(out, "null"),
+            };
+            out.push_str(",\"message\":\"");
+            escape_json(&issue.to_string(), out);
+            out.push_str("\"}");
+        }
+        let _ = write!(out, "],\"reachable\":{},\"stack_depth\":", self.reachable);
+        let _ = match self.stack_depth {
+            Some(depth) => write!(out, "{depth}"),
+            None => write!(out, "null"),
+        };
+        out.push_str(",\"extensions\":[");
+        for (n, used) in self.extensions.iter().enumerate() {
+            let separator = if n == 0 { "" } else { "," };
+            let _ = write!(
+                out,
+                "{separator}{{\"address\":{},\"opcode\":{},\"extension\":\"{}\",\"name\":\"{}\"}}",
+                used.address,
+                used.opcode,
+                used.extension.key(),
+                used.name
+            );
+        }
+        let variant = match self.suggested_variant() {
+            Some(_) => "\"schip\"",
+            None => "null",
+        };
+        let _ = write!(out, "],\"suggested_variant\":{variant}}}");
+    }
+}
+
+///
+/// The `KnownI` enum represents what the analysis knows of `I` before an instruction.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KnownI {
+    Known(u16),
+    Unknown,
+}
+
+impl KnownI {
+    fn join(self, other: Self) -> Self {
+        if self == other {
+            self
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+///
+/// The `Flow` struct represents where the control goes after an instruction: the instructions
+/// of the same subroutine, and the subroutine it calls.
+///
+#[derive(Debug, Default)]
+struct Flow {
+    next: Vec<u16>,
+    call: Option<u16>,
+}
+
+///
+/// The `Walker` struct represents the state of the analysis of a ROM loaded at `start`.
+///
+struct Walker<'a> {
+    rom: &'a [u8],
+    start: u16,
+    /// The reachable instructions, by address
+    code: BTreeMap<u16, u16>,
+    flows: BTreeMap<u16, Flow>,
+    issues: Vec<Issue>,
+    extensions: Vec<ExtensionUse>,
+}
+
+impl Walker<'_> {
+    fn opcode(&self, address: u16) -> Option<u16> {
+        let offset = usize::from(address.checked_sub(self.start)?);
+        match self.rom.get(offset..offset + 2)? {
+            &[hi, lo] => Some(u16::from_be_bytes([hi, lo])),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns the target of a jump or a call, unless it can't be followed.
+    ///
+    fn target(&mut self, address: u16, opcode: u16) -> Option<u16> {
+        let target = opcode & 0x0FFF;
+        let rom_end = usize::from(self.start) + self.rom.len();
+        if !target.is_multiple_of(2) {
+            self.issues.push(Issue::OddTarget {
+                address,
+                opcode,
+                target,
+            });
+            None
+        } else if target < self.start || usize::from(target) >= rom_end {
+            self.issues.push(Issue::TargetOutsideRom {
+                address,
+                opcode,
+                target,
+            });
+            None
+        } else {
+            Some(target)
+        }
+    }
+
+    fn flow(&mut self, address: u16, opcode: u16) -> Flow {
+        let next = address.wrapping_add(WORD_SIZE);
+        if let Some((extension, name)) = extension_instruction(opcode) {
+            self.extensions.push(ExtensionUse {
+                address,
+                opcode,
+                extension,
+                name,
+            });
+            return match opcode {
+                0x00FD => Flow::default(),
+                // The address comes in the next word
+                0xF000 => Flow {
+                    next: vec![next.wrapping_add(WORD_SIZE)],
+                    call: None,
+                },
+                _ => Flow {
+                    next: vec![next],
+                    call: None,
+                },
+            };
+        }
+
+        let Some(instruction) = Cpu::decode(opcode) else {
+            self.issues.push(Issue::UnknownOpcode { address, opcode });
+            return Flow::default();
+        };
+        match instruction {
+            Instruction::RET | Instruction::JMPV0 => Flow::default(),
+            Instruction::JMP => Flow {
+                next: self.target(address, opcode).into_iter().collect(),
+                call: None,
+            },
+            Instruction::CALL => Flow {
+                next: vec![next],
+                call: self.target(address, opcode),
+            },
+            Instruction::SE
+            | Instruction::SNE
+            | Instruction::SER
+            | Instruction::SNER
+            | Instruction::SKP
+            | Instruction::SKNP => Flow {
+                next: vec![next, next.wrapping_add(WORD_SIZE)],
+                call: None,
+            },
+            _ => Flow {
+                next: vec![next],
+                call: None,
+            },
+        }
+    }
+
+    ///
+    /// Finds the instructions the control flow reaches from the entry point.
+    ///
+    fn walk(&mut self) {
+        let mut pending = vec![self.start];
+        while let Some(address) = pending.pop() {
+            if !address.is_multiple_of(2) || self.code.contains_key(&address) {
+                continue;
+            }
+            let Some(opcode) = self.opcode(address) else {
+                continue;
+            };
+            self.code.insert(address, opcode);
+            let flow = self.flow(address, opcode);
+            pending.extend(flow.next.iter().chain(&flow.call));
+            self.flows.insert(address, flow);
+        }
+    }
+
+    ///
+    /// Follows `I` through the control flow, starting from 0, and reports the accesses through it
+    /// that run past the end of the memory.
+    ///
+    fn check_accesses(&mut self) {
+        let mut known: BTreeMap<u16, KnownI> = BTreeMap::new();
+        let mut pending = vec![(self.start, KnownI::Known(0))];
+        while let Some((address, i)) = pending.pop() {
+            let (Some(&opcode), Some(flow)) = (self.code.get(&address), self.flows.get(&address))
+            else {
+                continue;
+            };
+            let i = known.get(&address).map_or(i, |&old| old.join(i));
+            if known.insert(address, i) == Some(i) {
+                continue;
+            }
+
+            let after = match opcode & 0xF0FF {
+                _ if opcode & 0xF000 == 0xA000 => KnownI::Known(opcode & 0x0FFF),
+                0xF000 | 0xF01E | 0xF029 | 0xF030 | 0xF055 | 0xF065 => KnownI::Unknown,
+                _ => i,
+            };
+            pending.extend(flow.next.iter().map(|&next| {
+                // A subroutine may change I before it returns
+                let returned = flow.call.is_some();
+                (next, if returned { KnownI::Unknown } else { after })
+            }));
+            pending.extend(flow.call.map(|call| (call, after)));
+        }
+
+        for (&address, &i) in &known {
+            let KnownI::Known(i) = i else {
+                continue;
+            };
+            let opcode = self.code[&address];
+            let x = usize::from((opcode & 0x0F00) >> 8);
+            let len = match opcode & 0xF0FF {
+                _ if opcode & 0xF00F == 0xD000 => 32,
+                _ if opcode & 0xF000 == 0xD000 => usize::from(opcode & 0x000F),
+                0xF033 => 3,
+                0xF055 | 0xF065 => x + 1,
+                _ => continue,
+            };
+            let end = usize::from(i) + len;
+            if end > RAM_SIZE {
+                self.issues.push(Issue::DataOutsideMemory {
+                    address,
+                    opcode,
+                    start: usize::from(i),
+                    end,
+                });
+            }
+        }
+    }
+
+    ///
+    /// Returns how deep the calls made from the subroutine at `entry` nest, `None` if they
+    /// recurse.
+    ///
+    fn depth(
+        &self,
+        entry: u16,
+        depths: &mut BTreeMap<u16, Option<usize>>,
+        active: &mut BTreeSet<u16>,
+    ) -> Option<usize> {
+        if let Some(&depth) = depths.get(&entry) {
+            return depth;
+        }
+        if !active.insert(entry) {
+            return None;
+        }
+
+        let mut body = BTreeSet::new();
+        let mut pending = vec![entry];
+        let mut calls = BTreeSet::new();
+        while let Some(address) = pending.pop() {
+            let Some(flow) = self.flows.get(&address) else {
+                continue;
+            };
+            if body.insert(address) {
+                pending.extend(&flow.next);
+                calls.extend(flow.call);
+            }
+        }
+        let mut depth = Some(0);
+        for call in calls {
+            depth = match (depth, self.depth(call, depths, active)) {
+                (Some(depth), Some(nested)) => Some(depth.max(nested + 1)),
+                _ => None,
+            };
+        }
+
+        active.remove(&entry);
+        depths.insert(entry, depth);
+        depth
+    }
+}
+
+///
+/// Analyzes the ROM loaded at `start`, following its jumps, calls and skips from there. Only the
+/// instructions the control flow reaches are checked, so data mixed with the code raises
+/// nothing.
+///
+/// ```
+/// use chip8_emu::emu::analysis::analyze;
+///
+/// // CALL 0x206; JP 0x202; unknown opcode; RET
+/// let analysis = analyze(&[0x22, 0x06, 0x12, 0x02, 0xFF, 0xFF, 0x00, 0xEE], 0x200);
+/// assert!(analysis.issues.is_empty());
+/// assert_eq!(analysis.reachable, 3);
+/// assert_eq!(analysis.stack_depth, Some(1));
+/// ```
+///
+pub fn analyze(rom: &[u8], start: u16) -> Analysis {
+    let mut walker = Walker {
+        rom,
+        start,
+        code: BTreeMap::new(),
+        flows: BTreeMap::new(),
+        issues: Vec::new(),
+        extensions: Vec::new(),
+    };
+    walker.walk();
+    walker.check_accesses();
+    let stack_depth = walker.depth(start, &mut BTreeMap::new(), &mut BTreeSet::new());
+
+    let mut issues = Vec::new();
+    if usize::from(start) + rom.len() > RAM_SIZE {
+        issues.push(Issue::RomTooLarge {
+            size: rom.len(),
+            room: RAM_SIZE.saturating_sub(start.into()),
+        });
+    }
+    walker.issues.sort_by_key(Issue::address);
+    issues.append(&mut walker.issues);
+    if let Some(depth) = stack_depth.filter(|&depth| depth > STACK_SIZE) {
+        issues.push(Issue::StackOverflow { depth });
+    }
+    walker.extensions.sort_by_key(|used| used.address);
+
+    Analysis {
+        issues,
+        reachable: walker.code.len(),
+        stack_depth,
+        extensions: walker.extensions,
+    }
+}
+
+///
+/// The `DETECTION_EVIDENCE` constant is how many of the instructions that gave the variant away
+/// a `Detection` shows.
+///
+const DETECTION_EVIDENCE: usize = 4;
+
+///
+/// The `Detection` struct represents the variant a ROM's instructions give away, and the
+/// instructions that did.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detection {
+    /// The extension whose instructions the ROM runs, none for plain CHIP-8
+    pub extension: Option<Extension>,
+    /// The instructions of the extension, by address
+    pub evidence: Vec<ExtensionUse>,
+}
+
+impl Detection {
+    ///
+    /// Returns the variant whose quirks the ROM runs with: SUPER-CHIP for its instructions, none
+    /// for plain CHIP-8, and none for XO-CHIP, which isn't supported. SUPER-CHIP's instructions
+    /// themselves stay unknown opcodes, only its quirks are followed.
+    ///
+    pub fn variant(&self) -> Option<Variant> {
+        (self.extension == Some(Extension::SChip)).then_some(Variant::SChip)
+    }
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(extension) = self.extension else {
+            return write!(f, "CHIP-8, no SUPER-CHIP or XO-CHIP instructions");
+        };
+        write!(f, "{} from ", extension.name())?;
+        for (i, used) in self.evidence.iter().take(DETECTION_EVIDENCE).enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(
+                f,
+                "0x{:04X} {:04X} ({})",
+                used.address, used.opcode, used.name
+            )?;
+        }
+        if self.evidence.len() > DETECTION_EVIDENCE {
+            write!(f, " and {} more", self.evidence.len() - DETECTION_EVIDENCE)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// Tells which variant the ROM is from the instructions its control flow reaches, the bytes it
+/// never runs, like sprites, telling nothing.
+///
+/// XO-CHIP's instructions win over SUPER-CHIP's, which it has too. A `DXY0` only counts for
+/// SUPER-CHIP along with a `00FF` switching to its high resolution, CHIP-8 ROMs drawing nothing
+/// with it now and then.
+///
+/// ```
+/// use chip8_emu::emu::analysis::{detect_variant, Extension};
+///
+/// // hires; CLS
+/// let detection = detect_variant(&[0x00, 0xFF, 0x00, 0xE0], 0x200);
+/// assert_eq!(detection.extension, Some(Extension::SChip));
+/// assert_eq!(detection.to_string(), "SUPER-CHIP from 0x0200 00FF (hires)");
+/// ```
+///
+pub fn detect_variant(rom: &[u8], start: u16) -> Detection {
+    let extensions = analyze(rom, start).extensions;
+    let hires = extensions.iter().any(|used| used.opcode == 0x00FF);
+    let evidence = |extension| -> Vec<ExtensionUse> {
+        extensions
+            .iter()
+            .filter(|used| used.extension == extension)
+            .filter(|used| hires || used.opcode & 0xF00F != 0xD000)
+            .cloned()
+            .collect()
+    };
+
+    [Extension::XoChip, Extension::SChip]
+        .into_iter()
+        .map(|extension| (extension, evidence(extension)))
+        .find(|(_, evidence)| !evidence.is_empty())
+        .map_or_else(
+            || Detection {
+                extension: None,
+                evidence: Vec::new(),
+            },
+            |(extension, evidence)| Detection {
+                extension: Some(extension),
+                evidence,
+            },
+        )
+}
+
+#[cfg(test)]
+mod analysis_tests {
+    use super::{analyze, detect_variant, Extension, Issue};
+    use crate::emu::quirks::Variant;
+
+    #[test]
+    fn unknown_opcodes_on_reachable_paths() {
+        // SE V0, 0; unknown; JP 0x200; unknown (never reached)
+        let analysis = analyze(&[0x30, 0x00, 0xFF, 0xFF, 0x12, 0x00, 0xF0, 0xFF], 0x200);
+        let expected = Issue::UnknownOpcode {
+            address: 0x202,
+            opcode: 0xFFFF,
+        };
+        assert_eq!(analysis.issues, [expected]);
+        assert_eq!(analysis.reachable, 3);
+    }
+
+    #[test]
+    fn jumps_outside_the_rom_or_to_odd_addresses() {
+        // CALL 0x207; JP 0x100; CALL 0x400
+        let analysis = analyze(&[0x22, 0x07, 0x11, 0x00, 0x24, 0x00], 0x200);
+        let issues: Vec<String> = analysis.issues.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            issues,
+            [
+                "0x0200: 2207 targets the odd address 0x0207",
+                "0x0202: 1100 targets 0x0100 outside the ROM",
+            ]
+        );
+    }
+
+    #[test]
+    fn accesses_past_the_end_of_the_memory() {
+        // LD I, 0xFFE; DRW V0, V1, 2; DRW V0, V1, 3; LD [I], V2; ADD I, V0; LD V1, [I]; JP 0x20C
+        let rom = [
+            0xAF, 0xFE, 0xD0, 0x12, 0xD0, 0x13, 0xF2, 0x55, 0xF0, 0x1E, 0xF1, 0x65, 0x12, 0x0C,
+        ];
+        let issues: Vec<String> = analyze(&rom, 0x200)
+            .issues
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                "0x0204: D013 reads 0x0FFE-0x1000 past the end of the memory",
+                "0x0206: F255 writes 0x0FFE-0x1000 past the end of the memory",
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_depth() {
+        // CALL 0x204; JP 0x202; CALL 0x208; RET; RET
+        let rom = [0x22, 0x04, 0x12, 0x02, 0x22, 0x08, 0x00, 0xEE, 0x00, 0xEE];
+        assert_eq!(analyze(&rom, 0x200).stack_depth, Some(2));
+
+        // CALL 0x204; JP 0x202; CALL 0x204
+        let recursive = analyze(&[0x22, 0x04, 0x12, 0x02, 0x22, 0x04], 0x200);
+        assert_eq!(recursive.stack_depth, None);
+
+        // 17 subroutines, each calling the next one
+        let mut rom = Vec::new();
+        for n in 0..17u16 {
+            rom.extend((0x2204 + 4 * n).to_be_bytes());
+            rom.extend([0x00, 0xEE]);
+        }
+        rom.extend([0x00, 0xEE]);
+        let analysis = analyze(&rom, 0x200);
+        assert_eq!(analysis.stack_depth, Some(17));
+        assert_eq!(analysis.issues, [Issue::StackOverflow { depth: 17 }]);
+    }
+
+    #[test]
+    fn variant_specific_opcodes() {
+        // hires; DRW V0, V1, 0; audio; exit
+        let analysis = analyze(&[0x00, 0xFF, 0xD0, 0x10, 0xF0, 0x02, 0x00, 0xFD], 0x200);
+        assert!(analysis.issues.is_empty());
+        let used: Vec<(u16, Extension)> = analysis
+            .extensions
+            .iter()
+            .map(|used| (used.address, used.extension))
+            .collect();
+        assert_eq!(
+            used,
+            [
+                (0x200, Extension::SChip),
+                (0x202, Extension::SChip),
+                (0x204, Extension::XoChip),
+                (0x206, Extension::SChip),
+            ]
+        );
+        assert_eq!(analysis.suggested_variant(), Some(Variant::SChip));
+
+        let mut text = String::new();
+        analysis.write_text(&mut text);
+        assert_eq!(
+            text,
+            "Reachable instructions: 4\n\
+             Stack depth: at most 0 call(s)\n\
+             SUPER-CHIP instructions: 0x0200 00FF (hires), 0x0202 D010 (16x16 sprite), \
+             0x0206 00FD (exit); SUPER-CHIP's instructions aren't supported, --variant schip only \
+             follows its quirks\n\
+             XO-CHIP instructions: 0x0204 F002 (audio); XO-CHIP isn't supported\n"
+        );
+    }
+
+    #[test]
+    fn detects_the_variant_from_the_code() {
+        // CLS; LD V0, 1; DRW V0, V0, 5; JP 0x206
+        let chip8 = detect_variant(&[0x00, 0xE0, 0x60, 0x01, 0xD0, 0x05, 0x12, 0x06], 0x200);
+        assert_eq!(chip8.extension, None);
+        assert_eq!(chip8.variant(), None);
+        assert_eq!(
+            chip8.to_string(),
+            "CHIP-8, no SUPER-CHIP or XO-CHIP instructions"
+        );
+
+        // lores; scroll down 4; hires; DRW V0, V1, 0; JP 0x208
+        let schip = detect_variant(
+            &[0x00, 0xFE, 0x00, 0xC4, 0x00, 0xFF, 0xD0, 0x10, 0x12, 0x08],
+            0x200,
+        );
+        assert_eq!(schip.extension, Some(Extension::SChip));
+        assert_eq!(schip.variant(), Some(Variant::SChip));
+        assert_eq!(
+            schip.to_string(),
+            "SUPER-CHIP from 0x0200 00FE (lores), 0x0202 00C4 (scroll down), 0x0204 00FF (hires), \
+             0x0206 D010 (16x16 sprite)"
+        );
+
+        // hires; plane 3; save range V0-V1; long i := 0x0000
+        let xochip = detect_variant(
+            &[0x00, 0xFF, 0xF3, 0x01, 0x50, 0x12, 0xF0, 0x00, 0x00, 0x00],
+            0x200,
+        );
+        assert_eq!(xochip.extension, Some(Extension::XoChip));
+        assert_eq!(xochip.variant(), None);
+        assert_eq!(
+            xochip.to_string(),
+            "XO-CHIP from 0x0202 F301 (plane), 0x0204 5012 (save range), 0x0206 F000 (long i :=)"
+        );
+    }
+
+    #[test]
+    fn data_and_lone_sprites_tell_nothing() {
+        // JP 0x20C; DATA that reads as hires, long i := and plane; DRW V0, V1, 0; JP 0x20E
+        let rom = [
+            0x12, 0x0C, 0x00, 0xFF, 0xF0, 0x00, 0xF1, 0x01, 0x00, 0xFE, 0x00, 0xC1, 0xD0, 0x10,
+            0x12, 0x0E,
+        ];
+        assert_eq!(detect_variant(&rom, 0x200).extension, None);
+        // The analyzer still reports the sprite
+        assert_eq!(analyze(&rom, 0x200).extensions.len(), 1);
+
+        // More evidence than is shown
+        let rom: Vec<u8> = std::iter::repeat_n([0x00, 0xFB], 6).flatten().collect();
+        assert!(detect_variant(&rom, 0x200)
+            .to_string()
+            .ends_with("0x0206 00FB (scroll right) and 2 more"));
+    }
+
+    #[test]
+    fn json_report() {
+        // JP 0x201
+        let mut json = String::new();
+        analyze(&[0x12, 0x01], 0x200).write_json(&mut json);
+        assert_eq!(
+            json,
+            "{\"issues\":[{\"kind\":\"odd-target\",\"address\":512,\
+             \"message\":\"0x0200: 1201 targets the odd address 0x0201\"}],\
+             \"reachable\":1,\"stack_depth\":0,\"extensions\":[],\"suggested_variant\":null}"
+        );
+    }
+}
@@ -0,0 +1,816 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use super::memory::RAM_SIZE;
+
+///
+/// The `CLASSIC_MNEMONICS` constant lists the instructions of the classic syntax, the one
+/// `disassemble` writes. Every other statement is read as Octo.
+///
+const CLASSIC_MNEMONICS: [&str; 19] = [
+    "CLS", "RET", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN",
+    "SHL", "RND", "DRW", "SKP", "SKNP",
+];
+
+const NUMBER_HINT: &str = "numbers are written like 42, 0x2A or 0b101010";
+
+///
+/// The `AsmError` struct represents a mistake in an assembly source: what's wrong, where, and a
+/// hint at how to fix it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// The line and the column of the offending token, both counted from 1
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub message: String,
+    pub hint: String,
+    /// The source line the token is on, to point at it
+    source_line: String,
+}
+
+impl Error for AsmError {}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let indent = " ".repeat(self.column - 1);
+        let marker = "^".repeat(self.token.chars().count().max(1));
+        writeln!(
+            f,
+            "Line {}, column {}: {}!",
+            self.line, self.column, self.message
+        )?;
+        writeln!(f, "{number} | {}", self.source_line)?;
+        write!(f, "{gutter} | {indent}{marker} {}", self.hint)
+    }
+}
+
+///
+/// The `Token` struct represents a word of a source line, operands being separated by spaces or
+/// commas.
+///
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    column: usize,
+}
+
+///
+/// Splits a line into its tokens, up to its comment: `;` anywhere, or `#` followed by a space as
+/// in Octo, since `#0x2A` is an immediate.
+///
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut chars = line.char_indices().enumerate().peekable();
+    while let Some((column, (offset, c))) = chars.next() {
+        let comment = c == ';'
+            || (c == '#'
+                && chars
+                    .peek()
+                    .is_none_or(|&(_, (_, next))| next.is_whitespace()));
+        if comment || c.is_whitespace() || c == ',' {
+            if let Some((column, begin)) = start.take() {
+                tokens.push(Token {
+                    text: &line[begin..offset],
+                    column,
+                });
+            }
+            if comment {
+                return tokens;
+            }
+        } else if start.is_none() {
+            start = Some((column + 1, offset));
+        }
+    }
+    if let Some((column, begin)) = start {
+        tokens.push(Token {
+            text: &line[begin..],
+            column,
+        });
+    }
+    tokens
+}
+
+fn is_register(text: &str) -> bool {
+    matches!(text.as_bytes(), [b'v' | b'V', digit] if digit.is_ascii_hexdigit())
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+///
+/// Parses a number in decimal, in hexadecimal after `0x` or in binary after `0b`.
+///
+fn parse_number(text: &str) -> Option<u32> {
+    let lower = text.to_ascii_lowercase();
+    let (digits, radix) = match lower.get(..2) {
+        Some("0x") => (&lower[2..], 16),
+        Some("0b") => (&lower[2..], 2),
+        _ => (lower.as_str(), 10),
+    };
+    u32::from_str_radix(digits, radix).ok()
+}
+
+///
+/// Returns whether the statement is a line of bytes, the way Octo writes data.
+///
+fn is_data(first: &Token<'_>) -> bool {
+    let text = first.text.strip_prefix('#').unwrap_or(first.text);
+    text.starts_with(|c: char| c.is_ascii_digit())
+}
+
+///
+/// Returns the instruction forms of a classic mnemonic, for the hints.
+///
+fn forms(mnemonic: &str) -> &'static str {
+    match mnemonic {
+        "CLS" | "RET" => "it takes no operands",
+        "JP" => "expected `JP addr` or `JP V0, addr`",
+        "CALL" => "expected `CALL addr`",
+        "SE" | "SNE" => "expected `Vx, byte` or `Vx, Vy`",
+        "LD" => {
+            "expected `LD Vx, byte`, `LD Vx, Vy`, `LD I, addr`, `LD Vx, DT`, `LD Vx, K`, \
+             `LD DT, Vx`, `LD ST, Vx`, `LD F, Vx`, `LD B, Vx`, `LD [I], Vx` or `LD Vx, [I]`"
+        }
+        "ADD" => "expected `ADD Vx, byte`, `ADD Vx, Vy` or `ADD I, Vx`",
+        "RND" => "expected `RND Vx, byte`",
+        "DRW" => "expected `DRW Vx, Vy, nibble`",
+        "SKP" | "SKNP" => "expected a register, `Vx`",
+        "SHR" | "SHL" => "expected `Vx, Vy` or `Vx`",
+        _ => "expected `Vx, Vy`",
+    }
+}
+
+///
+/// The `Operand` enum represents an operand of a classic instruction, by what it names.
+///
+#[derive(Debug, Clone, Copy)]
+enum Operand<'a> {
+    V(u16),
+    I,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    /// `[I]`, the memory `I` points at
+    AtI,
+    /// A number or a label
+    Value(Token<'a>),
+}
+
+impl<'a> Operand<'a> {
+    fn of(token: Token<'a>) -> Self {
+        match token.text.to_ascii_uppercase().as_str() {
+            "I" => Self::I,
+            "DT" => Self::Dt,
+            "ST" => Self::St,
+            "K" => Self::K,
+            "F" => Self::F,
+            "B" => Self::B,
+            "[I]" => Self::AtI,
+            text if is_register(text) => Self::V(u16::from_str_radix(&text[1..], 16).unwrap_or(0)),
+            _ => Self::Value(token),
+        }
+    }
+}
+
+///
+/// The `Statement` struct represents a source line that produces bytes, placed at `address`.
+///
+struct Statement<'a> {
+    line: usize,
+    source: &'a str,
+    address: u16,
+    tokens: Vec<Token<'a>>,
+}
+
+///
+/// The `Labels` type maps every label to its address and the line defining it.
+///
+type Labels<'a> = BTreeMap<&'a str, (u16, usize)>;
+
+impl<'a> Statement<'a> {
+    ///
+    /// Returns how many bytes the statement produces, which the first pass needs before any
+    /// label is known.
+    ///
+    fn size(&self) -> usize {
+        let first = &self.tokens[0];
+        match first.text.to_ascii_lowercase().as_str() {
+            "db" => self.tokens.len() - 1,
+            "dw" => 2 * (self.tokens.len() - 1),
+            _ if is_data(first) => self.tokens.len(),
+            _ => 2,
+        }
+    }
+
+    fn error(&self, token: Token<'_>, message: String, hint: impl Into<String>) -> AsmError {
+        error(self.line, self.source, token, message, hint)
+    }
+
+    ///
+    /// Returns the operand token at `index`, or an empty one at the end of the line when the
+    /// statement is short of it.
+    ///
+    fn token(&self, index: usize) -> Token<'a> {
+        self.tokens.get(index).copied().unwrap_or_else(|| Token {
+            text: "",
+            column: self.source.chars().count() + 1,
+        })
+    }
+
+    ///
+    /// Fails on whatever follows the first `len` tokens.
+    ///
+    fn end(&self, len: usize) -> Result<(), AsmError> {
+        match self.tokens.get(len) {
+            Some(&token) => Err(self.error(
+                token,
+                format!("Unexpected `{}`", token.text),
+                "the statement ends before it",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn register(&self, token: Token<'_>) -> Result<u16, AsmError> {
+        if is_register(token.text) {
+            Ok(u16::from_str_radix(&token.text[1..], 16).unwrap_or(0))
+        } else {
+            Err(self.error(
+                token,
+                format!("Expected a register, found `{}`", token.text),
+                "registers are V0 to VF",
+            ))
+        }
+    }
+
+    ///
+    /// Returns the value of a number or a label, at most `max`.
+    ///
+    fn value(&self, token: Token<'_>, labels: &Labels<'_>, max: u16) -> Result<u16, AsmError> {
+        let text = token.text.strip_prefix('#').unwrap_or(token.text);
+        let value = if text.starts_with(|c: char| c.is_ascii_digit()) {
+            parse_number(text).ok_or_else(|| {
+                self.error(
+                    token,
+                    format!("`{}` isn't a number", token.text),
+                    NUMBER_HINT,
+                )
+            })?
+        } else if is_label(text) {
+            let (address, _) = labels.get(text).ok_or_else(|| {
+                self.error(
+                    token,
+                    format!("Undefined label `{text}`"),
+                    format!("define it with `{text}:` at the start of a line"),
+                )
+            })?;
+            u32::from(*address)
+        } else if text.is_empty() {
+            return Err(self.error(
+                token,
+                "Missing operand".to_string(),
+                "expected a number or a label",
+            ));
+        } else {
+            return Err(self.error(
+                token,
+                format!("Invalid operand `{}`", token.text),
+                "expected a number or a label",
+            ));
+        };
+        u16::try_from(value)
+            .ok()
+            .filter(|&value| value <= max)
+            .ok_or_else(|| {
+                self.error(
+                    token,
+                    format!("`{}` is out of range", token.text),
+                    format!("expected at most 0x{max:X}"),
+                )
+            })
+    }
+
+    ///
+    /// Appends the bytes of the statement to `rom`.
+    ///
+    fn encode(&self, labels: &Labels<'_>, rom: &mut Vec<u8>) -> Result<(), AsmError> {
+        let first = self.tokens[0];
+        let mnemonic = first.text.to_ascii_uppercase();
+        match mnemonic.as_str() {
+            "DB" | "DW" if self.tokens.len() == 1 => {
+                return Err(self.error(
+                    self.token(1),
+                    "Missing operand".to_string(),
+                    format!(
+                        "expected the values to store, e.g. `{} 0x2A, 42`",
+                        first.text
+                    ),
+                ));
+            }
+            "DB" => {
+                for &token in &self.tokens[1..] {
+                    rom.push(self.value(token, labels, 0xFF)? as u8);
+                }
+            }
+            "DW" => {
+                for &token in &self.tokens[1..] {
+                    rom.extend(self.value(token, labels, 0xFFFF)?.to_be_bytes());
+                }
+            }
+            _ if is_data(&first) => {
+                for &token in &self.tokens {
+                    rom.push(self.value(token, labels, 0xFF)? as u8);
+                }
+            }
+            _ if CLASSIC_MNEMONICS.contains(&mnemonic.as_str()) => {
+                rom.extend(self.classic(&mnemonic, labels)?.to_be_bytes());
+            }
+            _ => rom.extend(self.octo(labels)?.to_be_bytes()),
+        }
+        Ok(())
+    }
+
+    ///
+    /// Encodes an instruction of the classic syntax, e.g. `LD V0, #0x2A`.
+    ///
+    fn classic(&self, mnemonic: &str, labels: &Labels<'_>) -> Result<u16, AsmError> {
+        use Operand::{AtI, Dt, St, Value, B, F, I, K, V};
+
+        let operands: Vec<Operand<'_>> =
+            self.tokens[1..].iter().copied().map(Operand::of).collect();
+        let address = |token| self.value(token, labels, 0xFFF);
+        let byte = |token| self.value(token, labels, 0xFF);
+        let xy = |x: u16, y: u16| (x << 8) | (y << 4);
+        let opcode = match (mnemonic, operands.as_slice()) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("JP", [Value(nnn)]) => 0x1000 | address(*nnn)?,
+            ("JP", [V(0), Value(nnn)]) => 0xB000 | address(*nnn)?,
+            ("CALL", [Value(nnn)]) => 0x2000 | address(*nnn)?,
+            ("SE", [V(x), Value(kk)]) => 0x3000 | xy(*x, 0) | byte(*kk)?,
+            ("SNE", [V(x), Value(kk)]) => 0x4000 | xy(*x, 0) | byte(*kk)?,
+            ("SE", [V(x), V(y)]) => 0x5000 | xy(*x, *y),
+            ("LD", [V(x), Value(kk)]) => 0x6000 | xy(*x, 0) | byte(*kk)?,
+            ("ADD", [V(x), Value(kk)]) => 0x7000 | xy(*x, 0) | byte(*kk)?,
+            ("LD", [V(x), V(y)]) => 0x8000 | xy(*x, *y),
+            ("OR", [V(x), V(y)]) => 0x8001 | xy(*x, *y),
+            ("AND", [V(x), V(y)]) => 0x8002 | xy(*x, *y),
+            ("XOR", [V(x), V(y)]) => 0x8003 | xy(*x, *y),
+            ("ADD", [V(x), V(y)]) => 0x8004 | xy(*x, *y),
+            ("SUB", [V(x), V(y)]) => 0x8005 | xy(*x, *y),
+            ("SHR", [V(x), V(y)]) => 0x8006 | xy(*x, *y),
+            ("SHR", [V(x)]) => 0x8006 | xy(*x, 0),
+            ("SUBN", [V(x), V(y)]) => 0x8007 | xy(*x, *y),
+            ("SHL", [V(x), V(y)]) => 0x800E | xy(*x, *y),
+            ("SHL", [V(x)]) => 0x800E | xy(*x, 0),
+            ("SNE", [V(x), V(y)]) => 0x9000 | xy(*x, *y),
+            ("LD", [I, Value(nnn)]) => 0xA000 | address(*nnn)?,
+            ("RND", [V(x), Value(kk)]) => 0xC000 | xy(*x, 0) | byte(*kk)?,
+            ("DRW", [V(x), V(y), Value(n)]) => 0xD000 | xy(*x, *y) | self.value(*n, labels, 0xF)?,
+            ("SKP", [V(x)]) => 0xE09E | xy(*x, 0),
+            ("SKNP", [V(x)]) => 0xE0A1 | xy(*x, 0),
+            ("LD", [V(x), Dt]) => 0xF007 | xy(*x, 0),
+            ("LD", [V(x), K]) => 0xF00A | xy(*x, 0),
+            ("LD", [Dt, V(x)]) => 0xF015 | xy(*x, 0),
+            ("LD", [St, V(x)]) => 0xF018 | xy(*x, 0),
+            ("ADD", [I, V(x)]) => 0xF01E | xy(*x, 0),
+            ("LD", [F, V(x)]) => 0xF029 | xy(*x, 0),
+            ("LD", [B, V(x)]) => 0xF033 | xy(*x, 0),
+            ("LD", [AtI, V(x)]) => 0xF055 | xy(*x, 0),
+            ("LD", [V(x), AtI]) => 0xF065 | xy(*x, 0),
+            _ => {
+                return Err(self.error(
+                    self.tokens[0],
+                    format!("`{}` doesn't take these operands", self.tokens[0].text),
+                    forms(mnemonic),
+                ))
+            }
+        };
+        Ok(opcode)
+    }
+
+    ///
+    /// Encodes a statement of the Octo subset `disassemble_octo` writes, e.g. `v0 := 0x2A`.
+    ///
+    fn octo(&self, labels: &Labels<'_>) -> Result<u16, AsmError> {
+        let token = |index| self.token(index);
+        let address = |index| self.value(token(index), labels, 0xFFF);
+        let byte = |index| self.value(token(index), labels, 0xFF);
+        let x = |index| Ok::<_, AsmError>(self.register(token(index))? << 8);
+        let expect = |index, word: &str| {
+            if token(index).text == word {
+                Ok(())
+            } else {
+                Err(self.error(
+                    token(index),
+                    format!("Expected `{word}`, found `{}`", token(index).text),
+                    format!("e.g. `{}`", octo_example(self.tokens[0].text)),
+                ))
+            }
+        };
+
+        let (opcode, len) = match self.tokens[0].text {
+            "clear" => (0x00E0, 1),
+            "return" => (0x00EE, 1),
+            "jump" => (0x1000 | address(1)?, 2),
+            "jump0" => (0xB000 | address(1)?, 2),
+            ":call" => (0x2000 | address(1)?, 2),
+            "sprite" => {
+                let n = self.value(token(3), labels, 0xF)?;
+                (0xD000 | x(1)? | (self.register(token(2))? << 4) | n, 4)
+            }
+            "bcd" => (0xF033 | x(1)?, 2),
+            "save" => (0xF055 | x(1)?, 2),
+            "load" => (0xF065 | x(1)?, 2),
+            "delay" => {
+                expect(1, ":=")?;
+                (0xF015 | x(2)?, 3)
+            }
+            "buzzer" => {
+                expect(1, ":=")?;
+                (0xF018 | x(2)?, 3)
+            }
+            "i" if token(1).text == "+=" => (0xF01E | x(2)?, 3),
+            "i" if token(2).text == "hex" => {
+                expect(1, ":=")?;
+                (0xF029 | x(3)?, 4)
+            }
+            "i" => {
+                expect(1, ":=")?;
+                (0xA000 | address(2)?, 3)
+            }
+            "if" => {
+                let vx = x(1)?;
+                match token(2).text {
+                    "-key" => {
+                        expect(3, "then")?;
+                        (0xE09E | vx, 4)
+                    }
+                    "key" => {
+                        expect(3, "then")?;
+                        (0xE0A1 | vx, 4)
+                    }
+                    comparison @ ("!=" | "==") => {
+                        expect(4, "then")?;
+                        let register = is_register(token(3).text);
+                        let opcode = match (comparison, register) {
+                            ("!=", false) => 0x3000 | vx | byte(3)?,
+                            ("==", false) => 0x4000 | vx | byte(3)?,
+                            ("!=", true) => 0x5000 | vx | (x(3)? >> 4),
+                            _ => 0x9000 | vx | (x(3)? >> 4),
+                        };
+                        (opcode, 5)
+                    }
+                    _ => {
+                        return Err(self.error(
+                            token(2),
+                            format!("Unknown condition `{}`", token(2).text),
+                            "expected `==`, `!=`, `key` or `-key`",
+                        ))
+                    }
+                }
+            }
+            text if is_register(text) => self.octo_assignment(labels)?,
+            text => {
+                return Err(self.error(
+                    self.tokens[0],
+                    format!("Unknown instruction `{text}`"),
+                    "expected a mnemonic like `LD`, a label like `loop:`, `db` or `dw`",
+                ))
+            }
+        };
+        self.end(len)?;
+        Ok(opcode)
+    }
+
+    ///
+    /// Encodes an Octo assignment to a register, e.g. `v0 += v1`, returning it with its length in
+    /// tokens.
+    ///
+    fn octo_assignment(&self, labels: &Labels<'_>) -> Result<(u16, usize), AsmError> {
+        let x = self.register(self.tokens[0])? << 8;
+        let operator = self.token(1);
+        let source = self.token(2);
+        let y = || Ok::<_, AsmError>(self.register(source)? << 4);
+        let byte = || self.value(source, labels, 0xFF);
+        let register = is_register(source.text);
+        let assignment = match (operator.text, source.text) {
+            (":=", "random") => (0xC000 | x | self.value(self.token(3), labels, 0xFF)?, 4),
+            (":=", "delay") => (0xF007 | x, 3),
+            (":=", "key") => (0xF00A | x, 3),
+            (":=", _) if register => (0x8000 | x | y()?, 3),
+            (":=", _) => (0x6000 | x | byte()?, 3),
+            ("+=", _) if register => (0x8004 | x | y()?, 3),
+            ("+=", _) => (0x7000 | x | byte()?, 3),
+            ("|=", _) => (0x8001 | x | y()?, 3),
+            ("&=", _) => (0x8002 | x | y()?, 3),
+            ("^=", _) => (0x8003 | x | y()?, 3),
+            ("-=", _) => (0x8005 | x | y()?, 3),
+            (">>=", _) => (0x8006 | x | y()?, 3),
+            ("=-", _) => (0x8007 | x | y()?, 3),
+            ("<<=", _) => (0x800E | x | y()?, 3),
+            _ => {
+                return Err(self.error(
+                    operator,
+                    format!("Unknown operator `{}`", operator.text),
+                    "expected `:=`, `+=`, `-=`, `=-`, `|=`, `&=`, `^=`, `>>=` or `<<=`",
+                ))
+            }
+        };
+        Ok(assignment)
+    }
+}
+
+fn octo_example(statement: &str) -> &'static str {
+    match statement {
+        "delay" => "delay := v0",
+        "buzzer" => "buzzer := v0",
+        "i" => "i := 0x2EA",
+        _ => "if v0 == 0x2A then",
+    }
+}
+
+fn error(
+    line: usize,
+    source: &str,
+    token: Token<'_>,
+    message: String,
+    hint: impl Into<String>,
+) -> AsmError {
+    AsmError {
+        line,
+        column: token.column,
+        token: token.text.to_string(),
+        message,
+        hint: hint.into(),
+        source_line: source.replace('\t', " "),
+    }
+}
+
+///
+/// Takes the label the line starts with, either `name:` or Octo's `: name`, if it has one.
+///
+fn take_label<'a>(
+    line: usize,
+    source: &str,
+    tokens: &mut Vec<Token<'a>>,
+) -> Result<Option<Token<'a>>, AsmError> {
+    let label = match tokens.as_slice() {
+        [colon, name, ..] if colon.text == ":" => {
+            let name = *name;
+            tokens.drain(..2);
+            name
+        }
+        [colon] if colon.text == ":" => {
+            return Err(error(
+                line,
+                source,
+                Token {
+                    text: "",
+                    column: source.chars().count() + 1,
+                },
+                "Missing label name".to_string(),
+                "expected a name after `:`",
+            ))
+        }
+        [first, ..] if first.text.len() > 1 && first.text.ends_with(':') => {
+            let first = *first;
+            tokens.remove(0);
+            Token {
+                text: &first.text[..first.text.len() - 1],
+                column: first.column,
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    if !is_label(label.text) || is_register(label.text) {
+        return Err(error(
+            line,
+            source,
+            label,
+            format!("Invalid label name `{}`", label.text),
+            "labels start with a letter or `_`, followed by letters, digits, `_` or `-`, and \
+             can't be register names",
+        ));
+    }
+    Ok(Some(label))
+}
+
+///
+/// Assembles a source in the classic syntax `disassemble` writes, in the Octo subset
+/// `disassemble_octo` writes, or in a mix of both, into the bytes of a ROM loaded at `origin`.
+///
+/// Labels are defined as `name:` or `: name` and can be used wherever an address or a value is
+/// expected, before or after their definition. `db` and `dw` store bytes and big-endian words and
+/// comments start with `;`, or with `#` followed by a space.
+///
+/// ```
+/// use chip8_emu::emu::asm::assemble;
+///
+/// let rom = assemble("loop: ADD V0, #1 ; count\n  jump loop\ndb 0b1010, 42", 0x200).unwrap();
+/// assert_eq!(rom, [0x70, 0x01, 0x12, 0x00, 0x0A, 0x2A]);
+/// ```
+///
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+    // The first pass places the statements, so that the second one knows every label
+    let mut labels = Labels::new();
+    let mut statements = Vec::new();
+    let mut address = usize::from(origin);
+    for (n, text) in source.lines().enumerate() {
+        let line = n + 1;
+        let mut tokens = tokenize(text);
+        while let Some(label) = take_label(line, text, &mut tokens)? {
+            if let Some(&(_, first)) = labels.get(label.text) {
+                return Err(error(
+                    line,
+                    text,
+                    label,
+                    format!("Label `{}` is defined twice", label.text),
+                    format!("it's first defined on line {first}"),
+                ));
+            }
+            labels.insert(label.text, (address as u16, line));
+        }
+        if tokens.is_empty() {
+            continue;
+        }
+
+        let statement = Statement {
+            line,
+            source: text,
+            address: address as u16,
+            tokens,
+        };
+        let end = address + statement.size();
+        if end > RAM_SIZE {
+            return Err(statement.error(
+                statement.tokens[0],
+                "The program doesn't fit in the memory".to_string(),
+                format!(
+                    "it ends at 0x{end:X}, past the last address 0x{:X}",
+                    RAM_SIZE - 1
+                ),
+            ));
+        }
+        address = end;
+        statements.push(statement);
+    }
+
+    let mut rom = Vec::with_capacity(address - usize::from(origin));
+    for statement in &statements {
+        debug_assert_eq!(
+            usize::from(statement.address),
+            usize::from(origin) + rom.len()
+        );
+        statement.encode(&labels, &mut rom)?;
+    }
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod asm_tests {
+    use super::assemble;
+    use crate::emu::disasm::{disassemble, disassemble_octo};
+    use crate::emu::rom::DEMO_ROM;
+
+    const SOURCE: &str = include_str!("../../tests/fixtures/bounce.s");
+
+    ///
+    /// Writes the ROM back as a source, one disassembled instruction per line, the words that
+    /// don't decode as data.
+    ///
+    fn disassemble_rom(rom: &[u8], octo: bool) -> String {
+        let mut source = String::new();
+        for (n, word) in rom.chunks(2).enumerate() {
+            let address = 0x200 + 2 * n as u16;
+            let line = match *word {
+                [hi, lo] => {
+                    let opcode = u16::from_be_bytes([hi, lo]);
+                    match (octo, disassemble(address, opcode)) {
+                        (true, _) => disassemble_octo(address, opcode),
+                        (false, data) if data == "DATA" => format!("dw 0x{opcode:04X}"),
+                        (false, mnemonic) => mnemonic,
+                    }
+                }
+                [byte] => format!("db {byte}"),
+                _ => unreachable!(),
+            };
+            source.push_str(&line);
+            source.push('\n');
+        }
+        source
+    }
+
+    #[test]
+    fn round_trips_through_the_disassembler() {
+        let rom = assemble(SOURCE, 0x200).unwrap();
+        assert_eq!(&rom[..6], [0x00, 0xE0, 0x6A, 0x00, 0x6B, 0x10]);
+        for octo in [false, true] {
+            let source = disassemble_rom(&rom, octo);
+            assert_eq!(assemble(&source, 0x200).unwrap(), rom, "{source}");
+        }
+
+        let source = disassemble_rom(DEMO_ROM, false);
+        assert_eq!(assemble(&source, 0x200).unwrap(), DEMO_ROM);
+    }
+
+    #[test]
+    fn every_instruction_form() {
+        let source = "\
+            CLS\nRET\nJP #0x2EA\nJP V0, 0x2EA\nCALL 0x2EA\nSE VA, 42\nSNE VA, 0b101010\n\
+            SE VA, VB\nLD VA, 0x2A\nADD VA, 0x2A\nLD VA, VB\nOR VA, VB\nAND VA, VB\nXOR VA, VB\n\
+            ADD VA, VB\nSUB VA, VB\nSHR VA, VB\nSUBN VA, VB\nshl va, vb\nSNE VA, VB\n\
+            LD I, 0x2EA\nRND VA, 0x2A\nDRW V3, V4, 5\nSKP VA\nSKNP VA\nLD VA, DT\nLD VA, K\n\
+            LD DT, VA\nLD ST, VA\nADD I, VA\nLD F, VA\nLD B, VA\nLD [I], VA\nLD VA, [I]\n";
+        let rom = assemble(source, 0x200).unwrap();
+        let opcodes: Vec<u16> = rom
+            .chunks(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect();
+        assert_eq!(
+            opcodes,
+            [
+                0x00E0, 0x00EE, 0x12EA, 0xB2EA, 0x22EA, 0x3A2A, 0x4A2A, 0x5AB0, 0x6A2A, 0x7A2A,
+                0x8AB0, 0x8AB1, 0x8AB2, 0x8AB3, 0x8AB4, 0x8AB5, 0x8AB6, 0x8AB7, 0x8ABE, 0x9AB0,
+                0xA2EA, 0xCA2A, 0xD345, 0xEA9E, 0xEAA1, 0xFA07, 0xFA0A, 0xFA15, 0xFA18, 0xFA1E,
+                0xFA29, 0xFA33, 0xFA55, 0xFA65,
+            ]
+        );
+    }
+
+    #[test]
+    fn undefined_labels() {
+        let err = assemble("start:\n    JP lop ; retry\n", 0x200).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 8));
+        assert_eq!(err.token, "lop");
+        assert_eq!(
+            err.to_string(),
+            "\
+Line 2, column 8: Undefined label `lop`!
+2 |     JP lop ; retry
+  |        ^^^ define it with `lop:` at the start of a line"
+        );
+
+        let err = assemble("a: CLS\na: RET", 0x200).unwrap_err();
+        assert_eq!(err.message, "Label `a` is defined twice");
+        assert_eq!(err.hint, "it's first defined on line 1");
+    }
+
+    #[test]
+    fn out_of_range_operands() {
+        let err = assemble("LD V0, 0x100", 0x200).unwrap_err();
+        assert_eq!((err.line, err.column), (1, 8));
+        assert_eq!(err.message, "`0x100` is out of range");
+        assert_eq!(err.hint, "expected at most 0xFF");
+
+        let err = assemble("DRW V0, V1, 16", 0x200).unwrap_err();
+        assert_eq!(err.hint, "expected at most 0xF");
+
+        let err = assemble("far: db 0\nLD V0, far", 0x100).unwrap_err();
+        assert_eq!(err.message, "`far` is out of range");
+
+        let err = assemble("db 0x2A\ndw 0x10000", 0x200).unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.hint.as_str()),
+            (2, 4, "expected at most 0xFFFF")
+        );
+    }
+
+    #[test]
+    fn malformed_statements() {
+        let err = assemble("LD V0", 0x200).unwrap_err();
+        assert_eq!(err.message, "`LD` doesn't take these operands");
+
+        let err = assemble("MOV V0, V1", 0x200).unwrap_err();
+        assert_eq!(err.message, "Unknown instruction `MOV`");
+
+        let err = assemble("v0 := 0x2A v1", 0x200).unwrap_err();
+        assert_eq!((err.column, err.message.as_str()), (12, "Unexpected `v1`"));
+
+        let err = assemble("if v0 == 1", 0x200).unwrap_err();
+        assert_eq!(
+            (err.column, err.message.as_str()),
+            (11, "Expected `then`, found ``")
+        );
+
+        let err = assemble("sprite v0 vx 1", 0x200).unwrap_err();
+        assert_eq!(err.message, "Expected a register, found `vx`");
+
+        let err = assemble("JP 0xZZ", 0x200).unwrap_err();
+        assert_eq!(err.message, "`0xZZ` isn't a number");
+    }
+}
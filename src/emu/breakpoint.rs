@@ -0,0 +1,548 @@
+use std::error::Error;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use super::cpu::Instruction;
+use super::memory::{Ram, Registers, RAM_SIZE};
+
+///
+/// The `Operand` enum represents a side of a comparison: a register or a number.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    V(u8),
+    I,
+    Dt,
+    St,
+    Value(u16),
+}
+
+impl Operand {
+    fn value(self, registers: &Registers) -> u16 {
+        match self {
+            Self::V(x) => registers.v[usize::from(x & 0xF)].into(),
+            Self::I => registers.i,
+            Self::Dt => registers.dt.into(),
+            Self::St => registers.st.into(),
+            Self::Value(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V(x) => write!(f, "v{x:x}"),
+            Self::I => write!(f, "i"),
+            Self::Dt => write!(f, "dt"),
+            Self::St => write!(f, "st"),
+            Self::Value(value) => write!(f, "0x{value:X}"),
+        }
+    }
+}
+
+impl FromStr for Operand {
+    type Err = BreakpointError;
+
+    fn from_str(word: &str) -> Result<Self, Self::Err> {
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            return number(word).map(Self::Value);
+        }
+
+        let name = word.to_ascii_lowercase();
+        match name.as_str() {
+            "i" => Ok(Self::I),
+            "dt" => Ok(Self::Dt),
+            "st" => Ok(Self::St),
+            _ => name
+                .strip_prefix('v')
+                .filter(|x| x.len() == 1)
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .map(Self::V)
+                .ok_or_else(|| BreakpointError::UnknownRegister(word.to_string())),
+        }
+    }
+}
+
+///
+/// The `Comparison` enum represents how the two sides of a clause compare.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+}
+
+impl Comparison {
+    fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "==" => Some(Self::Eq),
+            "!=" => Some(Self::Ne),
+            "<" => Some(Self::Lt),
+            ">" => Some(Self::Gt),
+            _ => None,
+        }
+    }
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Gt => ">",
+        }
+    }
+}
+
+///
+/// The `Clause` struct represents a single comparison, e.g. `v5 == 3`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clause {
+    pub left: Operand,
+    pub comparison: Comparison,
+    pub right: Operand,
+}
+
+impl Clause {
+    fn holds(&self, registers: &Registers) -> bool {
+        let (left, right) = (self.left.value(registers), self.right.value(registers));
+        match self.comparison {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Gt => left > right,
+        }
+    }
+}
+
+///
+/// The `Condition` struct represents the clauses a breakpoint needs to hold, all of them, joined
+/// with `&&`.
+///
+/// ```
+/// use chip8_emu::emu::breakpoint::Condition;
+/// use chip8_emu::Registers;
+///
+/// let condition: Condition = "v5==3 && i>0x300".parse().unwrap();
+/// let mut registers = Registers::new();
+/// registers.v[5] = 3;
+/// assert!(!condition.holds(&registers));
+/// registers.i = 0x302;
+/// assert!(condition.holds(&registers));
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition(Vec<Clause>);
+
+impl Condition {
+    pub fn holds(&self, registers: &Registers) -> bool {
+        self.0.iter().all(|clause| clause.holds(registers))
+    }
+
+    pub fn clauses(&self) -> &[Clause] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, clause) in self.0.iter().enumerate() {
+            let separator = if n == 0 { "" } else { " && " };
+            write!(
+                f,
+                "{separator}{} {} {}",
+                clause.left,
+                clause.comparison.as_str(),
+                clause.right
+            )?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// Splits the condition into words (registers and numbers) and operators.
+///
+fn tokenize(text: &str) -> Result<Vec<&str>, BreakpointError> {
+    const OPERATORS: [&str; 5] = ["==", "!=", "&&", "<", ">"];
+
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_alphanumeric() {
+            rest.find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len())
+        } else {
+            OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(*operator))
+                .map(|operator| operator.len())
+                .ok_or(BreakpointError::UnexpectedCharacter(c))?
+        };
+        tokens.push(&rest[..len]);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+impl FromStr for Condition {
+    type Err = BreakpointError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Err(BreakpointError::EmptyCondition);
+        }
+
+        let mut clauses = Vec::new();
+        for clause in tokens.split(|&token| token == "&&") {
+            let [left, comparison, right] = *clause else {
+                return Err(BreakpointError::InvalidClause(clause.join(" ")));
+            };
+            let comparison = Comparison::from_token(comparison)
+                .ok_or_else(|| BreakpointError::InvalidClause(clause.join(" ")))?;
+            clauses.push(Clause {
+                left: left.parse()?,
+                comparison,
+                right: right.parse()?,
+            });
+        }
+        Ok(Self(clauses))
+    }
+}
+
+///
+/// The `Breakpoint` struct represents an address the run stops at, when its condition holds if
+/// it has one. It's written as `0x2A4` or `0x2A4 if v5==3 && i>0x300`.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub condition: Option<Condition>,
+}
+
+impl From<u16> for Breakpoint {
+    fn from(address: u16) -> Self {
+        Self {
+            address,
+            condition: None,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:04X}", self.address)?;
+        if let Some(condition) = &self.condition {
+            write!(f, " if {condition}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = BreakpointError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        let (address, rest) = spec.split_once(char::is_whitespace).unwrap_or((spec, ""));
+        let address = number(address)?;
+        if usize::from(address) >= RAM_SIZE {
+            return Err(BreakpointError::OutsideMemory(address));
+        }
+
+        let rest = rest.trim_start();
+        if rest.is_empty() {
+            return Ok(address.into());
+        }
+        let condition = rest
+            .strip_prefix("if")
+            .filter(|condition| condition.is_empty() || condition.starts_with(char::is_whitespace))
+            .ok_or_else(|| BreakpointError::ExpectedIf(rest.to_string()))?;
+        Ok(Self {
+            address,
+            condition: Some(condition.parse()?),
+        })
+    }
+}
+
+///
+/// The `Watch` enum represents a general register or a byte of memory whose changes stop the run,
+/// written as `vA` or `0x0345`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Register(u8),
+    Memory(u16),
+}
+
+impl Watch {
+    ///
+    /// Returns the current value of the register or byte.
+    ///
+    pub fn value(self, registers: &Registers, ram: &Ram) -> u8 {
+        match self {
+            Self::Register(x) => registers.v[usize::from(x & 0xF)],
+            Self::Memory(address) => ram.read_byte(usize::from(address)).unwrap_or_default(),
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register(x) => write!(f, "V{x:X}"),
+            Self::Memory(address) => write!(f, "0x{address:04X}"),
+        }
+    }
+}
+
+impl FromStr for Watch {
+    type Err = BreakpointError;
+
+    fn from_str(word: &str) -> Result<Self, Self::Err> {
+        let word = word.trim();
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            let address = number(word)?;
+            if usize::from(address) >= RAM_SIZE {
+                return Err(BreakpointError::OutsideMemory(address));
+            }
+            return Ok(Self::Memory(address));
+        }
+
+        match word.parse()? {
+            Operand::V(x) => Ok(Self::Register(x)),
+            _ => Err(BreakpointError::UnknownRegister(word.to_string())),
+        }
+    }
+}
+
+///
+/// The `WatchHit` struct represents a watched value changed by an instruction.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub watch: Watch,
+    pub old: u8,
+    pub new: u8,
+    /// The address of the instruction that changed it
+    pub pc: u16,
+    pub opcode: u16,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} changed from 0x{:02X} to 0x{:02X} by {:04X} at 0x{:04X}",
+            self.watch, self.old, self.new, self.opcode, self.pc
+        )
+    }
+}
+
+///
+/// Parses the name of an instruction to break on, as the `Instruction` enum spells it (`DRW`,
+/// `RND`, `CALL`...), whatever its case.
+///
+pub fn parse_instruction(name: &str) -> Result<Instruction, BreakpointError> {
+    let name = name.trim();
+    Instruction::ALL
+        .into_iter()
+        .find(|instruction| format!("{instruction:?}").eq_ignore_ascii_case(name))
+        .ok_or_else(|| BreakpointError::UnknownInstruction(name.to_string()))
+}
+
+///
+/// Parses a number the way addresses and values are written everywhere, in hex with a `0x`
+/// prefix (`0x200`) or in decimal (`512`).
+///
+pub fn parse_number(text: &str) -> Result<u16, ParseIntError> {
+    text.strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .map_or_else(|| text.parse(), |hex| u16::from_str_radix(hex, 16))
+}
+
+fn number(word: &str) -> Result<u16, BreakpointError> {
+    parse_number(word).map_err(|_| BreakpointError::InvalidNumber(word.to_string()))
+}
+
+///
+/// The `BreakpointError` enum represents the ways a breakpoint or a watch can be written wrong.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointError {
+    InvalidNumber(String),
+    OutsideMemory(u16),
+    ExpectedIf(String),
+    UnknownRegister(String),
+    UnexpectedCharacter(char),
+    InvalidClause(String),
+    EmptyCondition,
+    UnknownInstruction(String),
+}
+
+impl Error for BreakpointError {}
+
+impl fmt::Display for BreakpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidNumber(word) => {
+                write!(
+                    f,
+                    "Invalid number `{word}`, expected e.g. `0x200` or `512`!"
+                )
+            }
+            Self::OutsideMemory(address) => {
+                write!(f, "Address 0x{address:X} is outside the memory!")
+            }
+            Self::ExpectedIf(rest) => write!(f, "Expected `if` after the address, found `{rest}`!"),
+            Self::UnknownRegister(word) => {
+                write!(f, "Unknown register `{word}`, expected V0-VF, I, DT or ST!")
+            }
+            Self::UnexpectedCharacter(c) => write!(f, "Unexpected `{c}` in the condition!"),
+            Self::InvalidClause(clause) => write!(
+                f,
+                "Invalid comparison `{clause}`, expected e.g. `v5 == 3` (==, !=, < or >)!"
+            ),
+            Self::EmptyCondition => write!(f, "Missing the condition after `if`!"),
+            Self::UnknownInstruction(name) => {
+                write!(
+                    f,
+                    "Unknown instruction `{name}`, expected e.g. DRW, RND or CALL!"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod breakpoint_tests {
+    use super::{
+        parse_instruction, parse_number, Breakpoint, BreakpointError, Clause, Comparison,
+        Condition, Operand, Watch,
+    };
+    use crate::emu::cpu::Instruction;
+    use crate::emu::memory::Registers;
+
+    #[test]
+    fn parses_hex_and_decimal_numbers() {
+        assert_eq!(parse_number("0x2A4"), Ok(0x2A4));
+        assert_eq!(parse_number("0XFF"), Ok(0xFF));
+        assert_eq!(parse_number("512"), Ok(512));
+        for invalid in ["", "0x", "2A4", "0x10000", "-1", " 1"] {
+            assert!(parse_number(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parses_breakpoints() {
+        assert_eq!("0x2A4".parse(), Ok(Breakpoint::from(0x2A4)));
+        assert_eq!(" 512 ".parse(), Ok(Breakpoint::from(0x200)));
+
+        let breakpoint: Breakpoint = "0x2A4 if v5==3 && I>0x300".parse().unwrap();
+        assert_eq!(breakpoint.address, 0x2A4);
+        assert_eq!(
+            breakpoint.condition.as_ref().unwrap().clauses(),
+            [
+                Clause {
+                    left: Operand::V(5),
+                    comparison: Comparison::Eq,
+                    right: Operand::Value(3),
+                },
+                Clause {
+                    left: Operand::I,
+                    comparison: Comparison::Gt,
+                    right: Operand::Value(0x300),
+                },
+            ]
+        );
+        assert_eq!(breakpoint.to_string(), "0x02A4 if v5 == 0x3 && i > 0x300");
+    }
+
+    #[test]
+    fn parses_every_operand_and_comparison() {
+        let condition: Condition = "vA != vf && dt < 10 && 0 < st".parse().unwrap();
+        assert_eq!(condition.to_string(), "va != vf && dt < 0xA && 0x0 < st");
+    }
+
+    #[test]
+    fn rejects_bad_breakpoints() {
+        let cases = [
+            ("0x1000", BreakpointError::OutsideMemory(0x1000)),
+            ("pc", BreakpointError::InvalidNumber("pc".to_string())),
+            (
+                "0x2A4 when v5==3",
+                BreakpointError::ExpectedIf("when v5==3".to_string()),
+            ),
+            ("0x2A4 if", BreakpointError::EmptyCondition),
+            (
+                "0x2A4 if vg==3",
+                BreakpointError::UnknownRegister("vg".to_string()),
+            ),
+            ("0x2A4 if v5=3", BreakpointError::UnexpectedCharacter('=')),
+            (
+                "0x2A4 if v5 3",
+                BreakpointError::InvalidClause("v5 3".to_string()),
+            ),
+            (
+                "0x2A4 if v5==3 &&",
+                BreakpointError::InvalidClause(String::new()),
+            ),
+            (
+                "0x2A4 if v5 && v6",
+                BreakpointError::InvalidClause("v5".to_string()),
+            ),
+        ];
+        for (spec, err) in cases {
+            assert_eq!(spec.parse::<Breakpoint>(), Err(err), "{spec}");
+        }
+    }
+
+    #[test]
+    fn conditions_hold_when_every_clause_does() {
+        let condition: Condition = "v5==3 && i>0x300 && dt<2 && st!=1".parse().unwrap();
+        let mut registers = Registers::new();
+        registers.v[5] = 3;
+        registers.i = 0x300;
+        assert!(!condition.holds(&registers));
+        registers.i = 0x301;
+        assert!(condition.holds(&registers));
+        registers.st = 1;
+        assert!(!condition.holds(&registers));
+        registers.st = 0;
+        registers.dt = 2;
+        assert!(!condition.holds(&registers));
+    }
+
+    #[test]
+    fn parses_watches() {
+        assert_eq!("vA".parse(), Ok(Watch::Register(0xA)));
+        assert_eq!("VF".parse(), Ok(Watch::Register(0xF)));
+        assert_eq!("0x0345".parse(), Ok(Watch::Memory(0x345)));
+        assert_eq!(Watch::Register(0xA).to_string(), "VA");
+        assert_eq!(Watch::Memory(0x345).to_string(), "0x0345");
+        assert_eq!(
+            "i".parse::<Watch>(),
+            Err(BreakpointError::UnknownRegister("i".to_string()))
+        );
+        assert_eq!(
+            "0x1000".parse::<Watch>(),
+            Err(BreakpointError::OutsideMemory(0x1000))
+        );
+    }
+
+    #[test]
+    fn parses_instructions() {
+        assert_eq!(parse_instruction("DRW"), Ok(Instruction::DRW));
+        assert_eq!(parse_instruction(" rnd"), Ok(Instruction::RND));
+        assert_eq!(parse_instruction("LdK"), Ok(Instruction::LDK));
+        assert_eq!(
+            parse_instruction("DRAW"),
+            Err(BreakpointError::UnknownInstruction("DRAW".to_string()))
+        );
+    }
+}
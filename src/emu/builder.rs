@@ -0,0 +1,578 @@
+#[cfg(feature = "native")]
+use std::time::Duration;
+
+use super::chip8::{Chip8, Chip8Error, INSTRUCTIONS_PER_SECOND};
+use super::cpu::{Clock, RandomSource};
+use super::crash::DEFAULT_PC_HISTORY_SIZE;
+use super::fault::FaultPolicy;
+use super::io::{BufferScreen, Screen};
+use super::memory::{DEFAULT_PROGRAM_START_OFFSET, RAM_SIZE, RESERVED_SIZE};
+use super::quirks::{Quirks, Variant};
+#[cfg(feature = "native")]
+use super::schedule::{DEFAULT_MAX_CATCH_UP_FRAMES, DEFAULT_SPIN_THRESHOLD};
+#[cfg(feature = "sdl")]
+use crate::frontend::sdl::keymap::{Hotkeys, KeyMap};
+#[cfg(feature = "sdl")]
+use crate::frontend::sdl::render::{RenderOptions, Renderer, ScaleFilter, VisualBeep};
+#[cfg(feature = "sdl")]
+use crate::frontend::sdl::SdlFrontend;
+use crate::frontend::{Frontend, HeadlessFrontend};
+
+///
+/// The `Chip8Builder` struct represents the configuration of an emulator before it's created.
+///
+/// Without a frontend or a screen, `build` opens an SDL window of the configured size, unless
+/// `headless` is set or the `sdl` feature is disabled.
+///
+#[allow(clippy::struct_excessive_bools)]
+pub struct Chip8Builder {
+    pub(super) screen: Option<Box<dyn Screen>>,
+    pub(super) frontend: Option<Box<dyn Frontend>>,
+    pub(super) headless: bool,
+    #[cfg(feature = "sdl")]
+    pub(super) window_size: (u32, u32),
+    #[cfg(feature = "sdl")]
+    pub(super) key_bindings: (KeyMap, Hotkeys),
+    #[cfg(feature = "sdl")]
+    pub(super) side_panel: bool,
+    #[cfg(feature = "sdl")]
+    pub(super) render: RenderOptions,
+    pub(super) ips: u32,
+    pub(super) seed: Option<u64>,
+    pub(super) random_source: Option<Box<dyn RandomSource>>,
+    pub(super) clock: Option<Box<dyn Clock>>,
+    pub(super) quirks: Quirks,
+    // Whether to run as HiRes CHIP-8, none telling from the ROMs loaded
+    pub(super) hires: Option<bool>,
+    pub(super) megachip: bool,
+    pub(super) chip8x: bool,
+    pub(super) detect_variant: bool,
+    pub(super) load_address: u16,
+    pub(super) pc_history_size: usize,
+    pub(super) profile: bool,
+    pub(super) coverage: bool,
+    pub(super) detect_uninit: bool,
+    pub(super) fault_policy: FaultPolicy,
+    pub(super) max_faults: Option<u64>,
+    pub(super) draw_age: bool,
+    #[cfg(feature = "native")]
+    pub(super) max_catch_up_frames: u32,
+    #[cfg(feature = "native")]
+    pub(super) spin_threshold: Duration,
+    #[cfg(feature = "native")]
+    pub(super) measure_latency: bool,
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8Builder {
+    pub fn new() -> Self {
+        Self {
+            screen: None,
+            frontend: None,
+            headless: false,
+            #[cfg(feature = "sdl")]
+            window_size: (800, 600),
+            #[cfg(feature = "sdl")]
+            key_bindings: (KeyMap::default(), Hotkeys::default()),
+            #[cfg(feature = "sdl")]
+            side_panel: false,
+            #[cfg(feature = "sdl")]
+            render: RenderOptions::default(),
+            ips: INSTRUCTIONS_PER_SECOND,
+            seed: None,
+            random_source: None,
+            clock: None,
+            quirks: Quirks::default(),
+            hires: None,
+            megachip: false,
+            chip8x: false,
+            detect_variant: false,
+            load_address: DEFAULT_PROGRAM_START_OFFSET as u16,
+            pc_history_size: DEFAULT_PC_HISTORY_SIZE,
+            profile: false,
+            coverage: false,
+            detect_uninit: false,
+            fault_policy: FaultPolicy::default(),
+            max_faults: None,
+            draw_age: false,
+            #[cfg(feature = "native")]
+            max_catch_up_frames: DEFAULT_MAX_CATCH_UP_FRAMES,
+            #[cfg(feature = "native")]
+            spin_threshold: DEFAULT_SPIN_THRESHOLD,
+            #[cfg(feature = "native")]
+            measure_latency: false,
+        }
+    }
+
+    ///
+    /// Draws into the given screen instead of an SDL window, without any keyboard input.
+    ///
+    #[must_use]
+    pub fn screen(mut self, screen: Box<dyn Screen>) -> Self {
+        self.screen = Some(screen);
+        self
+    }
+
+    ///
+    /// Shows the screen and takes the input through `frontend` instead of an SDL window.
+    ///
+    #[must_use]
+    pub fn frontend(mut self, frontend: Box<dyn Frontend>) -> Self {
+        self.frontend = Some(frontend);
+        self
+    }
+
+    ///
+    /// Draws into an in-memory screen instead of an SDL window, without any keyboard input.
+    ///
+    #[must_use]
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.headless = headless;
+        self
+    }
+
+    ///
+    /// Lays the window out at `width` × `height`, grown to the 64×32 screen and shrunk to the
+    /// desktop when it opens if need be.
+    ///
+    #[cfg(feature = "sdl")]
+    #[must_use]
+    pub fn window_size(mut self, width: u32, height: u32) -> Self {
+        self.window_size = (width, height);
+        self
+    }
+
+    #[cfg(feature = "sdl")]
+    #[must_use]
+    pub fn key_bindings(mut self, keymap: KeyMap, hotkeys: Hotkeys) -> Self {
+        self.key_bindings = (keymap, hotkeys);
+        self
+    }
+
+    ///
+    /// Widens the window to show the disassembly pane beside the screen, from the start on.
+    ///
+    #[cfg(feature = "sdl")]
+    #[must_use]
+    pub fn side_panel(mut self, side_panel: bool) -> Self {
+        self.side_panel = side_panel;
+        self
+    }
+
+    ///
+    /// Opens the window through this SDL video driver, `dummy` drawing nowhere, instead of the one
+    /// SDL picks or `SDL_VIDEODRIVER` names.
+    ///
+    #[cfg(feature = "sdl")]
+    #[must_use]
+    pub fn video_driver(mut self, driver: impl Into<String>) -> Self {
+        self.render.video_driver = Some(driver.into());
+        self
+    }
+
+    ///
+    /// Asks SDL for this renderer, falling back to its default with a warning if it can't.
+    ///
+    #[cfg(feature = "sdl")]
+    #[must_use]
+    pub fn renderer(mut self, renderer: Renderer) -> Self {
+        self.render.renderer = renderer;
+        self
+    }
+
+    ///
+    /// Waits for the display's vertical sync to show every frame, on top of the pacing at 60
+    /// frames per second.
+    ///
+    #[cfg(feature = "sdl")]
+    #[must_use]
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.render.vsync = vsync;
+        self
+    }
+
+    ///
+    /// Samples the screen with this filter as it's scaled up to the window.
+    ///
+    #[cfg(feature = "sdl")]
+    #[must_use]
+    pub fn scale_filter(mut self, filter: ScaleFilter) -> Self {
+        self.render.scale_filter = filter;
+        self
+    }
+
+    ///
+    /// Draws `beep` over the screen while the buzzer sounds, none showing nothing.
+    ///
+    #[cfg(feature = "sdl")]
+    #[must_use]
+    pub fn visual_beep(mut self, beep: Option<VisualBeep>) -> Self {
+        self.render.visual_beep = beep;
+        self
+    }
+
+    ///
+    /// Sets how many instructions `run` and `run_frame` execute per second.
+    ///
+    #[must_use]
+    pub fn ips(mut self, ips: u32) -> Self {
+        self.ips = ips;
+        self
+    }
+
+    ///
+    /// Seeds the random number generator, making `RND` reproducible.
+    ///
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    ///
+    /// Makes `RND` draw its numbers from `random_source` instead of a random number generator,
+    /// ignoring the seed.
+    ///
+    #[must_use]
+    pub fn random_source(mut self, random_source: Box<dyn RandomSource>) -> Self {
+        self.random_source = Some(random_source);
+        self
+    }
+
+    ///
+    /// Makes `step` count the timers down with `clock` instead of the wall clock.
+    ///
+    #[must_use]
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    ///
+    /// Applies the quirks of the variant, replacing the ones set so far, and its display.
+    ///
+    #[must_use]
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.quirks = variant.quirks();
+        self.hires = Some(variant == Variant::HiRes);
+        self.megachip = variant == Variant::MegaChip;
+        self.chip8x = variant == Variant::Chip8X;
+        self
+    }
+
+    ///
+    /// Runs as HiRes CHIP-8, with a 64×64 screen, or never does. By default every ROM starting
+    /// with the `1260` of HiRes CHIP-8 runs as it.
+    ///
+    #[must_use]
+    pub fn hires(mut self, enabled: bool) -> Self {
+        self.hires = Some(enabled);
+        self
+    }
+
+    ///
+    /// Runs as MegaChip, with the instructions switching to its 256×192 screen of colors, or not.
+    ///
+    #[must_use]
+    pub fn megachip(mut self, enabled: bool) -> Self {
+        self.megachip = enabled;
+        self
+    }
+
+    ///
+    /// Runs as CHIP-8X, with the instructions coloring its zones and reading its second keypad,
+    /// or not.
+    ///
+    #[must_use]
+    pub fn chip8x(mut self, enabled: bool) -> Self {
+        self.chip8x = enabled;
+        self
+    }
+
+    ///
+    /// Tells the variant of every ROM loaded from the instructions it runs, running the ones of
+    /// SUPER-CHIP with its quirks and the others with the quirks set, or doesn't.
+    ///
+    #[must_use]
+    pub fn detect_variant(mut self, enabled: bool) -> Self {
+        self.detect_variant = enabled;
+        self
+    }
+
+    #[must_use]
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    #[must_use]
+    pub fn quirk_shift(mut self, enabled: bool) -> Self {
+        self.quirks.shift = enabled;
+        self
+    }
+
+    #[must_use]
+    pub fn quirk_memory(mut self, enabled: bool) -> Self {
+        self.quirks.memory = enabled;
+        self
+    }
+
+    #[must_use]
+    pub fn quirk_jump(mut self, enabled: bool) -> Self {
+        self.quirks.jump = enabled;
+        self
+    }
+
+    ///
+    /// Sets the address ROMs are loaded at and start executing from.
+    ///
+    #[must_use]
+    pub fn load_address(mut self, address: u16) -> Self {
+        self.load_address = address;
+        self
+    }
+
+    ///
+    /// Sets how many of the last executed instructions `pc_history` keeps, 0 keeping none.
+    ///
+    #[must_use]
+    pub fn pc_history_size(mut self, size: usize) -> Self {
+        self.pc_history_size = size;
+        self
+    }
+
+    ///
+    /// Counts the executions of every instruction and address, for `Chip8::profile`.
+    ///
+    #[must_use]
+    pub fn profile(mut self, profile: bool) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    ///
+    /// Records which addresses are executed and which instructions, for `Chip8::coverage`.
+    ///
+    #[must_use]
+    pub fn coverage(mut self, coverage: bool) -> Self {
+        self.coverage = coverage;
+        self
+    }
+
+    ///
+    /// Warns the first time the ROM reads each V register or byte of RAM nothing wrote since the
+    /// reset. Nothing is checked without it.
+    ///
+    #[must_use]
+    pub fn detect_uninit(mut self, detect: bool) -> Self {
+        self.detect_uninit = detect;
+        self
+    }
+
+    ///
+    /// Sets which faults, unknown opcodes and accesses past the RAM or the stack, the machine
+    /// skips the instruction of instead of stopping with the error. Each fault skipped is logged
+    /// the first time it happens at its address and counted after that, in `Chip8::faults`. The
+    /// default policy stops on all of them.
+    ///
+    #[must_use]
+    pub fn fault_policy(mut self, policy: FaultPolicy) -> Self {
+        self.fault_policy = policy;
+        self
+    }
+
+    ///
+    /// Stops with the error of the fault past `max` the policy skipped. Without it, it skips any
+    /// number.
+    ///
+    #[must_use]
+    pub fn max_faults(mut self, max: u64) -> Self {
+        self.max_faults = Some(max);
+        self
+    }
+
+    ///
+    /// Tints every pixel the frontend shows by how recently it changed, for following the order
+    /// things are drawn in. The game sees the screen as always.
+    ///
+    #[must_use]
+    pub fn debug_draw_age(mut self, draw_age: bool) -> Self {
+        self.draw_age = draw_age;
+        self
+    }
+
+    ///
+    /// Sets how many frames `run` emulates on top of the one due when the host falls behind, the
+    /// time of any more being dropped. 0 never catches up.
+    ///
+    #[cfg(feature = "native")]
+    #[must_use]
+    pub fn max_catch_up_frames(mut self, frames: u32) -> Self {
+        self.max_catch_up_frames = frames;
+        self
+    }
+
+    ///
+    /// Sets how long before a frame is due `run` stops sleeping and spins on the clock instead,
+    /// trading CPU time for frames on time. 0 only sleeps.
+    ///
+    #[cfg(feature = "native")]
+    #[must_use]
+    pub fn spin_threshold(mut self, threshold: Duration) -> Self {
+        self.spin_threshold = threshold;
+        self
+    }
+
+    ///
+    /// Measures how long the key presses take to reach the game, for `Chip8::latency`.
+    ///
+    #[cfg(feature = "native")]
+    #[must_use]
+    pub fn measure_latency(mut self, measure: bool) -> Self {
+        self.measure_latency = measure;
+        self
+    }
+
+    pub fn build(mut self) -> Result<Chip8, Chip8Error> {
+        if self.ips == 0 {
+            return Err(Chip8Error::InvalidSpeed(self.ips));
+        }
+        if !(RESERVED_SIZE..RAM_SIZE).contains(&usize::from(self.load_address)) {
+            return Err(Chip8Error::InvalidLoadAddress(self.load_address));
+        }
+
+        let screen = self.screen.take();
+        let frontend = match self.frontend.take() {
+            Some(frontend) => frontend,
+            None if screen.is_some() || self.headless => Box::new(HeadlessFrontend),
+            None => self.default_frontend()?,
+        };
+        let screen = screen.unwrap_or_else(|| Box::new(BufferScreen::new()));
+        Ok(Chip8::from_builder(&mut self, screen, frontend))
+    }
+
+    #[cfg(feature = "sdl")]
+    fn default_frontend(&self) -> Result<Box<dyn Frontend>, Chip8Error> {
+        let (width, height) = self.window_size;
+        let (keymap, hotkeys) = self.key_bindings.clone();
+        let frontend = SdlFrontend::new(
+            width,
+            height,
+            keymap,
+            hotkeys,
+            self.side_panel,
+            &self.render,
+        )?;
+        Ok(Box::new(frontend))
+    }
+
+    #[cfg(not(feature = "sdl"))]
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    fn default_frontend(&self) -> Result<Box<dyn Frontend>, Chip8Error> {
+        Ok(Box::new(HeadlessFrontend))
+    }
+}
+
+#[cfg(test)]
+mod builder_tests {
+    use super::Chip8Builder;
+    use crate::emu::chip8::Chip8Error;
+    use crate::emu::quirks::{Quirks, Variant};
+    #[cfg(feature = "sdl")]
+    use crate::frontend::sdl::render::{Renderer, ScaleFilter};
+
+    #[test]
+    fn defaults() {
+        let builder = Chip8Builder::new();
+        assert!(builder.screen.is_none());
+        assert!(builder.frontend.is_none());
+        assert!(!builder.headless);
+        #[cfg(feature = "sdl")]
+        assert_eq!(builder.window_size, (800, 600));
+        #[cfg(feature = "sdl")]
+        assert!(!builder.side_panel);
+        #[cfg(feature = "sdl")]
+        assert_eq!(builder.render.renderer, Renderer::Auto);
+        #[cfg(feature = "sdl")]
+        assert_eq!(builder.render.scale_filter, ScaleFilter::Nearest);
+        #[cfg(feature = "sdl")]
+        assert!(!builder.render.vsync);
+        #[cfg(feature = "sdl")]
+        assert_eq!(builder.render.visual_beep, None);
+        #[cfg(feature = "sdl")]
+        assert_eq!(builder.render.video_driver, None);
+        assert_eq!(builder.ips, 450);
+        assert_eq!(builder.seed, None);
+        assert!(builder.random_source.is_none());
+        assert!(builder.clock.is_none());
+        assert_eq!(builder.quirks, Quirks::default());
+        assert_eq!(builder.hires, None);
+        assert!(!builder.megachip);
+        assert!(!builder.chip8x);
+        assert!(!builder.detect_variant);
+        assert_eq!(builder.load_address, 0x200);
+        assert_eq!(builder.pc_history_size, 64);
+        assert!(!builder.profile);
+        assert!(!builder.draw_age);
+        #[cfg(feature = "native")]
+        assert_eq!(builder.max_catch_up_frames, 5);
+        #[cfg(feature = "native")]
+        assert_eq!(builder.spin_threshold, std::time::Duration::from_millis(2));
+        #[cfg(feature = "native")]
+        assert!(!builder.measure_latency);
+        assert!(!builder.detect_uninit);
+    }
+
+    #[test]
+    fn chained_setters() {
+        let builder = Chip8Builder::new()
+            .variant(Variant::SChip)
+            .quirk_memory(true)
+            .seed(42)
+            .ips(700)
+            .load_address(0x600);
+        assert_eq!(
+            builder.quirks,
+            Quirks {
+                memory: true,
+                ..Variant::SChip.quirks()
+            }
+        );
+        assert_eq!(builder.hires, Some(false));
+        assert_eq!(builder.seed, Some(42));
+        assert_eq!(builder.ips, 700);
+        assert_eq!(builder.load_address, 0x600);
+    }
+
+    #[test]
+    fn builds_headless() {
+        assert!(Chip8Builder::new().headless(true).build().is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        assert!(matches!(
+            Chip8Builder::new().headless(true).ips(0).build(),
+            Err(Chip8Error::InvalidSpeed(0))
+        ));
+        assert!(matches!(
+            Chip8Builder::new()
+                .headless(true)
+                .load_address(0x10)
+                .build(),
+            Err(Chip8Error::InvalidLoadAddress(0x10))
+        ));
+        assert!(matches!(
+            Chip8Builder::new()
+                .headless(true)
+                .load_address(0x1000)
+                .build(),
+            Err(Chip8Error::InvalidLoadAddress(0x1000))
+        ));
+    }
+}
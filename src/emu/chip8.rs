@@ -1,709 +1,3304 @@
-use std::error::Error;
-use std::fmt;
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+#[cfg(feature = "native")]
+use std::path::Path;
+#[cfg(feature = "native")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "native")]
+use std::sync::Arc;
+#[cfg(feature = "native")]
+use std::{fs, io};
+#[cfg(feature = "native")]
 use std::{thread, time};
 
-use rand::{rngs, Rng};
-use sdl2::{event::Event, keyboard::Keycode};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use super::analysis::{self, Extension};
+use super::breakpoint::{Breakpoint, Condition, Watch, WatchHit};
+use super::builder::Chip8Builder;
+use super::chip8x::ColorZones;
+use super::coverage::Coverage;
+pub use super::cpu::{Chip8Error, Instruction, WORD_SIZE};
+use super::cpu::{Clock, Cpu, RandomSource, INSTRUCTION_COUNT};
+use super::crash::PcHistory;
+use super::disasm::{self, ListingLine};
+use super::fault::{FaultKind, FaultLog, FaultPolicy, OnFault, Report};
+use super::hooks::{Chip8Hooks, InstructionObserver};
+use super::instruments::Instruments;
+use super::io::{BufferScreen, Screen, GRID_HEIGHT, GRID_WIDTH, HIRES_GRID_HEIGHT};
+#[cfg(feature = "native")]
+use super::ips;
+#[cfg(feature = "native")]
+use super::latency::{self, LatencyMeter};
+use super::mega::ColorPalette;
+use super::memory::Registers;
+#[cfg(feature = "native")]
+use super::memory::RAM_SIZE;
+use super::poke::{Poke, Pokes};
+use super::quirks::{Quirks, Variant};
+#[cfg(feature = "native")]
+use super::remote::{Command, ControlServer};
+use super::rom;
+#[cfg(feature = "native")]
+use super::rom::RomError;
+#[cfg(feature = "native")]
+use super::schedule::{Pacer, Scheduler, SystemClock};
+use super::screenshot;
+#[cfg(feature = "script")]
+use super::script::ScriptHost;
+use super::state::{Chip8State, StateError};
+#[cfg(feature = "native")]
+use super::stats::{FrameRate, FrameTimes, RunOutcome};
+use super::stats::{Profile, Stats};
+use super::trace::{TraceRecord, TraceRegisters, Tracer};
+use super::turbo::{Turbo, TurboKey};
+use super::uninit::UninitDetector;
+#[cfg(feature = "native")]
+use crate::frontend::Input;
+#[cfg(feature = "egui")]
+use crate::frontend::{DebugView, MEMORY_COLUMNS};
+use crate::frontend::{Frontend, HeadlessFrontend};
 
-use super::io::{Display, Keyboard, GRID_HEIGHT, GRID_WIDTH};
-use super::memory::{Ram, Registers};
+///
+/// The `INSTRUCTIONS_PER_SECOND` value need for emulate the COSMAC VIP CPU's frequency.
+///
+pub const INSTRUCTIONS_PER_SECOND: u32 = 450;
 
 ///
-/// The `WORD_SIZE` constant is the chip8's word size.
+/// The `FRAMES_PER_SECOND` value is the rate of the timers, and of `run_frame`.
 ///
-pub const WORD_SIZE: u16 = 2;
+pub const FRAMES_PER_SECOND: u32 = 60;
 
 ///
-/// The `INSTRUCTIONS_PER_SECOND` value need for emulate the COSMAC VIP CPU's frequency.
+/// The `PANE_ROWS` value is how many instructions the disassembly pane shows.
 ///
-const INSTRUCTIONS_PER_SECOND: u32 = 450;
+#[cfg(feature = "native")]
+const PANE_ROWS: u16 = 16;
 
 ///
-/// The `Chip8Error` enum represents the possible errors that can occur when running the CHIP-8 emulator.
+/// The `DEBUG_MEMORY_BYTES` value is how much memory the debug memory panel shows, in 8 rows.
 ///
-#[derive(Debug)]
-pub enum Chip8Error {
-    FailedToDecodeOpcode,
+#[cfg(feature = "egui")]
+const DEBUG_MEMORY_BYTES: usize = 8 * MEMORY_COLUMNS;
+
+///
+/// Returns the start of the memory panel's row holding `address`, as close to it as the panel
+/// fits in memory.
+///
+#[cfg(feature = "egui")]
+fn memory_row(address: i32) -> u16 {
+    let last = (RAM_SIZE - DEBUG_MEMORY_BYTES) as u16;
+    let address = u16::try_from(address.max(0)).map_or(last, |address| address.min(last));
+    address - address % MEMORY_COLUMNS as u16
 }
 
-impl Error for Chip8Error {}
+///
+/// The `StepOutcome` struct represents what a single instruction did to the machine.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StepOutcome {
+    pub display_changed: bool,
+    pub sound_on: bool,
+    pub waiting_for_key: bool,
+    pub cycles: u32,
+    /// The watched value the instruction changed, if any
+    pub watch: Option<WatchHit>,
+}
 
-impl fmt::Display for Chip8Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
-            Self::FailedToDecodeOpcode => write!(f, "Failed to decode opcode!"),
-        }
+///
+/// The `RunSummary` struct represents what a run of several instructions did to the machine: the
+/// display changed if any of them changed it, the rest is the state the run ended in.
+///
+/// `breakpoint` is the address of the breakpoint that stopped the run early, if one did, and
+/// `watch` the watched value whose change did.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RunSummary {
+    pub cycles: u64,
+    pub display_changed: bool,
+    pub sound_on: bool,
+    pub waiting_for_key: bool,
+    pub breakpoint: Option<u16>,
+    /// The kind of instruction the breakpoint was for, if it was for one rather than an address
+    pub break_on: Option<Instruction>,
+    pub watch: Option<WatchHit>,
+}
+
+impl RunSummary {
+    fn merge(&mut self, later: Self) {
+        self.cycles += later.cycles;
+        self.display_changed |= later.display_changed;
+        self.sound_on = later.sound_on;
+        self.waiting_for_key = later.waiting_for_key;
+        self.breakpoint = later.breakpoint;
+        self.break_on = later.break_on;
+        self.watch = later.watch;
+    }
+
+    ///
+    /// Returns whether a breakpoint or a watch stopped the run early.
+    ///
+    pub const fn stopped(&self) -> bool {
+        self.breakpoint.is_some() || self.watch.is_some()
     }
 }
 
 ///
-/// The `Instruction` enum represents the set of instructions supported by the Chip8 emulator.
+/// The `RunState` enum represents what the run loop does with a frame: run it, or wait it out on
+/// the frontend's input since nothing is going to happen without any.
 ///
-#[allow(clippy::upper_case_acronyms)]
-#[derive(PartialEq, Eq)]
-pub enum Instruction {
-    CLS,
-    RET,
-    JMP,
-    JMPV0,
-    CALL,
-    LD,
-    LDR,
-    LDRI,
-    LDRDT,
-    LDDTR,
-    LDRST,
-    LDK,
-    LDSR,
-    LDB,
-    LDRIR,
-    LDRRI,
-    SE,
-    SER,
-    SNE,
-    SNER,
-    ADD,
-    ADDR,
-    ADDRI,
-    SUB,
-    SUBN,
-    AND,
-    OR,
-    XOR,
-    SHR,
-    SHL,
-    RND,
-    DRW,
-    SKP,
-    SKNP,
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    /// Paused, or without a ROM to run
+    Paused,
+    /// Stuck on an `LD Vx, K` until a key is pressed, the timers still running
+    WaitingForKey,
+}
+
+impl From<StepOutcome> for RunSummary {
+    fn from(outcome: StepOutcome) -> Self {
+        Self {
+            cycles: u64::from(outcome.cycles),
+            display_changed: outcome.display_changed,
+            sound_on: outcome.sound_on,
+            waiting_for_key: outcome.waiting_for_key,
+            breakpoint: None,
+            break_on: None,
+            watch: outcome.watch,
+        }
+    }
 }
 
 ///
 /// The `Chip8` structure represents the interface for using the chip8 emulator.
 ///
+#[allow(clippy::struct_excessive_bools)]
 pub struct Chip8 {
-    display: Display,
-    keyboard: Keyboard,
-    ram: Ram,
-    registers: Registers,
-    rnd_engine: rngs::ThreadRng,
-    delay_timer: time::Instant,
+    display: Box<dyn Screen>,
+    frontend: Box<dyn Frontend>,
+    hooks: Vec<Box<dyn Chip8Hooks>>,
+    cpu: Cpu,
+    rnd_engine: Box<dyn RandomSource>,
+    clock: Option<Box<dyn Clock>>,
+    // Whether to run as HiRes CHIP-8, none telling from every ROM loaded
+    hires: Option<bool>,
+    // The quirks of the ROMs that aren't SUPER-CHIP's, when telling the variant of every ROM loaded
+    detected_quirks: Option<Quirks>,
+    ips: u32,
+    frame_credit: u32,
+    last_outcome: StepOutcome,
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    // The kinds of instructions that stop the run, indexed by their discriminant
+    break_on: [bool; INSTRUCTION_COUNT],
+    // The breakpoint the run stopped on, passed over once when it resumes
+    stopped_at: Option<u16>,
+    // Where a step over or out of a call stops, as an address and a stack depth
+    temporary_breakpoint: Option<(u16, usize)>,
+    // The watched values, as they were after the last instruction
+    watches: Vec<(Watch, u8)>,
+    turbo: Turbo,
+    pokes: Pokes,
+    pc_history: PcHistory,
+    fault_policy: FaultPolicy,
+    // The faults skipped over, while the policy skips any
+    faults: Option<FaultLog>,
+    // The tracer, the profile and the other tools observing every instruction, while there's one
+    instruments: Option<Box<Instruments>>,
+    // Whether the frontend is sent how recently every pixel changed along with the screen
+    draw_age: bool,
+    rom: Option<Vec<u8>>,
+    rom_path: Option<String>,
+    rom_hash: Option<String>,
+    #[cfg(feature = "native")]
+    paused: bool,
+    #[cfg(feature = "native")]
+    drop_requires_pause: bool,
+    #[cfg(feature = "native")]
+    overlay: bool,
+    #[cfg(feature = "native")]
+    pane: bool,
+    #[cfg(feature = "egui")]
+    debug_view: bool,
+    // The last frame times, only recorded while their graph is shown
+    #[cfg(feature = "native")]
+    frame_graph: Option<FrameTimes>,
+    // Whether the emulator was paused before the help was shown, none while it's hidden
+    #[cfg(feature = "native")]
+    help: Option<bool>,
+    // The address at the top of the debug memory panel
+    #[cfg(feature = "egui")]
+    memory_view: u16,
+    #[cfg(feature = "native")]
+    control: Option<ControlServer>,
+    #[cfg(feature = "script")]
+    script: Option<ScriptHost>,
+    // The lines the script drew over the screen on its last frame
+    #[cfg(feature = "native")]
+    hud: Vec<String>,
+    stats: Stats,
+    #[cfg(feature = "native")]
+    max_cycles: Option<u64>,
+    #[cfg(feature = "native")]
+    max_run_time: Option<time::Duration>,
+    #[cfg(feature = "native")]
+    max_catch_up_frames: u32,
+    #[cfg(feature = "native")]
+    spin_threshold: time::Duration,
+    // Set from outside, a signal handler typically, to end the run loop at the next frame
+    #[cfg(feature = "native")]
+    stop_requested: Option<Arc<AtomicBool>>,
+    // The input that ended the wait of an idle frame, handled first on the next one
+    #[cfg(feature = "native")]
+    pending_input: Option<Input>,
+    #[cfg(feature = "native")]
+    latency: Option<LatencyMeter>,
 }
 
 impl Chip8 {
-    pub fn new(window_width: u32, window_height: u32) -> Result<Self, Box<dyn Error>> {
-        Ok(Self {
-            display: Display::new(window_width, window_height)?,
-            keyboard: Keyboard::new(),
-            ram: Ram::new(),
-            registers: Registers::new(),
-            rnd_engine: rand::thread_rng(),
-            delay_timer: time::Instant::now(),
-        })
+    ///
+    /// Creates an emulator with the default configuration in an SDL window of the given size.
+    ///
+    #[cfg(feature = "sdl")]
+    pub fn new(window_width: u32, window_height: u32) -> Result<Self, Chip8Error> {
+        Chip8Builder::new()
+            .window_size(window_width, window_height)
+            .build()
     }
 
-    pub fn load_rom(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
-        let file = File::open(path)?;
-        let mut buf = Vec::new();
-        BufReader::new(file).read_to_end(&mut buf)?;
-        self.ram.load(buf.as_slice())?;
-        Ok(())
+    ///
+    /// Creates an emulator drawing into an in-memory screen, without any window or input.
+    ///
+    pub fn headless() -> Self {
+        Self::from_builder(
+            &mut Chip8Builder::new(),
+            Box::new(BufferScreen::new()),
+            Box::new(HeadlessFrontend),
+        )
     }
 
-    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
-        let mut event_pump = self.display.get_event_pump()?;
-        'exit_from_loop: loop {
-            for event in event_pump.poll_iter() {
-                match event {
-                    Event::Quit { .. }
-                    | Event::KeyDown {
-                        keycode: Some(Keycode::Escape),
-                        ..
-                    } => break 'exit_from_loop,
-                    Event::KeyDown { keycode, .. } => {
-                        if let Some(key) = keycode {
-                            self.keyboard.press_key(key);
-                        } else {
-                            self.keyboard.release_key();
-                        }
-                    }
-                    _ => {}
-                }
-            }
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::new()
+    }
 
-            let opcode = self.fetch()?;
-            let instruction = Self::decode(opcode)?;
+    #[allow(clippy::too_many_lines)]
+    pub(super) fn from_builder(
+        builder: &mut Chip8Builder,
+        display: Box<dyn Screen>,
+        frontend: Box<dyn Frontend>,
+    ) -> Self {
+        log::debug!(
+            "Quirks: shift {}, memory {}, jump {}",
+            builder.quirks.shift,
+            builder.quirks.memory,
+            builder.quirks.jump
+        );
+        #[cfg(feature = "native")]
+        let seeded = || {
+            builder
+                .seed
+                .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)
+        };
+        // Without an OS there's no entropy to draw from, the host has to pass a seed for variety
+        #[cfg(not(feature = "native"))]
+        let seeded = || StdRng::seed_from_u64(builder.seed.unwrap_or_default());
+        let rnd_engine = builder
+            .random_source
+            .take()
+            .unwrap_or_else(|| Box::new(seeded()));
+
+        #[cfg(feature = "native")]
+        let clock = builder
+            .clock
+            .take()
+            .or_else(|| Some(Box::new(InstantClock::new())));
+        #[cfg(not(feature = "native"))]
+        let clock = builder.clock.take();
+
+        let mut instruments = Box::new(Instruments::new());
+        instruments.profile = builder.profile.then(Profile::new);
+        instruments.coverage = builder.coverage.then(Coverage::new);
+        instruments.uninit = builder
+            .detect_uninit
+            .then(|| UninitDetector::new(usize::from(builder.load_address)));
+
+        let mut chip8 = Self {
+            display,
+            frontend,
+            hooks: Vec::new(),
+            cpu: Cpu::new(builder.quirks, builder.load_address),
+            rnd_engine,
+            clock,
+            hires: builder.hires,
+            detected_quirks: builder.detect_variant.then_some(builder.quirks),
+            ips: builder.ips,
+            frame_credit: 0,
+            last_outcome: StepOutcome::default(),
+            breakpoints: BTreeMap::new(),
+            break_on: [false; INSTRUCTION_COUNT],
+            stopped_at: None,
+            temporary_breakpoint: None,
+            watches: Vec::new(),
+            turbo: Turbo::new(),
+            pokes: Pokes::new(),
+            pc_history: PcHistory::new(builder.pc_history_size),
+            fault_policy: builder.fault_policy,
+            faults: builder
+                .fault_policy
+                .recovers()
+                .then(|| FaultLog::new(builder.max_faults)),
+            instruments: Some(instruments).filter(|instruments| !instruments.is_empty()),
+            draw_age: builder.draw_age,
+            rom: None,
+            rom_path: None,
+            rom_hash: None,
+            #[cfg(feature = "native")]
+            paused: false,
+            #[cfg(feature = "native")]
+            drop_requires_pause: false,
+            #[cfg(feature = "native")]
+            overlay: false,
+            #[cfg(feature = "sdl")]
+            pane: builder.side_panel && !builder.headless,
+            #[cfg(all(feature = "native", not(feature = "sdl")))]
+            pane: false,
+            #[cfg(feature = "egui")]
+            debug_view: false,
+            #[cfg(feature = "native")]
+            frame_graph: None,
+            #[cfg(feature = "native")]
+            help: None,
+            #[cfg(feature = "egui")]
+            memory_view: memory_row(i32::from(builder.load_address)),
+            #[cfg(feature = "native")]
+            control: None,
+            #[cfg(feature = "script")]
+            script: None,
+            #[cfg(feature = "native")]
+            hud: Vec::new(),
+            stats: Stats::default(),
+            #[cfg(feature = "native")]
+            max_cycles: None,
+            #[cfg(feature = "native")]
+            max_run_time: None,
+            #[cfg(feature = "native")]
+            max_catch_up_frames: builder.max_catch_up_frames,
+            #[cfg(feature = "native")]
+            spin_threshold: builder.spin_threshold,
+            #[cfg(feature = "native")]
+            stop_requested: None,
+            #[cfg(feature = "native")]
+            pending_input: None,
+            #[cfg(feature = "native")]
+            latency: builder.measure_latency.then(LatencyMeter::new),
+        };
+        chip8.set_hires(builder.hires == Some(true));
+        chip8.cpu.set_megachip(builder.megachip);
+        chip8.cpu.set_chip8x(builder.chip8x);
+        chip8.set_title("drop a ROM here");
+        chip8.emit_frame();
+        chip8
+    }
 
-            self.execute(&instruction, opcode)?;
-            Self::emulate_speed();
+    ///
+    /// Makes the machine HiRes CHIP-8, with a 64×64 screen, or back, blanking the screen.
+    ///
+    fn set_hires(&mut self, hires: bool) {
+        self.cpu.set_hires(hires);
+        self.display.set_size(
+            GRID_WIDTH,
+            if hires {
+                HIRES_GRID_HEIGHT
+            } else {
+                GRID_HEIGHT
+            },
+        );
+    }
+
+    ///
+    /// Returns whether the machine runs as HiRes CHIP-8, picked by the builder or by the ROM.
+    ///
+    pub const fn hires(&self) -> bool {
+        self.cpu.hires()
+    }
+
+    ///
+    /// Returns the palette the screen's pixel values are colored by while MegaChip's mega mode is
+    /// on, none when they're the usual on and off.
+    ///
+    pub fn palette(&self) -> Option<&ColorPalette> {
+        self.cpu.mega_mode().then(|| self.cpu.palette())
+    }
+
+    ///
+    /// Returns the colors of CHIP-8X's zones and background the screen's pixels are shown in,
+    /// none when the machine doesn't run as CHIP-8X.
+    ///
+    pub fn color_zones(&self) -> Option<&ColorZones> {
+        self.cpu.chip8x().then(|| self.cpu.color_zones())
+    }
+
+    ///
+    /// Attaches callbacks fired as the machine runs, on top of the ones already attached and the
+    /// frontend.
+    ///
+    pub fn add_hooks(&mut self, hooks: impl Chip8Hooks + 'static) {
+        self.hooks.push(Box::new(hooks));
+    }
+
+    ///
+    /// Hands every executed instruction to `tracer`, with the registers before and after it, in
+    /// place of the previous one.
+    ///
+    pub fn set_tracer(&mut self, tracer: impl Tracer + 'static) {
+        self.instruments_mut().tracer = Some(Box::new(tracer));
+    }
+
+    pub fn clear_tracer(&mut self) {
+        self.instruments_mut().tracer = None;
+        self.drop_idle_instruments();
+    }
+
+    ///
+    /// Hands every executed instruction to `observer`, before and after it executes, in place of
+    /// the previous one.
+    ///
+    pub fn set_observer(&mut self, observer: impl InstructionObserver + 'static) {
+        self.instruments_mut().observer = Some(Box::new(observer));
+    }
+
+    pub fn clear_observer(&mut self) {
+        self.instruments_mut().observer = None;
+        self.drop_idle_instruments();
+    }
+
+    fn instruments_mut(&mut self) -> &mut Instruments {
+        self.instruments.get_or_insert_with(Box::default)
+    }
+
+    ///
+    /// Stops observing the instructions once no tool is left to.
+    ///
+    fn drop_idle_instruments(&mut self) {
+        if self
+            .instruments
+            .as_ref()
+            .is_some_and(|instruments| instruments.is_empty())
+        {
+            self.instruments = None;
         }
+    }
 
-        Ok(())
+    fn set_title(&mut self, title: &str) {
+        self.frontend.set_title(title);
+        for hooks in &mut self.hooks {
+            hooks.on_title(title);
+        }
     }
 
-    fn fetch(&mut self) -> Result<u16, Box<dyn Error>> {
-        let opcode = self.ram.read_word(self.registers.pc as usize)?;
-        Ok(opcode)
-    }
-
-    fn decode(opcode: u16) -> Result<Instruction, Box<dyn Error>> {
-        if opcode == 0x00E0 {
-            Ok(Instruction::CLS)
-        } else if opcode == 0x00EE {
-            Ok(Instruction::RET)
-        } else if (opcode & 0xF000) == 0x1000 {
-            Ok(Instruction::JMP)
-        } else if (opcode & 0xF000) == 0x2000 {
-            Ok(Instruction::CALL)
-        } else if (opcode & 0xF000) == 0x3000 {
-            Ok(Instruction::SE)
-        } else if (opcode & 0xF000) == 0x4000 {
-            Ok(Instruction::SNE)
-        } else if (opcode & 0xF000) == 0x5000 {
-            Ok(Instruction::SER)
-        } else if (opcode & 0xF000) == 0x6000 {
-            Ok(Instruction::LD)
-        } else if (opcode & 0xF000) == 0x7000 {
-            Ok(Instruction::ADD)
-        } else if (opcode & 0xF000) == 0x8000 {
-            match (opcode & 0x000F) as u8 {
-                0x0 => Ok(Instruction::LDR),
-                0x1 => Ok(Instruction::OR),
-                0x2 => Ok(Instruction::AND),
-                0x3 => Ok(Instruction::XOR),
-                0x4 => Ok(Instruction::ADDR),
-                0x5 => Ok(Instruction::SUB),
-                0x6 => Ok(Instruction::SHR),
-                0x7 => Ok(Instruction::SUBN),
-                0xE => Ok(Instruction::SHL),
-                _ => Err(Chip8Error::FailedToDecodeOpcode.into()),
-            }
-        } else if (opcode & 0xF000) == 0x9000 {
-            Ok(Instruction::SNER)
-        } else if (opcode & 0xF000) == 0xA000 {
-            Ok(Instruction::LDRI)
-        } else if (opcode & 0xF000) == 0xB000 {
-            Ok(Instruction::JMPV0)
-        } else if (opcode & 0xF000) == 0xC000 {
-            Ok(Instruction::RND)
-        } else if (opcode & 0xF000) == 0xD000 {
-            Ok(Instruction::DRW)
-        } else if (opcode & 0xF000) == 0xE000 {
-            match (opcode & 0x00FF) as u8 {
-                0x9E => Ok(Instruction::SKP),
-                0xA1 => Ok(Instruction::SKNP),
-                _ => Err(Chip8Error::FailedToDecodeOpcode.into()),
-            }
-        } else if (opcode & 0xF000) == 0xF000 {
-            match (opcode & 0x00FF) as u8 {
-                0x07 => Ok(Instruction::LDRDT),
-                0x0A => Ok(Instruction::LDK),
-                0x15 => Ok(Instruction::LDDTR),
-                0x18 => Ok(Instruction::LDRST),
-                0x1E => Ok(Instruction::ADDRI),
-                0x29 => Ok(Instruction::LDSR),
-                0x33 => Ok(Instruction::LDB),
-                0x55 => Ok(Instruction::LDRIR),
-                0x65 => Ok(Instruction::LDRRI),
-                _ => Err(Chip8Error::FailedToDecodeOpcode.into()),
-            }
-        } else {
-            Err(Chip8Error::FailedToDecodeOpcode.into())
+    fn emit_frame(&mut self) {
+        self.present();
+        for hooks in &mut self.hooks {
+            hooks.on_frame(self.display.framebuffer());
         }
     }
 
-    fn execute(&mut self, instruction: &Instruction, opcode: u16) -> Result<(), Box<dyn Error>> {
-        match instruction {
-            Instruction::CLS => {
-                self.cls();
-                Ok(())
-            }
-            Instruction::RET => {
-                self.ret();
-                Ok(())
-            }
-            Instruction::JMP => {
-                self.jmp(opcode);
-                Ok(())
-            }
-            Instruction::CALL => {
-                self.call(opcode);
-                Ok(())
-            }
-            Instruction::SE => {
-                self.se(opcode);
-                Ok(())
-            }
-            Instruction::SNE => {
-                self.sne(opcode);
-                Ok(())
-            }
-            Instruction::SER => {
-                self.ser(opcode);
-                Ok(())
-            }
-            Instruction::LD => {
-                self.ld(opcode);
-                Ok(())
-            }
-            Instruction::ADD => {
-                self.add(opcode);
-                Ok(())
-            }
-            Instruction::LDR => {
-                self.ldr(opcode);
-                Ok(())
-            }
-            Instruction::OR => {
-                self.or(opcode);
-                Ok(())
-            }
-            Instruction::AND => {
-                self.and(opcode);
-                Ok(())
-            }
-            Instruction::XOR => {
-                self.xor(opcode);
-                Ok(())
-            }
-            Instruction::ADDR => {
-                self.addr(opcode);
-                Ok(())
-            }
-            Instruction::SUB => {
-                self.sub(opcode);
-                Ok(())
-            }
-            Instruction::SHR => {
-                self.shr(opcode);
-                Ok(())
-            }
-            Instruction::SUBN => {
-                self.subn(opcode);
-                Ok(())
-            }
-            Instruction::SHL => {
-                self.shl(opcode);
-                Ok(())
-            }
-            Instruction::SNER => {
-                self.sner(opcode);
-                Ok(())
-            }
-            Instruction::LDRI => {
-                self.ldri(opcode);
-                Ok(())
-            }
-            Instruction::JMPV0 => {
-                self.jmpv0(opcode);
-                Ok(())
-            }
-            Instruction::RND => {
-                self.rnd(opcode);
-                Ok(())
-            }
-            Instruction::DRW => self.drw(opcode),
-            Instruction::SKP => {
-                self.skp(opcode);
-                Ok(())
-            }
-            Instruction::SKNP => {
-                self.sknp(opcode);
-                Ok(())
-            }
-            Instruction::LDRDT => {
-                self.ldrdt(opcode);
-                Ok(())
-            }
-            Instruction::LDK => {
-                self.ldk(opcode);
-                Ok(())
-            }
-            Instruction::LDDTR => {
-                self.lddtr(opcode);
-                Ok(())
+    ///
+    /// Shows the screen on the frontend, with the age of its pixels when they're tinted by it.
+    ///
+    fn present(&mut self) {
+        #[cfg(feature = "profiling")]
+        let _span = tracing::trace_span!("present").entered();
+        if self.draw_age {
+            self.frontend.set_pixel_ages(self.display.pixel_ages());
+        }
+        if self.cpu.megachip() {
+            let palette = self.cpu.mega_mode().then(|| self.cpu.palette());
+            self.frontend.set_palette(palette);
+        }
+        if self.cpu.chip8x() {
+            self.frontend.set_color_zones(Some(self.cpu.color_zones()));
+        }
+        self.frontend.present(self.display.framebuffer());
+    }
+
+    pub const fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    ///
+    /// Loads the ROM and restarts the machine with it, keeping a copy so `reset` can restart it
+    /// again later. The running ROM is left untouched when the new one is invalid.
+    ///
+    pub fn load_rom_bytes(&mut self, bytes: &[u8]) -> Result<(), Chip8Error> {
+        self.cpu.check_rom(bytes)?;
+
+        let hash = sha1_smol::Sha1::from(bytes).digest().to_string();
+        log::debug!(
+            "Loaded a ROM of {} bytes at 0x{:04X}, SHA-1 {hash}",
+            bytes.len(),
+            self.cpu.load_address()
+        );
+        let hires = self
+            .hires
+            .unwrap_or_else(|| Variant::detect(bytes) == Some(Variant::HiRes));
+        if hires && self.hires.is_none() {
+            log::info!("Running the ROM as HiRes CHIP-8, it starts with 1260.");
+        }
+        self.set_hires(hires);
+        if let Some(quirks) = self.detected_quirks {
+            let detection = analysis::detect_variant(bytes, self.cpu.load_address());
+            match detection.extension {
+                Some(Extension::XoChip) => {
+                    log::warn!(
+                        "Detected {detection}, which isn't supported, running it as CHIP-8."
+                    );
+                }
+                Some(Extension::SChip) => log::warn!(
+                    "Detected {detection}, whose instructions aren't supported, running it with \
+                     SUPER-CHIP's quirks only."
+                ),
+                None => log::info!("Detected {detection}."),
             }
-            Instruction::LDRST => {
-                self.ldrst(opcode);
-                Ok(())
+            self.cpu
+                .set_quirks(detection.variant().map_or(quirks, Variant::quirks));
+        }
+        self.rom = Some(bytes.to_vec());
+        self.rom_path = None;
+        self.rom_hash = Some(hash);
+        self.reset();
+        Ok(())
+    }
+
+    ///
+    /// Loads the demo ROM embedded in the binary.
+    ///
+    pub fn load_demo(&mut self) -> Result<(), Chip8Error> {
+        self.load_rom_bytes(rom::DEMO_ROM)?;
+        self.set_title("demo");
+        Ok(())
+    }
+
+    ///
+    /// Restarts the machine from a blank state with the loaded ROM, if any.
+    ///
+    pub fn reset(&mut self) {
+        if self.cpu.mega_mode() {
+            // Back to the screen the ROM starts on
+            self.set_hires(self.cpu.hires());
+        }
+        self.display.clear();
+        self.emit_frame();
+        if let Some(clock) = &mut self.clock {
+            // Time spent before the reset doesn't count against the new timers
+            clock.ticks();
+        }
+        if self.last_outcome.sound_on {
+            self.set_sound(false);
+        }
+        self.last_outcome = StepOutcome::default();
+        self.stopped_at = None;
+        self.temporary_breakpoint = None;
+        self.pc_history.clear();
+        #[cfg(feature = "native")]
+        {
+            // The machine runs from the start once the help is out of the way
+            self.paused = self.help.is_some();
+            if let Some(paused) = &mut self.help {
+                *paused = false;
             }
-            Instruction::ADDRI => {
-                self.addri(opcode);
-                Ok(())
+        }
+
+        match &self.rom {
+            // Can't fail, the ROM was checked when it was loaded
+            Some(rom) => {
+                let _ = self.cpu.load_rom(rom);
             }
-            Instruction::LDSR => {
-                self.ldsr(opcode);
-                Ok(())
+            None => self.cpu.reset(),
+        }
+        let uninit = self
+            .instruments
+            .as_mut()
+            .and_then(|instruments| instruments.uninit.as_mut());
+        if let Some(detector) = uninit {
+            let rom_len = self.rom.as_ref().map_or(0, Vec::len);
+            detector.reset(usize::from(self.cpu.load_address()) + rom_len);
+        }
+        self.cpu.apply_pokes(&self.pokes);
+        self.refresh_watches();
+    }
+
+    ///
+    /// Returns how many instructions `run` and `run_frame` execute per second.
+    ///
+    pub const fn ips(&self) -> u32 {
+        self.ips
+    }
+
+    pub fn rom_path(&self) -> Option<&str> {
+        self.rom_path.as_deref()
+    }
+
+    ///
+    /// Returns the SHA-1 hash of the loaded ROM.
+    ///
+    pub fn rom_hash(&self) -> Option<&str> {
+        self.rom_hash.as_deref()
+    }
+
+    pub const fn pc(&self) -> u16 {
+        self.cpu.registers().pc
+    }
+
+    pub const fn i(&self) -> u16 {
+        self.cpu.registers().i
+    }
+
+    ///
+    /// Returns the value of the general register `Vx`, where `x` is taken modulo 16.
+    ///
+    /// ```
+    /// let mut chip8 = chip8_emu::Chip8::headless();
+    /// // LD V3, 0x2A
+    /// chip8.load_rom_bytes(&[0x63, 0x2A]).unwrap();
+    /// chip8.step().unwrap();
+    /// assert_eq!(chip8.v(3), 0x2A);
+    /// assert_eq!(chip8.pc(), 0x202);
+    /// ```
+    ///
+    pub const fn v(&self, x: u8) -> u8 {
+        self.cpu.registers().v[(x & 0xF) as usize]
+    }
+
+    pub const fn registers(&self) -> &Registers {
+        self.cpu.registers()
+    }
+
+    ///
+    /// Returns the bare machine underneath, with its memory.
+    ///
+    pub const fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    ///
+    /// Returns the current value of the delay timer, which counts down at 60 Hz.
+    ///
+    pub const fn delay_timer(&self) -> u8 {
+        self.cpu.registers().dt
+    }
+
+    ///
+    /// Returns the current value of the sound timer, which counts down at 60 Hz while the buzzer
+    /// sounds.
+    ///
+    pub const fn sound_timer(&self) -> u8 {
+        self.cpu.registers().st
+    }
+
+    ///
+    /// Returns whether the buzzer is sounding, as long as the sound timer is above zero, for
+    /// frontends showing it some other way than a tone.
+    ///
+    pub const fn sound_active(&self) -> bool {
+        self.sound_timer() > 0
+    }
+
+    ///
+    /// Counts the delay and sound timers down by one 60 Hz tick, for frontends driving the
+    /// emulator one frame at a time. With a `Clock` (native builds have one unless told otherwise),
+    /// `step` also ticks them as time passes.
+    ///
+    pub fn tick_timers(&mut self) {
+        self.tick(1);
+    }
+
+    fn tick(&mut self, ticks: u32) {
+        self.cpu.tick_timers(ticks);
+        if self.last_outcome.sound_on && self.sound_timer() == 0 {
+            self.last_outcome.sound_on = false;
+            self.set_sound(false);
+        }
+    }
+
+    fn set_sound(&mut self, on: bool) {
+        #[cfg(feature = "profiling")]
+        let _span = tracing::trace_span!("sound", on).entered();
+        self.frontend.set_tone(on);
+        for hooks in &mut self.hooks {
+            if on {
+                hooks.on_sound_start();
+            } else {
+                hooks.on_sound_stop();
             }
-            Instruction::LDB => self.ldb(opcode),
-            Instruction::LDRIR => self.ldrir(opcode),
-            Instruction::LDRRI => self.ldrri(opcode),
         }
     }
 
-    fn emulate_speed() {
-        thread::sleep(time::Duration::from_secs(1) / INSTRUCTIONS_PER_SECOND);
+    ///
+    /// Presses or releases a hex key of the keypad, for frontends doing their own input. A turbo
+    /// key is held instead, and pressed over and over from the next frame on.
+    ///
+    pub fn set_key(&mut self, hex_key: u8, pressed: bool) {
+        let hex_key = hex_key & 0xF;
+        if !self.turbo.hold(hex_key, pressed) || !pressed {
+            self.cpu.set_key(hex_key, pressed);
+        }
+    }
+
+    ///
+    /// Presses or releases a hex key of CHIP-8X's second keypad, for frontends doing their own
+    /// input.
+    ///
+    pub fn set_second_key(&mut self, hex_key: u8, pressed: bool) {
+        self.cpu.set_second_key(hex_key, pressed);
     }
 
-    fn cls(&mut self) {
-        self.display.clear();
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Makes holding the hex key press it over and over at the key's rate, on the same frames of
+    /// every run. A key that already was a turbo key takes the new rate.
+    ///
+    /// ```
+    /// let mut chip8 = chip8_emu::Chip8::headless();
+    /// // LD V0, 0x05; SKP V0; JMP 0x202; ADD V1, 0x01; SKNP V0; JMP 0x208; JMP 0x202, counting
+    /// // the presses of key 5 in V1
+    /// let rom = [0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02, 0x71, 0x01, 0xE0, 0xA1, 0x12, 0x08, 0x12, 0x02];
+    /// chip8.load_rom_bytes(&rom).unwrap();
+    /// chip8.add_turbo_key("5:10".parse().unwrap());
+    /// chip8.set_key(0x5, true);
+    /// // A second holding the key gives ten presses
+    /// chip8.run_frames(60).unwrap();
+    /// assert_eq!(chip8.v(1), 10);
+    /// ```
+    ///
+    pub fn add_turbo_key(&mut self, key: TurboKey) {
+        self.turbo.add(key);
     }
 
-    fn ret(&mut self) {
-        self.registers.pc = self.registers.sp.pop().unwrap();
+    ///
+    /// Freezes the byte of RAM at the poke's value, writing it now and again after every frame,
+    /// once the game's own writes are done. A later poke of the same address replaces it.
+    ///
+    /// The pokes only touch the RAM, the ROM and its hash stay the game's.
+    ///
+    /// ```
+    /// let mut chip8 = chip8_emu::Chip8::headless();
+    /// // LD V0, 0x03; LD I, 0x300; LD [I], V0; JMP 0x202, writing 3 lives over and over
+    /// chip8.load_rom_bytes(&[0x60, 0x03, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x02]).unwrap();
+    /// chip8.add_poke("0x300=0x63".parse().unwrap());
+    /// chip8.run_frames(2).unwrap();
+    /// assert_eq!(chip8.cpu().ram().read_byte(0x300), Ok(0x63));
+    /// ```
+    ///
+    pub fn add_poke(&mut self, poke: Poke) {
+        self.pokes.add(poke);
+        self.cpu.apply_pokes(&self.pokes);
+        self.refresh_watches();
     }
 
-    fn jmp(&mut self, opcode: u16) {
-        self.registers.pc = opcode & 0x0FFF;
+    ///
+    /// Lets go of the byte at `address`, returning whether it was frozen. It keeps its value
+    /// until the game writes it.
+    ///
+    pub fn remove_poke(&mut self, address: u16) -> bool {
+        self.pokes.remove(address)
     }
 
-    fn call(&mut self, opcode: u16) {
-        self.registers.sp.push(self.registers.pc + WORD_SIZE);
-        self.registers.pc = opcode & 0x0FFF;
+    ///
+    /// Writes the poke's byte once, for the game to overwrite as it pleases.
+    ///
+    pub fn poke(&mut self, poke: Poke) {
+        self.cpu.poke(poke);
+        self.refresh_watches();
     }
 
-    fn se(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let val = (opcode & 0x00FF) as u8;
+    pub fn clear_pokes(&mut self) {
+        self.pokes.clear();
+    }
 
-        if self.registers.v[x] == val {
-            self.registers.pc += WORD_SIZE * 2;
-        } else {
-            self.registers.pc += WORD_SIZE;
+    pub const fn pokes(&self) -> &Pokes {
+        &self.pokes
+    }
+
+    ///
+    /// Holds down the hex keys set in `keys` (bit `n` for key `n`) and releases the others. The
+    /// keypad only registers one key at a time, the lowest one held wins.
+    ///
+    pub fn set_keys(&mut self, keys: u16) {
+        self.cpu.release_keys();
+        if keys != 0 {
+            self.cpu.set_key(keys.trailing_zeros() as u8, true);
         }
     }
 
-    fn sne(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let val = (opcode & 0x00FF) as u8;
+    ///
+    /// Returns the return addresses of the pending calls, the innermost one last.
+    ///
+    /// ```
+    /// let mut chip8 = chip8_emu::Chip8::headless();
+    /// // CALL 0x204; JMP 0x202; RET
+    /// chip8.load_rom_bytes(&[0x22, 0x04, 0x12, 0x02, 0x00, 0xEE]).unwrap();
+    /// chip8.step().unwrap();
+    /// assert_eq!(chip8.stack(), [0x202]);
+    /// ```
+    ///
+    pub fn stack(&self) -> &[u16] {
+        self.cpu.registers().stack()
+    }
+
+    ///
+    /// Returns the lines of the status overlay: the registers as `Registers` prints them, the
+    /// stack depth instead of the stack, the effective speed and the given frame rate.
+    ///
+    pub fn status_lines(&self, fps: u32) -> Vec<String> {
+        let mut lines: Vec<_> = self
+            .registers()
+            .lines()
+            .map(|line| line.to_string())
+            .collect();
+        lines.push(format!(
+            "SP {}  IPS {:.0}  FPS {fps}",
+            self.stack().len(),
+            self.stats.instructions_per_second()
+        ));
+        lines
+    }
+
+    ///
+    /// Returns the registers as `Registers` prints them followed by the screen, a `#` for every
+    /// pixel on and a `.` for every pixel off.
+    ///
+    pub fn dump_state(&self) -> String {
+        format!(
+            "{}\n{}",
+            self.registers(),
+            screenshot::ascii_art(self.framebuffer(), self.display.width())
+        )
+    }
+
+    ///
+    /// Saves the machine's state: its registers, RAM, screen and quirks, and the hash of its ROM.
+    /// MegaChip and CHIP-8X have colors and modes on top that a save state doesn't hold.
+    ///
+    pub fn save_state(&self) -> Result<Chip8State, StateError> {
+        self.check_state_support()?;
+        Ok(Chip8State {
+            rom_hash: self.rom_hash.clone(),
+            hires: self.cpu.hires(),
+            quirks: self.cpu.quirks(),
+            registers: *self.cpu.registers(),
+            ram: Box::new(*self.cpu.ram().bytes()),
+            screen: self
+                .framebuffer()
+                .iter()
+                .map(|&pixel| u8::from(pixel != 0))
+                .collect(),
+        })
+    }
 
-        if self.registers.v[x] != val {
-            self.registers.pc += WORD_SIZE * 2;
+    ///
+    /// Puts the machine back in the saved state and shows its screen. The loaded ROM is kept, for
+    /// resets to restart it, whichever ROM the state was saved from.
+    ///
+    pub fn load_state(&mut self, state: &Chip8State) -> Result<(), StateError> {
+        self.check_state_support()?;
+        self.set_hires(state.hires);
+        self.cpu.restore(state.quirks, state.registers, &state.ram);
+        let pixels = self.display.width() * self.display.height();
+        for (n, &pixel) in state.screen.iter().take(pixels).enumerate() {
+            self.display
+                .set_pixel(n % GRID_WIDTH, n / GRID_WIDTH, pixel);
+        }
+        if self.last_outcome.sound_on {
+            self.set_sound(false);
+        }
+        self.last_outcome = StepOutcome::default();
+        self.stopped_at = None;
+        self.temporary_breakpoint = None;
+        self.pc_history.clear();
+        self.refresh_watches();
+        self.emit_frame();
+        Ok(())
+    }
+
+    fn check_state_support(&self) -> Result<(), StateError> {
+        if self.cpu.megachip() {
+            Err(StateError::Unsupported("MegaChip"))
+        } else if self.cpu.chip8x() {
+            Err(StateError::Unsupported("CHIP-8X"))
         } else {
-            self.registers.pc += WORD_SIZE;
+            Ok(())
         }
     }
 
-    fn ser(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    ///
+    /// Returns the `rows` instructions of the disassembly pane, following the PC.
+    ///
+    pub fn disassembly_pane(&self, rows: u16) -> Vec<ListingLine> {
+        disasm::pane(
+            self.cpu.ram(),
+            self.pc(),
+            &self.breakpoint_addresses(),
+            rows,
+        )
+    }
 
-        if self.registers.v[x] == self.registers.v[y] {
-            self.registers.pc += WORD_SIZE * 2;
-        } else {
-            self.registers.pc += WORD_SIZE;
+    ///
+    /// Returns what the debug panels show, the registers with the given frame rate as the status
+    /// overlay has them.
+    ///
+    #[cfg(feature = "egui")]
+    pub fn debug_view(&self, fps: u32) -> DebugView {
+        let start = usize::from(self.memory_view);
+        let ram = self.cpu.ram();
+        DebugView {
+            registers: self.status_lines(fps),
+            memory_start: self.memory_view,
+            memory: (start..start + DEBUG_MEMORY_BYTES)
+                .map(|address| ram.read_byte(address).unwrap_or_default())
+                .collect(),
+            disassembly: self.disassembly_pane(PANE_ROWS),
+            breakpoints: self.breakpoints.keys().copied().collect(),
+            paused: self.paused,
         }
     }
 
-    fn ld(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    ///
+    /// Moves the debug memory panel by `rows` of its rows, keeping it within the memory.
+    ///
+    #[cfg(feature = "egui")]
+    pub fn scroll_memory_view(&mut self, rows: i32) {
+        let offset = rows.saturating_mul((MEMORY_COLUMNS as u32).cast_signed());
+        self.memory_view = memory_row(i32::from(self.memory_view).saturating_add(offset));
+    }
+
+    ///
+    /// Returns the last instructions executed as `(pc, opcode)`, oldest first, including the one
+    /// that failed if the last step did. The builder sets how many are kept.
+    ///
+    pub fn pc_history(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.pc_history.iter()
+    }
+
+    ///
+    /// Returns how often each instruction and address was executed, if the builder enabled
+    /// profiling.
+    ///
+    pub fn profile(&self) -> Option<&Profile> {
+        self.instruments.as_ref()?.profile.as_ref()
+    }
+
+    ///
+    /// Returns the `top` hottest instructions and addresses of the profile, each address with the
+    /// instruction now found there.
+    ///
+    pub fn profile_report(&self, top: usize) -> Option<String> {
+        let ram = self.cpu.ram();
+        self.profile()
+            .map(|profile| profile.report(top, |address| disasm::instruction_at(ram, address)))
+    }
 
-        self.registers.v[x] = (opcode & 0x00FF) as u8;
+    ///
+    /// Returns which addresses and instructions were executed, if the builder enabled coverage.
+    ///
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.instruments.as_ref()?.coverage.as_ref()
+    }
 
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Returns what was written since the reset and how many reads of what wasn't were reported,
+    /// if the builder enabled detecting them.
+    ///
+    pub fn uninit(&self) -> Option<&UninitDetector> {
+        self.instruments.as_ref()?.uninit.as_ref()
     }
 
-    fn add(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let val = (opcode & 0x00FF) as u8;
+    ///
+    /// Returns the faults the machine skipped over, if the builder's policy skips any.
+    ///
+    pub const fn faults(&self) -> Option<&FaultLog> {
+        self.faults.as_ref()
+    }
 
-        self.registers.v[x] = self.registers.v[x].wrapping_add(val);
+    ///
+    /// Sums the coverage of the loaded ROM up, e.g. `312/840 ROM bytes executed, 14 opcodes never
+    /// used`, and writes it as JSON to `json`.
+    ///
+    pub fn coverage_report(&self, json: &mut String) -> Option<String> {
+        let coverage = self.coverage()?;
+        let start = self.cpu.load_address();
+        let len = self.rom.as_ref().map_or(0, Vec::len);
+        coverage.write_json(start, len, json);
+        Some(coverage.summary(start, len))
+    }
 
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Returns the calls that led to the current instruction, one frame per line, innermost
+    /// first: the PC as `#0 0x020E`, then every pending call as
+    /// `#1 0x020C called from 0x0206 (CALL #0x20C)`.
+    ///
+    pub fn backtrace(&self) -> String {
+        let mut backtrace = format!("#0 0x{:04X}\n", self.pc());
+        for (n, &return_address) in self.stack().iter().rev().enumerate() {
+            let call_site = return_address.wrapping_sub(WORD_SIZE);
+            let ram = self.cpu.ram();
+            let byte = |address: u16| ram.read_byte(usize::from(address)).unwrap_or_default();
+            let opcode = u16::from_be_bytes([byte(call_site), byte(call_site + 1)]);
+            let _ = writeln!(
+                backtrace,
+                "#{} 0x{:04X} called from 0x{call_site:04X} ({})",
+                n + 1,
+                opcode & 0x0FFF,
+                disasm::disassemble(call_site, opcode)
+            );
+        }
+        backtrace
     }
 
-    fn ldr(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    ///
+    /// Returns the screen's pixels, one byte (0 or 1) per pixel, row after row. In MegaChip's mega
+    /// mode every byte is the pixel's value in the `palette`, 256 to a row.
+    ///
+    /// ```
+    /// use chip8_emu::emu::io::GRID_WIDTH;
+    ///
+    /// let mut chip8 = chip8_emu::Chip8::headless();
+    /// chip8.load_demo().unwrap();
+    /// for _ in 0..100 {
+    ///     chip8.step().unwrap();
+    /// }
+    /// let lit = chip8.framebuffer().iter().filter(|&&pixel| pixel == 1).count();
+    /// assert!(lit > 0);
+    /// assert_eq!(chip8.framebuffer_rows().count(), 32);
+    /// assert!(chip8.framebuffer_rows().all(|row| row.len() == GRID_WIDTH));
+    /// ```
+    ///
+    pub fn framebuffer(&self) -> &[u8] {
+        self.display.framebuffer()
+    }
 
-        self.registers.v[x] = self.registers.v[y];
+    pub fn framebuffer_rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.display.framebuffer().chunks(self.display.width())
+    }
 
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Draws the text into the screen in the overlay font and shows it, for frontends putting up a
+    /// splash screen without running a ROM. Loading a ROM blanks it again.
+    ///
+    /// ```
+    /// let mut chip8 = chip8_emu::Chip8::headless();
+    /// chip8.draw_text(2, 2, "DROP A ROM HERE");
+    /// chip8.invert_region(0, 0, 64, 9);
+    /// assert_eq!(chip8.framebuffer()[0], 1);
+    /// ```
+    ///
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str) {
+        self.display.draw_text(x, y, text);
+        self.present();
     }
 
-    fn or(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    ///
+    /// Sets the pixels of the `width` × `height` rectangle at `(x, y)` to `value` and shows the
+    /// screen.
+    ///
+    pub fn fill_region(&mut self, x: usize, y: usize, width: usize, height: usize, value: u8) {
+        self.display.fill_region(x, y, width, height, value);
+        self.present();
+    }
 
-        self.registers.v[x] |= self.registers.v[y];
+    ///
+    /// Flips the pixels of the `width` × `height` rectangle at `(x, y)` and shows the screen.
+    ///
+    pub fn invert_region(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        self.display.invert_region(x, y, width, height);
+        self.present();
+    }
 
-        self.registers.pc += WORD_SIZE;
+    #[cfg(feature = "debug")]
+    pub fn set_v(&mut self, x: u8, value: u8) {
+        self.cpu.registers_mut().v[usize::from(x & 0xF)] = value;
+        self.refresh_watches();
     }
 
-    fn and(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    #[cfg(feature = "debug")]
+    pub fn set_pc(&mut self, pc: u16) {
+        self.cpu.registers_mut().pc = pc;
+    }
 
-        self.registers.v[x] &= self.registers.v[y];
+    #[cfg(feature = "debug")]
+    pub fn set_i(&mut self, i: u16) {
+        self.cpu.registers_mut().i = i;
+    }
 
-        self.registers.pc += WORD_SIZE;
+    #[cfg(feature = "debug")]
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.cpu.registers_mut().dt = value;
     }
 
-    fn xor(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    #[cfg(feature = "debug")]
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.cpu.registers_mut().st = value;
+    }
 
-        self.registers.v[x] ^= self.registers.v[y];
+    #[cfg(feature = "debug")]
+    pub fn write_byte(&mut self, address: u16, value: u8) -> Result<(), Chip8Error> {
+        self.cpu.ram_mut().write_byte(usize::from(address), value)?;
+        self.refresh_watches();
+        Ok(())
+    }
 
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Fetches, decodes and executes exactly one instruction, for frontends driving the emulator
+    /// from their own loop. Unlike `run`, it ignores pausing and the budget.
+    ///
+    pub fn step(&mut self) -> Result<StepOutcome, Chip8Error> {
+        let ticks = self.clock.as_mut().map_or(0, |clock| clock.ticks());
+        self.tick(ticks);
+        self.execute_step()
     }
 
-    fn addr(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    ///
+    /// Executes exactly `cycles` instructions, as that many calls to `step` would.
+    ///
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<RunSummary, Chip8Error> {
+        let mut summary = RunSummary::default();
+        for _ in 0..cycles {
+            summary.merge(self.step()?.into());
+        }
+        Ok(summary)
+    }
 
-        let sum = self.registers.v[x] as u16 + self.registers.v[y] as u16;
-        self.registers.v[x] = sum as u8;
+    ///
+    /// Runs one 60 Hz frame: the instructions the speed allots to it, then one tick of the timers.
+    /// The frame stands in for the clock, so the time spent between frames doesn't tick them again.
+    ///
+    /// ```
+    /// let mut chip8 = chip8_emu::Chip8::headless();
+    /// // LD V0, 0x3C; LD DT, V0; JMP 0x204
+    /// chip8.load_rom_bytes(&[0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04]).unwrap();
+    /// let summary = chip8.run_frames(60).unwrap();
+    /// assert_eq!(summary.cycles, 450);
+    /// assert_eq!(chip8.delay_timer(), 0);
+    /// ```
+    ///
+    pub fn run_frame(&mut self) -> Result<RunSummary, Chip8Error> {
+        let cycles = self.frame_cycles();
+        self.run_frame_cycles(cycles)
+    }
 
-        if sum > 0xFF {
-            self.registers.v[0xF] = 1;
-        } else {
-            self.registers.v[0xF] = 0;
+    pub fn run_frames(&mut self, frames: u32) -> Result<RunSummary, Chip8Error> {
+        let mut summary = RunSummary::default();
+        for _ in 0..frames {
+            summary.merge(self.run_frame()?);
+            if summary.stopped() {
+                break;
+            }
         }
+        Ok(summary)
+    }
 
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Makes `run`, `run_frame` and `run_frames` stop right before fetching the instruction at the
+    /// breakpoint's address, when its condition holds if it has one. Breakpoints are kept across
+    /// resets and ROM loads, and replace the one already set at the same address.
+    ///
+    /// ```
+    /// use chip8_emu::Breakpoint;
+    ///
+    /// let mut chip8 = chip8_emu::Chip8::headless();
+    /// // LD V0, 0x01; ADD V0, 0x01; JMP 0x202
+    /// chip8.load_rom_bytes(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02]).unwrap();
+    /// chip8.add_breakpoint("0x202 if v0 > 2".parse::<Breakpoint>().unwrap());
+    /// let summary = chip8.run_frame().unwrap();
+    /// assert_eq!(summary.breakpoint, Some(0x202));
+    /// assert_eq!(chip8.v(0), 3);
+    /// ```
+    ///
+    pub fn add_breakpoint(&mut self, breakpoint: impl Into<Breakpoint>) {
+        let breakpoint = breakpoint.into();
+        self.breakpoints
+            .insert(breakpoint.address, breakpoint.condition);
     }
 
-    fn sub(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    ///
+    /// Removes the breakpoint at `address`, returning whether there was one.
+    ///
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
 
-        let diff = self.registers.v[x].wrapping_sub(self.registers.v[y]) as i8;
-        self.registers.v[x] = diff as u8;
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
 
-        if diff < 0 {
-            self.registers.v[0xF] = 1;
-        } else {
-            self.registers.v[0xF] = 0;
+    pub fn breakpoint_addresses(&self) -> BTreeSet<u16> {
+        self.breakpoints.keys().copied().collect()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = Breakpoint> + '_ {
+        self.breakpoints
+            .iter()
+            .map(|(&address, condition)| Breakpoint {
+                address,
+                condition: condition.clone(),
+            })
+    }
+
+    ///
+    /// Stops the run before any instruction of the kind, along with the breakpoints at addresses,
+    /// e.g. to pause on the next `DRW`.
+    ///
+    pub fn add_break_on(&mut self, instruction: Instruction) {
+        self.break_on[instruction as usize] = true;
+    }
+
+    ///
+    /// Stops breaking on the kind of instruction, returning whether the run did.
+    ///
+    pub fn remove_break_on(&mut self, instruction: Instruction) -> bool {
+        std::mem::replace(&mut self.break_on[instruction as usize], false)
+    }
+
+    pub fn break_on(&self) -> impl Iterator<Item = Instruction> + '_ {
+        Instruction::ALL
+            .into_iter()
+            .filter(|&instruction| self.break_on[instruction as usize])
+    }
+
+    ///
+    /// Returns the kind of the next instruction if the run breaks on it.
+    ///
+    fn break_on_next(&self) -> Option<Instruction> {
+        let instruction = self
+            .cpu
+            .fetch()
+            .ok()
+            .and_then(|opcode| self.cpu.decode_opcode(opcode))?;
+        self.break_on[instruction as usize].then_some(instruction)
+    }
+
+    ///
+    /// Makes the run stop once at `address` when the stack is `stack_depth` calls deep, which is
+    /// how stepping over or out of a call finds its way back. Any stop removes it.
+    ///
+    pub fn set_temporary_breakpoint(&mut self, address: u16, stack_depth: usize) {
+        self.temporary_breakpoint = Some((address, stack_depth));
+    }
+
+    ///
+    /// Makes `run`, `run_frame` and `run_frames` stop right after an instruction changes the
+    /// watched register or byte, which `step` reports as well.
+    ///
+    pub fn add_watch(&mut self, watch: Watch) {
+        if self.watches.iter().all(|&(watched, _)| watched != watch) {
+            let value = watch.value(self.cpu.registers(), self.cpu.ram());
+            self.watches.push((watch, value));
         }
+    }
 
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Removes the watch, returning whether it was set.
+    ///
+    pub fn remove_watch(&mut self, watch: Watch) -> bool {
+        let count = self.watches.len();
+        self.watches.retain(|&(watched, _)| watched != watch);
+        self.watches.len() != count
     }
 
-    fn shr(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+    }
 
-        self.registers.v[0xF] = self.registers.v[x] & 0x1;
-        self.registers.v[x] >>= 1;
+    pub fn watches(&self) -> impl Iterator<Item = Watch> + '_ {
+        self.watches.iter().map(|&(watch, _)| watch)
+    }
 
-        self.registers.pc += WORD_SIZE;
+    fn refresh_watches(&mut self) {
+        for (watch, value) in &mut self.watches {
+            *value = watch.value(self.cpu.registers(), self.cpu.ram());
+        }
     }
 
-    fn subn(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    ///
+    /// Returns the first watched value the instruction at `pc` changed. The others are taken in
+    /// too, so they aren't blamed on the next instruction.
+    ///
+    fn changed_watch(&mut self, pc: u16, opcode: u16) -> Option<WatchHit> {
+        let mut hit = None;
+        for (watch, value) in &mut self.watches {
+            let new = watch.value(self.cpu.registers(), self.cpu.ram());
+            if new != *value {
+                hit = hit.or(Some(WatchHit {
+                    watch: *watch,
+                    old: *value,
+                    new,
+                    pc,
+                    opcode,
+                }));
+                *value = new;
+            }
+        }
+        hit
+    }
 
-        let diff = self.registers.v[y] as i8 - self.registers.v[x] as i8;
-        self.registers.v[x] = diff as u8;
+    ///
+    /// Returns whether the next fetch hits a breakpoint. The one the run last stopped on is passed
+    /// over, so resuming executes its instruction instead of stopping again.
+    ///
+    fn at_breakpoint(&mut self) -> bool {
+        let breaks_on_instructions = self.break_on.contains(&true);
+        if self.breakpoints.is_empty()
+            && self.temporary_breakpoint.is_none()
+            && !breaks_on_instructions
+        {
+            return false;
+        }
 
-        if diff < 0 {
-            self.registers.v[0xF] = 1;
-        } else {
-            self.registers.v[0xF] = 0;
+        let pc = self.pc();
+        if self.stopped_at == Some(pc) {
+            return false;
+        }
+        // The condition is only evaluated once the address matches
+        let hit = self.temporary_breakpoint == Some((pc, self.stack().len()))
+            || self.breakpoints.get(&pc).is_some_and(|condition| {
+                condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.holds(self.cpu.registers()))
+            })
+            || (breaks_on_instructions && self.break_on_next().is_some());
+        if hit {
+            self.stopped_at = Some(pc);
+            self.temporary_breakpoint = None;
         }
+        hit
+    }
 
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Returns how many instructions the next frame executes, spreading the remainder of the speed
+    /// over the frames (450 IPS alternates between 7 and 8 instructions).
+    ///
+    fn frame_cycles(&mut self) -> u64 {
+        self.frame_credit += self.ips;
+        let cycles = self.frame_credit / FRAMES_PER_SECOND;
+        self.frame_credit %= FRAMES_PER_SECOND;
+        u64::from(cycles)
     }
 
-    fn shl(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    fn run_frame_cycles(&mut self, cycles: u64) -> Result<RunSummary, Chip8Error> {
+        #[cfg(feature = "profiling")]
+        let span = tracing::trace_span!(
+            "instructions",
+            instructions = tracing::field::Empty,
+            draws = tracing::field::Empty
+        )
+        .entered();
+        #[cfg(feature = "profiling")]
+        let mut draws = 0_u64;
+        for (hex_key, down) in self.turbo.frame() {
+            self.cpu.set_key(hex_key, down);
+        }
+        if let Some(clock) = &mut self.clock {
+            clock.ticks();
+        }
+        #[cfg(feature = "native")]
+        let frame_start = self.profile().is_some().then(time::Instant::now);
+
+        let mut summary = RunSummary::default();
+        for _ in 0..cycles {
+            if self.at_breakpoint() {
+                summary.breakpoint = Some(self.pc());
+                summary.break_on = self.break_on_next();
+                break;
+            }
+            let outcome = self.execute_step()?;
+            #[cfg(feature = "profiling")]
+            {
+                draws += u64::from(outcome.display_changed);
+            }
+            summary.merge(outcome.into());
+            if summary.watch.is_some() {
+                self.temporary_breakpoint = None;
+                break;
+            }
+        }
+        #[cfg(feature = "profiling")]
+        span.record("instructions", summary.cycles)
+            .record("draws", draws);
+        #[cfg(feature = "native")]
+        let profile = self
+            .instruments
+            .as_mut()
+            .and_then(|instruments| instruments.profile.as_mut());
+        #[cfg(feature = "native")]
+        if let (Some(profile), Some(frame_start)) = (profile, frame_start) {
+            profile.sample(frame_start.elapsed());
+        }
+        if !self.pokes.is_empty() {
+            // After the game's writes, and not blamed on them by the watches
+            self.cpu.apply_pokes(&self.pokes);
+            self.refresh_watches();
+        }
+        self.tick(1);
+        self.display.age_pixels();
+        summary.sound_on = self.sound_timer() > 0;
+        Ok(summary)
+    }
 
-        self.registers.v[0xF] = (self.registers.v[x] & 0x80) >> 7;
-        self.registers.v[x] <<= 1;
+    fn execute_step(&mut self) -> Result<StepOutcome, Chip8Error> {
+        self.execute_instruction()
+            .or_else(|err| self.recover_fault(err))
+    }
 
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Skips the instruction that failed with `err` when the policy skips its kind of fault,
+    /// noting the fault in the log. Gives `err` back when it doesn't, or the ROM made more faults
+    /// than the log allows.
+    ///
+    fn recover_fault(&mut self, err: Chip8Error) -> Result<StepOutcome, Chip8Error> {
+        let Some((kind, pc)) =
+            FaultKind::of(&err).filter(|&(kind, _)| self.fault_policy.on(kind) == OnFault::Skip)
+        else {
+            return Err(err);
+        };
+        let Some(faults) = &mut self.faults else {
+            return Err(err);
+        };
+        match faults.record(kind, pc) {
+            Report::Full => log::warn!("{err} Skipping it."),
+            Report::Silent => {}
+            Report::Suppressed(count) => {
+                log::warn!("Suppressed {count} further warning(s) of faults.");
+            }
+        }
+        if let (true, Some(max)) = (faults.exceeded(), faults.max()) {
+            log::error!("The ROM made more than {max} fault(s), stopping.");
+            return Err(err);
+        }
+        self.cpu.skip_instruction(pc);
+        self.stats.cycles += 1;
+        Ok(StepOutcome {
+            display_changed: false,
+            cycles: 1,
+            watch: None,
+            ..self.last_outcome
+        })
     }
 
-    fn sner(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    fn execute_instruction(&mut self) -> Result<StepOutcome, Chip8Error> {
+        let opcode = self.cpu.fetch()?;
+        self.pc_history.push(self.pc(), opcode);
+        let instruction = self.cpu.decode_at_pc(opcode)?;
+        let pc = self.pc();
+        self.stopped_at = None;
+        // The trace is formatted for the log only when someone is listening
+        let log_trace = log::log_enabled!(log::Level::Trace);
+        let before = log_trace.then(|| *self.cpu.registers());
+        for hooks in &mut self.hooks {
+            hooks.on_instruction(pc, opcode);
+        }
+        #[cfg(feature = "native")]
+        if let Some(meter) = &mut self.latency {
+            let pressed_key = self.cpu.pressed_key();
+            if let Some(key) =
+                latency::observed_key(instruction, opcode, self.cpu.registers(), pressed_key)
+            {
+                meter.observe(key, time::Instant::now());
+            }
+        }
+        #[cfg(feature = "script")]
+        if let Some(script) = self
+            .script
+            .as_mut()
+            .filter(|script| script.wants_instructions())
+        {
+            script.on_instruction(pc, opcode);
+        }
+        if let Some(instruments) = &mut self.instruments {
+            instruments.before(pc, opcode, instruction, &self.cpu);
+        }
 
-        if self.registers.v[x] != self.registers.v[y] {
-            self.registers.pc += WORD_SIZE * 2;
-        } else {
-            self.registers.pc += WORD_SIZE;
+        self.cpu.execute(
+            instruction,
+            opcode,
+            self.display.as_mut(),
+            self.rnd_engine.as_mut(),
+        )?;
+        self.stats.cycles += 1;
+        if let Some(instruments) = &mut self.instruments {
+            let registers = self.cpu.registers();
+            instruments.after(self.stats.cycles - 1, pc, opcode, instruction, registers);
         }
+        if let Some(before) = &before {
+            let record = TraceRecord {
+                cycle: self.stats.cycles - 1,
+                pc,
+                opcode,
+                before,
+                after: self.cpu.registers(),
+            };
+            let mut line = String::new();
+            record.write_text(&mut line, TraceRegisters::Changed);
+            log::trace!("{line}");
+        }
+
+        let outcome = StepOutcome {
+            display_changed: matches!(instruction, Instruction::CLS | Instruction::DRW),
+            sound_on: self.sound_timer() > 0,
+            waiting_for_key: instruction == Instruction::LDK && self.pc() == pc,
+            cycles: 1,
+            watch: if self.watches.is_empty() {
+                None
+            } else {
+                self.changed_watch(pc, opcode)
+            },
+        };
+        self.fire_hooks(outcome, opcode);
+        self.last_outcome = outcome;
+        Ok(outcome)
     }
 
-    fn ldri(&mut self, opcode: u16) {
-        self.registers.i = opcode & 0x0FFF;
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Fires the hooks for what changed since the previous instruction.
+    ///
+    fn fire_hooks(&mut self, outcome: StepOutcome, opcode: u16) {
+        if outcome.display_changed {
+            self.emit_frame();
+        }
+
+        if outcome.sound_on != self.last_outcome.sound_on {
+            self.set_sound(outcome.sound_on);
+        }
+
+        if outcome.waiting_for_key && !self.last_outcome.waiting_for_key {
+            let register = ((opcode & 0x0F00) >> 8) as u8;
+            for hooks in &mut self.hooks {
+                hooks.on_key_wait(register);
+            }
+        }
     }
 
-    fn jmpv0(&mut self, opcode: u16) {
-        self.registers.pc = self.registers.v[0] as u16 + (opcode & 0x0FFF);
+    pub const fn decode(opcode: u16) -> Option<Instruction> {
+        Cpu::decode(opcode)
     }
+}
 
-    fn rnd(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let val = (opcode & 0x0FF) as u8;
+///
+/// The `InstantClock` struct represents the wall clock, ticking every 16 ms.
+///
+#[cfg(feature = "native")]
+struct InstantClock {
+    last_tick: time::Instant,
+}
 
-        let num = self.rnd_engine.gen_range(0..0xFF) as u8;
-        self.registers.v[x] = num & val;
+#[cfg(feature = "native")]
+impl InstantClock {
+    fn new() -> Self {
+        Self {
+            last_tick: time::Instant::now(),
+        }
+    }
+}
 
-        self.registers.pc += WORD_SIZE;
+#[cfg(feature = "native")]
+impl Clock for InstantClock {
+    fn ticks(&mut self) -> u32 {
+        let ticks = self.last_tick.elapsed().as_millis() / 16;
+        self.last_tick += time::Duration::from_millis(ticks as u64 * 16);
+        u32::try_from(ticks).unwrap_or(u32::MAX)
     }
+}
 
-    fn drw(&mut self, opcode: u16) -> Result<(), Box<dyn Error>> {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
-        let n = (opcode & 0x000F) as usize;
+///
+/// The parts of the emulator that need an OS: files, sleeping and the wall clock.
+///
+#[cfg(feature = "native")]
+impl Chip8 {
+    ///
+    /// Returns the input latency measured so far, if the builder asked for it.
+    ///
+    pub const fn latency(&self) -> Option<&LatencyMeter> {
+        self.latency.as_ref()
+    }
 
-        let x_pos = self.registers.v[x] as usize;
-        let y_pos = self.registers.v[y] as usize;
+    ///
+    /// Makes the run loop stop on its own once it executed `max_cycles` instructions or ran for
+    /// `max_run_time`, time spent paused excluded.
+    ///
+    pub fn set_budget(&mut self, max_cycles: Option<u64>, max_run_time: Option<time::Duration>) {
+        self.max_cycles = max_cycles;
+        self.max_run_time = max_run_time;
+    }
 
-        self.registers.v[0xF] = 0;
+    ///
+    /// Makes the run loop stop at the next frame once `flag` is set, as a closed window would.
+    ///
+    pub fn set_stop_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.stop_requested = Some(flag);
+    }
 
-        for byte in 0..n {
-            let sprite_byte = self.ram.read_byte(self.registers.i as usize + byte)?;
+    pub fn load_rom(&mut self, path: &str, zip_entry: Option<&str>) -> Result<(), Chip8Error> {
+        let buf = rom::read_rom(path, zip_entry)?;
+        self.load_rom_from(path, &buf)
+    }
 
-            for bit in 0..8usize {
-                let sprite_pixel = (sprite_byte >> (7 - bit)) & 1;
-                let screen_x = (x_pos + bit) % GRID_WIDTH;
-                let screen_y = (y_pos + byte) % GRID_HEIGHT;
+    ///
+    /// Loads the ROM at `path` as `load_rom` does, with the IPS patch applied to it first, and
+    /// returns how many records the patch applied. The ROM's hash is the patched one's.
+    ///
+    pub fn load_patched_rom(
+        &mut self,
+        path: &str,
+        zip_entry: Option<&str>,
+        ips_patch: &[u8],
+    ) -> Result<usize, Chip8Error> {
+        let buf = rom::read_rom(path, zip_entry)?;
+        let max_size = RAM_SIZE - usize::from(self.cpu.load_address());
+        let patched = ips::apply(&buf, ips_patch, max_size).map_err(RomError::InvalidPatch)?;
+        self.load_rom_from(path, &patched.rom)?;
+        log::info!(
+            "Applied {} IPS record(s) to the ROM, its SHA-1 going from {} to {}.",
+            patched.records,
+            sha1_smol::Sha1::from(&buf).digest(),
+            self.rom_hash.as_deref().unwrap_or_default()
+        );
+        Ok(patched.records)
+    }
 
-                let screen_pixel = self.display.get_pixel(screen_x, screen_y);
+    fn load_rom_from(&mut self, path: &str, bytes: &[u8]) -> Result<(), Chip8Error> {
+        self.load_rom_bytes(bytes)?;
+        self.rom_path = Some(path.to_string());
 
-                // XOR sprite pixel and screen pixel, then update the display
-                let new_pixel = sprite_pixel ^ screen_pixel;
-                self.display.set_pixel(screen_x, screen_y, new_pixel);
+        let name = Path::new(path)
+            .file_name()
+            .map_or_else(|| path.into(), |name| name.to_string_lossy());
+        self.set_title(&name);
+        Ok(())
+    }
+
+    ///
+    /// Saves the screen as a PNG, every pixel scaled up to a `scale`×`scale` square, in black and
+    /// white or the palette's colors in MegaChip's mega mode.
+    ///
+    pub fn screenshot(&self, path: impl AsRef<Path>, scale: u32) -> io::Result<()> {
+        let png = self.palette().map_or_else(
+            || screenshot::encode_png(self.framebuffer(), scale),
+            |palette| screenshot::encode_mega_png(self.framebuffer(), scale, palette),
+        );
+        fs::write(path, png)
+    }
+
+    ///
+    /// Draws the screen of a window without a ROM: the emulator's name on an inverted bar, and
+    /// where to put one.
+    ///
+    fn draw_drop_screen(&mut self) {
+        self.display.clear();
+        self.draw_text(14, 3, "CHIP8-EMU");
+        self.invert_region(0, 0, self.display.width(), 11);
+        self.draw_text(12, 16, "DROP A ROM\n   HERE");
+    }
+
+    pub fn run(&mut self) -> Result<RunOutcome, Chip8Error> {
+        if self.rom_hash.is_none() {
+            self.set_title("drop a ROM here");
+            self.draw_drop_screen();
+        }
+
+        let mut last_tick = time::Instant::now();
+        let mut frame_rate = FrameRate::default();
+        let mut scheduler = Scheduler::new(self.max_catch_up_frames);
+        let mut pacer = Pacer::new(self.spin_threshold);
+        let frame = time::Duration::from_secs(1) / FRAMES_PER_SECOND;
+        let mut was_running = false;
+        loop {
+            #[cfg(feature = "profiling")]
+            let _span = tracing::trace_span!("frame").entered();
+            let frame_start = time::Instant::now();
+            let cycles = self.stats.cycles;
+            if let Some(outcome) = self.handle_inputs() {
+                return Ok(outcome);
+            }
+
+            let running = self.rom_hash.is_some() && !self.paused;
+            let now = time::Instant::now();
+            let elapsed = now - last_tick;
+            if running {
+                self.stats.run_time += elapsed;
+                if was_running {
+                    self.stats.time_frame(elapsed, frame);
+                }
+            }
+            was_running = running;
+            last_tick = now;
+
+            if self.budget_exhausted() {
+                return Ok(RunOutcome::BudgetExhausted);
+            }
 
-                // If screen pixel was on and now is off, set VF to 1
-                if sprite_pixel == 1 && screen_pixel == 1 {
-                    self.registers.v[0xF] = 1;
+            if running {
+                let tick = scheduler.advance(elapsed);
+                if tick.dropped > 0 {
+                    self.stats.dropped_frames += u64::from(tick.dropped);
+                    if tick.fell_behind {
+                        log::warn!(
+                            "Running behind, dropped {} frame(s) of emulated time.",
+                            tick.dropped
+                        );
+                    }
+                }
+                for _ in 0..tick.frames {
+                    self.run_scheduled_frame()?;
+                    if self.paused || self.budget_exhausted() {
+                        break;
+                    }
                 }
+            } else {
+                scheduler.reset();
+            }
+            // Pixels fade as they age, even when the screen doesn't change
+            if self.overlay
+                || self.pane
+                || self.debug_view_shown()
+                || self.frame_graph.is_some()
+                || self.draw_age
+                || !self.hud.is_empty()
+            {
+                self.show_views(frame_rate.fps());
+            }
+            self.end_frame();
+            if self.run_state() == RunState::Running {
+                pacer.wait_until(&mut SystemClock, frame_start + scheduler.until_next_frame());
+            } else {
+                self.wait_for_input(frame_start);
+            }
+            frame_rate.frame(frame_start.elapsed());
+            if let Some(times) = &mut self.frame_graph {
+                times.push(frame_start.elapsed(), self.stats.cycles - cycles);
             }
         }
+    }
 
-        self.display.draw();
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Emulates a frame of the run loop, pausing on the breakpoints and watches it hits.
+    ///
+    fn run_scheduled_frame(&mut self) -> Result<(), Chip8Error> {
+        let mut cycles = self.frame_cycles();
+        if let Some(max_cycles) = self.max_cycles {
+            cycles = cycles.min(max_cycles.saturating_sub(self.stats.cycles));
+        }
+        let summary = self.run_frame_cycles(cycles)?;
+        if let Some(address) = summary.breakpoint {
+            self.paused = true;
+            match summary.break_on {
+                Some(instruction) => log::info!(
+                    "Break on {instruction:?} at 0x{address:04X}, P resumes and N steps."
+                ),
+                None => {
+                    log::info!("Breakpoint hit at 0x{address:04X}, P resumes and N steps.");
+                }
+            }
+            self.print_state();
+        } else if let Some(hit) = summary.watch {
+            self.paused = true;
+            log::info!("Watch: {hit}, P resumes and N steps.");
+            self.print_state();
+        }
+        #[cfg(feature = "script")]
+        self.run_script();
         Ok(())
     }
 
-    fn skp(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let key = self.registers.v[x];
+    ///
+    /// When set, a ROM dropped onto the window while another one is running is only loaded if
+    /// the emulator is paused.
+    ///
+    pub fn set_drop_requires_pause(&mut self, drop_requires_pause: bool) {
+        self.drop_requires_pause = drop_requires_pause;
+    }
+
+    pub const fn is_paused(&self) -> bool {
+        self.paused
+    }
 
-        if self.keyboard.is_key_pressed(key) {
-            self.keyboard.release_key();
-            self.registers.pc += WORD_SIZE * 2;
+    pub const fn run_state(&self) -> RunState {
+        if self.paused || self.rom_hash.is_none() {
+            RunState::Paused
+        } else if self.last_outcome.waiting_for_key {
+            RunState::WaitingForKey
         } else {
-            self.registers.pc += WORD_SIZE;
+            RunState::Running
         }
     }
 
-    fn sknp(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let key = self.registers.v[x];
+    ///
+    /// Pauses or resumes `run`, as the pause hotkey does.
+    ///
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    ///
+    /// Handles the pending input of the frontend and commands of the control server, returning how
+    /// the run ends if it ends it. `run` calls it once per frame, loops of their own have to as well.
+    ///
+    pub fn handle_inputs(&mut self) -> Option<RunOutcome> {
+        #[cfg(feature = "profiling")]
+        let _span = tracing::trace_span!("poll_input").entered();
+        let stop_requested = self.stop_requested.as_ref();
+        if stop_requested.is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+            return Some(RunOutcome::Interrupted);
+        }
+        while let Some(input) = self
+            .pending_input
+            .take()
+            .or_else(|| self.frontend.poll_input())
+        {
+            match input {
+                Input::Quit => return Some(RunOutcome::Quit),
+                Input::TogglePause => self.paused = !self.paused,
+                Input::Step => self.step_paused(),
+                Input::ToggleOverlay => {
+                    self.overlay = !self.overlay;
+                    if !self.overlay {
+                        self.frontend.set_overlay(&[]);
+                        self.present();
+                    }
+                }
+                Input::TogglePane => {
+                    self.pane = !self.pane;
+                    if !self.pane {
+                        self.frontend.set_pane(&[]);
+                        self.present();
+                    }
+                }
+                #[cfg(feature = "egui")]
+                Input::ToggleDebugView => {
+                    self.debug_view = !self.debug_view;
+                    if !self.debug_view {
+                        self.frontend.set_debug_view(None);
+                        self.present();
+                    }
+                }
+                Input::ToggleFrameGraph => {
+                    if self.frame_graph.take().is_some() {
+                        self.frontend.set_frame_graph(None);
+                        self.present();
+                    } else {
+                        self.frame_graph = Some(FrameTimes::new());
+                    }
+                }
+                Input::ToggleHelp => self.toggle_help(),
+                Input::Reset => self.reset(),
+                Input::ToggleBreakpoint(address) => {
+                    if !self.remove_breakpoint(address) {
+                        self.add_breakpoint(address);
+                    }
+                }
+                #[cfg(feature = "egui")]
+                Input::ScrollMemory(rows) => self.scroll_memory_view(rows),
+                Input::DropRom(path) => self.drop_rom(&path),
+                Input::Browse => return Some(RunOutcome::Browse),
+                Input::SaveProfile => return Some(RunOutcome::SaveProfile),
+                Input::Key {
+                    hex_key,
+                    pressed,
+                    at,
+                } => {
+                    self.set_key(hex_key, pressed);
+                    if let Some(meter) = &mut self.latency {
+                        meter.key(hex_key, pressed, at);
+                    }
+                }
+                Input::SecondKey { hex_key, pressed } => self.set_second_key(hex_key, pressed),
+                Input::ReleaseKeys => {
+                    self.turbo.release();
+                    self.cpu.release_keys();
+                    if let Some(meter) = &mut self.latency {
+                        meter.release_keys();
+                    }
+                }
+            }
+        }
+        while let Some(request) = self.control.as_ref().and_then(ControlServer::try_recv) {
+            let quit = request.command == Command::Quit;
+            request.answer(self);
+            if quit {
+                return Some(RunOutcome::Quit);
+            }
+        }
+        None
+    }
+
+    ///
+    /// Shows what the frame drew, for frontends only showing the screen once per frame. `run`
+    /// calls it at the end of every frame, loops of their own have to as well.
+    ///
+    pub fn end_frame(&mut self) {
+        #[cfg(feature = "profiling")]
+        let _span = tracing::trace_span!("end_frame").entered();
+        self.frontend.end_frame();
+    }
+
+    ///
+    /// Takes the commands of the control server's clients, once per frame along with the input.
+    ///
+    pub fn set_control_server(&mut self, server: ControlServer) {
+        self.control = Some(server);
+    }
+
+    ///
+    /// Runs the script between frames, `on_frame` first and `on_instruction` before every
+    /// instruction if it asks for them, replacing any script already attached.
+    ///
+    #[cfg(feature = "script")]
+    pub fn set_script(&mut self, script: ScriptHost) {
+        self.script = Some(script);
+    }
 
-        if !self.keyboard.is_key_pressed(key) {
-            self.registers.pc += WORD_SIZE * 2;
+    ///
+    /// Runs a frame of the script, if one is attached and still running, keeping the lines it
+    /// drew to show over the screen. `run` calls it after every frame.
+    ///
+    #[cfg(feature = "script")]
+    pub fn run_script(&mut self) {
+        let Some(mut script) = self.script.take() else {
+            return;
+        };
+        if script.stopped().is_none() {
+            match script.run_frame(self) {
+                Ok(hud) => self.hud = hud,
+                Err(err) => {
+                    log::error!("The script was stopped. Error => `{err}`");
+                    self.hud.clear();
+                    self.frontend.set_overlay(&[]);
+                }
+            }
+        }
+        self.script = Some(script);
+    }
+
+    ///
+    /// Returns whether the debug panels are shown, which they never are without `egui`.
+    ///
+    #[cfg(feature = "egui")]
+    fn debug_view_shown(&self) -> bool {
+        self.debug_view
+    }
+
+    #[cfg(all(feature = "native", not(feature = "egui")))]
+    #[allow(clippy::unused_self)]
+    fn debug_view_shown(&self) -> bool {
+        false
+    }
+
+    ///
+    /// Shows the status overlay, the disassembly pane, the frame graph and the debug panels that
+    /// are on, redrawing the screen since they change even when it doesn't.
+    ///
+    fn show_views(&mut self, fps: u32) {
+        #[cfg(feature = "egui")]
+        if self.debug_view {
+            self.frontend.set_debug_view(Some(&self.debug_view(fps)));
+        }
+        let mut overlay = if self.overlay {
+            self.status_lines(fps)
+        } else {
+            Vec::new()
+        };
+        overlay.extend_from_slice(&self.hud);
+        if !overlay.is_empty() {
+            self.frontend.set_overlay(&overlay);
+        }
+        if self.pane {
+            self.frontend.set_pane(&self.disassembly_pane(PANE_ROWS));
+        }
+        if let Some(times) = &self.frame_graph {
+            self.frontend.set_frame_graph(Some(&times.graph()));
+        }
+        self.present();
+    }
+
+    ///
+    /// Shows the help, pausing until it's hidden again, or hides it and goes back to running or
+    /// being paused as before.
+    ///
+    fn toggle_help(&mut self) {
+        if let Some(paused) = self.help.take() {
+            self.paused = paused;
         } else {
-            self.keyboard.release_key();
-            self.registers.pc += WORD_SIZE;
+            self.help = Some(self.paused);
+            self.paused = true;
         }
+        self.frontend.set_help(self.help.is_some());
+        self.present();
     }
 
-    fn ldrdt(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    ///
+    /// Executes the next instruction while paused, as the step hotkey does.
+    ///
+    fn step_paused(&mut self) {
+        if !self.paused || self.rom_hash.is_none() {
+            return;
+        }
 
-        self.registers.v[x] = self.get_delay_timer();
-        self.registers.pc += WORD_SIZE;
+        match self.step() {
+            Ok(outcome) => {
+                if let Some(hit) = outcome.watch {
+                    log::info!("Watch: {hit}");
+                }
+                self.print_state();
+            }
+            Err(err) => log::error!("Failed to step. Error => `{err}`"),
+        }
     }
 
-    fn ldk(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    ///
+    /// Prints the registers and the next instruction.
+    ///
+    fn print_state(&self) {
+        log::info!(
+            "{}\n=> {}",
+            self.registers(),
+            disasm::instruction_at(self.cpu.ram(), self.pc())
+        );
+    }
 
-        if let Some(val) = self.keyboard.get_pressed_key() {
-            self.registers.v[x] = val;
+    fn drop_rom(&mut self, path: &str) {
+        if self.rom_hash.is_some() && self.drop_requires_pause && !self.paused {
+            log::warn!("Pause the emulator (P) before dropping a new ROM.");
+            return;
         }
 
-        self.registers.pc += WORD_SIZE;
+        if let Err(err) = self.load_rom(path, None) {
+            log::error!("Failed to load the dropped ROM. Error => `{err}`");
+        }
     }
 
-    fn lddtr(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    fn budget_exhausted(&self) -> bool {
+        self.max_cycles
+            .is_some_and(|max_cycles| self.stats.cycles >= max_cycles)
+            || self
+                .max_run_time
+                .is_some_and(|max_run_time| self.stats.run_time >= max_run_time)
+    }
 
-        self.set_delay_timer(self.registers.v[x]);
+    fn emulate_speed(frame_start: time::Instant) {
+        let frame = time::Duration::from_secs(1) / FRAMES_PER_SECOND;
+        thread::sleep(frame.saturating_sub(frame_start.elapsed()));
+    }
 
-        self.registers.pc += WORD_SIZE;
+    ///
+    /// Waits out the rest of an idle frame on the frontend's input instead of sleeping through
+    /// it, keeping the input that ended the wait for the next frame.
+    ///
+    fn wait_for_input(&mut self, frame_start: time::Instant) {
+        let frame = time::Duration::from_secs(1) / FRAMES_PER_SECOND;
+        let remaining = frame.saturating_sub(frame_start.elapsed());
+        if !remaining.is_zero() {
+            self.pending_input = self.frontend.wait_input(remaining);
+        }
+        Self::emulate_speed(frame_start);
     }
+}
 
-    fn ldrst(&mut self, _opcode: u16) {
-        self.registers.pc += WORD_SIZE;
+#[cfg(test)]
+mod chip8_tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+    #[cfg(feature = "native")]
+    use std::time::{Duration, Instant};
+
+    use super::{Chip8, Chip8Error, Instruction, RunSummary, StepOutcome};
+    #[cfg(feature = "native")]
+    use super::{RunOutcome, RunState};
+    use crate::emu::breakpoint::{Watch, WatchHit};
+    use crate::emu::chip8x::ColorZones;
+    use crate::emu::cpu::Clock;
+    use crate::emu::fault::{FaultKind, Strictness};
+    use crate::emu::io::{GRID_WIDTH, MEGA_GRID_HEIGHT, PIXEL_AGE_FRAMES};
+    use crate::emu::quirks::{Quirks, Variant};
+    use crate::emu::screenshot;
+    use crate::emu::stats::FrameGraph;
+    #[cfg(feature = "egui")]
+    use crate::frontend::DebugView;
+    use crate::frontend::{AudioSink, Frontend, Input, Keypad};
+
+    // LD V0, 0x05; LD V1, 0x07; ADD V0, V1; LD I, 0x300; LD [I], V1; CLS; JMP 0x20C
+    const STEP_ROM: [u8; 14] = [
+        0x60, 0x05, 0x61, 0x07, 0x80, 0x14, 0xA3, 0x00, 0xF1, 0x55, 0x00, 0xE0, 0x12, 0x0C,
+    ];
+
+    #[test]
+    fn profile_counts_a_known_mix() {
+        // LD V0, 0; ADD V0, 1; SE V0, 3; JMP 0x202; JMP 0x208
+        let rom = [0x60, 0x00, 0x70, 0x01, 0x30, 0x03, 0x12, 0x02, 0x12, 0x08];
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .profile(true)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        chip8.run_cycles(12).unwrap();
+
+        let profile = chip8.profile().unwrap();
+        assert_eq!(profile.total(), 12);
+        assert_eq!(profile.instruction_count(Instruction::LD), 1);
+        assert_eq!(profile.instruction_count(Instruction::ADD), 3);
+        assert_eq!(profile.instruction_count(Instruction::SE), 3);
+        // Twice back to the ADD, then 3 times on the final loop
+        assert_eq!(profile.instruction_count(Instruction::JMP), 5);
+        assert_eq!(profile.address_count(0x208), 3);
+        assert_eq!(profile.address_count(0x206), 2);
+        let report = chip8.profile_report(1).unwrap();
+        assert!(
+            report.ends_with("0x0202: 7001  ADD V0, #0x01\n"),
+            "{report}"
+        );
+
+        assert!(Chip8::headless().profile().is_none());
     }
 
-    fn addri(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    #[cfg(feature = "native")]
+    #[test]
+    fn windows_without_a_rom_say_where_to_drop_one() {
+        let mut chip8 = Chip8::headless();
+        chip8.draw_drop_screen();
+        let lit = |chip8: &Chip8, x: usize, y: usize| chip8.framebuffer()[y * GRID_WIDTH + x];
+        // The bar is lit but for the name, the text below it lit on the blank screen
+        assert_eq!(lit(&chip8, 0, 0), 1);
+        assert_eq!(lit(&chip8, 15, 3), 0);
+        assert_eq!(lit(&chip8, 12, 16), 1);
+        assert_eq!(lit(&chip8, 0, 16), 0);
+
+        // Loading a ROM blanks it
+        chip8.load_demo().unwrap();
+        assert!(!chip8.framebuffer().contains(&1));
+    }
 
-        self.registers.i += self.registers.v[x] as u16;
+    #[test]
+    fn headless_demo_draws() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_demo().unwrap();
+        assert!(chip8.display.framebuffer().iter().all(|pixel| *pixel == 0));
 
-        self.registers.pc += WORD_SIZE;
+        for _ in 0..5_000 {
+            chip8.step().unwrap();
+        }
+        assert!(chip8.display.framebuffer().contains(&1));
     }
 
-    fn ldsr(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    #[cfg(feature = "native")]
+    #[test]
+    fn max_cycles_stops_the_run() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_demo().unwrap();
+        chip8.set_budget(Some(50), None);
 
-        // 5 because each sprite has 5 lines
-        self.registers.i = self.registers.v[x] as u16 * 5;
+        assert_eq!(chip8.run().unwrap(), RunOutcome::BudgetExhausted);
+        assert_eq!(chip8.stats().cycles, 50);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn run_seconds_stops_the_run() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_demo().unwrap();
+        chip8.set_budget(None, Some(Duration::from_millis(50)));
 
-        self.registers.pc += WORD_SIZE;
+        assert_eq!(chip8.run().unwrap(), RunOutcome::BudgetExhausted);
+        assert!(chip8.stats().run_time >= Duration::from_millis(50));
+        assert!(chip8.stats().cycles > 0);
     }
 
-    fn ldb(&mut self, opcode: u16) -> Result<(), Box<dyn Error>> {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    #[cfg(feature = "native")]
+    #[test]
+    fn paused_time_does_not_count() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_demo().unwrap();
+        chip8.paused = true;
+        chip8.set_budget(None, Some(Duration::from_millis(1)));
+
+        // Paused, the budget never runs out, so give it a cycle budget as a way out
+        chip8.max_cycles = Some(0);
+        assert_eq!(chip8.run().unwrap(), RunOutcome::BudgetExhausted);
+        assert_eq!(chip8.stats().run_time, Duration::ZERO);
+    }
 
-        self.ram
-            .write_byte(self.registers.i as usize, self.registers.v[x] / 100)?;
-        self.ram
-            .write_byte(self.registers.i as usize + 1, self.registers.v[x] % 100)?;
-        self.ram
-            .write_byte(self.registers.i as usize + 2, self.registers.v[x] % 10)?;
+    #[cfg(feature = "native")]
+    #[test]
+    fn step_matches_run() {
+        let mut stepped = Chip8::headless();
+        stepped.load_rom_bytes(&STEP_ROM).unwrap();
+        for _ in 0..10 {
+            stepped.step().unwrap();
+        }
 
-        self.registers.pc += WORD_SIZE;
-        Ok(())
+        let mut ran = Chip8::headless();
+        ran.load_rom_bytes(&STEP_ROM).unwrap();
+        ran.set_budget(Some(10), None);
+        assert_eq!(ran.run().unwrap(), RunOutcome::BudgetExhausted);
+
+        assert_eq!(stepped.registers().pc, 0x20C);
+        assert_eq!(stepped.registers().v, ran.registers().v);
+        assert_eq!(stepped.registers().v[0], 12);
+        assert_eq!(stepped.registers().i, ran.registers().i);
+        assert_eq!(stepped.registers().pc, ran.registers().pc);
+        assert_eq!(stepped.stats().cycles, ran.stats().cycles);
+        for address in 0x300..0x302 {
+            assert_eq!(
+                stepped.cpu().ram().read_byte(address).unwrap(),
+                ran.cpu().ram().read_byte(address).unwrap()
+            );
+        }
+        assert_eq!(stepped.cpu().ram().read_byte(0x301).unwrap(), 7);
     }
 
-    fn ldrir(&mut self, opcode: u16) -> Result<(), Box<dyn Error>> {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    #[test]
+    fn step_reports_display_changes() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+        for _ in 0..5 {
+            assert_eq!(
+                chip8.step().unwrap(),
+                StepOutcome {
+                    cycles: 1,
+                    ..StepOutcome::default()
+                }
+            );
+        }
+        assert!(chip8.step().unwrap().display_changed);
+        assert!(!chip8.step().unwrap().display_changed);
+    }
+
+    #[test]
+    fn step_waits_for_a_key() {
+        // LD V2, K; JMP 0x202
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&[0xF2, 0x0A, 0x12, 0x02]).unwrap();
+        assert!(chip8.step().unwrap().waiting_for_key);
+        assert!(chip8.step().unwrap().waiting_for_key);
+        assert_eq!(chip8.registers().pc, 0x200);
+
+        chip8.set_key(0x5, true);
+        assert!(!chip8.step().unwrap().waiting_for_key);
+        assert_eq!(chip8.registers().v[2], 0x5);
+        assert_eq!(chip8.registers().pc, 0x202);
+    }
 
-        for i in 0..=x {
-            self.ram
-                .write_byte(self.registers.i as usize + i, self.registers.v[i])?;
+    #[test]
+    fn reset_restarts_the_rom() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.registers().v[0], 12);
+
+        chip8.reset();
+        assert_eq!(chip8.registers().pc, 0x200);
+        assert_eq!(chip8.registers().v[0], 0);
+        assert!(chip8.rom_hash().is_some());
+        for _ in 0..3 {
+            chip8.step().unwrap();
         }
+        assert_eq!(chip8.registers().v[0], 12);
+    }
 
-        self.registers.i += x as u16 + 1;
-        self.registers.pc += WORD_SIZE;
-        Ok(())
+    #[test]
+    fn invalid_roms_keep_the_running_one() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+        chip8.step().unwrap();
+        let hash = chip8.rom_hash().map(str::to_string);
+
+        assert!(matches!(
+            chip8.load_rom_bytes(&[]),
+            Err(Chip8Error::EmptyRom)
+        ));
+        assert!(matches!(
+            chip8.load_rom_bytes(&[0x00; 4_096 - 0x200 + 1]),
+            Err(Chip8Error::RomTooLarge(3_585))
+        ));
+        assert_eq!(chip8.rom_hash().map(str::to_string), hash);
+        assert_eq!(chip8.registers().pc, 0x202);
     }
 
-    fn ldrri(&mut self, opcode: u16) -> Result<(), Box<dyn Error>> {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    #[test]
+    fn shift_quirk() {
+        // LD V1, 0x81; SHR V0, V1
+        let rom = [0x61, 0x81, 0x80, 0x16];
+        for (variant, v0, vf) in [(Variant::Chip8, 0x40, 1), (Variant::SChip, 0x00, 0)] {
+            let mut chip8 = Chip8::builder()
+                .headless(true)
+                .variant(variant)
+                .build()
+                .unwrap();
+            chip8.load_rom_bytes(&rom).unwrap();
+            chip8.step().unwrap();
+            chip8.step().unwrap();
+            assert_eq!((chip8.registers().v[0], chip8.registers().v[0xF]), (v0, vf));
+        }
+    }
 
-        for i in 0..=x {
-            self.registers.v[i] = self.ram.read_byte(self.registers.i as usize + i)?;
+    #[test]
+    fn hires_roms_draw_64_rows() {
+        let mut rom = vec![0; 0xE2];
+        rom[..2].copy_from_slice(&[0x12, 0x60]);
+        // LD V0, 0; LD V1, 40; LD I, 0x2E0; DRW V0, V1, 1; LD V1, 63; DRW V0, V1, 2; CLS
+        rom[0xC0..0xCE].copy_from_slice(&[
+            0x60, 0x00, 0x61, 0x28, 0xA2, 0xE0, 0xD0, 0x11, 0x61, 0x3F, 0xD0, 0x12, 0x02, 0x30,
+        ]);
+        rom[0xE0..].copy_from_slice(&[0xFF, 0xFF]);
+        let lit_rows = |chip8: &Chip8| -> Vec<usize> {
+            chip8
+                .framebuffer_rows()
+                .enumerate()
+                .filter(|(_, row)| row.iter().any(|&pixel| pixel != 0))
+                .map(|(y, _)| y)
+                .collect()
+        };
+
+        // The signature is enough, and starts the program past the header
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert!(chip8.hires());
+        assert_eq!(chip8.registers().pc, 0x2C0);
+        assert_eq!(chip8.framebuffer().len(), GRID_WIDTH * 64);
+        for _ in 0..4 {
+            chip8.step().unwrap();
         }
+        assert_eq!(lit_rows(&chip8), [40]);
+        assert_eq!(
+            chip8.framebuffer_rows().nth(40).unwrap()[..9],
+            [1, 1, 1, 1, 1, 1, 1, 1, 0]
+        );
+        // Sprites wrap around the bottom of the 64 rows
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(lit_rows(&chip8), [0, 40, 63]);
+
+        // It renders as tall as it is
+        let png = screenshot::encode_png(chip8.framebuffer(), 2);
+        assert_eq!(png[16..24], [0, 0, 0, 128, 0, 0, 0, 128]);
+        assert_eq!(
+            chip8
+                .dump_state()
+                .lines()
+                .filter(|line| line.len() == GRID_WIDTH)
+                .count(),
+            64
+        );
+
+        chip8.step().unwrap();
+        assert_eq!(lit_rows(&chip8), Vec::<usize>::new());
+
+        // Other variants run the header as it is
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .variant(Variant::Chip8)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert!(!chip8.hires());
+        assert_eq!(chip8.registers().pc, 0x200);
+        assert_eq!(chip8.framebuffer_rows().count(), 32);
+        // Asking for it runs any ROM on 64 rows, from the load address without the header
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .variant(Variant::HiRes)
+            .build()
+            .unwrap();
+        assert_eq!(chip8.framebuffer_rows().count(), 64);
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+        assert!(chip8.hires());
+        assert_eq!(chip8.registers().pc, 0x200);
+    }
 
-        self.registers.i += x as u16 + 1;
-        self.registers.pc += WORD_SIZE;
-        Ok(())
+    #[test]
+    fn megachip_roms_draw_in_color() {
+        // MEGAON; LD I, 0x210; LDPAL 1; LD I, 0x214; SPRW 2; SPRH 2; DRW V0, V0, 0; JMP 0x20E;
+        // DATA a color and a sprite of 2×2 pixels
+        let rom = [
+            0x00, 0x11, 0xA2, 0x10, 0x02, 0x01, 0xA2, 0x14, 0x03, 0x02, 0x04, 0x02, 0xD0, 0x00,
+            0x12, 0x0E, 0xFF, 0x00, 0x80, 0xFF, 0x01, 0x00, 0x00, 0x01,
+        ];
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .variant(Variant::MegaChip)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert!(chip8.palette().is_none());
+        chip8.run_cycles(7).unwrap();
+
+        assert_eq!(chip8.palette().unwrap().rgb(1), [0x00, 0x80, 0xFF]);
+        assert_eq!(chip8.framebuffer_rows().count(), MEGA_GRID_HEIGHT);
+        let rows: Vec<_> = chip8
+            .framebuffer_rows()
+            .take(2)
+            .map(|row| &row[..3])
+            .collect();
+        assert_eq!(rows, [[1, 0, 0], [0, 1, 0]]);
+        assert!(chip8
+            .dump_state()
+            .contains(&format!("#{}\n", ".".repeat(255))));
+
+        // The ROM starts over on the usual screen
+        chip8.reset();
+        assert!(chip8.palette().is_none());
+        assert_eq!(chip8.framebuffer_rows().count(), 32);
+        assert!(chip8.framebuffer_rows().all(|row| row.len() == GRID_WIDTH));
+
+        // Other variants don't know the instructions
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert!(matches!(
+            chip8.step(),
+            Err(Chip8Error::UnknownOpcode { opcode: 0x0011, .. })
+        ));
     }
 
-    fn set_delay_timer(&mut self, value: u8) {
-        self.delay_timer = time::Instant::now();
-        self.registers.dt = value;
+    #[test]
+    fn detects_reads_of_what_nothing_wrote() {
+        // LD I, 0x208; DRW V0, V1, 2; ADD V3, 1; JP 0x206; DATA a sprite of 2 rows
+        let rom = [0xA2, 0x08, 0xD0, 0x12, 0x73, 0x01, 0x12, 0x06, 0xFF, 0xFF];
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .detect_uninit(true)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        chip8.run_cycles(6).unwrap();
+        // V0 and V1, then V3, the sprite being the ROM's own, once each
+        assert_eq!(chip8.uninit().unwrap().reported(), 3);
+
+        // Reported again after a reset
+        chip8.reset();
+        chip8.run_cycles(3).unwrap();
+        assert_eq!(chip8.uninit().unwrap().reported(), 6);
+
+        assert!(Chip8::headless().uninit().is_none());
     }
 
-    fn get_delay_timer(&self) -> u8 {
-        let ms = self.delay_timer.elapsed().as_millis();
-        let ticks = ms / 16;
-        if ticks >= self.registers.dt as u128 {
-            0
-        } else {
-            self.registers.dt - ticks as u8
+    #[test]
+    fn skips_faults_when_recovering_from_them() {
+        // RET with nothing to return to; an unknown FFFF; JP 0x200
+        let rom = [0x00, 0xEE, 0xFF, 0xFF, 0x12, 0x00];
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .fault_policy(Strictness::Lenient.policy())
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        let summary = chip8.run_cycles(9).unwrap();
+        assert_eq!(summary.cycles, 9);
+        assert_eq!(chip8.stats().cycles, 9);
+        let faults: Vec<_> = chip8.faults().unwrap().breakdown().collect();
+        assert_eq!(
+            faults,
+            [
+                (FaultKind::UnknownOpcode, 0x202, 3),
+                (FaultKind::StackUnderflow, 0x200, 3)
+            ]
+        );
+
+        // The fault past the max stops the run like any
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .fault_policy(Strictness::Lenient.policy())
+            .max_faults(4)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert!(matches!(
+            chip8.run_cycles(9),
+            Err(Chip8Error::MemoryFault { pc: 0x200, .. })
+        ));
+        assert_eq!(chip8.faults().unwrap().total(), 5);
+
+        assert!(Chip8::headless().faults().is_none());
+
+        // Faults the policy doesn't skip stop the run
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .fault_policy(Strictness::Normal.policy())
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert!(matches!(
+            chip8.step(),
+            Err(Chip8Error::MemoryFault { pc: 0x200, .. })
+        ));
+        assert_eq!(chip8.faults().unwrap().total(), 0);
+    }
+
+    #[test]
+    fn detects_the_variant_of_every_rom_loaded() {
+        let quirks = Quirks {
+            shift: false,
+            memory: false,
+            jump: false,
+        };
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .quirks(quirks)
+            .detect_variant(true)
+            .build()
+            .unwrap();
+        // hires; JP 0x202
+        chip8.load_rom_bytes(&[0x00, 0xFF, 0x12, 0x02]).unwrap();
+        assert_eq!(chip8.cpu().quirks(), Variant::SChip.quirks());
+        // CLS; JP 0x202, with the quirks set until then
+        chip8.load_rom_bytes(&[0x00, 0xE0, 0x12, 0x02]).unwrap();
+        assert_eq!(chip8.cpu().quirks(), quirks);
+        // plane 1; JP 0x202, XO-CHIP running as CHIP-8
+        chip8.load_rom_bytes(&[0xF1, 0x01, 0x12, 0x02]).unwrap();
+        assert_eq!(chip8.cpu().quirks(), quirks);
+
+        // Without it the quirks stay the ones set
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .quirks(quirks)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&[0x00, 0xFF, 0x12, 0x02]).unwrap();
+        assert_eq!(chip8.cpu().quirks(), quirks);
+    }
+
+    #[test]
+    fn chip8x_roms_color_the_zones() {
+        // BGCOL; LD V0, 0x00; LD V1, 0x00; LD V2, 4; COL V0, V2; JMP 0x20A
+        let rom = [
+            0x02, 0xA0, 0x60, 0x00, 0x61, 0x00, 0x62, 0x04, 0xB0, 0x20, 0x12, 0x0A,
+        ];
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .variant(Variant::Chip8X)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert_eq!(chip8.color_zones(), Some(&ColorZones::new()));
+        chip8.run_cycles(5).unwrap();
+
+        let zones = chip8.color_zones().unwrap();
+        assert_eq!(zones.background(), 1);
+        assert_eq!(zones.foreground(7, 3), 4);
+        assert_eq!(zones.foreground(8, 0), 1);
+        // The game still sees pixels that are on and off
+        assert!(chip8.framebuffer().iter().all(|&pixel| pixel == 0));
+
+        // The colors start over with the ROM
+        chip8.reset();
+        assert_eq!(chip8.color_zones(), Some(&ColorZones::new()));
+
+        // Other variants don't know the instructions, and have no zones
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert!(chip8.color_zones().is_none());
+        assert!(matches!(
+            chip8.step(),
+            Err(Chip8Error::UnknownOpcode { opcode: 0x02A0, .. })
+        ));
+    }
+
+    #[test]
+    fn seeded_random_numbers_repeat() {
+        // RND V0, 0xFF; RND V1, 0xFF; RND V2, 0xFF
+        let rom = [0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF];
+        let registers = || {
+            let mut chip8 = Chip8::builder().headless(true).seed(42).build().unwrap();
+            chip8.load_rom_bytes(&rom).unwrap();
+            for _ in 0..3 {
+                chip8.step().unwrap();
+            }
+            chip8.registers().v
+        };
+        assert_eq!(registers(), registers());
+    }
+
+    ///
+    /// Ticks once per call, however fast the steps go.
+    ///
+    struct StepClock;
+
+    impl Clock for StepClock {
+        fn ticks(&mut self) -> u32 {
+            1
         }
     }
+
+    #[test]
+    fn injected_clock_drives_the_timers() {
+        // LD V0, 0x05; LD DT, V0; JMP 0x204
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .clock(Box::new(StepClock))
+            .build()
+            .unwrap();
+        chip8
+            .load_rom_bytes(&[0x60, 0x05, 0xF0, 0x15, 0x12, 0x04])
+            .unwrap();
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.delay_timer(), 3);
+    }
+
+    #[test]
+    fn run_frames_tick_the_timers_once_per_frame() {
+        // LD V0, 0xC8; LD DT, V0; LD ST, V0; JMP 0x206
+        let rom = [0x60, 0xC8, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06];
+        // The frames stand in for the clock, even one ticking on every call
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .clock(Box::new(StepClock))
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+
+        let summary = chip8.run_frames(120).unwrap();
+        assert_eq!(chip8.delay_timer(), 200 - 120);
+        assert_eq!(chip8.sound_timer(), 200 - 120);
+        assert_eq!(summary.cycles, 2 * 450);
+        assert!(summary.sound_on);
+        assert!(!summary.display_changed);
+
+        chip8.run_frames(80).unwrap();
+        assert_eq!(chip8.delay_timer(), 0);
+        assert!(!chip8.run_frame().unwrap().sound_on);
+    }
+
+    #[test]
+    fn the_buzzer_sounds_while_the_sound_timer_runs() {
+        // LD V0, 0x02; LD ST, V0; JMP 0x204
+        let rom = [0x60, 0x02, 0xF0, 0x18, 0x12, 0x04];
+        let frontend = ScriptedFrontend::default();
+        let mut chip8 = Chip8::builder()
+            .frontend(Box::new(frontend.clone()))
+            .clock(Box::new(StepClock))
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert!(!chip8.sound_active());
+
+        // Set to 2 and ticked once at the end of the frame, then down to 0 on the next
+        chip8.run_frame().unwrap();
+        assert_eq!(chip8.sound_timer(), 1);
+        assert!(chip8.sound_active());
+        assert_eq!(*frontend.tones.borrow(), [true]);
+        chip8.run_frame().unwrap();
+        assert!(!chip8.sound_active());
+        assert_eq!(*frontend.tones.borrow(), [true, false]);
+        chip8.run_frame().unwrap();
+        assert_eq!(*frontend.tones.borrow(), [true, false]);
+
+        // The frontends ticking the timers themselves see it stop as they do
+        chip8.load_rom_bytes(&rom).unwrap();
+        chip8.run_cycles(2).unwrap();
+        assert_eq!(chip8.sound_timer(), 2);
+        chip8.tick_timers();
+        assert!(chip8.sound_active());
+        chip8.tick_timers();
+        assert!(!chip8.sound_active());
+        assert_eq!(*frontend.tones.borrow(), [true, false, true, false]);
+    }
+
+    #[test]
+    fn run_cycles_executes_exactly_n_instructions() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+
+        let summary = chip8.run_cycles(5).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                cycles: 5,
+                ..RunSummary::default()
+            }
+        );
+        assert!(chip8.run_cycles(2).unwrap().display_changed);
+        assert_eq!(chip8.stats().cycles, 7);
+        assert_eq!(chip8.pc(), 0x20C);
+    }
+
+    ///
+    /// Plays back a script of inputs, a `None` ending the inputs of a frame, and records what the
+    /// emulator shows and sounds.
+    ///
+    #[derive(Default, Clone)]
+    struct ScriptedFrontend {
+        script: Rc<RefCell<VecDeque<Option<Input>>>>,
+        frames: Rc<RefCell<usize>>,
+        tones: Rc<RefCell<Vec<bool>>>,
+        #[cfg(feature = "egui")]
+        debug_view: Rc<RefCell<Option<DebugView>>>,
+        frame_graphs: Rc<RefCell<Vec<Option<FrameGraph>>>>,
+        pixel_ages: Rc<RefCell<Option<Vec<u8>>>>,
+        help: Rc<RefCell<bool>>,
+    }
+
+    impl Keypad for ScriptedFrontend {
+        fn poll_input(&mut self) -> Option<Input> {
+            self.script.borrow_mut().pop_front().flatten()
+        }
+    }
+
+    impl AudioSink for ScriptedFrontend {
+        fn set_tone(&mut self, on: bool) {
+            self.tones.borrow_mut().push(on);
+        }
+    }
+
+    impl Frontend for ScriptedFrontend {
+        fn present(&mut self, _framebuffer: &[u8]) {
+            *self.frames.borrow_mut() += 1;
+        }
+
+        #[cfg(feature = "egui")]
+        fn set_debug_view(&mut self, view: Option<&DebugView>) {
+            *self.debug_view.borrow_mut() = view.cloned();
+        }
+
+        fn set_frame_graph(&mut self, graph: Option<&FrameGraph>) {
+            self.frame_graphs.borrow_mut().push(graph.cloned());
+        }
+
+        fn set_pixel_ages(&mut self, ages: Option<&[u8]>) {
+            *self.pixel_ages.borrow_mut() = ages.map(<[u8]>::to_vec);
+        }
+
+        fn set_help(&mut self, shown: bool) {
+            *self.help.borrow_mut() = shown;
+        }
+    }
+
+    #[test]
+    fn pixel_ages_reach_the_frontend() {
+        // LD F, V0; DRW V0, V0, 5; JMP 0x204
+        let rom = [0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04];
+        let run = |draw_age| {
+            let frontend = ScriptedFrontend::default();
+            let mut chip8 = Chip8::builder()
+                .frontend(Box::new(frontend.clone()))
+                .debug_draw_age(draw_age)
+                .build()
+                .unwrap();
+            chip8.load_rom_bytes(&rom).unwrap();
+            chip8.run_frame().unwrap();
+            (chip8.framebuffer().to_vec(), frontend.pixel_ages.take())
+        };
+
+        let (screen, ages) = run(true);
+        let ages = ages.unwrap();
+        // The "0" glyph's top row, drawn during the frame that just ended
+        assert_eq!(ages[..4], [0; 4]);
+        assert_eq!(ages[4], PIXEL_AGE_FRAMES);
+        // Only the frontend gets to see the ages
+        assert_eq!(run(false), (screen, None));
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn run_is_driven_by_the_frontend() {
+        let frontend = ScriptedFrontend::default();
+        frontend.script.borrow_mut().extend([
+            Some(Input::Key {
+                hex_key: 0x5,
+                pressed: true,
+                at: Instant::now(),
+            }),
+            None,
+            Some(Input::TogglePause),
+            None,
+            None,
+            Some(Input::Quit),
+        ]);
+        let mut chip8 = Chip8::builder()
+            .frontend(Box::new(frontend.clone()))
+            .build()
+            .unwrap();
+        // LD V0, K; LD ST, V0; JMP 0x204
+        chip8
+            .load_rom_bytes(&[0xF0, 0x0A, 0xF0, 0x18, 0x12, 0x04])
+            .unwrap();
+
+        assert_eq!(chip8.run().unwrap(), RunOutcome::Quit);
+        // Only the first frame ran, the others were paused
+        assert_eq!(chip8.stats().cycles, 7);
+        assert_eq!(chip8.v(0), 0x5);
+        assert_eq!(chip8.sound_timer(), 0x4);
+        assert_eq!(*frontend.tones.borrow(), [true]);
+        // Once when built, once when the ROM was loaded
+        assert_eq!(*frontend.frames.borrow(), 2);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn measures_the_input_latency() {
+        let frontend = ScriptedFrontend::default();
+        let now = Instant::now();
+        let key = |hex_key, pressed, at| {
+            Some(Input::Key {
+                hex_key,
+                pressed,
+                at,
+            })
+        };
+        frontend.script.borrow_mut().extend([
+            key(0x6, true, now),
+            key(0x6, false, now),
+            key(
+                0x5,
+                true,
+                now.checked_sub(Duration::from_millis(40)).unwrap(),
+            ),
+            None,
+        ]);
+        let mut chip8 = Chip8::builder()
+            .frontend(Box::new(frontend))
+            .measure_latency(true)
+            .build()
+            .unwrap();
+        // LD V1, 0x05; SKP V1; JMP 0x202; JMP 0x206
+        chip8
+            .load_rom_bytes(&[0x61, 0x05, 0xE1, 0x9E, 0x12, 0x02, 0x12, 0x06])
+            .unwrap();
+        assert_eq!(chip8.latency().unwrap().histogram().count(), 0);
+
+        assert_eq!(chip8.handle_inputs(), None);
+        chip8.run_frame().unwrap();
+        assert_eq!(chip8.pc(), 0x206);
+        let meter = chip8.latency().unwrap();
+        // From when the frontend got the press, not when the emulator handled it
+        let latency = meter.histogram().percentile(50).unwrap();
+        assert!(latency >= Duration::from_millis(40), "{latency:?}");
+        assert_eq!(meter.histogram().count(), 1);
+        // Key 6 went up before anything looked at it
+        assert_eq!(meter.missed(), 1);
+
+        assert!(Chip8::headless().latency().is_none());
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn help_pauses_until_hidden() {
+        let frontend = ScriptedFrontend::default();
+        let mut chip8 = Chip8::builder()
+            .frontend(Box::new(frontend.clone()))
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        let press = |chip8: &mut Chip8, input| {
+            frontend.script.borrow_mut().push_back(Some(input));
+            assert_eq!(chip8.handle_inputs(), None);
+        };
+
+        for paused in [false, true] {
+            chip8.set_paused(paused);
+            press(&mut chip8, Input::ToggleHelp);
+            assert!(*frontend.help.borrow());
+            assert!(chip8.is_paused());
+            press(&mut chip8, Input::ToggleHelp);
+            assert!(!*frontend.help.borrow());
+            assert_eq!(chip8.is_paused(), paused);
+        }
+
+        // A machine reset while the help is shown runs once it's hidden
+        press(&mut chip8, Input::ToggleHelp);
+        press(&mut chip8, Input::Reset);
+        assert!(chip8.is_paused());
+        press(&mut chip8, Input::ToggleHelp);
+        assert!(!chip8.is_paused());
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn run_state_follows_pauses_and_key_waits() {
+        let mut chip8 = Chip8::headless();
+        assert_eq!(chip8.run_state(), RunState::Paused);
+
+        // LD V0, K; JMP 0x200
+        chip8.load_rom_bytes(&[0xF0, 0x0A, 0x12, 0x00]).unwrap();
+        assert_eq!(chip8.run_state(), RunState::Running);
+        chip8.step().unwrap();
+        assert_eq!(chip8.run_state(), RunState::WaitingForKey);
+        chip8.set_paused(true);
+        assert_eq!(chip8.run_state(), RunState::Paused);
+        chip8.set_paused(false);
+        assert_eq!(chip8.run_state(), RunState::WaitingForKey);
+
+        chip8.set_key(0x5, true);
+        chip8.step().unwrap();
+        assert_eq!(chip8.run_state(), RunState::Running);
+        chip8.set_key(0x5, false);
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.run_state(), RunState::WaitingForKey);
+        chip8.reset();
+        assert_eq!(chip8.run_state(), RunState::Running);
+    }
+
+    ///
+    /// The `WaitingFrontend` struct represents a frontend that never has input to poll, but quits
+    /// on its third wait.
+    ///
+    #[cfg(feature = "native")]
+    #[derive(Default, Clone)]
+    struct WaitingFrontend {
+        waits: Rc<RefCell<Vec<Duration>>>,
+    }
+
+    #[cfg(feature = "native")]
+    impl Keypad for WaitingFrontend {
+        fn poll_input(&mut self) -> Option<Input> {
+            None
+        }
+
+        fn wait_input(&mut self, timeout: Duration) -> Option<Input> {
+            let mut waits = self.waits.borrow_mut();
+            waits.push(timeout);
+            (waits.len() == 3).then_some(Input::Quit)
+        }
+    }
+
+    #[cfg(feature = "native")]
+    impl AudioSink for WaitingFrontend {
+        fn set_tone(&mut self, _on: bool) {}
+    }
+
+    #[cfg(feature = "native")]
+    impl Frontend for WaitingFrontend {
+        fn present(&mut self, _framebuffer: &[u8]) {}
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn idle_frames_wait_on_the_frontend() {
+        for paused in [true, false] {
+            let frontend = WaitingFrontend::default();
+            let mut chip8 = Chip8::builder()
+                .frontend(Box::new(frontend.clone()))
+                .build()
+                .unwrap();
+            // LD V0, K
+            chip8.load_rom_bytes(&[0xF0, 0x0A]).unwrap();
+            chip8.set_paused(paused);
+
+            assert_eq!(chip8.run().unwrap(), RunOutcome::Quit);
+            let waits = frontend.waits.borrow();
+            assert_eq!(waits.len(), 3);
+            assert!(waits
+                .iter()
+                .all(|&wait| wait <= Duration::from_secs(1) / 60));
+            // Waiting for a key still runs the frames, for the timers
+            assert_eq!(chip8.stats().cycles > 0, !paused);
+        }
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn frame_times_are_only_recorded_while_the_graph_is_shown() {
+        let frontend = ScriptedFrontend::default();
+        frontend.script.borrow_mut().extend([
+            None,
+            Some(Input::ToggleFrameGraph),
+            None,
+            None,
+            Some(Input::ToggleFrameGraph),
+            None,
+            Some(Input::Quit),
+        ]);
+        let mut chip8 = Chip8::builder()
+            .frontend(Box::new(frontend.clone()))
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+
+        assert_eq!(chip8.run().unwrap(), RunOutcome::Quit);
+        let frames = frontend
+            .frame_graphs
+            .borrow()
+            .iter()
+            .map(|graph| graph.as_ref().map(|graph| graph.times.len()))
+            .collect::<Vec<_>>();
+        // Shown from the frame it was toggled on, a frame time more every frame, then hidden
+        assert_eq!(frames, [Some(0), Some(1), None]);
+    }
+
+    #[cfg(feature = "egui")]
+    #[test]
+    fn debug_view_inputs_manage_breakpoints_and_memory() {
+        let frontend = ScriptedFrontend::default();
+        frontend.script.borrow_mut().extend([
+            Some(Input::ToggleDebugView),
+            Some(Input::TogglePause),
+            Some(Input::ToggleBreakpoint(0x204)),
+            Some(Input::ToggleBreakpoint(0x206)),
+            Some(Input::ToggleBreakpoint(0x206)),
+            Some(Input::ScrollMemory(-1)),
+            None,
+            Some(Input::Quit),
+        ]);
+        let mut chip8 = Chip8::builder()
+            .frontend(Box::new(frontend.clone()))
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+
+        assert_eq!(chip8.run().unwrap(), RunOutcome::Quit);
+        let view = frontend.debug_view.borrow().clone().unwrap();
+        assert!(view.paused);
+        assert_eq!(view.breakpoints, [0x204]);
+        assert_eq!(view.memory_start, 0x1F0);
+        assert_eq!(view.memory[0x10..0x10 + STEP_ROM.len()], STEP_ROM);
+        assert_eq!(view.registers, chip8.status_lines(0));
+        assert!(view
+            .disassembly
+            .iter()
+            .any(|line| line.current && line.address == 0x200));
+
+        chip8.scroll_memory_view(1_000);
+        assert_eq!(chip8.debug_view(0).memory_start, 0xF80);
+        chip8.scroll_memory_view(-1_000);
+        assert_eq!(chip8.debug_view(0).memory_start, 0x000);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn run_pauses_on_a_breakpoint_and_steps_from_it() {
+        let frontend = ScriptedFrontend::default();
+        frontend
+            .script
+            .borrow_mut()
+            .extend([None, Some(Input::Step), None, Some(Input::Quit)]);
+        let mut chip8 = Chip8::builder()
+            .frontend(Box::new(frontend))
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+        chip8.add_breakpoint(0x204);
+
+        assert_eq!(chip8.run().unwrap(), RunOutcome::Quit);
+        assert!(chip8.is_paused());
+        // Stopped before ADD V0, V1, then stepped over it
+        assert_eq!(chip8.stats().cycles, 3);
+        assert_eq!((chip8.pc(), chip8.v(0)), (0x206, 12));
+    }
+
+    #[test]
+    fn breakpoints_survive_reset() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+        chip8.add_breakpoint(0x202);
+        assert_eq!(chip8.run_frame().unwrap().breakpoint, Some(0x202));
+
+        chip8.reset();
+        assert_eq!(chip8.breakpoints().collect::<Vec<_>>(), [0x202.into()]);
+        let summary = chip8.run_frames(2).unwrap();
+        assert_eq!((summary.cycles, summary.breakpoint), (1, Some(0x202)));
+
+        assert!(chip8.remove_breakpoint(0x202));
+        assert!(!chip8.remove_breakpoint(0x202));
+        assert_eq!(chip8.run_frame().unwrap().breakpoint, None);
+    }
+
+    #[test]
+    fn status_lines_show_the_registers() {
+        let mut chip8 = Chip8::headless();
+        // LD V0, 0x2A; LD VF, 0x01; LD I, 0x300; CALL 0x208
+        chip8
+            .load_rom_bytes(&[0x60, 0x2A, 0x6F, 0x01, 0xA3, 0x00, 0x22, 0x08])
+            .unwrap();
+        chip8.run_cycles(4).unwrap();
+        assert_eq!(
+            chip8.status_lines(60),
+            [
+                "PC=0x0208 I=0x0300 DT=0x00 ST=0x00",
+                "V0=0x2A V1=0x00 V2=0x00 V3=0x00",
+                "V4=0x00 V5=0x00 V6=0x00 V7=0x00",
+                "V8=0x00 V9=0x00 VA=0x00 VB=0x00",
+                "VC=0x00 VD=0x00 VE=0x00 VF=0x01",
+                "SP 1  IPS 0  FPS 60",
+            ]
+        );
+    }
+
+    #[test]
+    fn dump_state_draws_the_screen() {
+        let mut chip8 = Chip8::headless();
+        // LD F, V0; DRW V0, V0, 5, drawing the 0 of the font in the top left corner
+        chip8.load_rom_bytes(&[0xF0, 0x29, 0xD0, 0x05]).unwrap();
+        chip8.run_cycles(2).unwrap();
+
+        let blank = ".".repeat(64);
+        let mut expected = "\
+PC=0x0204 I=0x0000 DT=0x00 ST=0x00
+V0=0x00 V1=0x00 V2=0x00 V3=0x00
+V4=0x00 V5=0x00 V6=0x00 V7=0x00
+V8=0x00 V9=0x00 VA=0x00 VB=0x00
+VC=0x00 VD=0x00 VE=0x00 VF=0x00
+Stack: []
+"
+        .to_string();
+        for glyph in ["####", "#..#", "#..#", "#..#", "####"] {
+            expected.push_str(glyph);
+            expected.push_str(&blank[4..]);
+            expected.push('\n');
+        }
+        for _ in 5..32 {
+            expected.push_str(&blank);
+            expected.push('\n');
+        }
+        assert_eq!(chip8.dump_state(), expected);
+    }
+
+    #[test]
+    fn backtrace_lists_the_call_chain() {
+        let mut chip8 = Chip8::headless();
+        // CALL 0x206; JMP 0x202; JMP 0x204
+        // 0x206: CALL 0x20A; RET. 0x20A: CALL 0x246; RET. 0x246: JMP 0x246
+        let mut rom = vec![
+            0x22, 0x06, 0x12, 0x02, 0x12, 0x04, 0x22, 0x0A, 0x00, 0xEE, 0x22, 0x46, 0x00, 0xEE,
+        ];
+        rom.resize(0x46, 0);
+        rom.extend([0x12, 0x46]);
+        chip8.load_rom_bytes(&rom).unwrap();
+        assert_eq!(chip8.backtrace(), "#0 0x0200\n");
+
+        chip8.run_cycles(4).unwrap();
+        assert_eq!(
+            chip8.backtrace(),
+            "#0 0x0246\n\
+             #1 0x0246 called from 0x020A (CALL #0x246)\n\
+             #2 0x020A called from 0x0206 (CALL #0x20A)\n\
+             #3 0x0206 called from 0x0200 (CALL #0x206)\n"
+        );
+        assert_eq!(chip8.stack(), [0x202, 0x208, 0x20C]);
+
+        chip8.reset();
+        assert_eq!(chip8.backtrace(), "#0 0x0200\n");
+    }
+
+    #[test]
+    fn temporary_breakpoints_match_the_stack_depth_once() {
+        let mut chip8 = Chip8::headless();
+        // CALL 0x204; JMP 0x200; RET
+        chip8
+            .load_rom_bytes(&[0x22, 0x04, 0x12, 0x00, 0x00, 0xEE])
+            .unwrap();
+        chip8.set_temporary_breakpoint(0x204, 0);
+        // 0x204 is only ever reached one call deep
+        assert_eq!(chip8.run_frame().unwrap().breakpoint, None);
+
+        chip8.set_temporary_breakpoint(0x204, 1);
+        assert_eq!(chip8.run_frame().unwrap().breakpoint, Some(0x204));
+        assert_eq!(chip8.stack(), [0x202]);
+        assert_eq!(chip8.run_frame().unwrap().breakpoint, None);
+    }
+
+    #[test]
+    fn watches_stop_after_the_changing_instruction() {
+        let mut chip8 = Chip8::headless();
+        // LD V0, 0xFF; LD V1, 0x01; ADD V0, V1; ADD V0, V1; JMP 0x208
+        chip8
+            .load_rom_bytes(&[0x60, 0xFF, 0x61, 0x01, 0x80, 0x14, 0x80, 0x14, 0x12, 0x08])
+            .unwrap();
+        chip8.add_watch(Watch::Register(0xF));
+
+        // The first ADD carries, the second one doesn't
+        let summary = chip8.run_frame().unwrap();
+        assert_eq!(summary.cycles, 3);
+        assert_eq!(
+            summary.watch,
+            Some(WatchHit {
+                watch: Watch::Register(0xF),
+                old: 0,
+                new: 1,
+                pc: 0x204,
+                opcode: 0x8014,
+            })
+        );
+        assert_eq!(chip8.pc(), 0x206);
+
+        let hit = chip8.step().unwrap().watch.unwrap();
+        assert_eq!((hit.old, hit.new, hit.pc), (1, 0, 0x206));
+        assert_eq!(chip8.run_frames(2).unwrap().watch, None);
+
+        assert!(chip8.remove_watch(Watch::Register(0xF)));
+        assert_eq!(chip8.watches().count(), 0);
+    }
+
+    #[test]
+    fn memory_watches_see_stores() {
+        let mut chip8 = Chip8::headless();
+        chip8.add_watch(Watch::Memory(0x301));
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+
+        let summary = chip8.run_frame().unwrap();
+        let hit = summary.watch.unwrap();
+        // LD [I], V1 stored V0 at 0x300 and V1 at 0x301
+        assert_eq!(
+            (hit.old, hit.new, hit.pc, hit.opcode),
+            (0, 7, 0x208, 0xF155)
+        );
+        assert_eq!(summary.cycles, 5);
+    }
+
+    #[test]
+    fn frozen_pokes_win_over_the_game() {
+        // ADD V0, 0x01; LD I, 0x300; LD [I], V0; JMP 0x200, counting up at 0x300
+        let rom = [0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00];
+        let mut chip8 = Chip8::headless();
+        chip8.add_watch(Watch::Memory(0x301));
+        chip8.load_rom_bytes(&rom).unwrap();
+        chip8.add_poke("0x300=0x10".parse().unwrap());
+        chip8.add_poke("0x301=0x20".parse().unwrap());
+        // The last poke of an address wins
+        chip8.add_poke("0x300=0x63".parse().unwrap());
+        let ram = |chip8: &Chip8, address| chip8.cpu().ram().read_byte(address).unwrap();
+        assert_eq!(ram(&chip8, 0x300), 0x63);
+
+        for _ in 0..3 {
+            // The game wrote its count during the frame, the poke undid it after it
+            let summary = chip8.run_frame().unwrap();
+            assert_eq!((ram(&chip8, 0x300), ram(&chip8, 0x301)), (0x63, 0x20));
+            // Forcing the byte isn't a change of the game's
+            assert_eq!(summary.watch, None);
+        }
+        assert_eq!(chip8.pokes().to_string(), "0x300=0x63, 0x301=0x20");
+
+        // They hold across a reset, and the game has the byte back once it's let go
+        chip8.reset();
+        assert_eq!(ram(&chip8, 0x300), 0x63);
+        assert!(chip8.remove_poke(0x300));
+        chip8.run_frame().unwrap();
+        assert_eq!(ram(&chip8, 0x300), chip8.v(0));
+        chip8.clear_pokes();
+        assert!(chip8.pokes().is_empty());
+    }
+
+    #[test]
+    fn custom_load_address() {
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .load_address(0x600)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&STEP_ROM).unwrap();
+        assert_eq!(chip8.registers().pc, 0x600);
+        chip8.step().unwrap();
+        assert_eq!(chip8.registers().v[0], 0x05);
+        assert_eq!(chip8.cpu().ram().read_byte(0x200).unwrap(), 0);
+    }
+
+    #[test]
+    fn demo_has_a_hash() {
+        let mut chip8 = Chip8::headless();
+        assert!(chip8.rom_hash().is_none());
+        chip8.load_demo().unwrap();
+        assert!(chip8.rom_hash().is_some());
+        assert!(chip8.rom_path().is_none());
+    }
 }
@@ -0,0 +1,202 @@
+use super::io::{GRID_HEIGHT, GRID_WIDTH};
+
+///
+/// The `ZONE_WIDTH` constant is how many pixels wide the zones CHIP-8X colors are, the columns
+/// of its color RAM.
+///
+pub const ZONE_WIDTH: usize = 8;
+
+///
+/// The `ZONE_HEIGHT` constant is how many rows tall the zones `BXY0` colors are. `BXYN` colors
+/// single rows of a zone's width.
+///
+pub const ZONE_HEIGHT: usize = 4;
+
+///
+/// How many zones make a row of the screen.
+///
+const ZONE_COLUMNS: usize = GRID_WIDTH / ZONE_WIDTH;
+
+///
+/// The `FOREGROUND_COLORS` constant is the RGB colors of the VP-590 color board the pixels that
+/// are on can take: black, red, blue, violet, green, yellow, aqua and white.
+///
+pub const FOREGROUND_COLORS: [[u8; 3]; 8] = [
+    [0x00, 0x00, 0x00],
+    [0xFF, 0x00, 0x00],
+    [0x00, 0x00, 0xFF],
+    [0xFF, 0x00, 0xFF],
+    [0x00, 0xFF, 0x00],
+    [0xFF, 0xFF, 0x00],
+    [0x00, 0xFF, 0xFF],
+    [0xFF, 0xFF, 0xFF],
+];
+
+///
+/// The `BACKGROUND_COLORS` constant is the RGB colors `02A0` cycles the background through, in
+/// order: dark blue, black, green and red.
+///
+pub const BACKGROUND_COLORS: [[u8; 3]; 4] = [
+    [0x00, 0x00, 0x80],
+    [0x00, 0x00, 0x00],
+    [0x00, 0x80, 0x00],
+    [0x80, 0x00, 0x00],
+];
+
+///
+/// The `DEFAULT_FOREGROUND` constant is the color every zone shows the pixels in until the ROM
+/// colors it, red.
+///
+pub const DEFAULT_FOREGROUND: u8 = 1;
+
+///
+/// The `ColorZones` struct represents the color board of CHIP-8X: the background color and the
+/// color of the pixels that are on, by zone.
+///
+/// The colors sit beside the screen and are only applied as it's shown, the game still sees
+/// pixels that are on and off.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorZones {
+    /// The index in `BACKGROUND_COLORS` of the background
+    background: u8,
+    /// The index in `FOREGROUND_COLORS` of every zone's color, by row of pixels
+    rows: [[u8; ZONE_COLUMNS]; GRID_HEIGHT],
+}
+
+impl Default for ColorZones {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ColorZones {
+    pub const fn new() -> Self {
+        Self {
+            background: 0,
+            rows: [[DEFAULT_FOREGROUND; ZONE_COLUMNS]; GRID_HEIGHT],
+        }
+    }
+
+    ///
+    /// Moves the background on to the next of `BACKGROUND_COLORS` as `02A0` does, the last one
+    /// going back to the first.
+    ///
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLORS.len() as u8;
+    }
+
+    pub const fn background(&self) -> u8 {
+        self.background
+    }
+
+    ///
+    /// Colors the zones of `BXY0`, `ZONE_WIDTH` × `ZONE_HEIGHT` pixels each: `columns` of them
+    /// from the `column`th and `rows` from the `row`th. Zones past the screen are left out.
+    ///
+    pub fn set_zones(&mut self, column: usize, columns: usize, row: usize, rows: usize, color: u8) {
+        let first_row = row * ZONE_HEIGHT;
+        let last_row = ((row + rows) * ZONE_HEIGHT).min(GRID_HEIGHT);
+        for pixel_row in self.rows.iter_mut().take(last_row).skip(first_row) {
+            for zone in pixel_row.iter_mut().skip(column).take(columns) {
+                *zone = color & 0x7;
+            }
+        }
+    }
+
+    ///
+    /// Colors `rows` rows of the zone of `BXYN` from the pixel at `(x, y)` on, a zone wide.
+    /// Rows past the bottom are left out.
+    ///
+    pub fn set_rows(&mut self, x: usize, y: usize, rows: usize, color: u8) {
+        let column = (x % GRID_WIDTH) / ZONE_WIDTH;
+        for pixel_row in self.rows.iter_mut().skip(y).take(rows) {
+            pixel_row[column] = color & 0x7;
+        }
+    }
+
+    ///
+    /// Returns the index in `FOREGROUND_COLORS` of the color the pixel at `(x, y)` shows in when
+    /// it's on. The rows of a taller screen take the colors of the 32 ones over again.
+    ///
+    pub const fn foreground(&self, x: usize, y: usize) -> u8 {
+        self.rows[y % GRID_HEIGHT][(x % GRID_WIDTH) / ZONE_WIDTH]
+    }
+
+    ///
+    /// Returns the RGB color the pixel at `(x, y)` shows in: its zone's when it's on, the
+    /// background's when it's off.
+    ///
+    pub const fn rgb(&self, x: usize, y: usize, pixel: u8) -> [u8; 3] {
+        if pixel == 0 {
+            BACKGROUND_COLORS[self.background as usize]
+        } else {
+            FOREGROUND_COLORS[self.foreground(x, y) as usize]
+        }
+    }
+}
+
+#[cfg(test)]
+mod chip8x_tests {
+    use super::{ColorZones, BACKGROUND_COLORS, FOREGROUND_COLORS};
+    use crate::emu::io::{BufferScreen, Screen, GRID_WIDTH};
+
+    ///
+    /// Colors the screen's framebuffer the way the window shows it.
+    ///
+    fn render(screen: &BufferScreen, zones: &ColorZones) -> Vec<[u8; 3]> {
+        screen
+            .framebuffer()
+            .iter()
+            .enumerate()
+            .map(|(index, &pixel)| zones.rgb(index % GRID_WIDTH, index / GRID_WIDTH, pixel))
+            .collect()
+    }
+
+    #[test]
+    fn colors_the_pixels_by_zone() {
+        let mut screen = BufferScreen::new();
+        for (x, y) in [(0, 0), (8, 0), (8, 4), (63, 31)] {
+            screen.set_pixel(x, y, 1);
+        }
+        let mut zones = ColorZones::new();
+        // The second and third zones of the first row of zones in blue
+        zones.set_zones(1, 2, 0, 1, 2);
+        let pixels = render(&screen, &zones);
+        let at = |x: usize, y: usize| pixels[y * GRID_WIDTH + x];
+
+        // Red until colored, and the game's pixels stay on and off
+        assert_eq!(at(0, 0), FOREGROUND_COLORS[1]);
+        assert_eq!(at(8, 0), FOREGROUND_COLORS[2]);
+        assert_eq!(at(8, 4), FOREGROUND_COLORS[1]);
+        assert_eq!(at(63, 31), FOREGROUND_COLORS[1]);
+        assert_eq!(at(9, 0), BACKGROUND_COLORS[0]);
+        assert!(screen.framebuffer().iter().all(|&pixel| pixel <= 1));
+
+        // Single rows of a zone, cut off at the bottom
+        zones.set_rows(15, 4, 1, 7);
+        zones.set_rows(60, 30, 5, 5);
+        let pixels = render(&screen, &zones);
+        assert_eq!(pixels[4 * GRID_WIDTH + 8], FOREGROUND_COLORS[7]);
+        assert_eq!(pixels[31 * GRID_WIDTH + 63], FOREGROUND_COLORS[5]);
+        assert_eq!(zones.foreground(8, 5), 1);
+
+        // Zones past the screen are left out, and the colors only take 3 bits
+        zones.set_zones(7, 5, 7, 5, 0xE);
+        assert_eq!(zones.foreground(63, 31), 6);
+        assert_eq!(zones.foreground(55, 31), 1);
+    }
+
+    #[test]
+    fn cycles_the_background() {
+        let mut zones = ColorZones::new();
+        assert_eq!(zones.rgb(0, 0, 0), BACKGROUND_COLORS[0]);
+        for expected in [1, 2, 3, 0] {
+            zones.cycle_background();
+            assert_eq!(zones.background(), expected);
+            assert_eq!(zones.rgb(5, 5, 0), BACKGROUND_COLORS[usize::from(expected)]);
+        }
+        // The pixels that are on keep their zone's color
+        assert_eq!(zones.rgb(5, 5, 1), FOREGROUND_COLORS[1]);
+    }
+}
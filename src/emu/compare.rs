@@ -0,0 +1,352 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::chip8::{Chip8, Chip8Error};
+use super::quirks::{Quirks, Variant};
+
+///
+/// The `QuirkSetting` struct represents the quirks one side of a comparison runs with, e.g.
+/// `shift=on` or `schip,jump=off`: a variant's quirks, then the ones set one by one.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkSetting {
+    label: String,
+    variant: Option<Variant>,
+    shift: Option<bool>,
+    memory: Option<bool>,
+    jump: Option<bool>,
+}
+
+impl QuirkSetting {
+    ///
+    /// Returns the quirks of `base` changed as the setting says.
+    ///
+    pub fn apply(&self, base: Quirks) -> Quirks {
+        let quirks = self.variant.map_or(base, Variant::quirks);
+        Quirks {
+            shift: self.shift.unwrap_or(quirks.shift),
+            memory: self.memory.unwrap_or(quirks.memory),
+            jump: self.jump.unwrap_or(quirks.jump),
+        }
+    }
+}
+
+impl fmt::Display for QuirkSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+///
+/// The `QuirkSettingError` struct represents a part of a quirk setting that isn't a variant or a
+/// quirk turned on or off.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkSettingError(String);
+
+impl Error for QuirkSettingError {}
+
+impl fmt::Display for QuirkSettingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid quirk setting `{}`, expected e.g. `shift=on`, `memory=off`, `jump=on` or `schip`!",
+            self.0
+        )
+    }
+}
+
+impl FromStr for QuirkSetting {
+    type Err = QuirkSettingError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut setting = Self {
+            label: text.to_string(),
+            variant: None,
+            shift: None,
+            memory: None,
+            jump: None,
+        };
+        for part in text.split(',').map(str::trim) {
+            let error = || QuirkSettingError(part.to_string());
+            let Some((name, value)) = part.split_once('=') else {
+                setting.variant = Some(part.parse().map_err(|_| error())?);
+                continue;
+            };
+            let enabled = match value.trim().to_ascii_lowercase().as_str() {
+                "on" | "true" | "1" => true,
+                "off" | "false" | "0" => false,
+                _ => return Err(error()),
+            };
+            let quirk = match name.trim().to_ascii_lowercase().as_str() {
+                "shift" => &mut setting.shift,
+                "memory" => &mut setting.memory,
+                "jump" => &mut setting.jump,
+                _ => return Err(error()),
+            };
+            *quirk = Some(enabled);
+        }
+        Ok(setting)
+    }
+}
+
+///
+/// The `Side` enum represents one of the two cores of a comparison.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::A => write!(f, "A"),
+            Self::B => write!(f, "B"),
+        }
+    }
+}
+
+///
+/// The `CompareError` struct represents one of the cores of a comparison failing.
+///
+#[derive(Debug)]
+pub struct CompareError {
+    pub side: Side,
+    pub error: Chip8Error,
+}
+
+impl Error for CompareError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl fmt::Display for CompareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Core {} failed: {}", self.side, self.error)
+    }
+}
+
+///
+/// The `Comparison` struct represents two cores running the same ROM in lockstep, frame by frame
+/// and with the same keys held, and the first frame at which their screens differed.
+///
+/// The cores should only differ in what's compared, their quirks say, and be seeded alike for the
+/// random numbers not to tell them apart.
+///
+/// ```
+/// use chip8_emu::emu::chip8::Chip8;
+/// use chip8_emu::emu::compare::Comparison;
+///
+/// // LD V0, 0x08; LD V1, 0x04; SHR V0, V1; LD F, V0; DRW V2, V2, 5; JMP 0x20A
+/// let rom = [0x60, 0x08, 0x61, 0x04, 0x80, 0x16, 0xF0, 0x29, 0xD2, 0x25, 0x12, 0x0A];
+/// let core = |shift| {
+///     let mut chip8 = Chip8::builder().headless(true).seed(7).quirk_shift(shift).build().unwrap();
+///     chip8.load_rom_bytes(&rom).unwrap();
+///     chip8
+/// };
+/// let mut comparison = Comparison::new(core(true), core(false));
+/// comparison.run_frame().unwrap();
+/// assert_eq!(comparison.divergence(), Some(1));
+/// ```
+///
+pub struct Comparison {
+    a: Chip8,
+    b: Chip8,
+    frames: u64,
+    divergence: Option<u64>,
+}
+
+impl Comparison {
+    pub const fn new(a: Chip8, b: Chip8) -> Self {
+        Self {
+            a,
+            b,
+            frames: 0,
+            divergence: None,
+        }
+    }
+
+    pub const fn core(&self, side: Side) -> &Chip8 {
+        match side {
+            Side::A => &self.a,
+            Side::B => &self.b,
+        }
+    }
+
+    ///
+    /// Presses or releases the key on both cores.
+    ///
+    pub fn set_key(&mut self, hex_key: u8, pressed: bool) {
+        self.a.set_key(hex_key, pressed);
+        self.b.set_key(hex_key, pressed);
+    }
+
+    ///
+    /// Runs a 60 Hz frame on both cores, then compares their screens. Returns whether they differ
+    /// for the first time.
+    ///
+    pub fn run_frame(&mut self) -> Result<bool, CompareError> {
+        self.a.run_frame().map_err(|error| CompareError {
+            side: Side::A,
+            error,
+        })?;
+        self.b.run_frame().map_err(|error| CompareError {
+            side: Side::B,
+            error,
+        })?;
+        self.frames += 1;
+        let diverged = self.divergence.is_none() && self.a.framebuffer() != self.b.framebuffer();
+        if diverged {
+            self.divergence = Some(self.frames);
+        }
+        Ok(diverged)
+    }
+
+    ///
+    /// Returns how many frames both cores ran.
+    ///
+    pub const fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    ///
+    /// Returns the frame, counting from 1, after which the screens first differed, if they did.
+    ///
+    pub const fn divergence(&self) -> Option<u64> {
+        self.divergence
+    }
+
+    ///
+    /// Describes how the screens compare so far, e.g. `Screens differ since frame 12`.
+    ///
+    pub fn status(&self) -> String {
+        self.divergence.map_or_else(
+            || format!("Screens identical for {} frames", self.frames),
+            |frame| format!("Screens differ since frame {frame}"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod compare_tests {
+    use super::{Comparison, QuirkSetting, QuirkSettingError, Side};
+    use crate::emu::chip8::{Chip8, Chip8Error};
+    use crate::emu::quirks::{Quirks, Variant};
+
+    // LD V3, 0x02; LD DT, V3; LD V3, DT; SE V3, 0x00; JMP 0x204; LD V0, 0x08; LD V1, 0x04;
+    // SHR V0, V1; LD F, V0; DRW V2, V2, 5; JMP 0x214
+    const ROM: [u8; 22] = [
+        0x63, 0x02, 0xF3, 0x15, 0xF3, 0x07, 0x33, 0x00, 0x12, 0x04, 0x60, 0x08, 0x61, 0x04, 0x80,
+        0x16, 0xF0, 0x29, 0xD2, 0x25, 0x12, 0x14,
+    ];
+
+    fn core(setting: &str) -> Chip8 {
+        let setting: QuirkSetting = setting.parse().unwrap();
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .seed(1)
+            .quirks(setting.apply(Quirks::default()))
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&ROM).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn parses_settings() {
+        let setting: QuirkSetting = "shift=off".parse().unwrap();
+        assert_eq!(setting.to_string(), "shift=off");
+        assert_eq!(
+            setting.apply(Quirks::default()),
+            Quirks {
+                shift: false,
+                ..Quirks::default()
+            }
+        );
+
+        let setting: QuirkSetting = "schip, jump=OFF".parse().unwrap();
+        assert_eq!(
+            setting.apply(Quirks::default()),
+            Quirks {
+                jump: false,
+                ..Variant::SChip.quirks()
+            }
+        );
+
+        assert_eq!(
+            "shift=maybe".parse::<QuirkSetting>(),
+            Err(QuirkSettingError("shift=maybe".to_string()))
+        );
+        assert_eq!(
+            "wrap=on".parse::<QuirkSetting>().unwrap_err().to_string(),
+            "Invalid quirk setting `wrap=on`, expected e.g. `shift=on`, `memory=off`, `jump=on` or `schip`!"
+        );
+        assert!("xochip".parse::<QuirkSetting>().is_err());
+    }
+
+    #[test]
+    fn finds_the_first_differing_frame() {
+        let mut comparison = Comparison::new(core("shift=on"), core("shift=off"));
+        // Both wait out the delay timer the same way before the shift tells them apart
+        for _ in 0..3 {
+            assert!(!comparison.run_frame().unwrap());
+        }
+        assert_eq!(comparison.divergence(), None);
+        assert_eq!(comparison.status(), "Screens identical for 3 frames");
+
+        assert!(comparison.run_frame().unwrap());
+        assert!(!comparison.run_frame().unwrap());
+        assert_eq!(comparison.frames(), 5);
+        assert_eq!(comparison.divergence(), Some(4));
+        assert_eq!(comparison.status(), "Screens differ since frame 4");
+        assert_ne!(
+            comparison.core(Side::A).framebuffer(),
+            comparison.core(Side::B).framebuffer()
+        );
+    }
+
+    #[test]
+    fn identical_settings_never_differ() {
+        let mut comparison = Comparison::new(core("shift=on"), core("shift=on,memory=on"));
+        for _ in 0..10 {
+            assert!(!comparison.run_frame().unwrap());
+        }
+        assert_eq!(comparison.divergence(), None);
+    }
+
+    #[test]
+    fn keys_reach_both_cores() {
+        // LD V0, K; LD F, V0; DRW V2, V2, 5; JMP 0x206
+        let rom = [0xF0, 0x0A, 0xF0, 0x29, 0xD2, 0x25, 0x12, 0x06];
+        let core = || {
+            let mut chip8 = Chip8::headless();
+            chip8.load_rom_bytes(&rom).unwrap();
+            chip8
+        };
+        let mut comparison = Comparison::new(core(), core());
+        comparison.run_frame().unwrap();
+        comparison.set_key(7, true);
+        comparison.run_frame().unwrap();
+        comparison.set_key(7, false);
+        comparison.run_frame().unwrap();
+        assert_eq!(comparison.core(Side::A).v(0), 7);
+        assert_eq!(comparison.core(Side::B).v(0), 7);
+        assert_eq!(comparison.divergence(), None);
+    }
+
+    #[test]
+    fn failures_name_the_core() {
+        let mut b = Chip8::headless();
+        // An unknown opcode
+        b.load_rom_bytes(&[0xFF, 0xFF]).unwrap();
+        let mut comparison = Comparison::new(core("shift=on"), b);
+        let err = comparison.run_frame().unwrap_err();
+        assert_eq!(err.side, Side::B);
+        assert!(matches!(err.error, Chip8Error::UnknownOpcode { .. }));
+        assert!(err.to_string().starts_with("Core B failed: "));
+    }
+}
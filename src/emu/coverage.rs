@@ -0,0 +1,277 @@
+use std::error::Error;
+use std::fmt::{self, Write as _};
+
+use super::breakpoint;
+use super::cpu::{Instruction, INSTRUCTION_COUNT};
+use super::json::Json;
+use super::memory::RAM_SIZE;
+
+///
+/// The `Coverage` struct represents which addresses instructions were executed from, one bit per
+/// address, and how often each kind of instruction was executed.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    executed: [u64; RAM_SIZE / 64],
+    instructions: [u64; INSTRUCTION_COUNT],
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self {
+            executed: [0; RAM_SIZE / 64],
+            instructions: [0; INSTRUCTION_COUNT],
+        }
+    }
+}
+
+///
+/// The `CoverageError` struct represents a coverage report that can't be read back.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageError(String);
+
+impl Error for CoverageError {}
+
+impl fmt::Display for CoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid coverage report: {}!", self.0)
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Records an execution of `instruction` at `pc`.
+    ///
+    pub fn record(&mut self, pc: u16, instruction: Instruction) {
+        let address = usize::from(pc) % RAM_SIZE;
+        self.executed[address / 64] |= 1 << (address % 64);
+        self.instructions[instruction as usize] += 1;
+    }
+
+    ///
+    /// Returns whether an instruction was executed from `address`.
+    ///
+    pub const fn is_executed(&self, address: u16) -> bool {
+        let address = address as usize % RAM_SIZE;
+        self.executed[address / 64] & (1 << (address % 64)) != 0
+    }
+
+    ///
+    /// Returns whether the byte at `address` is part of an executed instruction, its first byte
+    /// or its second.
+    ///
+    pub const fn covers(&self, address: u16) -> bool {
+        self.is_executed(address) || (address > 0 && self.is_executed(address - 1))
+    }
+
+    pub const fn instruction_count(&self, instruction: Instruction) -> u64 {
+        self.instructions[instruction as usize]
+    }
+
+    ///
+    /// Returns the addresses instructions were executed from, in order.
+    ///
+    pub fn executed_addresses(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..RAM_SIZE as u16).filter(|&address| self.is_executed(address))
+    }
+
+    ///
+    /// Returns the instructions never executed, in the order of `Instruction::ALL`. The ones of
+    /// a single variant only count once the ROM executed one of them, the others can't run them.
+    ///
+    pub fn never_used(&self) -> Vec<Instruction> {
+        let used: Vec<_> = Instruction::ALL
+            .into_iter()
+            .filter(|&instruction| self.instruction_count(instruction) > 0)
+            .filter_map(Instruction::variant)
+            .collect();
+        Instruction::ALL
+            .into_iter()
+            .filter(|instruction| {
+                instruction
+                    .variant()
+                    .is_none_or(|variant| used.contains(&variant))
+            })
+            .filter(|&instruction| self.instruction_count(instruction) == 0)
+            .collect()
+    }
+
+    ///
+    /// Returns how many of the `len` bytes of the ROM loaded at `start` were executed.
+    ///
+    pub fn executed_bytes(&self, start: u16, len: usize) -> usize {
+        (0..len)
+            .filter(|&offset| self.covers(start.wrapping_add(offset as u16)))
+            .count()
+    }
+
+    ///
+    /// Sums the coverage up for the `len` bytes of the ROM loaded at `start`, e.g.
+    /// `312/840 ROM bytes executed, 14 opcodes never used`.
+    ///
+    pub fn summary(&self, start: u16, len: usize) -> String {
+        format!(
+            "{}/{len} ROM bytes executed, {} opcodes never used",
+            self.executed_bytes(start, len),
+            self.never_used().len()
+        )
+    }
+
+    ///
+    /// Writes the coverage of the `len` bytes of the ROM loaded at `start` as a JSON object, on
+    /// a single line: the executed addresses, the count of every instruction and the ones never
+    /// used.
+    ///
+    pub fn write_json(&self, start: u16, len: usize, out: &mut String) {
+        let _ = write!(
+            out,
+            "{{\"rom_start\":{start},\"rom_size\":{len},\"executed_bytes\":{},\"executed\":[",
+            self.executed_bytes(start, len)
+        );
+        for (n, address) in self.executed_addresses().enumerate() {
+            let separator = if n == 0 { "" } else { "," };
+            let _ = write!(out, "{separator}{address}");
+        }
+        out.push_str("],\"instructions\":{");
+        for (n, instruction) in Instruction::ALL.into_iter().enumerate() {
+            let separator = if n == 0 { "" } else { "," };
+            let _ = write!(
+                out,
+                "{separator}\"{instruction:?}\":{}",
+                self.instruction_count(instruction)
+            );
+        }
+        out.push_str("},\"never_used\":[");
+        for (n, instruction) in self.never_used().into_iter().enumerate() {
+            let separator = if n == 0 { "" } else { "," };
+            let _ = write!(out, "{separator}\"{instruction:?}\"");
+        }
+        out.push_str("]}");
+    }
+
+    ///
+    /// Reads back a report `write_json` wrote.
+    ///
+    pub fn from_json(text: &str) -> Result<Self, CoverageError> {
+        let error = |reason: &str| CoverageError(reason.to_string());
+        let report = Json::parse(text.trim()).map_err(error)?;
+        let mut coverage = Self::new();
+
+        let executed = report
+            .get("executed")
+            .and_then(Json::as_array)
+            .ok_or_else(|| error("there's no `executed` array"))?;
+        for address in executed {
+            let address = address
+                .as_number()
+                .and_then(|address| u16::try_from(address).ok())
+                .filter(|&address| usize::from(address) < RAM_SIZE)
+                .ok_or_else(|| error("`executed` holds a value that isn't an address"))?;
+            let address = usize::from(address);
+            coverage.executed[address / 64] |= 1 << (address % 64);
+        }
+
+        let instructions = report
+            .get("instructions")
+            .map(|instructions| {
+                instructions
+                    .as_object()
+                    .ok_or_else(|| error("`instructions` isn't an object"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+        for (name, count) in instructions {
+            let instruction = breakpoint::parse_instruction(name)
+                .map_err(|_| CoverageError(format!("there's no instruction `{name}`")))?;
+            coverage.instructions[instruction as usize] = count
+                .as_number()
+                .ok_or_else(|| CoverageError(format!("the count of `{name}` isn't a number")))?;
+        }
+        Ok(coverage)
+    }
+}
+
+#[cfg(test)]
+mod coverage_tests {
+    use super::{Coverage, CoverageError};
+    use crate::emu::chip8::Chip8;
+    use crate::emu::cpu::{Instruction, INSTRUCTION_COUNT};
+
+    // LD V0, 0x01; SE V0, 0x01; LD V1, 0x02 (always skipped); JMP 0x206
+    const ROM: [u8; 8] = [0x60, 0x01, 0x30, 0x01, 0x61, 0x02, 0x12, 0x06];
+
+    fn covered() -> Coverage {
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .coverage(true)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&ROM).unwrap();
+        chip8.run_cycles(10).unwrap();
+        chip8.coverage().unwrap().clone()
+    }
+
+    #[test]
+    fn records_what_was_executed() {
+        let coverage = covered();
+        assert!(coverage.is_executed(0x200));
+        assert!(coverage.is_executed(0x202));
+        assert!(!coverage.is_executed(0x204));
+        assert!(coverage.is_executed(0x206));
+        assert!(coverage.covers(0x203));
+        assert!(!coverage.covers(0x205));
+        assert_eq!(
+            coverage.executed_addresses().collect::<Vec<_>>(),
+            [0x200, 0x202, 0x206]
+        );
+        assert_eq!(coverage.instruction_count(Instruction::JMP), 8);
+        assert_eq!(coverage.instruction_count(Instruction::LD), 1);
+        // The instructions of MegaChip and CHIP-8X aren't missed by a ROM that uses none of them
+        let variants = Instruction::ALL
+            .iter()
+            .filter(|instruction| instruction.variant().is_some());
+        assert_eq!(
+            coverage.never_used().len(),
+            INSTRUCTION_COUNT - variants.count() - 3
+        );
+        assert!(!coverage.never_used().contains(&Instruction::SE));
+        assert!(!coverage.never_used().contains(&Instruction::MEGAON));
+        assert_eq!(
+            coverage.summary(0x200, ROM.len()),
+            "6/8 ROM bytes executed, 31 opcodes never used"
+        );
+    }
+
+    #[test]
+    fn json_report_round_trips() {
+        let coverage = covered();
+        let mut json = String::new();
+        coverage.write_json(0x200, ROM.len(), &mut json);
+        assert!(json.starts_with(
+            "{\"rom_start\":512,\"rom_size\":8,\"executed_bytes\":6,\"executed\":[512,514,518],\"instructions\":{\"CLS\":0,"
+        ));
+        assert!(json.contains(",\"JMP\":8,"));
+        assert!(json.contains(",\"never_used\":[\"CLS\",\"RET\",\"JMPV0\",\"CALL\",\"LDR\","));
+        assert!(json.ends_with(",\"DRW\",\"SKP\",\"SKNP\"]}"));
+        assert_eq!(Coverage::from_json(&json), Ok(coverage));
+
+        assert_eq!(
+            Coverage::from_json("{\"executed\":[512,9999]}"),
+            Err(CoverageError(
+                "`executed` holds a value that isn't an address".to_string()
+            ))
+        );
+        assert_eq!(
+            Coverage::from_json("{\"executed\":[],\"instructions\":{\"XOR2\":1}}")
+                .unwrap_err()
+                .to_string(),
+            "Invalid coverage report: there's no instruction `XOR2`!"
+        );
+        assert!(Coverage::from_json("[]").is_err());
+    }
+}
@@ -0,0 +1,1782 @@
+use core::fmt;
+
+use super::chip8x::ColorZones;
+use super::gfx::{self, BlitOptions};
+use super::io::{
+    DisplayError, Keyboard, Screen, GRID_HEIGHT, GRID_WIDTH, MEGA_GRID_HEIGHT, MEGA_GRID_WIDTH,
+};
+use super::mega::{Blend, ColorPalette, MegaState};
+use super::memory::{Ram, RamError, Registers, HIRES_SIGNATURE, HIRES_START_ADDRESS, RAM_SIZE};
+#[cfg(feature = "std")]
+use super::poke::{Poke, Pokes};
+use super::quirks::{Quirks, Variant};
+#[cfg(feature = "std")]
+use super::rom::RomError;
+
+///
+/// The `WORD_SIZE` constant is the chip8's word size.
+///
+pub const WORD_SIZE: u16 = 2;
+
+///
+/// The `HIRES_CLS` constant is the opcode HiRes CHIP-8 clears its 64×64 screen with, a call to
+/// the routine of its interpreter patch.
+///
+pub const HIRES_CLS: u16 = 0x0230;
+
+///
+/// The `Chip8Error` enum represents the possible errors that can occur when running the CHIP-8 emulator.
+///
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// The opcode at `pc` isn't an instruction
+    UnknownOpcode {
+        pc: u16,
+        opcode: u16,
+    },
+    /// The instruction at `pc` accessed memory or the stack out of bounds
+    MemoryFault {
+        pc: u16,
+        opcode: u16,
+        instruction: Instruction,
+        err: RamError,
+    },
+    InvalidMemoryAccess(RamError),
+    EmptyRom,
+    RomTooLarge(usize),
+    InvalidSpeed(u32),
+    InvalidLoadAddress(u16),
+    FailedToInitDisplay(DisplayError),
+    /// The ROM file is an archive or hex text that couldn't be read
+    #[cfg(feature = "std")]
+    InvalidRom(RomError),
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidMemoryAccess(err) | Self::MemoryFault { err, .. } => Some(err),
+            Self::FailedToInitDisplay(err) => Some(err),
+            Self::InvalidRom(err) => Some(err),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOpcode { pc, opcode } => {
+                write!(f, "Unknown opcode 0x{opcode:04X} at PC=0x{pc:04X}!")
+            }
+            Self::MemoryFault {
+                pc,
+                opcode,
+                instruction,
+                err,
+            } => write!(
+                f,
+                "{instruction:?} 0x{opcode:04X} at PC=0x{pc:04X} failed: {err}"
+            ),
+            Self::InvalidMemoryAccess(err) => write!(f, "Invalid memory access: {err}"),
+            Self::EmptyRom => write!(f, "The ROM is empty!"),
+            Self::RomTooLarge(size) => {
+                write!(f, "The ROM is too large to fit in RAM ({size} bytes)!")
+            }
+            Self::InvalidSpeed(ips) => write!(f, "Invalid speed of {ips} instructions per second!"),
+            Self::InvalidLoadAddress(address) => {
+                write!(f, "Invalid load address 0x{address:04X}!")
+            }
+            Self::FailedToInitDisplay(err) => write!(f, "{err}"),
+            #[cfg(feature = "std")]
+            Self::InvalidRom(err) => write!(f, "{err}"),
+            #[cfg(feature = "std")]
+            Self::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<RamError> for Chip8Error {
+    fn from(err: RamError) -> Self {
+        Self::InvalidMemoryAccess(err)
+    }
+}
+
+impl From<DisplayError> for Chip8Error {
+    fn from(err: DisplayError) -> Self {
+        Self::FailedToInitDisplay(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<RomError> for Chip8Error {
+    fn from(err: RomError) -> Self {
+        Self::InvalidRom(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Chip8Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+///
+/// The `Instruction` enum represents the set of instructions supported by the Chip8 emulator.
+///
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    CLS,
+    RET,
+    JMP,
+    JMPV0,
+    CALL,
+    LD,
+    LDR,
+    LDRI,
+    LDRDT,
+    LDDTR,
+    LDRST,
+    LDK,
+    LDSR,
+    LDB,
+    LDRIR,
+    LDRRI,
+    SE,
+    SER,
+    SNE,
+    SNER,
+    ADD,
+    ADDR,
+    ADDRI,
+    SUB,
+    SUBN,
+    AND,
+    OR,
+    XOR,
+    SHR,
+    SHL,
+    RND,
+    DRW,
+    SKP,
+    SKNP,
+    /// MegaChip's, only decoded for it
+    MEGAOFF,
+    MEGAON,
+    LDHI,
+    LDPAL,
+    SPRW,
+    SPRH,
+    ALPHA,
+    DIGISND,
+    STOPSND,
+    BMODE,
+    CCOL,
+    /// CHIP-8X's, only decoded for it
+    BGCOL,
+    COLZONE,
+    COLROWS,
+    SKP2,
+    SKNP2,
+    OUT,
+    IN,
+}
+
+///
+/// The `INSTRUCTION_COUNT` value is how many instructions there are, for arrays indexed by them.
+///
+pub const INSTRUCTION_COUNT: usize = 52;
+
+impl Instruction {
+    ///
+    /// The `ALL` constant lists every instruction, each at the index of its discriminant.
+    ///
+    pub const ALL: [Self; INSTRUCTION_COUNT] = [
+        Self::CLS,
+        Self::RET,
+        Self::JMP,
+        Self::JMPV0,
+        Self::CALL,
+        Self::LD,
+        Self::LDR,
+        Self::LDRI,
+        Self::LDRDT,
+        Self::LDDTR,
+        Self::LDRST,
+        Self::LDK,
+        Self::LDSR,
+        Self::LDB,
+        Self::LDRIR,
+        Self::LDRRI,
+        Self::SE,
+        Self::SER,
+        Self::SNE,
+        Self::SNER,
+        Self::ADD,
+        Self::ADDR,
+        Self::ADDRI,
+        Self::SUB,
+        Self::SUBN,
+        Self::AND,
+        Self::OR,
+        Self::XOR,
+        Self::SHR,
+        Self::SHL,
+        Self::RND,
+        Self::DRW,
+        Self::SKP,
+        Self::SKNP,
+        Self::MEGAOFF,
+        Self::MEGAON,
+        Self::LDHI,
+        Self::LDPAL,
+        Self::SPRW,
+        Self::SPRH,
+        Self::ALPHA,
+        Self::DIGISND,
+        Self::STOPSND,
+        Self::BMODE,
+        Self::CCOL,
+        Self::BGCOL,
+        Self::COLZONE,
+        Self::COLROWS,
+        Self::SKP2,
+        Self::SKNP2,
+        Self::OUT,
+        Self::IN,
+    ];
+
+    ///
+    /// Returns the variant the instruction is only decoded for, none for the ones every variant
+    /// has.
+    ///
+    pub const fn variant(self) -> Option<Variant> {
+        match self {
+            Self::MEGAOFF
+            | Self::MEGAON
+            | Self::LDHI
+            | Self::LDPAL
+            | Self::SPRW
+            | Self::SPRH
+            | Self::ALPHA
+            | Self::DIGISND
+            | Self::STOPSND
+            | Self::BMODE
+            | Self::CCOL => Some(Variant::MegaChip),
+            Self::BGCOL
+            | Self::COLZONE
+            | Self::COLROWS
+            | Self::SKP2
+            | Self::SKNP2
+            | Self::OUT
+            | Self::IN => Some(Variant::Chip8X),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns the opcode pattern of the instruction, e.g. `Dxyn` for `DRW`.
+    ///
+    pub const fn pattern(self) -> &'static str {
+        match self {
+            Self::CLS => "00E0",
+            Self::RET => "00EE",
+            Self::JMP => "1nnn",
+            Self::JMPV0 => "Bnnn",
+            Self::CALL => "2nnn",
+            Self::LD => "6xkk",
+            Self::LDR => "8xy0",
+            Self::LDRI => "Annn",
+            Self::LDRDT => "Fx07",
+            Self::LDDTR => "Fx15",
+            Self::LDRST => "Fx18",
+            Self::LDK => "Fx0A",
+            Self::LDSR => "Fx29",
+            Self::LDB => "Fx33",
+            Self::LDRIR => "Fx55",
+            Self::LDRRI => "Fx65",
+            Self::SE => "3xkk",
+            Self::SER => "5xy0",
+            Self::SNE => "4xkk",
+            Self::SNER => "9xy0",
+            Self::ADD => "7xkk",
+            Self::ADDR => "8xy4",
+            Self::ADDRI => "Fx1E",
+            Self::SUB => "8xy5",
+            Self::SUBN => "8xy7",
+            Self::AND => "8xy2",
+            Self::OR => "8xy1",
+            Self::XOR => "8xy3",
+            Self::SHR => "8xy6",
+            Self::SHL => "8xyE",
+            Self::RND => "Cxkk",
+            Self::DRW => "Dxyn",
+            Self::SKP => "Ex9E",
+            Self::SKNP => "ExA1",
+            Self::MEGAOFF => "0010",
+            Self::MEGAON => "0011",
+            Self::LDHI => "01nn",
+            Self::LDPAL => "02nn",
+            Self::SPRW => "03nn",
+            Self::SPRH => "04nn",
+            Self::ALPHA => "05nn",
+            Self::DIGISND => "060n",
+            Self::STOPSND => "0700",
+            Self::BMODE => "080n",
+            Self::CCOL => "09nn",
+            Self::BGCOL => "02A0",
+            Self::COLZONE => "Bxy0",
+            Self::COLROWS => "Bxyn",
+            Self::SKP2 => "ExF2",
+            Self::SKNP2 => "ExF5",
+            Self::OUT => "FxF8",
+            Self::IN => "FxFB",
+        }
+    }
+}
+
+///
+/// The `RandomSource` trait represents where `RND` gets its random numbers from.
+///
+pub trait RandomSource {
+    fn random_byte(&mut self) -> u8;
+}
+
+impl<R: rand::RngCore> RandomSource for R {
+    fn random_byte(&mut self) -> u8 {
+        // Every byte, 0xFF included
+        rand::Rng::gen(self)
+    }
+}
+
+///
+/// The `SequenceRng` struct represents a random source for tests, handing out the bytes it was
+/// given in order and starting over after the last one.
+///
+/// ```
+/// use chip8_emu::{Chip8, SequenceRng};
+///
+/// let mut chip8 = Chip8::builder()
+///     .headless(true)
+///     .random_source(Box::new(SequenceRng::new([0xA5, 0xFF])))
+///     .build()
+///     .unwrap();
+/// // RND V0, 0x0F; RND V1, 0xFF
+/// chip8.load_rom_bytes(&[0xC0, 0x0F, 0xC1, 0xFF]).unwrap();
+/// chip8.step().unwrap();
+/// chip8.step().unwrap();
+/// assert_eq!((chip8.v(0), chip8.v(1)), (0x05, 0xFF));
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceRng<const N: usize> {
+    bytes: [u8; N],
+    next: usize,
+}
+
+impl<const N: usize> SequenceRng<N> {
+    pub const fn new(bytes: [u8; N]) -> Self {
+        Self { bytes, next: 0 }
+    }
+}
+
+impl<const N: usize> RandomSource for SequenceRng<N> {
+    ///
+    /// Returns the next byte of the sequence, or 0 for an empty one.
+    ///
+    fn random_byte(&mut self) -> u8 {
+        let Some(&byte) = self.bytes.get(self.next) else {
+            return 0;
+        };
+        self.next = (self.next + 1) % N;
+        byte
+    }
+}
+
+///
+/// The `Clock` trait represents what counts the delay and sound timers down at 60 Hz.
+///
+pub trait Clock {
+    ///
+    /// Returns how many 60 Hz ticks elapsed since the previous call.
+    ///
+    fn ticks(&mut self) -> u32;
+}
+
+///
+/// The `Cpu` structure represents the bare machine: memory, registers and the keypad.
+///
+/// It needs neither an allocator nor an OS: the screen and the random numbers are passed in, and
+/// the timers only move when `tick_timers` is called.
+///
+pub struct Cpu {
+    ram: Ram,
+    registers: Registers,
+    keyboard: Keyboard,
+    // CHIP-8X's second keypad
+    second_keyboard: Keyboard,
+    quirks: Quirks,
+    load_address: u16,
+    // Whether it's HiRes CHIP-8, starting its ROMs past their header and clearing with `0230`
+    hires: bool,
+    // Whether it's MegaChip, decoding the instructions of its mega mode
+    megachip: bool,
+    mega: MegaState,
+    // Whether it's CHIP-8X, decoding the instructions of its color board and second keypad
+    chip8x: bool,
+    colors: ColorZones,
+}
+
+impl Cpu {
+    pub fn new(quirks: Quirks, load_address: u16) -> Self {
+        let mut cpu = Self {
+            ram: Ram::new(),
+            registers: Registers::new(),
+            keyboard: Keyboard::new(),
+            second_keyboard: Keyboard::new(),
+            quirks,
+            load_address,
+            hires: false,
+            megachip: false,
+            mega: MegaState::new(),
+            chip8x: false,
+            colors: ColorZones::new(),
+        };
+        cpu.reset();
+        cpu
+    }
+
+    ///
+    /// Restarts the machine from a blank state, without any ROM.
+    ///
+    pub fn reset(&mut self) {
+        self.ram = Ram::new();
+        self.registers = Registers::new();
+        self.registers.pc = self.load_address;
+        self.keyboard.release_key();
+        self.second_keyboard.release_key();
+        self.mega = MegaState::new();
+        self.colors = ColorZones::new();
+    }
+
+    ///
+    /// Checks that the ROM isn't empty and fits in memory above the load address.
+    ///
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), Chip8Error> {
+        if rom.is_empty() {
+            return Err(Chip8Error::EmptyRom);
+        }
+        if rom.len() > RAM_SIZE - usize::from(self.load_address) {
+            return Err(Chip8Error::RomTooLarge(rom.len()));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Restarts the machine with the ROM. The machine is left untouched when the ROM is invalid.
+    ///
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        self.check_rom(rom)?;
+        self.reset();
+        self.ram.load_at(usize::from(self.load_address), rom)?;
+        if self.hires && rom.starts_with(&HIRES_SIGNATURE) {
+            self.registers.pc = HIRES_START_ADDRESS;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Makes the machine HiRes CHIP-8 or not, from the next `load_rom` on.
+    ///
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+    }
+
+    pub const fn hires(&self) -> bool {
+        self.hires
+    }
+
+    ///
+    /// Makes the machine MegaChip or not: whether it decodes the instructions of mega mode.
+    ///
+    pub fn set_megachip(&mut self, megachip: bool) {
+        self.megachip = megachip;
+    }
+
+    pub const fn megachip(&self) -> bool {
+        self.megachip
+    }
+
+    ///
+    /// Returns whether MegaChip's mega mode is on, the screen being `MEGA_GRID_WIDTH` ×
+    /// `MEGA_GRID_HEIGHT` values of the palette.
+    ///
+    pub const fn mega_mode(&self) -> bool {
+        self.mega.on
+    }
+
+    pub const fn mega(&self) -> &MegaState {
+        &self.mega
+    }
+
+    pub const fn palette(&self) -> &ColorPalette {
+        &self.mega.palette
+    }
+
+    ///
+    /// Makes the machine CHIP-8X or not: whether it decodes the instructions of its color board
+    /// and second keypad.
+    ///
+    pub fn set_chip8x(&mut self, chip8x: bool) {
+        self.chip8x = chip8x;
+    }
+
+    pub const fn chip8x(&self) -> bool {
+        self.chip8x
+    }
+
+    pub const fn color_zones(&self) -> &ColorZones {
+        &self.colors
+    }
+
+    ///
+    /// Fetches, decodes and executes exactly one instruction, returning it.
+    ///
+    pub fn step(
+        &mut self,
+        screen: &mut dyn Screen,
+        rng: &mut dyn RandomSource,
+    ) -> Result<Instruction, Chip8Error> {
+        let opcode = self.fetch()?;
+        let instruction = self.decode_at_pc(opcode)?;
+        self.execute(instruction, opcode, screen, rng)?;
+        Ok(instruction)
+    }
+
+    ///
+    /// Counts the delay and sound timers down by `ticks` 60 Hz ticks.
+    ///
+    pub fn tick_timers(&mut self, ticks: u32) {
+        let ticks = u8::try_from(ticks).unwrap_or(u8::MAX);
+        self.registers.dt = self.registers.dt.saturating_sub(ticks);
+        self.registers.st = self.registers.st.saturating_sub(ticks);
+    }
+
+    ///
+    /// Presses or releases a hex key of the keypad.
+    ///
+    pub fn set_key(&mut self, hex_key: u8, pressed: bool) {
+        if pressed {
+            self.keyboard.press_hex_key(hex_key);
+        } else if self.keyboard.is_key_pressed(hex_key & 0xF) {
+            self.keyboard.release_key();
+        }
+    }
+
+    ///
+    /// Presses or releases a hex key of CHIP-8X's second keypad.
+    ///
+    pub fn set_second_key(&mut self, hex_key: u8, pressed: bool) {
+        if pressed {
+            self.second_keyboard.press_hex_key(hex_key);
+        } else if self.second_keyboard.is_key_pressed(hex_key & 0xF) {
+            self.second_keyboard.release_key();
+        }
+    }
+
+    pub fn release_keys(&mut self) {
+        self.keyboard.release_key();
+        self.second_keyboard.release_key();
+    }
+
+    pub const fn pressed_key(&self) -> Option<u8> {
+        self.keyboard.get_pressed_key()
+    }
+
+    pub const fn load_address(&self) -> u16 {
+        self.load_address
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub const fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub const fn ram(&self) -> &Ram {
+        &self.ram
+    }
+
+    pub const fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn ram_mut(&mut self) -> &mut Ram {
+        &mut self.ram
+    }
+
+    ///
+    /// Moves the PC past the instruction at `pc`, which faulted, to go on as if it did nothing.
+    ///
+    #[cfg(feature = "std")]
+    pub const fn skip_instruction(&mut self, pc: u16) {
+        self.registers.pc = pc + WORD_SIZE;
+    }
+
+    ///
+    /// Puts the machine back in a saved state, keeping the ROM it was loaded with for resets.
+    ///
+    #[cfg(feature = "std")]
+    pub fn restore(&mut self, quirks: Quirks, registers: Registers, ram: &[u8; RAM_SIZE]) {
+        self.quirks = quirks;
+        self.registers = registers;
+        // Can't fail, the RAM is filled from its start
+        let _ = self.ram.load_at(0, ram);
+        self.release_keys();
+    }
+
+    #[cfg(feature = "std")]
+    pub fn poke(&mut self, poke: Poke) {
+        poke.apply(&mut self.ram);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn apply_pokes(&mut self, pokes: &Pokes) {
+        pokes.apply(&mut self.ram);
+    }
+
+    pub fn fetch(&self) -> Result<u16, Chip8Error> {
+        let opcode = self.ram.read_word(self.registers.pc as usize)?;
+        Ok(opcode)
+    }
+
+    ///
+    /// Decodes `opcode` as the instruction at the PC, the error saying where it was.
+    ///
+    pub fn decode_at_pc(&self, opcode: u16) -> Result<Instruction, Chip8Error> {
+        self.decode_opcode(opcode).ok_or(Chip8Error::UnknownOpcode {
+            pc: self.registers.pc,
+            opcode,
+        })
+    }
+
+    ///
+    /// Decodes `opcode` as this machine runs it: HiRes CHIP-8 also clears the screen with `0230`,
+    /// MegaChip has the instructions of its mega mode and CHIP-8X the ones of its color board.
+    ///
+    pub const fn decode_opcode(&self, opcode: u16) -> Option<Instruction> {
+        if self.hires && opcode == HIRES_CLS {
+            Some(Instruction::CLS)
+        } else if let (true, Some(instruction)) = (self.megachip, Self::decode_mega(opcode)) {
+            Some(instruction)
+        } else if let (true, Some(instruction)) = (self.chip8x, Self::decode_chip8x(opcode)) {
+            Some(instruction)
+        } else {
+            Self::decode(opcode)
+        }
+    }
+
+    ///
+    /// Decodes the MegaChip instructions, the `0nnn` that aren't CHIP-8's. `LDHI` takes the word
+    /// after it too.
+    ///
+    pub const fn decode_mega(opcode: u16) -> Option<Instruction> {
+        match opcode {
+            0x0010 => Some(Instruction::MEGAOFF),
+            0x0011 => Some(Instruction::MEGAON),
+            0x0100..=0x01FF => Some(Instruction::LDHI),
+            0x0200..=0x02FF => Some(Instruction::LDPAL),
+            0x0300..=0x03FF => Some(Instruction::SPRW),
+            0x0400..=0x04FF => Some(Instruction::SPRH),
+            0x0500..=0x05FF => Some(Instruction::ALPHA),
+            0x0600..=0x060F => Some(Instruction::DIGISND),
+            0x0700 => Some(Instruction::STOPSND),
+            0x0800..=0x080F => Some(Instruction::BMODE),
+            0x0900..=0x09FF => Some(Instruction::CCOL),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Decodes the CHIP-8X instructions: `02A0`, the `Bxyn` that take the place of `JMP V0` and
+    /// the second keypad and port ones.
+    ///
+    pub const fn decode_chip8x(opcode: u16) -> Option<Instruction> {
+        match (opcode & 0xF000, opcode & 0x00FF) {
+            _ if opcode == 0x02A0 => Some(Instruction::BGCOL),
+            _ if opcode & 0xF00F == 0xB000 => Some(Instruction::COLZONE),
+            (0xB000, _) => Some(Instruction::COLROWS),
+            (0xE000, 0xF2) => Some(Instruction::SKP2),
+            (0xE000, 0xF5) => Some(Instruction::SKNP2),
+            (0xF000, 0xF8) => Some(Instruction::OUT),
+            (0xF000, 0xFB) => Some(Instruction::IN),
+            _ => None,
+        }
+    }
+
+    pub const fn decode(opcode: u16) -> Option<Instruction> {
+        if opcode == 0x00E0 {
+            Some(Instruction::CLS)
+        } else if opcode == 0x00EE {
+            Some(Instruction::RET)
+        } else if (opcode & 0xF000) == 0x1000 {
+            Some(Instruction::JMP)
+        } else if (opcode & 0xF000) == 0x2000 {
+            Some(Instruction::CALL)
+        } else if (opcode & 0xF000) == 0x3000 {
+            Some(Instruction::SE)
+        } else if (opcode & 0xF000) == 0x4000 {
+            Some(Instruction::SNE)
+        } else if (opcode & 0xF000) == 0x5000 {
+            Some(Instruction::SER)
+        } else if (opcode & 0xF000) == 0x6000 {
+            Some(Instruction::LD)
+        } else if (opcode & 0xF000) == 0x7000 {
+            Some(Instruction::ADD)
+        } else if (opcode & 0xF000) == 0x8000 {
+            match (opcode & 0x000F) as u8 {
+                0x0 => Some(Instruction::LDR),
+                0x1 => Some(Instruction::OR),
+                0x2 => Some(Instruction::AND),
+                0x3 => Some(Instruction::XOR),
+                0x4 => Some(Instruction::ADDR),
+                0x5 => Some(Instruction::SUB),
+                0x6 => Some(Instruction::SHR),
+                0x7 => Some(Instruction::SUBN),
+                0xE => Some(Instruction::SHL),
+                _ => None,
+            }
+        } else if (opcode & 0xF000) == 0x9000 {
+            Some(Instruction::SNER)
+        } else if (opcode & 0xF000) == 0xA000 {
+            Some(Instruction::LDRI)
+        } else if (opcode & 0xF000) == 0xB000 {
+            Some(Instruction::JMPV0)
+        } else if (opcode & 0xF000) == 0xC000 {
+            Some(Instruction::RND)
+        } else if (opcode & 0xF000) == 0xD000 {
+            Some(Instruction::DRW)
+        } else if (opcode & 0xF000) == 0xE000 {
+            match (opcode & 0x00FF) as u8 {
+                0x9E => Some(Instruction::SKP),
+                0xA1 => Some(Instruction::SKNP),
+                _ => None,
+            }
+        } else if (opcode & 0xF000) == 0xF000 {
+            match (opcode & 0x00FF) as u8 {
+                0x07 => Some(Instruction::LDRDT),
+                0x0A => Some(Instruction::LDK),
+                0x15 => Some(Instruction::LDDTR),
+                0x18 => Some(Instruction::LDRST),
+                0x1E => Some(Instruction::ADDRI),
+                0x29 => Some(Instruction::LDSR),
+                0x33 => Some(Instruction::LDB),
+                0x55 => Some(Instruction::LDRIR),
+                0x65 => Some(Instruction::LDRRI),
+                _ => None,
+            }
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// Executes the decoded instruction, drawing into `screen` and drawing random numbers from
+    /// `rng`.
+    ///
+    pub fn execute(
+        &mut self,
+        instruction: Instruction,
+        opcode: u16,
+        screen: &mut dyn Screen,
+        rng: &mut dyn RandomSource,
+    ) -> Result<(), Chip8Error> {
+        let pc = self.registers.pc;
+        self.execute_instruction(instruction, opcode, screen, rng)
+            .map_err(|err| Chip8Error::MemoryFault {
+                pc,
+                opcode,
+                instruction,
+                err,
+            })
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn execute_instruction(
+        &mut self,
+        instruction: Instruction,
+        opcode: u16,
+        screen: &mut dyn Screen,
+        rng: &mut dyn RandomSource,
+    ) -> Result<(), RamError> {
+        match instruction {
+            Instruction::CLS => {
+                self.cls(screen);
+                Ok(())
+            }
+            Instruction::RET => self.ret(),
+            Instruction::JMP => {
+                self.jmp(opcode);
+                Ok(())
+            }
+            Instruction::CALL => self.call(opcode),
+            Instruction::SE => {
+                self.se(opcode);
+                Ok(())
+            }
+            Instruction::SNE => {
+                self.sne(opcode);
+                Ok(())
+            }
+            Instruction::SER => {
+                self.ser(opcode);
+                Ok(())
+            }
+            Instruction::LD => {
+                self.ld(opcode);
+                Ok(())
+            }
+            Instruction::ADD => {
+                self.add(opcode);
+                Ok(())
+            }
+            Instruction::LDR => {
+                self.ldr(opcode);
+                Ok(())
+            }
+            Instruction::OR => {
+                self.or(opcode);
+                Ok(())
+            }
+            Instruction::AND => {
+                self.and(opcode);
+                Ok(())
+            }
+            Instruction::XOR => {
+                self.xor(opcode);
+                Ok(())
+            }
+            Instruction::ADDR => {
+                self.addr(opcode);
+                Ok(())
+            }
+            Instruction::SUB => {
+                self.sub(opcode);
+                Ok(())
+            }
+            Instruction::SHR => {
+                self.shr(opcode);
+                Ok(())
+            }
+            Instruction::SUBN => {
+                self.subn(opcode);
+                Ok(())
+            }
+            Instruction::SHL => {
+                self.shl(opcode);
+                Ok(())
+            }
+            Instruction::SNER => {
+                self.sner(opcode);
+                Ok(())
+            }
+            Instruction::LDRI => {
+                self.ldri(opcode);
+                Ok(())
+            }
+            Instruction::JMPV0 => {
+                self.jmpv0(opcode);
+                Ok(())
+            }
+            Instruction::RND => {
+                self.rnd(rng, opcode);
+                Ok(())
+            }
+            Instruction::DRW if self.mega.on => self.drw_mega(screen, opcode),
+            Instruction::DRW => self.drw(screen, opcode),
+            Instruction::SKP => {
+                self.skp(opcode);
+                Ok(())
+            }
+            Instruction::SKNP => {
+                self.sknp(opcode);
+                Ok(())
+            }
+            Instruction::LDRDT => {
+                self.ldrdt(opcode);
+                Ok(())
+            }
+            Instruction::LDK => {
+                self.ldk(opcode);
+                Ok(())
+            }
+            Instruction::LDDTR => {
+                self.lddtr(opcode);
+                Ok(())
+            }
+            Instruction::LDRST => {
+                self.ldrst(opcode);
+                Ok(())
+            }
+            Instruction::ADDRI => {
+                self.addri(opcode);
+                Ok(())
+            }
+            Instruction::LDSR => {
+                self.ldsr(opcode);
+                Ok(())
+            }
+            Instruction::LDB => self.ldb(opcode),
+            Instruction::LDRIR => self.ldrir(opcode),
+            Instruction::LDRRI => self.ldrri(opcode),
+            Instruction::MEGAOFF => {
+                self.megaoff(screen);
+                Ok(())
+            }
+            Instruction::MEGAON => {
+                self.megaon(screen);
+                Ok(())
+            }
+            Instruction::LDHI => self.ldhi(opcode),
+            Instruction::LDPAL => self.ldpal(opcode),
+            Instruction::SPRW => {
+                self.sprw(opcode);
+                Ok(())
+            }
+            Instruction::SPRH => {
+                self.sprh(opcode);
+                Ok(())
+            }
+            Instruction::ALPHA => {
+                self.alpha(opcode);
+                Ok(())
+            }
+            // There's no digitised sound to play, the buzzer stays as it was, and the only device
+            // of CHIP-8X's port was the tone generator, whose pitch there's no way to set
+            Instruction::DIGISND | Instruction::STOPSND | Instruction::OUT => {
+                self.registers.pc += WORD_SIZE;
+                Ok(())
+            }
+            Instruction::BMODE => {
+                self.bmode(opcode);
+                Ok(())
+            }
+            Instruction::CCOL => {
+                self.ccol(opcode);
+                Ok(())
+            }
+            Instruction::BGCOL => {
+                self.colors.cycle_background();
+                self.registers.pc += WORD_SIZE;
+                Ok(())
+            }
+            Instruction::COLZONE => {
+                self.colzone(opcode);
+                Ok(())
+            }
+            Instruction::COLROWS => {
+                self.colrows(opcode);
+                Ok(())
+            }
+            Instruction::SKP2 => {
+                self.skp2(opcode);
+                Ok(())
+            }
+            Instruction::SKNP2 => {
+                self.sknp2(opcode);
+                Ok(())
+            }
+            Instruction::IN => {
+                self.in_port(opcode);
+                Ok(())
+            }
+        }
+    }
+
+    fn cls(&mut self, screen: &mut dyn Screen) {
+        screen.clear();
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn ret(&mut self) -> Result<(), RamError> {
+        self.registers.pc = self.registers.pop()?;
+        Ok(())
+    }
+
+    fn jmp(&mut self, opcode: u16) {
+        self.registers.pc = opcode & 0x0FFF;
+    }
+
+    fn call(&mut self, opcode: u16) -> Result<(), RamError> {
+        self.registers.push(self.registers.pc + WORD_SIZE)?;
+        self.registers.pc = opcode & 0x0FFF;
+        Ok(())
+    }
+
+    fn se(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let val = (opcode & 0x00FF) as u8;
+
+        if self.registers.v[x] == val {
+            self.registers.pc += WORD_SIZE * 2;
+        } else {
+            self.registers.pc += WORD_SIZE;
+        }
+    }
+
+    fn sne(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let val = (opcode & 0x00FF) as u8;
+
+        if self.registers.v[x] == val {
+            self.registers.pc += WORD_SIZE;
+        } else {
+            self.registers.pc += WORD_SIZE * 2;
+        }
+    }
+
+    fn ser(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        if self.registers.v[x] == self.registers.v[y] {
+            self.registers.pc += WORD_SIZE * 2;
+        } else {
+            self.registers.pc += WORD_SIZE;
+        }
+    }
+
+    fn ld(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        self.registers.v[x] = (opcode & 0x00FF) as u8;
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn add(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let val = (opcode & 0x00FF) as u8;
+
+        self.registers.v[x] = self.registers.v[x].wrapping_add(val);
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn ldr(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        self.registers.v[x] = self.registers.v[y];
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn or(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        self.registers.v[x] |= self.registers.v[y];
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn and(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        self.registers.v[x] &= self.registers.v[y];
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn xor(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        self.registers.v[x] ^= self.registers.v[y];
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn addr(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        let sum = u16::from(self.registers.v[x]) + u16::from(self.registers.v[y]);
+        self.registers.v[x] = sum as u8;
+
+        if sum > 0xFF {
+            self.registers.v[0xF] = 1;
+        } else {
+            self.registers.v[0xF] = 0;
+        }
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn sub(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        let (vx, vy) = (self.registers.v[x], self.registers.v[y]);
+        self.registers.v[x] = vx.wrapping_sub(vy);
+        // VF is set when there's no borrow, after the result in case it's VF itself
+        self.registers.v[0xF] = u8::from(vx >= vy);
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn shr(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        let value = if self.quirks.shift {
+            self.registers.v[x]
+        } else {
+            self.registers.v[y]
+        };
+        self.registers.v[x] = value >> 1;
+        self.registers.v[0xF] = value & 0x1;
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn subn(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        let (vx, vy) = (self.registers.v[x], self.registers.v[y]);
+        self.registers.v[x] = vy.wrapping_sub(vx);
+        self.registers.v[0xF] = u8::from(vy >= vx);
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn shl(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        let value = if self.quirks.shift {
+            self.registers.v[x]
+        } else {
+            self.registers.v[y]
+        };
+        self.registers.v[x] = value << 1;
+        self.registers.v[0xF] = (value & 0x80) >> 7;
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn sner(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        if self.registers.v[x] == self.registers.v[y] {
+            self.registers.pc += WORD_SIZE;
+        } else {
+            self.registers.pc += WORD_SIZE * 2;
+        }
+    }
+
+    fn ldri(&mut self, opcode: u16) {
+        self.registers.i = opcode & 0x0FFF;
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn jmpv0(&mut self, opcode: u16) {
+        let x = if self.quirks.jump {
+            ((opcode & 0x0F00) >> 8) as usize
+        } else {
+            0
+        };
+        self.registers.pc = u16::from(self.registers.v[x]) + (opcode & 0x0FFF);
+    }
+
+    fn rnd(&mut self, rng: &mut dyn RandomSource, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let val = (opcode & 0x0FF) as u8;
+
+        let num = rng.random_byte();
+        self.registers.v[x] = num & val;
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn drw(&mut self, screen: &mut dyn Screen, opcode: u16) -> Result<(), RamError> {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = (opcode & 0x000F) as usize;
+
+        let x_pos = self.registers.v[x] as usize;
+        let y_pos = self.registers.v[y] as usize;
+
+        let sprite = self.ram.read_bytes(self.registers.i as usize, n)?;
+        let collision = gfx::blit_sprite(screen, x_pos, y_pos, sprite, BlitOptions::new());
+        self.registers.v[0xF] = u8::from(collision.any());
+
+        self.registers.pc += WORD_SIZE;
+        Ok(())
+    }
+
+    fn drw_mega(&mut self, screen: &mut dyn Screen, opcode: u16) -> Result<(), RamError> {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        let size = self.mega.sprite_width * self.mega.sprite_height;
+        let sprite = self.ram.read_bytes(self.registers.i as usize, size)?;
+        let x_pos = self.registers.v[x] as usize;
+        let y_pos = self.registers.v[y] as usize;
+        let collided = self.mega.blit(screen, x_pos, y_pos, sprite);
+        self.registers.v[0xF] = u8::from(collided);
+
+        self.registers.pc += WORD_SIZE;
+        Ok(())
+    }
+
+    fn skp(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let key = self.registers.v[x];
+
+        if self.keyboard.is_key_pressed(key) {
+            self.keyboard.release_key();
+            self.registers.pc += WORD_SIZE * 2;
+        } else {
+            self.registers.pc += WORD_SIZE;
+        }
+    }
+
+    fn sknp(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let key = self.registers.v[x];
+
+        if self.keyboard.is_key_pressed(key) {
+            self.keyboard.release_key();
+            self.registers.pc += WORD_SIZE;
+        } else {
+            self.registers.pc += WORD_SIZE * 2;
+        }
+    }
+
+    fn skp2(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let key = self.registers.v[x];
+
+        if self.second_keyboard.is_key_pressed(key) {
+            self.second_keyboard.release_key();
+            self.registers.pc += WORD_SIZE * 2;
+        } else {
+            self.registers.pc += WORD_SIZE;
+        }
+    }
+
+    fn sknp2(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let key = self.registers.v[x];
+
+        if self.second_keyboard.is_key_pressed(key) {
+            self.second_keyboard.release_key();
+            self.registers.pc += WORD_SIZE;
+        } else {
+            self.registers.pc += WORD_SIZE * 2;
+        }
+    }
+
+    fn colzone(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+
+        // Vx holds the first column of zones and how many more, V(x+1) the same of the rows
+        let columns = self.registers.v[x];
+        let rows = self.registers.v[(x + 1) & 0xF];
+        self.colors.set_zones(
+            usize::from(columns & 0x0F),
+            usize::from(columns >> 4) + 1,
+            usize::from(rows & 0x0F),
+            usize::from(rows >> 4) + 1,
+            self.registers.v[y],
+        );
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn colrows(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = (opcode & 0x000F) as usize;
+
+        self.colors.set_rows(
+            usize::from(self.registers.v[x]),
+            usize::from(self.registers.v[(x + 1) & 0xF]),
+            n,
+            self.registers.v[y],
+        );
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn in_port(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        // Nothing is plugged into the port, so there's nothing to wait for and it reads as 0
+        self.registers.v[x] = 0;
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn ldrdt(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        self.registers.v[x] = self.registers.dt;
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn ldk(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        // Without a key pressed, stay on this instruction until there is one
+        if let Some(val) = self.keyboard.get_pressed_key() {
+            self.registers.v[x] = val;
+            self.registers.pc += WORD_SIZE;
+        }
+    }
+
+    fn lddtr(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        self.registers.dt = self.registers.v[x];
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn ldrst(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        self.registers.st = self.registers.v[x];
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn addri(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        self.registers.i = self
+            .registers
+            .i
+            .wrapping_add(u16::from(self.registers.v[x]));
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn ldsr(&mut self, opcode: u16) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        // 5 because each sprite has 5 lines
+        self.registers.i = u16::from(self.registers.v[x]) * 5;
+
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn ldb(&mut self, opcode: u16) -> Result<(), RamError> {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        self.ram
+            .write_byte(self.registers.i as usize, self.registers.v[x] / 100)?;
+        self.ram
+            .write_byte(self.registers.i as usize + 1, self.registers.v[x] / 10 % 10)?;
+        self.ram
+            .write_byte(self.registers.i as usize + 2, self.registers.v[x] % 10)?;
+
+        self.registers.pc += WORD_SIZE;
+        Ok(())
+    }
+
+    fn ldrir(&mut self, opcode: u16) -> Result<(), RamError> {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        for i in 0..=x {
+            self.ram
+                .write_byte(self.registers.i as usize + i, self.registers.v[i])?;
+        }
+
+        if self.quirks.memory {
+            self.registers.i += x as u16 + 1;
+        }
+        self.registers.pc += WORD_SIZE;
+        Ok(())
+    }
+
+    fn ldrri(&mut self, opcode: u16) -> Result<(), RamError> {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+
+        for i in 0..=x {
+            self.registers.v[i] = self.ram.read_byte(self.registers.i as usize + i)?;
+        }
+
+        if self.quirks.memory {
+            self.registers.i += x as u16 + 1;
+        }
+        self.registers.pc += WORD_SIZE;
+        Ok(())
+    }
+
+    fn megaoff(&mut self, screen: &mut dyn Screen) {
+        self.mega.on = false;
+        screen.set_size(GRID_WIDTH, GRID_HEIGHT);
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn megaon(&mut self, screen: &mut dyn Screen) {
+        self.mega.on = true;
+        screen.set_size(MEGA_GRID_WIDTH, MEGA_GRID_HEIGHT);
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn ldhi(&mut self, opcode: u16) -> Result<(), RamError> {
+        // The 24-bit address goes on in the word after the instruction
+        let low = self.ram.read_word(self.registers.pc as usize + 2)?;
+        let address = usize::from(opcode & 0x00FF) << 16 | usize::from(low);
+        if address >= RAM_SIZE {
+            return Err(RamError::OutOfBound(address));
+        }
+
+        self.registers.i = address as u16;
+        self.registers.pc += WORD_SIZE * 2;
+        Ok(())
+    }
+
+    fn ldpal(&mut self, opcode: u16) -> Result<(), RamError> {
+        let count = usize::from(opcode & 0x00FF);
+
+        let colors = self.ram.read_bytes(self.registers.i as usize, count * 4)?;
+        self.mega.palette.load(colors);
+
+        self.registers.pc += WORD_SIZE;
+        Ok(())
+    }
+
+    fn sprw(&mut self, opcode: u16) {
+        // 0 stands for 256
+        self.mega.sprite_width = match opcode & 0x00FF {
+            0 => 256,
+            width => usize::from(width),
+        };
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn sprh(&mut self, opcode: u16) {
+        self.mega.sprite_height = match opcode & 0x00FF {
+            0 => 256,
+            height => usize::from(height),
+        };
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn alpha(&mut self, opcode: u16) {
+        self.mega.palette.set_alpha((opcode & 0x00FF) as u8);
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn bmode(&mut self, opcode: u16) {
+        // The modes that don't exist draw as the normal one
+        self.mega.blend = Blend::from_mode((opcode & 0x000F) as u8).unwrap_or_default();
+        self.registers.pc += WORD_SIZE;
+    }
+
+    fn ccol(&mut self, opcode: u16) {
+        self.mega.collision = (opcode & 0x00FF) as u8;
+        self.registers.pc += WORD_SIZE;
+    }
+}
+
+#[cfg(test)]
+mod cpu_tests {
+    use super::{Chip8Error, Cpu, Instruction, RandomSource, SequenceRng, INSTRUCTION_COUNT};
+    use crate::emu::io::BufferScreen;
+    use crate::emu::memory::RamError;
+    use crate::emu::quirks::Quirks;
+
+    struct Fixed(u8);
+
+    impl RandomSource for Fixed {
+        fn random_byte(&mut self) -> u8 {
+            self.0
+        }
+    }
+
+    fn cpu_with(rom: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new(Quirks::default(), 0x200);
+        cpu.load_rom(rom).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn all_instructions_by_discriminant() {
+        assert_eq!(Instruction::ALL.len(), INSTRUCTION_COUNT);
+        for (index, instruction) in Instruction::ALL.iter().enumerate() {
+            assert_eq!(*instruction as usize, index);
+        }
+        assert_eq!(Instruction::DRW.pattern(), "Dxyn");
+    }
+
+    #[test]
+    fn random_numbers_come_from_the_source() {
+        // RND V0, 0x0F
+        let mut cpu = cpu_with(&[0xC0, 0x0F]);
+        let instruction = cpu
+            .step(&mut BufferScreen::new(), &mut Fixed(0xAB))
+            .unwrap();
+        assert_eq!(instruction, Instruction::RND);
+        assert_eq!(cpu.registers().v[0], 0x0B);
+    }
+
+    #[test]
+    fn rnd_masks_the_random_byte() {
+        // RND V0, 0xFF; RND V1, 0xF0; RND V2, 0x00; RND V3, 0x81; RND V4, 0xFF
+        let mut cpu = cpu_with(&[0xC0, 0xFF, 0xC1, 0xF0, 0xC2, 0x00, 0xC3, 0x81, 0xC4, 0xFF]);
+        let mut rng = SequenceRng::new([0xFF, 0x3C, 0xFF, 0xFF]);
+        for _ in 0..5 {
+            cpu.step(&mut BufferScreen::new(), &mut rng).unwrap();
+        }
+        // The last one starts the sequence over
+        assert_eq!(cpu.registers().v[..5], [0xFF, 0x30, 0x00, 0x81, 0xFF]);
+        assert_eq!(cpu.registers().pc, 0x20A);
+    }
+
+    #[test]
+    fn sequences_repeat() {
+        let mut rng = SequenceRng::new([1, 2, 3]);
+        let bytes: Vec<_> = (0..7).map(|_| rng.random_byte()).collect();
+        assert_eq!(bytes, [1, 2, 3, 1, 2, 3, 1]);
+        assert_eq!(SequenceRng::new([]).random_byte(), 0);
+    }
+
+    #[test]
+    fn generators_draw_every_byte() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut seen = [false; 256];
+        for _ in 0..10_000 {
+            seen[usize::from(rng.random_byte())] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+    }
+
+    #[test]
+    fn timers_only_move_when_ticked() {
+        // LD V0, 0x05; LD DT, V0
+        let mut cpu = cpu_with(&[0x60, 0x05, 0xF0, 0x15]);
+        for _ in 0..2 {
+            cpu.step(&mut BufferScreen::new(), &mut Fixed(0)).unwrap();
+        }
+        assert_eq!(cpu.registers().dt, 5);
+        cpu.tick_timers(2);
+        assert_eq!(cpu.registers().dt, 3);
+        cpu.tick_timers(300);
+        assert_eq!(cpu.registers().dt, 0);
+    }
+
+    #[test]
+    fn invalid_roms_leave_the_machine_alone() {
+        let mut cpu = cpu_with(&[0x60, 0x05]);
+        cpu.step(&mut BufferScreen::new(), &mut Fixed(0)).unwrap();
+        assert!(matches!(cpu.load_rom(&[]), Err(Chip8Error::EmptyRom)));
+        assert!(matches!(
+            cpu.load_rom(&[0; 0xE01]),
+            Err(Chip8Error::RomTooLarge(0xE01))
+        ));
+        assert_eq!(cpu.registers().pc, 0x202);
+        assert_eq!(cpu.registers().v[0], 0x05);
+    }
+
+    #[test]
+    fn malformed_programs_fail_without_panicking() {
+        // RET without a call
+        let mut cpu = cpu_with(&[0x00, 0xEE]);
+        assert!(matches!(
+            cpu.step(&mut BufferScreen::new(), &mut Fixed(0)),
+            Err(Chip8Error::MemoryFault {
+                pc: 0x200,
+                opcode: 0x00EE,
+                instruction: Instruction::RET,
+                err: RamError::StackUnderflow,
+            })
+        ));
+
+        // LD V0, 0x80; LD V1, 0x7F; SUBN V0, V1 overflowed as signed bytes
+        let mut cpu = cpu_with(&[0x60, 0x80, 0x61, 0x7F, 0x80, 0x17]);
+        for _ in 0..3 {
+            cpu.step(&mut BufferScreen::new(), &mut Fixed(0)).unwrap();
+        }
+        assert_eq!(cpu.registers().v[0], 0xFF);
+
+        // JMP 0xFFF, the last byte of memory, with no room for a second one
+        let mut cpu = cpu_with(&[0x1F, 0xFF]);
+        cpu.step(&mut BufferScreen::new(), &mut Fixed(0)).unwrap();
+        assert!(matches!(
+            cpu.step(&mut BufferScreen::new(), &mut Fixed(0)),
+            Err(Chip8Error::InvalidMemoryAccess(RamError::OutOfBound(0xFFF)))
+        ));
+    }
+
+    #[test]
+    fn errors_say_where_they_happened() {
+        assert_eq!(Cpu::decode(0xF1A3), None);
+        assert_eq!(Cpu::decode(0xF265), Some(Instruction::LDRRI));
+
+        // LD V0, 0x05; an unknown opcode
+        let mut cpu = cpu_with(&[0x60, 0x05, 0xF1, 0xA3]);
+        cpu.step(&mut BufferScreen::new(), &mut Fixed(0)).unwrap();
+        let err = cpu
+            .step(&mut BufferScreen::new(), &mut Fixed(0))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Chip8Error::UnknownOpcode {
+                pc: 0x202,
+                opcode: 0xF1A3
+            }
+        ));
+        assert_eq!(err.to_string(), "Unknown opcode 0xF1A3 at PC=0x0202!");
+
+        // LD I, 0xFFF; LD V0-V2, [I] reading past the end of memory
+        let mut cpu = cpu_with(&[0xAF, 0xFF, 0xF2, 0x65]);
+        cpu.step(&mut BufferScreen::new(), &mut Fixed(0)).unwrap();
+        let err = cpu
+            .step(&mut BufferScreen::new(), &mut Fixed(0))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Chip8Error::MemoryFault {
+                pc: 0x202,
+                opcode: 0xF265,
+                instruction: Instruction::LDRRI,
+                err: RamError::OutOfBound(0x1000),
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "LDRRI 0xF265 at PC=0x0202 failed: Out of bound memory at 0x1000!"
+        );
+    }
+
+    // The ALU is checked with every pair of values rather than a sample, first on two ordinary
+    // registers, then with VF as the destination and as the source
+    const REGISTER_PAIRS: [(usize, usize); 3] = [(0x1, 0x2), (0xF, 0x1), (0x1, 0xF)];
+
+    ///
+    /// Checks that `8xyN` leaves `Vx` and `VF` as `expected` says for every value `a` of `Vx` and
+    /// `b` of `Vy`, VF holding the flag whenever it's also the destination.
+    ///
+    fn check_alu(n: u16, quirks: Quirks, expected: impl Fn(u8, u8) -> (u8, u8)) {
+        let mut screen = BufferScreen::new();
+        for (x, y) in REGISTER_PAIRS {
+            let opcode = 0x8000 | (x as u16) << 8 | (y as u16) << 4 | n;
+            let instruction = Cpu::decode(opcode).unwrap();
+            let mut cpu = Cpu::new(quirks, 0x200);
+            for a in 0..=u8::MAX {
+                for b in 0..=u8::MAX {
+                    cpu.registers.pc = 0x200;
+                    cpu.registers.v = [0; 16];
+                    cpu.registers.v[x] = a;
+                    cpu.registers.v[y] = b;
+                    cpu.execute(instruction, opcode, &mut screen, &mut Fixed(0))
+                        .unwrap();
+
+                    let (result, flag) = expected(a, b);
+                    assert_eq!(cpu.registers.v[0xF], flag, "VF of {opcode:04X} on {a}, {b}");
+                    if x != 0xF {
+                        assert_eq!(cpu.registers.v[x], result, "{opcode:04X} on {a}, {b}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn add_sets_vf_on_carry() {
+        check_alu(0x4, Quirks::default(), |a, b| {
+            let sum = u16::from(a) + u16::from(b);
+            ((sum & 0xFF) as u8, u8::from(sum > 0xFF))
+        });
+    }
+
+    #[test]
+    fn sub_sets_vf_without_borrow() {
+        check_alu(0x5, Quirks::default(), |a, b| {
+            (a.wrapping_sub(b), u8::from(a >= b))
+        });
+        check_alu(0x7, Quirks::default(), |a, b| {
+            (b.wrapping_sub(a), u8::from(b >= a))
+        });
+    }
+
+    #[test]
+    fn shifts_move_the_lost_bit_into_vf() {
+        for shift in [false, true] {
+            let quirks = Quirks {
+                shift,
+                ..Quirks::default()
+            };
+            // Shifting in place shifts Vx, otherwise Vy is shifted into Vx
+            let source = move |a, b| if shift { a } else { b };
+            check_alu(0x6, quirks, |a, b| (source(a, b) >> 1, source(a, b) & 0x1));
+            check_alu(0xE, quirks, |a, b| (source(a, b) << 1, source(a, b) >> 7));
+        }
+    }
+
+    #[test]
+    fn add_immediate_leaves_vf_alone() {
+        let mut screen = BufferScreen::new();
+        let mut cpu = Cpu::new(Quirks::default(), 0x200);
+        for x in [0x1, 0xF] {
+            for kk in 0..=u8::MAX {
+                let opcode = 0x7000 | (x as u16) << 8 | u16::from(kk);
+                for a in 0..=u8::MAX {
+                    cpu.registers.pc = 0x200;
+                    cpu.registers.v[0xF] = 0xAA;
+                    cpu.registers.v[x] = a;
+                    cpu.execute(Instruction::ADD, opcode, &mut screen, &mut Fixed(0))
+                        .unwrap();
+                    assert_eq!(cpu.registers.v[x], a.wrapping_add(kk));
+                    if x != 0xF {
+                        assert_eq!(cpu.registers.v[0xF], 0xAA);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bcd_digits_recompose_the_value() {
+        let mut screen = BufferScreen::new();
+        let mut cpu = Cpu::new(Quirks::default(), 0x200);
+        cpu.registers.i = 0x300;
+        for value in 0..=u8::MAX {
+            cpu.registers.v[0x3] = value;
+            cpu.execute(Instruction::LDB, 0xF333, &mut screen, &mut Fixed(0))
+                .unwrap();
+
+            let digits = [0, 1, 2].map(|n| cpu.ram.read_byte(0x300 + n).unwrap());
+            assert!(
+                digits.iter().all(|&digit| digit < 10),
+                "{value}: {digits:?}"
+            );
+            let recomposed =
+                u16::from(digits[0]) * 100 + u16::from(digits[1]) * 10 + u16::from(digits[2]);
+            assert_eq!(recomposed, u16::from(value));
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt::{self, Write as _};
+
+use super::chip8::Chip8;
+use super::disasm;
+use super::memory::{Ram, RAM_SIZE};
+
+///
+/// The `DEFAULT_PC_HISTORY_SIZE` value is how many of the last executed instructions the machine
+/// remembers, unless configured otherwise.
+///
+pub const DEFAULT_PC_HISTORY_SIZE: usize = 64;
+
+///
+/// The `CRASH_CONTEXT` value is how many instructions a crash report disassembles on either side
+/// of the PC.
+///
+const CRASH_CONTEXT: u16 = 8;
+
+///
+/// The `PcHistory` struct represents a ring buffer of the last instructions executed, as
+/// `(pc, opcode)`. Its slots are allocated once, so recording is cheap enough to never stop.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcHistory {
+    entries: Box<[(u16, u16)]>,
+    next: usize,
+    len: usize,
+}
+
+impl Default for PcHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_PC_HISTORY_SIZE)
+    }
+}
+
+impl PcHistory {
+    ///
+    /// Creates a history of the last `size` instructions, or of none when `size` is 0.
+    ///
+    pub fn new(size: usize) -> Self {
+        Self {
+            entries: vec![(0, 0); size].into_boxed_slice(),
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, pc: u16, opcode: u16) {
+        let Some(entry) = self.entries.get_mut(self.next) else {
+            return;
+        };
+        *entry = (pc, opcode);
+        self.next = (self.next + 1) % self.entries.len();
+        self.len = (self.len + 1).min(self.entries.len());
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    ///
+    /// Returns the instructions as `(pc, opcode)`, oldest first.
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        let size = self.entries.len();
+        let oldest = (self.next + size - self.len) % size.max(1);
+        (0..self.len).map(move |n| self.entries[(oldest + n) % size])
+    }
+}
+
+///
+/// Writes the instructions of the history, oldest first, one disassembled instruction per line.
+///
+pub fn write_history(history: impl Iterator<Item = (u16, u16)>, out: &mut String) {
+    for (pc, opcode) in history {
+        let _ = writeln!(
+            out,
+            "0x{pc:04X}: {opcode:04X}  {}",
+            disasm::disassemble(pc, opcode)
+        );
+    }
+}
+
+///
+/// Returns a report of the machine as `error` left it: the error, the instruction at fault, the
+/// registers, the pending calls, the instructions around the PC and the last ones executed.
+///
+pub fn crash_report(chip8: &Chip8, error: &dyn fmt::Display) -> String {
+    let ram = chip8.cpu().ram();
+    let pc = chip8.pc();
+    let mut report = format!("Error: {error}\n");
+    let _ = writeln!(report, "Fault: {}", disasm::instruction_at(ram, pc));
+
+    let _ = writeln!(report, "\nRegisters:\n{}", chip8.registers());
+    let _ = write!(report, "\nBacktrace:\n{}", chip8.backtrace());
+
+    report.push_str("\nDisassembly:\n");
+    let first = pc.saturating_sub(CRASH_CONTEXT * 2);
+    let last = pc.saturating_add(CRASH_CONTEXT * 2);
+    for address in (first..=last).step_by(2) {
+        if usize::from(address) >= RAM_SIZE {
+            break;
+        }
+        let marker = if address == pc { "=>" } else { "  " };
+        let _ = writeln!(report, "{marker} {}", disasm::instruction_at(ram, address));
+    }
+
+    report.push_str("\nHistory, oldest first:\n");
+    write_history(chip8.pc_history(), &mut report);
+    report
+}
+
+///
+/// Writes `len` bytes of memory from `address` as lines of 16 hexadecimal bytes, each prefixed
+/// with its address, stopping at the end of the memory.
+///
+pub fn write_hex_dump(ram: &Ram, address: u16, len: usize, out: &mut String) {
+    let end = (usize::from(address) + len).min(RAM_SIZE);
+    for line in (usize::from(address)..end).step_by(16) {
+        let _ = write!(out, "0x{line:04X}:");
+        for address in line..(line + 16).min(end) {
+            let _ = write!(out, " {:02X}", ram.read_byte(address).unwrap_or_default());
+        }
+        out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod crash_tests {
+    use super::{crash_report, PcHistory, DEFAULT_PC_HISTORY_SIZE};
+    use crate::emu::chip8::Chip8;
+
+    #[test]
+    fn history_wraps_around() {
+        let mut history = PcHistory::new(4);
+        assert!(history.is_empty());
+        history.push(0x200, 0x6001);
+        history.push(0x202, 0x7001);
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            [(0x200, 0x6001), (0x202, 0x7001)]
+        );
+
+        for n in 0..10 {
+            history.push(0x300 + n * 2, n);
+        }
+        assert_eq!(history.len(), 4);
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            [(0x30C, 6), (0x30E, 7), (0x310, 8), (0x312, 9)]
+        );
+
+        history.clear();
+        assert_eq!(history.iter().count(), 0);
+    }
+
+    #[test]
+    fn empty_histories_record_nothing() {
+        let mut history = PcHistory::new(0);
+        history.push(0x200, 0x6001);
+        assert_eq!(history.iter().count(), 0);
+        assert_eq!(PcHistory::default().capacity(), DEFAULT_PC_HISTORY_SIZE);
+    }
+
+    #[test]
+    fn executed_instructions_are_recorded() {
+        // LD V0, 0x01; ADD V0, 0x01; JP 0x202
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .pc_history_size(3)
+            .build()
+            .unwrap();
+        chip8
+            .load_rom_bytes(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02])
+            .unwrap();
+        chip8.run_cycles(2).unwrap();
+        assert_eq!(
+            chip8.pc_history().collect::<Vec<_>>(),
+            [(0x200, 0x6001), (0x202, 0x7001)]
+        );
+
+        chip8.run_cycles(3).unwrap();
+        assert_eq!(
+            chip8.pc_history().collect::<Vec<_>>(),
+            [(0x204, 0x1202), (0x202, 0x7001), (0x204, 0x1202)]
+        );
+        chip8.reset();
+        assert_eq!(chip8.pc_history().count(), 0);
+    }
+
+    #[test]
+    fn reports_the_machine_state() {
+        // CALL 0x204; JP 0x202; LD V0, 0x2A; DATA 0xFFFF
+        let mut chip8 = Chip8::headless();
+        chip8
+            .load_rom_bytes(&[0x22, 0x04, 0x12, 0x02, 0x60, 0x2A, 0xFF, 0xFF])
+            .unwrap();
+        let err = chip8.run_frame().unwrap_err();
+
+        let report = crash_report(&chip8, &err);
+        assert_eq!(
+            report,
+            "\
+Error: Unknown opcode 0xFFFF at PC=0x0206!
+Fault: 0x0206: FFFF  DATA
+
+Registers:
+PC=0x0206 I=0x0000 DT=0x00 ST=0x00
+V0=0x2A V1=0x00 V2=0x00 V3=0x00
+V4=0x00 V5=0x00 V6=0x00 V7=0x00
+V8=0x00 V9=0x00 VA=0x00 VB=0x00
+VC=0x00 VD=0x00 VE=0x00 VF=0x00
+Stack: [0x0202]
+
+Backtrace:
+#0 0x0206
+#1 0x0204 called from 0x0200 (CALL #0x204)
+
+Disassembly:
+   0x01F6: 0000  DATA
+   0x01F8: 0000  DATA
+   0x01FA: 0000  DATA
+   0x01FC: 0000  DATA
+   0x01FE: 0000  DATA
+   0x0200: 2204  CALL #0x204
+   0x0202: 1202  JP #0x202 ; halt
+   0x0204: 602A  LD V0, #0x2A
+=> 0x0206: FFFF  DATA
+   0x0208: 0000  DATA
+   0x020A: 0000  DATA
+   0x020C: 0000  DATA
+   0x020E: 0000  DATA
+   0x0210: 0000  DATA
+   0x0212: 0000  DATA
+   0x0214: 0000  DATA
+   0x0216: 0000  DATA
+
+History, oldest first:
+0x0200: 2204  CALL #0x204
+0x0204: 602A  LD V0, #0x2A
+0x0206: FFFF  DATA
+"
+        );
+    }
+}
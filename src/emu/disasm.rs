@@ -0,0 +1,768 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Write as _};
+use std::ops::Range;
+
+use super::coverage::Coverage;
+use super::cpu::{Cpu, Instruction, WORD_SIZE};
+use super::memory::{Ram, RAM_SIZE};
+
+///
+/// The `Syntax` enum represents the assembly dialects the disassembler writes.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// The mnemonics of Cowgod's technical reference: `DRW V3, V4, 5`, `LD I, #0x2EA`
+    #[default]
+    Classic,
+    /// The statements of Octo: `sprite v3 v4 5`, `i := 0x2EA`
+    Octo,
+}
+
+///
+/// The operands of an opcode, named after the usual `_xyn`/`_xkk`/`_nnn` patterns.
+///
+struct Operands {
+    x: u16,
+    y: u16,
+    n: u16,
+    kk: u16,
+    nnn: u16,
+}
+
+impl Operands {
+    const fn of(opcode: u16) -> Self {
+        Self {
+            x: (opcode & 0x0F00) >> 8,
+            y: (opcode & 0x00F0) >> 4,
+            n: opcode & 0x000F,
+            kk: opcode & 0x00FF,
+            nnn: opcode & 0x0FFF,
+        }
+    }
+}
+
+///
+/// Returns the classic mnemonic of the opcode at `addr`, or `DATA` when it doesn't decode. A jump
+/// to its own address, the usual way ROMs halt, is marked as such.
+///
+/// ```
+/// use chip8_emu::emu::disasm::disassemble;
+///
+/// assert_eq!(disassemble(0x200, 0xD345), "DRW V3, V4, 5");
+/// assert_eq!(disassemble(0x200, 0xA2EA), "LD I, #0x2EA");
+/// assert_eq!(disassemble(0x204, 0x1204), "JP #0x204 ; halt");
+/// ```
+///
+pub fn disassemble(addr: u16, opcode: u16) -> String {
+    let Some(instruction) = Cpu::decode(opcode) else {
+        return "DATA".to_string();
+    };
+
+    let Operands { x, y, n, kk, nnn } = Operands::of(opcode);
+    let mnemonic = match instruction {
+        Instruction::CLS => "CLS".to_string(),
+        Instruction::RET => "RET".to_string(),
+        Instruction::JMP => format!("JP #0x{nnn:03X}"),
+        Instruction::JMPV0 => format!("JP V0, #0x{nnn:03X}"),
+        Instruction::CALL => format!("CALL #0x{nnn:03X}"),
+        Instruction::SE => format!("SE V{x:X}, #0x{kk:02X}"),
+        Instruction::SNE => format!("SNE V{x:X}, #0x{kk:02X}"),
+        Instruction::SER => format!("SE V{x:X}, V{y:X}"),
+        Instruction::SNER => format!("SNE V{x:X}, V{y:X}"),
+        Instruction::LD => format!("LD V{x:X}, #0x{kk:02X}"),
+        Instruction::ADD => format!("ADD V{x:X}, #0x{kk:02X}"),
+        Instruction::LDR => format!("LD V{x:X}, V{y:X}"),
+        Instruction::OR => format!("OR V{x:X}, V{y:X}"),
+        Instruction::AND => format!("AND V{x:X}, V{y:X}"),
+        Instruction::XOR => format!("XOR V{x:X}, V{y:X}"),
+        Instruction::ADDR => format!("ADD V{x:X}, V{y:X}"),
+        Instruction::SUB => format!("SUB V{x:X}, V{y:X}"),
+        Instruction::SHR => format!("SHR V{x:X}, V{y:X}"),
+        Instruction::SUBN => format!("SUBN V{x:X}, V{y:X}"),
+        Instruction::SHL => format!("SHL V{x:X}, V{y:X}"),
+        Instruction::LDRI => format!("LD I, #0x{nnn:03X}"),
+        Instruction::RND => format!("RND V{x:X}, #0x{kk:02X}"),
+        Instruction::DRW => format!("DRW V{x:X}, V{y:X}, {n}"),
+        Instruction::SKP => format!("SKP V{x:X}"),
+        Instruction::SKNP => format!("SKNP V{x:X}"),
+        Instruction::LDRDT => format!("LD V{x:X}, DT"),
+        Instruction::LDK => format!("LD V{x:X}, K"),
+        Instruction::LDDTR => format!("LD DT, V{x:X}"),
+        Instruction::LDRST => format!("LD ST, V{x:X}"),
+        Instruction::ADDRI => format!("ADD I, V{x:X}"),
+        Instruction::LDSR => format!("LD F, V{x:X}"),
+        Instruction::LDB => format!("LD B, V{x:X}"),
+        Instruction::LDRIR => format!("LD [I], V{x:X}"),
+        Instruction::LDRRI => format!("LD V{x:X}, [I]"),
+        Instruction::MEGAOFF => "MEGAOFF".to_string(),
+        Instruction::MEGAON => "MEGAON".to_string(),
+        // The low word of the address is the next one
+        Instruction::LDHI => format!("LDHI I, #0x{kk:02X}...."),
+        Instruction::LDPAL => format!("LDPAL {kk}"),
+        Instruction::SPRW => format!("SPRW {kk}"),
+        Instruction::SPRH => format!("SPRH {kk}"),
+        Instruction::ALPHA => format!("ALPHA #0x{kk:02X}"),
+        Instruction::DIGISND => format!("DIGISND {n}"),
+        Instruction::STOPSND => "STOPSND".to_string(),
+        Instruction::BMODE => format!("BMODE {n}"),
+        Instruction::CCOL => format!("CCOL #0x{kk:02X}"),
+        Instruction::BGCOL => "BGCOL".to_string(),
+        Instruction::COLZONE => format!("COL V{x:X}, V{y:X}"),
+        Instruction::COLROWS => format!("COL V{x:X}, V{y:X}, {n}"),
+        Instruction::SKP2 => format!("SKP2 V{x:X}"),
+        Instruction::SKNP2 => format!("SKNP2 V{x:X}"),
+        Instruction::OUT => format!("OUT V{x:X}"),
+        Instruction::IN => format!("IN V{x:X}"),
+    };
+
+    if instruction == Instruction::JMP && nnn == addr {
+        format!("{mnemonic} ; halt")
+    } else {
+        mnemonic
+    }
+}
+
+///
+/// Returns the Octo statement of the opcode at `addr`, or its two bytes when it doesn't decode. A
+/// jump to its own address is marked as a halt.
+///
+/// ```
+/// use chip8_emu::emu::disasm::disassemble_octo;
+///
+/// assert_eq!(disassemble_octo(0x200, 0xD345), "sprite v3 v4 5");
+/// assert_eq!(disassemble_octo(0x200, 0x3A2A), "if va != 0x2A then");
+/// ```
+///
+pub fn disassemble_octo(addr: u16, opcode: u16) -> String {
+    let Some(instruction) = Cpu::decode(opcode) else {
+        return format!("0x{:02X} 0x{:02X}", opcode >> 8, opcode & 0xFF);
+    };
+
+    let Operands { x, y, n, kk, nnn } = Operands::of(opcode);
+    // The skips read as the condition under which the next instruction runs
+    let statement = match instruction {
+        Instruction::CLS => "clear".to_string(),
+        Instruction::RET => "return".to_string(),
+        Instruction::JMP => format!("jump 0x{nnn:03X}"),
+        Instruction::JMPV0 => format!("jump0 0x{nnn:03X}"),
+        Instruction::CALL => format!(":call 0x{nnn:03X}"),
+        Instruction::SE => format!("if v{x:x} != 0x{kk:02X} then"),
+        Instruction::SNE => format!("if v{x:x} == 0x{kk:02X} then"),
+        Instruction::SER => format!("if v{x:x} != v{y:x} then"),
+        Instruction::SNER => format!("if v{x:x} == v{y:x} then"),
+        Instruction::LD => format!("v{x:x} := 0x{kk:02X}"),
+        Instruction::ADD => format!("v{x:x} += 0x{kk:02X}"),
+        Instruction::LDR => format!("v{x:x} := v{y:x}"),
+        Instruction::OR => format!("v{x:x} |= v{y:x}"),
+        Instruction::AND => format!("v{x:x} &= v{y:x}"),
+        Instruction::XOR => format!("v{x:x} ^= v{y:x}"),
+        Instruction::ADDR => format!("v{x:x} += v{y:x}"),
+        Instruction::SUB => format!("v{x:x} -= v{y:x}"),
+        Instruction::SHR => format!("v{x:x} >>= v{y:x}"),
+        Instruction::SUBN => format!("v{x:x} =- v{y:x}"),
+        Instruction::SHL => format!("v{x:x} <<= v{y:x}"),
+        Instruction::LDRI => format!("i := 0x{nnn:03X}"),
+        Instruction::RND => format!("v{x:x} := random 0x{kk:02X}"),
+        Instruction::DRW => format!("sprite v{x:x} v{y:x} {n}"),
+        Instruction::SKP => format!("if v{x:x} -key then"),
+        Instruction::SKNP => format!("if v{x:x} key then"),
+        Instruction::LDRDT => format!("v{x:x} := delay"),
+        Instruction::LDK => format!("v{x:x} := key"),
+        Instruction::LDDTR => format!("delay := v{x:x}"),
+        Instruction::LDRST => format!("buzzer := v{x:x}"),
+        Instruction::ADDRI => format!("i += v{x:x}"),
+        Instruction::LDSR => format!("i := hex v{x:x}"),
+        Instruction::LDB => format!("bcd v{x:x}"),
+        Instruction::LDRIR => format!("save v{x:x}"),
+        Instruction::LDRRI => format!("load v{x:x}"),
+        // Octo has no statements for MegaChip and CHIP-8X
+        Instruction::MEGAOFF
+        | Instruction::MEGAON
+        | Instruction::LDHI
+        | Instruction::LDPAL
+        | Instruction::SPRW
+        | Instruction::SPRH
+        | Instruction::ALPHA
+        | Instruction::DIGISND
+        | Instruction::STOPSND
+        | Instruction::BMODE
+        | Instruction::CCOL
+        | Instruction::BGCOL
+        | Instruction::COLZONE
+        | Instruction::COLROWS
+        | Instruction::SKP2
+        | Instruction::SKNP2
+        | Instruction::OUT
+        | Instruction::IN => format!("0x{:02X} 0x{:02X}", opcode >> 8, opcode & 0xFF),
+    };
+
+    if instruction == Instruction::JMP && nnn == addr {
+        format!("{statement} # halt")
+    } else {
+        statement
+    }
+}
+
+///
+/// Returns which words of the ROM, loaded at `start`, can be reached by following the control flow
+/// from `start`. Jumps through `V0` can't be followed, and neither can jumps to odd addresses, so
+/// what they lead to is left as data.
+///
+fn reachable(rom: &[u8], start: u16) -> Vec<bool> {
+    let mut reachable = vec![false; rom.len()];
+    let mut pending = vec![start];
+    while let Some(addr) = pending.pop() {
+        let Some(offset) = addr.checked_sub(start).map(usize::from) else {
+            continue;
+        };
+        if addr % 2 != 0 || offset + 1 >= rom.len() || reachable[offset] {
+            continue;
+        }
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        let Some(instruction) = Cpu::decode(opcode) else {
+            continue;
+        };
+        reachable[offset] = true;
+
+        let next = addr.wrapping_add(WORD_SIZE);
+        match instruction {
+            Instruction::RET | Instruction::JMPV0 => {}
+            Instruction::JMP => pending.push(opcode & 0x0FFF),
+            Instruction::CALL => pending.extend([opcode & 0x0FFF, next]),
+            Instruction::SE
+            | Instruction::SNE
+            | Instruction::SER
+            | Instruction::SNER
+            | Instruction::SKP
+            | Instruction::SKNP => pending.extend([next, next.wrapping_add(WORD_SIZE)]),
+            _ => pending.push(next),
+        }
+    }
+    reachable
+}
+
+///
+/// Returns the listing line of the instruction at `addr` in memory, e.g.
+/// `0x0202: 7001  ADD V0, #0x01`.
+///
+pub fn instruction_at(ram: &Ram, addr: u16) -> String {
+    // Both bytes are read on their own, since the last address has no second byte
+    let word = ram
+        .read_byte(usize::from(addr))
+        .and_then(|hi| Ok([hi, ram.read_byte(usize::from(addr) + 1)?]));
+    word.map_or_else(
+        |_| format!("0x{addr:04X}: outside the memory"),
+        |bytes| {
+            let opcode = u16::from_be_bytes(bytes);
+            format!("0x{addr:04X}: {opcode:04X}  {}", disassemble(addr, opcode))
+        },
+    )
+}
+
+///
+/// The `ListingLine` struct represents an instruction in memory, as listed by debuggers: whether
+/// it's the current one and whether a breakpoint is set on it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingLine {
+    pub address: u16,
+    /// The instruction, as `instruction_at` formats it
+    pub text: String,
+    pub current: bool,
+    pub breakpoint: bool,
+}
+
+impl ListingLine {
+    ///
+    /// Returns the margin: `=>` for the current instruction, ` *` for a breakpoint, or blanks.
+    ///
+    pub const fn marker(&self) -> &'static str {
+        match (self.current, self.breakpoint) {
+            (true, _) => "=>",
+            (false, true) => " *",
+            (false, false) => "  ",
+        }
+    }
+}
+
+impl fmt::Display for ListingLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.marker(), self.text)
+    }
+}
+
+///
+/// Returns `rows` instructions of memory from `from`, marking the one at `pc` and the ones at the
+/// `breakpoints`.
+///
+pub fn memory_listing(
+    ram: &Ram,
+    from: u16,
+    rows: u16,
+    pc: u16,
+    breakpoints: &BTreeSet<u16>,
+) -> Vec<ListingLine> {
+    (from..)
+        .step_by(WORD_SIZE.into())
+        .take(rows.into())
+        .map(|address| ListingLine {
+            address,
+            text: instruction_at(ram, address),
+            current: address == pc,
+            breakpoint: breakpoints.contains(&address),
+        })
+        .collect()
+}
+
+///
+/// Returns the lines of a disassembly pane of `rows` instructions following the PC: centered on
+/// it, unless that would run past either end of the memory.
+///
+/// ```
+/// use std::collections::BTreeSet;
+///
+/// use chip8_emu::emu::disasm::pane;
+/// use chip8_emu::Ram;
+///
+/// let lines = pane(&Ram::new(), 0x300, &BTreeSet::from([0x302]), 16);
+/// assert_eq!(lines.len(), 16);
+/// assert_eq!(lines[8].to_string(), "=> 0x0300: 0000  DATA");
+/// assert_eq!(lines[9].to_string(), " * 0x0302: 0000  DATA");
+/// ```
+///
+pub fn pane(ram: &Ram, pc: u16, breakpoints: &BTreeSet<u16>, rows: u16) -> Vec<ListingLine> {
+    let last_start = (RAM_SIZE as u16).saturating_sub(rows * WORD_SIZE);
+    let from = pc
+        .saturating_sub(rows / 2 * WORD_SIZE)
+        .min(last_start - pc % WORD_SIZE);
+    memory_listing(ram, from, rows, pc, breakpoints)
+}
+
+///
+/// Returns an address-prefixed listing of the ROM loaded at `start`, with the raw bytes of every
+/// line.
+///
+/// Only the instructions reachable from `start` are disassembled, the rest is listed as data, two
+/// bytes at a time.
+///
+pub fn listing(rom: &[u8], start: u16, syntax: Syntax) -> String {
+    write_listing(rom, start, syntax, None)
+}
+
+///
+/// Returns the listing of the ROM loaded at `start` with every line marked by whether it was
+/// executed: `+` if it was, `-` for an instruction never executed, nothing for data never
+/// executed.
+///
+pub fn coverage_listing(rom: &[u8], start: u16, syntax: Syntax, coverage: &Coverage) -> String {
+    write_listing(rom, start, syntax, Some(coverage))
+}
+
+fn write_listing(rom: &[u8], start: u16, syntax: Syntax, coverage: Option<&Coverage>) -> String {
+    let marker = |executed: bool, instruction: bool| match coverage {
+        None => "",
+        Some(_) if executed => "+ ",
+        Some(_) if instruction => "- ",
+        Some(_) => "  ",
+    };
+    let reachable = reachable(rom, start);
+    let mut listing = String::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let addr = start.wrapping_add(offset as u16);
+        if reachable[offset] {
+            let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+            let text = match syntax {
+                Syntax::Classic => disassemble(addr, opcode),
+                Syntax::Octo => disassemble_octo(addr, opcode),
+            };
+            let executed = coverage.is_some_and(|coverage| coverage.is_executed(addr));
+            let marker = marker(executed, true);
+            let _ = writeln!(listing, "{marker}0x{addr:04X}: {opcode:04X}  {text}");
+            offset += 2;
+            continue;
+        }
+
+        // Data runs up to the next instruction, which may start at an odd offset
+        let len = if offset + 1 < rom.len() && !reachable[offset + 1] {
+            2
+        } else {
+            1
+        };
+        let (raw, octo) = match rom[offset..offset + len] {
+            [hi, lo] => (
+                format!("{hi:02X}{lo:02X}"),
+                format!("0x{hi:02X} 0x{lo:02X}"),
+            ),
+            [byte] => (format!("{byte:02X}"), format!("0x{byte:02X}")),
+            _ => unreachable!(),
+        };
+        let text = match syntax {
+            Syntax::Classic => "DATA".to_string(),
+            Syntax::Octo => octo,
+        };
+        // Data counts as executed if any of its bytes was, the analysis having missed the way there
+        let executed = coverage
+            .is_some_and(|coverage| (0..len as u16).any(|n| coverage.covers(addr.wrapping_add(n))));
+        let marker = marker(executed, false);
+        let _ = writeln!(listing, "{marker}0x{addr:04X}: {raw:<4}  {text}");
+        offset += len;
+    }
+    listing
+}
+
+///
+/// Returns the runs of instructions the coverage says were executed in the ROM loaded at `start`,
+/// each one an instruction after the other in memory, in order.
+///
+pub fn executed_blocks(rom: &[u8], start: u16, coverage: &Coverage) -> Vec<Range<u16>> {
+    let end = start.saturating_add(rom.len() as u16);
+    let mut blocks: Vec<Range<u16>> = Vec::new();
+    // The second byte of the last instruction must be part of the ROM too
+    for addr in (start..end.saturating_sub(1)).filter(|&addr| coverage.is_executed(addr)) {
+        match blocks.last_mut() {
+            Some(block) if block.end == addr => block.end = addr + WORD_SIZE,
+            // An instruction executed from the middle of the previous one starts a block too
+            _ => blocks.push(addr..addr + WORD_SIZE),
+        }
+    }
+    blocks
+}
+
+///
+/// Returns the addresses the executed jumps and calls of the ROM loaded at `start` go to, which
+/// were executed too.
+///
+pub fn executed_labels(rom: &[u8], start: u16, coverage: &Coverage) -> BTreeSet<u16> {
+    executed_blocks(rom, start, coverage)
+        .into_iter()
+        .flat_map(|block| block.step_by(usize::from(WORD_SIZE)))
+        .filter_map(|addr| {
+            let opcode = opcode_at(rom, start, addr);
+            match Cpu::decode(opcode)? {
+                Instruction::JMP | Instruction::CALL => Some(opcode & 0x0FFF),
+                _ => None,
+            }
+        })
+        .filter(|&target| coverage.is_executed(target))
+        .collect()
+}
+
+///
+/// Returns the name of the label at `addr`, e.g. `L_0246`.
+///
+pub fn label_name(addr: u16) -> String {
+    format!("L_{addr:04X}")
+}
+
+///
+/// Returns a listing of only the executed instructions of the ROM loaded at `start`.
+///
+/// The blocks of them are separated by `; --- gap ---`, and the targets of the jumps and calls
+/// labeled, the jumps and calls going to the labels.
+///
+/// ```
+/// use chip8_emu::emu::coverage::Coverage;
+/// use chip8_emu::emu::cpu::Instruction;
+/// use chip8_emu::emu::disasm::{executed_listing, Syntax};
+///
+/// // JP 0x204; a table; CLS; JP 0x204
+/// let rom = [0x12, 0x04, 0xFF, 0xFF, 0x00, 0xE0, 0x12, 0x04];
+/// let mut coverage = Coverage::new();
+/// coverage.record(0x200, Instruction::JMP);
+/// coverage.record(0x204, Instruction::CLS);
+/// coverage.record(0x206, Instruction::JMP);
+/// assert_eq!(
+///     executed_listing(&rom, 0x200, Syntax::Classic, &coverage),
+///     "0x0200: 1204  JP L_0204\n; --- gap ---\nL_0204:\n0x0204: 00E0  CLS\n0x0206: 1204  JP L_0204\n",
+/// );
+/// ```
+///
+pub fn executed_listing(rom: &[u8], start: u16, syntax: Syntax, coverage: &Coverage) -> String {
+    let labels = executed_labels(rom, start, coverage);
+    let mut listing = String::new();
+    for (n, block) in executed_blocks(rom, start, coverage)
+        .into_iter()
+        .enumerate()
+    {
+        if n > 0 {
+            listing.push_str("; --- gap ---\n");
+        }
+        for addr in block.step_by(usize::from(WORD_SIZE)) {
+            if labels.contains(&addr) {
+                let _ = match syntax {
+                    Syntax::Classic => writeln!(listing, "{}:", label_name(addr)),
+                    Syntax::Octo => writeln!(listing, ": {}", label_name(addr)),
+                };
+            }
+            let opcode = opcode_at(rom, start, addr);
+            let mut text = match syntax {
+                Syntax::Classic => disassemble(addr, opcode),
+                Syntax::Octo => disassemble_octo(addr, opcode),
+            };
+            let target = opcode & 0x0FFF;
+            let jumps = matches!(
+                Cpu::decode(opcode),
+                Some(Instruction::JMP | Instruction::CALL)
+            );
+            if jumps && labels.contains(&target) {
+                // The classic syntax writes the address as `#0x246`, Octo as `0x246`
+                let address = format!("0x{target:03X}");
+                let address = match syntax {
+                    Syntax::Classic => format!("#{address}"),
+                    Syntax::Octo => address,
+                };
+                text = text.replacen(&address, &label_name(target), 1);
+            }
+            let _ = writeln!(listing, "0x{addr:04X}: {opcode:04X}  {text}");
+        }
+    }
+    listing
+}
+
+fn opcode_at(rom: &[u8], start: u16, addr: u16) -> u16 {
+    let offset = usize::from(addr - start);
+    u16::from_be_bytes([rom[offset], rom[offset + 1]])
+}
+
+#[cfg(test)]
+mod disasm_tests {
+    use std::collections::BTreeSet;
+
+    use super::{
+        coverage_listing, disassemble, disassemble_octo, executed_blocks, executed_labels,
+        executed_listing, instruction_at, listing, pane, Syntax,
+    };
+    use crate::emu::coverage::Coverage;
+    use crate::emu::cpu::Instruction;
+    use crate::emu::memory::Ram;
+    use crate::emu::rom::DEMO_ROM;
+
+    #[test]
+    fn every_instruction_form() {
+        let table: [(u16, &str, &str); 35] = [
+            (0x00E0, "CLS", "clear"),
+            (0x00EE, "RET", "return"),
+            (0x12EA, "JP #0x2EA", "jump 0x2EA"),
+            (0x22EA, "CALL #0x2EA", ":call 0x2EA"),
+            (0x3A2A, "SE VA, #0x2A", "if va != 0x2A then"),
+            (0x4A2A, "SNE VA, #0x2A", "if va == 0x2A then"),
+            (0x5AB0, "SE VA, VB", "if va != vb then"),
+            (0x6A2A, "LD VA, #0x2A", "va := 0x2A"),
+            (0x7A2A, "ADD VA, #0x2A", "va += 0x2A"),
+            (0x8AB0, "LD VA, VB", "va := vb"),
+            (0x8AB1, "OR VA, VB", "va |= vb"),
+            (0x8AB2, "AND VA, VB", "va &= vb"),
+            (0x8AB3, "XOR VA, VB", "va ^= vb"),
+            (0x8AB4, "ADD VA, VB", "va += vb"),
+            (0x8AB5, "SUB VA, VB", "va -= vb"),
+            (0x8AB6, "SHR VA, VB", "va >>= vb"),
+            (0x8AB7, "SUBN VA, VB", "va =- vb"),
+            (0x8ABE, "SHL VA, VB", "va <<= vb"),
+            (0x9AB0, "SNE VA, VB", "if va == vb then"),
+            (0xA2EA, "LD I, #0x2EA", "i := 0x2EA"),
+            (0xB2EA, "JP V0, #0x2EA", "jump0 0x2EA"),
+            (0xCA2A, "RND VA, #0x2A", "va := random 0x2A"),
+            (0xD345, "DRW V3, V4, 5", "sprite v3 v4 5"),
+            (0xEA9E, "SKP VA", "if va -key then"),
+            (0xEAA1, "SKNP VA", "if va key then"),
+            (0xFA07, "LD VA, DT", "va := delay"),
+            (0xFA0A, "LD VA, K", "va := key"),
+            (0xFA15, "LD DT, VA", "delay := va"),
+            (0xFA18, "LD ST, VA", "buzzer := va"),
+            (0xFA1E, "ADD I, VA", "i += va"),
+            (0xFA29, "LD F, VA", "i := hex va"),
+            (0xFA33, "LD B, VA", "bcd va"),
+            (0xFA55, "LD [I], VA", "save va"),
+            (0xFA65, "LD VA, [I]", "load va"),
+            (0xFFFF, "DATA", "0xFF 0xFF"),
+        ];
+        for (opcode, classic, octo) in table {
+            assert_eq!(disassemble(0x200, opcode), classic, "{opcode:04X}");
+            assert_eq!(disassemble_octo(0x200, opcode), octo, "{opcode:04X}");
+        }
+    }
+
+    #[test]
+    fn jumps_to_themselves_halt() {
+        assert_eq!(disassemble(0x2EA, 0x12EA), "JP #0x2EA ; halt");
+        assert_eq!(disassemble_octo(0x2EA, 0x12EA), "jump 0x2EA # halt");
+        assert_eq!(disassemble(0x2EA, 0x22EA), "CALL #0x2EA");
+    }
+
+    #[test]
+    fn instructions_in_memory() {
+        let mut ram = Ram::new();
+        ram.write_byte(0x202, 0x70).unwrap();
+        ram.write_byte(0x203, 0x01).unwrap();
+        assert_eq!(instruction_at(&ram, 0x202), "0x0202: 7001  ADD V0, #0x01");
+        assert_eq!(instruction_at(&ram, 0xFFF), "0x0FFF: outside the memory");
+    }
+
+    #[test]
+    fn unreachable_bytes_are_data() {
+        // CLS; SE V1, 0x2A; JMP 0x208; LD V2, 0x01 (skipped to); sprite data; RET (never reached)
+        let rom = [
+            0x00, 0xE0, 0x31, 0x2A, 0x12, 0x08, 0x62, 0x01, 0x00, 0xEE, 0xFF, 0x81, 0x00, 0xEE,
+        ];
+        assert_eq!(
+            listing(&rom, 0x200, Syntax::Classic),
+            "0x0200: 00E0  CLS\n\
+             0x0202: 312A  SE V1, #0x2A\n\
+             0x0204: 1208  JP #0x208\n\
+             0x0206: 6201  LD V2, #0x01\n\
+             0x0208: 00EE  RET\n\
+             0x020A: FF81  DATA\n\
+             0x020C: 00EE  DATA\n"
+        );
+    }
+
+    #[test]
+    fn odd_lengths_and_start_addresses() {
+        // JMP 0x603 (odd, not followed); trailing byte
+        let rom = [0x16, 0x03, 0x42];
+        assert_eq!(
+            listing(&rom, 0x600, Syntax::Octo),
+            "0x0600: 1603  jump 0x603\n\
+             0x0602: 42    0x42\n"
+        );
+        assert_eq!(listing(&[], 0x200, Syntax::Classic), "");
+    }
+
+    fn demo_ram() -> Ram {
+        let mut ram = Ram::new();
+        ram.load(DEMO_ROM).unwrap();
+        ram
+    }
+
+    #[test]
+    fn pane_is_centered_on_the_pc() {
+        let lines = pane(&demo_ram(), 0x20C, &BTreeSet::from([0x210, 0x20C]), 8);
+        let lines: Vec<String> = lines.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "   0x0204: 600C  LD V0, #0x0C",
+                "   0x0206: 6108  LD V1, #0x08",
+                "   0x0208: D01F  DRW V0, V1, 15",
+                "   0x020A: 7009  ADD V0, #0x09",
+                "=> 0x020C: A239  LD I, #0x239",
+                "   0x020E: D01F  DRW V0, V1, 15",
+                " * 0x0210: A248  LD I, #0x248",
+                "   0x0212: 7008  ADD V0, #0x08",
+            ]
+        );
+    }
+
+    #[test]
+    fn pane_scrolls_with_the_pc() {
+        let ram = demo_ram();
+        let breakpoints = BTreeSet::new();
+        let first = |pc| pane(&ram, pc, &breakpoints, 16)[0].address;
+        assert_eq!(first(0x200), 0x1F0);
+        assert_eq!(first(0x202), 0x1F2);
+        assert_eq!(first(0x228), 0x218);
+        // The pane stops at either end of the memory rather than showing less
+        assert_eq!(first(0x004), 0x000);
+        assert_eq!(first(0xFFE), 0xFE0);
+        assert_eq!(first(0xFFF), 0xFDF);
+
+        let lines = pane(&ram, 0xFFE, &breakpoints, 16);
+        assert_eq!(lines.len(), 16);
+        assert!(lines[15].current);
+        assert_eq!(lines.iter().filter(|line| line.current).count(), 1);
+    }
+    #[test]
+    fn coverage_marks_the_listing() {
+        // LD V0, 0x01; SE V0, 0x01; LD V1, 0x02 (always skipped); JP 0x206; a byte of data
+        let rom = [0x60, 0x01, 0x30, 0x01, 0x61, 0x02, 0x12, 0x06, 0xAA];
+        let mut coverage = Coverage::new();
+        coverage.record(0x200, Instruction::LD);
+        coverage.record(0x202, Instruction::SE);
+        coverage.record(0x206, Instruction::JMP);
+        assert_eq!(
+            coverage_listing(&rom, 0x200, Syntax::Classic, &coverage),
+            "\
++ 0x0200: 6001  LD V0, #0x01
++ 0x0202: 3001  SE V0, #0x01
+- 0x0204: 6102  LD V1, #0x02
++ 0x0206: 1206  JP #0x206 ; halt
+  0x0208: AA    DATA
+"
+        );
+    }
+    ///
+    /// A ROM with a subroutine, a table between the code and a branch never taken, and the
+    /// coverage of a run through it.
+    ///
+    fn executed_fixture() -> ([u8; 16], Coverage) {
+        // 0x200: CALL 0x20A; JP 0x200; a table; 0x20A: SE V0, 0x00; CLS (never executed); RET
+        let rom = [
+            0x22, 0x0A, 0x12, 0x00, 0xDE, 0xAD, 0xBE, 0xEF, 0xFF, 0xFF, 0x30, 0x00, 0x00, 0xE0,
+            0x00, 0xEE,
+        ];
+        let mut coverage = Coverage::new();
+        coverage.record(0x200, Instruction::CALL);
+        coverage.record(0x20A, Instruction::SE);
+        coverage.record(0x20E, Instruction::RET);
+        coverage.record(0x202, Instruction::JMP);
+        (rom, coverage)
+    }
+
+    #[test]
+    fn executed_blocks_and_labels() {
+        let (rom, coverage) = executed_fixture();
+        assert_eq!(
+            executed_blocks(&rom, 0x200, &coverage),
+            [0x200..0x204, 0x20A..0x20C, 0x20E..0x210]
+        );
+        assert_eq!(
+            executed_labels(&rom, 0x200, &coverage),
+            BTreeSet::from([0x200, 0x20A])
+        );
+
+        // Executed addresses outside of the ROM aren't part of it
+        let mut outside = coverage.clone();
+        outside.record(0x300, Instruction::CLS);
+        outside.record(0x20F, Instruction::CLS);
+        assert_eq!(
+            executed_blocks(&rom, 0x200, &outside),
+            executed_blocks(&rom, 0x200, &coverage)
+        );
+    }
+
+    #[test]
+    fn executed_listing_of_either_syntax() {
+        let (rom, coverage) = executed_fixture();
+        assert_eq!(
+            executed_listing(&rom, 0x200, Syntax::Classic, &coverage),
+            "\
+L_0200:
+0x0200: 220A  CALL L_020A
+0x0202: 1200  JP L_0200
+; --- gap ---
+L_020A:
+0x020A: 3000  SE V0, #0x00
+; --- gap ---
+0x020E: 00EE  RET
+"
+        );
+        assert_eq!(
+            executed_listing(&rom, 0x200, Syntax::Octo, &coverage),
+            "\
+: L_0200
+0x0200: 220A  :call L_020A
+0x0202: 1200  jump L_0200
+; --- gap ---
+: L_020A
+0x020A: 3000  if v0 != 0x00 then
+; --- gap ---
+0x020E: 00EE  return
+"
+        );
+        assert_eq!(
+            executed_listing(&rom, 0x200, Syntax::Classic, &Coverage::new()),
+            ""
+        );
+    }
+}
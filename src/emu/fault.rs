@@ -0,0 +1,414 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::cpu::Chip8Error;
+use super::memory::RamError;
+
+///
+/// How many warnings of faults are suppressed between two notes saying so.
+///
+pub const SUPPRESSED_NOTE_INTERVAL: u64 = 1_000;
+
+///
+/// The `FaultKind` enum represents the faults of an instruction the machine can recover from by
+/// skipping it.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FaultKind {
+    UnknownOpcode,
+    /// The instruction went through `I` past the end of the RAM
+    OutOfBounds,
+    StackOverflow,
+    StackUnderflow,
+}
+
+impl FaultKind {
+    ///
+    /// Returns the kind of fault `err` is and the address of the instruction that made it, when
+    /// skipping that instruction recovers from it. Running past the RAM or loading a ROM can't be.
+    ///
+    pub const fn of(err: &Chip8Error) -> Option<(Self, u16)> {
+        match *err {
+            Chip8Error::UnknownOpcode { pc, .. } => Some((Self::UnknownOpcode, pc)),
+            Chip8Error::MemoryFault { pc, err, .. } => match err {
+                RamError::OutOfBound(_) => Some((Self::OutOfBounds, pc)),
+                RamError::StackOverflow => Some((Self::StackOverflow, pc)),
+                RamError::StackUnderflow => Some((Self::StackUnderflow, pc)),
+                RamError::NotEnoughSpace => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::UnknownOpcode => "unknown opcode",
+            Self::OutOfBounds => "out of bound access",
+            Self::StackOverflow => "stack overflow",
+            Self::StackUnderflow => "stack underflow",
+        };
+        write!(f, "{name}")
+    }
+}
+
+///
+/// The `OnFault` enum represents what the machine does when an instruction faults.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnFault {
+    /// Stops with the error and its crash report
+    Stop,
+    /// Warns and goes on past the instruction, as if it did nothing
+    Skip,
+}
+
+///
+/// The `FaultPolicy` struct represents what the machine does on every kind of fault. The default
+/// stops on all of them.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultPolicy {
+    pub unknown_opcode: OnFault,
+    /// Accesses through `I` past the end of the RAM, sprites drawn from there included
+    pub out_of_bounds: OnFault,
+    /// Calls past the 16 levels of the stack and returns without a call
+    pub stack: OnFault,
+}
+
+impl Default for FaultPolicy {
+    fn default() -> Self {
+        Strictness::Strict.policy()
+    }
+}
+
+impl FaultPolicy {
+    pub const fn on(self, kind: FaultKind) -> OnFault {
+        match kind {
+            FaultKind::UnknownOpcode => self.unknown_opcode,
+            FaultKind::OutOfBounds => self.out_of_bounds,
+            FaultKind::StackOverflow | FaultKind::StackUnderflow => self.stack,
+        }
+    }
+
+    ///
+    /// Tells whether the machine skips any kind of fault.
+    ///
+    pub fn recovers(self) -> bool {
+        [self.unknown_opcode, self.out_of_bounds, self.stack].contains(&OnFault::Skip)
+    }
+}
+
+///
+/// The `Strictness` enum represents the fault policies bundled together.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Stops on every fault, for checking one's own ROMs
+    Strict,
+    /// Skips unknown opcodes and accesses past the RAM, but stops when the stack breaks
+    #[default]
+    Normal,
+    /// Never stops, for playing ROMs of unknown quality
+    Lenient,
+}
+
+impl Strictness {
+    pub const fn policy(self) -> FaultPolicy {
+        let (unknown_opcode, out_of_bounds, stack) = match self {
+            Self::Strict => (OnFault::Stop, OnFault::Stop, OnFault::Stop),
+            Self::Normal => (OnFault::Skip, OnFault::Skip, OnFault::Stop),
+            Self::Lenient => (OnFault::Skip, OnFault::Skip, OnFault::Skip),
+        };
+        FaultPolicy {
+            unknown_opcode,
+            out_of_bounds,
+            stack,
+        }
+    }
+}
+
+///
+/// The `PolicyError` struct represents a name of a strictness or of what to do on a fault that
+/// isn't one.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyError {
+    name: String,
+    expected: &'static str,
+}
+
+impl Error for PolicyError {}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown `{}`, expected {}!", self.name, self.expected)
+    }
+}
+
+impl FromStr for OnFault {
+    type Err = PolicyError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "stop" => Ok(Self::Stop),
+            "skip" => Ok(Self::Skip),
+            _ => Err(PolicyError {
+                name: name.to_string(),
+                expected: "`stop` or `skip`",
+            }),
+        }
+    }
+}
+
+impl FromStr for Strictness {
+    type Err = PolicyError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "strict" => Ok(Self::Strict),
+            "normal" => Ok(Self::Normal),
+            "lenient" => Ok(Self::Lenient),
+            _ => Err(PolicyError {
+                name: name.to_string(),
+                expected: "`strict`, `normal` or `lenient`",
+            }),
+        }
+    }
+}
+
+///
+/// The `Report` enum represents what to log of a fault that was just recorded.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Report {
+    /// The first fault of its kind at its address, to log in full
+    Full,
+    /// A fault seen before, only counted
+    Silent,
+    /// A fault seen before, which makes this many suppressed since the last note
+    Suppressed(u64),
+}
+
+///
+/// The `FaultLog` struct represents the faults the machine recovered from, counted by kind and
+/// address, and how many it recovers from at most.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FaultLog {
+    counts: BTreeMap<(FaultKind, u16), u64>,
+    total: u64,
+    suppressed: u64,
+    max: Option<u64>,
+}
+
+impl FaultLog {
+    ///
+    /// Returns an empty log, exceeded by the fault past `max` when there's one.
+    ///
+    pub const fn new(max: Option<u64>) -> Self {
+        Self {
+            counts: BTreeMap::new(),
+            total: 0,
+            suppressed: 0,
+            max,
+        }
+    }
+
+    ///
+    /// Counts a fault of `kind` at `pc`, telling whether to log it in full, not at all or with a
+    /// note of the ones suppressed since the previous note.
+    ///
+    pub fn record(&mut self, kind: FaultKind, pc: u16) -> Report {
+        self.total += 1;
+        let count = self.counts.entry((kind, pc)).or_default();
+        *count += 1;
+        if *count == 1 {
+            return Report::Full;
+        }
+        self.suppressed += 1;
+        if self.suppressed < SUPPRESSED_NOTE_INTERVAL {
+            return Report::Silent;
+        }
+        let suppressed = self.suppressed;
+        self.suppressed = 0;
+        Report::Suppressed(suppressed)
+    }
+
+    ///
+    /// Returns how many faults were recorded.
+    ///
+    pub const fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub const fn max(&self) -> Option<u64> {
+        self.max
+    }
+
+    ///
+    /// Tells whether more faults were recorded than the log allows.
+    ///
+    pub fn exceeded(&self) -> bool {
+        self.max.is_some_and(|max| self.total > max)
+    }
+
+    ///
+    /// Returns how many faults of every kind happened at every address, by kind then address.
+    ///
+    pub fn breakdown(&self) -> impl Iterator<Item = (FaultKind, u16, u64)> + '_ {
+        self.counts
+            .iter()
+            .map(|(&(kind, pc), &count)| (kind, pc, count))
+    }
+}
+
+impl fmt::Display for FaultLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Recovered from {} fault(s)", self.total)?;
+        for (n, (kind, pc, count)) in self.breakdown().enumerate() {
+            let separator = if n == 0 { ":" } else { "," };
+            write!(f, "{separator} {kind} at 0x{pc:04X} {count} time(s)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod fault_tests {
+    use super::{
+        FaultKind, FaultLog, FaultPolicy, OnFault, Report, Strictness, SUPPRESSED_NOTE_INTERVAL,
+    };
+    use crate::emu::cpu::{Chip8Error, Instruction};
+    use crate::emu::memory::RamError;
+
+    #[test]
+    fn only_recoverable_errors_have_a_kind() {
+        let unknown = Chip8Error::UnknownOpcode {
+            pc: 0x204,
+            opcode: 0xF0FF,
+        };
+        assert_eq!(
+            FaultKind::of(&unknown),
+            Some((FaultKind::UnknownOpcode, 0x204))
+        );
+        let fault = |err| Chip8Error::MemoryFault {
+            pc: 0x210,
+            opcode: 0x00EE,
+            instruction: Instruction::RET,
+            err,
+        };
+        assert_eq!(
+            FaultKind::of(&fault(RamError::StackUnderflow)),
+            Some((FaultKind::StackUnderflow, 0x210))
+        );
+        assert_eq!(
+            FaultKind::of(&fault(RamError::OutOfBound(0x1000))),
+            Some((FaultKind::OutOfBounds, 0x210))
+        );
+        assert_eq!(FaultKind::of(&fault(RamError::NotEnoughSpace)), None);
+        let fetch = Chip8Error::InvalidMemoryAccess(RamError::OutOfBound(0xFFF));
+        assert_eq!(FaultKind::of(&fetch), None);
+        assert_eq!(FaultKind::of(&Chip8Error::EmptyRom), None);
+    }
+
+    #[test]
+    fn strictnesses_resolve_to_their_policies() {
+        use OnFault::{Skip, Stop};
+        let kinds = [
+            FaultKind::UnknownOpcode,
+            FaultKind::OutOfBounds,
+            FaultKind::StackOverflow,
+            FaultKind::StackUnderflow,
+        ];
+        let matrix = [
+            (Strictness::Strict, [Stop, Stop, Stop, Stop]),
+            (Strictness::Normal, [Skip, Skip, Stop, Stop]),
+            (Strictness::Lenient, [Skip, Skip, Skip, Skip]),
+        ];
+        for (strictness, expected) in matrix {
+            let policy = strictness.policy();
+            assert_eq!(
+                kinds.map(|kind| policy.on(kind)),
+                expected,
+                "{strictness:?}"
+            );
+        }
+        assert_eq!(FaultPolicy::default(), Strictness::Strict.policy());
+        assert!(!FaultPolicy::default().recovers());
+        assert!(Strictness::Normal.policy().recovers());
+        assert_eq!(Strictness::default(), Strictness::Normal);
+    }
+
+    #[test]
+    fn policy_names() {
+        assert_eq!("Lenient".parse(), Ok(Strictness::Lenient));
+        assert_eq!("skip".parse(), Ok(OnFault::Skip));
+        assert_eq!(
+            "loose".parse::<Strictness>().unwrap_err().to_string(),
+            "Unknown `loose`, expected `strict`, `normal` or `lenient`!"
+        );
+        assert_eq!(
+            "nop".parse::<OnFault>().unwrap_err().to_string(),
+            "Unknown `nop`, expected `stop` or `skip`!"
+        );
+    }
+
+    #[test]
+    fn every_fault_is_logged_in_full_once() {
+        let mut log = FaultLog::new(None);
+        assert_eq!(log.record(FaultKind::UnknownOpcode, 0x200), Report::Full);
+        assert_eq!(log.record(FaultKind::UnknownOpcode, 0x200), Report::Silent);
+        assert_eq!(log.record(FaultKind::UnknownOpcode, 0x202), Report::Full);
+        assert_eq!(log.record(FaultKind::StackOverflow, 0x200), Report::Full);
+        assert_eq!(log.total(), 4);
+        assert_eq!(
+            log.breakdown().collect::<Vec<_>>(),
+            [
+                (FaultKind::UnknownOpcode, 0x200, 2),
+                (FaultKind::UnknownOpcode, 0x202, 1),
+                (FaultKind::StackOverflow, 0x200, 1)
+            ]
+        );
+        assert_eq!(
+            log.to_string(),
+            "Recovered from 4 fault(s): unknown opcode at 0x0200 2 time(s), unknown opcode at \
+             0x0202 1 time(s), stack overflow at 0x0200 1 time(s)"
+        );
+    }
+
+    #[test]
+    fn suppressed_faults_are_noted_now_and_then() {
+        let mut log = FaultLog::new(None);
+        log.record(FaultKind::OutOfBounds, 0x300);
+        let reports: Vec<Report> = (0..2 * SUPPRESSED_NOTE_INTERVAL)
+            .map(|_| log.record(FaultKind::OutOfBounds, 0x300))
+            .filter(|&report| report != Report::Silent)
+            .collect();
+        assert_eq!(
+            reports,
+            [
+                Report::Suppressed(SUPPRESSED_NOTE_INTERVAL),
+                Report::Suppressed(SUPPRESSED_NOTE_INTERVAL)
+            ]
+        );
+        assert_eq!(
+            log.to_string(),
+            "Recovered from 2001 fault(s): out of bound access at 0x0300 2001 time(s)"
+        );
+    }
+
+    #[test]
+    fn the_fault_past_the_max_exceeds_it() {
+        let mut log = FaultLog::new(Some(2));
+        log.record(FaultKind::UnknownOpcode, 0x200);
+        log.record(FaultKind::UnknownOpcode, 0x200);
+        assert!(!log.exceeded());
+        log.record(FaultKind::StackUnderflow, 0x204);
+        assert!(log.exceeded());
+        assert!(!FaultLog::new(None).exceeded());
+    }
+}
@@ -0,0 +1,292 @@
+use super::io::Screen;
+
+///
+/// The `BlitOptions` struct represents how `blit_sprite` draws a sprite: what happens at the
+/// edges of the screen, how wide the sprite is and which planes it's drawn into.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlitOptions {
+    /// Whether the pixels past the right and bottom edges are cut off rather than wrapping around
+    /// to the other side. The sprite's top left corner wraps around either way.
+    pub clip: bool,
+    /// How many pixels wide the sprite is, 8 or 16, every row taking a byte per 8 pixels
+    pub width: usize,
+    /// The planes drawn into, a bit each of the pixels' values, every plane taking the rows of its
+    /// own from the sprite in turn, lowest first
+    pub planes: u8,
+}
+
+impl Default for BlitOptions {
+    ///
+    /// The sprites of `DRW`: 8 pixels wide, wrapping around and drawn into the single plane.
+    ///
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlitOptions {
+    pub const fn new() -> Self {
+        Self {
+            clip: false,
+            width: 8,
+            planes: 1,
+        }
+    }
+}
+
+///
+/// The `Collision` struct represents what drawing a sprite turned off.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Collision {
+    /// How many rows of the sprite turned at least a pixel off, for every plane drawn
+    pub rows: usize,
+}
+
+impl Collision {
+    ///
+    /// Returns whether any pixel was turned off, what `DRW` sets `VF` to.
+    ///
+    pub const fn any(self) -> bool {
+        self.rows > 0
+    }
+}
+
+///
+/// Draws the sprite of `rows`, one bit per pixel from the most significant, with its top left
+/// corner at `(x, y)`, returning what it turned off.
+///
+/// Every pixel of the sprite that's on flips the screen's pixel under it, in the bit of every
+/// plane of `options`. The sprite is as many rows tall as `rows` holds for one plane, a row
+/// short of its bytes being left out.
+///
+/// ```
+/// use chip8_emu::emu::gfx::{blit_sprite, BlitOptions};
+/// use chip8_emu::emu::io::{BufferScreen, Screen};
+///
+/// let mut screen = BufferScreen::new();
+/// // A 2×2 square at the bottom right corner, wrapping around to the other three
+/// let collision = blit_sprite(&mut screen, 63, 31, &[0xC0, 0xC0], BlitOptions::new());
+/// assert!(!collision.any());
+/// assert_eq!(screen.get_pixel(0, 0), 1);
+/// assert!(blit_sprite(&mut screen, 63, 31, &[0xC0], BlitOptions::new()).any());
+/// ```
+///
+pub fn blit_sprite(
+    screen: &mut (impl Screen + ?Sized),
+    x: usize,
+    y: usize,
+    rows: &[u8],
+    options: BlitOptions,
+) -> Collision {
+    let (width, height) = (screen.width(), screen.height());
+    let (x, y) = (x % width, y % height);
+    let row_bytes = options.width.div_ceil(8).max(1);
+    let planes = options.planes.count_ones() as usize;
+    let sprite_height = (rows.len() / row_bytes)
+        .checked_div(planes)
+        .unwrap_or_default();
+
+    let mut collision = Collision::default();
+    let mut sprite_rows = rows.chunks_exact(row_bytes);
+    for plane in (0..8).map(|bit| 1_u8 << bit) {
+        if options.planes & plane == 0 {
+            continue;
+        }
+        for (row, bytes) in sprite_rows.by_ref().take(sprite_height).enumerate() {
+            if options.clip && y + row >= height {
+                continue;
+            }
+            // Wrapping at the screen's own height, the 64 rows of HiRes CHIP-8 as well as the 32
+            let screen_y = (y + row) % height;
+            let mut collided = false;
+            for column in 0..options.width.min(row_bytes * 8) {
+                if options.clip && x + column >= width {
+                    break;
+                }
+                if (bytes[column / 8] >> (7 - column % 8)) & 1 == 0 {
+                    continue;
+                }
+                let screen_x = (x + column) % width;
+                let pixel = screen.get_pixel(screen_x, screen_y);
+                collided |= pixel & plane != 0;
+                screen.set_pixel(screen_x, screen_y, pixel ^ plane);
+            }
+            collision.rows += usize::from(collided);
+        }
+    }
+    screen.draw();
+    collision
+}
+
+#[cfg(test)]
+mod gfx_tests {
+    use super::{blit_sprite, BlitOptions, Collision};
+    use crate::emu::io::{BufferScreen, Screen, GRID_HEIGHT, GRID_WIDTH, HIRES_GRID_HEIGHT};
+
+    ///
+    /// Returns the coordinates of the pixels that are on.
+    ///
+    fn lit(screen: &BufferScreen) -> Vec<(usize, usize)> {
+        let width = screen.width();
+        screen
+            .framebuffer()
+            .iter()
+            .enumerate()
+            .filter(|(_, &pixel)| pixel != 0)
+            .map(|(index, _)| (index % width, index / width))
+            .collect()
+    }
+
+    const CLIP: BlitOptions = BlitOptions {
+        clip: true,
+        ..BlitOptions::new()
+    };
+
+    #[test]
+    fn wraps_or_clips_at_every_edge() {
+        // A 2×2 square, at every corner and edge of the screen
+        let square = [0xC0, 0xC0];
+        let (right, bottom) = (GRID_WIDTH - 1, GRID_HEIGHT - 1);
+        let cases = [
+            ((0, 0), vec![(0, 0), (1, 0), (0, 1), (1, 1)], 4),
+            ((right, 0), vec![(0, 0), (right, 0), (0, 1), (right, 1)], 2),
+            (
+                (0, bottom),
+                vec![(0, 0), (1, 0), (0, bottom), (1, bottom)],
+                2,
+            ),
+            (
+                (right, bottom),
+                vec![(0, 0), (right, 0), (0, bottom), (right, bottom)],
+                1,
+            ),
+            (
+                (30, bottom),
+                vec![(30, 0), (31, 0), (30, bottom), (31, bottom)],
+                2,
+            ),
+        ];
+        for ((x, y), wrapped, clipped) in cases {
+            let mut screen = BufferScreen::new();
+            blit_sprite(&mut screen, x, y, &square, BlitOptions::new());
+            assert_eq!(lit(&screen), wrapped, "wrapped at ({x}, {y})");
+
+            let mut screen = BufferScreen::new();
+            blit_sprite(&mut screen, x, y, &square, CLIP);
+            assert_eq!(lit(&screen).len(), clipped, "clipped at ({x}, {y})");
+            assert!(lit(&screen)
+                .iter()
+                .all(|&(lit_x, lit_y)| lit_x >= x && lit_y >= y));
+        }
+
+        // The corner itself wraps around in both modes
+        for options in [BlitOptions::new(), CLIP] {
+            let mut screen = BufferScreen::new();
+            blit_sprite(
+                &mut screen,
+                GRID_WIDTH + 3,
+                GRID_HEIGHT + 2,
+                &[0x80],
+                options,
+            );
+            assert_eq!(lit(&screen), [(3, 2)]);
+        }
+    }
+
+    #[test]
+    fn wraps_around_the_rows_of_a_taller_screen() {
+        let mut screen = BufferScreen::new();
+        screen.set_size(GRID_WIDTH, HIRES_GRID_HEIGHT);
+        blit_sprite(&mut screen, 0, 63, &[0x80, 0x80], BlitOptions::new());
+        assert_eq!(lit(&screen), [(0, 0), (0, 63)]);
+    }
+
+    #[test]
+    fn collides_by_the_rows_that_turned_pixels_off() {
+        let mut screen = BufferScreen::new();
+        let sprite = [0xFF, 0x81, 0xFF];
+        assert_eq!(
+            blit_sprite(&mut screen, 10, 10, &sprite, BlitOptions::new()),
+            Collision::default()
+        );
+        // Only the first and last rows land on pixels that are on
+        let collision = blit_sprite(&mut screen, 11, 10, &[0x40, 0x00, 0x02], BlitOptions::new());
+        assert_eq!(collision, Collision { rows: 2 });
+        assert!(collision.any());
+        assert_eq!(screen.get_pixel(12, 10), 0);
+        assert_eq!(screen.get_pixel(17, 12), 0);
+
+        // Drawing a sprite twice erases it
+        let mut screen = BufferScreen::new();
+        blit_sprite(&mut screen, 5, 5, &sprite, BlitOptions::new());
+        blit_sprite(&mut screen, 5, 5, &sprite, BlitOptions::new());
+        assert!(lit(&screen).is_empty());
+    }
+
+    #[test]
+    fn draws_16_pixel_wide_sprites() {
+        let wide = BlitOptions {
+            width: 16,
+            ..BlitOptions::new()
+        };
+        // Two rows, the first with its outer pixels on, the second full
+        let sprite = [0x80, 0x01, 0xFF, 0xFF];
+        let mut screen = BufferScreen::new();
+        assert!(!blit_sprite(&mut screen, 56, 0, &sprite, wide).any());
+        let lit_pixels = lit(&screen);
+        assert_eq!(lit_pixels.len(), 18);
+        assert!(lit_pixels.contains(&(56, 0)) && lit_pixels.contains(&(7, 0)));
+
+        // Clipped, the half past the right edge is left out
+        let mut screen = BufferScreen::new();
+        blit_sprite(
+            &mut screen,
+            56,
+            0,
+            &sprite,
+            BlitOptions { clip: true, ..wide },
+        );
+        assert_eq!(lit(&screen).len(), 9);
+
+        // A byte short of a row is left out
+        let mut screen = BufferScreen::new();
+        blit_sprite(&mut screen, 0, 0, &[0xFF, 0xFF, 0xFF], wide);
+        assert_eq!(lit(&screen).len(), 16);
+    }
+
+    #[test]
+    fn draws_every_plane_from_its_own_rows() {
+        let both = BlitOptions {
+            planes: 0b11,
+            ..BlitOptions::new()
+        };
+        let mut screen = BufferScreen::new();
+        // The first plane's row, then the second's
+        blit_sprite(&mut screen, 0, 0, &[0xC0, 0x60], both);
+        let row: Vec<u8> = (0..4).map(|x| screen.get_pixel(x, 0)).collect();
+        assert_eq!(row, [0b01, 0b11, 0b10, 0]);
+
+        // Only the second plane collides
+        let second = BlitOptions {
+            planes: 0b10,
+            ..BlitOptions::new()
+        };
+        assert_eq!(
+            blit_sprite(&mut screen, 0, 0, &[0x80], second),
+            Collision::default()
+        );
+        assert_eq!(screen.get_pixel(0, 0), 0b11);
+        assert!(blit_sprite(&mut screen, 0, 0, &[0x80], second).any());
+        assert_eq!(screen.get_pixel(0, 0), 0b01);
+
+        // No plane draws nothing
+        let none = BlitOptions {
+            planes: 0,
+            ..BlitOptions::new()
+        };
+        assert!(!blit_sprite(&mut screen, 0, 0, &[0xFF], none).any());
+        assert_eq!(screen.get_pixel(0, 0), 0b01);
+    }
+}
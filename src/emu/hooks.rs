@@ -0,0 +1,220 @@
+use super::memory::Registers;
+
+///
+/// The `Chip8Hooks` trait represents the callbacks the emulator fires as the machine runs, so
+/// frontends don't have to poll its state. Every method does nothing by default.
+///
+pub trait Chip8Hooks {
+    ///
+    /// Called with the whole framebuffer, one byte per pixel, whenever the screen changed.
+    ///
+    fn on_frame(&mut self, _framebuffer: &[u8]) {}
+
+    fn on_sound_start(&mut self) {}
+
+    fn on_sound_stop(&mut self) {}
+
+    ///
+    /// Called once when `LD Vx, K` starts waiting for a key to be stored in `Vx`.
+    ///
+    fn on_key_wait(&mut self, _register: u8) {}
+
+    ///
+    /// Called before every instruction, so keep it cheap.
+    ///
+    fn on_instruction(&mut self, _pc: u16, _opcode: u16) {}
+
+    ///
+    /// Called with a short description of what's running, e.g. the name of the loaded ROM.
+    ///
+    fn on_title(&mut self, _title: &str) {}
+}
+
+///
+/// The `InstructionObserver` trait represents a tool watching every instruction execute, e.g. a
+/// tracer or a coverage recorder.
+///
+/// `Chip8` only calls it while one is set, so there's no cost to observing when it's off. Both
+/// methods do nothing by default.
+///
+pub trait InstructionObserver {
+    ///
+    /// Called before the instruction at `pc` executes.
+    ///
+    fn before(&mut self, _pc: u16, _opcode: u16) {}
+
+    ///
+    /// Called after the instruction at `pc` executed, with the registers it left.
+    ///
+    fn after(&mut self, _pc: u16, _opcode: u16, _registers: &Registers) {}
+}
+
+#[cfg(test)]
+mod hooks_tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{Chip8Hooks, InstructionObserver};
+    use crate::emu::chip8::Chip8;
+    use crate::emu::memory::Registers;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Event {
+        Frame(usize),
+        SoundStart,
+        SoundStop,
+        KeyWait(u8),
+        Instruction(u16, u16),
+        Title(String),
+    }
+
+    ///
+    /// Records every callback, along with the number of lit pixels for frames.
+    ///
+    #[derive(Default, Clone)]
+    struct RecordingHooks {
+        events: Rc<RefCell<Vec<Event>>>,
+    }
+
+    impl Chip8Hooks for RecordingHooks {
+        fn on_frame(&mut self, framebuffer: &[u8]) {
+            let lit = framebuffer.iter().map(|&pixel| usize::from(pixel)).sum();
+            self.events.borrow_mut().push(Event::Frame(lit));
+        }
+
+        fn on_sound_start(&mut self) {
+            self.events.borrow_mut().push(Event::SoundStart);
+        }
+
+        fn on_sound_stop(&mut self) {
+            self.events.borrow_mut().push(Event::SoundStop);
+        }
+
+        fn on_key_wait(&mut self, register: u8) {
+            self.events.borrow_mut().push(Event::KeyWait(register));
+        }
+
+        fn on_instruction(&mut self, pc: u16, opcode: u16) {
+            self.events
+                .borrow_mut()
+                .push(Event::Instruction(pc, opcode));
+        }
+
+        fn on_title(&mut self, title: &str) {
+            self.events
+                .borrow_mut()
+                .push(Event::Title(title.to_string()));
+        }
+    }
+
+    fn recorded(chip8: &mut Chip8, rom: &[u8], steps: usize) -> Vec<Event> {
+        let hooks = RecordingHooks::default();
+        chip8.add_hooks(hooks.clone());
+        chip8.load_rom_bytes(rom).unwrap();
+        for _ in 0..steps {
+            chip8.step().unwrap();
+        }
+        hooks.events.take()
+    }
+
+    #[test]
+    fn frames_and_instructions() {
+        // LD I, 0x000 (font sprite of 0); DRW V0, V0, 5; CLS
+        let rom = [0xA0, 0x00, 0xD0, 0x05, 0x00, 0xE0];
+        let events = recorded(&mut Chip8::headless(), &rom, 3);
+        assert_eq!(
+            events,
+            vec![
+                Event::Frame(0),
+                Event::Instruction(0x200, 0xA000),
+                Event::Instruction(0x202, 0xD005),
+                Event::Frame(14),
+                Event::Instruction(0x204, 0x00E0),
+                Event::Frame(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_wait_fires_once() {
+        // LD V7, K
+        let events = recorded(&mut Chip8::headless(), &[0xF7, 0x0A], 3);
+        let waits: Vec<&Event> = events
+            .iter()
+            .filter(|event| matches!(event, Event::KeyWait(_)))
+            .collect();
+        assert_eq!(waits, vec![&Event::KeyWait(7)]);
+    }
+
+    // The timers only tick with the wall clock of `native`
+    #[cfg(feature = "native")]
+    #[test]
+    fn sound_starts_and_stops() {
+        // LD V0, 0x01; LD ST, V0; JMP 0x204
+        let mut chip8 = Chip8::headless();
+        let events = recorded(&mut chip8, &[0x60, 0x01, 0xF0, 0x18, 0x12, 0x04], 2);
+        assert_eq!(events.last(), Some(&Event::SoundStart));
+
+        // One tick of the sound timer is 1/60 s
+        std::thread::sleep(std::time::Duration::from_millis(40));
+        let hooks = RecordingHooks::default();
+        chip8.add_hooks(hooks.clone());
+        chip8.step().unwrap();
+        assert!(hooks.events.take().contains(&Event::SoundStop));
+    }
+
+    ///
+    /// Records the PC before every instruction, and after it the PC it moved on to and `V0`.
+    ///
+    #[derive(Default, Clone)]
+    struct RecordingObserver {
+        events: Rc<RefCell<Vec<(u16, u16, u8)>>>,
+    }
+
+    impl InstructionObserver for RecordingObserver {
+        fn before(&mut self, pc: u16, opcode: u16) {
+            self.events.borrow_mut().push((pc, opcode, 0xFF));
+        }
+
+        fn after(&mut self, _pc: u16, opcode: u16, registers: &Registers) {
+            self.events
+                .borrow_mut()
+                .push((registers.pc, opcode, registers.v[0]));
+        }
+    }
+
+    #[test]
+    fn observes_every_instruction_before_and_after() {
+        // LD V0, 0x05; JMP 0x200
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&[0x60, 0x05, 0x12, 0x00]).unwrap();
+        let observer = RecordingObserver::default();
+        chip8.set_observer(observer.clone());
+        chip8.run_cycles(2).unwrap();
+        assert_eq!(
+            observer.events.take(),
+            [
+                (0x200, 0x6005, 0xFF),
+                (0x202, 0x6005, 0x05),
+                (0x202, 0x1200, 0xFF),
+                (0x200, 0x1200, 0x05),
+            ]
+        );
+
+        chip8.clear_observer();
+        chip8.run_cycles(2).unwrap();
+        assert!(observer.events.take().is_empty());
+    }
+
+    #[test]
+    fn titles() {
+        let hooks = RecordingHooks::default();
+        let mut chip8 = Chip8::headless();
+        chip8.add_hooks(hooks.clone());
+        chip8.load_demo().unwrap();
+        assert!(hooks
+            .events
+            .take()
+            .contains(&Event::Title("demo".to_string())));
+    }
+}
@@ -0,0 +1,90 @@
+use super::coverage::Coverage;
+use super::cpu::{Cpu, Instruction};
+use super::hooks::InstructionObserver;
+use super::memory::Registers;
+use super::stats::Profile;
+use super::trace::{TraceRecord, Tracer};
+use super::uninit::{self, UninitDetector};
+
+///
+/// The `Instruments` struct represents the tools observing every executed instruction: the
+/// tracer, the profile, the coverage, the uninitialized read detector and the library user's
+/// observer.
+///
+/// They all sit behind the one `Option` of `Chip8`, which is only set while one of them is, so an
+/// instruction costs a single check when nothing observes it.
+///
+#[derive(Default)]
+pub struct Instruments {
+    pub tracer: Option<Box<dyn Tracer>>,
+    pub profile: Option<Profile>,
+    pub coverage: Option<Coverage>,
+    pub uninit: Option<UninitDetector>,
+    pub observer: Option<Box<dyn InstructionObserver>>,
+    /// The registers before the instruction executing, while there's a tracer
+    before: Registers,
+}
+
+impl Instruments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.tracer.is_none()
+            && self.profile.is_none()
+            && self.coverage.is_none()
+            && self.uninit.is_none()
+            && self.observer.is_none()
+    }
+
+    ///
+    /// Observes the instruction at `pc` about to execute on `cpu`.
+    ///
+    pub fn before(&mut self, pc: u16, opcode: u16, instruction: Instruction, cpu: &Cpu) {
+        if self.tracer.is_some() {
+            self.before = *cpu.registers();
+        }
+        if let Some(detector) = &mut self.uninit {
+            let accesses = uninit::accesses(instruction, opcode, cpu.registers(), cpu.quirks());
+            for read in detector.check(pc, &accesses, cpu.registers(), cpu.ram()) {
+                log::warn!("{read}.");
+            }
+        }
+        if let Some(observer) = &mut self.observer {
+            observer.before(pc, opcode);
+        }
+    }
+
+    ///
+    /// Observes the instruction at `pc` having executed as the `cycle`th one, leaving
+    /// `registers`.
+    ///
+    pub fn after(
+        &mut self,
+        cycle: u64,
+        pc: u16,
+        opcode: u16,
+        instruction: Instruction,
+        registers: &Registers,
+    ) {
+        if let Some(profile) = &mut self.profile {
+            profile.count(pc, instruction);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(pc, instruction);
+        }
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(&TraceRecord {
+                cycle,
+                pc,
+                opcode,
+                before: &self.before,
+                after: registers,
+            });
+        }
+        if let Some(observer) = &mut self.observer {
+            observer.after(pc, opcode, registers);
+        }
+    }
+}
@@ -1,15 +1,4 @@
-use std::error::Error;
-use std::fmt;
-
-use sdl2::{
-    keyboard::Keycode, pixels::Color, rect::Rect, render::WindowCanvas, video::WindowBuilder,
-    EventPump,
-};
-
-///
-/// The title of the window for the Chip8 emulator.
-///
-const WINDOW_NAME: &str = "The CHIP8 Emulator";
+use core::fmt;
 
 /// The width of the display grid for the Chip8 emulator in pixels.
 pub const GRID_WIDTH: usize = 64;
@@ -17,9 +6,22 @@ pub const GRID_WIDTH: usize = 64;
 /// The height of the display grid for the Chip8 emulator in pixels.
 pub const GRID_HEIGHT: usize = 32;
 
+/// The height of the display grid of HiRes CHIP-8, two pages of the original one, in pixels.
+pub const HIRES_GRID_HEIGHT: usize = 64;
+
+/// The width of the display grid of MegaChip's mega mode in pixels.
+pub const MEGA_GRID_WIDTH: usize = 256;
+
+/// The height of the display grid of MegaChip's mega mode in pixels.
+pub const MEGA_GRID_HEIGHT: usize = 192;
+
+/// How many pixels the biggest screen has, the one of MegaChip's mega mode.
+const MAX_PIXELS: usize = MEGA_GRID_WIDTH * MEGA_GRID_HEIGHT;
+
 ///
 /// The `DisplayError` enum represents the possible errors that can occur when working with the display.
 ///
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum DisplayError {
     FailedToCreateContext,
@@ -29,7 +31,8 @@ pub enum DisplayError {
     FailedToGetEventPump,
 }
 
-impl Error for DisplayError {}
+#[cfg(feature = "std")]
+impl std::error::Error for DisplayError {}
 
 impl fmt::Display for DisplayError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -46,114 +49,338 @@ impl fmt::Display for DisplayError {
 }
 
 ///
-/// The `Display` structure represents display for the chip8 emulator.
+/// The `Screen` trait represents the framebuffer the chip8 emulator draws into, monochrome but in
+/// MegaChip's mega mode, where every pixel is a value of its palette.
 ///
-pub struct Display {
-    width: u32,
-    height: u32,
-    sdl_context: sdl2::Sdl,
-    canvas: WindowCanvas,
-    grid: [u8; GRID_WIDTH * GRID_HEIGHT],
-}
+pub trait Screen {
+    fn set_pixel(&mut self, x: usize, y: usize, new_pixel: u8);
+    fn get_pixel(&self, x: usize, y: usize) -> u8;
+    fn framebuffer(&self) -> &[u8];
+    fn draw(&mut self);
+    fn clear(&mut self);
 
-impl Display {
-    pub fn new(width: u32, height: u32) -> Result<Self, DisplayError> {
-        let sdl_context = match sdl2::init() {
-            Ok(sdl_context) => sdl_context,
-            Err(_) => return Err(DisplayError::FailedToCreateContext),
-        };
+    ///
+    /// Returns how many pixels the rows of the screen have, `GRID_WIDTH` but in mega mode.
+    ///
+    fn width(&self) -> usize {
+        GRID_WIDTH
+    }
 
-        let video_subsystem = match sdl_context.video() {
-            Ok(video_subsystem) => video_subsystem,
-            Err(_) => return Err(DisplayError::FailedToCreateVideoSubsystem),
-        };
+    ///
+    /// Returns how many rows the screen has, sprites wrapping around past the last one.
+    ///
+    fn height(&self) -> usize {
+        GRID_HEIGHT
+    }
 
-        let Ok(window) = WindowBuilder::new(&video_subsystem, WINDOW_NAME, width, height)
-            .position_centered()
-            .build()
-            .map_err(|e| e.to_string())
-        else {
-            return Err(DisplayError::FailedToCreateWindow);
-        };
+    ///
+    /// Switches the screen to `width` × `height` pixels and blanks it: `GRID_WIDTH` wide and
+    /// `GRID_HEIGHT` or `HIRES_GRID_HEIGHT` tall, or `MEGA_GRID_WIDTH` × `MEGA_GRID_HEIGHT`.
+    /// Screens of a fixed size only blank.
+    ///
+    fn set_size(&mut self, _width: usize, _height: usize) {
+        self.clear();
+    }
 
-        let canvas = match window.into_canvas().build().map_err(|e| e.to_string()) {
-            Ok(canvas) => canvas,
-            Err(_) => return Err(DisplayError::FailedToCreateCanvas),
-        };
+    ///
+    /// Returns how many frames ago every pixel last changed, up to `PIXEL_AGE_FRAMES`, for
+    /// screens keeping track of it.
+    ///
+    fn pixel_ages(&self) -> Option<&[u8]> {
+        None
+    }
 
-        let display = Self {
-            width: width,
-            height: height,
-            sdl_context,
-            canvas,
-            grid: [0; GRID_WIDTH * GRID_HEIGHT],
-        };
+    ///
+    /// Ages every pixel by a frame, the emulator calling it at the end of every frame.
+    ///
+    fn age_pixels(&mut self) {}
+
+    ///
+    /// Draws the text in the overlay font with its top left corner at `(x, y)`, lighting the
+    /// glyphs' pixels and leaving the rest as it is, every `\n` starting a line below. Characters
+    /// without a glyph are drawn as `?`, and whatever is past the edges is cut off.
+    ///
+    fn draw_text(&mut self, x: usize, y: usize, text: &str) {
+        let (width, height) = (self.width(), self.height());
+        let (mut left, mut top) = (x, y);
+        for c in text.chars() {
+            if c == '\n' {
+                (left, top) = (x, top.saturating_add(LINE_HEIGHT as usize));
+                continue;
+            }
+            let rows = glyph(c).or(glyph('?')).unwrap_or_default();
+            for (row, bits) in rows.into_iter().enumerate() {
+                for column in 0..3 {
+                    let (pixel_x, pixel_y) = (left.saturating_add(column), top.saturating_add(row));
+                    if bits & (0b100 >> column) != 0 && pixel_x < width && pixel_y < height {
+                        self.set_pixel(pixel_x, pixel_y, 1);
+                    }
+                }
+            }
+            left = left.saturating_add(CHAR_WIDTH as usize);
+        }
+        self.draw();
+    }
 
-        Ok(display)
+    ///
+    /// Sets every pixel of the `width` × `height` rectangle at `(x, y)` to `value`, the part past
+    /// the edges cut off.
+    ///
+    fn fill_region(&mut self, x: usize, y: usize, width: usize, height: usize, value: u8) {
+        for row in y..y.saturating_add(height).min(self.height()) {
+            for column in x..x.saturating_add(width).min(self.width()) {
+                self.set_pixel(column, row, value);
+            }
+        }
+        self.draw();
     }
 
-    pub fn get_event_pump(&mut self) -> Result<EventPump, DisplayError> {
-        match self.sdl_context.event_pump() {
-            Ok(event_pump) => Ok(event_pump),
-            Err(_) => Err(DisplayError::FailedToGetEventPump),
+    ///
+    /// Turns the lit pixels of the `width` × `height` rectangle at `(x, y)` off and the others on,
+    /// the part past the edges cut off.
+    ///
+    fn invert_region(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        for row in y..y.saturating_add(height).min(self.height()) {
+            for column in x..x.saturating_add(width).min(self.width()) {
+                let pixel = self.get_pixel(column, row);
+                self.set_pixel(column, row, u8::from(pixel == 0));
+            }
         }
+        self.draw();
     }
+}
 
-    pub fn set_pixel(&mut self, x: usize, y: usize, new_pixel: u8) {
-        let index = y * GRID_WIDTH + x;
+///
+/// The `PIXEL_AGE_FRAMES` constant is the age of the pixels that haven't changed for long, the
+/// oldest there is.
+///
+pub const PIXEL_AGE_FRAMES: u8 = 30;
+
+///
+/// The `BufferScreen` structure represents an in-memory screen, backing both headless runs and the
+/// SDL window.
+///
+pub struct BufferScreen {
+    // Room for the biggest screen, only the first `height` rows of `width` pixels of it shown
+    grid: [u8; MAX_PIXELS],
+    // How many frames ago every pixel last changed, for showing the order things are drawn in
+    ages: [u8; MAX_PIXELS],
+    width: usize,
+    height: usize,
+}
+
+impl Default for BufferScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BufferScreen {
+    // The core doesn't allocate, so the room for MegaChip's screen is part of the struct
+    #[allow(clippy::large_stack_arrays)]
+    pub const fn new() -> Self {
+        Self {
+            grid: [0; MAX_PIXELS],
+            ages: [PIXEL_AGE_FRAMES; MAX_PIXELS],
+            width: GRID_WIDTH,
+            height: GRID_HEIGHT,
+        }
+    }
+}
+
+impl Screen for BufferScreen {
+    fn set_pixel(&mut self, x: usize, y: usize, new_pixel: u8) {
+        let index = y * self.width + x;
+        // Drawing leaves most of a sprite's box unchanged, only what it flipped is new
+        if self.grid[index] != new_pixel {
+            self.ages[index] = 0;
+        }
         self.grid[index] = new_pixel;
     }
 
-    pub fn get_pixel(&self, x: usize, y: usize) -> u8 {
-        let index = y * GRID_WIDTH + x;
-        self.grid[index]
+    fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        self.grid[y * self.width + x]
     }
 
-    pub fn draw(&mut self) {
-        // Set the background color to black
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        self.canvas.clear();
+    fn framebuffer(&self) -> &[u8] {
+        &self.grid[..self.width * self.height]
+    }
 
-        // Calculate the size of each pixel to fit the display size
-        let pixel_width = self.width / GRID_WIDTH as u32;
-        let pixel_height = self.height / GRID_HEIGHT as u32;
+    fn draw(&mut self) {}
 
-        for y in 0..GRID_HEIGHT {
-            for x in 0..GRID_WIDTH {
-                // Calculate the index for the 1D array
-                let index = y * GRID_WIDTH + x;
+    fn clear(&mut self) {
+        // The pixels past the ones shown are blank already, they were cleared as they were hidden
+        let shown = self.width * self.height;
+        for (pixel, age) in self.grid[..shown].iter_mut().zip(&mut self.ages) {
+            if *pixel != 0 {
+                *age = 0;
+            }
+            *pixel = 0;
+        }
+    }
 
-                // Access the pixel value using the calculated index
-                let pixel_value = self.grid[index];
-                // Set draw color based on the pixel's state
-                if pixel_value == 1 {
-                    self.canvas.set_draw_color(Color::RGB(0, 255, 0)); // Green for 'on' pixels
-                } else {
-                    continue; // Skip drawing 'off' pixels (background is already set)
-                }
+    fn width(&self) -> usize {
+        self.width
+    }
 
-                let pixel_rect = Rect::new(
-                    (x as u32 * pixel_width) as i32,
-                    (y as u32 * pixel_height) as i32,
-                    pixel_width,
-                    pixel_height,
-                );
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn set_size(&mut self, width: usize, height: usize) {
+        self.clear();
+        self.width = width.clamp(1, MEGA_GRID_WIDTH);
+        self.height = height.clamp(1, MEGA_GRID_HEIGHT);
+    }
 
-                if let Err(e) = self.canvas.fill_rect(pixel_rect) {
-                    eprintln!("Failed to draw pixel at ({x}, {y}): {e}");
+    fn pixel_ages(&self) -> Option<&[u8]> {
+        Some(&self.ages[..self.width * self.height])
+    }
+
+    fn age_pixels(&mut self) {
+        for age in &mut self.ages[..self.width * self.height] {
+            *age = (*age + 1).min(PIXEL_AGE_FRAMES);
+        }
+    }
+}
+
+///
+/// The width of a character of the overlay font in font pixels, the spacing included.
+///
+pub const CHAR_WIDTH: u32 = 4;
+
+///
+/// The height of a line of the overlay font in font pixels, the spacing included.
+///
+pub const LINE_HEIGHT: u32 = 6;
+
+///
+/// Returns the 3×5 glyph of the character, one byte per row with the leftmost pixel in bit 2.
+/// Lowercase letters share the uppercase glyphs.
+///
+pub const fn glyph(c: char) -> Option<[u8; 5]> {
+    let glyph = match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0; 5],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '?' => [0b111, 0b001, 0b010, 0b000, 0b010],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        ';' => [0b000, 0b010, 0b000, 0b010, 0b100],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '|' => [0b010, 0b010, 0b010, 0b010, 0b010],
+        _ => return None,
+    };
+    Some(glyph)
+}
+
+///
+/// The `TextRenderer` struct represents the overlay font drawn at `scale` screen pixels per font
+/// pixel, for frontends to draw text over the screen with whatever fills rectangles.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRenderer {
+    scale: u32,
+}
+
+impl TextRenderer {
+    pub const fn new(scale: u32) -> Self {
+        Self { scale }
+    }
+
+    ///
+    /// Returns the size in screen pixels of the lines drawn one below the other, spacing included.
+    ///
+    pub fn size<'a>(&self, lines: impl IntoIterator<Item = &'a str>) -> (u32, u32) {
+        let (columns, rows) = lines.into_iter().fold((0, 0), |(columns, rows), line| {
+            (columns.max(line.chars().count() as u32), rows + 1)
+        });
+        (
+            columns * CHAR_WIDTH * self.scale,
+            rows * LINE_HEIGHT * self.scale,
+        )
+    }
+
+    ///
+    /// Draws the line with its top left corner at `(x, y)`, calling `fill(x, y, size)` for every
+    /// square of `size` screen pixels to light. Characters without a glyph are drawn as `?`.
+    ///
+    pub fn draw(&self, text: &str, x: i32, y: i32, mut fill: impl FnMut(i32, i32, u32)) {
+        let scale = self.scale.cast_signed();
+        let mut left = x;
+        for c in text.chars() {
+            let rows = glyph(c).or(glyph('?')).unwrap_or_default();
+            for (row, bits) in (0..).zip(rows) {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        fill(left + column * scale, y + row * scale, self.scale);
+                    }
                 }
             }
+            left += CHAR_WIDTH.cast_signed() * scale;
         }
-
-        // Present the updated canvas
-        self.canvas.present();
     }
 
-    pub fn clear(&mut self) {
-        self.grid.fill(0);
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        self.canvas.clear();
-        self.canvas.present();
+    ///
+    /// Draws the lines one below the other, the first one's top left corner at `(x, y)`.
+    ///
+    pub fn draw_lines<'a>(
+        &self,
+        lines: impl IntoIterator<Item = &'a str>,
+        x: i32,
+        y: i32,
+        mut fill: impl FnMut(i32, i32, u32),
+    ) {
+        let line_height = (LINE_HEIGHT * self.scale).cast_signed();
+        for (n, line) in (0..).zip(lines) {
+            self.draw(line, x, y + n * line_height, &mut fill);
+        }
     }
 }
 
@@ -164,31 +391,19 @@ pub struct Keyboard {
     key: u8,
 }
 
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Keyboard {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self { key: 0x0 }
     }
 
-    pub fn press_key(&mut self, key: Keycode) {
-        match key {
-            Keycode::Num1 => self.key = 0x1,
-            Keycode::Num2 => self.key = 0x2,
-            Keycode::Num3 => self.key = 0x3,
-            Keycode::Num4 => self.key = 0xC,
-            Keycode::Q => self.key = 0x4,
-            Keycode::W => self.key = 0x5,
-            Keycode::E => self.key = 0x6,
-            Keycode::R => self.key = 0xD,
-            Keycode::A => self.key = 0x7,
-            Keycode::S => self.key = 0x8,
-            Keycode::D => self.key = 0x9,
-            Keycode::F => self.key = 0xE,
-            Keycode::Z => self.key = 0xA,
-            Keycode::X => self.key = 0x0,
-            Keycode::C => self.key = 0xB,
-            Keycode::V => self.key = 0xF,
-            _ => (),
-        }
+    pub fn press_hex_key(&mut self, hex_key: u8) {
+        self.key = hex_key & 0xF;
     }
 
     pub fn release_key(&mut self) {
@@ -207,3 +422,178 @@ impl Keyboard {
         self.key == key
     }
 }
+
+#[cfg(test)]
+mod io_tests {
+    use super::{
+        glyph, BufferScreen, Screen, TextRenderer, GRID_HEIGHT, GRID_WIDTH, PIXEL_AGE_FRAMES,
+    };
+
+    #[test]
+    fn pixel_ages() {
+        let mut screen = BufferScreen::new();
+        let age = |screen: &BufferScreen, x: usize, y: usize| {
+            screen.pixel_ages().unwrap()[y * GRID_WIDTH + x]
+        };
+        assert_eq!(age(&screen, 0, 0), PIXEL_AGE_FRAMES);
+
+        screen.set_pixel(3, 2, 1);
+        // Writing a pixel as it already is doesn't change it
+        screen.set_pixel(4, 2, 0);
+        assert_eq!(age(&screen, 3, 2), 0);
+        assert_eq!(age(&screen, 4, 2), PIXEL_AGE_FRAMES);
+
+        for _ in 0..5 {
+            screen.age_pixels();
+        }
+        assert_eq!(age(&screen, 3, 2), 5);
+        screen.set_pixel(5, 2, 1);
+        screen.age_pixels();
+        assert_eq!(age(&screen, 3, 2), 6);
+        assert_eq!(age(&screen, 5, 2), 1);
+
+        // Erasing is a change too, and so is clearing what was lit
+        screen.set_pixel(3, 2, 0);
+        assert_eq!(age(&screen, 3, 2), 0);
+        screen.age_pixels();
+        screen.clear();
+        assert_eq!(age(&screen, 3, 2), 1);
+        assert_eq!(age(&screen, 5, 2), 0);
+        assert_eq!(screen.get_pixel(5, 2), 0);
+
+        for _ in 0..100 {
+            screen.age_pixels();
+        }
+        assert!(screen
+            .pixel_ages()
+            .unwrap()
+            .iter()
+            .all(|&age| age == PIXEL_AGE_FRAMES));
+    }
+
+    #[test]
+    fn glyph_lookup() {
+        assert_eq!(glyph('7'), Some([0b111, 0b001, 0b001, 0b010, 0b010]));
+        assert_eq!(glyph('f'), glyph('F'));
+        assert_eq!(glyph(' '), Some([0; 5]));
+        assert_eq!(glyph('~'), None);
+        assert!("0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ:=.,-/[]()#?!;*>+|"
+            .chars()
+            .all(|c| glyph(c).is_some()));
+    }
+
+    #[test]
+    fn layout() {
+        let renderer = TextRenderer::new(2);
+        assert_eq!(renderer.size(["PC 0200", "I 0300", ""]), (56, 36));
+        assert_eq!(renderer.size([]), (0, 0));
+
+        let mut squares = Vec::new();
+        renderer.draw_lines(["1", " -"], 10, 20, |x, y, size| squares.push((x, y, size)));
+        assert_eq!(
+            squares,
+            [
+                // The 1, two pixels wide at the top
+                (12, 20, 2),
+                (10, 22, 2),
+                (12, 22, 2),
+                (12, 24, 2),
+                (12, 26, 2),
+                (10, 28, 2),
+                (12, 28, 2),
+                (14, 28, 2),
+                // The dash, one character in on the second line
+                (18, 36, 2),
+                (20, 36, 2),
+                (22, 36, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_characters_are_question_marks() {
+        let renderer = TextRenderer::new(1);
+        let (mut unknown, mut question) = (Vec::new(), Vec::new());
+        renderer.draw("~", 0, 0, |x, y, _| unknown.push((x, y)));
+        renderer.draw("?", 0, 0, |x, y, _| question.push((x, y)));
+        assert_eq!(unknown, question);
+    }
+
+    ///
+    /// Returns the 3×5 glyph at `(x, y)` of the screen, in the rows of `glyph`.
+    ///
+    fn glyph_at(screen: &BufferScreen, x: usize, y: usize) -> [u8; 5] {
+        core::array::from_fn(|row| {
+            (0..3).fold(0, |bits, column| {
+                bits << 1 | screen.get_pixel(x + column, y + row)
+            })
+        })
+    }
+
+    #[test]
+    fn draws_text_in_the_glyphs_of_the_font() {
+        let mut screen = BufferScreen::new();
+        screen.draw_text(1, 2, "Hi 7\n?~");
+        for (n, c) in "HI 7".chars().enumerate() {
+            assert_eq!(Some(glyph_at(&screen, 1 + n * 4, 2)), glyph(c), "{c}");
+        }
+        // The next line, the unknown character as a question mark
+        assert_eq!(Some(glyph_at(&screen, 1, 8)), glyph('?'));
+        assert_eq!(Some(glyph_at(&screen, 5, 8)), glyph('?'));
+        // Nothing but the glyphs is lit, the spacing included
+        let lit = screen
+            .framebuffer()
+            .iter()
+            .filter(|&&pixel| pixel != 0)
+            .count();
+        let glyphs: u32 = "HI7??"
+            .chars()
+            .flat_map(|c| glyph(c).unwrap())
+            .map(u8::count_ones)
+            .sum();
+        assert_eq!(lit, glyphs as usize);
+
+        // What's there already stays lit
+        let mut screen = BufferScreen::new();
+        screen.fill_region(0, 0, 3, 5, 1);
+        screen.draw_text(0, 0, "1");
+        assert_eq!(glyph_at(&screen, 0, 0), [0b111; 5]);
+    }
+
+    #[test]
+    fn cuts_text_and_regions_off_at_the_edges() {
+        let mut screen = BufferScreen::new();
+        // The 8 only has its left two columns and top three rows on the screen
+        screen.draw_text(GRID_WIDTH - 2, GRID_HEIGHT - 3, "8 8");
+        screen.draw_text(usize::MAX, 0, "8");
+        let lit: Vec<(usize, usize)> = (0..GRID_HEIGHT)
+            .flat_map(|y| (0..GRID_WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| screen.get_pixel(x, y) != 0)
+            .collect();
+        assert_eq!(lit, [(62, 29), (63, 29), (62, 30), (62, 31), (63, 31)]);
+
+        let mut screen = BufferScreen::new();
+        screen.fill_region(GRID_WIDTH - 4, GRID_HEIGHT - 2, 100, usize::MAX, 1);
+        assert_eq!(
+            screen
+                .framebuffer()
+                .iter()
+                .filter(|&&pixel| pixel != 0)
+                .count(),
+            8
+        );
+        screen.invert_region(GRID_WIDTH - 5, GRID_HEIGHT - 1, 10, 10);
+        // The last row's four lit pixels turned off, the one before them on
+        assert_eq!(screen.get_pixel(GRID_WIDTH - 5, GRID_HEIGHT - 1), 1);
+        assert_eq!(screen.get_pixel(GRID_WIDTH - 1, GRID_HEIGHT - 1), 0);
+        assert_eq!(screen.get_pixel(GRID_WIDTH - 1, GRID_HEIGHT - 2), 1);
+        assert_eq!(
+            screen
+                .framebuffer()
+                .iter()
+                .filter(|&&pixel| pixel != 0)
+                .count(),
+            5
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::error::Error;
+use std::fmt;
+
+///
+/// The `HEADER` constant is the signature every IPS patch starts with.
+///
+const HEADER: &[u8] = b"PATCH";
+
+///
+/// The `EOF` constant is the marker ending the records, where the next record's offset would be.
+///
+const EOF: [u8; 3] = *b"EOF";
+
+///
+/// The `IpsError` enum represents a patch that isn't a valid IPS patch, or doesn't fit the ROM.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpsError {
+    MissingHeader,
+    /// The patch ended inside the record starting at this byte of the patch
+    Truncated(usize),
+    MissingEof,
+    /// The record at `offset` writes up to `end`, past the `max` bytes a ROM can have
+    OutOfRange {
+        offset: usize,
+        end: usize,
+        max: usize,
+    },
+}
+
+impl Error for IpsError {}
+
+impl fmt::Display for IpsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingHeader => write!(f, "The patch doesn't start with `PATCH`!"),
+            Self::Truncated(at) => write!(f, "The patch ends inside the record at byte {at}!"),
+            Self::MissingEof => write!(f, "The patch ends without its `EOF` marker!"),
+            Self::OutOfRange { offset, end, max } => write!(
+                f,
+                "The record at 0x{offset:06X} writes up to byte {end}, past the {max} bytes a ROM can have!"
+            ),
+        }
+    }
+}
+
+///
+/// The `Patched` struct represents a ROM with a patch applied, and how many records it took.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patched {
+    pub rom: Vec<u8>,
+    pub records: usize,
+}
+
+///
+/// Applies the IPS patch to the ROM, returning the patched copy.
+///
+/// The ROM grows as records write past its end, up to `max_size` bytes. Run-length records fill
+/// their bytes with one value, and a size after the `EOF` marker cuts the ROM to it.
+///
+/// # Errors
+///
+/// Fails without the `PATCH` header or the `EOF` marker, on a record cut short and on one writing
+/// past `max_size`.
+///
+pub fn apply(rom: &[u8], patch: &[u8], max_size: usize) -> Result<Patched, IpsError> {
+    let mut rest = patch.strip_prefix(HEADER).ok_or(IpsError::MissingHeader)?;
+    let mut patched = rom.to_vec();
+    let mut records = 0;
+    loop {
+        let at = patch.len() - rest.len();
+        let truncated = || IpsError::Truncated(at);
+        let Some((offset, after)) = rest.split_first_chunk::<3>() else {
+            return Err(if rest.is_empty() {
+                IpsError::MissingEof
+            } else {
+                truncated()
+            });
+        };
+        if *offset == EOF {
+            if let Some((size, _)) = after.split_first_chunk::<3>() {
+                patched.truncate(be24(*size));
+            }
+            return Ok(Patched {
+                rom: patched,
+                records,
+            });
+        }
+
+        let offset = be24(*offset);
+        let (size, after) = after.split_first_chunk::<2>().ok_or_else(truncated)?;
+        let (bytes, after) = match usize::from(u16::from_be_bytes(*size)) {
+            // A run of one value, its length and the value following
+            0 => {
+                let (run, after) = after.split_first_chunk::<3>().ok_or_else(truncated)?;
+                let length = usize::from(u16::from_be_bytes([run[0], run[1]]));
+                (Bytes::Run(length, run[2]), after)
+            }
+            size => {
+                let bytes = after.get(..size).ok_or_else(truncated)?;
+                (Bytes::Copy(bytes), &after[size..])
+            }
+        };
+
+        let end = offset + bytes.len();
+        if end > max_size {
+            return Err(IpsError::OutOfRange {
+                offset,
+                end,
+                max: max_size,
+            });
+        }
+        if patched.len() < end {
+            patched.resize(end, 0);
+        }
+        let target = &mut patched[offset..end];
+        match bytes {
+            Bytes::Copy(bytes) => target.copy_from_slice(bytes),
+            Bytes::Run(_, value) => target.fill(value),
+        }
+        records += 1;
+        rest = after;
+    }
+}
+
+///
+/// The `Bytes` enum represents what a record writes.
+///
+enum Bytes<'a> {
+    Copy(&'a [u8]),
+    Run(usize, u8),
+}
+
+impl Bytes<'_> {
+    const fn len(&self) -> usize {
+        match self {
+            Self::Copy(bytes) => bytes.len(),
+            Self::Run(length, _) => *length,
+        }
+    }
+}
+
+const fn be24(bytes: [u8; 3]) -> usize {
+    (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize
+}
+
+#[cfg(test)]
+mod ips_tests {
+    use super::{apply, IpsError, Patched};
+
+    ///
+    /// Builds a patch out of its records, every one an offset and its bytes, or an offset, a
+    /// length and a value for a run.
+    ///
+    fn patch(records: &[(u32, &[u8])], runs: &[(u32, u16, u8)]) -> Vec<u8> {
+        let mut patch = b"PATCH".to_vec();
+        for &(offset, bytes) in records {
+            patch.extend_from_slice(&offset.to_be_bytes()[1..]);
+            patch.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+            patch.extend_from_slice(bytes);
+        }
+        for &(offset, length, value) in runs {
+            patch.extend_from_slice(&offset.to_be_bytes()[1..]);
+            patch.extend_from_slice(&[0, 0]);
+            patch.extend_from_slice(&length.to_be_bytes());
+            patch.push(value);
+        }
+        patch.extend_from_slice(b"EOF");
+        patch
+    }
+
+    #[test]
+    fn applies_the_records() {
+        let rom = [0x00, 0xE0, 0x12, 0x00];
+        let patched = apply(&rom, &patch(&[(2, &[0x13, 0x02])], &[]), 0xE00).unwrap();
+        assert_eq!(
+            patched,
+            Patched {
+                rom: vec![0x00, 0xE0, 0x13, 0x02],
+                records: 1
+            }
+        );
+
+        // Writing past the end grows the ROM, and runs fill their bytes
+        let patched = apply(&rom, &patch(&[(6, &[0xAB])], &[(1, 2, 0xFF)]), 0xE00).unwrap();
+        assert_eq!(patched.rom, [0x00, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xAB]);
+        assert_eq!(patched.records, 2);
+
+        // Nothing to do
+        assert_eq!(apply(&rom, b"PATCHEOF", 0xE00).unwrap().rom, rom);
+    }
+
+    #[test]
+    fn cuts_the_rom_to_the_size_after_the_marker() {
+        let mut cut = patch(&[(0, &[0xA2])], &[]);
+        cut.extend_from_slice(&[0, 0, 3]);
+        let patched = apply(&[0x00, 0xE0, 0x12, 0x00], &cut, 0xE00).unwrap();
+        assert_eq!(patched.rom, [0xA2, 0xE0, 0x12]);
+    }
+
+    #[test]
+    fn stays_within_the_size_a_rom_can_have() {
+        let rom = [0; 4];
+        assert_eq!(
+            apply(&rom, &patch(&[(0xDFF, &[1])], &[]), 0xE00)
+                .unwrap()
+                .rom
+                .len(),
+            0xE00
+        );
+        assert_eq!(
+            apply(&rom, &patch(&[(0xDFF, &[1, 2])], &[]), 0xE00),
+            Err(IpsError::OutOfRange {
+                offset: 0xDFF,
+                end: 0xE01,
+                max: 0xE00
+            })
+        );
+        let err = apply(&rom, &patch(&[], &[(0x10000, 1, 0)]), 0xE00).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The record at 0x010000 writes up to byte 65537, past the 3584 bytes a ROM can have!"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_patches() {
+        let rom = [0; 4];
+        assert_eq!(apply(&rom, b"PATCE", 0xE00), Err(IpsError::MissingHeader));
+        assert_eq!(apply(&rom, b"PATCH", 0xE00), Err(IpsError::MissingEof));
+        let mut cut = patch(&[(0, &[1, 2, 3])], &[]);
+        cut.truncate(cut.len() - 4);
+        assert_eq!(apply(&rom, &cut, 0xE00), Err(IpsError::Truncated(5)));
+        assert_eq!(
+            apply(&rom, b"PATCH\0\0", 0xE00),
+            Err(IpsError::Truncated(5))
+        );
+        assert_eq!(
+            apply(&rom, b"PATCH\0\0\0\0\0\0", 0xE00)
+                .unwrap_err()
+                .to_string(),
+            "The patch ends inside the record at byte 5!"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+///
+/// The `Json` enum represents the JSON values read from reference traces and control commands,
+/// numbers being limited to non-negative integers.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Self>),
+    Object(Vec<(String, Self)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let mut parser = JsonParser { text, pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos == text.len() {
+            Ok(value)
+        } else {
+            Err("unexpected characters after the value")
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        self.as_object()?
+            .iter()
+            .find_map(|(name, value)| (name == key).then_some(value))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub const fn as_number(&self) -> Option<u64> {
+        match self {
+            Self::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Self)]> {
+        match self {
+            Self::Object(fields) => Some(fields),
+            _ => None,
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, literal: &str) -> bool {
+        let found = self.text[self.pos..].starts_with(literal);
+        if found {
+            self.pos += literal.len();
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Json, &'static str> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'0'..=b'9') => self.number(),
+            _ if self.eat("null") => Ok(Json::Null),
+            _ if self.eat("true") => Ok(Json::Bool(true)),
+            _ if self.eat("false") => Ok(Json::Bool(false)),
+            Some(b'-') => Err("negative numbers aren't supported"),
+            Some(_) => Err("expected a JSON value"),
+            None => Err("the value ends early"),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, &'static str> {
+        let digits = self.text[self.pos..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        let number = self.text[self.pos..self.pos + digits]
+            .parse()
+            .map_err(|_| "a number is too large")?;
+        self.pos += digits;
+        if matches!(self.peek(), Some(b'.' | b'e' | b'E')) {
+            return Err("only whole numbers are supported");
+        }
+        Ok(Json::Number(number))
+    }
+
+    fn string(&mut self) -> Result<String, &'static str> {
+        self.pos += 1;
+        let mut string = String::new();
+        let mut chars = self.text[self.pos..].char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += offset + 1;
+                    return Ok(string);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let code = u32::from_str_radix(&hex, 16).map_err(|_| "invalid escape")?;
+                        string.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    Some(c @ ('"' | '\\' | '/')) => string.push(c),
+                    _ => return Err("invalid escape"),
+                },
+                c => string.push(c),
+            }
+        }
+        Err("a string isn't closed")
+    }
+
+    fn array(&mut self) -> Result<Json, &'static str> {
+        self.pos += 1;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.eat("]") {
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            if self.eat("]") {
+                return Ok(Json::Array(values));
+            }
+            if !self.eat(",") {
+                return Err("expected `,` or `]`");
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, &'static str> {
+        self.pos += 1;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.eat("}") {
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err("expected a key");
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(":") {
+                return Err("expected `:`");
+            }
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            if self.eat("}") {
+                return Ok(Json::Object(fields));
+            }
+            if !self.eat(",") {
+                return Err("expected `,` or `}`");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod json_tests {
+    use super::Json;
+
+    #[test]
+    fn parses_json() {
+        assert_eq!(
+            Json::parse(r#" {"a": [1, true, null], "b\"": "xA"} "#),
+            Ok(Json::Object(vec![
+                (
+                    "a".to_string(),
+                    Json::Array(vec![Json::Number(1), Json::Bool(true), Json::Null])
+                ),
+                ("b\"".to_string(), Json::String("xA".to_string())),
+            ]))
+        );
+        assert!(Json::parse("{\"a\":1").is_err());
+        assert!(Json::parse("{\"a\":1.5}").is_err());
+        assert!(Json::parse("{\"a\":1} x").is_err());
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::chip8::FRAMES_PER_SECOND;
+use super::cpu::Instruction;
+use super::memory::Registers;
+
+///
+/// The `LATENCY_BUCKET` constant is how wide the buckets of the latency histogram are, the finest
+/// the latencies are told apart.
+///
+pub const LATENCY_BUCKET: Duration = Duration::from_micros(100);
+
+///
+/// Returns the hex key the instruction looks at and finds pressed, if any: `SKP` and `SKNP` on the
+/// key in their register, `LD Vx, K` on whichever key there is.
+///
+pub fn observed_key(
+    instruction: Instruction,
+    opcode: u16,
+    registers: &Registers,
+    pressed_key: Option<u8>,
+) -> Option<u8> {
+    match instruction {
+        Instruction::SKP | Instruction::SKNP => {
+            let key = registers.v[usize::from((opcode & 0x0F00) >> 8)];
+            pressed_key.filter(|&pressed| pressed == key)
+        }
+        Instruction::LDK => pressed_key,
+        _ => None,
+    }
+}
+
+///
+/// The `Histogram` struct represents latencies counted into `LATENCY_BUCKET` wide buckets, taking
+/// the same room however many there are.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// How many latencies fell in every bucket, by the bucket's index
+    buckets: BTreeMap<u64, u64>,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let bucket = latency.as_nanos() / LATENCY_BUCKET.as_nanos();
+        *self
+            .buckets
+            .entry(u64::try_from(bucket).unwrap_or(u64::MAX))
+            .or_default() += 1;
+        self.count += 1;
+    }
+
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    ///
+    /// Returns the latency `percent`% of the recorded ones are at most, by the nearest rank,
+    /// rounded down to its bucket. None before the first one.
+    ///
+    pub fn percentile(&self, percent: u32) -> Option<Duration> {
+        // The rank of the latency, from 1, never below the first
+        let rank = (self.count * u64::from(percent.min(100)))
+            .div_ceil(100)
+            .max(1);
+        let mut seen = 0;
+        self.buckets.iter().find_map(|(&bucket, &count)| {
+            seen += count;
+            (seen >= rank).then(|| LATENCY_BUCKET * u32::try_from(bucket).unwrap_or(u32::MAX))
+        })
+    }
+}
+
+///
+/// The `LatencyMeter` struct represents the input latency measured from when the frontend got a
+/// key press to when the game first looks at the key and finds it down.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LatencyMeter {
+    /// When every hex key was pressed, while the game hasn't seen it yet
+    pending: [Option<Instant>; 16],
+    histogram: Histogram,
+    /// The presses released before the game saw them
+    missed: u64,
+}
+
+impl LatencyMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Notes the hex key going down or up `at` the time the frontend got it. A key pressed again
+    /// before the game saw it keeps its first press.
+    ///
+    pub fn key(&mut self, hex_key: u8, pressed: bool, at: Instant) {
+        let pending = &mut self.pending[usize::from(hex_key & 0xF)];
+        if pressed {
+            pending.get_or_insert(at);
+        } else if pending.take().is_some() {
+            self.missed += 1;
+        }
+    }
+
+    ///
+    /// Lets go of every key, the presses the game hasn't seen yet counting as missed.
+    ///
+    pub fn release_keys(&mut self) {
+        for hex_key in 0..16 {
+            self.key(hex_key, false, Instant::now());
+        }
+    }
+
+    ///
+    /// Notes the game looking at the hex key `now`, returning the latency of its press if this is
+    /// the first time since.
+    ///
+    pub fn observe(&mut self, hex_key: u8, now: Instant) -> Option<Duration> {
+        let pressed = self.pending[usize::from(hex_key & 0xF)].take()?;
+        let latency = now.saturating_duration_since(pressed);
+        self.histogram.record(latency);
+        Some(latency)
+    }
+
+    pub const fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+
+    pub const fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+fn write_latency(f: &mut fmt::Formatter<'_>, name: &str, latency: Duration) -> fmt::Result {
+    let seconds = latency.as_secs_f64();
+    write!(
+        f,
+        "{name} {:.1} ms ({:.2} frames)",
+        seconds * 1000.0,
+        seconds * f64::from(FRAMES_PER_SECOND)
+    )
+}
+
+impl fmt::Display for LatencyMeter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (
+            self.histogram.percentile(0),
+            self.histogram.percentile(50),
+            self.histogram.percentile(95),
+        ) {
+            (Some(min), Some(median), Some(p95)) => {
+                write!(
+                    f,
+                    "Input latency over {} press(es): ",
+                    self.histogram.count()
+                )?;
+                write_latency(f, "min", min)?;
+                write_latency(f, ", median", median)?;
+                write_latency(f, ", p95", p95)?;
+            }
+            _ => write!(f, "Input latency: no key press seen by the game")?,
+        }
+        if self.missed > 0 {
+            write!(f, ", {} press(es) released unseen", self.missed)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod latency_tests {
+    use std::time::{Duration, Instant};
+
+    use super::{observed_key, Histogram, LatencyMeter};
+    use crate::emu::cpu::Instruction;
+    use crate::emu::memory::Registers;
+
+    const fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn percentiles_by_the_nearest_rank() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(50), None);
+        for latency in [3, 1, 2, 10, 4, 5, 6, 7, 8, 9] {
+            histogram.record(ms(latency));
+        }
+        assert_eq!(histogram.count(), 10);
+        assert_eq!(histogram.percentile(0), Some(ms(1)));
+        assert_eq!(histogram.percentile(50), Some(ms(5)));
+        assert_eq!(histogram.percentile(95), Some(ms(10)));
+        assert_eq!(histogram.percentile(100), Some(ms(10)));
+
+        // Latencies are told apart down to a tenth of a millisecond
+        let mut histogram = Histogram::new();
+        histogram.record(Duration::from_micros(1_234));
+        histogram.record(Duration::from_micros(1_299));
+        assert_eq!(histogram.percentile(95), Some(Duration::from_micros(1_200)));
+        histogram.record(Duration::from_secs(2));
+        assert_eq!(histogram.percentile(100), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn observes_the_keys_found_down() {
+        let mut registers = Registers::new();
+        registers.v[3] = 0x5;
+        // SKP V3, SKNP V3
+        for (instruction, opcode) in [(Instruction::SKP, 0xE39E), (Instruction::SKNP, 0xE3A1)] {
+            assert_eq!(
+                observed_key(instruction, opcode, &registers, Some(0x5)),
+                Some(0x5)
+            );
+            assert_eq!(
+                observed_key(instruction, opcode, &registers, Some(0x6)),
+                None
+            );
+            assert_eq!(observed_key(instruction, opcode, &registers, None), None);
+        }
+        // LD V3, K
+        assert_eq!(
+            observed_key(Instruction::LDK, 0xF30A, &registers, Some(0x6)),
+            Some(0x6)
+        );
+        assert_eq!(
+            observed_key(Instruction::LDK, 0xF30A, &registers, None),
+            None
+        );
+        assert_eq!(
+            observed_key(Instruction::ADD, 0x7301, &registers, Some(0x5)),
+            None
+        );
+    }
+
+    #[test]
+    fn measures_from_the_first_press() {
+        let start = Instant::now();
+        let mut meter = LatencyMeter::new();
+        assert_eq!(meter.observe(0x5, start), None);
+
+        meter.key(0x5, true, start);
+        // Pressed again before the game looked, e.g. by the key repeating
+        meter.key(0x5, true, start + ms(5));
+        assert_eq!(meter.observe(0x6, start + ms(8)), None);
+        assert_eq!(meter.observe(0x5, start + ms(8)), Some(ms(8)));
+        // Only the first look counts
+        assert_eq!(meter.observe(0x5, start + ms(9)), None);
+
+        meter.key(0x6, true, start + ms(10));
+        meter.key(0x6, false, start + ms(12));
+        assert_eq!(meter.observe(0x6, start + ms(20)), None);
+        meter.key(0x7, true, start + ms(10));
+        meter.release_keys();
+        assert_eq!(meter.missed(), 2);
+        assert_eq!(meter.histogram().count(), 1);
+    }
+
+    #[test]
+    fn reports_in_milliseconds_and_frames() {
+        let start = Instant::now();
+        let mut meter = LatencyMeter::new();
+        assert_eq!(
+            meter.to_string(),
+            "Input latency: no key press seen by the game"
+        );
+        for (key, latency) in [(1, 5), (2, 50), (3, 10)] {
+            meter.key(key, true, start);
+            meter.observe(key, start + ms(latency));
+        }
+        meter.key(4, true, start);
+        meter.key(4, false, start);
+        assert_eq!(
+            meter.to_string(),
+            "Input latency over 3 press(es): min 5.0 ms (0.30 frames), median 10.0 ms (0.60 frames), \
+             p95 50.0 ms (3.00 frames), 1 press(es) released unseen"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use super::io::Screen;
+
+///
+/// The `PALETTE_SIZE` constant is how many colors MegaChip's palette has, one for every value a
+/// pixel can take.
+///
+pub const PALETTE_SIZE: usize = 256;
+
+///
+/// The `ColorPalette` struct represents the colors of the pixel values of MegaChip's mega mode,
+/// loaded by the ROM, and how faded the whole screen is.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorPalette {
+    /// The RGBA color of every value, 0 being the transparent black of blank pixels
+    colors: [[u8; 4]; PALETTE_SIZE],
+    /// How opaque the whole screen is, 255 not fading it at all
+    alpha: u8,
+}
+
+impl Default for ColorPalette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ColorPalette {
+    ///
+    /// Returns a palette of transparent black, what every value is until the ROM loads colors.
+    ///
+    pub const fn new() -> Self {
+        Self {
+            colors: [[0; 4]; PALETTE_SIZE],
+            alpha: u8::MAX,
+        }
+    }
+
+    ///
+    /// Loads the colors, 4 bytes each as alpha, red, green and blue, into the values from 1 on as
+    /// `02nn` does. Value 0 stays blank, and colors past the last value are left out.
+    ///
+    pub fn load(&mut self, argb: &[u8]) {
+        for (color, bytes) in self.colors[1..].iter_mut().zip(argb.chunks_exact(4)) {
+            *color = [bytes[1], bytes[2], bytes[3], bytes[0]];
+        }
+    }
+
+    ///
+    /// Returns the RGBA color of the pixel value.
+    ///
+    pub const fn color(&self, value: u8) -> [u8; 4] {
+        self.colors[value as usize]
+    }
+
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.alpha = alpha;
+    }
+
+    pub const fn alpha(&self) -> u8 {
+        self.alpha
+    }
+
+    ///
+    /// Returns the color the pixel value shows in over black, faded by its own alpha and the
+    /// screen's.
+    ///
+    pub fn rgb(&self, value: u8) -> [u8; 3] {
+        let [red, green, blue, alpha] = self.color(value);
+        let opacity = u32::from(alpha) * u32::from(self.alpha);
+        [red, green, blue].map(|channel| (u32::from(channel) * opacity / (255 * 255)) as u8)
+    }
+}
+
+///
+/// The `Blend` enum represents how MegaChip's sprites were meant to mix with what's under them,
+/// set by `080n`.
+///
+/// The screen keeps palette values rather than colors, so there's nothing to mix: every mode
+/// draws as `Normal` does, copying the sprite's pixels that aren't blank.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Blend {
+    #[default]
+    Normal,
+    Opacity25,
+    Opacity50,
+    Opacity75,
+    Add,
+    Multiply,
+}
+
+impl Blend {
+    ///
+    /// Returns the mode `080n` picks, none for the `n` that aren't one.
+    ///
+    pub const fn from_mode(mode: u8) -> Option<Self> {
+        match mode {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Opacity25),
+            2 => Some(Self::Opacity50),
+            3 => Some(Self::Opacity75),
+            4 => Some(Self::Add),
+            5 => Some(Self::Multiply),
+            _ => None,
+        }
+    }
+}
+
+///
+/// The `MegaState` struct represents what MegaChip draws with in mega mode: the palette, the size
+/// of the sprites, the blend mode and the value sprites collide with.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MegaState {
+    /// Whether mega mode is on, from `0011` to `0010`
+    pub on: bool,
+    pub palette: ColorPalette,
+    /// How many pixels wide the sprites `DRW` draws are, set by `03nn`
+    pub sprite_width: usize,
+    /// How many rows tall the sprites `DRW` draws are, set by `04nn`
+    pub sprite_height: usize,
+    pub blend: Blend,
+    /// The value of the pixels a sprite collides with when it's drawn over them, set by `09nn`
+    pub collision: u8,
+}
+
+impl Default for MegaState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MegaState {
+    pub const fn new() -> Self {
+        Self {
+            on: false,
+            palette: ColorPalette::new(),
+            sprite_width: 0,
+            sprite_height: 0,
+            blend: Blend::Normal,
+            collision: 0,
+        }
+    }
+
+    ///
+    /// Draws the `sprite_width` × `sprite_height` sprite, a byte per pixel from `sprite`, with
+    /// its top left corner at `(x, y)`, returning whether it collided.
+    ///
+    /// Pixels are copied over the screen rather than flipped, the blank ones leaving what's under
+    /// them, and the sprite is cut off at the edges instead of wrapping around. It collides when
+    /// a pixel lands on one of the `collision` value, blank pixels never colliding.
+    ///
+    pub fn blit(&self, screen: &mut dyn Screen, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut collided = false;
+        let rows = sprite
+            .chunks(self.sprite_width.max(1))
+            .take(self.sprite_height);
+        for (screen_y, row) in (y..screen.height()).zip(rows) {
+            for (screen_x, &value) in (x..screen.width()).zip(row) {
+                if value == 0 {
+                    continue;
+                }
+                let under = screen.get_pixel(screen_x, screen_y);
+                collided |= under != 0 && under == self.collision;
+                screen.set_pixel(screen_x, screen_y, value);
+            }
+        }
+        screen.draw();
+        collided
+    }
+}
+
+#[cfg(test)]
+mod mega_tests {
+    use super::{Blend, ColorPalette, MegaState};
+    use crate::emu::io::{BufferScreen, Screen, MEGA_GRID_HEIGHT, MEGA_GRID_WIDTH};
+
+    #[test]
+    fn loads_argb_colors_from_the_second_value() {
+        let mut palette = ColorPalette::new();
+        palette.load(&[0xFF, 0x10, 0x20, 0x30, 0x80, 0xFF, 0x00, 0x00, 0xAA]);
+        // Value 0 stays blank, and the byte short of a third color is left out
+        assert_eq!(palette.color(0), [0; 4]);
+        assert_eq!(palette.color(1), [0x10, 0x20, 0x30, 0xFF]);
+        assert_eq!(palette.color(2), [0xFF, 0x00, 0x00, 0x80]);
+        assert_eq!(palette.color(3), [0; 4]);
+
+        // Shown over black, faded by the color's alpha and the screen's
+        assert_eq!(palette.rgb(1), [0x10, 0x20, 0x30]);
+        assert_eq!(palette.rgb(2), [0x80, 0x00, 0x00]);
+        palette.set_alpha(0);
+        assert_eq!(palette.rgb(1), [0; 3]);
+
+        // Only 255 values have a color to load
+        let mut palette = ColorPalette::new();
+        palette.load(&[0xFF; 4 * 300]);
+        assert_eq!(palette.color(255), [0xFF; 4]);
+    }
+
+    #[test]
+    fn blits_over_the_screen_without_flipping() {
+        let mut screen = BufferScreen::new();
+        screen.set_size(MEGA_GRID_WIDTH, MEGA_GRID_HEIGHT);
+        let mega = MegaState {
+            sprite_width: 3,
+            sprite_height: 2,
+            collision: 7,
+            ..MegaState::new()
+        };
+        // Two rows of three pixels
+        let sprite = [1, 0, 2, 0, 7, 7];
+        assert!(!mega.blit(&mut screen, 10, 20, &sprite));
+        let row = |screen: &BufferScreen, y: usize| {
+            screen.framebuffer()[y * MEGA_GRID_WIDTH + 9..y * MEGA_GRID_WIDTH + 14].to_vec()
+        };
+        assert_eq!(row(&screen, 20), [0, 1, 0, 2, 0]);
+        assert_eq!(row(&screen, 21), [0, 0, 7, 7, 0]);
+
+        // Landing on the collision value collides, and the blank pixel leaves the 7 under it
+        let mega = MegaState {
+            sprite_width: 2,
+            sprite_height: 1,
+            ..mega
+        };
+        assert!(mega.blit(&mut screen, 11, 21, &[3, 0]));
+        assert_eq!(row(&screen, 21), [0, 0, 3, 7, 0]);
+        // A value other than the collision one doesn't collide
+        assert!(!mega.blit(&mut screen, 10, 20, &[4, 4]));
+        assert_eq!(row(&screen, 20), [0, 4, 4, 2, 0]);
+    }
+
+    #[test]
+    fn cuts_sprites_off_at_the_edges() {
+        let mut screen = BufferScreen::new();
+        screen.set_size(MEGA_GRID_WIDTH, MEGA_GRID_HEIGHT);
+        let mega = MegaState {
+            sprite_width: 2,
+            sprite_height: 2,
+            ..MegaState::new()
+        };
+        mega.blit(
+            &mut screen,
+            MEGA_GRID_WIDTH - 1,
+            MEGA_GRID_HEIGHT - 1,
+            &[5; 4],
+        );
+        let framebuffer = screen.framebuffer();
+        assert_eq!(framebuffer[MEGA_GRID_WIDTH * MEGA_GRID_HEIGHT - 1], 5);
+        assert_eq!(framebuffer.iter().filter(|&&pixel| pixel != 0).count(), 1);
+
+        assert_eq!(Blend::from_mode(5), Some(Blend::Multiply));
+        assert_eq!(Blend::from_mode(6), None);
+    }
+}
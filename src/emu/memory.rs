@@ -1,62 +1,230 @@
-use std::error::Error;
-use std::fmt::{Display, Formatter};
+use core::fmt::{self, Display, Formatter};
 
 ///
 /// The `RAM_SIZE` constant is the size of the RAM in bytes for the CHIP-8.
 ///
-const RAM_SIZE: usize = 4_096;
+pub const RAM_SIZE: usize = 4_096;
 
 ///
 /// The `RESERVED_SIZE` constant is the size of the reserved memory in the RAM for sprites.
 ///
-const RESERVED_SIZE: usize = 80;
+pub const RESERVED_SIZE: usize = 80;
 
 ///
 /// The `DEFAULT_PROGRAM_START_OFFSET` constant is the default offset for the start of the program in the RAM.
 ///
-const DEFAULT_PROGRAM_START_OFFSET: usize = 0x200;
+pub const DEFAULT_PROGRAM_START_OFFSET: usize = 0x200;
 
 ///
-/// The `Registers` struct represents the registers of the CHIP-8.
+/// The `HIRES_SIGNATURE` constant is the first instruction of HiRes CHIP-8 ROMs, `1260`: a jump
+/// into the interpreter patch they carry, which switches the VIP to two pages of display.
 ///
+pub const HIRES_SIGNATURE: [u8; 2] = [0x12, 0x60];
+
+///
+/// The `HIRES_START_ADDRESS` constant is where the programs of HiRes CHIP-8 ROMs start, past
+/// the interpreter patch the emulator doesn't run.
+///
+pub const HIRES_START_ADDRESS: u16 = 0x2C0;
+
+///
+/// The `STACK_SIZE` constant is how many calls the CHIP-8 can nest.
+///
+pub const STACK_SIZE: usize = 16;
+
+///
+/// The `Registers` struct represents the registers of the CHIP-8, the stack included.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Registers {
-    pub pc: u16,      // current instruction in memory
-    pub sp: Vec<u16>, // stack pointer
-    pub i: u16,       // index register
-    pub st: u8,       // sound timer
-    pub dt: u8,       // delay timer
-    pub v: [u8; 16],  // general registers
+    pub pc: u16,     // current instruction in memory
+    pub sp: u8,      // stack pointer, how many calls are pending
+    pub i: u16,      // index register
+    pub st: u8,      // sound timer
+    pub dt: u8,      // delay timer
+    pub v: [u8; 16], // general registers
+    /// The return addresses of the pending calls, up to `sp`
+    pub stack: [u16; STACK_SIZE],
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Registers {
     pub const fn new() -> Self {
         Self {
             pc: DEFAULT_PROGRAM_START_OFFSET as u16,
-            sp: Vec::new(),
+            sp: 0,
             i: 0,
             st: 0,
             dt: 0,
             v: [0; 16],
+            stack: [0; STACK_SIZE],
+        }
+    }
+
+    pub fn push(&mut self, address: u16) -> Result<(), RamError> {
+        let slot = self
+            .stack
+            .get_mut(usize::from(self.sp))
+            .ok_or(RamError::StackOverflow)?;
+        *slot = address;
+        self.sp += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<u16, RamError> {
+        let sp = self.sp.checked_sub(1).ok_or(RamError::StackUnderflow)?;
+        let address = *self
+            .stack
+            .get(usize::from(sp))
+            .ok_or(RamError::StackOverflow)?;
+        self.sp = sp;
+        Ok(address)
+    }
+
+    ///
+    /// Returns the return addresses of the pending calls, the innermost one last. Each one
+    /// follows the `CALL` instruction that pushed it.
+    ///
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..usize::from(self.sp).min(STACK_SIZE)]
+    }
+}
+
+///
+/// The `Register` enum names one of the registers, as debugging tools spell them: `v0`-`vf`, `i`,
+/// `pc`, `dt` or `st`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    Dt,
+    St,
+}
+
+impl Register {
+    ///
+    /// Parses a register name, whatever its case.
+    ///
+    pub fn parse(name: &str) -> Option<Self> {
+        let named = [
+            ("pc", Self::Pc),
+            ("i", Self::I),
+            ("dt", Self::Dt),
+            ("st", Self::St),
+        ];
+        if let Some(&(_, register)) = named.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            return Some(register);
+        }
+        match name.as_bytes() {
+            &[b'v' | b'V', x] => (x as char).to_digit(16).map(|x| Self::V(x as u8)),
+            _ => None,
+        }
+    }
+
+    pub const fn get(self, registers: &Registers) -> u16 {
+        match self {
+            Self::V(x) => registers.v[(x & 0xF) as usize] as u16,
+            Self::I => registers.i,
+            Self::Pc => registers.pc,
+            Self::Dt => registers.dt as u16,
+            Self::St => registers.st as u16,
         }
     }
 }
 
+///
+/// The `REGISTER_LINES` constant is how many lines `Registers::lines` returns: the special
+/// registers, then the general ones in a 4×4 grid.
+///
+pub const REGISTER_LINES: usize = 5;
+
+///
+/// The `RegisterLine` struct represents one line of the register dump, e.g.
+/// `PC=0x0200 I=0x0000 DT=0x00 ST=0x00` or `V4=0x00 V5=0x00 V6=0x00 V7=0x00`.
+///
+#[derive(Debug, Clone, Copy)]
+pub struct RegisterLine<'a> {
+    registers: &'a Registers,
+    line: usize,
+}
+
+impl Display for RegisterLine<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let registers = self.registers;
+        let Some(row) = self.line.checked_sub(1) else {
+            return write!(
+                f,
+                "PC=0x{:04X} I=0x{:04X} DT=0x{:02X} ST=0x{:02X}",
+                registers.pc, registers.i, registers.dt, registers.st
+            );
+        };
+        for x in row * 4..row * 4 + 4 {
+            let separator = if x % 4 == 0 { "" } else { " " };
+            write!(f, "{separator}V{x:X}=0x{:02X}", registers.v[x])?;
+        }
+        Ok(())
+    }
+}
+
+impl Registers {
+    ///
+    /// Returns the lines of the register dump without the stack, for views with room for
+    /// little else.
+    ///
+    pub fn lines(&self) -> impl Iterator<Item = RegisterLine<'_>> {
+        (0..REGISTER_LINES).map(|line| RegisterLine {
+            registers: self,
+            line,
+        })
+    }
+}
+
+///
+/// Prints the registers over six lines: the special ones, `V0`-`VF` in a 4×4 grid and the stack.
+///
+impl Display for Registers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for line in self.lines() {
+            writeln!(f, "{line}")?;
+        }
+        write!(f, "Stack: [")?;
+        for (n, address) in self.stack().iter().enumerate() {
+            let separator = if n == 0 { "" } else { ", " };
+            write!(f, "{separator}0x{address:04X}")?;
+        }
+        write!(f, "]")
+    }
+}
+
 ///
 /// The `RamError` enum represents the possible errors that can occur when loading a program into the RAM.
 ///
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RamError {
     NotEnoughSpace,
-    OutOfBound,
+    /// The address that was accessed
+    OutOfBound(usize),
+    StackOverflow,
+    StackUnderflow,
 }
 
-impl Error for RamError {}
+#[cfg(feature = "std")]
+impl std::error::Error for RamError {}
 
 impl Display for RamError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
             Self::NotEnoughSpace => write!(f, "Not enough space to load program!"),
-            Self::OutOfBound => write!(f, "Out of bound memory!"),
+            Self::OutOfBound(address) => write!(f, "Out of bound memory at 0x{address:04X}!"),
+            Self::StackOverflow => write!(f, "Stack overflow!"),
+            Self::StackUnderflow => write!(f, "Stack underflow, returned without a call!"),
         }
     }
 }
@@ -68,6 +236,12 @@ pub struct Ram {
     data: [u8; RAM_SIZE],
 }
 
+impl Default for Ram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Ram {
     pub fn new() -> Self {
         let mut ram = Self {
@@ -100,59 +274,94 @@ impl Ram {
         ram
     }
 
-    #[allow(dead_code)]
     pub fn load(&mut self, data: &[u8]) -> Result<(), RamError> {
-        if data.len() <= RAM_SIZE - DEFAULT_PROGRAM_START_OFFSET {
-            for (i, byte) in data.iter().enumerate() {
-                self.data[i + DEFAULT_PROGRAM_START_OFFSET] = *byte;
-            }
+        self.load_at(DEFAULT_PROGRAM_START_OFFSET, data)
+    }
+
+    pub fn load_at(&mut self, address: usize, data: &[u8]) -> Result<(), RamError> {
+        if address <= RAM_SIZE && data.len() <= RAM_SIZE - address {
+            self.data[address..address + data.len()].copy_from_slice(data);
             Ok(())
         } else {
             Err(RamError::NotEnoughSpace)
         }
     }
 
-    #[allow(dead_code)]
+    pub const fn bytes(&self) -> &[u8; RAM_SIZE] {
+        &self.data
+    }
+
     pub const fn read_byte(&self, address: usize) -> Result<u8, RamError> {
         if address < RAM_SIZE {
             Ok(self.data[address])
         } else {
-            Err(RamError::OutOfBound)
+            Err(RamError::OutOfBound(address))
         }
     }
 
-    #[allow(dead_code)]
+    ///
+    /// Returns the `length` bytes from `address` on, failing at the first one past the end.
+    ///
+    pub fn read_bytes(&self, address: usize, length: usize) -> Result<&[u8], RamError> {
+        self.data
+            .get(address..address.saturating_add(length))
+            .ok_or_else(|| RamError::OutOfBound(address.max(RAM_SIZE)))
+    }
+
     pub fn write_byte(&mut self, address: usize, value: u8) -> Result<(), RamError> {
         if address < RAM_SIZE {
             self.data[address] = value;
             Ok(())
         } else {
-            Err(RamError::OutOfBound)
+            Err(RamError::OutOfBound(address))
         }
     }
 
-    #[allow(dead_code)]
     pub fn read_word(&self, address: usize) -> Result<u16, RamError> {
-        if address < RAM_SIZE {
+        if address + 1 < RAM_SIZE {
             Ok(u16::from(self.data[address]) << 8 | u16::from(self.data[address + 1]))
         } else {
-            Err(RamError::OutOfBound)
+            Err(RamError::OutOfBound(address))
         }
     }
 
-    #[allow(dead_code)]
     pub fn write_word(&mut self, address: usize, value: u16) -> Result<(), RamError> {
-        if address < RAM_SIZE {
+        if address + 1 < RAM_SIZE {
             self.data[address] = (value >> 8) as u8;
             self.data[address + 1] = value as u8;
             Ok(())
         } else {
-            Err(RamError::OutOfBound)
+            Err(RamError::OutOfBound(address))
         }
     }
 }
 
+#[cfg(test)]
 mod ram_tests {
+    #[test]
+    fn registers_dump() {
+        let mut registers = super::Registers::new();
+        registers.pc = 0x20A;
+        registers.i = 0x300;
+        registers.dt = 0x3C;
+        registers.v[0x0] = 0x2A;
+        registers.v[0x7] = 0x07;
+        registers.v[0xF] = 0x01;
+        registers.push(0x204).unwrap();
+        registers.push(0x246).unwrap();
+        assert_eq!(
+            registers.to_string(),
+            "\
+PC=0x020A I=0x0300 DT=0x3C ST=0x00
+V0=0x2A V1=0x00 V2=0x00 V3=0x00
+V4=0x00 V5=0x00 V6=0x00 V7=0x07
+V8=0x00 V9=0x00 VA=0x00 VB=0x00
+VC=0x00 VD=0x00 VE=0x00 VF=0x01
+Stack: [0x0204, 0x0246]"
+        );
+        assert_eq!(registers.lines().count(), super::REGISTER_LINES);
+    }
+
     #[test]
     fn read_byte() {
         let ram = super::Ram::new();
@@ -170,6 +379,11 @@ mod ram_tests {
     fn read_word() {
         let ram = super::Ram::new();
         assert_eq!(ram.read_word(0usize).unwrap(), 0xF090);
+        assert!(ram.read_word(super::RAM_SIZE - 2).is_ok());
+        assert!(matches!(
+            ram.read_word(super::RAM_SIZE - 1),
+            Err(super::RamError::OutOfBound(0xFFF))
+        ));
     }
 
     #[test]
@@ -178,4 +392,34 @@ mod ram_tests {
         ram.write_word(0usize, 1024u16).unwrap();
         assert_eq!(ram.read_word(0usize).unwrap(), 1024u16);
     }
+
+    #[test]
+    fn stack_is_bounded() {
+        let mut registers = super::Registers::new();
+        assert_eq!(registers.pop(), Err(super::RamError::StackUnderflow));
+        for address in 0..16 {
+            registers.push(0x200 + address * 2).unwrap();
+        }
+        assert_eq!(registers.push(0x220), Err(super::RamError::StackOverflow));
+        assert_eq!(registers.sp, 16);
+        assert_eq!(registers.stack().len(), 16);
+
+        // Last in, first out
+        assert_eq!(registers.pop(), Ok(0x21E));
+        assert_eq!(registers.pop(), Ok(0x21C));
+        assert_eq!(registers.stack().last(), Some(&0x21A));
+        registers.push(0x300).unwrap();
+        assert_eq!(registers.stack().last(), Some(&0x300));
+        for _ in 0..15 {
+            registers.pop().unwrap();
+        }
+        assert_eq!(registers.pop(), Err(super::RamError::StackUnderflow));
+        assert!(registers.stack().is_empty());
+
+        // A stack pointer set out of range by a debugger doesn't panic
+        registers.sp = 20;
+        assert_eq!(registers.stack().len(), 16);
+        assert_eq!(registers.push(0x200), Err(super::RamError::StackOverflow));
+        assert_eq!(registers.pop(), Err(super::RamError::StackOverflow));
+    }
 }
@@ -1,4 +1,66 @@
-mod io;
-mod memory;
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod asm;
+#[cfg(feature = "std")]
+pub mod breakpoint;
+#[cfg(feature = "std")]
+pub mod builder;
+pub mod chip8x;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "std")]
+pub mod coverage;
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod crash;
+#[cfg(feature = "std")]
+pub mod disasm;
+#[cfg(feature = "std")]
+pub mod fault;
+pub mod gfx;
+#[cfg(feature = "std")]
+pub mod hooks;
+#[cfg(feature = "std")]
+mod instruments;
+pub mod io;
+#[cfg(feature = "std")]
+pub mod ips;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "native")]
+pub mod latency;
+pub mod mega;
+pub mod memory;
+#[cfg(feature = "std")]
+pub mod poke;
+pub mod quirks;
+#[cfg(feature = "native")]
+pub mod remote;
+#[cfg(feature = "native")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod rom;
+#[cfg(feature = "native")]
+pub mod schedule;
+#[cfg(feature = "std")]
+pub mod screenshot;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "std")]
+pub mod state;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod turbo;
+#[cfg(feature = "std")]
+pub mod uninit;
+#[cfg(feature = "std")]
+pub mod verify;
 
+#[cfg(feature = "std")]
 pub mod chip8;
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::breakpoint::parse_number;
+use super::memory::{Ram, RAM_SIZE};
+
+///
+/// The `Poke` struct represents a byte of RAM forced to a value, e.g. `0x3A2=0x63` for 99 lives.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Poke {
+    /// Below `RAM_SIZE`
+    pub address: u16,
+    pub value: u8,
+}
+
+impl Poke {
+    ///
+    /// Writes the byte into the RAM.
+    ///
+    pub fn apply(self, ram: &mut Ram) {
+        // Can't fail, the address was checked when it was parsed
+        let _ = ram.write_byte(usize::from(self.address), self.value);
+    }
+}
+
+impl fmt::Display for Poke {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:03X}=0x{:02X}", self.address, self.value)
+    }
+}
+
+///
+/// The `PokeError` struct represents a poke that isn't an address of the RAM and a byte.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PokeError(String);
+
+impl Error for PokeError {}
+
+impl fmt::Display for PokeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid poke `{}`, expected an address below 0x{RAM_SIZE:X} and a byte, e.g. `0x3A2=0x63`!",
+            self.0
+        )
+    }
+}
+
+impl FromStr for Poke {
+    type Err = PokeError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || PokeError(text.to_string());
+        let (address, value) = text.split_once('=').ok_or_else(error)?;
+        let address = parse_number(address.trim())
+            .ok()
+            .filter(|&address| usize::from(address) < RAM_SIZE)
+            .ok_or_else(error)?;
+        let value = parse_number(value.trim())
+            .ok()
+            .and_then(|value| u8::try_from(value).ok())
+            .ok_or_else(error)?;
+        Ok(Self { address, value })
+    }
+}
+
+///
+/// The `Pokes` struct represents the frozen pokes, written over the RAM after every frame so
+/// whatever the game wrote there doesn't stick.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Pokes {
+    values: BTreeMap<u16, u8>,
+}
+
+impl Pokes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Freezes the byte at the poke's address, returning the value it was frozen to before. The
+    /// last poke of an address wins.
+    ///
+    pub fn add(&mut self, poke: Poke) -> Option<u8> {
+        self.values.insert(poke.address, poke.value)
+    }
+
+    ///
+    /// Lets go of the byte at `address`, returning whether it was frozen.
+    ///
+    pub fn remove(&mut self, address: u16) -> bool {
+        self.values.remove(&address).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    ///
+    /// Returns the frozen pokes, by address.
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = Poke> + '_ {
+        self.values
+            .iter()
+            .map(|(&address, &value)| Poke { address, value })
+    }
+
+    ///
+    /// Writes the frozen values over the RAM.
+    ///
+    pub fn apply(&self, ram: &mut Ram) {
+        for poke in self.iter() {
+            poke.apply(ram);
+        }
+    }
+}
+
+impl fmt::Display for Pokes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, poke) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{poke}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod poke_tests {
+    use super::{Poke, PokeError, Pokes};
+    use crate::emu::memory::Ram;
+
+    #[test]
+    fn parses_pokes() {
+        assert_eq!(
+            "0x3A2=0x63".parse(),
+            Ok(Poke {
+                address: 0x3A2,
+                value: 0x63
+            })
+        );
+        assert_eq!(
+            "930 = 99".parse::<Poke>().unwrap().to_string(),
+            "0x3A2=0x63"
+        );
+        assert_eq!("0XFFF=0XFF".parse::<Poke>().unwrap().address, 0xFFF);
+        for invalid in [
+            "0x3A2",
+            "0x1000=1",
+            "0x3A2=0x100",
+            "=1",
+            "0x3A2=",
+            "lives=99",
+        ] {
+            assert_eq!(
+                invalid.parse::<Poke>(),
+                Err(PokeError(invalid.to_string())),
+                "{invalid}"
+            );
+        }
+        assert_eq!(
+            "0x3A2=256".parse::<Poke>().unwrap_err().to_string(),
+            "Invalid poke `0x3A2=256`, expected an address below 0x1000 and a byte, e.g. `0x3A2=0x63`!"
+        );
+    }
+
+    #[test]
+    fn the_last_poke_of_an_address_wins() {
+        let mut pokes = Pokes::new();
+        assert_eq!(pokes.add("0x3A2=3".parse().unwrap()), None);
+        pokes.add("0x200=1".parse().unwrap());
+        assert_eq!(pokes.add("0x3A2=0x63".parse().unwrap()), Some(3));
+        assert_eq!(pokes.to_string(), "0x200=0x01, 0x3A2=0x63");
+
+        assert!(pokes.remove(0x200));
+        assert!(!pokes.remove(0x200));
+        assert_eq!(pokes.iter().count(), 1);
+        pokes.clear();
+        assert!(pokes.is_empty());
+        assert_eq!(pokes.to_string(), "");
+    }
+
+    #[test]
+    fn writes_over_the_ram() {
+        let mut ram = Ram::new();
+        ram.write_byte(0x3A2, 0x01).unwrap();
+        ram.write_byte(0x3A3, 0x02).unwrap();
+        let mut pokes = Pokes::new();
+        pokes.add("0x3A2=0x63".parse().unwrap());
+        pokes.add("0x3A2=0x09".parse().unwrap());
+        pokes.apply(&mut ram);
+        assert_eq!(ram.read_byte(0x3A2), Ok(0x09));
+        // Bytes that aren't frozen are left alone
+        assert_eq!(ram.read_byte(0x3A3), Ok(0x02));
+    }
+}
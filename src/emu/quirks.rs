@@ -0,0 +1,149 @@
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use std::fmt;
+#[cfg(feature = "std")]
+use std::str::FromStr;
+
+use super::memory::HIRES_SIGNATURE;
+
+///
+/// The `Quirks` struct represents the behaviors that differ between CHIP-8 interpreters, named
+/// after the quirks checked by the common test ROMs.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift `Vx` in place instead of shifting `Vy` into `Vx`
+    pub shift: bool,
+    /// `Fx55`/`Fx65` leave `I` pointing past the last register they copied
+    pub memory: bool,
+    /// `Bnnn` jumps to `nnn + Vx`, where `x` is the highest nibble of `nnn`, instead of `nnn + V0`
+    pub jump: bool,
+}
+
+impl Default for Quirks {
+    ///
+    /// The behavior the emulator always had: in-place shifts, incrementing `I` and jumping with `V0`.
+    ///
+    fn default() -> Self {
+        Self {
+            shift: true,
+            memory: true,
+            jump: false,
+        }
+    }
+}
+
+///
+/// The `Variant` enum represents the CHIP-8 dialects the emulator can behave like.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Chip8,
+    SChip,
+    /// The VIP's CHIP-8 with two pages of display, 64×64 pixels
+    HiRes,
+    /// SUPER-CHIP with a mode of 256×192 pixels in 256 colors
+    MegaChip,
+    /// The VIP's CHIP-8 with the VP-590 color board and a second keypad
+    Chip8X,
+}
+
+impl Variant {
+    ///
+    /// Returns the variant the ROM says it needs, when it starts with the `HIRES_SIGNATURE` of
+    /// HiRes CHIP-8. Other ROMs don't tell.
+    ///
+    pub fn detect(rom: &[u8]) -> Option<Self> {
+        rom.starts_with(&HIRES_SIGNATURE).then_some(Self::HiRes)
+    }
+
+    pub const fn quirks(self) -> Quirks {
+        match self {
+            // HiRes CHIP-8 only patched the display of the VIP's interpreter
+            Self::Chip8 | Self::HiRes | Self::Chip8X => Quirks {
+                shift: false,
+                memory: true,
+                jump: false,
+            },
+            Self::SChip | Self::MegaChip => Quirks {
+                shift: true,
+                memory: false,
+                jump: true,
+            },
+        }
+    }
+}
+
+///
+/// The `VariantError` struct represents a variant name that isn't supported.
+///
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantError(String);
+
+#[cfg(feature = "std")]
+impl Error for VariantError {}
+
+#[cfg(feature = "std")]
+impl fmt::Display for VariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown variant `{}`, expected `chip8`, `schip`, `hires`, `megachip` or `chip8x`!",
+            self.0
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl FromStr for Variant {
+    type Err = VariantError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Self::Chip8),
+            "schip" | "superchip" | "super-chip" => Ok(Self::SChip),
+            "hires" | "hires-chip8" | "hires-chip-8" => Ok(Self::HiRes),
+            "megachip" | "mega-chip" | "megachip8" => Ok(Self::MegaChip),
+            "chip8x" | "chip-8x" => Ok(Self::Chip8X),
+            _ => Err(VariantError(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod quirks_tests {
+    use super::{Quirks, Variant};
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn variant_names() {
+        assert_eq!("chip8".parse(), Ok(Variant::Chip8));
+        assert_eq!("CHIP-8".parse(), Ok(Variant::Chip8));
+        assert_eq!("schip".parse(), Ok(Variant::SChip));
+        assert_eq!("HiRes".parse(), Ok(Variant::HiRes));
+        assert_eq!("MegaChip".parse(), Ok(Variant::MegaChip));
+        assert_eq!("CHIP-8X".parse(), Ok(Variant::Chip8X));
+        assert_eq!(
+            "xochip".parse::<Variant>().unwrap_err().to_string(),
+            "Unknown variant `xochip`, expected `chip8`, `schip`, `hires`, `megachip` or `chip8x`!"
+        );
+    }
+
+    #[test]
+    fn hires_roms_are_detected() {
+        assert_eq!(Variant::detect(&[0x12, 0x60, 0x00]), Some(Variant::HiRes));
+        assert_eq!(Variant::detect(&[0x12, 0x62]), None);
+        assert_eq!(Variant::detect(&[0x12]), None);
+        assert_eq!(Variant::HiRes.quirks(), Variant::Chip8.quirks());
+    }
+
+    #[test]
+    fn variant_quirks_differ_from_the_default() {
+        assert_ne!(Variant::Chip8.quirks(), Quirks::default());
+        assert_ne!(Variant::SChip.quirks(), Quirks::default());
+        assert!(!Variant::Chip8.quirks().shift);
+        assert!(Variant::SChip.quirks().jump);
+    }
+}
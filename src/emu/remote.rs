@@ -0,0 +1,415 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use super::chip8::Chip8;
+use super::json::Json;
+use super::memory::{Register, RAM_SIZE};
+use super::trace::escape_json;
+
+///
+/// The `Command` enum represents what a control client asks the emulator to do, one JSON object
+/// per line such as `{"cmd": "read_mem", "addr": 768, "len": 3}`.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    KeyDown(u8),
+    KeyUp(u8),
+    Pause,
+    Resume,
+    Step(u64),
+    /// A register by name (`v0`-`vf`, `i`, `pc`, `dt` or `st`), or all of them
+    ReadReg(Option<String>),
+    ReadMem {
+        addr: u16,
+        len: u16,
+    },
+    Screenshot(String),
+    Framebuffer,
+    Quit,
+}
+
+impl Command {
+    ///
+    /// Parses a line of the protocol into a command, or into the reason it isn't one.
+    ///
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let json = Json::parse(line).map_err(|reason| format!("Invalid JSON: {reason}"))?;
+        let name = json
+            .get("cmd")
+            .and_then(Json::as_str)
+            .ok_or("Expected a `cmd` string")?;
+        let number = |field: &str| {
+            json.get(field)
+                .and_then(Json::as_number)
+                .ok_or_else(|| format!("`{name}` needs a `{field}` number"))
+        };
+        let key = || {
+            number("key").and_then(|key| {
+                u8::try_from(key)
+                    .ok()
+                    .filter(|&key| key < 16)
+                    .ok_or_else(|| format!("Invalid key {key}, expected 0 to 15"))
+            })
+        };
+        let address = |field: &str| {
+            number(field)?
+                .try_into()
+                .ok()
+                .filter(|&value| usize::from(value) <= RAM_SIZE)
+                .ok_or_else(|| format!("`{field}` is outside the memory"))
+        };
+
+        match name {
+            "key_down" => Ok(Self::KeyDown(key()?)),
+            "key_up" => Ok(Self::KeyUp(key()?)),
+            "pause" => Ok(Self::Pause),
+            "resume" => Ok(Self::Resume),
+            "step" => Ok(Self::Step(json.get("n").map_or(Ok(1), |_| number("n"))?)),
+            "read_reg" => Ok(Self::ReadReg(
+                json.get("reg")
+                    .and_then(Json::as_str)
+                    .map(str::to_lowercase),
+            )),
+            "read_mem" => Ok(Self::ReadMem {
+                addr: address("addr")?,
+                len: address("len")?,
+            }),
+            "screenshot" => json
+                .get("path")
+                .and_then(Json::as_str)
+                .map(|path| Self::Screenshot(path.to_string()))
+                .ok_or_else(|| "`screenshot` needs a `path` string".to_string()),
+            "framebuffer" => Ok(Self::Framebuffer),
+            "quit" => Ok(Self::Quit),
+            _ => Err(format!("Unknown command `{name}`")),
+        }
+    }
+
+    ///
+    /// Carries the command out on the emulator, returning the fields of the answer, if any, as a
+    /// piece of a JSON object.
+    ///
+    pub fn apply(&self, chip8: &mut Chip8) -> Result<String, String> {
+        match self {
+            Self::KeyDown(key) => chip8.set_key(*key, true),
+            Self::KeyUp(key) => chip8.set_key(*key, false),
+            Self::Pause => chip8.set_paused(true),
+            Self::Resume => chip8.set_paused(false),
+            Self::Step(n) => {
+                chip8.run_cycles(*n).map_err(|err| err.to_string())?;
+                return Ok(format!("\"pc\":{}", chip8.pc()));
+            }
+            Self::ReadReg(name) => return read_register(chip8, name.as_deref()),
+            Self::ReadMem { addr, len } => {
+                let ram = chip8.cpu().ram();
+                let bytes = (*addr..addr.saturating_add(*len))
+                    .map(|address| {
+                        ram.read_byte(usize::from(address))
+                            .map(|byte| byte.to_string())
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| err.to_string())?;
+                return Ok(format!("\"bytes\":[{}]", bytes.join(",")));
+            }
+            Self::Screenshot(path) => {
+                chip8
+                    .screenshot(path, 8)
+                    .map_err(|err| format!("Failed to save the screenshot: {err}"))?;
+            }
+            Self::Framebuffer => {
+                let width = chip8.framebuffer_rows().next().map_or(0, <[u8]>::len);
+                let rows: Vec<_> = chip8
+                    .framebuffer_rows()
+                    .map(|row| format!("\"{}\"", base64(&pack_row(row))))
+                    .collect();
+                return Ok(format!("\"width\":{width},\"rows\":[{}]", rows.join(",")));
+            }
+            Self::Quit => {}
+        }
+        Ok(String::new())
+    }
+}
+
+fn read_register(chip8: &Chip8, name: Option<&str>) -> Result<String, String> {
+    let registers = chip8.registers();
+    let Some(name) = name else {
+        let v: Vec<_> = registers.v.iter().map(u8::to_string).collect();
+        return Ok(format!(
+            "\"pc\":{},\"i\":{},\"dt\":{},\"st\":{},\"v\":[{}]",
+            registers.pc,
+            registers.i,
+            registers.dt,
+            registers.st,
+            v.join(",")
+        ));
+    };
+
+    let value = Register::parse(name)
+        .ok_or_else(|| format!("Unknown register `{name}`"))?
+        .get(registers);
+    Ok(format!("\"value\":{value}"))
+}
+
+///
+/// Packs a row of pixels into bytes, the leftmost pixel in the highest bit.
+///
+fn pack_row(row: &[u8]) -> Vec<u8> {
+    row.chunks(8)
+        .map(|pixels| {
+            pixels.iter().enumerate().fold(0, |byte, (bit, &pixel)| {
+                byte | u8::from(pixel != 0) << (7 - bit)
+            })
+        })
+        .collect()
+}
+
+///
+/// Encodes bytes as standard, padded base64.
+///
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (n, &byte)| {
+            group | u32::from(byte) << (16 - 8 * n)
+        });
+        for n in 0..4 {
+            if n <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * n) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+///
+/// Formats the answer to a command as a line of JSON.
+///
+fn response(result: Result<String, String>) -> String {
+    match result {
+        Ok(fields) if fields.is_empty() => "{\"ok\":true}".to_string(),
+        Ok(fields) => format!("{{\"ok\":true,{fields}}}"),
+        Err(err) => {
+            let mut response = "{\"ok\":false,\"error\":\"".to_string();
+            escape_json(&err, &mut response);
+            response.push_str("\"}");
+            response
+        }
+    }
+}
+
+///
+/// The `Request` struct represents a command waiting for the emulation thread, with where to send
+/// its answer.
+///
+pub struct Request {
+    pub command: Command,
+    reply: Sender<String>,
+}
+
+impl Request {
+    ///
+    /// Carries the command out and sends the answer back to the client.
+    ///
+    pub fn answer(self, chip8: &mut Chip8) {
+        let result = self.command.apply(chip8);
+        // The client may have hung up in the meantime
+        let _ = self.reply.send(response(result));
+    }
+}
+
+///
+/// The `ControlServer` struct represents a TCP server taking line-delimited JSON commands, each
+/// connection served by a thread of its own queueing them to the emulation thread.
+///
+pub struct ControlServer {
+    address: SocketAddr,
+    requests: Receiver<Request>,
+}
+
+impl ControlServer {
+    ///
+    /// Starts listening on `address`, port 0 picking any free one.
+    ///
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || serve(stream, &sender));
+            }
+        });
+        Ok(Self { address, requests })
+    }
+
+    pub const fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    ///
+    /// Returns the next queued command, if any, without waiting for one.
+    ///
+    pub fn try_recv(&self) -> Option<Request> {
+        self.requests.try_recv().ok()
+    }
+}
+
+///
+/// Answers the commands of a client until it disconnects, or the emulator stops taking them.
+///
+fn serve(stream: TcpStream, requests: &Sender<Request>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let answer = match Command::parse(&line) {
+            Ok(command) => {
+                let (reply, answer) = mpsc::channel();
+                if requests.send(Request { command, reply }).is_err() {
+                    return;
+                }
+                match answer.recv() {
+                    Ok(answer) => answer,
+                    Err(_) => return,
+                }
+            }
+            Err(err) => response(Err(err)),
+        };
+        if writeln!(writer, "{answer}").is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod remote_tests {
+    use super::{base64, pack_row, response, Command};
+    use crate::emu::chip8::Chip8;
+    use crate::emu::json::Json;
+    use crate::emu::quirks::Variant;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            Command::parse(r#"{"cmd": "key_down", "key": 10}"#),
+            Ok(Command::KeyDown(10))
+        );
+        assert_eq!(Command::parse(r#"{"cmd":"step"}"#), Ok(Command::Step(1)));
+        assert_eq!(
+            Command::parse(r#"{"cmd":"step","n":60}"#),
+            Ok(Command::Step(60))
+        );
+        assert_eq!(
+            Command::parse(r#"{"cmd":"read_reg","reg":"VA"}"#),
+            Ok(Command::ReadReg(Some("va".to_string())))
+        );
+        assert_eq!(
+            Command::parse(r#"{"cmd":"read_mem","addr":768,"len":3}"#),
+            Ok(Command::ReadMem { addr: 768, len: 3 })
+        );
+        assert_eq!(Command::parse(r#"{"cmd":"quit"}"#), Ok(Command::Quit));
+
+        assert_eq!(
+            Command::parse(r#"{"cmd":"key_up","key":16}"#),
+            Err("Invalid key 16, expected 0 to 15".to_string())
+        );
+        assert_eq!(
+            Command::parse(r#"{"cmd":"read_mem","addr":768}"#),
+            Err("`read_mem` needs a `len` number".to_string())
+        );
+        assert_eq!(
+            Command::parse(r#"{"cmd":"jump"}"#),
+            Err("Unknown command `jump`".to_string())
+        );
+        assert!(Command::parse("step 5")
+            .unwrap_err()
+            .starts_with("Invalid JSON"));
+    }
+
+    #[test]
+    fn applies_commands() {
+        // LD V0, K; LD I, 0x300; LD [I], V0; JMP 0x206
+        let mut chip8 = Chip8::headless();
+        chip8
+            .load_rom_bytes(&[0xF0, 0x0A, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06])
+            .unwrap();
+
+        assert_eq!(Command::KeyDown(7).apply(&mut chip8), Ok(String::new()));
+        assert_eq!(
+            Command::Step(3).apply(&mut chip8),
+            Ok("\"pc\":518".to_string())
+        );
+        assert_eq!(
+            Command::ReadReg(Some("v0".to_string())).apply(&mut chip8),
+            Ok("\"value\":7".to_string())
+        );
+        assert_eq!(
+            Command::ReadMem {
+                addr: 0x300,
+                len: 2
+            }
+            .apply(&mut chip8),
+            Ok("\"bytes\":[7,0]".to_string())
+        );
+        assert!(Command::ReadReg(None)
+            .apply(&mut chip8)
+            .unwrap()
+            .starts_with("\"pc\":518,\"i\":769,\"dt\":0,\"st\":0,\"v\":[7,0,"));
+        assert_eq!(
+            response(Command::ReadReg(Some("x".to_string())).apply(&mut chip8)),
+            "{\"ok\":false,\"error\":\"Unknown register `x`\"}"
+        );
+        assert_eq!(
+            response(Command::ReadMem { addr: 4095, len: 2 }.apply(&mut chip8)),
+            "{\"ok\":false,\"error\":\"Out of bound memory at 0x1000!\"}"
+        );
+    }
+
+    #[test]
+    fn sends_the_rows_of_the_screen_shown() {
+        let framebuffer = |chip8: &mut Chip8| {
+            let response = Command::Framebuffer.apply(chip8).unwrap();
+            let json = Json::parse(&format!("{{{response}}}")).unwrap();
+            let rows = json.get("rows").and_then(Json::as_array).unwrap().len();
+            (json.get("width").and_then(Json::as_number).unwrap(), rows)
+        };
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        assert_eq!(framebuffer(&mut chip8), (64, 32));
+
+        // MEGAON; JMP 0x202
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .variant(Variant::MegaChip)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&[0x00, 0x11, 0x12, 0x02]).unwrap();
+        chip8.run_cycles(1).unwrap();
+        assert_eq!(framebuffer(&mut chip8), (256, 192));
+    }
+
+    #[test]
+    fn encodes_framebuffer_rows() {
+        let mut row = [0; 64];
+        row[0] = 1;
+        row[9] = 1;
+        assert_eq!(pack_row(&row), [0x80, 0x40, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(base64(&pack_row(&row)), "gEAAAAAAAAA=");
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+    }
+}
@@ -0,0 +1,256 @@
+use std::error::Error;
+use std::fmt;
+
+use super::chip8::{Chip8, Chip8Error};
+use super::json::Json;
+use super::quirks::Variant;
+
+///
+/// The `KeyEvent` struct represents a hex key of a replay going down or up as its frame starts.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u32,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+///
+/// The `Replay` struct represents a recorded run: the ROM it was played on, the settings that make
+/// it play the same every time and the key presses, by frame.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Replay {
+    /// The path of the ROM, relative to the replay file
+    pub rom: Option<String>,
+    /// The SHA-1 of the ROM, in lowercase hex
+    pub sha1: Option<String>,
+    pub seed: u64,
+    pub ips: Option<u32>,
+    pub variant: Option<Variant>,
+    /// How many frames the run lasts, the replay's length
+    pub frames: u32,
+    /// The key presses and releases, in the order of their frames
+    pub events: Vec<KeyEvent>,
+}
+
+///
+/// The `ReplayError` struct represents why a replay file can't be played.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayError(pub String);
+
+impl Error for ReplayError {}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The replay is invalid: {}!", self.0)
+    }
+}
+
+impl Replay {
+    ///
+    /// Parses a replay file, a JSON object with the `frames` the run lasts and its key `events`,
+    /// and optionally the `rom` and its `sha1`, the `seed`, the `ips` and the `variant`.
+    ///
+    /// ```
+    /// use chip8_emu::emu::replay::Replay;
+    ///
+    /// let replay = Replay::parse(
+    ///     "{\"frames\":120,\"events\":[{\"frame\":10,\"key\":5,\"pressed\":true},\
+    ///      {\"frame\":20,\"key\":5,\"pressed\":false}]}",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(replay.frames, 120);
+    /// assert_eq!(replay.events.len(), 2);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file isn't JSON, a field is missing or of the wrong type, or an event is out
+    /// of order or past the last frame.
+    ///
+    pub fn parse(text: &str) -> Result<Self, ReplayError> {
+        let error = |reason: &str| ReplayError(reason.to_string());
+        let json = Json::parse(text).map_err(error)?;
+        json.as_object()
+            .ok_or_else(|| error("it isn't an object"))?;
+        let number = |json: &Json, name: &str| {
+            json.get(name)
+                .map(|value| {
+                    value
+                        .as_number()
+                        .ok_or_else(|| ReplayError(format!("`{name}` isn't a number")))
+                })
+                .transpose()
+        };
+        let string = |name: &str| {
+            json.get(name)
+                .map(|value| {
+                    value
+                        .as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| ReplayError(format!("`{name}` isn't a string")))
+                })
+                .transpose()
+        };
+
+        let frames = number(&json, "frames")?.ok_or_else(|| error("there's no `frames`"))?;
+        let frames = u32::try_from(frames).map_err(|_| error("`frames` is too large"))?;
+        let ips = number(&json, "ips")?
+            .map(|ips| u32::try_from(ips).map_err(|_| error("`ips` is too large")))
+            .transpose()?;
+        let variant = string("variant")?
+            .map(|name| {
+                name.parse()
+                    .map_err(|_| error("`variant` isn't chip8, schip, hires, megachip or chip8x"))
+            })
+            .transpose()?;
+        let mut replay = Self {
+            rom: string("rom")?,
+            sha1: string("sha1")?.map(|sha1| sha1.to_ascii_lowercase()),
+            seed: number(&json, "seed")?.unwrap_or_default(),
+            ips,
+            variant,
+            frames,
+            events: Vec::new(),
+        };
+
+        let events = json.get("events").map_or(Ok(&[][..]), |events| {
+            events
+                .as_array()
+                .ok_or_else(|| error("`events` isn't an array"))
+        })?;
+        for (n, event) in events.iter().enumerate() {
+            let event_error = |reason: &str| ReplayError(format!("event {}: {reason}", n + 1));
+            let frame = number(event, "frame")?.ok_or_else(|| event_error("there's no `frame`"))?;
+            let frame = u32::try_from(frame)
+                .ok()
+                .filter(|&frame| frame < replay.frames)
+                .ok_or_else(|| event_error("the frame is past the last one"))?;
+            if replay.events.last().is_some_and(|last| last.frame > frame) {
+                return Err(event_error("the frame is before the previous event's"));
+            }
+            let key = number(event, "key")?
+                .and_then(|key| u8::try_from(key).ok())
+                .filter(|&key| key < 16)
+                .ok_or_else(|| event_error("`key` isn't a hex key from 0 to 15"))?;
+            let pressed = match event.get("pressed") {
+                Some(Json::Bool(pressed)) => *pressed,
+                _ => return Err(event_error("`pressed` isn't true or false")),
+            };
+            replay.events.push(KeyEvent {
+                frame,
+                key,
+                pressed,
+            });
+        }
+        Ok(replay)
+    }
+
+    ///
+    /// Plays the replay on the emulator, which has the ROM loaded: the frames run as fast as
+    /// possible, every one ended for the frontend to show it, the keys going down and up as their
+    /// frames start. `on_frame` is told every frame played, from 1.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the emulator crashes.
+    ///
+    pub fn play(&self, chip8: &mut Chip8, mut on_frame: impl FnMut(u32)) -> Result<(), Chip8Error> {
+        let mut events = self.events.iter().peekable();
+        for frame in 0..self.frames {
+            while let Some(event) = events.next_if(|event| event.frame == frame) {
+                chip8.set_key(event.key, event.pressed);
+            }
+            chip8.run_frame()?;
+            chip8.end_frame();
+            on_frame(frame + 1);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::{KeyEvent, Replay};
+    use crate::emu::chip8::Chip8;
+    use crate::emu::quirks::Variant;
+
+    #[test]
+    fn parses_the_settings_and_the_events() {
+        let replay = Replay::parse(
+            r#"{"rom": "PONG", "sha1": "ABC123", "seed": 7, "ips": 700, "variant": "schip",
+                "frames": 3, "events": [{"frame": 0, "key": 10, "pressed": true},
+                {"frame": 2, "key": 10, "pressed": false}]}"#,
+        )
+        .unwrap();
+        assert_eq!(replay.rom.as_deref(), Some("PONG"));
+        assert_eq!(replay.sha1.as_deref(), Some("abc123"));
+        assert_eq!((replay.seed, replay.ips), (7, Some(700)));
+        assert_eq!(replay.variant, Some(Variant::SChip));
+        assert_eq!(
+            replay.events[1],
+            KeyEvent {
+                frame: 2,
+                key: 10,
+                pressed: false
+            }
+        );
+
+        // The settings left out are the defaults
+        let replay = Replay::parse(r#"{"frames": 0}"#).unwrap();
+        assert_eq!(replay, Replay::default());
+    }
+
+    #[test]
+    fn rejects_events_it_cant_play() {
+        let error = |text: &str| Replay::parse(text).unwrap_err().to_string();
+        assert_eq!(error("[]"), "The replay is invalid: it isn't an object!");
+        assert_eq!(error("{}"), "The replay is invalid: there's no `frames`!");
+        assert_eq!(
+            error(r#"{"frames": 2, "events": [{"frame": 2, "key": 1, "pressed": true}]}"#),
+            "The replay is invalid: event 1: the frame is past the last one!"
+        );
+        assert_eq!(
+            error(
+                r#"{"frames": 9, "events": [{"frame": 5, "key": 1, "pressed": true},
+                {"frame": 4, "key": 1, "pressed": false}]}"#
+            ),
+            "The replay is invalid: event 2: the frame is before the previous event's!"
+        );
+        assert_eq!(
+            error(r#"{"frames": 2, "events": [{"frame": 0, "key": 16, "pressed": true}]}"#),
+            "The replay is invalid: event 1: `key` isn't a hex key from 0 to 15!"
+        );
+        assert_eq!(
+            error(r#"{"frames": 2, "variant": "xochip"}"#),
+            "The replay is invalid: `variant` isn't chip8, schip, hires, megachip or chip8x!"
+        );
+    }
+
+    #[test]
+    fn presses_the_keys_on_their_frames() {
+        // LD V0, K; JP 0x202
+        let rom = [0xF0, 0x0A, 0x12, 0x02];
+        let replay =
+            Replay::parse(r#"{"frames": 3, "events": [{"frame": 2, "key": 5, "pressed": true}]}"#)
+                .unwrap();
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&rom).unwrap();
+        let mut played = Vec::new();
+        replay.play(&mut chip8, |frame| played.push(frame)).unwrap();
+        assert_eq!(played, [1, 2, 3]);
+        assert_eq!(chip8.cpu().registers().v[0], 5);
+
+        // Still waiting for the key on the frames before
+        let early = Replay {
+            frames: 2,
+            events: Vec::new(),
+            ..replay
+        };
+        chip8.load_rom_bytes(&rom).unwrap();
+        early.play(&mut chip8, |_| {}).unwrap();
+        assert_eq!(chip8.cpu().registers().v[0], 0);
+    }
+}
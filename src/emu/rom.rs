@@ -0,0 +1,451 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "native")]
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use zip::ZipArchive;
+
+#[cfg(feature = "native")]
+use super::cpu::Chip8Error;
+use super::ips::IpsError;
+use super::memory::{RAM_SIZE, RESERVED_SIZE};
+
+///
+/// The `ZIP_MAGIC` constant is the signature every zip archive starts with.
+///
+#[cfg(feature = "native")]
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+///
+/// The `MAX_ROM_SIZE` constant is the largest ROM any load address has room for, the memory above
+/// the font.
+///
+const MAX_ROM_SIZE: usize = RAM_SIZE - RESERVED_SIZE;
+
+///
+/// The `ROM_EXTENSIONS` constant lists the file extensions recognized as CHIP-8 ROMs in archives.
+///
+const ROM_EXTENSIONS: [&str; 3] = ["ch8", "c8", "rom"];
+
+///
+/// The `HEX_EXTENSIONS` constant lists the file extensions of ROMs stored as hex text.
+///
+#[cfg(feature = "native")]
+const HEX_EXTENSIONS: [&str; 2] = ["hex", "txt"];
+
+///
+/// The `DEMO_ROM` constant is the public-domain IBM logo ROM, embedded so the emulator works out of
+/// the box.
+///
+pub const DEMO_ROM: &[u8] = include_bytes!("../../assets/IBM Logo");
+
+///
+/// The `RomError` enum represents the possible errors that can occur when reading a ROM file.
+///
+#[derive(Debug)]
+pub enum RomError {
+    FailedToReadArchive,
+    NoRomInArchive,
+    EntryNotFound(String),
+    AmbiguousArchive(Vec<String>),
+    EntryTooLarge {
+        name: String,
+        size: u64,
+    },
+    MalformedHex {
+        line: usize,
+        column: usize,
+        token: String,
+    },
+    InvalidPatch(IpsError),
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidPatch(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FailedToReadArchive => write!(f, "Failed to read the zip archive!"),
+            Self::NoRomInArchive => write!(f, "The zip archive doesn't contain any ROM!"),
+            Self::EntryNotFound(name) => write!(f, "The zip archive has no entry `{name}`!"),
+            Self::AmbiguousArchive(candidates) => write!(
+                f,
+                "The zip archive contains several ROMs ({}), pick one with --zip-entry!",
+                candidates.join(", ")
+            ),
+            Self::EntryTooLarge { name, size } => write!(
+                f,
+                "The zip entry `{name}` is too large to fit in RAM ({size} bytes)!"
+            ),
+            Self::MalformedHex {
+                line,
+                column,
+                token,
+            } => write!(
+                f,
+                "Malformed hex token `{token}` at line {line}, column {column}!"
+            ),
+            Self::InvalidPatch(err) => write!(f, "Failed to apply the IPS patch: {err}"),
+        }
+    }
+}
+
+///
+/// Reads the ROM at `path`, transparently extracting it when the file is a zip archive and
+/// parsing it when the file is hex text.
+///
+#[cfg(feature = "native")]
+pub fn read_rom(path: &str, zip_entry: Option<&str>) -> Result<Vec<u8>, Chip8Error> {
+    let bytes = fs::read(path)?;
+    let extension = Path::new(path).extension();
+
+    if extension.is_some_and(|ext| ext.eq_ignore_ascii_case("zip")) || bytes.starts_with(ZIP_MAGIC)
+    {
+        log::debug!("Extracting the ROM from the archive {path}");
+        Ok(extract_rom(&bytes, zip_entry)?)
+    } else if extension.is_some_and(|ext| {
+        HEX_EXTENSIONS
+            .iter()
+            .any(|hex| ext.eq_ignore_ascii_case(hex))
+    }) || looks_like_hex(&bytes)
+    {
+        log::debug!("Parsing {path} as a ROM written in hex");
+        Ok(parse_hex(&String::from_utf8_lossy(&bytes))?)
+    } else {
+        Ok(bytes)
+    }
+}
+
+///
+/// Parses a ROM written as hex text: pairs of hex digits separated by whitespace or commas,
+/// optionally prefixed with `0x`, with `#` and `;` starting comments until the end of the line.
+///
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, RomError> {
+    let mut rom = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        let code = line
+            .find(['#', ';'])
+            .map_or(line, |comment| &line[..comment]);
+
+        let mut offset = 0;
+        for token in code.split(|c: char| c.is_whitespace() || c == ',') {
+            let column = offset + 1;
+            offset += token.len() + 1;
+            if token.is_empty() {
+                continue;
+            }
+
+            let malformed = || RomError::MalformedHex {
+                line: line_index + 1,
+                column,
+                token: token.to_string(),
+            };
+
+            let digits = token
+                .strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+                .unwrap_or(token);
+            if digits.is_empty()
+                || digits.len() % 2 != 0
+                || !digits.bytes().all(|b| b.is_ascii_hexdigit())
+            {
+                return Err(malformed());
+            }
+
+            for pair in digits.as_bytes().chunks(2) {
+                let pair = std::str::from_utf8(pair).map_err(|_| malformed())?;
+                rom.push(u8::from_str_radix(pair, 16).map_err(|_| malformed())?);
+            }
+        }
+    }
+
+    Ok(rom)
+}
+
+///
+/// Guesses whether the file is hex text: only printable ASCII, and nothing but hex digits,
+/// separators and `0x` prefixes outside of comments.
+///
+#[cfg(feature = "native")]
+fn looks_like_hex(bytes: &[u8]) -> bool {
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return false;
+    };
+    if !text
+        .bytes()
+        .all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+    {
+        return false;
+    }
+
+    let mut has_digits = false;
+    for line in text.lines() {
+        let code = line
+            .find(['#', ';'])
+            .map_or(line, |comment| &line[..comment]);
+        for c in code.chars() {
+            match c {
+                c if c.is_ascii_hexdigit() => has_digits = true,
+                'x' | 'X' | ',' => {}
+                c if c.is_whitespace() => {}
+                _ => return false,
+            }
+        }
+    }
+    has_digits
+}
+
+///
+/// Extracts a ROM from the zip archive, either the entry named `zip_entry` or the single entry
+/// with a ROM extension.
+///
+pub fn extract_rom(archive: &[u8], zip_entry: Option<&str>) -> Result<Vec<u8>, RomError> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(archive)).map_err(|_| RomError::FailedToReadArchive)?;
+
+    let names: Vec<String> = archive
+        .file_names()
+        .map(|name| name.map(Cow::into_owned))
+        .collect::<Result<_, _>>()
+        .map_err(|_| RomError::FailedToReadArchive)?;
+
+    let name = if let Some(entry) = zip_entry {
+        names
+            .into_iter()
+            .find(|name| name == entry || file_name(name) == entry)
+            .ok_or_else(|| RomError::EntryNotFound(entry.to_string()))?
+    } else {
+        let mut candidates: Vec<String> =
+            names.into_iter().filter(|name| is_rom_name(name)).collect();
+        candidates.sort();
+
+        match candidates.len() {
+            0 => return Err(RomError::NoRomInArchive),
+            1 => candidates.remove(0),
+            _ => return Err(RomError::AmbiguousArchive(candidates)),
+        }
+    };
+
+    let file = archive
+        .by_name(&name)
+        .map_err(|_| RomError::FailedToReadArchive)?;
+
+    let too_large = |size| RomError::EntryTooLarge {
+        name: name.clone(),
+        size,
+    };
+    if file.size() > MAX_ROM_SIZE as u64 {
+        return Err(too_large(file.size()));
+    }
+
+    // The archive may understate the size, so never decompress more than one byte past the limit
+    let mut rom = Vec::new();
+    file.take(MAX_ROM_SIZE as u64 + 1)
+        .read_to_end(&mut rom)
+        .map_err(|_| RomError::FailedToReadArchive)?;
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(too_large(rom.len() as u64));
+    }
+    Ok(rom)
+}
+
+fn file_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+fn is_rom_name(name: &str) -> bool {
+    !name.ends_with('/')
+        && Path::new(name).extension().is_some_and(|ext| {
+            ROM_EXTENSIONS
+                .iter()
+                .any(|rom_ext| ext.eq_ignore_ascii_case(rom_ext))
+        })
+}
+
+#[cfg(test)]
+mod rom_tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    #[cfg(feature = "native")]
+    use super::looks_like_hex;
+    use super::{extract_rom, parse_hex, RomError};
+
+    fn zip_fixture(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn no_candidates() {
+        let archive = zip_fixture(&[("README.txt", b"hello")]);
+        assert!(matches!(
+            extract_rom(&archive, None),
+            Err(RomError::NoRomInArchive)
+        ));
+    }
+
+    #[test]
+    fn single_candidate() {
+        let archive = zip_fixture(&[("README.txt", b"hello"), ("games/PONG.ch8", &[0x00, 0xE0])]);
+        assert_eq!(extract_rom(&archive, None).unwrap(), vec![0x00, 0xE0]);
+    }
+
+    #[test]
+    fn many_candidates() {
+        let archive = zip_fixture(&[("TETRIS.c8", &[0x12, 0x00]), ("BRIX.ROM", &[0x00, 0xE0])]);
+        let Err(RomError::AmbiguousArchive(candidates)) = extract_rom(&archive, None) else {
+            panic!("expected an ambiguous archive");
+        };
+        assert_eq!(candidates, vec!["BRIX.ROM", "TETRIS.c8"]);
+    }
+
+    #[test]
+    fn many_candidates_with_entry() {
+        let archive = zip_fixture(&[
+            ("roms/TETRIS.c8", &[0x12, 0x00]),
+            ("BRIX.ROM", &[0x00, 0xE0]),
+        ]);
+        assert_eq!(
+            extract_rom(&archive, Some("TETRIS.c8")).unwrap(),
+            vec![0x12, 0x00]
+        );
+        assert_eq!(
+            extract_rom(&archive, Some("BRIX.ROM")).unwrap(),
+            vec![0x00, 0xE0]
+        );
+        assert!(matches!(
+            extract_rom(&archive, Some("PONG.ch8")),
+            Err(RomError::EntryNotFound(name)) if name == "PONG.ch8"
+        ));
+    }
+
+    #[test]
+    fn oversized_entry_is_rejected() {
+        let archive = zip_fixture(&[("BIG.ch8", &[0xAA; 8_192])]);
+        assert!(matches!(
+            extract_rom(&archive, None),
+            Err(RomError::EntryTooLarge { name, size: 8_192 }) if name == "BIG.ch8"
+        ));
+    }
+
+    #[test]
+    fn entries_too_large_for_0x200_are_extracted_whole() {
+        // Too large to load at 0x200, but it fits from 0x50 on
+        let archive = zip_fixture(&[("LOW.ch8", &[0xAA; 3_900])]);
+        assert_eq!(extract_rom(&archive, None).unwrap().len(), 3_900);
+    }
+
+    #[test]
+    fn not_an_archive() {
+        assert!(matches!(
+            extract_rom(b"PK\x03\x04garbage", None),
+            Err(RomError::FailedToReadArchive)
+        ));
+    }
+
+    #[test]
+    fn hex_pairs_and_words() {
+        assert_eq!(
+            parse_hex("00 E0 a22a\n600c").unwrap(),
+            vec![0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C]
+        );
+    }
+
+    #[test]
+    fn hex_commas_and_prefixes() {
+        assert_eq!(
+            parse_hex("0x00,0xE0, 0XA2 ,\t0x2A,").unwrap(),
+            vec![0x00, 0xE0, 0xA2, 0x2A]
+        );
+    }
+
+    #[test]
+    fn hex_comments() {
+        let text = "# IBM logo\n00E0 ; clear the screen\n\n  A22A # load I\n;12 34\n";
+        assert_eq!(parse_hex(text).unwrap(), vec![0x00, 0xE0, 0xA2, 0x2A]);
+    }
+
+    #[test]
+    fn hex_empty() {
+        assert!(parse_hex("; nothing here\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn hex_malformed_tokens() {
+        for (text, line, column, token) in [
+            ("00E0\nA2G4", 2, 1, "A2G4"),
+            ("00 E0  ABC", 1, 8, "ABC"),
+            ("00,0x", 1, 4, "0x"),
+            ("12 34 ; ok\n  56 zz", 2, 6, "zz"),
+        ] {
+            let Err(RomError::MalformedHex {
+                line: err_line,
+                column: err_column,
+                token: err_token,
+            }) = parse_hex(text)
+            else {
+                panic!("expected `{text}` to be malformed");
+            };
+            assert_eq!(
+                (err_line, err_column, err_token.as_str()),
+                (line, column, token)
+            );
+        }
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn hex_heuristic() {
+        assert!(looks_like_hex(b"00E0 A22A\n# comment with words\n600C"));
+        assert!(looks_like_hex(b"0x00, 0xE0"));
+        assert!(!looks_like_hex(&[0x00, 0xE0, 0xA2, 0x2A]));
+        assert!(!looks_like_hex(b"hello world"));
+        assert!(!looks_like_hex(b"   \n"));
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn read_rom_errors_keep_their_cause() {
+        use std::error::Error as _;
+        use std::fs;
+
+        use super::read_rom;
+        use crate::emu::cpu::Chip8Error;
+
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.ch8");
+        let err = read_rom(missing.to_str().unwrap(), None).unwrap_err();
+        assert!(matches!(err, Chip8Error::Io(_)));
+        assert!(err.source().is_some());
+
+        let hex = dir.path().join("bad.hex");
+        fs::write(&hex, "00E0 0xZZ").unwrap();
+        assert!(matches!(
+            read_rom(hex.to_str().unwrap(), None),
+            Err(Chip8Error::InvalidRom(RomError::MalformedHex {
+                column: 6,
+                ..
+            }))
+        ));
+    }
+}
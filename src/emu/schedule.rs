@@ -0,0 +1,455 @@
+use std::time::{Duration, Instant};
+use std::{hint, thread};
+
+use super::chip8::FRAMES_PER_SECOND;
+
+///
+/// The `DEFAULT_MAX_CATCH_UP_FRAMES` constant is how many frames behind real time the run loop
+/// catches up on by default, about 83 ms.
+///
+pub const DEFAULT_MAX_CATCH_UP_FRAMES: u32 = 5;
+
+///
+/// The `DEFAULT_SPIN_THRESHOLD` constant is how long before a frame is due the run loop stops
+/// sleeping and spins instead, more than Windows oversleeps by.
+///
+pub const DEFAULT_SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+///
+/// The `Tick` struct represents what an iteration of the run loop has to do to keep up with real
+/// time.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Tick {
+    /// The frames to emulate, more than one when catching up
+    pub frames: u32,
+    /// The frames too far behind to catch up on, whose emulated time is lost
+    pub dropped: u32,
+    /// Whether frames were dropped after keeping up until now
+    pub fell_behind: bool,
+}
+
+///
+/// The `Scheduler` struct represents the fixed 60 Hz timestep of the run loop, carrying the time
+/// the iterations don't add up to a whole frame over to the next ones.
+///
+/// When the host is slow, it emulates the frames the loop fell behind by, up to `max_catch_up` a
+/// time, and drops the rest instead of spiralling into frames that take ever longer.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scheduler {
+    frame: Duration,
+    max_catch_up: u32,
+    accumulator: Duration,
+    behind: bool,
+}
+
+impl Scheduler {
+    ///
+    /// Returns a scheduler catching up on at most `max_catch_up` frames on top of the one due,
+    /// with the first frame due right away.
+    ///
+    pub fn new(max_catch_up: u32) -> Self {
+        let frame = Duration::from_secs(1) / FRAMES_PER_SECOND;
+        Self {
+            frame,
+            max_catch_up,
+            accumulator: frame,
+            behind: false,
+        }
+    }
+
+    ///
+    /// Accounts for the `elapsed` time since the previous iteration and returns how many frames
+    /// to emulate now.
+    ///
+    pub fn advance(&mut self, elapsed: Duration) -> Tick {
+        self.accumulator += elapsed;
+        let due = self.accumulator.as_nanos() / self.frame.as_nanos();
+        let due = u32::try_from(due).unwrap_or(u32::MAX);
+        let frames = due.min(self.max_catch_up.saturating_add(1));
+        let dropped = due - frames;
+        if dropped == 0 {
+            self.accumulator -= self.frame * frames;
+        } else {
+            // The time of the dropped frames is lost, only the part of a frame left is kept
+            self.accumulator =
+                Duration::from_nanos((self.accumulator.as_nanos() % self.frame.as_nanos()) as u64);
+        }
+        let fell_behind = dropped > 0 && !self.behind;
+        // Keeping up again once a frame is run on time
+        if dropped > 0 {
+            self.behind = true;
+        } else if frames <= 1 {
+            self.behind = false;
+        }
+        Tick {
+            frames,
+            dropped,
+            fell_behind,
+        }
+    }
+
+    ///
+    /// Returns how long until the next frame is due, counting from the start of the iteration.
+    ///
+    pub fn until_next_frame(&self) -> Duration {
+        self.frame.saturating_sub(self.accumulator)
+    }
+
+    ///
+    /// Forgets the time carried over, for when the emulation was paused, with the next frame due
+    /// right away.
+    ///
+    pub fn reset(&mut self) {
+        self.accumulator = self.frame;
+        self.behind = false;
+    }
+}
+
+///
+/// The `PacingClock` trait represents the time the run loop waits on, the wall clock outside of
+/// tests.
+///
+pub trait PacingClock {
+    fn now(&self) -> Instant;
+
+    ///
+    /// Sleeps for at least `duration`, often more.
+    ///
+    fn sleep(&mut self, duration: Duration);
+
+    ///
+    /// Busy-waits for a moment.
+    ///
+    fn spin(&mut self);
+}
+
+///
+/// The `SystemClock` struct represents the wall clock, sleeping the thread.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl PacingClock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+
+    fn spin(&mut self) {
+        hint::spin_loop();
+    }
+}
+
+///
+/// The `Pacer` struct represents how the run loop waits for the next frame: sleeping until
+/// `spin_threshold` before it's due, then spinning on the clock for the rest.
+///
+/// Sleeps overshoot, by a millisecond or more on Windows, so it also asks for less by as much as
+/// the recent sleeps overslept.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pacer {
+    spin_threshold: Duration,
+    // A moving average of how much longer the sleeps took than requested
+    oversleep: Duration,
+}
+
+impl Pacer {
+    ///
+    /// Returns a pacer spinning for the last `spin_threshold` of every wait, 0 only sleeping.
+    ///
+    pub const fn new(spin_threshold: Duration) -> Self {
+        Self {
+            spin_threshold,
+            oversleep: Duration::ZERO,
+        }
+    }
+
+    ///
+    /// Returns how much longer than requested the recent sleeps took.
+    ///
+    pub const fn oversleep(&self) -> Duration {
+        self.oversleep
+    }
+
+    ///
+    /// Returns how long to sleep for when `remaining` is left until the deadline.
+    ///
+    pub fn sleep_request(&self, remaining: Duration) -> Duration {
+        remaining.saturating_sub(self.spin_threshold + self.oversleep)
+    }
+
+    ///
+    /// Waits on the clock until `deadline`, returning right away if it's already passed.
+    ///
+    pub fn wait_until(&mut self, clock: &mut impl PacingClock, deadline: Instant) {
+        let start = clock.now();
+        let request = self.sleep_request(deadline.saturating_duration_since(start));
+        if !request.is_zero() {
+            clock.sleep(request);
+            let overslept = clock
+                .now()
+                .saturating_duration_since(start)
+                .saturating_sub(request);
+            self.oversleep = (self.oversleep * 3 + overslept) / 4;
+        }
+        if self.spin_threshold.is_zero() {
+            // Without spinning, the oversleep left is waited out by sleeping again
+            let remaining = deadline.saturating_duration_since(clock.now());
+            if !remaining.is_zero() {
+                clock.sleep(remaining);
+            }
+            return;
+        }
+        while clock.now() < deadline {
+            clock.spin();
+        }
+    }
+}
+
+#[cfg(test)]
+mod schedule_tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Pacer, PacingClock, Scheduler, Tick};
+
+    const FRAME: Duration = Duration::from_nanos(16_666_666);
+
+    ///
+    /// Runs the scheduler over the given iteration times, returning the frames every iteration
+    /// emulates and the frames dropped overall.
+    ///
+    fn schedule(scheduler: &mut Scheduler, iterations: &[Duration]) -> (Vec<u32>, u32) {
+        let mut dropped = 0;
+        let frames = iterations
+            .iter()
+            .map(|&elapsed| {
+                let tick = scheduler.advance(elapsed);
+                dropped += tick.dropped;
+                tick.frames
+            })
+            .collect();
+        (frames, dropped)
+    }
+
+    #[test]
+    fn first_frame_is_due_right_away() {
+        let mut scheduler = Scheduler::new(5);
+        assert_eq!(
+            scheduler.advance(Duration::ZERO),
+            Tick {
+                frames: 1,
+                dropped: 0,
+                fell_behind: false
+            }
+        );
+        assert_eq!(scheduler.until_next_frame(), FRAME);
+    }
+
+    #[test]
+    fn steady_frames_run_one_at_a_time() {
+        let mut scheduler = Scheduler::new(5);
+        scheduler.advance(Duration::ZERO);
+        // Waking a little late every time, which the next wait makes up for
+        let mut frames = Vec::new();
+        for _ in 0..120 {
+            let elapsed = scheduler.until_next_frame() + Duration::from_micros(300);
+            frames.push(scheduler.advance(elapsed).frames);
+        }
+        assert!(frames.iter().all(|&frames| frames == 1));
+        assert_eq!(
+            scheduler.until_next_frame(),
+            Duration::from_nanos(16_366_666)
+        );
+    }
+
+    #[test]
+    fn leftover_time_carries_over() {
+        let mut scheduler = Scheduler::new(5);
+        scheduler.advance(Duration::ZERO);
+        // Iterations of half a frame run a frame every other one
+        let (frames, dropped) = schedule(&mut scheduler, &[FRAME / 2; 6]);
+        assert_eq!(frames, [0, 1, 0, 1, 0, 1]);
+        assert_eq!(dropped, 0);
+        // And iterations of one and a half frames three frames every two
+        let (frames, dropped) = schedule(&mut scheduler, &[FRAME * 3 / 2; 6]);
+        assert_eq!(frames, [1, 2, 1, 2, 1, 2]);
+        assert_eq!(dropped, 0);
+    }
+
+    #[test]
+    fn spikes_are_caught_up_on() {
+        let mut scheduler = Scheduler::new(5);
+        scheduler.advance(Duration::ZERO);
+        // A 50 ms hitch, e.g. while encoding a GIF, then back to normal
+        let (frames, dropped) = schedule(
+            &mut scheduler,
+            &[FRAME, FRAME * 3 + Duration::from_millis(1), FRAME, FRAME],
+        );
+        assert_eq!(frames, [1, 3, 1, 1]);
+        assert_eq!(dropped, 0);
+    }
+
+    #[test]
+    fn catching_up_is_capped() {
+        let mut scheduler = Scheduler::new(5);
+        scheduler.advance(Duration::ZERO);
+        let tick = scheduler.advance(FRAME * 10 + FRAME / 2);
+        assert_eq!(
+            tick,
+            Tick {
+                frames: 6,
+                dropped: 4,
+                fell_behind: true
+            }
+        );
+        // Only the part of a frame left is carried over
+        assert_eq!(scheduler.until_next_frame(), FRAME / 2);
+
+        let mut scheduler = Scheduler::new(0);
+        scheduler.advance(Duration::ZERO);
+        assert_eq!(scheduler.advance(FRAME * 3).frames, 1);
+    }
+
+    #[test]
+    fn persistently_slow_frames_keep_dropping() {
+        let mut scheduler = Scheduler::new(2);
+        scheduler.advance(Duration::ZERO);
+        // Every iteration takes 5 frames, of which only 3 are emulated
+        let ticks: Vec<Tick> = (0..4).map(|_| scheduler.advance(FRAME * 5)).collect();
+        assert!(ticks
+            .iter()
+            .all(|tick| tick.frames == 3 && tick.dropped == 2));
+        // The warning is only due when falling behind, not on every frame behind
+        let fell_behind: Vec<bool> = ticks.iter().map(|tick| tick.fell_behind).collect();
+        assert_eq!(fell_behind, [true, false, false, false]);
+
+        // Keeping up again, then falling behind anew
+        assert_eq!(scheduler.advance(FRAME).frames, 1);
+        assert!(scheduler.advance(FRAME * 5).fell_behind);
+    }
+
+    #[test]
+    fn reset_forgets_the_time_behind() {
+        let mut scheduler = Scheduler::new(5);
+        scheduler.advance(Duration::ZERO);
+        scheduler.advance(FRAME / 2);
+        scheduler.reset();
+        assert_eq!(scheduler.advance(Duration::ZERO).frames, 1);
+        assert_eq!(scheduler.until_next_frame(), FRAME);
+    }
+
+    ///
+    /// The `FakeClock` struct represents a clock whose sleeps overshoot by a fixed amount, and
+    /// whose every spin takes 100 µs.
+    ///
+    struct FakeClock {
+        start: Instant,
+        elapsed: Duration,
+        oversleep: Duration,
+        sleeps: Vec<Duration>,
+        spins: u32,
+    }
+
+    impl FakeClock {
+        fn new(oversleep: Duration) -> Self {
+            Self {
+                start: Instant::now(),
+                elapsed: Duration::ZERO,
+                oversleep,
+                sleeps: Vec::new(),
+                spins: 0,
+            }
+        }
+
+        fn at(&self, elapsed: Duration) -> Instant {
+            self.start + elapsed
+        }
+    }
+
+    impl PacingClock for FakeClock {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.sleeps.push(duration);
+            self.elapsed += duration + self.oversleep;
+        }
+
+        fn spin(&mut self) {
+            self.spins += 1;
+            self.elapsed += Duration::from_micros(100);
+        }
+    }
+
+    #[test]
+    fn sleeps_then_spins_up_to_the_deadline() {
+        let mut clock = FakeClock::new(Duration::ZERO);
+        let mut pacer = Pacer::new(Duration::from_millis(2));
+        let deadline = clock.at(FRAME);
+        pacer.wait_until(&mut clock, deadline);
+        assert_eq!(clock.sleeps, [Duration::from_nanos(14_666_666)]);
+        assert_eq!(clock.spins, 20);
+        assert!(clock.now() >= clock.at(FRAME));
+        assert!(clock.now() < clock.at(FRAME + Duration::from_micros(100)));
+    }
+
+    #[test]
+    fn oversleeping_shortens_the_next_sleeps() {
+        let mut clock = FakeClock::new(Duration::from_millis(1));
+        let mut pacer = Pacer::new(Duration::from_millis(2));
+        let mut late = Vec::new();
+        for frame in 1..=30 {
+            let deadline = clock.at(FRAME * frame);
+            pacer.wait_until(&mut clock, deadline);
+            late.push(clock.now() - deadline);
+        }
+        // The oversleep fits in the threshold, every frame ends on time
+        assert!(late.iter().all(|&late| late < Duration::from_micros(100)));
+        // And the average converges on it, leaving less to spin
+        assert!(pacer.oversleep() > Duration::from_micros(990));
+        assert_eq!(
+            pacer.sleep_request(FRAME) + Duration::from_millis(2) + pacer.oversleep(),
+            FRAME
+        );
+        assert!(clock.sleeps[29] < clock.sleeps[0]);
+    }
+
+    #[test]
+    fn passed_deadlines_return_right_away() {
+        let mut clock = FakeClock::new(Duration::from_millis(1));
+        let mut pacer = Pacer::new(Duration::from_millis(2));
+        clock.elapsed = FRAME * 2;
+        let deadline = clock.at(FRAME);
+        pacer.wait_until(&mut clock, deadline);
+        assert!(clock.sleeps.is_empty());
+        assert_eq!(clock.spins, 0);
+
+        // Less left than the threshold is only spun
+        let deadline = clock.at(FRAME * 2 + Duration::from_millis(1));
+        pacer.wait_until(&mut clock, deadline);
+        assert!(clock.sleeps.is_empty());
+        assert_eq!(clock.spins, 10);
+    }
+
+    #[test]
+    fn zero_threshold_only_sleeps() {
+        let mut clock = FakeClock::new(Duration::from_millis(3));
+        let mut pacer = Pacer::new(Duration::ZERO);
+        let deadline = clock.at(FRAME);
+        pacer.wait_until(&mut clock, deadline);
+        assert_eq!(clock.spins, 0);
+        assert_eq!(clock.sleeps, [FRAME]);
+        // The next sleep asks for less by the average oversleep
+        let deadline = clock.at(FRAME * 2);
+        pacer.wait_until(&mut clock, deadline);
+        assert_eq!(clock.spins, 0);
+        assert_eq!(clock.sleeps[1], Duration::from_nanos(12_916_666));
+    }
+}
@@ -0,0 +1,258 @@
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::io::{GRID_HEIGHT, GRID_WIDTH, HIRES_GRID_HEIGHT, MEGA_GRID_HEIGHT, MEGA_GRID_WIDTH};
+use super::mega::ColorPalette;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+///
+/// The `Palette` type represents the RGB colors of the pixels that are off and on, in that order.
+///
+pub type Palette = [[u8; 3]; 2];
+
+///
+/// The `WINDOW_PALETTE` constant is the green on black the window shows the screen in.
+///
+pub const WINDOW_PALETTE: Palette = [[0x00, 0x00, 0x00], [0x00, 0xFF, 0x00]];
+
+///
+/// Encodes the framebuffer (one byte, 0 or 1, per pixel) as a black and white PNG, every pixel
+/// scaled up to a `scale`×`scale` square.
+///
+pub fn encode_png(framebuffer: &[u8], scale: u32) -> Vec<u8> {
+    encode(framebuffer, GRID_WIDTH, scale, None, |pixel| {
+        if pixel == 0 {
+            0x00
+        } else {
+            0xFF
+        }
+    })
+}
+
+///
+/// Encodes the framebuffer as a PNG in the colors of `palette`, every pixel scaled up to a
+/// `scale`×`scale` square.
+///
+pub fn encode_palette_png(framebuffer: &[u8], scale: u32, palette: &Palette) -> Vec<u8> {
+    encode(framebuffer, GRID_WIDTH, scale, Some(palette), |pixel| {
+        u8::from(pixel != 0)
+    })
+}
+
+///
+/// Encodes the framebuffer of MegaChip's mega mode (one palette value per pixel, 256 to a row)
+/// as a PNG in the colors of `palette` over black, every pixel scaled up to a `scale`×`scale`
+/// square.
+///
+pub fn encode_mega_png(framebuffer: &[u8], scale: u32, palette: &ColorPalette) -> Vec<u8> {
+    let colors: Vec<_> = (0..=u8::MAX).map(|value| palette.rgb(value)).collect();
+    encode(
+        framebuffer,
+        MEGA_GRID_WIDTH,
+        scale,
+        Some(&colors),
+        |pixel| pixel,
+    )
+}
+
+///
+/// Encodes the framebuffer, `columns` pixels to a row, as an 8-bit PNG of the values `value`
+/// maps its pixels to: grayscale without a palette and indexed with one.
+///
+fn encode(
+    framebuffer: &[u8],
+    columns: usize,
+    scale: u32,
+    palette: Option<&[[u8; 3]]>,
+    value: impl Fn(u8) -> u8,
+) -> Vec<u8> {
+    let scale = scale.max(1) as usize;
+    // 32 rows, or the 64 of HiRes CHIP-8, or the 192 of MegaChip
+    let rows = if columns == MEGA_GRID_WIDTH {
+        MEGA_GRID_HEIGHT
+    } else {
+        framebuffer
+            .len()
+            .div_ceil(columns)
+            .clamp(GRID_HEIGHT, HIRES_GRID_HEIGHT)
+    };
+    let (width, height) = (columns * scale, rows * scale);
+
+    // 8-bit scanlines, each one starting with its filter type (none)
+    let mut scanlines = Vec::with_capacity((width + 1) * height);
+    for row in framebuffer.chunks(columns).take(rows) {
+        let mut scanline = vec![0];
+        for &pixel in row {
+            scanline.extend(std::iter::repeat_n(value(pixel), scale));
+        }
+        for _ in 0..scale {
+            scanlines.extend_from_slice(&scanline);
+        }
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    // Writing into a Vec can't fail
+    let _ = encoder.write_all(&scanlines);
+    let image_data = encoder.finish().unwrap_or_default();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth 8, grayscale or indexed, deflate, adaptive filtering, no interlacing
+    let color_type = if palette.is_some() { 3 } else { 0 };
+    header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, *b"IHDR", &header);
+    if let Some(palette) = palette {
+        write_chunk(&mut png, *b"PLTE", palette.as_flattened());
+    }
+    write_chunk(&mut png, *b"IDAT", &image_data);
+    write_chunk(&mut png, *b"IEND", &[]);
+    png
+}
+
+///
+/// Draws a framebuffer `width` pixels wide as text, a line per row with `#` for the pixels that
+/// are on and `.` for the ones that are off, for tests to compare and print.
+///
+pub fn ascii_art(framebuffer: &[u8], width: usize) -> String {
+    let width = width.max(1);
+    let mut art = String::with_capacity(framebuffer.len() + framebuffer.len() / width);
+    for row in framebuffer.chunks(width) {
+        art.extend(row.iter().map(|&pixel| if pixel == 0 { '.' } else { '#' }));
+        art.push('\n');
+    }
+    art
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(&kind);
+    png.extend_from_slice(data);
+    let crc = crc32fast::hash(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+#[cfg(test)]
+mod screenshot_tests {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    use super::{
+        ascii_art, encode_mega_png, encode_palette_png, encode_png, PNG_SIGNATURE, WINDOW_PALETTE,
+    };
+    use crate::emu::io::{GRID_HEIGHT, GRID_WIDTH, MEGA_GRID_HEIGHT, MEGA_GRID_WIDTH};
+    use crate::emu::mega::ColorPalette;
+
+    #[test]
+    fn scaled_black_and_white_png() {
+        let mut framebuffer = vec![0; GRID_WIDTH * GRID_HEIGHT];
+        framebuffer[1] = 1;
+        let png = encode_png(&framebuffer, 2);
+
+        assert_eq!(png[..8], PNG_SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..20], 128u32.to_be_bytes());
+        assert_eq!(png[20..24], 64u32.to_be_bytes());
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let mut scanlines = Vec::new();
+        ZlibDecoder::new(&png[41..41 + idat_len])
+            .read_to_end(&mut scanlines)
+            .unwrap();
+        assert_eq!(scanlines.len(), (128 + 1) * 64);
+        // Both scanlines of the first row have the second pixel lit, twice as wide
+        for scanline in scanlines.chunks(129).take(2) {
+            assert_eq!(scanline[..6], [0, 0x00, 0x00, 0xFF, 0xFF, 0x00]);
+        }
+        assert!(scanlines[2 * 129..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn palette_png() {
+        let mut framebuffer = vec![0; GRID_WIDTH * GRID_HEIGHT];
+        framebuffer[GRID_WIDTH * GRID_HEIGHT - 1] = 1;
+        let png = encode_palette_png(&framebuffer, 1, &WINDOW_PALETTE);
+
+        assert_eq!(png[16..20], 64u32.to_be_bytes());
+        assert_eq!(png[20..24], 32u32.to_be_bytes());
+        // Indexed colors
+        assert_eq!(png[25], 3);
+        assert_eq!(png[33..37], 6u32.to_be_bytes());
+        assert_eq!(&png[37..41], b"PLTE");
+        assert_eq!(png[41..47], [0x00, 0x00, 0x00, 0x00, 0xFF, 0x00]);
+
+        let idat = 47 + 4;
+        let idat_len = u32::from_be_bytes(png[idat..idat + 4].try_into().unwrap()) as usize;
+        assert_eq!(&png[idat + 4..idat + 8], b"IDAT");
+        let mut scanlines = Vec::new();
+        ZlibDecoder::new(&png[idat + 8..idat + 8 + idat_len])
+            .read_to_end(&mut scanlines)
+            .unwrap();
+        assert_eq!(scanlines.len(), (64 + 1) * 32);
+        assert_eq!(scanlines[scanlines.len() - 2..], [0, 1]);
+    }
+
+    #[test]
+    fn mega_png_in_the_loaded_colors() {
+        let mut framebuffer = vec![0; MEGA_GRID_WIDTH * MEGA_GRID_HEIGHT];
+        framebuffer[1] = 2;
+        let mut palette = ColorPalette::new();
+        palette.load(&[0xFF, 0x10, 0x20, 0x30, 0xFF, 0xAA, 0xBB, 0xCC]);
+        let png = encode_mega_png(&framebuffer, 1, &palette);
+
+        assert_eq!(png[16..20], 256u32.to_be_bytes());
+        assert_eq!(png[20..24], 192u32.to_be_bytes());
+        assert_eq!(png[25], 3);
+        // A color for every value, the blank 0 being black
+        assert_eq!(png[33..37], (256u32 * 3).to_be_bytes());
+        assert_eq!(&png[37..41], b"PLTE");
+        assert_eq!(
+            png[41..50],
+            [0x00, 0x00, 0x00, 0x10, 0x20, 0x30, 0xAA, 0xBB, 0xCC]
+        );
+
+        let idat = 41 + 256 * 3 + 4;
+        let idat_len = u32::from_be_bytes(png[idat..idat + 4].try_into().unwrap()) as usize;
+        let mut scanlines = Vec::new();
+        ZlibDecoder::new(&png[idat + 8..idat + 8 + idat_len])
+            .read_to_end(&mut scanlines)
+            .unwrap();
+        assert_eq!(scanlines.len(), (256 + 1) * 192);
+        assert_eq!(scanlines[..3], [0, 0, 2]);
+    }
+
+    #[test]
+    fn ascii_art_rows() {
+        let mut framebuffer = vec![0; GRID_WIDTH * GRID_HEIGHT];
+        framebuffer[0] = 1;
+        framebuffer[GRID_WIDTH + 2] = 1;
+        let art = ascii_art(&framebuffer, GRID_WIDTH);
+
+        let rows: Vec<_> = art.lines().collect();
+        assert_eq!(rows.len(), GRID_HEIGHT);
+        assert_eq!(rows[0], format!("#{}", ".".repeat(GRID_WIDTH - 1)));
+        assert_eq!(&rows[1][..4], "..#.");
+        assert!(rows[2..].iter().all(|row| !row.contains('#')));
+    }
+
+    #[test]
+    fn ascii_art_of_a_hires_framebuffer() {
+        let mut framebuffer = vec![0; 128 * 64];
+        framebuffer[128 * 64 - 1] = 1;
+        let art = ascii_art(&framebuffer, 128);
+
+        let rows: Vec<_> = art.lines().collect();
+        assert_eq!(rows.len(), 64);
+        assert!(rows.iter().all(|row| row.len() == 128));
+        assert!(rows[63].ends_with(".#"));
+    }
+}
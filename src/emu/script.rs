@@ -0,0 +1,896 @@
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::mem;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, INT};
+
+use super::chip8::Chip8;
+use super::memory::{Register, Registers};
+
+///
+/// The `DEFAULT_SCRIPT_BUDGET` value is how long a script may take for a frame before it's
+/// stopped, a quarter of a frame at 60 frames per second.
+///
+pub const DEFAULT_SCRIPT_BUDGET: Duration = Duration::from_millis(4);
+
+///
+/// The `ScriptError` enum represents why a script failed, which stops it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    UnknownRegister(String),
+    InvalidAddress(u16),
+    InvalidKey(u8),
+    /// The frame that took longer than the budget, and how long it had taken when it was stopped
+    OutOfTime(Duration),
+    /// Whatever else the script reports
+    Failed(String),
+}
+
+impl std::error::Error for ScriptError {}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownRegister(name) => write!(f, "Unknown register `{name}`!"),
+            Self::InvalidAddress(address) => {
+                write!(f, "Address 0x{address:04X} is outside the memory!")
+            }
+            Self::InvalidKey(key) => write!(f, "Invalid key {key}, expected 0 to 15!"),
+            Self::OutOfTime(took) => {
+                write!(f, "The script took {took:?} for a frame, over its budget!")
+            }
+            Self::Failed(reason) => write!(f, "{reason}!"),
+        }
+    }
+}
+
+///
+/// The `Script` trait represents game-specific tooling run by the emulator: auto-splitters,
+/// training modes, cheats. Every method does nothing by default.
+///
+pub trait Script {
+    ///
+    /// Called between frames, with the API to read and change the machine and draw on the HUD.
+    ///
+    fn on_frame(&mut self, _emu: &mut ScriptApi<'_>) -> Result<(), ScriptError> {
+        Ok(())
+    }
+
+    ///
+    /// Called before every instruction if `wants_instructions` says so, since it's hot.
+    ///
+    fn on_instruction(&mut self, _pc: u16, _opcode: u16) {}
+
+    fn wants_instructions(&self) -> bool {
+        false
+    }
+}
+
+///
+/// The `ScriptApi` struct represents what a script can do to the emulator during `on_frame`.
+///
+/// Past the frame's deadline every method fails with `OutOfTime`, so a script stuck in a loop
+/// calling the API is stopped there rather than hanging the emulator.
+///
+pub struct ScriptApi<'a> {
+    chip8: &'a mut Chip8,
+    hud: Vec<String>,
+    start: Instant,
+    /// When the frame's budget ends, none for no limit
+    deadline: Option<Instant>,
+}
+
+impl<'a> ScriptApi<'a> {
+    pub fn new(chip8: &'a mut Chip8) -> Self {
+        Self {
+            chip8,
+            hud: Vec::new(),
+            start: Instant::now(),
+            deadline: None,
+        }
+    }
+
+    ///
+    /// Gives the script `budget` from now on, the methods failing once it's spent.
+    ///
+    fn within(chip8: &'a mut Chip8, budget: Duration) -> Self {
+        let mut api = Self::new(chip8);
+        api.deadline = Some(api.start + budget);
+        api
+    }
+
+    ///
+    /// Fails with `OutOfTime` once the frame's deadline passed.
+    ///
+    fn check_time(&self) -> Result<(), ScriptError> {
+        match self.deadline {
+            Some(deadline) if Instant::now() > deadline => {
+                Err(ScriptError::OutOfTime(self.start.elapsed()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn read_mem(&self, address: u16) -> Result<u8, ScriptError> {
+        self.check_time()?;
+        self.chip8
+            .cpu()
+            .ram()
+            .read_byte(usize::from(address))
+            .map_err(|_| ScriptError::InvalidAddress(address))
+    }
+
+    pub fn write_mem(&mut self, address: u16, value: u8) -> Result<(), ScriptError> {
+        self.check_time()?;
+        self.chip8
+            .write_byte(address, value)
+            .map_err(|_| ScriptError::InvalidAddress(address))
+    }
+
+    ///
+    /// Returns a register by name: `v0`-`vf`, `i`, `pc`, `dt` or `st`.
+    ///
+    pub fn get_reg(&self, name: &str) -> Result<u16, ScriptError> {
+        self.check_time()?;
+        Ok(register(name)?.get(self.chip8.registers()))
+    }
+
+    ///
+    /// Sets a register by name, the 8-bit ones to the low byte of `value`.
+    ///
+    pub fn set_reg(&mut self, name: &str, value: u16) -> Result<(), ScriptError> {
+        self.check_time()?;
+        let register = register(name)?;
+        self.set_register(register, value);
+        Ok(())
+    }
+
+    fn set_register(&mut self, register: Register, value: u16) {
+        let [_, low] = value.to_be_bytes();
+        match register {
+            Register::V(x) => self.chip8.set_v(x, low),
+            Register::I => self.chip8.set_i(value),
+            Register::Pc => self.chip8.set_pc(value),
+            Register::Dt => self.chip8.set_delay_timer(low),
+            Register::St => self.chip8.set_sound_timer(low),
+        }
+    }
+
+    pub fn press_key(&mut self, key: u8, pressed: bool) -> Result<(), ScriptError> {
+        self.check_time()?;
+        if key >= 16 {
+            return Err(ScriptError::InvalidKey(key));
+        }
+        self.chip8.set_key(key, pressed);
+        Ok(())
+    }
+
+    ///
+    /// Adds a line to the HUD shown over the screen until the next frame.
+    ///
+    pub fn draw_text(&mut self, text: impl Into<String>) -> Result<(), ScriptError> {
+        self.check_time()?;
+        self.hud.push(text.into());
+        Ok(())
+    }
+
+    pub fn hud(&self) -> &[String] {
+        &self.hud
+    }
+}
+
+fn register(name: &str) -> Result<Register, ScriptError> {
+    Register::parse(name).ok_or_else(|| ScriptError::UnknownRegister(name.to_string()))
+}
+
+///
+/// The `ScriptHost` struct represents a script attached to the emulator, stopped for good by its
+/// first error or the first frame it takes longer than its budget for.
+///
+/// A script over its budget is stopped by the next call it makes to the API, which fails. Scripts
+/// run on the emulator's thread, so a Rust one looping without ever calling the API can't be
+/// interrupted, where Rhai stops a `RhaiScript` anywhere.
+///
+pub struct ScriptHost {
+    script: Box<dyn Script>,
+    budget: Duration,
+    stopped: Option<ScriptError>,
+}
+
+impl ScriptHost {
+    pub fn new(script: impl Script + 'static) -> Self {
+        Self {
+            script: Box::new(script),
+            budget: DEFAULT_SCRIPT_BUDGET,
+            stopped: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn budget(mut self, budget: Duration) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    ///
+    /// Returns why the script was stopped, if it was.
+    ///
+    pub const fn stopped(&self) -> Option<&ScriptError> {
+        self.stopped.as_ref()
+    }
+
+    pub fn wants_instructions(&self) -> bool {
+        self.stopped.is_none() && self.script.wants_instructions()
+    }
+
+    pub fn on_instruction(&mut self, pc: u16, opcode: u16) {
+        self.script.on_instruction(pc, opcode);
+    }
+
+    ///
+    /// Runs the script's `on_frame`, returning the lines it drew on the HUD, or the error that
+    /// stopped it.
+    ///
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<Vec<String>, ScriptError> {
+        if let Some(err) = &self.stopped {
+            return Err(err.clone());
+        }
+
+        let mut api = ScriptApi::within(chip8, self.budget);
+        let result = self.script.on_frame(&mut api);
+        let took = api.start.elapsed();
+        let result = result.and_then(|()| {
+            if took > self.budget {
+                Err(ScriptError::OutOfTime(took))
+            } else {
+                Ok(api.hud)
+            }
+        });
+        if let Err(err) = &result {
+            self.stopped = Some(err.clone());
+        }
+        result
+    }
+}
+
+///
+/// How many operations a Rhai script runs between two looks at the clock.
+///
+const OPERATIONS_PER_CHECK: u64 = 256;
+
+///
+/// The `Change` enum represents a change a Rhai script made to the machine, made to the real one
+/// once its callback returns.
+///
+#[derive(Debug, Clone, Copy)]
+enum Change {
+    Mem(u16, u8),
+    Reg(Register, u16),
+    Key(u8, bool),
+}
+
+///
+/// The `Frame` struct represents the machine as a Rhai script sees it during a callback: copies of
+/// the RAM and the registers with its own changes on top, the changes themselves and its HUD.
+///
+struct Frame {
+    ram: Vec<u8>,
+    registers: Registers,
+    changes: Vec<Change>,
+    hud: Vec<String>,
+    // The error of the API the callback failed with, reported as it is rather than as Rhai's
+    error: Option<ScriptError>,
+    start: Instant,
+    deadline: Option<Instant>,
+}
+
+impl Frame {
+    fn new() -> Self {
+        Self {
+            ram: Vec::new(),
+            registers: Registers::new(),
+            changes: Vec::new(),
+            hud: Vec::new(),
+            error: None,
+            start: Instant::now(),
+            deadline: None,
+        }
+    }
+
+    fn check_time(&self) -> Result<(), ScriptError> {
+        match self.deadline {
+            Some(deadline) if Instant::now() > deadline => {
+                Err(ScriptError::OutOfTime(self.start.elapsed()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+///
+/// The `Emu` struct represents the `emu` the `on_frame` of a Rhai script is passed, with the
+/// methods of `ScriptApi`.
+///
+#[derive(Clone)]
+struct Emu(Rc<RefCell<Frame>>);
+
+impl Emu {
+    ///
+    /// Runs `call` on the frame after checking the time, keeping the error it fails with for the
+    /// callback to report.
+    ///
+    fn with<T>(
+        &self,
+        call: impl FnOnce(&mut Frame) -> Result<T, ScriptError>,
+    ) -> Result<T, Box<EvalAltResult>> {
+        let mut frame = self.0.borrow_mut();
+        frame
+            .check_time()
+            .and_then(|()| call(&mut frame))
+            .map_err(|err| {
+                let message = err.to_string();
+                frame.error = Some(err);
+                message.into()
+            })
+    }
+
+    fn read_mem(&mut self, address: INT) -> Result<INT, Box<EvalAltResult>> {
+        self.with(|frame| {
+            let address = ram_address(address, frame)?;
+            Ok(frame.ram[address].into())
+        })
+    }
+
+    fn write_mem(&mut self, address: INT, value: INT) -> Result<(), Box<EvalAltResult>> {
+        self.with(|frame| {
+            let address = ram_address(address, frame)?;
+            let value = u8::try_from(value)
+                .map_err(|_| ScriptError::Failed(format!("{value} doesn't fit in a byte")))?;
+            frame.ram[address] = value;
+            frame.changes.push(Change::Mem(address as u16, value));
+            Ok(())
+        })
+    }
+
+    fn get_reg(&mut self, name: &str) -> Result<INT, Box<EvalAltResult>> {
+        self.with(|frame| Ok(register(name)?.get(&frame.registers).into()))
+    }
+
+    fn set_reg(&mut self, name: &str, value: INT) -> Result<(), Box<EvalAltResult>> {
+        self.with(|frame| {
+            let register = register(name)?;
+            let value = u16::try_from(value)
+                .map_err(|_| ScriptError::Failed(format!("{value} doesn't fit in a register")))?;
+            let [_, low] = value.to_be_bytes();
+            let registers = &mut frame.registers;
+            match register {
+                Register::V(x) => registers.v[usize::from(x)] = low,
+                Register::I => registers.i = value,
+                Register::Pc => registers.pc = value,
+                Register::Dt => registers.dt = low,
+                Register::St => registers.st = low,
+            }
+            frame.changes.push(Change::Reg(register, value));
+            Ok(())
+        })
+    }
+
+    fn press_key(&mut self, key: INT, pressed: bool) -> Result<(), Box<EvalAltResult>> {
+        self.with(|frame| {
+            let key = u8::try_from(key)
+                .ok()
+                .filter(|&key| key < 16)
+                .ok_or_else(|| {
+                    ScriptError::Failed(format!("Invalid key {key}, expected 0 to 15"))
+                })?;
+            frame.changes.push(Change::Key(key, pressed));
+            Ok(())
+        })
+    }
+
+    fn draw_text(&mut self, text: &str) -> Result<(), Box<EvalAltResult>> {
+        self.with(|frame| {
+            frame.hud.push(text.to_string());
+            Ok(())
+        })
+    }
+}
+
+fn ram_address(address: INT, frame: &Frame) -> Result<usize, ScriptError> {
+    usize::try_from(address)
+        .ok()
+        .filter(|&address| address < frame.ram.len())
+        .ok_or_else(|| {
+            u16::try_from(address).map_or_else(
+                |_| ScriptError::Failed(format!("Address {address} is outside the memory")),
+                ScriptError::InvalidAddress,
+            )
+        })
+}
+
+///
+/// The `RhaiScript` struct represents a script written in Rhai, whose `on_frame(emu)` and, if it
+/// has one, `on_instruction(pc, opcode)` are called as a `Script`'s.
+///
+/// `emu` has the methods of `ScriptApi`, `this` is an object map kept from call to call for the
+/// script's own state, and `print` logs. The script's statements outside of functions run once,
+/// as it's loaded. Past the frame's budget the script is stopped, even in a loop calling nothing.
+///
+pub struct RhaiScript {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    this: Dynamic,
+    frame: Rc<RefCell<Frame>>,
+    on_frame: bool,
+    on_instruction: bool,
+}
+
+impl RhaiScript {
+    ///
+    /// Compiles the script and runs its statements outside of functions, for at most
+    /// `DEFAULT_SCRIPT_BUDGET`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the syntax error of the script, or the error its statements fail with.
+    ///
+    pub fn new(source: &str) -> Result<Self, ScriptError> {
+        let frame = Rc::new(RefCell::new(Frame::new()));
+        let mut engine = Engine::new();
+        engine
+            .register_type_with_name::<Emu>("Emu")
+            .register_fn("read_mem", Emu::read_mem)
+            .register_fn("write_mem", Emu::write_mem)
+            .register_fn("get_reg", Emu::get_reg)
+            .register_fn("set_reg", Emu::set_reg)
+            .register_fn("press_key", Emu::press_key)
+            .register_fn("draw_text", Emu::draw_text)
+            .on_print(|text| log::info!("{text}"));
+        let clock = Rc::clone(&frame);
+        engine.on_progress(move |operations| {
+            let timed_out =
+                operations % OPERATIONS_PER_CHECK == 0 && clock.borrow().check_time().is_err();
+            timed_out.then_some(Dynamic::UNIT)
+        });
+        let ast = engine
+            .compile(source)
+            .map_err(|err| ScriptError::Failed(err.to_string()))?;
+        let has_function = |name: &str, params: usize| {
+            ast.iter_functions()
+                .any(|function| function.name == name && function.params.len() == params)
+        };
+        let mut script = Self {
+            on_frame: has_function("on_frame", 1),
+            on_instruction: has_function("on_instruction", 2),
+            engine,
+            ast,
+            scope: Scope::new(),
+            this: Map::new().into(),
+            frame,
+        };
+        script.start_call();
+        let result = script
+            .engine
+            .run_ast_with_scope(&mut script.scope, &script.ast);
+        script.finish_call(result)?;
+        Ok(script)
+    }
+
+    ///
+    /// Reads the script from a file and compiles it as `new` does.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file can't be read, or as `new` does.
+    ///
+    pub fn load(path: &str) -> Result<Self, ScriptError> {
+        let source = fs::read_to_string(path).map_err(|err| {
+            ScriptError::Failed(format!("Failed to read the script {path}: {err}"))
+        })?;
+        Self::new(&source)
+    }
+
+    ///
+    /// Gives the next call the script's budget from now on.
+    ///
+    fn start_call(&self) {
+        let mut frame = self.frame.borrow_mut();
+        frame.start = Instant::now();
+        frame.deadline = Some(frame.start + DEFAULT_SCRIPT_BUDGET);
+    }
+
+    ///
+    /// Turns how a call ended into the script's error: the API's own, an `OutOfTime` when the
+    /// budget stopped it, or Rhai's.
+    ///
+    fn finish_call<T>(&self, result: Result<T, Box<EvalAltResult>>) -> Result<T, ScriptError> {
+        let mut frame = self.frame.borrow_mut();
+        result.map_err(|err| match (frame.error.take(), *err) {
+            (Some(err), _) => err,
+            (None, EvalAltResult::ErrorTerminated(..)) => {
+                ScriptError::OutOfTime(frame.start.elapsed())
+            }
+            (None, err) => ScriptError::Failed(err.to_string()),
+        })
+    }
+
+    fn call(&mut self, name: &str, args: impl rhai::FuncArgs) -> Result<(), Box<EvalAltResult>> {
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.this);
+        self.engine
+            .call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, name, args)
+            .map(|_| ())
+    }
+}
+
+impl Script for RhaiScript {
+    ///
+    /// Calls the script's `on_frame` on copies of the RAM and the registers, then makes its
+    /// changes to the machine. A failing call changes nothing.
+    ///
+    fn on_frame(&mut self, emu: &mut ScriptApi<'_>) -> Result<(), ScriptError> {
+        // The error of an `on_instruction`, which can't report it
+        if let Some(err) = self.frame.borrow_mut().error.take() {
+            return Err(err);
+        }
+        if !self.on_frame {
+            return Ok(());
+        }
+        {
+            let mut frame = self.frame.borrow_mut();
+            frame.ram = emu.chip8.cpu().ram().bytes().to_vec();
+            frame.registers = *emu.chip8.registers();
+            frame.changes.clear();
+            frame.hud.clear();
+            frame.start = emu.start;
+            frame.deadline = emu.deadline;
+        }
+        let result = self.call("on_frame", (Emu(Rc::clone(&self.frame)),));
+        self.finish_call(result)?;
+
+        let (changes, hud) = {
+            let mut frame = self.frame.borrow_mut();
+            (mem::take(&mut frame.changes), mem::take(&mut frame.hud))
+        };
+        for change in changes {
+            emu.check_time()?;
+            match change {
+                Change::Mem(address, value) => emu.write_mem(address, value)?,
+                Change::Reg(register, value) => emu.set_register(register, value),
+                Change::Key(key, pressed) => emu.press_key(key, pressed)?,
+            }
+        }
+        for line in hud {
+            emu.draw_text(line)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Calls the script's `on_instruction`. Its error is kept for the next `on_frame` to stop the
+    /// script with, instructions going uncalled until then.
+    ///
+    fn on_instruction(&mut self, pc: u16, opcode: u16) {
+        if self.frame.borrow().error.is_some() {
+            return;
+        }
+        self.start_call();
+        let result = self.call("on_instruction", (INT::from(pc), INT::from(opcode)));
+        if let Err(err) = self.finish_call(result) {
+            self.frame.borrow_mut().error = Some(err);
+        }
+    }
+
+    fn wants_instructions(&self) -> bool {
+        self.on_instruction
+    }
+}
+
+#[cfg(test)]
+mod script_tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{RhaiScript, Script, ScriptApi, ScriptError, ScriptHost};
+    use crate::emu::chip8::Chip8;
+
+    ///
+    /// Runs `on_frame` as the closure, counting the instructions it's told about.
+    ///
+    struct ClosureScript<F> {
+        on_frame: F,
+        instructions: Rc<RefCell<Vec<u16>>>,
+    }
+
+    impl<F: FnMut(&mut ScriptApi<'_>) -> Result<(), ScriptError>> Script for ClosureScript<F> {
+        fn on_frame(&mut self, emu: &mut ScriptApi<'_>) -> Result<(), ScriptError> {
+            (self.on_frame)(emu)
+        }
+
+        fn on_instruction(&mut self, pc: u16, _opcode: u16) {
+            self.instructions.borrow_mut().push(pc);
+        }
+
+        fn wants_instructions(&self) -> bool {
+            true
+        }
+    }
+
+    fn host(
+        on_frame: impl FnMut(&mut ScriptApi<'_>) -> Result<(), ScriptError> + 'static,
+    ) -> (ScriptHost, Rc<RefCell<Vec<u16>>>) {
+        let instructions = Rc::default();
+        let script = ClosureScript {
+            on_frame,
+            instructions: Rc::clone(&instructions),
+        };
+        (ScriptHost::new(script), instructions)
+    }
+
+    #[test]
+    fn api_reads_and_changes_the_machine() {
+        let mut chip8 = Chip8::headless();
+        // SKP V0
+        chip8.load_rom_bytes(&[0xE0, 0x9E]).unwrap();
+        let mut api = ScriptApi::new(&mut chip8);
+        api.write_mem(0x300, 0x42).unwrap();
+        assert_eq!(api.read_mem(0x300), Ok(0x42));
+        assert_eq!(
+            api.read_mem(0x1000),
+            Err(ScriptError::InvalidAddress(0x1000))
+        );
+
+        api.set_reg("vA", 0x1FF).unwrap();
+        api.set_reg("i", 0x345).unwrap();
+        api.set_reg("dt", 9).unwrap();
+        assert_eq!(api.get_reg("va"), Ok(0xFF));
+        assert_eq!(api.get_reg("I"), Ok(0x345));
+        assert_eq!(api.get_reg("pc"), Ok(0x200));
+        assert_eq!(api.get_reg("dt"), Ok(9));
+        assert_eq!(
+            api.get_reg("vg"),
+            Err(ScriptError::UnknownRegister("vg".to_string()))
+        );
+
+        api.set_reg("v0", 0x7).unwrap();
+        api.press_key(0x7, true).unwrap();
+        assert_eq!(api.press_key(16, true), Err(ScriptError::InvalidKey(16)));
+        api.draw_text("HUD").unwrap();
+        assert_eq!(api.hud(), ["HUD"]);
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc(), 0x204);
+    }
+
+    #[test]
+    fn frames_return_the_hud() {
+        let mut chip8 = Chip8::headless();
+        let (mut host, _) = host(|emu| {
+            let lives = emu.read_mem(0x300)?;
+            emu.draw_text(format!("Lives {lives}"))
+        });
+        chip8.write_byte(0x300, 3).unwrap();
+        assert_eq!(host.run_frame(&mut chip8), Ok(vec!["Lives 3".to_string()]));
+    }
+
+    #[test]
+    fn errors_stop_the_script() {
+        let mut chip8 = Chip8::headless();
+        let (mut host, _) = host(|emu| emu.set_reg("sp", 0));
+        let err = ScriptError::UnknownRegister("sp".to_string());
+        assert_eq!(host.run_frame(&mut chip8), Err(err.clone()));
+        assert_eq!(host.stopped(), Some(&err));
+        assert!(!host.wants_instructions());
+    }
+
+    #[test]
+    fn slow_frames_stop_the_script() {
+        let mut chip8 = Chip8::headless();
+        let (host, _) = host(|_| {
+            thread::sleep(Duration::from_millis(5));
+            Ok(())
+        });
+        let mut host = host.budget(Duration::from_millis(1));
+        assert!(matches!(
+            host.run_frame(&mut chip8),
+            Err(ScriptError::OutOfTime(_))
+        ));
+        assert!(host.stopped().is_some());
+    }
+
+    #[test]
+    fn scripts_looping_on_the_api_are_stopped() {
+        let mut chip8 = Chip8::headless();
+        let calls = Rc::new(RefCell::new(0_u64));
+        let counted = Rc::clone(&calls);
+        let (host, _) = host(move |emu| loop {
+            emu.read_mem(0x300)?;
+            *counted.borrow_mut() += 1;
+        });
+        let mut host = host.budget(Duration::from_millis(1));
+        let Err(ScriptError::OutOfTime(took)) = host.run_frame(&mut chip8) else {
+            panic!("the script wasn't stopped");
+        };
+        assert!(took >= Duration::from_millis(1));
+        assert!(*calls.borrow() > 0);
+
+        // Without a deadline the API never runs out of time
+        let api = ScriptApi::new(&mut chip8);
+        thread::sleep(Duration::from_millis(2));
+        assert!(api.read_mem(0x300).is_ok());
+    }
+
+    ///
+    /// Runs a frame of the Rhai script on the machine, stopping it as a host does.
+    ///
+    fn rhai_frame(chip8: &mut Chip8, source: &str) -> Result<Vec<String>, ScriptError> {
+        ScriptHost::new(RhaiScript::new(source)?).run_frame(chip8)
+    }
+
+    #[test]
+    fn rhai_scripts_read_and_change_the_machine() {
+        let mut chip8 = Chip8::headless();
+        // SKP V0
+        chip8.load_rom_bytes(&[0xE0, 0x9E]).unwrap();
+        chip8.write_byte(0x300, 3).unwrap();
+        let script = r#"
+            fn on_frame(emu) {
+                let lives = emu.read_mem(0x300);
+                emu.write_mem(0x300, lives + 1);
+                emu.set_reg("vA", 0x1FF);
+                emu.set_reg("i", 0x345);
+                emu.set_reg("v0", 7);
+                emu.press_key(7, true);
+                // Reads see what the script changed
+                emu.draw_text(`Lives ${emu.read_mem(0x300)}`);
+                emu.draw_text(`${emu.get_reg("VA")} ${emu.get_reg("I")} ${emu.get_reg("pc")}`);
+            }
+        "#;
+        let hud = rhai_frame(&mut chip8, script).unwrap();
+        assert_eq!(hud, ["Lives 4", "255 837 512"]);
+        assert_eq!(chip8.cpu().ram().read_byte(0x300), Ok(4));
+        assert_eq!(chip8.registers().v[0xA], 0xFF);
+        assert_eq!(chip8.registers().i, 0x345);
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc(), 0x204);
+    }
+
+    #[test]
+    fn rhai_scripts_keep_their_state_in_this() {
+        let mut chip8 = Chip8::headless();
+        let script = r"
+            let ignored = 1;
+            fn on_frame(emu) {
+                this.frames = (this.frames ?? 0) + 1;
+                emu.draw_text(`Frame ${this.frames}`);
+            }
+        ";
+        let mut host = ScriptHost::new(RhaiScript::new(script).unwrap());
+        host.run_frame(&mut chip8).unwrap();
+        assert_eq!(host.run_frame(&mut chip8), Ok(vec!["Frame 2".to_string()]));
+    }
+
+    #[test]
+    fn rhai_errors_stop_the_script() {
+        let mut chip8 = Chip8::headless();
+        let failing = [
+            ("emu.read_mem(0x1000)", ScriptError::InvalidAddress(0x1000)),
+            (
+                "emu.write_mem(-1, 0)",
+                ScriptError::Failed("Address -1 is outside the memory".to_string()),
+            ),
+            (
+                "emu.write_mem(0x300, 256)",
+                ScriptError::Failed("256 doesn't fit in a byte".to_string()),
+            ),
+            (
+                "emu.set_reg(\"sp\", 0)",
+                ScriptError::UnknownRegister("sp".to_string()),
+            ),
+            (
+                "emu.press_key(16, true)",
+                ScriptError::Failed("Invalid key 16, expected 0 to 15".to_string()),
+            ),
+        ];
+        for (call, err) in failing {
+            let script = format!("fn on_frame(emu) {{ emu.draw_text(\"HUD\"); {call}; }}");
+            assert_eq!(rhai_frame(&mut chip8, &script), Err(err), "{call}");
+        }
+        // Nothing a failing frame did is kept
+        let script = "fn on_frame(emu) { emu.write_mem(0x300, 9); emu.get_reg(\"vg\"); }";
+        assert!(rhai_frame(&mut chip8, script).is_err());
+        assert_eq!(chip8.cpu().ram().read_byte(0x300), Ok(0));
+
+        let Err(ScriptError::Failed(reason)) = rhai_frame(&mut chip8, "fn on_frame(emu) { x }")
+        else {
+            panic!("the script didn't fail");
+        };
+        assert!(reason.contains("Variable not found: x"), "{reason}");
+        let Err(ScriptError::Failed(reason)) = RhaiScript::new("fn on_frame(emu) {") else {
+            panic!("the script compiled");
+        };
+        assert!(reason.contains("line 1"), "{reason}");
+    }
+
+    #[test]
+    fn rhai_loops_are_stopped_by_the_budget() {
+        let mut chip8 = Chip8::headless();
+        let script = RhaiScript::new("fn on_frame(emu) { let n = 0; loop { n += 1; } }").unwrap();
+        let mut host = ScriptHost::new(script).budget(Duration::from_millis(1));
+        let Err(ScriptError::OutOfTime(took)) = host.run_frame(&mut chip8) else {
+            panic!("the script wasn't stopped");
+        };
+        assert!(took >= Duration::from_millis(1));
+
+        // Statements outside of functions have a budget of their own
+        assert!(matches!(
+            RhaiScript::new("loop {}"),
+            Err(ScriptError::OutOfTime(_))
+        ));
+    }
+
+    #[test]
+    fn rhai_instructions_are_opt_in() {
+        let script = RhaiScript::new("fn on_frame(emu) {}").unwrap();
+        assert!(!script.wants_instructions());
+
+        let mut chip8 = Chip8::headless();
+        let script = r"
+            fn on_instruction(pc, opcode) {
+                this.last = `${pc.to_hex()} ${opcode.to_hex()}`;
+                this.count = (this.count ?? 0) + 1;
+            }
+            fn on_frame(emu) {
+                emu.draw_text(`${this.count} ${this.last}`);
+            }
+        ";
+        let mut host = ScriptHost::new(RhaiScript::new(script).unwrap());
+        assert!(host.wants_instructions());
+        for (pc, opcode) in [(0x200, 0x00E0), (0x202, 0x1200), (0x200, 0x00E0)] {
+            host.on_instruction(pc, opcode);
+        }
+        assert_eq!(host.run_frame(&mut chip8), Ok(vec!["3 200 e0".to_string()]));
+
+        // The error of an instruction stops the script on the next frame
+        let mut chip8 = Chip8::headless();
+        let script = "fn on_instruction(pc, opcode) { throw \"Bad opcode\"; }";
+        let mut host = ScriptHost::new(RhaiScript::new(script).unwrap());
+        host.on_instruction(0x200, 0x00E0);
+        assert!(matches!(
+            host.run_frame(&mut chip8),
+            Err(ScriptError::Failed(_))
+        ));
+    }
+
+    #[test]
+    fn the_example_hud_script_runs() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/hud.rhai");
+        let mut host = ScriptHost::new(RhaiScript::load(path).unwrap());
+        let mut chip8 = Chip8::headless();
+        chip8.write_byte(0x1F0, 0x2A).unwrap();
+        let hud = host.run_frame(&mut chip8).unwrap();
+        assert_eq!(hud, ["[1F0] = 2A (42)", "Frame 1"]);
+        assert!(matches!(
+            RhaiScript::load("missing.rhai"),
+            Err(ScriptError::Failed(_))
+        ));
+    }
+
+    #[test]
+    fn instructions_are_opt_in() {
+        let mut chip8 = Chip8::headless();
+        let (host, instructions) = host(|_| Ok(()));
+        chip8.set_script(host);
+        // CLS; JMP 0x200
+        chip8.load_rom_bytes(&[0x00, 0xE0, 0x12, 0x00]).unwrap();
+        chip8.run_cycles(3).unwrap();
+        assert_eq!(*instructions.borrow(), [0x200, 0x202, 0x200]);
+    }
+}
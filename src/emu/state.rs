@@ -0,0 +1,681 @@
+use std::error::Error;
+use std::fmt::{self, Write as _};
+
+use super::io::{GRID_HEIGHT, GRID_WIDTH, HIRES_GRID_HEIGHT};
+use super::json::Json;
+use super::memory::{Registers, RAM_SIZE, STACK_SIZE};
+use super::quirks::Quirks;
+
+///
+/// The `STATE_MAGIC` constant is the first bytes of every save state.
+///
+pub const STATE_MAGIC: [u8; 4] = *b"C8SV";
+
+///
+/// The `STATE_VERSION` constant is the version of the save state format, the only one read.
+///
+pub const STATE_VERSION: u8 = 1;
+
+///
+/// How many bytes the SHA-1 of the ROM takes in the header.
+///
+const HASH_SIZE: usize = 20;
+
+///
+/// How many bytes of RAM a row of the JSON holds.
+///
+const RAM_ROW: usize = 16;
+
+// The bits of the flags byte of the header
+const FLAG_HIRES: u8 = 1 << 0;
+const FLAG_SHIFT: u8 = 1 << 1;
+const FLAG_MEMORY: u8 = 1 << 2;
+const FLAG_JUMP: u8 = 1 << 3;
+const FLAG_ROM_HASH: u8 = 1 << 4;
+const FLAGS: u8 = FLAG_HIRES | FLAG_SHIFT | FLAG_MEMORY | FLAG_JUMP | FLAG_ROM_HASH;
+
+///
+/// The `Chip8State` struct represents a snapshot of the machine: the registers, the RAM, the
+/// screen and the quirks it follows, all it needs to go on from where it was.
+///
+/// The ROM isn't part of it, its code being in the RAM, only its hash is kept to tell the game.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chip8State {
+    /// The SHA-1 of the ROM the machine was running, in lowercase hex
+    pub rom_hash: Option<String>,
+    /// Whether the machine ran as HiRes CHIP-8, its screen 64×64 pixels instead of 64×32
+    pub hires: bool,
+    pub quirks: Quirks,
+    pub registers: Registers,
+    pub ram: Box<[u8; RAM_SIZE]>,
+    /// The pixels of the screen row after row, 1 for the ones that are on
+    pub screen: Vec<u8>,
+}
+
+///
+/// The `StateError` enum represents why a machine can't be saved or a save state can't be read.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The variant the machine runs as, whose extra state save states don't hold
+    Unsupported(&'static str),
+    NotAState,
+    Version(u64),
+    Size {
+        expected: usize,
+        actual: usize,
+    },
+    /// A field that doesn't have the shape it should
+    Invalid(String),
+    OutOfRange {
+        field: String,
+        value: u64,
+        max: u64,
+    },
+}
+
+impl Error for StateError {}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(variant) => write!(f, "Save states of {variant} aren't supported!"),
+            Self::NotAState => write!(f, "Not a save state, it doesn't start with `C8SV`!"),
+            Self::Version(version) => write!(
+                f,
+                "Unsupported save state version {version}, expected {STATE_VERSION}!"
+            ),
+            Self::Size { expected, actual } => {
+                write!(f, "The save state is {actual} bytes, expected {expected}!")
+            }
+            Self::Invalid(reason) => write!(f, "Invalid save state, {reason}!"),
+            Self::OutOfRange { field, value, max } => {
+                write!(
+                    f,
+                    "`{field}` 0x{value:X} is out of range, at most 0x{max:X}!"
+                )
+            }
+        }
+    }
+}
+
+impl Chip8State {
+    ///
+    /// Returns how many rows the screen has, more of them in HiRes CHIP-8.
+    ///
+    pub const fn screen_height(&self) -> usize {
+        screen_height(self.hires)
+    }
+
+    ///
+    /// Writes the save state in its binary format: a header of the magic, the version, the flags
+    /// and the ROM's SHA-1, then the registers, the RAM and the screen, a bit per pixel.
+    ///
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(state_size(self.hires));
+        bytes.extend_from_slice(&STATE_MAGIC);
+        bytes.push(STATE_VERSION);
+        let flags = [
+            (self.hires, FLAG_HIRES),
+            (self.quirks.shift, FLAG_SHIFT),
+            (self.quirks.memory, FLAG_MEMORY),
+            (self.quirks.jump, FLAG_JUMP),
+            (self.rom_hash.is_some(), FLAG_ROM_HASH),
+        ];
+        bytes.push(
+            flags
+                .into_iter()
+                .filter_map(|(set, flag)| set.then_some(flag))
+                .sum(),
+        );
+        let hash = self.rom_hash.as_deref().and_then(parse_hash);
+        bytes.extend_from_slice(&hash.unwrap_or_default());
+
+        let registers = &self.registers;
+        bytes.extend_from_slice(&registers.pc.to_be_bytes());
+        bytes.extend_from_slice(&registers.i.to_be_bytes());
+        bytes.extend_from_slice(&[registers.sp, registers.dt, registers.st]);
+        bytes.extend_from_slice(&registers.v);
+        for address in registers.stack {
+            bytes.extend_from_slice(&address.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.ram[..]);
+
+        let pixels = GRID_WIDTH * self.screen_height();
+        for eight in (0..pixels).step_by(8) {
+            let byte = (0..8)
+                .filter(|bit| {
+                    self.screen
+                        .get(eight + bit)
+                        .is_some_and(|&pixel| pixel != 0)
+                })
+                .fold(0, |byte, bit| byte | 0x80 >> bit);
+            bytes.push(byte);
+        }
+        bytes
+    }
+
+    ///
+    /// Reads a save state written by `to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the bytes aren't a save state of this version, have the wrong size, or hold a
+    /// register past what the machine has.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateError> {
+        let (header, rest) = bytes
+            .split_first_chunk::<6>()
+            .ok_or(StateError::NotAState)?;
+        if header[..4] != STATE_MAGIC {
+            return Err(StateError::NotAState);
+        }
+        if header[4] != STATE_VERSION {
+            return Err(StateError::Version(header[4].into()));
+        }
+        let flags = header[5];
+        if flags & !FLAGS != 0 {
+            return Err(StateError::Invalid(format!(
+                "the flags 0x{flags:02X} aren't all known"
+            )));
+        }
+        let hires = flags & FLAG_HIRES != 0;
+        if bytes.len() != state_size(hires) {
+            return Err(StateError::Size {
+                expected: state_size(hires),
+                actual: bytes.len(),
+            });
+        }
+
+        let mut reader = Reader(rest);
+        let hash = reader.take::<HASH_SIZE>();
+        let rom_hash = if flags & FLAG_ROM_HASH != 0 {
+            Some(hash.iter().fold(String::new(), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            }))
+        } else if hash != [0; HASH_SIZE] {
+            return Err(StateError::Invalid(
+                "there's a ROM hash but the flags say there's none".to_string(),
+            ));
+        } else {
+            None
+        };
+
+        let mut registers = Registers::new();
+        registers.pc = u16::from_be_bytes(reader.take());
+        registers.i = u16::from_be_bytes(reader.take());
+        [registers.sp, registers.dt, registers.st] = reader.take();
+        registers.v = reader.take();
+        for address in &mut registers.stack {
+            *address = u16::from_be_bytes(reader.take());
+        }
+        let ram = Box::new(reader.take::<RAM_SIZE>());
+        let screen = reader
+            .0
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |bit| u8::from(byte & 0x80 >> bit != 0)))
+            .collect();
+
+        let state = Self {
+            rom_hash,
+            hires,
+            quirks: Quirks {
+                shift: flags & FLAG_SHIFT != 0,
+                memory: flags & FLAG_MEMORY != 0,
+                jump: flags & FLAG_JUMP != 0,
+            },
+            registers,
+            ram,
+            screen,
+        };
+        state.validate()?;
+        Ok(state)
+    }
+
+    ///
+    /// Writes the save state as JSON to edit by hand: the registers as named hex strings, the RAM
+    /// as rows of 16 hex bytes after their address and the screen as ASCII art, a `#` for every
+    /// pixel on and a `.` for every pixel off.
+    ///
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let _ = writeln!(json, "{{\n  \"version\": {STATE_VERSION},");
+        let _ = match &self.rom_hash {
+            Some(hash) => writeln!(json, "  \"rom_sha1\": \"{hash}\","),
+            None => writeln!(json, "  \"rom_sha1\": null,"),
+        };
+        let _ = writeln!(json, "  \"hires\": {},", self.hires);
+        let _ = writeln!(
+            json,
+            "  \"quirks\": {{ \"shift\": {}, \"memory\": {}, \"jump\": {} }},",
+            self.quirks.shift, self.quirks.memory, self.quirks.jump
+        );
+
+        let registers = &self.registers;
+        let _ = write!(
+            json,
+            "  \"registers\": {{\n    \"pc\": \"0x{:04X}\", \"i\": \"0x{:04X}\", \"sp\": \"0x{:02X}\", \
+             \"dt\": \"0x{:02X}\", \"st\": \"0x{:02X}\",",
+            registers.pc, registers.i, registers.sp, registers.dt, registers.st
+        );
+        for (x, value) in registers.v.iter().enumerate() {
+            let separator = if x % 8 == 0 { "\n    " } else { " " };
+            let comma = if x == 15 { "" } else { "," };
+            let _ = write!(json, "{separator}\"v{x:x}\": \"0x{value:02X}\"{comma}");
+        }
+        json.push_str("\n  },\n  \"stack\": [");
+        for (n, address) in registers.stack.iter().enumerate() {
+            let separator = if n == 0 { "" } else { ", " };
+            let _ = write!(json, "{separator}\"0x{address:04X}\"");
+        }
+
+        json.push_str("],\n  \"ram\": [\n");
+        for (row, bytes) in self.ram.chunks(RAM_ROW).enumerate() {
+            let _ = write!(json, "    \"0x{:03X}:", row * RAM_ROW);
+            for byte in bytes {
+                let _ = write!(json, " {byte:02X}");
+            }
+            let comma = if (row + 1) * RAM_ROW == RAM_SIZE {
+                ""
+            } else {
+                ","
+            };
+            let _ = writeln!(json, "\"{comma}");
+        }
+
+        json.push_str("  ],\n  \"screen\": [\n");
+        let height = self.screen_height();
+        for y in 0..height {
+            json.push_str("    \"");
+            json.extend((0..GRID_WIDTH).map(|x| {
+                let pixel = self.screen.get(y * GRID_WIDTH + x).copied();
+                if pixel.unwrap_or_default() == 0 {
+                    '.'
+                } else {
+                    '#'
+                }
+            }));
+            let comma = if y + 1 == height { "" } else { "," };
+            let _ = writeln!(json, "\"{comma}");
+        }
+        json.push_str("  ]\n}\n");
+        json
+    }
+
+    ///
+    /// Reads a save state written by `to_json`, maybe edited since.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the text isn't JSON, a field is missing or of the wrong shape, or a register
+    /// holds more than it can.
+    ///
+    pub fn from_json(text: &str) -> Result<Self, StateError> {
+        let json = Json::parse(text).map_err(|reason| StateError::Invalid(reason.to_string()))?;
+        let version = field(&json, "version")?
+            .as_number()
+            .ok_or_else(|| invalid("`version` isn't a number"))?;
+        if version != u64::from(STATE_VERSION) {
+            return Err(StateError::Version(version));
+        }
+
+        let rom_hash = match field(&json, "rom_sha1")? {
+            Json::Null => None,
+            hash => {
+                let hash = hash
+                    .as_str()
+                    .filter(|hash| parse_hash(hash).is_some())
+                    .ok_or_else(|| invalid("`rom_sha1` isn't null or a SHA-1 in hex"))?;
+                Some(hash.to_ascii_lowercase())
+            }
+        };
+        let hires = boolean(&json, "hires", "hires")?;
+        let quirks = field(&json, "quirks")?;
+        let quirks = Quirks {
+            shift: boolean(quirks, "shift", "quirks.shift")?,
+            memory: boolean(quirks, "memory", "quirks.memory")?,
+            jump: boolean(quirks, "jump", "quirks.jump")?,
+        };
+
+        let json_registers = field(&json, "registers")?;
+        let register = |name: &str, max: u16| {
+            hex(
+                field(json_registers, name)?,
+                &format!("registers.{name}"),
+                max,
+            )
+        };
+        let mut registers = Registers::new();
+        registers.pc = register("pc", u16::MAX)?;
+        registers.i = register("i", u16::MAX)?;
+        // The registers below are a byte each, so they fit
+        registers.sp = register("sp", STACK_SIZE as u16)? as u8;
+        registers.dt = register("dt", u8::MAX.into())? as u8;
+        registers.st = register("st", u8::MAX.into())? as u8;
+        for (x, value) in registers.v.iter_mut().enumerate() {
+            *value = register(&format!("v{x:x}"), u8::MAX.into())? as u8;
+        }
+        let stack = array(&json, "stack", STACK_SIZE)?;
+        for (n, (address, json)) in registers.stack.iter_mut().zip(stack).enumerate() {
+            *address = hex(json, &format!("stack[{n}]"), u16::MAX)?;
+        }
+
+        let state = Self {
+            rom_hash,
+            hires,
+            quirks,
+            registers,
+            ram: ram_from_json(array(&json, "ram", RAM_SIZE / RAM_ROW)?)?,
+            screen: screen_from_json(array(&json, "screen", screen_height(hires))?)?,
+        };
+        state.validate()?;
+        Ok(state)
+    }
+
+    ///
+    /// Checks the registers the machine would fault on or go past its memory with.
+    ///
+    fn validate(&self) -> Result<(), StateError> {
+        let out_of_range = |field: &str, value: u16, max: usize| {
+            (usize::from(value) > max).then(|| StateError::OutOfRange {
+                field: field.to_string(),
+                value: value.into(),
+                max: max as u64,
+            })
+        };
+        let registers = &self.registers;
+        out_of_range("registers.pc", registers.pc, RAM_SIZE - 2)
+            .or_else(|| out_of_range("registers.sp", registers.sp.into(), STACK_SIZE))
+            .map_or(Ok(()), Err)
+    }
+}
+
+///
+/// Returns how many rows the screen of a save state has.
+///
+const fn screen_height(hires: bool) -> usize {
+    if hires {
+        HIRES_GRID_HEIGHT
+    } else {
+        GRID_HEIGHT
+    }
+}
+
+///
+/// Returns how many bytes a save state takes.
+///
+const fn state_size(hires: bool) -> usize {
+    let header = STATE_MAGIC.len() + 2 + HASH_SIZE;
+    let registers = 2 + 2 + 3 + 16 + 2 * STACK_SIZE;
+    header + registers + RAM_SIZE + GRID_WIDTH * screen_height(hires) / 8
+}
+
+///
+/// Reads the RAM from the rows of `to_json`, each an address and 16 bytes in hex.
+///
+fn ram_from_json(rows: &[Json]) -> Result<Box<[u8; RAM_SIZE]>, StateError> {
+    let mut ram = Box::new([0; RAM_SIZE]);
+    for ((n, row), bytes) in rows.iter().enumerate().zip(ram.chunks_mut(RAM_ROW)) {
+        let address = format!("0x{:03X}:", n * RAM_ROW);
+        let row_error = || {
+            invalid(&format!(
+                "`ram` row {n} should be `{address}` followed by {RAM_ROW} hex bytes"
+            ))
+        };
+        let mut words = row.as_str().ok_or_else(row_error)?.split_whitespace();
+        if !words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case(&address))
+        {
+            return Err(row_error());
+        }
+        for byte in bytes.iter_mut() {
+            *byte = words
+                .next()
+                .filter(|word| word.len() == 2)
+                .and_then(|word| u8::from_str_radix(word, 16).ok())
+                .ok_or_else(row_error)?;
+        }
+        if words.next().is_some() {
+            return Err(row_error());
+        }
+    }
+    Ok(ram)
+}
+
+///
+/// Reads the screen from the rows of `to_json`, `#` for the pixels on and `.` for the others.
+///
+fn screen_from_json(rows: &[Json]) -> Result<Vec<u8>, StateError> {
+    let mut screen = Vec::with_capacity(GRID_WIDTH * rows.len());
+    for (y, row) in rows.iter().enumerate() {
+        let row_error = || {
+            invalid(&format!(
+                "`screen` row {y} should be {GRID_WIDTH} of `#` and `.`"
+            ))
+        };
+        let row = row
+            .as_str()
+            .filter(|row| row.len() == GRID_WIDTH)
+            .ok_or_else(row_error)?;
+        for pixel in row.chars() {
+            screen.push(match pixel {
+                '#' => 1,
+                '.' => 0,
+                _ => return Err(row_error()),
+            });
+        }
+    }
+    Ok(screen)
+}
+
+fn parse_hash(hex: &str) -> Option<[u8; HASH_SIZE]> {
+    if hex.len() != 2 * HASH_SIZE || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = [0; HASH_SIZE];
+    for (byte, digits) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+fn invalid(reason: &str) -> StateError {
+    StateError::Invalid(reason.to_string())
+}
+
+fn field<'a>(json: &'a Json, name: &str) -> Result<&'a Json, StateError> {
+    json.get(name)
+        .ok_or_else(|| invalid(&format!("there's no `{name}`")))
+}
+
+fn boolean(json: &Json, name: &str, path: &str) -> Result<bool, StateError> {
+    match field(json, name)? {
+        Json::Bool(value) => Ok(*value),
+        _ => Err(invalid(&format!("`{path}` isn't true or false"))),
+    }
+}
+
+fn array<'a>(json: &'a Json, name: &str, len: usize) -> Result<&'a [Json], StateError> {
+    field(json, name)?
+        .as_array()
+        .filter(|values| values.len() == len)
+        .ok_or_else(|| invalid(&format!("`{name}` isn't an array of {len}")))
+}
+
+///
+/// Reads a hex string like `0x2A`, up to `max`.
+///
+fn hex(json: &Json, path: &str, max: u16) -> Result<u16, StateError> {
+    let value = json
+        .as_str()
+        .and_then(|text| text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")))
+        .and_then(|digits| u64::from_str_radix(digits, 16).ok())
+        .ok_or_else(|| invalid(&format!("`{path}` isn't a hex string like \"0x2A\"")))?;
+    u16::try_from(value)
+        .ok()
+        .filter(|&value| value <= max)
+        .ok_or_else(|| StateError::OutOfRange {
+            field: path.to_string(),
+            value,
+            max: max.into(),
+        })
+}
+
+///
+/// The `Reader` struct represents the bytes of a save state left to read, its size checked before.
+///
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        match self.0.split_first_chunk::<N>() {
+            Some((bytes, rest)) => {
+                self.0 = rest;
+                *bytes
+            }
+            None => [0; N],
+        }
+    }
+}
+
+#[cfg(test)]
+mod state_tests {
+    use super::{Chip8State, StateError, STATE_MAGIC};
+    use crate::emu::chip8::Chip8;
+    use crate::emu::quirks::Variant;
+
+    fn saved_demo() -> Chip8State {
+        let mut chip8 = Chip8::builder().headless(true).seed(0).build().unwrap();
+        chip8.load_demo().unwrap();
+        chip8.run_cycles(500).unwrap();
+        chip8.save_state().unwrap()
+    }
+
+    #[test]
+    fn binary_to_json_to_binary_is_byte_identical() {
+        let state = saved_demo();
+        let bytes = state.to_bytes();
+        assert_eq!(bytes[..4], STATE_MAGIC);
+        assert_eq!(Chip8State::from_bytes(&bytes).unwrap(), state);
+
+        let json = state.to_json();
+        let read = Chip8State::from_json(&json).unwrap();
+        assert_eq!(read, state);
+        assert_eq!(read.to_bytes(), bytes);
+        assert_eq!(read.to_json(), json);
+    }
+
+    #[test]
+    fn hires_states_have_a_taller_screen() {
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .variant(Variant::HiRes)
+            .build()
+            .unwrap();
+        // CLS, then LD I, 0x0; DRW V0, V0, 5 at the bottom half
+        chip8
+            .load_rom_bytes(&[0x00, 0xE0, 0xA0, 0x00, 0x60, 0x30, 0xD0, 0x05])
+            .unwrap();
+        chip8.run_cycles(4).unwrap();
+        let state = chip8.save_state().unwrap();
+        assert!(state.hires);
+        assert_eq!(state.screen.len(), 64 * 64);
+        assert_eq!(state.to_json().matches('#').count(), 14);
+        assert_eq!(
+            Chip8State::from_bytes(&state.to_bytes()).unwrap().screen,
+            state.screen
+        );
+    }
+
+    #[test]
+    fn loading_a_state_goes_on_from_it() {
+        let state = saved_demo();
+        let mut chip8 = Chip8::builder().headless(true).seed(0).build().unwrap();
+        chip8.load_demo().unwrap();
+        chip8.load_state(&state).unwrap();
+        assert_eq!(chip8.save_state().unwrap(), state);
+
+        let mut original = Chip8::builder().headless(true).seed(0).build().unwrap();
+        original.load_demo().unwrap();
+        original.run_cycles(500).unwrap();
+        original.run_cycles(300).unwrap();
+        chip8.run_cycles(300).unwrap();
+        assert_eq!(chip8.framebuffer(), original.framebuffer());
+        assert_eq!(chip8.registers(), original.registers());
+    }
+
+    #[test]
+    fn megachip_and_chip8x_cant_be_saved() {
+        let chip8 = Chip8::builder()
+            .headless(true)
+            .variant(Variant::Chip8X)
+            .build()
+            .unwrap();
+        assert_eq!(chip8.save_state(), Err(StateError::Unsupported("CHIP-8X")));
+    }
+
+    #[test]
+    fn rejects_invalid_binary_states() {
+        let bytes = saved_demo().to_bytes();
+        assert_eq!(Chip8State::from_bytes(b"PNG"), Err(StateError::NotAState));
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        assert_eq!(
+            Chip8State::from_bytes(&newer).unwrap_err().to_string(),
+            "Unsupported save state version 2, expected 1!"
+        );
+        assert_eq!(
+            Chip8State::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(StateError::Size {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            })
+        );
+        // SP, right after the header and PC and I
+        let mut overflowing = bytes;
+        overflowing[30] = 17;
+        assert_eq!(
+            Chip8State::from_bytes(&overflowing)
+                .unwrap_err()
+                .to_string(),
+            "`registers.sp` 0x11 is out of range, at most 0x10!"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_json_fields() {
+        let json = saved_demo().to_json();
+        let error = |json: &str| Chip8State::from_json(json).unwrap_err().to_string();
+        let v3 = json.find("\"v3\": \"").unwrap() + 7;
+        let edited = format!("{}0x100{}", &json[..v3], &json[v3 + 4..]);
+        assert_eq!(
+            error(&edited),
+            "`registers.v3` 0x100 is out of range, at most 0xFF!"
+        );
+        let pc = json.find("\"pc\": \"").unwrap() + 7;
+        let edited = format!("{}0x0FFF{}", &json[..pc], &json[pc + 6..]);
+        assert_eq!(
+            error(&edited),
+            "`registers.pc` 0xFFF is out of range, at most 0xFFE!"
+        );
+        assert_eq!(
+            error(&json.replacen("0x000: F0", "0x000: G0", 1)),
+            "Invalid save state, `ram` row 0 should be `0x000:` followed by 16 hex bytes!"
+        );
+        assert_eq!(
+            error(&json.replacen("....", "..o.", 1)),
+            "Invalid save state, `screen` row 0 should be 64 of `#` and `.`!"
+        );
+        assert_eq!(
+            error(&json.replacen("\"hires\": false", "\"hires\": 0", 1)),
+            "Invalid save state, `hires` isn't true or false!"
+        );
+        assert_eq!(
+            error("{\"version\": 1}"),
+            "Invalid save state, there's no `rom_sha1`!"
+        );
+    }
+}
@@ -0,0 +1,673 @@
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::io;
+use std::time::Duration;
+
+use super::chip8::FRAMES_PER_SECOND;
+use super::cpu::{Instruction, INSTRUCTION_COUNT};
+use super::memory::RAM_SIZE;
+
+///
+/// The `Stats` struct represents the counters collected while the emulator runs.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Stats {
+    pub cycles: u64,
+    pub run_time: Duration,
+    /// The frames of emulated time dropped because the host fell too far behind
+    pub dropped_frames: u64,
+    /// The frames of the run loop timed, and how far off 60 Hz they were in total
+    pub timed_frames: u64,
+    pub frame_deviation: Duration,
+}
+
+impl Stats {
+    ///
+    /// Returns the effective number of instructions executed per second of (unpaused) run time.
+    ///
+    #[allow(clippy::cast_precision_loss)]
+    pub fn instructions_per_second(&self) -> f64 {
+        let seconds = self.run_time.as_secs_f64();
+        if seconds > 0.0 {
+            self.cycles as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    ///
+    /// Counts a frame of the run loop that took `elapsed` instead of `target`.
+    ///
+    pub fn time_frame(&mut self, elapsed: Duration, target: Duration) {
+        self.timed_frames += 1;
+        self.frame_deviation += elapsed.abs_diff(target);
+    }
+
+    ///
+    /// Returns how far off the target the frames of the run loop were on average.
+    ///
+    pub fn frame_jitter(&self) -> Duration {
+        if self.timed_frames == 0 {
+            return Duration::ZERO;
+        }
+        let average = self.frame_deviation.as_nanos() / u128::from(self.timed_frames);
+        Duration::from_nanos(average as u64)
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Executed {} instructions in {:.2}s ({:.0} IPS)",
+            self.cycles,
+            self.run_time.as_secs_f64(),
+            self.instructions_per_second()
+        )?;
+        if self.timed_frames > 0 {
+            write!(
+                f,
+                ", frame jitter {:.2} ms",
+                self.frame_jitter().as_secs_f64() * 1000.0
+            )?;
+        }
+        if self.dropped_frames > 0 {
+            write!(
+                f,
+                ", dropped {} frame(s) running behind",
+                self.dropped_frames
+            )?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// The `FrameRate` struct represents the rate the run loop goes through its frames, measured
+/// over about a second at a time.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRate {
+    window: Duration,
+    frames: u32,
+    fps: u32,
+}
+
+impl FrameRate {
+    ///
+    /// Counts a frame that took `elapsed`, updating the rate once the frames add up to a second.
+    ///
+    pub fn frame(&mut self, elapsed: Duration) {
+        self.window += elapsed;
+        self.frames += 1;
+        if self.window >= Duration::from_secs(1) {
+            let micros = self.window.as_micros();
+            self.fps = ((u128::from(self.frames) * 1_000_000 + micros / 2) / micros) as u32;
+            self.window = Duration::ZERO;
+            self.frames = 0;
+        }
+    }
+
+    ///
+    /// Returns the frames per second over the last measured second, 0 before the first one.
+    ///
+    pub const fn fps(&self) -> u32 {
+        self.fps
+    }
+}
+
+///
+/// The `FRAME_BUDGET` constant is how long a frame has at 60 Hz.
+///
+pub const FRAME_BUDGET: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND as u64);
+
+///
+/// The `FRAME_TIMES_KEPT` constant is how many of the last frames the frame-time graph plots.
+///
+pub const FRAME_TIMES_KEPT: usize = 120;
+
+///
+/// The `FrameTimes` struct represents the host time and the instructions of the last
+/// `FRAME_TIMES_KEPT` frames of the run loop, the oldest ones making room for the new.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FrameTimes {
+    frames: VecDeque<(Duration, u64)>,
+}
+
+impl FrameTimes {
+    pub fn new() -> Self {
+        Self {
+            frames: VecDeque::with_capacity(FRAME_TIMES_KEPT),
+        }
+    }
+
+    ///
+    /// Counts a frame that took `elapsed` and executed `cycles` instructions.
+    ///
+    pub fn push(&mut self, elapsed: Duration, cycles: u64) {
+        if self.frames.len() == FRAME_TIMES_KEPT {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((elapsed, cycles));
+    }
+
+    ///
+    /// Returns the instructions executed per second over the kept frames, 0 before any time went
+    /// by.
+    ///
+    pub fn ips(&self) -> u32 {
+        let (time, cycles) = self.frames.iter().fold(
+            (Duration::ZERO, 0),
+            |(time, cycles), &(elapsed, executed)| (time + elapsed, cycles + executed),
+        );
+        if time.is_zero() {
+            return 0;
+        }
+        let ips = u128::from(cycles) * 1_000_000_000 / time.as_nanos();
+        u32::try_from(ips).unwrap_or(u32::MAX)
+    }
+
+    ///
+    /// Returns what the frame-time graph shows of the kept frames.
+    ///
+    pub fn graph(&self) -> FrameGraph {
+        FrameGraph {
+            times: self.frames.iter().map(|&(elapsed, _)| elapsed).collect(),
+            ips: self.ips(),
+        }
+    }
+}
+
+///
+/// The `FrameGraph` struct represents what the frame-time graph shows: the times of the last
+/// frames, oldest first, and the instructions executed per second over them.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FrameGraph {
+    pub times: Vec<Duration>,
+    pub ips: u32,
+}
+
+impl FrameGraph {
+    ///
+    /// Returns the frame time at the top of the graph: twice the budget, or as many budgets as
+    /// the slowest frame needs, so the budget always falls on a whole fraction of the height.
+    ///
+    pub fn full_scale(&self) -> Duration {
+        let slowest = self.times.iter().max().copied().unwrap_or_default();
+        let budgets = slowest.as_nanos().div_ceil(FRAME_BUDGET.as_nanos()).max(2);
+        FRAME_BUDGET * u32::try_from(budgets).unwrap_or(u32::MAX)
+    }
+
+    ///
+    /// Scales the frame times to bars of up to `height` pixels, with whether each frame went over
+    /// the budget.
+    ///
+    pub fn bars(&self, height: u32) -> Vec<(u32, bool)> {
+        let scale = self.full_scale();
+        self.times
+            .iter()
+            .map(|&time| (scaled(time, scale, height), time > FRAME_BUDGET))
+            .collect()
+    }
+
+    ///
+    /// Returns how high the budget is on a graph of `height` pixels.
+    ///
+    pub fn budget_height(&self, height: u32) -> u32 {
+        scaled(FRAME_BUDGET, self.full_scale(), height)
+    }
+}
+
+///
+/// Returns how many of `height` pixels `time` takes up on a graph topping out at `scale`,
+/// rounded to the nearest.
+///
+fn scaled(time: Duration, scale: Duration, height: u32) -> u32 {
+    let pixels = (time.as_nanos() * u128::from(height) + scale.as_nanos() / 2) / scale.as_nanos();
+    u32::try_from(pixels).unwrap_or(u32::MAX).min(height)
+}
+
+///
+/// The `Profile` struct represents how often each instruction and each address was executed, and
+/// roughly how much host time went into them.
+///
+/// Counting is an array increment per instruction. The host time is only measured per frame and
+/// split between what the frame executed, in proportion to the counts.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    instructions: [u64; INSTRUCTION_COUNT],
+    addresses: Vec<u64>,
+    instruction_nanos: [u64; INSTRUCTION_COUNT],
+    address_nanos: Vec<u64>,
+    // The counts as they were when the time was last sampled
+    sampled_instructions: [u64; INSTRUCTION_COUNT],
+    sampled_addresses: Vec<u64>,
+    sampled_total: u64,
+}
+
+///
+/// The `ProfileEntry` struct represents one line of a profile: an instruction or an address, how
+/// often it was executed and how much time went into it.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileEntry<K> {
+    pub key: K,
+    pub count: u64,
+    pub time: Duration,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            instructions: [0; INSTRUCTION_COUNT],
+            addresses: vec![0; RAM_SIZE],
+            instruction_nanos: [0; INSTRUCTION_COUNT],
+            address_nanos: vec![0; RAM_SIZE],
+            sampled_instructions: [0; INSTRUCTION_COUNT],
+            sampled_addresses: vec![0; RAM_SIZE],
+            sampled_total: 0,
+        }
+    }
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Counts an execution of `instruction` at `pc`.
+    ///
+    pub fn count(&mut self, pc: u16, instruction: Instruction) {
+        self.instructions[instruction as usize] += 1;
+        self.addresses[usize::from(pc) % RAM_SIZE] += 1;
+    }
+
+    ///
+    /// Splits `elapsed` between the instructions counted since the last sample.
+    ///
+    pub fn sample(&mut self, elapsed: Duration) {
+        let executed = self.total() - self.sampled_total;
+        if executed == 0 {
+            return;
+        }
+
+        let nanos = elapsed.as_nanos();
+        let instructions = self.instructions.iter().zip(&mut self.sampled_instructions);
+        split(instructions, &mut self.instruction_nanos, nanos, executed);
+        let addresses = self.addresses.iter().zip(&mut self.sampled_addresses);
+        split(addresses, &mut self.address_nanos, nanos, executed);
+        self.sampled_total += executed;
+    }
+
+    ///
+    /// Returns how many instructions were executed in all.
+    ///
+    pub fn total(&self) -> u64 {
+        self.instructions.iter().sum()
+    }
+
+    pub fn total_time(&self) -> Duration {
+        Duration::from_nanos(self.instruction_nanos.iter().sum())
+    }
+
+    pub const fn instruction_count(&self, instruction: Instruction) -> u64 {
+        self.instructions[instruction as usize]
+    }
+
+    pub fn address_count(&self, address: u16) -> u64 {
+        self.addresses[usize::from(address) % RAM_SIZE]
+    }
+
+    ///
+    /// Returns the executed instructions, the most executed first.
+    ///
+    pub fn hottest_instructions(&self) -> Vec<ProfileEntry<Instruction>> {
+        let entries = Instruction::ALL.iter().map(|&instruction| ProfileEntry {
+            key: instruction,
+            count: self.instructions[instruction as usize],
+            time: Duration::from_nanos(self.instruction_nanos[instruction as usize]),
+        });
+        hottest(entries)
+    }
+
+    ///
+    /// Returns the executed addresses, the most executed first.
+    ///
+    pub fn hottest_addresses(&self) -> Vec<ProfileEntry<u16>> {
+        let entries = (0..).zip(&self.addresses).zip(&self.address_nanos).map(
+            |((address, &count), &nanos)| ProfileEntry {
+                key: address,
+                count,
+                time: Duration::from_nanos(nanos),
+            },
+        );
+        hottest(entries)
+    }
+
+    ///
+    /// Returns the `top` hottest instructions and addresses with their share of the executions
+    /// and of the time, each address described by `describe`.
+    ///
+    pub fn report(&self, top: usize, describe: impl Fn(u16) -> String) -> String {
+        let total = self.total();
+        let total_time = self.total_time();
+        let mut report = String::new();
+        let _ = writeln!(report, "Hottest instructions:");
+        for entry in self.hottest_instructions().iter().take(top) {
+            let _ = writeln!(
+                report,
+                "  {}  {} {:?}",
+                entry.shares(total, total_time),
+                entry.key.pattern(),
+                entry.key
+            );
+        }
+        let _ = writeln!(report, "Hottest addresses:");
+        for entry in self.hottest_addresses().iter().take(top) {
+            let _ = writeln!(
+                report,
+                "  {}  {}",
+                entry.shares(total, total_time),
+                describe(entry.key)
+            );
+        }
+        report
+    }
+
+    ///
+    /// Writes every executed instruction and address as CSV, with their counts and times.
+    ///
+    pub fn write_csv(&self, out: &mut impl io::Write) -> io::Result<()> {
+        writeln!(out, "kind,key,count,time_us")?;
+        for entry in self.hottest_instructions() {
+            writeln!(
+                out,
+                "instruction,{:?},{},{}",
+                entry.key,
+                entry.count,
+                entry.time.as_micros()
+            )?;
+        }
+        for entry in self.hottest_addresses() {
+            writeln!(
+                out,
+                "address,0x{:04X},{},{}",
+                entry.key,
+                entry.count,
+                entry.time.as_micros()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<K> ProfileEntry<K> {
+    ///
+    /// Formats the count and time with their percentages of `total` and `total_time`.
+    ///
+    #[allow(clippy::cast_precision_loss)]
+    fn shares(&self, total: u64, total_time: Duration) -> String {
+        let percent = |part: f64, whole: f64| {
+            if whole > 0.0 {
+                part / whole * 100.0
+            } else {
+                0.0
+            }
+        };
+        format!(
+            "{:>10} {:>5.1}% {:>9.3}ms {:>5.1}%",
+            self.count,
+            percent(self.count as f64, total as f64),
+            self.time.as_secs_f64() * 1000.0,
+            percent(self.time.as_secs_f64(), total_time.as_secs_f64())
+        )
+    }
+}
+
+///
+/// Adds to each of `nanos` its share of the `elapsed` nanoseconds the `executed` instructions
+/// since the last sample took, updating the sampled counts.
+///
+#[allow(clippy::cast_possible_truncation)]
+fn split<'a>(
+    counts: impl Iterator<Item = (&'a u64, &'a mut u64)>,
+    nanos: &mut [u64],
+    elapsed: u128,
+    executed: u64,
+) {
+    for ((&count, sampled), nanos) in counts.zip(nanos) {
+        let delta = count - *sampled;
+        if delta != 0 {
+            *nanos += (elapsed * u128::from(delta) / u128::from(executed)) as u64;
+            *sampled = count;
+        }
+    }
+}
+
+///
+/// Keeps the executed entries, sorted from the most executed to the least.
+///
+fn hottest<K>(entries: impl Iterator<Item = ProfileEntry<K>>) -> Vec<ProfileEntry<K>> {
+    let mut entries: Vec<_> = entries.filter(|entry| entry.count > 0).collect();
+    // Stable, so ties stay in the order of the instructions or addresses
+    entries.sort_by_key(|entry| Reverse(entry.count));
+    entries
+}
+
+///
+/// The `RunOutcome` enum represents why the emulator's run loop stopped.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    Quit,
+    BudgetExhausted,
+    /// The stop flag was set, by Ctrl-C typically
+    Interrupted,
+    /// The user asked to pick another ROM from the library
+    Browse,
+    /// The user asked to save the settings the ROM runs with, to run on after
+    SaveProfile,
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use std::time::Duration;
+
+    use super::{
+        FrameGraph, FrameRate, FrameTimes, Profile, Stats, FRAME_BUDGET, FRAME_TIMES_KEPT,
+    };
+    use crate::emu::cpu::Instruction;
+
+    #[test]
+    fn summary() {
+        let stats = Stats {
+            cycles: 900,
+            run_time: Duration::from_secs(2),
+            ..Stats::default()
+        };
+        assert_eq!(
+            stats.to_string(),
+            "Executed 900 instructions in 2.00s (450 IPS)"
+        );
+        let mut stats = Stats {
+            dropped_frames: 12,
+            ..stats
+        };
+        assert_eq!(
+            stats.to_string(),
+            "Executed 900 instructions in 2.00s (450 IPS), dropped 12 frame(s) running behind"
+        );
+
+        let target = Duration::from_micros(16_667);
+        stats.time_frame(target + Duration::from_micros(1500), target);
+        stats.time_frame(Duration::from_micros(16_067), target);
+        stats.time_frame(target, target);
+        assert_eq!(stats.frame_jitter(), Duration::from_micros(700));
+        assert_eq!(
+            stats.to_string(),
+            "Executed 900 instructions in 2.00s (450 IPS), frame jitter 0.70 ms, dropped 12 \
+             frame(s) running behind"
+        );
+    }
+
+    #[test]
+    fn summary_without_run_time() {
+        assert_eq!(
+            Stats::default().to_string(),
+            "Executed 0 instructions in 0.00s (0 IPS)"
+        );
+    }
+
+    #[test]
+    fn frame_rate() {
+        let mut rate = FrameRate::default();
+        for _ in 0..59 {
+            rate.frame(Duration::from_millis(17));
+        }
+        // 59 frames of 17 ms are just over a second
+        assert_eq!(rate.fps(), 59);
+        for _ in 0..30 {
+            rate.frame(Duration::from_millis(34));
+        }
+        assert_eq!(rate.fps(), 29);
+    }
+
+    fn mixed_profile() -> Profile {
+        let mut profile = Profile::new();
+        // A loop of LD, then ADD and SE three times, then a JMP back
+        profile.count(0x200, Instruction::LD);
+        for _ in 0..3 {
+            profile.count(0x202, Instruction::ADD);
+            profile.count(0x204, Instruction::SE);
+        }
+        profile.count(0x206, Instruction::JMP);
+        profile
+    }
+
+    #[test]
+    fn profile_counts() {
+        let profile = mixed_profile();
+        assert_eq!(profile.total(), 8);
+        assert_eq!(profile.instruction_count(Instruction::ADD), 3);
+        assert_eq!(profile.instruction_count(Instruction::DRW), 0);
+        assert_eq!(profile.address_count(0x204), 3);
+
+        let instructions: Vec<_> = profile
+            .hottest_instructions()
+            .iter()
+            .map(|entry| (entry.key, entry.count))
+            .collect();
+        assert_eq!(
+            instructions,
+            [
+                (Instruction::SE, 3),
+                (Instruction::ADD, 3),
+                (Instruction::JMP, 1),
+                (Instruction::LD, 1),
+            ]
+        );
+        let addresses: Vec<_> = profile
+            .hottest_addresses()
+            .iter()
+            .map(|entry| entry.key)
+            .collect();
+        assert_eq!(addresses, [0x202, 0x204, 0x200, 0x206]);
+    }
+
+    #[test]
+    fn profile_splits_the_time_by_count() {
+        let mut profile = mixed_profile();
+        profile.sample(Duration::from_millis(8));
+        profile.count(0x200, Instruction::LD);
+        profile.sample(Duration::from_millis(4));
+        // Sampling again without executing anything changes nothing
+        profile.sample(Duration::from_millis(100));
+
+        let time = |instruction| {
+            profile
+                .hottest_instructions()
+                .into_iter()
+                .find(|entry| entry.key == instruction)
+                .map(|entry| entry.time)
+        };
+        assert_eq!(time(Instruction::ADD), Some(Duration::from_millis(3)));
+        assert_eq!(time(Instruction::LD), Some(Duration::from_millis(5)));
+        assert_eq!(profile.total_time(), Duration::from_millis(12));
+    }
+
+    #[test]
+    fn profile_report() {
+        let profile = mixed_profile();
+        let report = profile.report(2, |address| format!("0x{address:04X}"));
+        assert_eq!(
+            report,
+            "Hottest instructions:\n\
+             \x20          3  37.5%     0.000ms   0.0%  3xkk SE\n\
+             \x20          3  37.5%     0.000ms   0.0%  7xkk ADD\n\
+             Hottest addresses:\n\
+             \x20          3  37.5%     0.000ms   0.0%  0x0202\n\
+             \x20          3  37.5%     0.000ms   0.0%  0x0204\n"
+        );
+
+        let mut csv = Vec::new();
+        profile.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("kind,key,count,time_us\ninstruction,SE,3,0\n"));
+        assert!(csv.ends_with("address,0x0206,1,0\n"));
+    }
+
+    #[test]
+    fn frame_times_keep_the_last_frames() {
+        let mut times = FrameTimes::new();
+        assert_eq!(times.ips(), 0);
+        assert!(times.graph().times.is_empty());
+        for frame in 0..FRAME_TIMES_KEPT as u64 + 5 {
+            times.push(Duration::from_millis(frame), 10);
+        }
+        let graph = times.graph();
+        assert_eq!(graph.times.len(), FRAME_TIMES_KEPT);
+        assert_eq!(graph.times[0], Duration::from_millis(5));
+
+        // 700 instructions a second, at 60 frames a second
+        let mut times = FrameTimes::new();
+        for cycles in [11, 12, 12] {
+            times.push(FRAME_BUDGET, cycles);
+        }
+        assert_eq!(times.ips(), 700);
+    }
+
+    #[test]
+    fn graph_scales_to_the_slowest_frame() {
+        let ms = Duration::from_millis;
+        let graph = FrameGraph {
+            times: vec![ms(0), FRAME_BUDGET, ms(20), ms(8)],
+            ips: 700,
+        };
+        // The budget is halfway up, whatever the frames
+        assert_eq!(graph.full_scale(), FRAME_BUDGET * 2);
+        assert_eq!(graph.budget_height(40), 20);
+        assert_eq!(
+            graph.bars(40),
+            [(0, false), (20, false), (24, true), (10, false)]
+        );
+
+        // A stutter of 40 ms takes three budgets
+        let graph = FrameGraph {
+            times: vec![ms(40), ms(10)],
+            ips: 0,
+        };
+        assert_eq!(graph.full_scale(), FRAME_BUDGET * 3);
+        assert_eq!(graph.budget_height(30), 10);
+        assert_eq!(graph.bars(30), [(24, true), (6, false)]);
+
+        // Nothing to plot yet
+        let empty = FrameGraph::default();
+        assert_eq!(empty.full_scale(), FRAME_BUDGET * 2);
+        assert!(empty.bars(40).is_empty());
+    }
+}
@@ -0,0 +1,420 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+
+use super::io::{GRID_WIDTH, HIRES_GRID_HEIGHT};
+
+///
+/// The `ROW_SIZE` constant is the size of a packed row of the screen: a bit per pixel, the
+/// leftmost pixel of every byte in its highest bit.
+///
+pub const ROW_SIZE: usize = GRID_WIDTH / 8;
+
+///
+/// The `MAX_ROWS` constant is how many rows a packed screen has at most, the 64 of HiRes CHIP-8.
+///
+pub const MAX_ROWS: usize = HIRES_GRID_HEIGHT;
+
+///
+/// The `PackedScreen` type represents a screen packed a bit per pixel, row by row, `ROW_SIZE`
+/// bytes to a row.
+///
+pub type PackedScreen = Vec<u8>;
+
+const FULL: u8 = b'F';
+const DELTA: u8 = b'D';
+
+///
+/// Packs the framebuffer, one byte (0 or 1) per pixel and 64 pixels to a row, a bit per pixel.
+/// The rows past `MAX_ROWS` are left out.
+///
+pub fn pack(framebuffer: &[u8]) -> PackedScreen {
+    framebuffer
+        .chunks(8)
+        .take(MAX_ROWS * ROW_SIZE)
+        .map(|pixels| {
+            pixels.iter().enumerate().fold(0, |byte, (bit, &pixel)| {
+                byte | u8::from(pixel != 0) << (7 - bit)
+            })
+        })
+        .collect()
+}
+
+///
+/// Unpacks a packed screen back into a framebuffer, one byte per pixel.
+///
+pub fn unpack(packed: &[u8]) -> Vec<u8> {
+    packed
+        .iter()
+        .flat_map(|&byte| (0..8).map(move |bit| (byte >> (7 - bit)) & 1))
+        .collect()
+}
+
+///
+/// The `Packet` enum represents a screen sent to a spectator, with the number of the frame that
+/// showed it.
+///
+/// A full packet is `F`, the frame number as a big-endian `u32`, the count of rows, 32 or the 64
+/// of HiRes CHIP-8, and the packed screen. A delta packet is `D`, the frame number, the count of
+/// changed bytes and every one of them as its big-endian `u16` index and its new value, against
+/// the screen of the packet before.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Full {
+        frame: u32,
+        screen: PackedScreen,
+    },
+    Delta {
+        frame: u32,
+        /// The index of every changed byte and its new value
+        changes: Vec<(u16, u8)>,
+    },
+}
+
+///
+/// The `StreamError` enum represents a stream that isn't made of packets.
+///
+#[derive(Debug)]
+pub enum StreamError {
+    Io(io::Error),
+    UnknownPacket(u8),
+    /// A full screen of no rows, or of more than `MAX_ROWS`
+    InvalidRows(u8),
+    /// A delta came before any full screen it could apply to
+    MissingScreen,
+}
+
+impl Error for StreamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "Failed to read the stream: {err}!"),
+            Self::UnknownPacket(kind) => write!(f, "Unknown packet kind 0x{kind:02X}!"),
+            Self::InvalidRows(rows) => write!(f, "A screen of {rows} rows can't be shown!"),
+            Self::MissingScreen => write!(f, "A delta came before any full screen!"),
+        }
+    }
+}
+
+impl From<io::Error> for StreamError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl Packet {
+    pub const fn frame(&self) -> u32 {
+        match self {
+            Self::Full { frame, .. } | Self::Delta { frame, .. } => *frame,
+        }
+    }
+
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Full { frame, screen } => {
+                out.push(FULL);
+                out.extend_from_slice(&frame.to_be_bytes());
+                out.push((screen.len() / ROW_SIZE) as u8);
+                out.extend_from_slice(screen);
+            }
+            Self::Delta { frame, changes } => {
+                out.push(DELTA);
+                out.extend_from_slice(&frame.to_be_bytes());
+                // An encoder sends the full screen well before that many bytes changed
+                out.push(changes.len().min(usize::from(u8::MAX)) as u8);
+                for &(index, value) in changes.iter().take(usize::from(u8::MAX)) {
+                    out.extend_from_slice(&index.to_be_bytes());
+                    out.push(value);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Reads the next packet, waiting for all of it.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the reader does, e.g. with `UnexpectedEof` once the stream ended, or when the
+    /// packet is of an unknown kind or a screen of too many rows.
+    ///
+    pub fn read(reader: &mut impl Read) -> Result<Self, StreamError> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        let frame = u32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+        match header[0] {
+            FULL => {
+                let mut rows = [0];
+                reader.read_exact(&mut rows)?;
+                if !(1..=MAX_ROWS).contains(&usize::from(rows[0])) {
+                    return Err(StreamError::InvalidRows(rows[0]));
+                }
+                let mut screen = vec![0; usize::from(rows[0]) * ROW_SIZE];
+                reader.read_exact(&mut screen)?;
+                Ok(Self::Full { frame, screen })
+            }
+            DELTA => {
+                let mut count = [0];
+                reader.read_exact(&mut count)?;
+                let mut bytes = vec![0; 3 * usize::from(count[0])];
+                reader.read_exact(&mut bytes)?;
+                let changes = bytes
+                    .chunks_exact(3)
+                    .map(|change| (u16::from_be_bytes([change[0], change[1]]), change[2]))
+                    .collect();
+                Ok(Self::Delta { frame, changes })
+            }
+            kind => Err(StreamError::UnknownPacket(kind)),
+        }
+    }
+}
+
+///
+/// The `Encoder` struct represents the sending end of a stream: it sends the changes to the
+/// screen it sent last, or the whole screen when that's as short or its size changed.
+///
+#[derive(Debug, Default, Clone)]
+pub struct Encoder {
+    sent: Option<PackedScreen>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Starts over with a full screen, e.g. for a new connection.
+    ///
+    pub fn reset(&mut self) {
+        self.sent = None;
+    }
+
+    pub fn encode(&mut self, frame: u32, screen: &[u8]) -> Packet {
+        let changes = self
+            .sent
+            .as_ref()
+            .filter(|sent| sent.len() == screen.len())
+            .map(|sent| {
+                sent.iter()
+                    .zip(screen)
+                    .enumerate()
+                    .filter(|(_, (old, new))| old != new)
+                    .map(|(index, (_, &new))| (index as u16, new))
+                    .collect::<Vec<_>>()
+            });
+        self.sent = Some(screen.to_vec());
+        // Three bytes a change, the full screen is shorter from a third of it on
+        let most_changes = (screen.len() / 3).min(usize::from(u8::MAX));
+        match changes {
+            Some(changes) if changes.len() < most_changes => Packet::Delta { frame, changes },
+            _ => Packet::Full {
+                frame,
+                screen: screen.to_vec(),
+            },
+        }
+    }
+}
+
+///
+/// The `Decoder` struct represents the receiving end of a stream: the screen as the packets so far
+/// left it.
+///
+#[derive(Debug, Default, Clone)]
+pub struct Decoder {
+    screen: Option<PackedScreen>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Applies the packet, returning the screen it leaves.
+    ///
+    /// # Errors
+    ///
+    /// Fails on a delta coming before any full screen.
+    ///
+    pub fn apply(&mut self, packet: &Packet) -> Result<&PackedScreen, StreamError> {
+        match packet {
+            Packet::Full { screen, .. } => Ok(self.screen.insert(screen.clone())),
+            Packet::Delta { changes, .. } => {
+                let screen = self.screen.as_mut().ok_or(StreamError::MissingScreen)?;
+                for &(index, value) in changes {
+                    if let Some(byte) = screen.get_mut(usize::from(index)) {
+                        *byte = value;
+                    }
+                }
+                Ok(screen)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod stream_tests {
+    use super::{pack, unpack, Decoder, Encoder, Packet, StreamError, ROW_SIZE};
+    use crate::emu::io::{GRID_HEIGHT, GRID_WIDTH, HIRES_GRID_HEIGHT};
+
+    const PACKED_SIZE: usize = GRID_HEIGHT * ROW_SIZE;
+
+    fn screen(lit: &[usize]) -> Vec<u8> {
+        sized_screen(GRID_HEIGHT, lit)
+    }
+
+    fn sized_screen(height: usize, lit: &[usize]) -> Vec<u8> {
+        let mut framebuffer = vec![0; GRID_WIDTH * height];
+        for &pixel in lit {
+            framebuffer[pixel] = 1;
+        }
+        framebuffer
+    }
+
+    fn encoded(packet: &Packet) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        packet.encode(&mut bytes);
+        bytes
+    }
+
+    #[test]
+    fn packs_a_bit_per_pixel() {
+        let framebuffer = screen(&[0, 9, GRID_WIDTH * GRID_HEIGHT - 1]);
+        let packed = pack(&framebuffer);
+        assert_eq!(packed[..2], [0x80, 0x40]);
+        assert_eq!(packed[PACKED_SIZE - 1], 0x01);
+        assert_eq!(packed.iter().filter(|&&byte| byte != 0).count(), 3);
+        assert_eq!(unpack(&packed), framebuffer);
+    }
+
+    #[test]
+    fn sends_the_changes_once_a_screen_was_sent() {
+        let mut encoder = Encoder::new();
+        let first = encoder.encode(1, &pack(&screen(&[0])));
+        assert!(matches!(first, Packet::Full { frame: 1, .. }));
+        assert_eq!(encoded(&first).len(), 6 + PACKED_SIZE);
+        assert_eq!(encoded(&first)[..7], [b'F', 0, 0, 0, 1, 32, 0x80]);
+
+        let second = encoder.encode(2, &pack(&screen(&[0, 9, 300])));
+        assert_eq!(
+            second,
+            Packet::Delta {
+                frame: 2,
+                changes: vec![(1, 0x40), (37, 0x08)]
+            }
+        );
+        assert_eq!(
+            encoded(&second),
+            [b'D', 0, 0, 0, 2, 2, 0, 1, 0x40, 0, 37, 0x08]
+        );
+        // Nothing changed
+        assert_eq!(
+            encoded(&encoder.encode(3, &pack(&screen(&[0, 9, 300])))).len(),
+            6
+        );
+
+        // A screen mostly changed is sent whole, as a new connection's first one is
+        let lit: Vec<_> = (0..GRID_WIDTH * GRID_HEIGHT).step_by(8).collect();
+        assert!(matches!(
+            encoder.encode(4, &pack(&screen(&lit))),
+            Packet::Full { frame: 4, .. }
+        ));
+        encoder.reset();
+        assert!(matches!(
+            encoder.encode(5, &pack(&screen(&lit))),
+            Packet::Full { .. }
+        ));
+    }
+
+    #[test]
+    fn decodes_what_was_encoded() {
+        let screens = [
+            screen(&[]),
+            screen(&[5, 6, 7]),
+            screen(&[6, 2047]),
+            screen(&[]),
+        ];
+        let mut encoder = Encoder::new();
+        let mut bytes = Vec::new();
+        for (frame, framebuffer) in screens.iter().enumerate() {
+            encoder
+                .encode(frame as u32, &pack(framebuffer))
+                .encode(&mut bytes);
+        }
+
+        let mut reader = bytes.as_slice();
+        let mut decoder = Decoder::new();
+        for (frame, framebuffer) in screens.iter().enumerate() {
+            let packet = Packet::read(&mut reader).unwrap();
+            assert_eq!(packet.frame(), frame as u32);
+            assert_eq!(unpack(decoder.apply(&packet).unwrap()), *framebuffer);
+        }
+        assert!(matches!(
+            Packet::read(&mut reader),
+            Err(StreamError::Io(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_stream() {
+        let delta = Packet::Delta {
+            frame: 0,
+            changes: vec![(0, 0xFF)],
+        };
+        let err = Decoder::new().apply(&delta).unwrap_err();
+        assert_eq!(err.to_string(), "A delta came before any full screen!");
+
+        let err = Packet::read(&mut &b"GET / HTTP/1.1"[..]).unwrap_err();
+        assert!(matches!(err, StreamError::UnknownPacket(b'G')));
+        assert_eq!(err.to_string(), "Unknown packet kind 0x47!");
+        // Cut short
+        assert!(Packet::read(&mut &[b'D', 0, 0, 0, 1, 2, 0][..]).is_err());
+        let err = Packet::read(&mut &[b'F', 0, 0, 0, 1, 65][..]).unwrap_err();
+        assert_eq!(err.to_string(), "A screen of 65 rows can't be shown!");
+    }
+
+    #[test]
+    fn streams_the_64_rows_of_hires_screens() {
+        let tall = sized_screen(HIRES_GRID_HEIGHT, &[0, GRID_WIDTH * HIRES_GRID_HEIGHT - 1]);
+        let packed = pack(&tall);
+        assert_eq!(packed.len(), HIRES_GRID_HEIGHT * ROW_SIZE);
+
+        let mut encoder = Encoder::new();
+        let mut bytes = Vec::new();
+        // A screen growing is sent whole, the changes past the first rows as wide indexes
+        encoder.encode(0, &pack(&screen(&[0]))).encode(&mut bytes);
+        let full = encoder.encode(1, &packed);
+        assert!(matches!(&full, Packet::Full { screen, .. } if screen.len() == 512));
+        full.encode(&mut bytes);
+        let lit = [0, GRID_WIDTH * HIRES_GRID_HEIGHT - 2];
+        let delta = encoder.encode(2, &pack(&sized_screen(HIRES_GRID_HEIGHT, &lit)));
+        assert_eq!(
+            delta,
+            Packet::Delta {
+                frame: 2,
+                changes: vec![(511, 0x02)]
+            }
+        );
+        delta.encode(&mut bytes);
+
+        let mut reader = bytes.as_slice();
+        let mut decoder = Decoder::new();
+        let mut screens = Vec::new();
+        while let Ok(packet) = Packet::read(&mut reader) {
+            screens.push(unpack(decoder.apply(&packet).unwrap()));
+        }
+        assert_eq!(screens[0], screen(&[0]));
+        assert_eq!(screens[1], tall);
+        assert_eq!(screens[2], sized_screen(HIRES_GRID_HEIGHT, &lit));
+    }
+}
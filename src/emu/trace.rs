@@ -0,0 +1,309 @@
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::io;
+use std::str::FromStr;
+
+use super::disasm;
+use super::memory::Registers;
+
+///
+/// The `TraceFormat` enum represents how the executed instructions are written out.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One aligned line per instruction, for people
+    Text,
+    /// One JSON object per line, for tools
+    Json,
+}
+
+///
+/// The `TraceRegisters` enum represents which registers every traced instruction lists.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TraceRegisters {
+    /// All of them, before and after the instruction
+    Full,
+    /// Only the ones the instruction changed, with their old and new values
+    #[default]
+    Changed,
+}
+
+///
+/// The `TraceOptionError` struct represents a trace format or verbosity that isn't supported.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceOptionError(String);
+
+impl Error for TraceOptionError {}
+
+impl fmt::Display for TraceOptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for TraceFormat {
+    type Err = TraceOptionError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" | "jsonl" => Ok(Self::Json),
+            _ => Err(TraceOptionError(format!(
+                "Unknown trace format `{name}`, expected `text` or `json`!"
+            ))),
+        }
+    }
+}
+
+impl FromStr for TraceRegisters {
+    type Err = TraceOptionError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "changed" => Ok(Self::Changed),
+            _ => Err(TraceOptionError(format!(
+                "Unknown trace registers `{name}`, expected `full` or `changed`!"
+            ))),
+        }
+    }
+}
+
+///
+/// The `TraceRecord` struct represents one executed instruction, with the registers around it.
+///
+#[derive(Debug, Clone, Copy)]
+pub struct TraceRecord<'a> {
+    /// How many instructions ran before this one
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub before: &'a Registers,
+    pub after: &'a Registers,
+}
+
+///
+/// The `REGISTER_NAMES` constant is how the traces name the general registers.
+///
+const REGISTER_NAMES: [&str; 16] = [
+    "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7", "v8", "v9", "va", "vb", "vc", "vd", "ve", "vf",
+];
+
+impl TraceRecord<'_> {
+    ///
+    /// Returns the registers the instruction changed, the PC and the stack excluded, as
+    /// `(name, old, new)`.
+    ///
+    fn changes(&self) -> impl Iterator<Item = (&'static str, u16, u16)> + '_ {
+        let general = REGISTER_NAMES
+            .iter()
+            .zip(self.before.v.iter().zip(&self.after.v))
+            .map(|(&name, (&old, &new))| (name, old.into(), new.into()));
+        let special = [
+            ("i", self.before.i, self.after.i),
+            ("dt", self.before.dt.into(), self.after.dt.into()),
+            ("st", self.before.st.into(), self.after.st.into()),
+        ];
+        general.chain(special).filter(|&(_, old, new)| old != new)
+    }
+
+    ///
+    /// Writes the record as a single line of JSON, without the line break. The keys always come
+    /// in the same order, so traces can be diffed as text.
+    ///
+    pub fn write_json(&self, out: &mut String, registers: TraceRegisters) {
+        let _ = write!(
+            out,
+            "{{\"cycle\":{},\"pc\":{},\"opcode\":{},\"mnemonic\":\"",
+            self.cycle, self.pc, self.opcode
+        );
+        escape_json(&disasm::disassemble(self.pc, self.opcode), out);
+        let _ = write!(
+            out,
+            "\",\"i\":{},\"dt\":{},\"st\":{}",
+            self.after.i, self.after.dt, self.after.st
+        );
+
+        match registers {
+            TraceRegisters::Full => {
+                out.push_str(",\"before\":");
+                write_json_registers(self.before, out);
+                out.push_str(",\"after\":");
+                write_json_registers(self.after, out);
+            }
+            TraceRegisters::Changed => {
+                out.push_str(",\"changes\":{");
+                for (n, (name, old, new)) in self.changes().enumerate() {
+                    let separator = if n == 0 { "" } else { "," };
+                    let _ = write!(out, "{separator}\"{name}\":[{old},{new}]");
+                }
+                out.push('}');
+            }
+        }
+        out.push('}');
+    }
+
+    ///
+    /// Writes the record as a line of text, without the line break, e.g.
+    /// `3 0x0204: 8014  ADD V0, V1    V0 0x05 -> 0x0C`.
+    ///
+    pub fn write_text(&self, out: &mut String, registers: TraceRegisters) {
+        let _ = write!(
+            out,
+            "{:>8} 0x{:04X}: {:04X}  {:<20}",
+            self.cycle,
+            self.pc,
+            self.opcode,
+            disasm::disassemble(self.pc, self.opcode)
+        );
+        match registers {
+            TraceRegisters::Full => {
+                for (x, value) in self.after.v.iter().enumerate() {
+                    let _ = write!(out, " V{x:X}={value:02X}");
+                }
+                let _ = write!(
+                    out,
+                    " I={:04X} DT={:02X} ST={:02X}",
+                    self.after.i, self.after.dt, self.after.st
+                );
+            }
+            TraceRegisters::Changed => {
+                for (name, old, new) in self.changes() {
+                    let _ = write!(out, " {} 0x{old:02X} -> 0x{new:02X}", name.to_uppercase());
+                }
+            }
+        }
+        out.truncate(out.trim_end().len());
+    }
+}
+
+fn write_json_registers(registers: &Registers, out: &mut String) {
+    out.push_str("{\"v\":[");
+    for (x, value) in registers.v.iter().enumerate() {
+        let separator = if x == 0 { "" } else { "," };
+        let _ = write!(out, "{separator}{value}");
+    }
+    let _ = write!(
+        out,
+        "],\"i\":{},\"dt\":{},\"st\":{},\"pc\":{},\"sp\":{}}}",
+        registers.i, registers.dt, registers.st, registers.pc, registers.sp
+    );
+}
+
+pub(crate) fn escape_json(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+///
+/// The `Tracer` trait represents where the executed instructions go. `Chip8` only snapshots the
+/// registers for it while one is set, so there's no cost to tracing when it's off.
+///
+pub trait Tracer {
+    fn trace(&mut self, record: &TraceRecord<'_>);
+}
+
+///
+/// The `TraceWriter` struct represents a trace written into `out`, one line per instruction,
+/// reusing the same line buffer for all of them.
+///
+pub struct TraceWriter<W: io::Write> {
+    out: W,
+    format: TraceFormat,
+    registers: TraceRegisters,
+    line: String,
+}
+
+impl<W: io::Write> TraceWriter<W> {
+    pub const fn new(out: W, format: TraceFormat, registers: TraceRegisters) -> Self {
+        Self {
+            out,
+            format,
+            registers,
+            line: String::new(),
+        }
+    }
+}
+
+impl<W: io::Write> Tracer for TraceWriter<W> {
+    fn trace(&mut self, record: &TraceRecord<'_>) {
+        self.line.clear();
+        match self.format {
+            TraceFormat::Text => record.write_text(&mut self.line, self.registers),
+            TraceFormat::Json => record.write_json(&mut self.line, self.registers),
+        }
+        self.line.push('\n');
+        // A closed pipe (e.g. `| head`) only ends the trace, not the emulation
+        let _ = self.out.write_all(self.line.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod trace_tests {
+    use super::{TraceFormat, TraceRecord, TraceRegisters};
+    use crate::emu::memory::Registers;
+
+    fn add_record<'a>(before: &'a Registers, after: &'a Registers) -> TraceRecord<'a> {
+        TraceRecord {
+            cycle: 2,
+            pc: 0x204,
+            opcode: 0x8014,
+            before,
+            after,
+        }
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!("json".parse(), Ok(TraceFormat::Json));
+        assert_eq!("Text".parse(), Ok(TraceFormat::Text));
+        assert_eq!("full".parse(), Ok(TraceRegisters::Full));
+        assert_eq!(TraceRegisters::default(), TraceRegisters::Changed);
+        assert_eq!(
+            "xml".parse::<TraceFormat>().unwrap_err().to_string(),
+            "Unknown trace format `xml`, expected `text` or `json`!"
+        );
+    }
+
+    #[test]
+    fn changed_registers() {
+        let mut before = Registers::new();
+        before.v[0] = 0x05;
+        before.v[1] = 0x07;
+        let mut after = before;
+        after.v[0] = 0x0C;
+        after.pc = 0x206;
+
+        let mut json = String::new();
+        add_record(&before, &after).write_json(&mut json, TraceRegisters::Changed);
+        assert_eq!(
+            json,
+            "{\"cycle\":2,\"pc\":516,\"opcode\":32788,\"mnemonic\":\"ADD V0, V1\",\
+             \"i\":0,\"dt\":0,\"st\":0,\"changes\":{\"v0\":[5,12]}}"
+        );
+
+        let mut text = String::new();
+        add_record(&before, &after).write_text(&mut text, TraceRegisters::Changed);
+        assert_eq!(
+            text,
+            "       2 0x0204: 8014  ADD V0, V1           V0 0x05 -> 0x0C"
+        );
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        let mut out = String::new();
+        super::escape_json("JP #0x2EA ; \"halt\"\\\n", &mut out);
+        assert_eq!(out, "JP #0x2EA ; \\\"halt\\\"\\\\\\u000a");
+    }
+}
@@ -0,0 +1,256 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::chip8::FRAMES_PER_SECOND;
+
+///
+/// The `MAX_TURBO_RATE` constant is the fastest a turbo key can be pressed, in presses per second:
+/// down one frame, up the next.
+///
+pub const MAX_TURBO_RATE: u32 = FRAMES_PER_SECOND / 2;
+
+///
+/// The `TurboKey` struct represents a hex key pressed over and over while it's held, e.g. `5:10`
+/// for key 5 pressed ten times a second.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurboKey {
+    pub hex_key: u8,
+    /// Presses per second, from 1 to `MAX_TURBO_RATE`
+    pub rate: u32,
+}
+
+impl TurboKey {
+    ///
+    /// Returns whether the key is down after it has been held for `frames` frames, starting down
+    /// and going up halfway through every press.
+    ///
+    pub const fn is_down(&self, frames: u32) -> bool {
+        // Where in the current press the frame falls, in 60ths of a press
+        let phase = (frames as u64 * self.rate as u64) % FRAMES_PER_SECOND as u64;
+        phase < FRAMES_PER_SECOND as u64 / 2
+    }
+}
+
+impl fmt::Display for TurboKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:X}:{}", self.hex_key, self.rate)
+    }
+}
+
+///
+/// The `TurboKeyError` struct represents a turbo binding that isn't a hex key and a rate.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurboKeyError(String);
+
+impl Error for TurboKeyError {}
+
+impl fmt::Display for TurboKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid turbo key `{}`, expected a hex key and from 1 to {MAX_TURBO_RATE} presses a second, e.g. `5:10`!",
+            self.0
+        )
+    }
+}
+
+impl FromStr for TurboKey {
+    type Err = TurboKeyError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || TurboKeyError(text.to_string());
+        let (key, rate) = text.split_once(':').ok_or_else(error)?;
+        let key = key.trim();
+        let hex_key = u8::from_str_radix(key.trim_start_matches("0x"), 16)
+            .ok()
+            .filter(|&hex_key| hex_key < 0x10 && !key.is_empty())
+            .ok_or_else(error)?;
+        let rate = rate
+            .trim()
+            .parse()
+            .ok()
+            .filter(|rate| (1..=MAX_TURBO_RATE).contains(rate))
+            .ok_or_else(error)?;
+        Ok(Self { hex_key, rate })
+    }
+}
+
+///
+/// The `Turbo` struct represents the turbo keys and how long each one has been held, counted in
+/// frames so the presses happen on the same frames every run.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Turbo {
+    keys: Vec<TurboKey>,
+    /// The frames every hex key has been held for and whether it's down, none while it's up
+    held: [Option<(u32, bool)>; 16],
+}
+
+impl Turbo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Makes the hex key a turbo key, replacing its binding if it already was one.
+    ///
+    pub fn add(&mut self, key: TurboKey) {
+        self.keys.retain(|turbo| turbo.hex_key != key.hex_key);
+        self.keys.push(key);
+    }
+
+    pub fn keys(&self) -> &[TurboKey] {
+        &self.keys
+    }
+
+    ///
+    /// Holds or lets go of the hex key, returning whether it's a turbo key. Others are left to be
+    /// pressed as usual.
+    ///
+    pub fn hold(&mut self, hex_key: u8, held: bool) -> bool {
+        if self.keys.iter().all(|turbo| turbo.hex_key != hex_key) {
+            return false;
+        }
+        let state = &mut self.held[usize::from(hex_key & 0xF)];
+        if !held {
+            *state = None;
+        } else if state.is_none() {
+            *state = Some((0, false));
+        }
+        true
+    }
+
+    ///
+    /// Returns whether the turbo key is down in the current frame, none if it isn't held.
+    ///
+    pub fn is_down(&self, hex_key: u8) -> Option<bool> {
+        self.held[usize::from(hex_key & 0xF)].map(|(_, down)| down)
+    }
+
+    ///
+    /// Lets go of every turbo key, e.g. when the window loses the focus.
+    ///
+    pub fn release(&mut self) {
+        self.held = [None; 16];
+    }
+
+    ///
+    /// Moves on to the next frame, returning the held turbo keys going down or up in it, as
+    /// presses and releases of the keypad.
+    ///
+    pub fn frame(&mut self) -> Vec<(u8, bool)> {
+        let mut changes = Vec::new();
+        for key in &self.keys {
+            if let Some((frames, down)) = &mut self.held[usize::from(key.hex_key)] {
+                if key.is_down(*frames) != *down {
+                    *down = !*down;
+                    changes.push((key.hex_key, *down));
+                }
+                *frames = frames.saturating_add(1);
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod turbo_tests {
+    use super::{Turbo, TurboKey, TurboKeyError};
+
+    ///
+    /// Runs `frames` frames, drawing the key down as `#`, up as `_` and not held as `.`.
+    ///
+    fn pattern(turbo: &mut Turbo, hex_key: u8, frames: usize) -> String {
+        (0..frames)
+            .map(|_| {
+                turbo.frame();
+                match turbo.is_down(hex_key) {
+                    Some(true) => '#',
+                    Some(false) => '_',
+                    None => '.',
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_bindings() {
+        assert_eq!(
+            "5:10".parse(),
+            Ok(TurboKey {
+                hex_key: 5,
+                rate: 10
+            })
+        );
+        assert_eq!("a:30".parse::<TurboKey>().unwrap().hex_key, 0xA);
+        assert_eq!("0xF:1".parse::<TurboKey>().unwrap().to_string(), "F:1");
+        for invalid in ["5", "5:0", "5:31", "10:5", ":5", "G:5", "5:fast"] {
+            assert_eq!(
+                invalid.parse::<TurboKey>(),
+                Err(TurboKeyError(invalid.to_string())),
+                "{invalid}"
+            );
+        }
+        assert_eq!(
+            "5:60".parse::<TurboKey>().unwrap_err().to_string(),
+            "Invalid turbo key `5:60`, expected a hex key and from 1 to 30 presses a second, e.g. `5:10`!"
+        );
+    }
+
+    #[test]
+    fn presses_at_the_rate() {
+        let mut turbo = Turbo::new();
+        turbo.add("5:10".parse().unwrap());
+        assert_eq!(pattern(&mut turbo, 5, 3), "...");
+        assert!(turbo.hold(5, true));
+        // Ten presses a second are three frames down, three up
+        assert_eq!(pattern(&mut turbo, 5, 14), "###___###___##");
+        // Holding it again doesn't start over
+        turbo.hold(5, true);
+        assert_eq!(pattern(&mut turbo, 5, 4), "#___");
+        turbo.hold(5, false);
+        assert_eq!(pattern(&mut turbo, 5, 2), "..");
+
+        turbo.add("5:30".parse().unwrap());
+        turbo.hold(5, true);
+        assert_eq!(pattern(&mut turbo, 5, 6), "#_#_#_");
+        turbo.add("5:4".parse().unwrap());
+        assert_eq!(
+            pattern(&mut turbo, 5, 16),
+            ["#".repeat(2), "_".repeat(7), "#".repeat(7)].concat()
+        );
+        assert_eq!(turbo.keys().len(), 1);
+    }
+
+    #[test]
+    fn uneven_rates_keep_the_average() {
+        let mut turbo = Turbo::new();
+        turbo.add("3:7".parse().unwrap());
+        turbo.hold(3, true);
+        let second = pattern(&mut turbo, 3, 60);
+        assert_eq!(second.matches("_#").count() + 1, 7, "{second}");
+        assert_eq!(second.matches('#').count(), 30);
+    }
+
+    #[test]
+    fn several_keys() {
+        let mut turbo = Turbo::new();
+        turbo.add("1:30".parse().unwrap());
+        turbo.add("2:15".parse().unwrap());
+        assert!(!turbo.hold(3, true));
+        assert_eq!(turbo.is_down(3), None);
+        turbo.hold(1, true);
+        // Only the keys going down or up are pressed or released
+        assert_eq!(turbo.frame(), [(1, true)]);
+        turbo.hold(2, true);
+        assert_eq!(turbo.frame(), [(1, false), (2, true)]);
+        assert_eq!(turbo.frame(), [(1, true)]);
+        assert_eq!(turbo.frame(), [(1, false), (2, false)]);
+        turbo.release();
+        assert_eq!(turbo.frame(), []);
+        assert_eq!(turbo.is_down(1), None);
+    }
+}
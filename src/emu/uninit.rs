@@ -0,0 +1,404 @@
+use std::fmt;
+use std::ops::Range;
+
+use super::cpu::Instruction;
+use super::memory::{Ram, Registers, RAM_SIZE};
+use super::quirks::Quirks;
+
+///
+/// How many words of 64 bits the bitmaps of the RAM take.
+///
+const RAM_WORDS: usize = RAM_SIZE / 64;
+
+///
+/// The `Accesses` struct represents what an instruction reads and writes: V registers, bit `n`
+/// standing for `Vn`, and the bytes of RAM it goes through `I` for.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Accesses {
+    pub reads: u16,
+    pub writes: u16,
+    pub ram_reads: Range<usize>,
+    pub ram_writes: Range<usize>,
+}
+
+///
+/// Returns the bits of the V registers from `V0` to `Vx`.
+///
+const fn up_to(x: usize) -> u16 {
+    u16::MAX >> (15 - x)
+}
+
+///
+/// Returns the bytes from `I` on, cut off at the end of the RAM.
+///
+fn from_i(registers: &Registers, length: usize) -> Range<usize> {
+    let start = usize::from(registers.i).min(RAM_SIZE);
+    start..(start + length).min(RAM_SIZE)
+}
+
+///
+/// Returns what the instruction reads and writes when it runs with `registers` and `quirks`.
+///
+/// A `DRW` only reads the `n` bytes of its sprite, the sprites of MegaChip's mega mode left out.
+///
+pub fn accesses(
+    instruction: Instruction,
+    opcode: u16,
+    registers: &Registers,
+    quirks: Quirks,
+) -> Accesses {
+    let x = usize::from((opcode & 0x0F00) >> 8);
+    let y = usize::from((opcode & 0x00F0) >> 4);
+    let (vx, vy, vf) = (1 << x, 1 << y, 1 << 0xF);
+    let none = Accesses::default();
+    match instruction {
+        Instruction::SE | Instruction::SNE | Instruction::SKP | Instruction::SKNP => {
+            Accesses { reads: vx, ..none }
+        }
+        Instruction::SKP2
+        | Instruction::SKNP2
+        | Instruction::LDDTR
+        | Instruction::LDRST
+        | Instruction::ADDRI
+        | Instruction::LDSR
+        | Instruction::OUT => Accesses { reads: vx, ..none },
+        Instruction::SER | Instruction::SNER => Accesses {
+            reads: vx | vy,
+            ..none
+        },
+        Instruction::LD
+        | Instruction::RND
+        | Instruction::LDRDT
+        | Instruction::LDK
+        | Instruction::IN => Accesses { writes: vx, ..none },
+        Instruction::ADD => Accesses {
+            reads: vx,
+            writes: vx,
+            ..none
+        },
+        Instruction::LDR => Accesses {
+            reads: vy,
+            writes: vx,
+            ..none
+        },
+        Instruction::ADDR
+        | Instruction::SUB
+        | Instruction::SUBN
+        | Instruction::AND
+        | Instruction::OR
+        | Instruction::XOR => Accesses {
+            reads: vx | vy,
+            writes: vx | vf,
+            ..none
+        },
+        Instruction::SHR | Instruction::SHL => Accesses {
+            reads: if quirks.shift { vx } else { vy },
+            writes: vx | vf,
+            ..none
+        },
+        Instruction::JMPV0 => Accesses {
+            reads: if quirks.jump { vx } else { 1 },
+            ..none
+        },
+        Instruction::DRW => Accesses {
+            reads: vx | vy,
+            writes: vf,
+            ram_reads: from_i(registers, usize::from(opcode & 0x000F)),
+            ..none
+        },
+        Instruction::LDB => Accesses {
+            reads: vx,
+            ram_writes: from_i(registers, 3),
+            ..none
+        },
+        Instruction::LDRIR => Accesses {
+            reads: up_to(x),
+            ram_writes: from_i(registers, x + 1),
+            ..none
+        },
+        Instruction::LDRRI => Accesses {
+            writes: up_to(x),
+            ram_reads: from_i(registers, x + 1),
+            ..none
+        },
+        Instruction::LDPAL => Accesses {
+            ram_reads: from_i(registers, 4 * usize::from(opcode & 0x00FF)),
+            ..none
+        },
+        Instruction::COLZONE | Instruction::COLROWS => Accesses {
+            reads: vx | 1 << ((x + 1) & 0xF) | vy,
+            ..none
+        },
+        Instruction::CLS
+        | Instruction::RET
+        | Instruction::JMP
+        | Instruction::CALL
+        | Instruction::LDRI
+        | Instruction::MEGAOFF
+        | Instruction::MEGAON
+        | Instruction::LDHI
+        | Instruction::SPRW
+        | Instruction::SPRH
+        | Instruction::ALPHA
+        | Instruction::DIGISND
+        | Instruction::STOPSND
+        | Instruction::BMODE
+        | Instruction::CCOL
+        | Instruction::BGCOL => none,
+    }
+}
+
+///
+/// The `Location` enum represents where an instruction read from.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Register(u8),
+    Ram(u16),
+}
+
+///
+/// The `UninitRead` struct represents an instruction reading a location nothing wrote since the
+/// reset, and the value that happened to be there.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitRead {
+    pub pc: u16,
+    pub location: Location,
+    pub value: u8,
+}
+
+impl fmt::Display for UninitRead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:04X}: read ", self.pc)?;
+        match self.location {
+            Location::Register(x) => write!(f, "V{x:X}")?,
+            Location::Ram(address) => write!(f, "0x{address:04X}")?,
+        }
+        write!(f, " before anything wrote it, it held 0x{:02X}", self.value)
+    }
+}
+
+///
+/// The `Bitmap` struct represents a bit for every byte of the RAM.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bitmap([u64; RAM_WORDS]);
+
+impl Bitmap {
+    const fn get(&self, address: usize) -> bool {
+        self.0[address / 64] & (1 << (address % 64)) != 0
+    }
+
+    fn set(&mut self, address: usize) {
+        self.0[address / 64] |= 1 << (address % 64);
+    }
+}
+
+///
+/// The `UninitDetector` struct represents which V registers and bytes of RAM were written since
+/// the reset, and which of the ones that weren't were already reported as read.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UninitDetector {
+    registers: u16,
+    ram: Bitmap,
+    reported_registers: u16,
+    reported_ram: Bitmap,
+    /// How many reads were reported since the detector was made, every reset included
+    reported: usize,
+}
+
+impl UninitDetector {
+    ///
+    /// Returns a detector that counts the bytes below `loaded_end`, the font and the ROM, as
+    /// written, and nothing else.
+    ///
+    pub fn new(loaded_end: usize) -> Self {
+        let mut detector = Self {
+            registers: 0,
+            ram: Bitmap([0; RAM_WORDS]),
+            reported_registers: 0,
+            reported_ram: Bitmap([0; RAM_WORDS]),
+            reported: 0,
+        };
+        detector.reset(loaded_end);
+        detector
+    }
+
+    ///
+    /// Starts over with only the bytes below `loaded_end` written, the reads reported so far
+    /// reported again.
+    ///
+    pub fn reset(&mut self, loaded_end: usize) {
+        self.registers = 0;
+        self.reported_registers = 0;
+        self.ram = Bitmap([0; RAM_WORDS]);
+        self.reported_ram = Bitmap([0; RAM_WORDS]);
+        for address in 0..loaded_end.min(RAM_SIZE) {
+            self.ram.set(address);
+        }
+    }
+
+    ///
+    /// Checks the instruction at `pc` reading only what was written, about to run on `registers`
+    /// and `ram`, and then notes what it writes. Returns the first read of every location that
+    /// wasn't.
+    ///
+    pub fn check(
+        &mut self,
+        pc: u16,
+        accesses: &Accesses,
+        registers: &Registers,
+        ram: &Ram,
+    ) -> Vec<UninitRead> {
+        let mut reads = Vec::new();
+        let unwritten = accesses.reads & !self.registers & !self.reported_registers;
+        for x in (0..16u8).filter(|&x| unwritten & (1 << x) != 0) {
+            reads.push(UninitRead {
+                pc,
+                location: Location::Register(x),
+                value: registers.v[usize::from(x)],
+            });
+        }
+        self.reported_registers |= unwritten;
+        for address in accesses.ram_reads.clone() {
+            if self.ram.get(address) || self.reported_ram.get(address) {
+                continue;
+            }
+            self.reported_ram.set(address);
+            reads.push(UninitRead {
+                pc,
+                location: Location::Ram(address as u16),
+                value: ram.read_byte(address).unwrap_or_default(),
+            });
+        }
+
+        self.reported += reads.len();
+        self.registers |= accesses.writes;
+        for address in accesses.ram_writes.clone() {
+            self.ram.set(address);
+        }
+        reads
+    }
+
+    pub const fn reported(&self) -> usize {
+        self.reported
+    }
+}
+
+#[cfg(test)]
+mod uninit_tests {
+    use super::{accesses, Accesses, Location, UninitDetector, UninitRead};
+    use crate::emu::cpu::{Cpu, Instruction, SequenceRng};
+    use crate::emu::io::BufferScreen;
+    use crate::emu::quirks::Quirks;
+
+    ///
+    /// Runs the ROM's first `steps` instructions, returning the reads of what nothing wrote.
+    ///
+    fn run(rom: &[u8], steps: usize) -> Vec<UninitRead> {
+        let quirks = Quirks {
+            memory: false,
+            ..Quirks::default()
+        };
+        let mut cpu = Cpu::new(quirks, 0x200);
+        cpu.load_rom(rom).unwrap();
+        let mut detector = UninitDetector::new(0x200 + rom.len());
+        let mut screen = BufferScreen::new();
+        let mut rng = SequenceRng::new([]);
+        let mut reads = Vec::new();
+        for _ in 0..steps {
+            let pc = cpu.registers().pc;
+            let opcode = cpu.fetch().unwrap();
+            let instruction = cpu.decode_at_pc(opcode).unwrap();
+            let accesses = accesses(instruction, opcode, cpu.registers(), cpu.quirks());
+            reads.extend(detector.check(pc, &accesses, cpu.registers(), cpu.ram()));
+            cpu.execute(instruction, opcode, &mut screen, &mut rng)
+                .unwrap();
+        }
+        reads
+    }
+
+    #[test]
+    fn reports_the_first_read_of_a_register_never_written() {
+        // LD V1, 5; ADD V1, V3; ADD V1, V3; LD V3, 1; ADD V1, V3
+        let rom = [0x61, 0x05, 0x81, 0x34, 0x81, 0x34, 0x63, 0x01, 0x81, 0x34];
+        let reads = run(&rom, 5);
+        assert_eq!(
+            reads,
+            [UninitRead {
+                pc: 0x202,
+                location: Location::Register(3),
+                value: 0,
+            }]
+        );
+        assert_eq!(
+            reads[0].to_string(),
+            "0x0202: read V3 before anything wrote it, it held 0x00"
+        );
+    }
+
+    #[test]
+    fn the_rom_and_what_it_wrote_are_written() {
+        // LD I, 0x20C; LD V1, [I]; LD I, 0x300; LD [I], V0; LD V0, [I]; LD V1, [I]; DATA 2 bytes
+        let rom = [
+            0xA2, 0x0C, 0xF1, 0x65, 0xA3, 0x00, 0xF0, 0x55, 0xF0, 0x65, 0xF1, 0x65, 0x12, 0x34,
+        ];
+        let reads = run(&rom, 6);
+        // Only the byte past the one `LD [I], V0` wrote, once
+        assert_eq!(
+            reads.iter().map(|read| read.location).collect::<Vec<_>>(),
+            [Location::Ram(0x301)]
+        );
+        assert_eq!(reads[0].pc, 0x20A);
+
+        let mut detector = UninitDetector::new(0x200);
+        let accesses = Accesses {
+            ram_reads: 0x200..0x202,
+            ..Accesses::default()
+        };
+        let cpu = Cpu::new(Quirks::default(), 0x200);
+        assert_eq!(
+            detector
+                .check(0x200, &accesses, cpu.registers(), cpu.ram())
+                .len(),
+            2
+        );
+        // Reported again after a reset
+        detector.reset(0x201);
+        assert_eq!(
+            detector
+                .check(0x200, &accesses, cpu.registers(), cpu.ram())
+                .len(),
+            1
+        );
+        assert_eq!(detector.reported(), 3);
+    }
+
+    #[test]
+    fn follows_the_quirks() {
+        let cpu = Cpu::new(Quirks::default(), 0x200);
+        // SHR V1, V2 shifts V1 in place by default, V2 without the quirk
+        let shift = |quirks| accesses(Instruction::SHR, 0x8126, cpu.registers(), quirks).reads;
+        assert_eq!(shift(Quirks::default()), 1 << 1);
+        assert_eq!(
+            shift(Quirks {
+                shift: false,
+                ..Quirks::default()
+            }),
+            1 << 2
+        );
+        assert_eq!(
+            accesses(
+                Instruction::LDRIR,
+                0xF255,
+                cpu.registers(),
+                Quirks::default()
+            )
+            .reads,
+            0b111
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+use super::chip8::{Chip8, Chip8Error};
+use super::disasm;
+use super::json::Json;
+use super::memory::Registers;
+use super::trace::{TraceRecord, Tracer};
+
+///
+/// The `RECENT_INSTRUCTIONS` value is how many of the last executed instructions a divergence
+/// shows, the diverging one included.
+///
+const RECENT_INSTRUCTIONS: usize = 5;
+
+///
+/// The `Expected` struct represents one record of a reference trace: the instruction it executed
+/// and the state it left, as far as the record tells.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Expected {
+    pub pc: u16,
+    pub opcode: Option<u16>,
+    pub v: Option<[u8; 16]>,
+    pub i: Option<u16>,
+}
+
+///
+/// The `ReferenceError` struct represents a line of a reference trace that can't be used.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceError {
+    pub line: usize,
+    pub reason: String,
+}
+
+impl Error for ReferenceError {}
+
+impl fmt::Display for ReferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Line {} of the reference trace is invalid: {}!",
+            self.line, self.reason
+        )
+    }
+}
+
+///
+/// Parses a reference trace in JSON Lines, one record per executed instruction.
+///
+/// The trace is either one written by `--trace-format json`, with either `--trace-registers`, or
+/// one converted from another emulator, every line holding at least the `pc`, and optionally the
+/// `opcode` and the registers after it as `v` and `i`.
+///
+/// ```
+/// use chip8_emu::emu::verify::parse_reference;
+///
+/// let reference = parse_reference(
+///     "{\"pc\":512,\"opcode\":24581,\"changes\":{\"v0\":[0,5]}}\n{\"pc\":514}\n",
+/// )
+/// .unwrap();
+/// assert_eq!(reference.len(), 2);
+/// assert_eq!(reference[0].v.unwrap()[0], 5);
+/// ```
+///
+pub fn parse_reference(text: &str) -> Result<Vec<Expected>, ReferenceError> {
+    // A trace of the changes only is replayed over the blank registers it started from
+    let mut v = [0; 16];
+    let mut reference = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let error = |reason: String| ReferenceError {
+            line: n + 1,
+            reason,
+        };
+        let record = Json::parse(line).map_err(|reason| error(reason.to_string()))?;
+        let expected = expected(&record, &mut v).map_err(error)?;
+        reference.push(expected);
+    }
+    Ok(reference)
+}
+
+fn expected(record: &Json, v: &mut [u8; 16]) -> Result<Expected, String> {
+    let field = |json: &Json, name: &str| -> Result<Option<u16>, String> {
+        json.get(name)
+            .map(|value| {
+                value
+                    .as_number()
+                    .and_then(|number| u16::try_from(number).ok())
+                    .ok_or_else(|| format!("`{name}` isn't a 16-bit number"))
+            })
+            .transpose()
+    };
+
+    let pc = field(record, "pc")?.ok_or("there's no `pc`")?;
+    let opcode = field(record, "opcode")?;
+    let after = record.get("after").unwrap_or(record);
+    let mut i = field(after, "i")?;
+    let full = after.get("v");
+    if let Some(values) = full {
+        let values = values.as_array().ok_or("`v` isn't an array")?;
+        if values.len() != v.len() {
+            return Err("`v` doesn't hold 16 registers".to_string());
+        }
+        for (register, value) in v.iter_mut().zip(values) {
+            *register = value
+                .as_number()
+                .and_then(|number| u8::try_from(number).ok())
+                .ok_or("`v` holds a value that isn't a byte")?;
+        }
+    }
+    let changes = record.get("changes");
+    if let Some(changes) = changes {
+        let changes = changes.as_object().ok_or("`changes` isn't an object")?;
+        for (name, change) in changes {
+            let new = match change.as_array() {
+                Some([_, new]) => new.as_number(),
+                _ => None,
+            }
+            .ok_or_else(|| format!("the change of `{name}` isn't an [old, new] pair"))?;
+            let register = name
+                .strip_prefix('v')
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .filter(|&x| x < 16);
+            match (register, name.as_str()) {
+                (Some(x), _) => {
+                    v[usize::from(x)] = u8::try_from(new)
+                        .map_err(|_| format!("the new value of `{name}` isn't a byte"))?;
+                }
+                (None, "i") if i.is_none() => i = u16::try_from(new).ok(),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(Expected {
+        pc,
+        opcode,
+        v: (full.is_some() || changes.is_some()).then_some(*v),
+        i,
+    })
+}
+
+///
+/// The `Divergence` struct represents the first instruction at which the emulator and the
+/// reference disagree.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// How many instructions matched before this one
+    pub index: usize,
+    /// What disagrees: `pc`, `opcode`, `i` or a general register like `v3`
+    pub field: String,
+    pub expected: u16,
+    pub actual: u16,
+    /// The last few instructions executed, as `(pc, opcode)`, the diverging one last
+    pub recent: Vec<(u16, u16)>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Instruction {}: {} is 0x{:02X}, expected 0x{:02X}",
+            self.index, self.field, self.actual, self.expected
+        )?;
+        for (n, &(pc, opcode)) in self.recent.iter().enumerate() {
+            let marker = if n + 1 == self.recent.len() {
+                "=>"
+            } else {
+                "  "
+            };
+            let newline = if n + 1 == self.recent.len() { "" } else { "\n" };
+            write!(
+                f,
+                "{marker} 0x{pc:04X}: {opcode:04X}  {}{newline}",
+                disasm::disassemble(pc, opcode)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// The `Verdict` enum represents how a run compared to the reference trace.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Every one of these instructions matched
+    Match(usize),
+    Diverged(Divergence),
+}
+
+///
+/// The `Executed` struct represents an instruction the emulator executed, as the verifier saw it.
+///
+struct Executed {
+    pc: u16,
+    opcode: u16,
+    after: Registers,
+}
+
+///
+/// The `Recorder` struct represents a tracer keeping the executed instructions for the verifier,
+/// which checks them after every frame.
+///
+struct Recorder(Rc<RefCell<Vec<Executed>>>);
+
+impl Tracer for Recorder {
+    fn trace(&mut self, record: &TraceRecord<'_>) {
+        self.0.borrow_mut().push(Executed {
+            pc: record.pc,
+            opcode: record.opcode,
+            after: *record.after,
+        });
+    }
+}
+
+///
+/// Runs the loaded ROM frame by frame, as `run` does, until it executed as many instructions as
+/// the reference holds, stopping at the first one that disagrees with its record.
+///
+/// The run is only reproducible with a seed, and with the speed the reference was recorded at,
+/// since both decide when the timers tick and what `RND` draws.
+///
+pub fn verify(chip8: &mut Chip8, reference: &[Expected]) -> Result<Verdict, Chip8Error> {
+    let executed = Rc::new(RefCell::new(Vec::new()));
+    chip8.set_tracer(Recorder(Rc::clone(&executed)));
+    let verdict = compare(chip8, reference, &executed);
+    chip8.clear_tracer();
+    verdict
+}
+
+fn compare(
+    chip8: &mut Chip8,
+    reference: &[Expected],
+    executed: &RefCell<Vec<Executed>>,
+) -> Result<Verdict, Chip8Error> {
+    let mut recent = VecDeque::with_capacity(RECENT_INSTRUCTIONS);
+    let mut expected = reference.iter().enumerate();
+    loop {
+        chip8.run_frame()?;
+        for actual in executed.borrow_mut().drain(..) {
+            let Some((index, record)) = expected.next() else {
+                return Ok(Verdict::Match(reference.len()));
+            };
+            if recent.len() == RECENT_INSTRUCTIONS {
+                recent.pop_front();
+            }
+            recent.push_back((actual.pc, actual.opcode));
+
+            if let Some((field, expected, actual)) = mismatch(record, &actual) {
+                return Ok(Verdict::Diverged(Divergence {
+                    index,
+                    field,
+                    expected,
+                    actual,
+                    recent: recent.into(),
+                }));
+            }
+        }
+        if executed.borrow().is_empty() && expected.len() == 0 {
+            return Ok(Verdict::Match(reference.len()));
+        }
+    }
+}
+
+///
+/// Returns the first field of the record the instruction disagrees with, as
+/// `(field, expected, actual)`.
+///
+fn mismatch(record: &Expected, actual: &Executed) -> Option<(String, u16, u16)> {
+    if record.pc != actual.pc {
+        return Some(("pc".to_string(), record.pc, actual.pc));
+    }
+    if let Some(opcode) = record.opcode.filter(|&opcode| opcode != actual.opcode) {
+        return Some(("opcode".to_string(), opcode, actual.opcode));
+    }
+    if let Some(v) = record.v {
+        let diverging = v.iter().zip(&actual.after.v).position(|(a, b)| a != b);
+        if let Some(x) = diverging {
+            let name = format!("v{x:X}");
+            return Some((name, v[x].into(), actual.after.v[x].into()));
+        }
+    }
+    record
+        .i
+        .filter(|&i| i != actual.after.i)
+        .map(|i| ("i".to_string(), i, actual.after.i))
+}
+
+#[cfg(test)]
+mod verify_tests {
+    use super::{parse_reference, verify, Expected, Verdict};
+    use crate::emu::chip8::Chip8;
+
+    // LD V0, 0x05; LD V1, 0x07; ADD V0, V1; LD I, 0x300; JMP 0x208
+    const ROM: [u8; 10] = [0x60, 0x05, 0x61, 0x07, 0x80, 0x14, 0xA3, 0x00, 0x12, 0x08];
+
+    const REFERENCE: &str = "\
+{\"cycle\":0,\"pc\":512,\"opcode\":24581,\"changes\":{\"v0\":[0,5]}}
+{\"cycle\":1,\"pc\":514,\"opcode\":24839,\"changes\":{\"v1\":[0,7]}}
+{\"cycle\":2,\"pc\":516,\"opcode\":32788,\"changes\":{\"v0\":[5,12]}}
+{\"cycle\":3,\"pc\":518,\"opcode\":41728,\"i\":768,\"changes\":{\"i\":[0,768]}}
+{\"cycle\":4,\"pc\":520,\"opcode\":4616,\"changes\":{}}
+";
+
+    fn chip8() -> Chip8 {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&ROM).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn parses_references() {
+        let full = "{\"pc\":512,\"after\":{\"v\":[5,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1],\"i\":768}}";
+        let mut v = [0; 16];
+        v[0] = 5;
+        v[0xF] = 1;
+        assert_eq!(
+            parse_reference(full),
+            Ok(vec![Expected {
+                pc: 0x200,
+                opcode: None,
+                v: Some(v),
+                i: Some(0x300),
+            }])
+        );
+
+        let reference = parse_reference(REFERENCE).unwrap();
+        assert_eq!(reference.len(), 5);
+        assert_eq!(reference[2].v.unwrap()[..2], [12, 7]);
+        assert_eq!(reference[3].i, Some(0x300));
+
+        let err = parse_reference("{\"pc\":512}\n\n{\"opcode\":4616}").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(
+            err.to_string(),
+            "Line 3 of the reference trace is invalid: there's no `pc`!"
+        );
+    }
+
+    #[test]
+    fn matching_traces() {
+        let reference = parse_reference(REFERENCE).unwrap();
+        assert_eq!(verify(&mut chip8(), &reference).unwrap(), Verdict::Match(5));
+        // A reference longer than a frame is checked over several of them
+        let looping = [REFERENCE, &"{\"pc\":520,\"opcode\":4616}\n".repeat(20)].concat();
+        let reference = parse_reference(&looping).unwrap();
+        assert_eq!(
+            verify(&mut chip8(), &reference).unwrap(),
+            Verdict::Match(25)
+        );
+    }
+
+    #[test]
+    fn corrupted_traces_diverge() {
+        let corrupted = REFERENCE.replace("\"v0\":[5,12]", "\"v0\":[5,13]");
+        let reference = parse_reference(&corrupted).unwrap();
+        let Ok(Verdict::Diverged(divergence)) = verify(&mut chip8(), &reference) else {
+            panic!("the corrupted trace matched");
+        };
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.field, "v0");
+        assert_eq!((divergence.expected, divergence.actual), (13, 12));
+        assert_eq!(
+            divergence.to_string(),
+            "Instruction 2: v0 is 0x0C, expected 0x0D\n   \
+             0x0200: 6005  LD V0, #0x05\n   \
+             0x0202: 6107  LD V1, #0x07\n\
+             => 0x0204: 8014  ADD V0, V1"
+        );
+
+        let skipped = REFERENCE.replace("\"pc\":518", "\"pc\":520");
+        let reference = parse_reference(&skipped).unwrap();
+        let Ok(Verdict::Diverged(divergence)) = verify(&mut chip8(), &reference) else {
+            panic!("the corrupted trace matched");
+        };
+        assert_eq!(divergence.index, 3);
+        assert_eq!(divergence.field, "pc");
+        assert_eq!((divergence.expected, divergence.actual), (0x208, 0x206));
+    }
+}
@@ -0,0 +1,313 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::emu::chip8::Chip8;
+use crate::emu::io::{BufferScreen, GRID_HEIGHT, GRID_WIDTH};
+
+/// The call succeeded.
+pub const CHIP8_OK: i32 = 0;
+/// A pointer argument was null.
+pub const CHIP8_ERR_NULL: i32 = -1;
+/// A length or register index was out of range.
+pub const CHIP8_ERR_INVALID_ARGUMENT: i32 = -2;
+/// The ROM is empty or doesn't fit in memory.
+pub const CHIP8_ERR_INVALID_ROM: i32 = -3;
+/// The emulator hit an instruction it can't decode or an out-of-bounds memory access.
+pub const CHIP8_ERR_EXECUTION: i32 = -4;
+/// The emulator panicked, its state shouldn't be trusted anymore.
+pub const CHIP8_ERR_PANIC: i32 = -5;
+
+/// `chip8_get_register` index of `I`, after `V0` to `VF`.
+pub const CHIP8_REG_I: u8 = 16;
+/// `chip8_get_register` index of the program counter.
+pub const CHIP8_REG_PC: u8 = 17;
+/// `chip8_get_register` index of the delay timer.
+pub const CHIP8_REG_DT: u8 = 18;
+/// `chip8_get_register` index of the sound timer.
+pub const CHIP8_REG_ST: u8 = 19;
+
+///
+/// Runs `f`, turning a panic into `CHIP8_ERR_PANIC` so it never unwinds into C.
+///
+fn guard(f: impl FnOnce() -> i32) -> i32 {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(CHIP8_ERR_PANIC)
+}
+
+///
+/// Creates a headless emulator. Returns null if it couldn't be created.
+///
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    panic::catch_unwind(|| {
+        Chip8::builder()
+            .screen(Box::new(BufferScreen::new()))
+            .build()
+            .map_or(ptr::null_mut(), |chip8| Box::into_raw(Box::new(chip8)))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+///
+/// Destroys an emulator created by `chip8_new`. Null is ignored.
+///
+/// # Safety
+///
+/// `chip8` must be null or come from `chip8_new`, and mustn't be used afterwards.
+///
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        // A panicking destructor can't be reported, but it mustn't unwind into C either
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(chip8))));
+    }
+}
+
+///
+/// Loads the `len` bytes at `rom` and restarts the machine with them.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`, and `rom` must point to `len` readable bytes.
+///
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, len: usize) -> i32 {
+    let (Some(chip8), false) = (chip8.as_mut(), rom.is_null()) else {
+        return CHIP8_ERR_NULL;
+    };
+    if len == 0 {
+        return CHIP8_ERR_INVALID_ROM;
+    }
+
+    let rom = std::slice::from_raw_parts(rom, len);
+    guard(|| match chip8.load_rom_bytes(rom) {
+        Ok(()) => CHIP8_OK,
+        Err(_) => CHIP8_ERR_INVALID_ROM,
+    })
+}
+
+///
+/// Executes exactly one instruction.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8) -> i32 {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERR_NULL;
+    };
+
+    guard(|| match chip8.step() {
+        Ok(_) => CHIP8_OK,
+        Err(_) => CHIP8_ERR_EXECUTION,
+    })
+}
+
+///
+/// Runs one 60 Hz frame with the hex keys set in `keys` (bit `n` for key `n`) held down.
+/// Returns `1` while the buzzer sounds, `0` otherwise, or a negative error code.
+///
+/// The keypad only registers one key at a time, the lowest one held wins.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn chip8_frame(chip8: *mut Chip8, keys: u16) -> i32 {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERR_NULL;
+    };
+
+    guard(|| {
+        chip8.set_keys(keys);
+        chip8
+            .run_frame()
+            .map_or(CHIP8_ERR_EXECUTION, |summary| i32::from(summary.sound_on))
+    })
+}
+
+///
+/// Copies the 64×32 framebuffer, one byte (0 or 1) per pixel row after row, into `out`, which
+/// must have room for at least `len` >= 2048 bytes.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`, and `out` must point to `len` writable bytes.
+///
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Chip8, out: *mut u8, len: usize) -> i32 {
+    let (Some(chip8), false) = (chip8.as_ref(), out.is_null()) else {
+        return CHIP8_ERR_NULL;
+    };
+    if len < GRID_WIDTH * GRID_HEIGHT {
+        return CHIP8_ERR_INVALID_ARGUMENT;
+    }
+
+    guard(|| {
+        let framebuffer = chip8.framebuffer();
+        ptr::copy_nonoverlapping(framebuffer.as_ptr(), out, framebuffer.len());
+        CHIP8_OK
+    })
+}
+
+///
+/// Stores the value of a register into `out`: `V0` to `VF` for indices 0 to 15, then
+/// `CHIP8_REG_I`, `CHIP8_REG_PC`, `CHIP8_REG_DT` and `CHIP8_REG_ST`.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`, and `out` must point to a writable `uint16_t`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_register(chip8: *const Chip8, index: u8, out: *mut u16) -> i32 {
+    let (Some(chip8), Some(out)) = (chip8.as_ref(), out.as_mut()) else {
+        return CHIP8_ERR_NULL;
+    };
+
+    *out = match index {
+        0..=15 => u16::from(chip8.v(index)),
+        CHIP8_REG_I => chip8.i(),
+        CHIP8_REG_PC => chip8.pc(),
+        CHIP8_REG_DT => u16::from(chip8.delay_timer()),
+        CHIP8_REG_ST => u16::from(chip8.sound_timer()),
+        _ => return CHIP8_ERR_INVALID_ARGUMENT,
+    };
+    CHIP8_OK
+}
+
+#[cfg(test)]
+mod ffi_tests {
+    use std::ptr;
+
+    use super::{
+        chip8_frame, chip8_framebuffer, chip8_free, chip8_get_register, chip8_load_rom, chip8_new,
+        chip8_step, CHIP8_ERR_EXECUTION, CHIP8_ERR_INVALID_ARGUMENT, CHIP8_ERR_INVALID_ROM,
+        CHIP8_ERR_NULL, CHIP8_OK, CHIP8_REG_I, CHIP8_REG_PC, CHIP8_REG_ST,
+    };
+    use crate::emu::io::{GRID_HEIGHT, GRID_WIDTH};
+
+    #[test]
+    fn steps_and_reads_registers() {
+        // LD V3, 0x2A; LD I, 0x123
+        let rom = [0x63, 0x2A, 0xA1, 0x23];
+        unsafe {
+            let chip8 = chip8_new();
+            assert!(!chip8.is_null());
+            assert_eq!(chip8_load_rom(chip8, rom.as_ptr(), rom.len()), CHIP8_OK);
+            assert_eq!(chip8_step(chip8), CHIP8_OK);
+            assert_eq!(chip8_step(chip8), CHIP8_OK);
+
+            let mut value = 0;
+            assert_eq!(chip8_get_register(chip8, 3, &raw mut value), CHIP8_OK);
+            assert_eq!(value, 0x2A);
+            assert_eq!(
+                chip8_get_register(chip8, CHIP8_REG_I, &raw mut value),
+                CHIP8_OK
+            );
+            assert_eq!(value, 0x123);
+            assert_eq!(
+                chip8_get_register(chip8, CHIP8_REG_PC, &raw mut value),
+                CHIP8_OK
+            );
+            assert_eq!(value, 0x204);
+            assert_eq!(
+                chip8_get_register(chip8, 20, &raw mut value),
+                CHIP8_ERR_INVALID_ARGUMENT
+            );
+
+            // Past the ROM, the zeroed memory doesn't decode
+            assert_eq!(chip8_step(chip8), CHIP8_ERR_EXECUTION);
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn frames_report_the_sound_and_take_keys() {
+        // LD V0, K; LD ST, V0; JMP 0x204
+        let rom = [0xF0, 0x0A, 0xF0, 0x18, 0x12, 0x04];
+        unsafe {
+            let chip8 = chip8_new();
+            assert_eq!(chip8_load_rom(chip8, rom.as_ptr(), rom.len()), CHIP8_OK);
+            assert_eq!(chip8_frame(chip8, 0), 0);
+            assert_eq!(chip8_frame(chip8, 1 << 0x9 | 1 << 0xC), 1);
+
+            let mut value = 0;
+            assert_eq!(chip8_get_register(chip8, 0, &raw mut value), CHIP8_OK);
+            assert_eq!(value, 0x9);
+            assert_eq!(
+                chip8_get_register(chip8, CHIP8_REG_ST, &raw mut value),
+                CHIP8_OK
+            );
+            assert_eq!(value, 0x8);
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn copies_the_framebuffer() {
+        // LD I, 0x000 (font sprite of 0); DRW V0, V0, 5
+        let rom = [0xA0, 0x00, 0xD0, 0x05];
+        let mut framebuffer = vec![0xFF; GRID_WIDTH * GRID_HEIGHT];
+        unsafe {
+            let chip8 = chip8_new();
+            assert_eq!(chip8_load_rom(chip8, rom.as_ptr(), rom.len()), CHIP8_OK);
+            assert_eq!(chip8_step(chip8), CHIP8_OK);
+            assert_eq!(chip8_step(chip8), CHIP8_OK);
+
+            assert_eq!(
+                chip8_framebuffer(chip8, framebuffer.as_mut_ptr(), 100),
+                CHIP8_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                chip8_framebuffer(chip8, framebuffer.as_mut_ptr(), framebuffer.len()),
+                CHIP8_OK
+            );
+            chip8_free(chip8);
+        }
+        assert_eq!(framebuffer[..5], [1, 1, 1, 1, 0]);
+        assert_eq!(framebuffer[GRID_WIDTH], 1);
+        assert_eq!(framebuffer[GRID_WIDTH + 1], 0);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let rom = [0x00, 0xE0];
+        let mut value = 0;
+        unsafe {
+            assert_eq!(
+                chip8_load_rom(ptr::null_mut(), rom.as_ptr(), 2),
+                CHIP8_ERR_NULL
+            );
+            assert_eq!(chip8_step(ptr::null_mut()), CHIP8_ERR_NULL);
+            assert_eq!(chip8_frame(ptr::null_mut(), 0), CHIP8_ERR_NULL);
+            assert_eq!(
+                chip8_framebuffer(ptr::null(), ptr::null_mut(), 0),
+                CHIP8_ERR_NULL
+            );
+            assert_eq!(
+                chip8_get_register(ptr::null(), 0, &raw mut value),
+                CHIP8_ERR_NULL
+            );
+            chip8_free(ptr::null_mut());
+
+            let chip8 = chip8_new();
+            assert_eq!(chip8_load_rom(chip8, ptr::null(), 2), CHIP8_ERR_NULL);
+            assert_eq!(
+                chip8_load_rom(chip8, rom.as_ptr(), 0),
+                CHIP8_ERR_INVALID_ROM
+            );
+            let huge = vec![0; 0x1000];
+            assert_eq!(
+                chip8_load_rom(chip8, huge.as_ptr(), huge.len()),
+                CHIP8_ERR_INVALID_ROM
+            );
+            assert_eq!(
+                chip8_get_register(chip8, 0, ptr::null_mut()),
+                CHIP8_ERR_NULL
+            );
+            chip8_free(chip8);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+#[cfg(feature = "sdl")]
+pub mod sdl;
+#[cfg(feature = "native")]
+pub mod stream;
+#[cfg(feature = "native")]
+pub mod threaded;
+#[cfg(feature = "native")]
+pub mod video;
+
+use std::time::{Duration, Instant};
+
+use crate::emu::chip8x::ColorZones;
+use crate::emu::disasm::ListingLine;
+use crate::emu::mega::ColorPalette;
+use crate::emu::stats::FrameGraph;
+
+///
+/// The `Input` enum represents what the user asked the emulator to do, in the emulator's own terms.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Quit,
+    TogglePause,
+    /// Executes one instruction while paused
+    Step,
+    /// Shows or hides the registers and the speed over the screen
+    ToggleOverlay,
+    /// Shows or hides the disassembly around the PC
+    TogglePane,
+    /// Shows or hides the graph of the last frame times
+    ToggleFrameGraph,
+    /// Shows or hides the debug panels
+    #[cfg(feature = "egui")]
+    ToggleDebugView,
+    /// Shows or hides the controls, pausing while they're shown
+    ToggleHelp,
+    /// Restarts the loaded ROM
+    Reset,
+    /// Sets a breakpoint on the address, or removes the one already there
+    ToggleBreakpoint(u16),
+    /// Moves the memory panel by that many of its rows, negative ones towards address 0
+    #[cfg(feature = "egui")]
+    ScrollMemory(i32),
+    DropRom(String),
+    /// Goes back to the ROM library to pick another ROM
+    Browse,
+    /// Saves the settings the ROM runs with into its sidecar file
+    SaveProfile,
+    Key {
+        hex_key: u8,
+        pressed: bool,
+        /// When the frontend got the key, for measuring the input latency
+        at: Instant,
+    },
+    /// A hex key of CHIP-8X's second keypad
+    SecondKey {
+        hex_key: u8,
+        pressed: bool,
+    },
+    ReleaseKeys,
+}
+
+///
+/// The `MEMORY_COLUMNS` value is how many bytes make a row of the debug memory panel.
+///
+#[cfg(feature = "egui")]
+pub const MEMORY_COLUMNS: usize = 16;
+
+///
+/// The `DebugView` struct represents what the debug panels show, all of it read through the
+/// emulator's getters.
+///
+#[cfg(feature = "egui")]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DebugView {
+    /// The registers, as the status overlay shows them
+    pub registers: Vec<String>,
+    /// The address of the first byte of `memory`
+    pub memory_start: u16,
+    /// The bytes of the memory panel, `MEMORY_COLUMNS` to a row
+    pub memory: Vec<u8>,
+    pub disassembly: Vec<ListingLine>,
+    pub breakpoints: Vec<u16>,
+    pub paused: bool,
+}
+
+///
+/// The `Keypad` trait represents where the user's input comes from.
+///
+pub trait Keypad {
+    ///
+    /// Returns the next pending input, or `None` once there's nothing left to handle for now.
+    ///
+    fn poll_input(&mut self) -> Option<Input>;
+
+    ///
+    /// Waits up to `timeout` for the next input, returning `None` if none came. A keypad that
+    /// can't block returns `None` right away, the run loop then sleeps instead.
+    ///
+    fn wait_input(&mut self, _timeout: Duration) -> Option<Input> {
+        None
+    }
+}
+
+///
+/// The `AudioSink` trait represents where the buzzer sounds.
+///
+pub trait AudioSink {
+    fn set_tone(&mut self, on: bool);
+}
+
+///
+/// The `Frontend` trait represents everything the emulator needs from the outside world: showing
+/// the screen, taking input and sounding the buzzer. `Chip8` drives one picked at startup.
+///
+pub trait Frontend: Keypad + AudioSink {
+    ///
+    /// Shows the whole framebuffer, one byte (0 or 1) per pixel, whenever the screen changed.
+    ///
+    fn present(&mut self, framebuffer: &[u8]);
+
+    ///
+    /// Shows a short description of what's running, e.g. the name of the loaded ROM.
+    ///
+    fn set_title(&mut self, _title: &str) {}
+
+    ///
+    /// Sets the lines of text shown over the screen from the next `present` on, none hiding them.
+    ///
+    fn set_overlay(&mut self, _lines: &[String]) {}
+
+    ///
+    /// Sets the instructions of the disassembly pane from the next `present` on, none hiding it.
+    ///
+    fn set_pane(&mut self, _lines: &[ListingLine]) {}
+
+    ///
+    /// Sets what the debug panels show from the next `present` on, none hiding them.
+    ///
+    #[cfg(feature = "egui")]
+    fn set_debug_view(&mut self, _view: Option<&DebugView>) {}
+
+    ///
+    /// Sets the frame times the frame-time graph plots from the next `present` on, none hiding it.
+    ///
+    fn set_frame_graph(&mut self, _graph: Option<&FrameGraph>) {}
+
+    ///
+    /// Shows or hides the keypad layout and the hotkeys over the screen from the next `present` on.
+    ///
+    fn set_help(&mut self, _shown: bool) {}
+
+    ///
+    /// Sets how many frames ago every pixel last changed, up to `PIXEL_AGE_FRAMES`, for tinting
+    /// the pixels of the next `present` on by it. None shows them as always.
+    ///
+    fn set_pixel_ages(&mut self, _ages: Option<&[u8]>) {}
+
+    ///
+    /// Sets the palette the pixel values of the next `present` on are colored by, a screen of
+    /// MegaChip's mega mode. None shows the pixels as on and off again.
+    ///
+    fn set_palette(&mut self, _palette: Option<&ColorPalette>) {}
+
+    ///
+    /// Sets the CHIP-8X color zones the pixels of the next `present` on are colored by, over its
+    /// background color. None shows the pixels as on and off again.
+    ///
+    fn set_color_zones(&mut self, _zones: Option<&ColorZones>) {}
+
+    ///
+    /// Ends a frame of the run loop. Frontends that don't show every `present` right away, the
+    /// ones waiting for the vertical sync, show the last one here.
+    ///
+    fn end_frame(&mut self) {}
+}
+
+///
+/// The `HeadlessFrontend` struct represents no frontend at all: nothing is shown, heard or typed.
+///
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeadlessFrontend;
+
+impl Keypad for HeadlessFrontend {
+    fn poll_input(&mut self) -> Option<Input> {
+        None
+    }
+}
+
+impl AudioSink for HeadlessFrontend {
+    fn set_tone(&mut self, _on: bool) {}
+}
+
+impl Frontend for HeadlessFrontend {
+    fn present(&mut self, _framebuffer: &[u8]) {}
+}
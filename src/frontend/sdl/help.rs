@@ -0,0 +1,129 @@
+use sdl2::rect::Rect;
+
+use super::keymap::{self, Hotkeys, KeyMap};
+use crate::emu::io::{CHAR_WIDTH, LINE_HEIGHT};
+
+///
+/// The `HELP_MAX_SCALE` constant is how many screen pixels at most make a pixel of the help's font,
+/// on windows big enough for it.
+///
+pub const HELP_MAX_SCALE: u32 = 4;
+
+///
+/// The `HelpLayout` struct represents where the help goes in the window and how big it's drawn.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HelpLayout {
+    /// How many screen pixels make a pixel of the font
+    pub scale: u32,
+    /// The box behind the text
+    pub backdrop: Rect,
+    /// Where the first line's top left corner is
+    pub text: (i32, i32),
+}
+
+///
+/// Lays `columns` × `rows` characters out in the middle of a window of `size`.
+///
+/// They're drawn `margin` pixels inside their box, at the biggest scale they fit at. Either way
+/// they don't fit even at the smallest one, they start from the window's edge and run off the
+/// other.
+///
+pub fn help_layout(columns: u32, rows: u32, size: (u32, u32), margin: u32) -> HelpLayout {
+    let backdrop = |scale| {
+        (
+            columns * CHAR_WIDTH * scale + 2 * margin,
+            rows * LINE_HEIGHT * scale + 2 * margin,
+        )
+    };
+    let scale = (1..=HELP_MAX_SCALE)
+        .rev()
+        .find(|&scale| {
+            let (width, height) = backdrop(scale);
+            width <= size.0 && height <= size.1
+        })
+        .unwrap_or(1);
+    let (width, height) = backdrop(scale);
+    let left = size.0.saturating_sub(width) / 2;
+    let top = size.1.saturating_sub(height) / 2;
+    HelpLayout {
+        scale,
+        backdrop: Rect::new(left.cast_signed(), top.cast_signed(), width, height),
+        text: ((left + margin).cast_signed(), (top + margin).cast_signed()),
+    }
+}
+
+///
+/// Returns the lines of the help: the keypad and the hotkeys as `--list-keys` prints them, so the
+/// two always agree.
+///
+pub fn help_lines(keymap: &KeyMap, hotkeys: &Hotkeys) -> Vec<String> {
+    keymap::key_table(keymap, hotkeys)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod help_tests {
+    use sdl2::keyboard::Keycode;
+    use sdl2::rect::Rect;
+
+    use super::{help_layout, help_lines, HelpLayout, HELP_MAX_SCALE};
+    use crate::frontend::sdl::keymap::{Hotkeys, KeyMap};
+
+    #[test]
+    fn fills_the_window_as_far_as_it_fits() {
+        // 31 columns and 24 rows are 124 × 144 font pixels
+        assert_eq!(
+            help_layout(31, 24, (800, 600), 4),
+            HelpLayout {
+                scale: HELP_MAX_SCALE,
+                backdrop: Rect::new(148, 8, 504, 584),
+                text: (152, 12),
+            }
+        );
+        // Too short for a scale of 4
+        assert_eq!(help_layout(31, 24, (1600, 500), 4).scale, 3);
+        assert_eq!(
+            help_layout(31, 24, (400, 300), 4),
+            HelpLayout {
+                scale: 2,
+                backdrop: Rect::new(72, 2, 256, 296),
+                text: (76, 6),
+            }
+        );
+        // The smallest scale fits exactly
+        assert_eq!(
+            help_layout(31, 24, (132, 152), 4).backdrop,
+            Rect::new(0, 0, 132, 152)
+        );
+    }
+
+    #[test]
+    fn starts_from_the_edge_when_it_cannot_fit() {
+        // Still centered the way it fits
+        assert_eq!(
+            help_layout(31, 24, (160, 120), 4),
+            HelpLayout {
+                scale: 1,
+                backdrop: Rect::new(14, 0, 132, 152),
+                text: (18, 4),
+            }
+        );
+        assert_eq!(help_layout(31, 24, (0, 0), 4).text, (4, 4));
+    }
+
+    #[test]
+    fn lists_the_bindings_in_use() {
+        let mut keymap = KeyMap::default();
+        keymap.bind(0x5, Keycode::Up);
+        let lines = help_lines(&keymap, &Hotkeys::default());
+        assert_eq!(lines[0], "Keypad:");
+        assert!(lines.iter().any(|line| line.contains("5: W/Up")));
+        assert!(lines.iter().any(|line| line.trim() == "Help            F1"));
+        assert!(lines
+            .iter()
+            .any(|line| line.trim() == "Quit            Escape"));
+    }
+}
@@ -0,0 +1,311 @@
+use std::fmt::Write as _;
+
+use sdl2::keyboard::Keycode;
+
+///
+/// The `KEYPAD_LAYOUT` constant is the physical arrangement of the hex keys on the CHIP-8 keypad.
+///
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+///
+/// The `KeyMap` struct represents the physical keys bound to each hex key of the CHIP-8 keypad.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    bindings: [Vec<Keycode>; 16],
+    /// The keys of CHIP-8X's second keypad
+    second: [Vec<Keycode>; 16],
+}
+
+impl Default for KeyMap {
+    ///
+    /// Mirrors the keypad onto the left-hand block of a QWERTY keyboard (`1234`/`QWER`/`ASDF`/`ZXCV`),
+    /// and CHIP-8X's second keypad onto the numpad.
+    ///
+    fn default() -> Self {
+        let rows = [
+            [Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4],
+            [Keycode::Q, Keycode::W, Keycode::E, Keycode::R],
+            [Keycode::A, Keycode::S, Keycode::D, Keycode::F],
+            [Keycode::Z, Keycode::X, Keycode::C, Keycode::V],
+        ];
+        let second_rows = [
+            [Keycode::Kp7, Keycode::Kp8, Keycode::Kp9, Keycode::KpDivide],
+            [
+                Keycode::Kp4,
+                Keycode::Kp5,
+                Keycode::Kp6,
+                Keycode::KpMultiply,
+            ],
+            [Keycode::Kp1, Keycode::Kp2, Keycode::Kp3, Keycode::KpMinus],
+            [
+                Keycode::Kp0,
+                Keycode::KpPeriod,
+                Keycode::KpEnter,
+                Keycode::KpPlus,
+            ],
+        ];
+
+        let mut keymap = Self {
+            bindings: Default::default(),
+            second: Default::default(),
+        };
+        for ((hex_row, key_row), second_row) in KEYPAD_LAYOUT.iter().zip(rows).zip(second_rows) {
+            for ((&hex_key, key), second_key) in hex_row.iter().zip(key_row).zip(second_row) {
+                keymap.bind(hex_key, key);
+                keymap.bind_second(hex_key, second_key);
+            }
+        }
+        keymap
+    }
+}
+
+impl KeyMap {
+    ///
+    /// Binds an additional physical key to the hex key, taking it away from any other hex key.
+    ///
+    pub fn bind(&mut self, hex_key: u8, key: Keycode) {
+        self.unbind(key);
+        self.bindings[usize::from(hex_key & 0xF)].push(key);
+    }
+
+    ///
+    /// Binds an additional physical key to the hex key of CHIP-8X's second keypad, taking it away
+    /// from any other hex key of either keypad.
+    ///
+    pub fn bind_second(&mut self, hex_key: u8, key: Keycode) {
+        self.unbind(key);
+        self.second[usize::from(hex_key & 0xF)].push(key);
+    }
+
+    fn unbind(&mut self, key: Keycode) {
+        for keys in self.bindings.iter_mut().chain(&mut self.second) {
+            keys.retain(|&bound| bound != key);
+        }
+    }
+
+    pub fn keys(&self, hex_key: u8) -> &[Keycode] {
+        &self.bindings[usize::from(hex_key & 0xF)]
+    }
+
+    pub fn hex_key(&self, key: Keycode) -> Option<u8> {
+        self.bindings
+            .iter()
+            .position(|keys| keys.contains(&key))
+            .map(|hex_key| hex_key as u8)
+    }
+
+    pub fn second_keys(&self, hex_key: u8) -> &[Keycode] {
+        &self.second[usize::from(hex_key & 0xF)]
+    }
+
+    pub fn second_hex_key(&self, key: Keycode) -> Option<u8> {
+        self.second
+            .iter()
+            .position(|keys| keys.contains(&key))
+            .map(|hex_key| hex_key as u8)
+    }
+}
+
+///
+/// The `SAVE_PROFILE_KEY` constant is the key that saves the settings the ROM runs with into its
+/// sidecar file, along with Ctrl so it stays free for the keypad.
+///
+pub const SAVE_PROFILE_KEY: Keycode = Keycode::S;
+
+///
+/// The `Hotkeys` struct represents the physical keys bound to the emulator's own controls.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotkeys {
+    pub help: Keycode,
+    pub quit: Keycode,
+    pub pause: Keycode,
+    pub step: Keycode,
+    pub overlay: Keycode,
+    pub pane: Keycode,
+    pub frame_graph: Keycode,
+    #[cfg(feature = "egui")]
+    pub debug_view: Keycode,
+    pub fullscreen: Keycode,
+    pub scale_filter: Keycode,
+    pub browse: Keycode,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            help: Keycode::F1,
+            quit: Keycode::Escape,
+            pause: Keycode::P,
+            step: Keycode::N,
+            overlay: Keycode::F3,
+            pane: Keycode::F4,
+            frame_graph: Keycode::F5,
+            #[cfg(feature = "egui")]
+            debug_view: Keycode::F10,
+            fullscreen: Keycode::F11,
+            scale_filter: Keycode::F9,
+            browse: Keycode::F2,
+        }
+    }
+}
+
+impl Hotkeys {
+    pub fn entries(&self) -> Vec<(&'static str, Keycode)> {
+        vec![
+            ("Help", self.help),
+            ("Quit", self.quit),
+            ("Pause/resume", self.pause),
+            ("Step (paused)", self.step),
+            ("Status overlay", self.overlay),
+            ("Disassembly", self.pane),
+            ("Frame graph", self.frame_graph),
+            #[cfg(feature = "egui")]
+            ("Debug panels", self.debug_view),
+            ("Fullscreen", self.fullscreen),
+            ("Scale filter", self.scale_filter),
+            ("ROM library", self.browse),
+        ]
+    }
+}
+
+///
+/// Renders the keypad as a 4×4 table of `hex: keys` cells, followed by the hotkeys.
+///
+pub fn key_table(keymap: &KeyMap, hotkeys: &Hotkeys) -> String {
+    let cells = KEYPAD_LAYOUT.map(|row| {
+        row.map(|hex_key| {
+            let keys: Vec<String> = keymap.keys(hex_key).iter().map(|key| key.name()).collect();
+            let keys = if keys.is_empty() {
+                "-".to_string()
+            } else {
+                keys.join("/")
+            };
+            format!("{hex_key:X}: {keys}")
+        })
+    });
+    let width = cells.iter().flatten().map(String::len).max().unwrap_or(0);
+    let separator = format!("+{}\n", format!("{}+", "-".repeat(width + 2)).repeat(4));
+
+    let mut table = String::from("Keypad:\n");
+    table.push_str(&separator);
+    for row in &cells {
+        table.push('|');
+        for cell in row {
+            let _ = write!(table, " {cell:<width$} |");
+        }
+        table.push('\n');
+        table.push_str(&separator);
+    }
+
+    table.push_str("\nHotkeys:\n");
+    let entries = hotkeys.entries();
+    let width = entries
+        .iter()
+        .map(|(action, _)| action.len())
+        .max()
+        .unwrap_or(0);
+    for (action, key) in entries {
+        let _ = writeln!(table, "  {action:<width$}  {}", key.name());
+    }
+    let _ = writeln!(
+        table,
+        "  {:<width$}  Ctrl+{}",
+        "Save settings",
+        SAVE_PROFILE_KEY.name()
+    );
+    table
+}
+
+#[cfg(test)]
+mod keymap_tests {
+    use sdl2::keyboard::Keycode;
+
+    use super::{key_table, Hotkeys, KeyMap};
+
+    #[test]
+    fn default_layout() {
+        let keymap = KeyMap::default();
+        assert_eq!(keymap.hex_key(Keycode::Num1), Some(0x1));
+        assert_eq!(keymap.hex_key(Keycode::Num4), Some(0xC));
+        assert_eq!(keymap.hex_key(Keycode::X), Some(0x0));
+        assert_eq!(keymap.hex_key(Keycode::V), Some(0xF));
+        assert_eq!(keymap.hex_key(Keycode::P), None);
+        assert_eq!(keymap.keys(0xA), [Keycode::Z]);
+    }
+
+    #[test]
+    fn bind_moves_the_key() {
+        let mut keymap = KeyMap::default();
+        keymap.bind(0x5, Keycode::Up);
+        keymap.bind(0x5, Keycode::X);
+        assert_eq!(keymap.keys(0x5), [Keycode::W, Keycode::Up, Keycode::X]);
+        assert!(keymap.keys(0x0).is_empty());
+        assert_eq!(keymap.hex_key(Keycode::X), Some(0x5));
+    }
+
+    #[test]
+    fn second_keypad_on_the_numpad() {
+        let mut keymap = KeyMap::default();
+        assert_eq!(keymap.second_hex_key(Keycode::Kp7), Some(0x1));
+        assert_eq!(keymap.second_hex_key(Keycode::KpPlus), Some(0xF));
+        assert_eq!(keymap.second_hex_key(Keycode::X), None);
+        assert_eq!(keymap.hex_key(Keycode::Kp7), None);
+
+        // A key moved to the other keypad leaves the one it was on
+        keymap.bind_second(0x0, Keycode::X);
+        assert_eq!(keymap.second_keys(0x0), [Keycode::KpPeriod, Keycode::X]);
+        assert_eq!(keymap.hex_key(Keycode::X), None);
+        keymap.bind(0x1, Keycode::Kp7);
+        assert_eq!(keymap.second_hex_key(Keycode::Kp7), None);
+        assert!(keymap.second_keys(0x1).is_empty());
+    }
+
+    #[test]
+    fn default_table() {
+        let expected = "Keypad:\n\
+             +------+------+------+------+\n\
+             | 1: 1 | 2: 2 | 3: 3 | C: 4 |\n\
+             +------+------+------+------+\n\
+             | 4: Q | 5: W | 6: E | D: R |\n\
+             +------+------+------+------+\n\
+             | 7: A | 8: S | 9: D | E: F |\n\
+             +------+------+------+------+\n\
+             | A: Z | 0: X | B: C | F: V |\n\
+             +------+------+------+------+\n\
+             \n\
+             Hotkeys:\n\
+             \x20 Help            F1\n\
+             \x20 Quit            Escape\n\
+             \x20 Pause/resume    P\n\
+             \x20 Step (paused)   N\n\
+             \x20 Status overlay  F3\n\
+             \x20 Disassembly     F4\n\
+             \x20 Frame graph     F5\n\
+             \x20 Debug panels    F10\n\
+             \x20 Fullscreen      F11\n\
+             \x20 Scale filter    F9\n\
+             \x20 ROM library     F2\n\
+             \x20 Save settings   Ctrl+S\n";
+        // The debug panels only have a hotkey with `egui`
+        #[cfg(not(feature = "egui"))]
+        let expected = expected.replace("  Debug panels    F10\n", "");
+        assert_eq!(key_table(&KeyMap::default(), &Hotkeys::default()), expected);
+    }
+
+    #[test]
+    fn table_with_overrides() {
+        let mut keymap = KeyMap::default();
+        keymap.bind(0x5, Keycode::Up);
+        keymap.bind(0x5, Keycode::X);
+        let table = key_table(&keymap, &Hotkeys::default());
+        assert!(table.contains("| 5: W/Up/X |"));
+        assert!(table.contains("| 0: -      |"));
+    }
+}
@@ -0,0 +1,904 @@
+use std::time::{Duration, Instant};
+
+use sdl2::{
+    event::Event,
+    keyboard::Mod,
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{BlendMode, WindowCanvas},
+    video::{FullscreenType, WindowBuilder, WindowPos},
+    EventPump,
+};
+
+pub mod help;
+pub mod keymap;
+#[cfg(feature = "egui")]
+pub mod panels;
+pub mod render;
+pub mod window;
+
+use self::help::{help_layout, help_lines};
+use self::keymap::{Hotkeys, KeyMap, SAVE_PROFILE_KEY};
+#[cfg(feature = "egui")]
+use self::panels::DebugPanels;
+use self::render::{RenderOptions, ScaleFilter, VisualBeep};
+use self::window::{fit_window, WindowGeometry, MIN_WINDOW_SIZE};
+#[cfg(feature = "egui")]
+use super::DebugView;
+use super::{AudioSink, Frontend, Input, Keypad};
+use crate::emu::chip8x::ColorZones;
+use crate::emu::disasm::ListingLine;
+use crate::emu::io::{
+    DisplayError, TextRenderer, CHAR_WIDTH, GRID_WIDTH, LINE_HEIGHT, MEGA_GRID_WIDTH,
+    PIXEL_AGE_FRAMES,
+};
+use crate::emu::mega::ColorPalette;
+use crate::emu::stats::{FrameGraph, FRAME_TIMES_KEPT};
+
+///
+/// The title of the window for the Chip8 emulator.
+///
+const WINDOW_NAME: &str = "The CHIP8 Emulator";
+
+///
+/// How many screen pixels make a pixel of the overlay font.
+///
+const OVERLAY_SCALE: u32 = 2;
+
+///
+/// How far the overlay's text is from the corner of the window and from the edges of its box.
+///
+const OVERLAY_MARGIN: u32 = 4;
+
+///
+/// How wide the disassembly pane is, enough for an address, an opcode and its longest mnemonic.
+///
+const PANE_WIDTH: u32 = 36 * CHAR_WIDTH * OVERLAY_SCALE + 2 * OVERLAY_MARGIN;
+
+///
+/// How tall the bars of the frame-time graph are at its full scale.
+///
+const FRAME_GRAPH_HEIGHT: u32 = 64;
+
+///
+/// How wide every bar of the frame-time graph is.
+///
+const FRAME_BAR_WIDTH: u32 = 2;
+
+///
+/// The color of the instructions with a breakpoint.
+///
+const BREAKPOINT_COLOR: Color = Color::RGB(255, 64, 64);
+
+///
+/// The color of the buzzer's cue, and how opaque its flash is over the screen.
+///
+const BEEP_COLOR: Color = Color::RGB(255, 176, 0);
+const BEEP_FLASH_ALPHA: u8 = 96;
+
+///
+/// Returns the color of a pixel, none for the background: green when it's on, and when it's
+/// given an age, white fading to green if it was just drawn and grey fading to black if it was
+/// just erased.
+///
+fn pixel_color(pixel: u8, age: Option<u8>) -> Option<Color> {
+    let Some(age) = age else {
+        return (pixel == 1).then_some(Color::RGB(0, 255, 0));
+    };
+    // 255 when the pixel just changed, 0 once it's as old as the ages go
+    let fresh =
+        u32::from(PIXEL_AGE_FRAMES - age.min(PIXEL_AGE_FRAMES)) * 255 / u32::from(PIXEL_AGE_FRAMES);
+    let fresh = fresh as u8;
+    if pixel == 1 {
+        Some(Color::RGB(fresh, 255, fresh))
+    } else {
+        (fresh > 0).then_some(Color::RGB(fresh / 2, fresh / 2, fresh / 2))
+    }
+}
+
+///
+/// The `SdlFrontend` structure represents the SDL window of the chip8 emulator and its keyboard.
+///
+#[allow(clippy::struct_excessive_bools)]
+pub struct SdlFrontend {
+    width: u32,
+    height: u32,
+    /// Dropped before the canvas, whose renderer its textures belong to
+    #[cfg(feature = "egui")]
+    panels: DebugPanels,
+    canvas: WindowCanvas,
+    event_pump: EventPump,
+    keymap: KeyMap,
+    hotkeys: Hotkeys,
+    overlay: Vec<String>,
+    pane: Vec<ListingLine>,
+    /// Whether the window is widened to show the pane beside the screen instead of over it
+    side_panel: bool,
+    #[cfg(feature = "egui")]
+    debug_view: Option<DebugView>,
+    frame_graph: Option<FrameGraph>,
+    /// How many frames ago every pixel last changed, when the pixels are tinted by it
+    pixel_ages: Option<Vec<u8>>,
+    /// The colors of the pixel values while the screen is MegaChip's
+    palette: Option<ColorPalette>,
+    /// The colors of CHIP-8X's zones and background
+    color_zones: Option<ColorZones>,
+    /// The lines of the help while it's shown
+    help: Option<Vec<String>>,
+    /// The cue shown while the buzzer sounds, and whether it does
+    visual_beep: Option<VisualBeep>,
+    tone: bool,
+    /// The last framebuffer shown, to show it again with or without the cue as the buzzer changes
+    last_frame: Vec<u8>,
+    /// Whether showing the canvas waits for the vertical sync, and is then left to `end_frame`
+    vsync: bool,
+    needs_present: bool,
+    /// How the screen is sampled as it's scaled up, unless integer scaling keeps it sharp
+    scale_filter: ScaleFilter,
+    /// Where the window was and how big before it went fullscreen, to return there
+    windowed: (Option<(i32, i32)>, (u32, u32)),
+    /// Called with the window's geometry as it's closed, to open it the same way the next time
+    on_close: Option<Box<dyn FnOnce(&WindowGeometry)>>,
+}
+
+impl SdlFrontend {
+    ///
+    /// Opens a window showing the screen in `width` × `height` pixels, widened by the width of
+    /// the disassembly pane if `side_panel` is set, drawn as `render` asks if SDL can.
+    ///
+    pub fn new(
+        width: u32,
+        height: u32,
+        keymap: KeyMap,
+        hotkeys: Hotkeys,
+        side_panel: bool,
+        render: &RenderOptions,
+    ) -> Result<Self, DisplayError> {
+        if let Some(driver) = &render.video_driver {
+            sdl2::hint::set("SDL_VIDEODRIVER", driver);
+        }
+        let Ok(sdl_context) = sdl2::init() else {
+            return Err(DisplayError::FailedToCreateContext);
+        };
+
+        let Ok(video_subsystem) = sdl_context.video() else {
+            return Err(DisplayError::FailedToCreateVideoSubsystem);
+        };
+
+        // The CLI rejects windows smaller than the screen, but a library may ask for any size, and
+        // neither knows how big the desktop is
+        let panel_width = if side_panel { PANE_WIDTH } else { 0 };
+        let desktop = video_subsystem
+            .desktop_display_mode(0)
+            .ok()
+            .and_then(|mode| Some((u32::try_from(mode.w).ok()?, u32::try_from(mode.h).ok()?)))
+            .map(|(desktop_width, desktop_height)| {
+                (desktop_width.saturating_sub(panel_width), desktop_height)
+            });
+        let (fitted_width, fitted_height) = fit_window((width, height), desktop);
+        if fitted_width < width || fitted_height < height {
+            log::warn!(
+                "The {width}×{height} window doesn't fit the desktop, opening it at \
+                 {fitted_width}×{fitted_height} instead."
+            );
+        }
+        if fitted_width > width || fitted_height > height {
+            log::warn!(
+                "The {width}×{height} window is smaller than the {}×{} screen, opening it at \
+                 {fitted_width}×{fitted_height} instead.",
+                MIN_WINDOW_SIZE.0,
+                MIN_WINDOW_SIZE.1
+            );
+        }
+        let (width, height) = (fitted_width, fitted_height);
+        let window_width = width + panel_width;
+        let window = || {
+            WindowBuilder::new(&video_subsystem, WINDOW_NAME, window_width, height)
+                .position_centered()
+                .resizable()
+                .build()
+                .map_err(|_| DisplayError::FailedToCreateWindow)
+        };
+
+        // The window goes with the canvas that failed, so the fallback needs a new one
+        let video_driver = video_subsystem.current_video_driver();
+        let mut canvas = match render
+            .configure(window()?.into_canvas(), video_driver)
+            .build()
+        {
+            Ok(canvas) => canvas,
+            Err(e) => {
+                log::warn!(
+                    "Failed to create the {} renderer, falling back to SDL's default: {e}",
+                    render.renderer
+                );
+                window()?
+                    .into_canvas()
+                    .build()
+                    .map_err(|_| DisplayError::FailedToCreateCanvas)?
+            }
+        };
+        // The window is laid out at the size it opened with, and scaled to whatever it's resized to
+        if let Err(e) = canvas.set_logical_size(window_width, height) {
+            log::warn!("Failed to keep the screen's size as the window is resized: {e}");
+        }
+        let info = canvas.info();
+        log::info!("Rendering with {}.", render::describe(&info));
+
+        let event_pump = sdl_context
+            .event_pump()
+            .map_err(|_| DisplayError::FailedToGetEventPump)?;
+
+        let frontend = Self {
+            width,
+            height,
+            #[cfg(feature = "egui")]
+            panels: DebugPanels::default(),
+            canvas,
+            event_pump,
+            keymap,
+            hotkeys,
+            overlay: Vec::new(),
+            pane: Vec::new(),
+            side_panel,
+            #[cfg(feature = "egui")]
+            debug_view: None,
+            frame_graph: None,
+            pixel_ages: None,
+            palette: None,
+            color_zones: None,
+            help: None,
+            visual_beep: render.visual_beep,
+            tone: false,
+            last_frame: Vec::new(),
+            vsync: render::has_vsync(&info),
+            needs_present: false,
+            scale_filter: render.scale_filter,
+            windowed: (None, (window_width, height)),
+            on_close: None,
+        };
+
+        Ok(frontend)
+    }
+
+    ///
+    /// Moves and resizes the window as `geometry` says, centering it instead when the position
+    /// would be off every display connected.
+    ///
+    pub fn restore_geometry(&mut self, geometry: &WindowGeometry) {
+        let displays = self.displays();
+        let position = geometry
+            .position
+            .and_then(|position| window::visible_position(position, geometry.size, &displays));
+        if geometry.position.is_some() && position.is_none() {
+            log::info!("The window was last on a display that isn't connected, centering it.");
+        }
+
+        let window = self.canvas.window_mut();
+        if let Err(e) = window.set_size(geometry.size.0, geometry.size.1) {
+            log::warn!("Failed to resize the window: {e}");
+        }
+        match position {
+            Some((x, y)) => window.set_position(WindowPos::Positioned(x), WindowPos::Positioned(y)),
+            None => window.set_position(WindowPos::Centered, WindowPos::Centered),
+        }
+        self.windowed = (position, geometry.size);
+        if let Err(e) = self.canvas.set_integer_scale(geometry.integer_scale) {
+            log::warn!("Failed to change how the screen is scaled: {e}");
+        }
+        if geometry.fullscreen {
+            self.set_fullscreen(true);
+        }
+    }
+
+    ///
+    /// Returns where the window is and how it shows the screen, its position and size being the
+    /// ones it had before going fullscreen if it is.
+    ///
+    pub fn geometry(&self) -> WindowGeometry {
+        let window = self.canvas.window();
+        let fullscreen = window.fullscreen_state() != FullscreenType::Off;
+        let (position, size) = if fullscreen {
+            self.windowed
+        } else {
+            (Some(window.position()), window.size())
+        };
+        WindowGeometry {
+            position,
+            size,
+            fullscreen,
+            integer_scale: self.canvas.integer_scale(),
+        }
+    }
+
+    ///
+    /// Calls `save` with the window's geometry once it's closed.
+    ///
+    pub fn on_close(&mut self, save: impl FnOnce(&WindowGeometry) + 'static) {
+        self.on_close = Some(Box::new(save));
+    }
+
+    ///
+    /// Returns how the screen is sampled as it's scaled up: always the nearest pixel while it's
+    /// only scaled by whole multiples, which keeps it sharp anyway.
+    ///
+    pub fn scale_filter(&self) -> ScaleFilter {
+        if self.canvas.integer_scale() {
+            ScaleFilter::Nearest
+        } else {
+            self.scale_filter
+        }
+    }
+
+    ///
+    /// Samples the screen with `filter` from the next `present` on, as soon as integer scaling is
+    /// off.
+    ///
+    pub fn set_scale_filter(&mut self, filter: ScaleFilter) {
+        self.scale_filter = filter;
+    }
+
+    ///
+    /// Reads back what was last drawn, as the size of the output in pixels and their RGB bytes,
+    /// row by row.
+    ///
+    /// # Errors
+    ///
+    /// Returns SDL's error when the renderer can't be read from.
+    ///
+    pub fn capture(&self) -> Result<((u32, u32), Vec<u8>), String> {
+        let size = self.canvas.output_size()?;
+        let pixels = self.canvas.read_pixels(None, PixelFormatEnum::RGB24)?;
+        Ok((size, pixels))
+    }
+
+    ///
+    /// Returns the bounds of every display connected.
+    ///
+    fn displays(&self) -> Vec<Rect> {
+        let video = self.canvas.window().subsystem();
+        let count = video.num_video_displays().unwrap_or(0);
+        (0..count)
+            .filter_map(|display| video.display_bounds(display).ok())
+            .collect()
+    }
+
+    fn set_fullscreen(&mut self, fullscreen: bool) {
+        let window = self.canvas.window_mut();
+        let state = if fullscreen {
+            self.windowed = (Some(window.position()), window.size());
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Off
+        };
+        if let Err(e) = window.set_fullscreen(state) {
+            log::warn!("Failed to switch the window to fullscreen or back: {e}");
+        }
+    }
+
+    ///
+    /// Translates a window event into an input, if it means anything to the emulator. Window
+    /// controls the emulator doesn't need to know of, going fullscreen, are handled here.
+    ///
+    fn input(&mut self, event: Event) -> Option<Input> {
+        #[cfg(feature = "egui")]
+        if self.debug_view.is_some() {
+            self.panels.handle_event(&event);
+        }
+        match event {
+            Event::Quit { .. } => Some(Input::Quit),
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.fullscreen => {
+                let fullscreen = self.canvas.window().fullscreen_state() == FullscreenType::Off;
+                self.set_fullscreen(fullscreen);
+                None
+            }
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.scale_filter => {
+                self.set_scale_filter(self.scale_filter.toggled());
+                log::info!("Scaling the screen with the {} filter.", self.scale_filter);
+                None
+            }
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.quit => Some(Input::Quit),
+            Event::DropFile { filename, .. } => Some(Input::DropRom(filename)),
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.pause => Some(Input::TogglePause),
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.step => Some(Input::Step),
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.overlay => Some(Input::ToggleOverlay),
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.pane => Some(Input::TogglePane),
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.frame_graph => Some(Input::ToggleFrameGraph),
+            #[cfg(feature = "egui")]
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.debug_view => Some(Input::ToggleDebugView),
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.browse => Some(Input::Browse),
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } if key == self.hotkeys.help => Some(Input::ToggleHelp),
+            Event::KeyDown {
+                keycode: Some(SAVE_PROFILE_KEY),
+                keymod,
+                ..
+            } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => Some(Input::SaveProfile),
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } => match (self.keymap.hex_key(key), self.keymap.second_hex_key(key)) {
+                (Some(hex_key), _) => Some(Input::Key {
+                    hex_key,
+                    pressed: true,
+                    at: Instant::now(),
+                }),
+                (None, Some(hex_key)) => Some(Input::SecondKey {
+                    hex_key,
+                    pressed: true,
+                }),
+                (None, None) => None,
+            },
+            Event::KeyDown { keycode: None, .. } => Some(Input::ReleaseKeys),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Draws the overlay's lines in the top left corner, over a black box keeping them readable.
+    ///
+    fn draw_overlay(&mut self) {
+        if self.overlay.is_empty() {
+            return;
+        }
+
+        let text = TextRenderer::new(OVERLAY_SCALE);
+        let lines = || self.overlay.iter().map(String::as_str);
+        let (width, height) = text.size(lines());
+        let margin = OVERLAY_MARGIN.cast_signed();
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        let backdrop = Rect::new(
+            0,
+            0,
+            width + 2 * OVERLAY_MARGIN,
+            height + 2 * OVERLAY_MARGIN,
+        );
+        if let Err(e) = self.canvas.fill_rect(backdrop) {
+            log::error!("Failed to draw the overlay: {e}");
+        }
+
+        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+        let canvas = &mut self.canvas;
+        text.draw_lines(lines(), margin, margin, |x, y, size| {
+            let _ = canvas.fill_rect(Rect::new(x, y, size, size));
+        });
+    }
+
+    ///
+    /// Draws the frame-time graph in the bottom left corner: a bar for every frame, green within
+    /// the budget of a 60 Hz frame and red past it, a grey line at the budget and the speed above.
+    ///
+    fn draw_frame_graph(&mut self) {
+        let Some(graph) = &self.frame_graph else {
+            return;
+        };
+
+        let text = TextRenderer::new(OVERLAY_SCALE);
+        let last = graph
+            .times
+            .last()
+            .map_or(0.0, |time| time.as_secs_f64() * 1000.0);
+        let label = format!("{} IPS  {last:.1} ms", graph.ips);
+        let (label_width, label_height) = text.size([label.as_str()]);
+        let width =
+            (FRAME_TIMES_KEPT as u32 * FRAME_BAR_WIDTH).max(label_width) + 2 * OVERLAY_MARGIN;
+        let height = label_height + FRAME_GRAPH_HEIGHT + 3 * OVERLAY_MARGIN;
+        let top = self
+            .canvas
+            .logical_size()
+            .1
+            .saturating_sub(height)
+            .cast_signed();
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        if let Err(e) = self.canvas.fill_rect(Rect::new(0, top, width, height)) {
+            log::error!("Failed to draw the frame graph: {e}");
+        }
+
+        let margin = OVERLAY_MARGIN.cast_signed();
+        let bottom = top + height.cast_signed() - margin;
+        for (n, (bar_height, over_budget)) in (0..).zip(graph.bars(FRAME_GRAPH_HEIGHT)) {
+            let color = if over_budget {
+                Color::RGB(255, 64, 64)
+            } else {
+                Color::RGB(0, 200, 0)
+            };
+            self.canvas.set_draw_color(color);
+            let x = margin + (n * FRAME_BAR_WIDTH).cast_signed();
+            let _ = self.canvas.fill_rect(Rect::new(
+                x,
+                bottom - bar_height.cast_signed(),
+                FRAME_BAR_WIDTH,
+                bar_height,
+            ));
+        }
+        self.canvas.set_draw_color(Color::RGB(128, 128, 128));
+        let budget = bottom - graph.budget_height(FRAME_GRAPH_HEIGHT).cast_signed();
+        let _ = self.canvas.fill_rect(Rect::new(
+            margin,
+            budget,
+            FRAME_TIMES_KEPT as u32 * FRAME_BAR_WIDTH,
+            1,
+        ));
+
+        self.draw_text(&label, margin, top + margin, Color::RGB(255, 255, 255));
+    }
+
+    ///
+    /// Draws the keypad layout and the hotkeys in the middle of the window, as big as they fit.
+    ///
+    fn draw_help(&mut self) {
+        let Some(lines) = &self.help else {
+            return;
+        };
+
+        let columns = lines.iter().map(|line| line.chars().count()).max();
+        let layout = help_layout(
+            columns.unwrap_or(0) as u32,
+            lines.len() as u32,
+            self.canvas.logical_size(),
+            OVERLAY_MARGIN,
+        );
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        if let Err(e) = self.canvas.fill_rect(layout.backdrop) {
+            log::error!("Failed to draw the help: {e}");
+        }
+
+        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+        let canvas = &mut self.canvas;
+        let (x, y) = layout.text;
+        TextRenderer::new(layout.scale).draw_lines(
+            lines.iter().map(String::as_str),
+            x,
+            y,
+            |x, y, size| {
+                let _ = canvas.fill_rect(Rect::new(x, y, size, size));
+            },
+        );
+    }
+
+    ///
+    /// Draws the disassembly pane beside the screen, or over its right side without a side panel,
+    /// the current instruction on a highlighted line and the breakpoints in red.
+    ///
+    fn draw_pane(&mut self) {
+        if self.pane.is_empty() {
+            return;
+        }
+
+        let left = if self.side_panel {
+            self.width
+        } else {
+            self.width.saturating_sub(PANE_WIDTH)
+        };
+        let line_height = LINE_HEIGHT * OVERLAY_SCALE;
+        let height = self.pane.len() as u32 * line_height + 2 * OVERLAY_MARGIN;
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        if let Err(e) = self
+            .canvas
+            .fill_rect(Rect::new(left.cast_signed(), 0, PANE_WIDTH, height))
+        {
+            log::error!("Failed to draw the disassembly pane: {e}");
+        }
+
+        let text = TextRenderer::new(OVERLAY_SCALE);
+        let x = (left + OVERLAY_MARGIN).cast_signed();
+        for (n, line) in (0..).zip(&self.pane) {
+            let y = (OVERLAY_MARGIN + n * line_height).cast_signed();
+            if line.current {
+                self.canvas.set_draw_color(Color::RGB(0, 96, 0));
+                let highlight = Rect::new(left.cast_signed(), y - 1, PANE_WIDTH, line_height);
+                let _ = self.canvas.fill_rect(highlight);
+            }
+            let color = if line.breakpoint {
+                Color::RGB(255, 64, 64)
+            } else {
+                Color::RGB(255, 255, 255)
+            };
+            self.canvas.set_draw_color(color);
+            let canvas = &mut self.canvas;
+            text.draw(&line.to_string(), x, y, |x, y, size| {
+                let _ = canvas.fill_rect(Rect::new(x, y, size, size));
+            });
+        }
+    }
+
+    ///
+    /// Draws a line of text in the color, its top left corner at `x`, `y`.
+    ///
+    fn draw_text(&mut self, text: &str, x: i32, y: i32, color: Color) {
+        self.canvas.set_draw_color(color);
+        let canvas = &mut self.canvas;
+        TextRenderer::new(OVERLAY_SCALE).draw(text, x, y, |x, y, size| {
+            let _ = canvas.fill_rect(Rect::new(x, y, size, size));
+        });
+    }
+
+    ///
+    /// Draws the egui debug panels over the screen: the registers with the pause, step and reset
+    /// buttons, the breakpoints, the memory and the disassembly. Clicking an instruction sets or
+    /// removes a breakpoint there, and the mouse wheel scrolls the memory.
+    ///
+    #[cfg(feature = "egui")]
+    fn draw_debug_panels(&mut self) {
+        let Some(view) = &self.debug_view else {
+            return;
+        };
+        if let Err(e) = self.panels.draw(&mut self.canvas, view) {
+            log::error!("Failed to draw the debug panels: {e}");
+        }
+    }
+
+    ///
+    /// Shows two framebuffers side by side, each in half of the window, split by a line which
+    /// turns red once they `differ`.
+    ///
+    pub fn present_side_by_side(&mut self, left: &[u8], right: &[u8], differ: bool) {
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        let half = self.width / 2;
+        self.draw_framebuffer(left, 0, half);
+        self.draw_framebuffer(right, half, half);
+
+        let color = if differ {
+            BREAKPOINT_COLOR
+        } else {
+            Color::RGB(96, 96, 96)
+        };
+        self.canvas.set_draw_color(color);
+        let divider = Rect::new(half.cast_signed() - 1, 0, 2, self.height);
+        if let Err(e) = self.canvas.fill_rect(divider) {
+            log::error!("Failed to draw the divider: {e}");
+        }
+        self.finish_present();
+    }
+
+    ///
+    /// Draws the framebuffer's pixels in the `width` pixels of the window from `left` on.
+    ///
+    fn draw_framebuffer(&mut self, framebuffer: &[u8], left: u32, width: u32) {
+        // 64 columns, or the 256 of MegaChip's screen
+        let columns = if self.palette.is_some() {
+            MEGA_GRID_WIDTH
+        } else {
+            GRID_WIDTH
+        };
+        // 32 rows, or the 64 of HiRes CHIP-8, or MegaChip's 192
+        let rows = (framebuffer.len() / columns).max(1);
+        // Calculate the size of each pixel to fit the display size, never less than one even when
+        // the screen has more pixels than the window
+        debug_assert!(
+            width > 0 && self.height > 0,
+            "the window is {width}×{}",
+            self.height
+        );
+        let pixel_width = (width / columns as u32).max(1);
+        let pixel_height = (self.height / rows as u32).max(1);
+
+        // One RGB texel per pixel, the 'off' ones left black like the background
+        let mut texels = vec![0; columns * rows * 3];
+        for ((index, texel), &pixel) in texels.chunks_exact_mut(3).enumerate().zip(framebuffer) {
+            if let Some(palette) = &self.palette {
+                texel.copy_from_slice(&palette.rgb(pixel));
+                continue;
+            }
+            if let Some(zones) = &self.color_zones {
+                texel.copy_from_slice(&zones.rgb(index % columns, index / columns, pixel));
+                continue;
+            }
+            let age = self
+                .pixel_ages
+                .as_ref()
+                .and_then(|ages| ages.get(index).copied());
+            if let Some(color) = pixel_color(pixel, age) {
+                texel.copy_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+
+        // Textures are sampled with the filter the hint says as they're created
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", self.scale_filter().hint());
+        let creator = self.canvas.texture_creator();
+        let texture = creator
+            .create_texture_static(PixelFormatEnum::RGB24, columns as u32, rows as u32)
+            .map_err(|e| e.to_string())
+            .and_then(|mut texture| {
+                texture
+                    .update(None, &texels, columns * 3)
+                    .map_err(|e| e.to_string())?;
+                Ok(texture)
+            });
+        let screen = Rect::new(
+            left.cast_signed(),
+            0,
+            pixel_width * columns as u32,
+            pixel_height * rows as u32,
+        );
+        if let Err(e) = texture.and_then(|texture| self.canvas.copy(&texture, None, screen)) {
+            log::error!("Failed to draw the screen: {e}");
+        }
+    }
+
+    ///
+    /// Draws the buzzer's cue over the screen while it sounds.
+    ///
+    fn draw_beep(&mut self) {
+        let Some(beep) = self.visual_beep.filter(|_| self.tone) else {
+            return;
+        };
+        let alpha = if beep == VisualBeep::Flash {
+            BEEP_FLASH_ALPHA
+        } else {
+            u8::MAX
+        };
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas
+            .set_draw_color(Color::RGBA(BEEP_COLOR.r, BEEP_COLOR.g, BEEP_COLOR.b, alpha));
+        if let Err(e) = self.canvas.fill_rects(&beep.cue(self.width, self.height)) {
+            log::error!("Failed to draw the buzzer's cue: {e}");
+        }
+        self.canvas.set_blend_mode(BlendMode::None);
+    }
+
+    ///
+    /// Draws what goes over the screen and shows the canvas, or leaves it to `end_frame`.
+    ///
+    fn finish_present(&mut self) {
+        self.draw_pane();
+        self.draw_overlay();
+        self.draw_frame_graph();
+        #[cfg(feature = "egui")]
+        self.draw_debug_panels();
+        self.draw_help();
+        // Waiting for the vertical sync on every change would slow the emulation down to one
+        // instruction drawing per refresh, so it waits once at the end of the frame instead
+        if self.vsync {
+            self.needs_present = true;
+        } else {
+            self.canvas.present();
+        }
+    }
+}
+
+impl Drop for SdlFrontend {
+    fn drop(&mut self) {
+        if let Some(save) = self.on_close.take() {
+            save(&self.geometry());
+        }
+    }
+}
+
+impl Keypad for SdlFrontend {
+    fn poll_input(&mut self) -> Option<Input> {
+        #[cfg(feature = "egui")]
+        if let Some(input) = self.panels.clicked() {
+            return Some(input);
+        }
+        while let Some(event) = self.event_pump.poll_event() {
+            if let Some(input) = self.input(event) {
+                return Some(input);
+            }
+        }
+        None
+    }
+
+    fn wait_input(&mut self, timeout: Duration) -> Option<Input> {
+        #[cfg(feature = "egui")]
+        if let Some(input) = self.panels.clicked() {
+            return Some(input);
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let millis = u32::try_from(remaining.as_millis()).unwrap_or(u32::MAX);
+            if millis == 0 {
+                return None;
+            }
+            // Events that aren't any input, mouse moves and the like, don't end the wait
+            let event = self.event_pump.wait_event_timeout(millis)?;
+            if let Some(input) = self.input(event) {
+                return Some(input);
+            }
+        }
+    }
+}
+
+///
+/// The window has no sound, the buzzer stays silent.
+///
+impl AudioSink for SdlFrontend {
+    fn set_tone(&mut self, on: bool) {
+        let changed = self.tone != on;
+        self.tone = on;
+        // The screen may not change for a while, the cue has to show right away
+        if changed && self.visual_beep.is_some() && !self.last_frame.is_empty() {
+            let frame = std::mem::take(&mut self.last_frame);
+            self.present(&frame);
+        }
+    }
+}
+
+impl Frontend for SdlFrontend {
+    fn set_title(&mut self, title: &str) {
+        let title = format!("{WINDOW_NAME} - {title}");
+        if let Err(e) = self.canvas.window_mut().set_title(&title) {
+            log::warn!("Failed to set the window title: {e}");
+        }
+    }
+
+    fn present(&mut self, framebuffer: &[u8]) {
+        #[cfg(feature = "profiling")]
+        let _span = tracing::trace_span!("draw").entered();
+        // Set the background color to black
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.draw_framebuffer(framebuffer, 0, self.width);
+        if self.visual_beep.is_some() {
+            self.last_frame.clear();
+            self.last_frame.extend_from_slice(framebuffer);
+            self.draw_beep();
+        }
+        self.finish_present();
+    }
+
+    fn end_frame(&mut self) {
+        if self.needs_present {
+            self.needs_present = false;
+            self.canvas.present();
+        }
+    }
+
+    fn set_overlay(&mut self, lines: &[String]) {
+        self.overlay = lines.to_vec();
+    }
+
+    fn set_pane(&mut self, lines: &[ListingLine]) {
+        self.pane = lines.to_vec();
+    }
+
+    #[cfg(feature = "egui")]
+    fn set_debug_view(&mut self, view: Option<&DebugView>) {
+        if view.is_none() {
+            self.panels.hide();
+        }
+        self.debug_view = view.cloned();
+    }
+
+    fn set_frame_graph(&mut self, graph: Option<&FrameGraph>) {
+        self.frame_graph = graph.cloned();
+    }
+
+    fn set_pixel_ages(&mut self, ages: Option<&[u8]>) {
+        self.pixel_ages = ages.map(<[u8]>::to_vec);
+    }
+
+    fn set_palette(&mut self, palette: Option<&ColorPalette>) {
+        self.palette = palette.cloned();
+    }
+
+    fn set_color_zones(&mut self, zones: Option<&ColorZones>) {
+        self.color_zones = zones.cloned();
+    }
+
+    fn set_help(&mut self, shown: bool) {
+        self.help = shown.then(|| help_lines(&self.keymap, &self.hotkeys));
+    }
+}
@@ -0,0 +1,559 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::time::Instant;
+
+use egui::epaint::textures::TexturesDelta;
+use egui::epaint::{ClippedPrimitive, ImageData, ImageDelta, Mesh, Primitive, TextureId};
+use egui::{
+    Align2, Color32, Context, Event as EguiEvent, Modifiers, PointerButton, Pos2, RawInput,
+    RichText, TextWrapMode, TextureFilter, Ui, Window,
+};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::mouse::{MouseButton, MouseWheelDirection};
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use sdl2::sys;
+
+use crate::frontend::{DebugView, Input, MEMORY_COLUMNS};
+
+///
+/// How far the panels are from the edges of the window when they first open.
+///
+const PANEL_MARGIN: f32 = 8.0;
+
+///
+/// How many rows the memory panel's buttons move it by, all the rows it shows.
+///
+const MEMORY_PAGE: i32 = 8;
+
+///
+/// The color of the current instruction's line, and of the instructions with a breakpoint.
+///
+const CURRENT_COLOR: Color32 = Color32::from_rgb(0, 96, 0);
+const BREAKPOINT_COLOR: Color32 = Color32::from_rgb(255, 64, 64);
+
+///
+/// Translates a pointer event of the window into egui's, none for the other events: the keys
+/// stay the emulator's, and the mouse wheel scrolls the memory panel itself.
+///
+#[allow(clippy::cast_precision_loss)]
+pub fn pointer_event(event: &Event) -> Option<EguiEvent> {
+    let button = |mouse_btn| match mouse_btn {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        _ => None,
+    };
+    let position = |x: i32, y: i32| Pos2::new(x as f32, y as f32);
+    match *event {
+        Event::MouseMotion { x, y, .. } => Some(EguiEvent::PointerMoved(position(x, y))),
+        Event::MouseButtonDown {
+            mouse_btn, x, y, ..
+        } => Some(EguiEvent::PointerButton {
+            pos: position(x, y),
+            button: button(mouse_btn)?,
+            pressed: true,
+            modifiers: Modifiers::NONE,
+        }),
+        Event::MouseButtonUp {
+            mouse_btn, x, y, ..
+        } => Some(EguiEvent::PointerButton {
+            pos: position(x, y),
+            button: button(mouse_btn)?,
+            pressed: false,
+            modifiers: Modifiers::NONE,
+        }),
+        Event::Window {
+            win_event: WindowEvent::Leave,
+            ..
+        } => Some(EguiEvent::PointerGone),
+        _ => None,
+    }
+}
+
+///
+/// Returns how many rows of memory a turn of the mouse wheel scrolls by, negative ones towards
+/// address 0.
+///
+pub const fn wheel_rows(event: &Event) -> Option<i32> {
+    match *event {
+        Event::MouseWheel {
+            y,
+            direction: MouseWheelDirection::Flipped,
+            ..
+        } => Some(y),
+        // Scrolling up goes towards address 0
+        Event::MouseWheel { y, .. } => Some(-y),
+        _ => None,
+    }
+}
+
+///
+/// Lays the panels out for the view, pushing the inputs of the buttons and lines clicked. The
+/// wheel's `rows` scroll the memory panel once the pointer is over it.
+///
+fn show_panels(ctx: &Context, view: &DebugView, rows: i32, clicked: &mut Vec<Input>) {
+    let screen = ctx.content_rect();
+    let registers = Window::new("Registers")
+        .default_pos([PANEL_MARGIN, PANEL_MARGIN])
+        .resizable(false)
+        .show(ctx, |ui| {
+            for line in &view.registers {
+                ui.monospace(line);
+            }
+            ui.horizontal(|ui| {
+                let pause = if view.paused { "Resume" } else { "Pause" };
+                for (label, input) in [
+                    (pause, Input::TogglePause),
+                    ("Step", Input::Step),
+                    ("Reset", Input::Reset),
+                ] {
+                    if ui.button(label).clicked() {
+                        clicked.push(input);
+                    }
+                }
+            });
+        });
+
+    // Under the registers, wherever they end up
+    let top = registers.map_or(PANEL_MARGIN, |registers| {
+        registers.response.rect.bottom() + PANEL_MARGIN
+    });
+    Window::new("Breakpoints")
+        .default_pos([PANEL_MARGIN, top])
+        .resizable(false)
+        .show(ctx, |ui| {
+            if view.breakpoints.is_empty() {
+                ui.weak("None, click an instruction");
+            }
+            for &address in &view.breakpoints {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(format!("{address:04X}"))
+                            .monospace()
+                            .color(BREAKPOINT_COLOR),
+                    );
+                    if ui.small_button("Remove").clicked() {
+                        clicked.push(Input::ToggleBreakpoint(address));
+                    }
+                });
+            }
+        });
+
+    let memory = Window::new("Memory")
+        .pivot(Align2::LEFT_BOTTOM)
+        .default_pos([PANEL_MARGIN, screen.height() - PANEL_MARGIN])
+        .resizable(false)
+        .show(ctx, |ui| memory_panel(ui, view, clicked));
+    if rows != 0 && memory.is_some_and(|memory| memory.response.contains_pointer()) {
+        clicked.push(Input::ScrollMemory(rows));
+    }
+
+    Window::new("Disassembly")
+        .pivot(Align2::RIGHT_TOP)
+        .default_pos([screen.width() - PANEL_MARGIN, PANEL_MARGIN])
+        .resizable(false)
+        .show(ctx, |ui| {
+            for line in &view.disassembly {
+                let mut text = RichText::new(line.to_string()).monospace();
+                if line.breakpoint {
+                    text = text.color(BREAKPOINT_COLOR);
+                }
+                if line.current {
+                    text = text.background_color(CURRENT_COLOR);
+                }
+                let clicked_line = ui
+                    .selectable_label(false, text)
+                    .on_hover_text("Click to set or remove a breakpoint")
+                    .clicked();
+                if clicked_line {
+                    clicked.push(Input::ToggleBreakpoint(line.address));
+                }
+            }
+        });
+}
+
+///
+/// Lays the rows of the memory panel out, with the buttons paging through them.
+///
+fn memory_panel(ui: &mut Ui, view: &DebugView, clicked: &mut Vec<Input>) {
+    ui.style_mut().wrap_mode = Some(TextWrapMode::Extend);
+    for (address, row) in (u32::from(view.memory_start)..)
+        .step_by(MEMORY_COLUMNS)
+        .zip(view.memory.chunks(MEMORY_COLUMNS))
+    {
+        let mut line = format!("{address:04X}");
+        for byte in row {
+            let _ = write!(line, " {byte:02X}");
+        }
+        ui.monospace(line);
+    }
+    ui.horizontal(|ui| {
+        if ui.button("Up").clicked() {
+            clicked.push(Input::ScrollMemory(-MEMORY_PAGE));
+        }
+        if ui.button("Down").clicked() {
+            clicked.push(Input::ScrollMemory(MEMORY_PAGE));
+        }
+        ui.weak("or scroll");
+    });
+}
+
+///
+/// The `SdlTexture` struct represents an egui image uploaded to the renderer, destroyed with it.
+///
+struct SdlTexture {
+    raw: *mut sys::SDL_Texture,
+}
+
+impl Drop for SdlTexture {
+    fn drop(&mut self) {
+        // SAFETY: the texture was created by `SDL_CreateTexture` and is destroyed only here, before
+        // the renderer it belongs to
+        unsafe { sys::SDL_DestroyTexture(self.raw) };
+    }
+}
+
+///
+/// The `DebugPanels` struct represents the egui debug panels drawn over the window: the pointer's
+/// input waiting for the next pass, the textures egui drew into and the inputs clicked on them.
+///
+/// The textures belong to the window's renderer, so the panels must be dropped before it.
+///
+pub struct DebugPanels {
+    ctx: Context,
+    input: RawInput,
+    /// How many rows the mouse wheel scrolled by since the last pass
+    rows: i32,
+    start: Instant,
+    textures: HashMap<TextureId, SdlTexture>,
+    clicked: VecDeque<Input>,
+}
+
+impl Default for DebugPanels {
+    fn default() -> Self {
+        Self {
+            ctx: Context::default(),
+            input: RawInput::default(),
+            rows: 0,
+            start: Instant::now(),
+            textures: HashMap::new(),
+            clicked: VecDeque::new(),
+        }
+    }
+}
+
+impl DebugPanels {
+    ///
+    /// Takes the window's event for the next pass of the panels, if it's the pointer's.
+    ///
+    pub fn handle_event(&mut self, event: &Event) {
+        if let Some(event) = pointer_event(event) {
+            self.input.events.push(event);
+        }
+        if let Some(rows) = wheel_rows(event) {
+            self.rows = self.rows.saturating_add(rows);
+        }
+    }
+
+    ///
+    /// Returns the next input clicked on the panels, oldest first.
+    ///
+    pub fn clicked(&mut self) -> Option<Input> {
+        self.clicked.pop_front()
+    }
+
+    ///
+    /// Forgets the pointer's input when the panels are hidden, so nothing is clicked as they're
+    /// shown again.
+    ///
+    pub fn hide(&mut self) {
+        self.input.events.clear();
+        self.rows = 0;
+    }
+
+    ///
+    /// Lays the panels out for the view with the pointer's input since the last pass, and draws
+    /// them on the canvas.
+    ///
+    /// # Errors
+    ///
+    /// Returns SDL's error when a texture can't be uploaded or a mesh drawn.
+    ///
+    #[allow(clippy::cast_precision_loss)]
+    pub fn draw(&mut self, canvas: &mut WindowCanvas, view: &DebugView) -> Result<(), String> {
+        let (width, height) = canvas.logical_size();
+        self.input.screen_rect = Some(egui::Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(width as f32, height as f32),
+        ));
+        self.input.time = Some(self.start.elapsed().as_secs_f64());
+        let input = self.input.take();
+        let rows = std::mem::take(&mut self.rows);
+
+        let mut clicked = Vec::new();
+        let mut output = self
+            .ctx
+            .run_ui(input, |ui| show_panels(ui.ctx(), view, rows, &mut clicked));
+        self.clicked.extend(clicked);
+
+        // egui insists on every change of the textures being applied, even when drawing fails
+        let mut delta = std::mem::take(&mut output.textures_delta);
+        let shapes = std::mem::take(&mut output.shapes);
+        let drawn = self.upload(canvas, &delta).and_then(|()| {
+            let primitives = self.ctx.tessellate(shapes, output.pixels_per_point);
+            self.paint(canvas, primitives)
+        });
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+        delta.clear();
+        drawn
+    }
+
+    ///
+    /// Draws egui's meshes, each clipped to its rectangle.
+    ///
+    #[allow(clippy::cast_sign_loss)]
+    fn paint(
+        &self,
+        canvas: &mut WindowCanvas,
+        primitives: Vec<ClippedPrimitive>,
+    ) -> Result<(), String> {
+        let drawn = primitives.into_iter().try_for_each(|primitive| {
+            let Primitive::Mesh(mesh) = primitive.primitive else {
+                return Ok(());
+            };
+            let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                return Ok(());
+            };
+            let clip = primitive.clip_rect;
+            canvas.set_clip_rect(Rect::new(
+                clip.min.x as i32,
+                clip.min.y as i32,
+                clip.width().max(0.0) as u32,
+                clip.height().max(0.0) as u32,
+            ));
+            draw_mesh(canvas, texture, &mesh)
+        });
+        canvas.set_clip_rect(None);
+        drawn
+    }
+
+    ///
+    /// Creates the textures egui added and updates the ones it changed.
+    ///
+    fn upload(&mut self, canvas: &WindowCanvas, delta: &TexturesDelta) -> Result<(), String> {
+        for (id, images) in &delta.set {
+            for image in images {
+                if image.pos.is_none() {
+                    let texture = create_texture(canvas, image)?;
+                    self.textures.insert(*id, texture);
+                }
+                let texture = self
+                    .textures
+                    .get(id)
+                    .ok_or_else(|| format!("egui updated the texture {id:?} before creating it"))?;
+                update_texture(texture, image)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+///
+/// Creates a texture of the image's size, sampled as egui asks and blended over the screen.
+///
+fn create_texture(canvas: &WindowCanvas, image: &ImageDelta) -> Result<SdlTexture, String> {
+    let [width, height] = image.image.size();
+    let size = |side: usize| i32::try_from(side).map_err(|e| e.to_string());
+    // SAFETY: the renderer is alive for as long as the canvas is borrowed
+    let raw = unsafe {
+        sys::SDL_CreateTexture(
+            canvas.raw(),
+            sys::SDL_PixelFormatEnum::SDL_PIXELFORMAT_RGBA32 as u32,
+            sys::SDL_TextureAccess::SDL_TEXTUREACCESS_STATIC as i32,
+            size(width)?,
+            size(height)?,
+        )
+    };
+    if raw.is_null() {
+        return Err(sdl2::get_error());
+    }
+    let texture = SdlTexture { raw };
+    let scale_mode = match image.options.magnification {
+        TextureFilter::Nearest => sys::SDL_ScaleMode::SDL_ScaleModeNearest,
+        TextureFilter::Linear => sys::SDL_ScaleMode::SDL_ScaleModeLinear,
+    };
+    // SAFETY: the texture was just created
+    let failed = unsafe {
+        sys::SDL_SetTextureBlendMode(texture.raw, sys::SDL_BlendMode::SDL_BLENDMODE_BLEND) != 0
+            || sys::SDL_SetTextureScaleMode(texture.raw, scale_mode) != 0
+    };
+    if failed {
+        return Err(sdl2::get_error());
+    }
+    Ok(texture)
+}
+
+///
+/// Copies the image's pixels into the texture, at the position egui gives for a partial update.
+///
+fn update_texture(texture: &SdlTexture, image: &ImageDelta) -> Result<(), String> {
+    let ImageData::Color(pixels) = &image.image;
+    let [width, height] = pixels.size;
+    let [x, y] = image.pos.unwrap_or([0, 0]);
+    let side = |side: usize| i32::try_from(side).map_err(|e| e.to_string());
+    let area = sys::SDL_Rect {
+        x: side(x)?,
+        y: side(y)?,
+        w: side(width)?,
+        h: side(height)?,
+    };
+    // The pixels are blended with SDL's straight alpha, so they're taken out of egui's
+    // premultiplied one
+    let bytes: Vec<u8> = pixels
+        .pixels
+        .iter()
+        .flat_map(Color32::to_srgba_unmultiplied)
+        .collect();
+    // SAFETY: the texture is alive, and `bytes` holds `height` rows of `width` RGBA pixels
+    let failed = unsafe {
+        sys::SDL_UpdateTexture(
+            texture.raw,
+            &raw const area,
+            bytes.as_ptr().cast(),
+            area.w * 4,
+        ) != 0
+    };
+    if failed {
+        return Err(sdl2::get_error());
+    }
+    Ok(())
+}
+
+///
+/// Draws egui's triangles with the texture.
+///
+fn draw_mesh(canvas: &WindowCanvas, texture: &SdlTexture, mesh: &Mesh) -> Result<(), String> {
+    let vertices: Vec<sys::SDL_Vertex> = mesh
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let [r, g, b, a] = vertex.color.to_srgba_unmultiplied();
+            sys::SDL_Vertex {
+                position: sys::SDL_FPoint {
+                    x: vertex.pos.x,
+                    y: vertex.pos.y,
+                },
+                color: sys::SDL_Color { r, g, b, a },
+                tex_coord: sys::SDL_FPoint {
+                    x: vertex.uv.x,
+                    y: vertex.uv.y,
+                },
+            }
+        })
+        .collect();
+    let indices: Vec<i32> = mesh
+        .indices
+        .iter()
+        .map(|&index| index.cast_signed())
+        .collect();
+    let count = |len: usize| i32::try_from(len).map_err(|e| e.to_string());
+    // SAFETY: the renderer and the texture are alive, and every index is one of the vertices'
+    let failed = unsafe {
+        sys::SDL_RenderGeometry(
+            canvas.raw(),
+            texture.raw,
+            vertices.as_ptr(),
+            count(vertices.len())?,
+            indices.as_ptr(),
+            count(indices.len())?,
+        ) != 0
+    };
+    if failed {
+        return Err(sdl2::get_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod panels_tests {
+    use egui::{Event as EguiEvent, PointerButton, Pos2};
+    use sdl2::event::Event;
+    use sdl2::mouse::{MouseButton, MouseState, MouseWheelDirection};
+
+    use super::{pointer_event, show_panels, wheel_rows};
+    use crate::frontend::{DebugView, Input};
+
+    #[test]
+    fn pointer_events_go_to_egui_and_the_wheel_scrolls_the_memory() {
+        let click = Event::MouseButtonDown {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x: 12,
+            y: 34,
+        };
+        assert!(matches!(
+            pointer_event(&click),
+            Some(EguiEvent::PointerButton {
+                pos: Pos2 { x: 12.0, y: 34.0 },
+                button: PointerButton::Primary,
+                pressed: true,
+                ..
+            })
+        ));
+        let motion = Event::MouseMotion {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mousestate: MouseState::from_sdl_state(0),
+            x: 5,
+            y: 6,
+            xrel: 1,
+            yrel: 1,
+        };
+        assert_eq!(
+            pointer_event(&motion),
+            Some(EguiEvent::PointerMoved(Pos2::new(5.0, 6.0)))
+        );
+        assert_eq!(pointer_event(&Event::Quit { timestamp: 0 }), None);
+
+        let wheel = |y, direction| Event::MouseWheel {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            x: 0,
+            y,
+            direction,
+            precise_x: 0.0,
+            precise_y: 0.0,
+        };
+        assert_eq!(wheel_rows(&wheel(1, MouseWheelDirection::Normal)), Some(-1));
+        assert_eq!(wheel_rows(&wheel(1, MouseWheelDirection::Flipped)), Some(1));
+        assert_eq!(pointer_event(&wheel(1, MouseWheelDirection::Normal)), None);
+    }
+
+    #[test]
+    fn panels_click_nothing_without_the_pointer() {
+        let ctx = egui::Context::default();
+        let view = DebugView {
+            registers: vec!["PC: 0x200".to_string()],
+            memory: vec![0; 8 * 16],
+            breakpoints: vec![0x204],
+            ..DebugView::default()
+        };
+        let mut clicked: Vec<Input> = Vec::new();
+        let output = ctx.run_ui(egui::RawInput::default(), |ui| {
+            show_panels(ui.ctx(), &view, 1, &mut clicked);
+        });
+        assert!(clicked.is_empty());
+        assert!(!output.shapes.is_empty());
+        // The font's atlas is the first texture drawn with
+        assert!(!output.textures_delta.set.is_empty());
+        output.drop_without_applying_deltas();
+    }
+}
@@ -0,0 +1,403 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use sdl2::rect::Rect;
+use sdl2::render::{CanvasBuilder, RendererInfo};
+use sdl2::sys::SDL_RendererFlags;
+
+///
+/// The `DUMMY_VIDEO_DRIVER` constant is the SDL video driver drawing nowhere, for tests and CI.
+///
+pub const DUMMY_VIDEO_DRIVER: &str = "dummy";
+
+///
+/// The `Renderer` enum represents which SDL renderer draws the window.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Renderer {
+    /// Whichever SDL prefers, accelerated when it can
+    #[default]
+    Auto,
+    Accelerated,
+    /// Drawn by the CPU, for virtual machines and remote desktops without a usable GPU
+    Software,
+}
+
+impl Renderer {
+    pub(super) fn configure(self, builder: CanvasBuilder) -> CanvasBuilder {
+        match self {
+            Self::Auto => builder,
+            Self::Accelerated => builder.accelerated(),
+            Self::Software => builder.software(),
+        }
+    }
+}
+
+impl Display for Renderer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Auto => "auto",
+            Self::Accelerated => "accelerated",
+            Self::Software => "software",
+        };
+        write!(f, "{name}")
+    }
+}
+
+///
+/// The `RendererError` struct represents a renderer name that isn't one.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RendererError(String);
+
+impl Error for RendererError {}
+
+impl Display for RendererError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown renderer `{}`, expected `auto`, `accelerated` or `software`!",
+            self.0
+        )
+    }
+}
+
+impl FromStr for Renderer {
+    type Err = RendererError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "accelerated" => Ok(Self::Accelerated),
+            "software" => Ok(Self::Software),
+            _ => Err(RendererError(name.to_string())),
+        }
+    }
+}
+
+///
+/// The `ScaleFilter` enum represents how the screen is sampled as it's scaled up to the window.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleFilter {
+    /// Every pixel a sharp square, as on the original hardware
+    #[default]
+    Nearest,
+    /// The pixels blended into their neighbours, smoother when they don't scale by whole multiples
+    Linear,
+}
+
+impl ScaleFilter {
+    #[must_use]
+    pub const fn toggled(self) -> Self {
+        match self {
+            Self::Nearest => Self::Linear,
+            Self::Linear => Self::Nearest,
+        }
+    }
+
+    ///
+    /// Returns the value of SDL's `SDL_RENDER_SCALE_QUALITY` hint, which the textures created
+    /// after it's set are sampled with.
+    ///
+    pub(super) const fn hint(self) -> &'static str {
+        match self {
+            Self::Nearest => "nearest",
+            Self::Linear => "linear",
+        }
+    }
+}
+
+impl Display for ScaleFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.hint())
+    }
+}
+
+///
+/// The `ScaleFilterError` struct represents a scale filter name that isn't one.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScaleFilterError(String);
+
+impl Error for ScaleFilterError {}
+
+impl Display for ScaleFilterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown scale filter `{}`, expected `nearest` or `linear`!",
+            self.0
+        )
+    }
+}
+
+impl FromStr for ScaleFilter {
+    type Err = ScaleFilterError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "nearest" => Ok(Self::Nearest),
+            "linear" => Ok(Self::Linear),
+            _ => Err(ScaleFilterError(name.to_string())),
+        }
+    }
+}
+
+///
+/// The `VisualBeep` enum represents how the window shows the buzzer sounding, for players who
+/// can't hear it.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisualBeep {
+    /// A colored border around the screen
+    Border,
+    /// The whole screen tinted
+    Flash,
+    /// A speaker in the top right corner
+    Icon,
+}
+
+///
+/// The speaker of `VisualBeep::Icon`, a byte per row with the leftmost pixel in bit 7.
+///
+const SPEAKER_ICON: [u8; 7] = [
+    0b0001_0000,
+    0b0011_0010,
+    0b1111_0001,
+    0b1111_0101,
+    0b1111_0001,
+    0b0011_0010,
+    0b0001_0000,
+];
+
+impl VisualBeep {
+    ///
+    /// Returns the rectangles the cue fills over a screen of `width` × `height` pixels, scaled
+    /// along with it.
+    ///
+    pub(super) fn cue(self, width: u32, height: u32) -> Vec<Rect> {
+        let unit = (width.min(height) / 60).max(1);
+        match self {
+            Self::Border => {
+                let thickness = 2 * unit;
+                vec![
+                    Rect::new(0, 0, width, thickness),
+                    Rect::new(
+                        0,
+                        (height - thickness.min(height)).cast_signed(),
+                        width,
+                        thickness,
+                    ),
+                    Rect::new(0, 0, thickness, height),
+                    Rect::new(
+                        (width - thickness.min(width)).cast_signed(),
+                        0,
+                        thickness,
+                        height,
+                    ),
+                ]
+            }
+            Self::Flash => vec![Rect::new(0, 0, width, height)],
+            Self::Icon => {
+                // A margin of two icon pixels from the corner
+                let left = width.saturating_sub(10 * unit).cast_signed();
+                let top = (2 * unit).cast_signed();
+                let step = unit.cast_signed();
+                (0..)
+                    .zip(SPEAKER_ICON)
+                    .flat_map(|(row, bits)| {
+                        (0..8)
+                            .filter(move |column| bits & (0x80 >> column) != 0)
+                            .map(move |column| {
+                                Rect::new(left + column * step, top + row * step, unit, unit)
+                            })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Border => "border",
+            Self::Flash => "flash",
+            Self::Icon => "icon",
+        }
+    }
+}
+
+impl Display for VisualBeep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+///
+/// The `VisualBeepError` struct represents a visual beep name that isn't one.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisualBeepError(String);
+
+impl Error for VisualBeepError {}
+
+impl Display for VisualBeepError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown visual beep `{}`, expected `border`, `flash` or `icon`!",
+            self.0
+        )
+    }
+}
+
+impl FromStr for VisualBeep {
+    type Err = VisualBeepError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "border" => Ok(Self::Border),
+            "flash" => Ok(Self::Flash),
+            "icon" => Ok(Self::Icon),
+            _ => Err(VisualBeepError(name.to_string())),
+        }
+    }
+}
+
+///
+/// The `RenderOptions` struct represents how the window is drawn: through which video driver and
+/// renderer, whether showing a frame waits for the display's vertical sync and how the screen is
+/// scaled.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// The SDL video driver, e.g. `dummy` for no display at all, SDL picking one if unset
+    pub video_driver: Option<String>,
+    pub renderer: Renderer,
+    pub vsync: bool,
+    pub scale_filter: ScaleFilter,
+    /// The cue drawn over the screen while the buzzer sounds, none showing nothing
+    pub visual_beep: Option<VisualBeep>,
+}
+
+impl RenderOptions {
+    ///
+    /// Configures the canvas, without vsync under the dummy driver, which has no display to wait
+    /// for.
+    ///
+    pub(super) fn configure(&self, builder: CanvasBuilder, video_driver: &str) -> CanvasBuilder {
+        let builder = self.renderer.configure(builder);
+        if self.vsync && video_driver != DUMMY_VIDEO_DRIVER {
+            builder.present_vsync()
+        } else {
+            builder
+        }
+    }
+}
+
+///
+/// Returns whether the renderer SDL picked waits for the vertical sync.
+///
+pub(super) const fn has_vsync(info: &RendererInfo) -> bool {
+    info.flags & SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32 != 0
+}
+
+///
+/// Describes the renderer SDL picked, e.g. `opengl (accelerated, vsync)`.
+///
+pub(super) fn describe(info: &RendererInfo) -> String {
+    let kind = if info.flags & SDL_RendererFlags::SDL_RENDERER_SOFTWARE as u32 != 0 {
+        "software"
+    } else {
+        "accelerated"
+    };
+    let vsync = if has_vsync(info) { ", vsync" } else { "" };
+    format!("{} ({kind}{vsync})", info.name)
+}
+
+#[cfg(test)]
+mod render_tests {
+    use sdl2::rect::Rect;
+
+    use super::{
+        Renderer, RendererError, ScaleFilter, ScaleFilterError, VisualBeep, VisualBeepError,
+    };
+
+    #[test]
+    fn renderer_names() {
+        assert_eq!("auto".parse(), Ok(Renderer::Auto));
+        assert_eq!("Accelerated".parse(), Ok(Renderer::Accelerated));
+        assert_eq!("software".parse(), Ok(Renderer::Software));
+        assert_eq!(
+            "metal".parse::<Renderer>(),
+            Err(RendererError("metal".to_string()))
+        );
+        assert_eq!(Renderer::Software.to_string(), "software");
+    }
+
+    #[test]
+    fn scale_filter_names() {
+        assert_eq!("nearest".parse(), Ok(ScaleFilter::Nearest));
+        assert_eq!("Linear".parse(), Ok(ScaleFilter::Linear));
+        assert_eq!(
+            "bicubic".parse::<ScaleFilter>(),
+            Err(ScaleFilterError("bicubic".to_string()))
+        );
+        assert_eq!(ScaleFilter::Linear.to_string(), "linear");
+        assert_eq!(ScaleFilter::Nearest.toggled(), ScaleFilter::Linear);
+        assert_eq!(ScaleFilter::Linear.toggled(), ScaleFilter::Nearest);
+    }
+
+    #[test]
+    fn visual_beep_names() {
+        assert_eq!("border".parse(), Ok(VisualBeep::Border));
+        assert_eq!("Flash".parse(), Ok(VisualBeep::Flash));
+        assert_eq!("icon".parse(), Ok(VisualBeep::Icon));
+        assert_eq!(
+            "bell".parse::<VisualBeep>(),
+            Err(VisualBeepError("bell".to_string()))
+        );
+        assert_eq!(VisualBeep::Icon.to_string(), "icon");
+    }
+
+    #[test]
+    fn visual_beeps_cover_their_part_of_the_screen() {
+        let contains = |rects: &[Rect], x, y| rects.iter().any(|rect| rect.contains_point((x, y)));
+
+        // 800 × 600 makes the cue's pixels 10 screen pixels wide
+        let border = VisualBeep::Border.cue(800, 600);
+        for (x, y) in [
+            (0, 0),
+            (799, 599),
+            (400, 19),
+            (400, 580),
+            (19, 300),
+            (780, 300),
+        ] {
+            assert!(contains(&border, x, y), "({x}, {y})");
+        }
+        for (x, y) in [(400, 300), (400, 20), (20, 300), (779, 579)] {
+            assert!(!contains(&border, x, y), "({x}, {y})");
+        }
+
+        assert_eq!(VisualBeep::Flash.cue(800, 600), [Rect::new(0, 0, 800, 600)]);
+
+        // The speaker's cone starts at its left edge, 10 pixels from the top right corner
+        let icon = VisualBeep::Icon.cue(800, 600);
+        assert_eq!(icon.len(), 24);
+        assert!(contains(&icon, 700, 40));
+        assert!(icon
+            .iter()
+            .all(|rect| rect.left() >= 700 && rect.right() <= 780 && rect.top() >= 20));
+
+        // Even the smallest window has room for every cue, which stay on it
+        for beep in [VisualBeep::Border, VisualBeep::Flash, VisualBeep::Icon] {
+            let cue = beep.cue(64, 32);
+            assert!(!cue.is_empty());
+            assert!(cue
+                .iter()
+                .all(|rect| rect.left() >= 0 && rect.right() <= 64 && rect.bottom() <= 32));
+        }
+    }
+}
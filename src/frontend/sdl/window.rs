@@ -0,0 +1,151 @@
+use sdl2::rect::Rect;
+
+use crate::emu::io::{GRID_HEIGHT, GRID_WIDTH};
+
+///
+/// The `MIN_VISIBLE` constant is how much of a window, in pixels each way, has to be on a display
+/// for it to be reachable with the mouse there.
+///
+pub const MIN_VISIBLE: u32 = 48;
+
+///
+/// The `MIN_WINDOW_SIZE` constant is the smallest screen the window lays out, a window pixel for
+/// every pixel of the CHIP-8 screen.
+///
+pub const MIN_WINDOW_SIZE: (u32, u32) = (GRID_WIDTH as u32, GRID_HEIGHT as u32);
+
+///
+/// The `WindowGeometry` struct represents where the window is and how it shows the screen.
+///
+/// That's its position and size while windowed, whether it's fullscreen and whether the screen is
+/// only scaled by whole multiples.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowGeometry {
+    /// None centers the window
+    pub position: Option<(i32, i32)>,
+    pub size: (u32, u32),
+    pub fullscreen: bool,
+    pub integer_scale: bool,
+}
+
+impl WindowGeometry {
+    ///
+    /// Returns a window of `size` centered on the screen, windowed and scaled freely.
+    ///
+    pub const fn centered(size: (u32, u32)) -> Self {
+        Self {
+            position: None,
+            size,
+            fullscreen: false,
+            integer_scale: false,
+        }
+    }
+}
+
+///
+/// Returns `position` if at least `MIN_VISIBLE` pixels each way of a window of `size` there are
+/// on one of the `displays`, none if it'd be lost off them, e.g. on a monitor that was unplugged.
+///
+pub fn visible_position(
+    position: (i32, i32),
+    size: (u32, u32),
+    displays: &[Rect],
+) -> Option<(i32, i32)> {
+    let window = Rect::new(position.0, position.1, size.0.max(1), size.1.max(1));
+    displays
+        .iter()
+        .filter_map(|display| display.intersection(window))
+        .any(|visible| {
+            visible.width() >= MIN_VISIBLE.min(size.0)
+                && visible.height() >= MIN_VISIBLE.min(size.1)
+        })
+        .then_some(position)
+}
+
+///
+/// Returns the size a window asked to be `size` opens at: shrunk to fit the `desktop` when its
+/// size is known, and grown to `MIN_WINDOW_SIZE` either way.
+///
+pub fn fit_window(size: (u32, u32), desktop: Option<(u32, u32)>) -> (u32, u32) {
+    let (width, height) = desktop.map_or(size, |desktop| {
+        (size.0.min(desktop.0), size.1.min(desktop.1))
+    });
+    (width.max(MIN_WINDOW_SIZE.0), height.max(MIN_WINDOW_SIZE.1))
+}
+
+#[cfg(test)]
+mod window_tests {
+    use sdl2::rect::Rect;
+
+    use super::{fit_window, visible_position, WindowGeometry};
+
+    fn monitors() -> [Rect; 2] {
+        [Rect::new(0, 0, 1920, 1080), Rect::new(1920, 0, 1280, 1024)]
+    }
+
+    #[test]
+    fn positions_on_a_display_are_kept() {
+        assert_eq!(
+            visible_position((100, 80), (800, 600), &monitors()),
+            Some((100, 80))
+        );
+        // On the second monitor, and straddling both
+        assert_eq!(
+            visible_position((2200, 300), (800, 600), &monitors()),
+            Some((2200, 300))
+        );
+        assert_eq!(
+            visible_position((1500, 0), (800, 600), &monitors()),
+            Some((1500, 0))
+        );
+        // Mostly off the left edge, yet with enough left to grab
+        assert_eq!(
+            visible_position((-700, 10), (800, 600), &monitors()),
+            Some((-700, 10))
+        );
+    }
+
+    #[test]
+    fn positions_off_every_display_fall_back() {
+        // Where an unplugged third monitor was
+        assert_eq!(visible_position((3300, 200), (800, 600), &monitors()), None);
+        // Below the second monitor, which is shorter than the first
+        assert_eq!(
+            visible_position((2000, 1050), (800, 600), &monitors()),
+            None
+        );
+        // A sliver on screen isn't enough to grab
+        assert_eq!(visible_position((-780, 10), (800, 600), &monitors()), None);
+        assert_eq!(visible_position((100, 80), (800, 600), &[]), None);
+    }
+
+    #[test]
+    fn windows_fit_between_the_screen_and_the_desktop() {
+        let desktop = Some((1920, 1080));
+        let cases = [
+            ((800, 600), desktop, (800, 600)),
+            ((64, 32), desktop, (64, 32)),
+            ((20_000, 20_000), desktop, (1920, 1080)),
+            ((20_000, 300), desktop, (1920, 300)),
+            ((0, 0), desktop, (64, 32)),
+            ((10, 5000), desktop, (64, 1080)),
+            // Without a desktop to go by, only the minimum holds
+            ((20_000, 20_000), None, (20_000, 20_000)),
+            ((0, 3), None, (64, 32)),
+            // Not even a desktop smaller than the screen shrinks it further
+            ((800, 600), Some((40, 20)), (64, 32)),
+        ];
+        for (size, desktop, fitted) in cases {
+            assert_eq!(fit_window(size, desktop), fitted, "{size:?} on {desktop:?}");
+        }
+    }
+
+    #[test]
+    fn centered_windows() {
+        let geometry = WindowGeometry::centered((800, 600));
+        assert_eq!(geometry.position, None);
+        assert_eq!(geometry.size, (800, 600));
+        assert!(!geometry.fullscreen);
+    }
+}
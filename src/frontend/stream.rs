@@ -0,0 +1,431 @@
+use std::io::{self, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "egui")]
+use super::DebugView;
+use super::{AudioSink, Frontend, Input, Keypad};
+use crate::emu::chip8x::ColorZones;
+use crate::emu::disasm::ListingLine;
+use crate::emu::io::{GRID_HEIGHT, GRID_WIDTH};
+use crate::emu::mega::ColorPalette;
+use crate::emu::stats::FrameGraph;
+use crate::emu::stream::{pack, unpack, Decoder, Encoder, PackedScreen, Packet, StreamError};
+
+///
+/// The `QUEUED_SCREENS` constant is how many screens wait for the connection before new ones are
+/// dropped.
+///
+pub const QUEUED_SCREENS: usize = 4;
+
+///
+/// The `RECONNECT_INTERVAL` constant is how long the sender waits between tries to reach the
+/// spectator.
+///
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+// A spectator that stops reading for that long is hung up on, and tried again later
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+///
+/// The `StreamSender` struct represents a connection to a spectator, written by a thread of its own.
+///
+/// Screens are queued for that thread rather than sent right away, so neither a slow spectator nor
+/// a missing one ever stalls the emulation: once `QUEUED_SCREENS` are waiting, new ones are dropped
+/// and counted. The thread connects again whenever the connection drops, starting over with a
+/// full screen.
+///
+#[derive(Debug)]
+pub struct StreamSender {
+    screens: SyncSender<(u32, PackedScreen)>,
+    dropped: u64,
+}
+
+impl StreamSender {
+    pub fn connect(address: SocketAddr) -> Self {
+        let (screens, receiver) = mpsc::sync_channel(QUEUED_SCREENS);
+        thread::spawn(move || send_screens(address, &receiver));
+        Self {
+            screens,
+            dropped: 0,
+        }
+    }
+
+    ///
+    /// Queues the framebuffer (one byte, 0 or 1, per pixel) shown by `frame`, or drops it if the
+    /// connection is behind.
+    ///
+    pub fn push(&mut self, frame: u32, framebuffer: &[u8]) {
+        if let Err(TrySendError::Full(_)) = self.screens.try_send((frame, pack(framebuffer))) {
+            self.dropped += 1;
+        }
+    }
+
+    ///
+    /// Returns how many screens were dropped because the connection couldn't keep up.
+    ///
+    pub const fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+///
+/// Sends the queued screens to `address` until the sender hangs up, connecting again whenever the
+/// connection drops.
+///
+fn send_screens(address: SocketAddr, screens: &Receiver<(u32, PackedScreen)>) {
+    let mut connection: Option<TcpStream> = None;
+    let mut encoder = Encoder::new();
+    let mut retry_at = Instant::now();
+    // Whether the last try failed, so a missing spectator is only reported once
+    let mut unreachable = false;
+    let mut bytes = Vec::new();
+    for (frame, screen) in screens {
+        if connection.is_none() && Instant::now() >= retry_at {
+            match connect(address) {
+                Ok(stream) => {
+                    log::info!("Streaming to {address}.");
+                    encoder.reset();
+                    unreachable = false;
+                    connection = Some(stream);
+                }
+                Err(err) => {
+                    if !unreachable {
+                        log::warn!("Failed to reach the spectator at {address}, retrying. Error => `{err}`");
+                    }
+                    unreachable = true;
+                    retry_at = Instant::now() + RECONNECT_INTERVAL;
+                }
+            }
+        }
+        let Some(stream) = &mut connection else {
+            continue;
+        };
+
+        bytes.clear();
+        encoder.encode(frame, &screen).encode(&mut bytes);
+        if let Err(err) = stream.write_all(&bytes) {
+            log::warn!("Lost the spectator at {address}, reconnecting. Error => `{err}`");
+            connection = None;
+            retry_at = Instant::now() + RECONNECT_INTERVAL;
+        }
+    }
+}
+
+fn connect(address: SocketAddr) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(&address, RECONNECT_INTERVAL)?;
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    Ok(stream)
+}
+
+///
+/// The `StreamFrontend` struct represents a frontend streaming what another one shows to a
+/// spectator.
+///
+/// The last screen presented is sent whenever it changed, every time a frame of the run loop ends.
+///
+pub struct StreamFrontend {
+    frontend: Box<dyn Frontend>,
+    sender: StreamSender,
+    frame: u32,
+    framebuffer: Vec<u8>,
+    changed: bool,
+    /// Whether the screen is MegaChip's, colored by a palette
+    mega: bool,
+    /// Whether MegaChip's screens were warned about, only done once
+    warned_mega: bool,
+}
+
+impl StreamFrontend {
+    pub fn new(frontend: Box<dyn Frontend>, sender: StreamSender) -> Self {
+        Self {
+            frontend,
+            sender,
+            frame: 0,
+            framebuffer: Vec::new(),
+            changed: false,
+            mega: false,
+            warned_mega: false,
+        }
+    }
+}
+
+impl Drop for StreamFrontend {
+    fn drop(&mut self) {
+        let dropped = self.sender.dropped();
+        if dropped > 0 {
+            log::warn!("Dropped {dropped} screens the spectator couldn't keep up with.");
+        }
+    }
+}
+
+impl Keypad for StreamFrontend {
+    fn poll_input(&mut self) -> Option<Input> {
+        self.frontend.poll_input()
+    }
+
+    fn wait_input(&mut self, timeout: Duration) -> Option<Input> {
+        self.frontend.wait_input(timeout)
+    }
+}
+
+impl AudioSink for StreamFrontend {
+    fn set_tone(&mut self, on: bool) {
+        self.frontend.set_tone(on);
+    }
+}
+
+impl Frontend for StreamFrontend {
+    fn present(&mut self, framebuffer: &[u8]) {
+        self.framebuffer.clear();
+        if self.mega {
+            // Only the screens of 64 columns are sent, MegaChip's showing as blank
+            self.framebuffer.resize(GRID_WIDTH * GRID_HEIGHT, 0);
+        } else {
+            self.framebuffer.extend_from_slice(framebuffer);
+        }
+        self.changed = true;
+        self.frontend.present(framebuffer);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.frontend.set_title(title);
+    }
+
+    fn set_overlay(&mut self, lines: &[String]) {
+        self.frontend.set_overlay(lines);
+    }
+
+    fn set_pane(&mut self, lines: &[ListingLine]) {
+        self.frontend.set_pane(lines);
+    }
+
+    #[cfg(feature = "egui")]
+    fn set_debug_view(&mut self, view: Option<&DebugView>) {
+        self.frontend.set_debug_view(view);
+    }
+
+    fn set_frame_graph(&mut self, graph: Option<&FrameGraph>) {
+        self.frontend.set_frame_graph(graph);
+    }
+
+    fn set_pixel_ages(&mut self, ages: Option<&[u8]>) {
+        self.frontend.set_pixel_ages(ages);
+    }
+
+    fn set_palette(&mut self, palette: Option<&ColorPalette>) {
+        if palette.is_some() && !self.warned_mega {
+            self.warned_mega = true;
+            log::warn!("MegaChip's screens can't be streamed, the spectator sees them blank.");
+        }
+        self.mega = palette.is_some();
+        self.frontend.set_palette(palette);
+    }
+
+    fn set_color_zones(&mut self, zones: Option<&ColorZones>) {
+        self.frontend.set_color_zones(zones);
+    }
+
+    fn set_help(&mut self, shown: bool) {
+        self.frontend.set_help(shown);
+    }
+
+    fn end_frame(&mut self) {
+        if self.changed {
+            self.sender.push(self.frame, &self.framebuffer);
+            self.changed = false;
+        }
+        self.frame = self.frame.wrapping_add(1);
+        self.frontend.end_frame();
+    }
+}
+
+///
+/// The `SpectatedFrame` struct represents a screen received from a stream, one byte (0 or 1) per
+/// pixel, with the number of the frame that showed it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpectatedFrame {
+    pub frame: u32,
+    pub framebuffer: Vec<u8>,
+}
+
+///
+/// The `Spectator` struct represents a TCP server taking streams of screens, each connection read
+/// by a thread of its own queueing the screens it decodes.
+///
+pub struct Spectator {
+    address: SocketAddr,
+    frames: Receiver<SpectatedFrame>,
+}
+
+impl Spectator {
+    ///
+    /// Starts listening on `address`, port 0 picking any free one.
+    ///
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let (sender, frames) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || receive(stream, &sender));
+            }
+        });
+        Ok(Self { address, frames })
+    }
+
+    pub const fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    ///
+    /// Waits up to `timeout` for a screen, returning the latest one received, if any. The screens
+    /// before it are skipped, a spectator that fell behind catching up at once.
+    ///
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<SpectatedFrame> {
+        let first = match self.frames.recv_timeout(timeout) {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return None,
+        };
+        Some(self.frames.try_iter().last().unwrap_or(first))
+    }
+}
+
+///
+/// Decodes the screens of a stream until it ends, or the spectator stops taking them.
+///
+fn receive(stream: TcpStream, frames: &Sender<SpectatedFrame>) {
+    let peer = stream
+        .peer_addr()
+        .map_or_else(|_| "a sender".to_string(), |peer| peer.to_string());
+    log::info!("Spectating {peer}.");
+    let mut reader = BufReader::new(stream);
+    let mut decoder = Decoder::new();
+    loop {
+        let screen = Packet::read(&mut reader).and_then(|packet| {
+            let screen = decoder.apply(&packet)?;
+            Ok(SpectatedFrame {
+                frame: packet.frame(),
+                framebuffer: unpack(screen),
+            })
+        });
+        match screen {
+            Ok(screen) => {
+                if frames.send(screen).is_err() {
+                    return;
+                }
+            }
+            Err(StreamError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                log::info!("The stream from {peer} ended.");
+                return;
+            }
+            Err(err) => {
+                log::warn!("Dropped the stream from {peer}. Error => `{err}`");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod stream_tests {
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::time::{Duration, Instant};
+
+    use super::{Spectator, StreamFrontend, StreamSender};
+    use crate::emu::io::{GRID_HEIGHT, GRID_WIDTH, HIRES_GRID_HEIGHT};
+    use crate::frontend::{Frontend, HeadlessFrontend};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn framebuffer(lit: usize) -> Vec<u8> {
+        let mut framebuffer = vec![0; GRID_WIDTH * GRID_HEIGHT];
+        framebuffer[lit] = 1;
+        framebuffer
+    }
+
+    ///
+    /// Waits for the spectator to show the screen of `frame`.
+    ///
+    fn wait_for(spectator: &Spectator, frame: u32) -> Vec<u8> {
+        let deadline = Instant::now() + TIMEOUT;
+        while Instant::now() < deadline {
+            if let Some(received) = spectator.recv_timeout(Duration::from_millis(50)) {
+                if received.frame == frame {
+                    return received.framebuffer;
+                }
+            }
+        }
+        panic!("frame {frame} never came");
+    }
+
+    #[test]
+    fn spectators_see_the_presented_screens() {
+        let spectator = Spectator::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let sender = StreamSender::connect(spectator.address());
+        let mut frontend = StreamFrontend::new(Box::new(HeadlessFrontend), sender);
+        frontend.present(&framebuffer(3));
+        frontend.end_frame();
+        assert_eq!(wait_for(&spectator, 0), framebuffer(3));
+
+        // Frames showing nothing new aren't sent, the next one is a delta
+        frontend.end_frame();
+        frontend.present(&framebuffer(2047));
+        frontend.end_frame();
+        assert_eq!(wait_for(&spectator, 2), framebuffer(2047));
+
+        // The 64 rows of HiRes CHIP-8 all make it
+        let mut tall = vec![0; GRID_WIDTH * HIRES_GRID_HEIGHT];
+        tall[GRID_WIDTH * HIRES_GRID_HEIGHT - 1] = 1;
+        frontend.present(&tall);
+        frontend.end_frame();
+        assert_eq!(wait_for(&spectator, 3), tall);
+    }
+
+    #[test]
+    fn missing_spectators_dont_stall_the_emulation() {
+        // Nothing listens on a port just freed
+        let address = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut sender = StreamSender::connect(address);
+        let start = Instant::now();
+        for frame in 0..1000 {
+            sender.push(frame, &framebuffer(frame as usize));
+        }
+        assert!(start.elapsed() < TIMEOUT);
+
+        // A spectator starting late gets the screens from then on
+        let spectator = Spectator::bind(address).unwrap();
+        let deadline = Instant::now() + TIMEOUT;
+        let mut frame = 1000;
+        while spectator.recv_timeout(Duration::from_millis(10)).is_none() {
+            assert!(Instant::now() < deadline, "the sender never reconnected");
+            sender.push(frame, &framebuffer(5));
+            frame += 1;
+        }
+    }
+
+    #[test]
+    fn slow_spectators_get_frames_dropped() {
+        // A spectator that never reads, the connections waiting to be accepted
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut sender = StreamSender::connect(listener.local_addr().unwrap());
+        let start = Instant::now();
+        for frame in 0..100_000 {
+            // Whole screens every time, to fill the socket's buffers
+            let lit: Vec<_> = (0..GRID_WIDTH * GRID_HEIGHT)
+                .map(|pixel| u8::from((pixel + frame) % 2 == 0))
+                .collect();
+            sender.push(frame as u32, &lit);
+        }
+        assert!(start.elapsed() < TIMEOUT * 2);
+        assert!(sender.dropped() > 0);
+    }
+}
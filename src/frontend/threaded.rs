@@ -0,0 +1,409 @@
+use std::mem;
+use std::panic;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(feature = "egui")]
+use super::DebugView;
+use super::{AudioSink, Frontend, Input, Keypad};
+use crate::emu::chip8x::ColorZones;
+use crate::emu::disasm::ListingLine;
+use crate::emu::mega::ColorPalette;
+use crate::emu::stats::FrameGraph;
+
+///
+/// How long the main thread waits for the emulation's output before polling the frontend's input
+/// again.
+///
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+///
+/// The `Output` enum represents what the emulation thread asks the frontend to do, one message per
+/// call of the `Frontend` trait.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    /// A snapshot of the framebuffer to show
+    Present(Vec<u8>),
+    Title(String),
+    Overlay(Vec<String>),
+    Pane(Vec<ListingLine>),
+    #[cfg(feature = "egui")]
+    DebugView(Option<DebugView>),
+    FrameGraph(Option<FrameGraph>),
+    PixelAges(Option<Vec<u8>>),
+    Palette(Option<Box<ColorPalette>>),
+    ColorZones(Option<Box<ColorZones>>),
+    Help(bool),
+    Tone(bool),
+    EndFrame,
+}
+
+///
+/// The `ChannelFrontend` struct represents the frontend of an emulator running on a thread of its
+/// own: it sends what it's asked to show to the main thread, and takes the input from there.
+///
+#[derive(Debug)]
+pub struct ChannelFrontend {
+    output: Sender<Output>,
+    input: Receiver<Input>,
+}
+
+impl ChannelFrontend {
+    fn send(&self, output: Output) {
+        // The main thread only stops listening once the emulation is over
+        let _ = self.output.send(output);
+    }
+}
+
+impl Keypad for ChannelFrontend {
+    fn poll_input(&mut self) -> Option<Input> {
+        match self.input.try_recv() {
+            Ok(input) => Some(input),
+            Err(TryRecvError::Empty) => None,
+            // Nobody is left to close the window, the run ends
+            Err(TryRecvError::Disconnected) => Some(Input::Quit),
+        }
+    }
+
+    fn wait_input(&mut self, timeout: Duration) -> Option<Input> {
+        match self.input.recv_timeout(timeout) {
+            Ok(input) => Some(input),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(Input::Quit),
+        }
+    }
+}
+
+impl AudioSink for ChannelFrontend {
+    fn set_tone(&mut self, on: bool) {
+        self.send(Output::Tone(on));
+    }
+}
+
+impl Frontend for ChannelFrontend {
+    fn present(&mut self, framebuffer: &[u8]) {
+        self.send(Output::Present(framebuffer.to_vec()));
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.send(Output::Title(title.to_string()));
+    }
+
+    fn set_overlay(&mut self, lines: &[String]) {
+        self.send(Output::Overlay(lines.to_vec()));
+    }
+
+    fn set_pane(&mut self, lines: &[ListingLine]) {
+        self.send(Output::Pane(lines.to_vec()));
+    }
+
+    #[cfg(feature = "egui")]
+    fn set_debug_view(&mut self, view: Option<&DebugView>) {
+        self.send(Output::DebugView(view.cloned()));
+    }
+
+    fn set_frame_graph(&mut self, graph: Option<&FrameGraph>) {
+        self.send(Output::FrameGraph(graph.cloned()));
+    }
+
+    fn set_pixel_ages(&mut self, ages: Option<&[u8]>) {
+        self.send(Output::PixelAges(ages.map(<[u8]>::to_vec)));
+    }
+
+    fn set_palette(&mut self, palette: Option<&ColorPalette>) {
+        self.send(Output::Palette(palette.cloned().map(Box::new)));
+    }
+
+    fn set_color_zones(&mut self, zones: Option<&ColorZones>) {
+        self.send(Output::ColorZones(zones.cloned().map(Box::new)));
+    }
+
+    fn set_help(&mut self, shown: bool) {
+        self.send(Output::Help(shown));
+    }
+
+    fn end_frame(&mut self) {
+        self.send(Output::EndFrame);
+    }
+}
+
+///
+/// The `FrontendHost` struct represents the main thread's side of a threaded emulator: it owns the
+/// real frontend, shows what the emulation sends and forwards the input to it.
+///
+pub struct FrontendHost {
+    frontend: Box<dyn Frontend>,
+    output: Receiver<Output>,
+    input: Sender<Input>,
+    // The last frame received and not shown yet, older ones being skipped
+    frame: Option<Vec<u8>>,
+    end_frame: bool,
+}
+
+///
+/// Returns the two ends connecting an emulator on another thread to the frontend on this one.
+///
+pub fn channel(frontend: Box<dyn Frontend>) -> (FrontendHost, ChannelFrontend) {
+    let (output_tx, output_rx) = mpsc::channel();
+    let (input_tx, input_rx) = mpsc::channel();
+    let host = FrontendHost {
+        frontend,
+        output: output_rx,
+        input: input_tx,
+        frame: None,
+        end_frame: false,
+    };
+    let channel = ChannelFrontend {
+        output: output_tx,
+        input: input_rx,
+    };
+    (host, channel)
+}
+
+impl FrontendHost {
+    ///
+    /// Forwards the frontend's pending input, then shows what the emulation sent, waiting for it
+    /// briefly if it sent nothing. Returns `false` once the emulation ended.
+    ///
+    pub fn pump(&mut self) -> bool {
+        while let Some(input) = self.frontend.poll_input() {
+            // The emulation may have just ended, the input then goes nowhere
+            let _ = self.input.send(input);
+        }
+
+        let mut running = match self.output.recv_timeout(POLL_INTERVAL) {
+            Ok(output) => {
+                self.apply(output);
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => false,
+        };
+        while running {
+            match self.output.try_recv() {
+                Ok(output) => self.apply(output),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => running = false,
+            }
+        }
+        self.show();
+        running
+    }
+
+    fn apply(&mut self, output: Output) {
+        match output {
+            Output::Present(framebuffer) => self.frame = Some(framebuffer),
+            Output::Title(title) => self.frontend.set_title(&title),
+            Output::Overlay(lines) => self.frontend.set_overlay(&lines),
+            Output::Pane(lines) => self.frontend.set_pane(&lines),
+            #[cfg(feature = "egui")]
+            Output::DebugView(view) => self.frontend.set_debug_view(view.as_ref()),
+            Output::FrameGraph(graph) => self.frontend.set_frame_graph(graph.as_ref()),
+            Output::PixelAges(ages) => self.frontend.set_pixel_ages(ages.as_deref()),
+            Output::Palette(palette) => self.frontend.set_palette(palette.as_deref()),
+            Output::ColorZones(zones) => self.frontend.set_color_zones(zones.as_deref()),
+            Output::Help(shown) => self.frontend.set_help(shown),
+            Output::Tone(on) => self.frontend.set_tone(on),
+            Output::EndFrame => self.end_frame = true,
+        }
+    }
+
+    ///
+    /// Shows the last frame received with the overlays set so far, ending the frame once.
+    ///
+    fn show(&mut self) {
+        if let Some(framebuffer) = self.frame.take() {
+            self.frontend.present(&framebuffer);
+        }
+        if mem::take(&mut self.end_frame) {
+            self.frontend.end_frame();
+        }
+    }
+}
+
+///
+/// Runs `emulate` on a thread of its own, showing its output on `frontend` from this one.
+///
+/// This thread never waits on the emulation for longer than a millisecond. Returns what `emulate`
+/// returned.
+///
+/// # Panics
+///
+/// Panics if the thread can't be spawned, and carries on the panic of `emulate`.
+///
+pub fn run<T, F>(frontend: Box<dyn Frontend>, emulate: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce(ChannelFrontend) -> T + Send + 'static,
+{
+    let (mut host, channel) = channel(frontend);
+    let worker = thread::Builder::new()
+        .name("emulation".to_string())
+        .spawn(move || emulate(channel))
+        .expect("Failed to spawn the emulation thread");
+    while host.pump() {}
+    worker
+        .join()
+        .unwrap_or_else(|panic| panic::resume_unwind(panic))
+}
+
+#[cfg(test)]
+mod threaded_tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+    use std::time::Instant;
+
+    use super::{channel, run, POLL_INTERVAL};
+    use crate::emu::disasm::ListingLine;
+    use crate::frontend::{AudioSink, Frontend, Input, Keypad};
+
+    ///
+    /// The `RecordingFrontend` struct represents a frontend writing down every call it gets, and
+    /// handing out the inputs it's given.
+    ///
+    #[derive(Default)]
+    struct RecordingFrontend {
+        calls: Rc<RefCell<Vec<String>>>,
+        inputs: VecDeque<Input>,
+    }
+
+    impl Keypad for RecordingFrontend {
+        fn poll_input(&mut self) -> Option<Input> {
+            self.inputs.pop_front()
+        }
+    }
+
+    impl AudioSink for RecordingFrontend {
+        fn set_tone(&mut self, on: bool) {
+            self.calls.borrow_mut().push(format!("tone {on}"));
+        }
+    }
+
+    impl Frontend for RecordingFrontend {
+        fn present(&mut self, framebuffer: &[u8]) {
+            self.calls
+                .borrow_mut()
+                .push(format!("present {framebuffer:?}"));
+        }
+
+        fn set_title(&mut self, title: &str) {
+            self.calls.borrow_mut().push(format!("title {title}"));
+        }
+
+        fn set_overlay(&mut self, lines: &[String]) {
+            self.calls.borrow_mut().push(format!("overlay {lines:?}"));
+        }
+
+        fn set_pane(&mut self, lines: &[ListingLine]) {
+            let lines = lines.len();
+            self.calls.borrow_mut().push(format!("pane {lines}"));
+        }
+
+        fn end_frame(&mut self) {
+            self.calls.borrow_mut().push("end".to_string());
+        }
+    }
+
+    #[test]
+    fn outputs_reach_the_frontend_in_order() {
+        let recording = RecordingFrontend::default();
+        let calls = Rc::clone(&recording.calls);
+        let (mut host, mut frontend) = channel(Box::new(recording));
+
+        frontend.set_title("PONG");
+        frontend.set_tone(true);
+        frontend.present(&[1, 0]);
+        frontend.set_overlay(&["PC 0x200".to_string()]);
+        frontend.present(&[0, 1]);
+        frontend.set_pane(&[]);
+        frontend.end_frame();
+        assert!(host.pump());
+        // Only the last frame is shown, once the overlays it goes with are set
+        assert_eq!(
+            *calls.borrow(),
+            [
+                "title PONG",
+                "tone true",
+                "overlay [\"PC 0x200\"]",
+                "pane 0",
+                "present [0, 1]",
+                "end"
+            ]
+        );
+
+        calls.borrow_mut().clear();
+        assert!(host.pump());
+        assert!(calls.borrow().is_empty());
+
+        drop(frontend);
+        assert!(!host.pump());
+    }
+
+    #[test]
+    fn inputs_reach_the_emulation() {
+        let at = Instant::now();
+        let recording = RecordingFrontend {
+            inputs: VecDeque::from([
+                Input::TogglePause,
+                Input::Key {
+                    hex_key: 5,
+                    pressed: true,
+                    at,
+                },
+            ]),
+            ..RecordingFrontend::default()
+        };
+        let (mut host, mut frontend) = channel(Box::new(recording));
+        assert_eq!(frontend.poll_input(), None);
+        assert!(host.pump());
+        assert_eq!(frontend.poll_input(), Some(Input::TogglePause));
+        assert_eq!(
+            frontend.wait_input(POLL_INTERVAL),
+            Some(Input::Key {
+                hex_key: 5,
+                pressed: true,
+                at
+            })
+        );
+        assert_eq!(frontend.wait_input(POLL_INTERVAL), None);
+
+        // Without a host, nothing can stop the run but quitting
+        drop(host);
+        assert_eq!(frontend.poll_input(), Some(Input::Quit));
+        assert_eq!(frontend.wait_input(POLL_INTERVAL), Some(Input::Quit));
+    }
+
+    #[test]
+    fn runs_until_the_emulation_returns() {
+        let recording = RecordingFrontend {
+            inputs: VecDeque::from([Input::Step, Input::Quit]),
+            ..RecordingFrontend::default()
+        };
+        let calls = Rc::clone(&recording.calls);
+        let seen = run(Box::new(recording), |mut frontend| {
+            let mut seen = Vec::new();
+            loop {
+                if let Some(input) = frontend.wait_input(POLL_INTERVAL * 10) {
+                    frontend.present(&[seen.len() as u8]);
+                    if input == Input::Quit {
+                        return seen;
+                    }
+                    seen.push(input);
+                }
+            }
+        });
+        assert_eq!(seen, [Input::Step]);
+        assert_eq!(calls.borrow().last().unwrap(), "present [1]");
+    }
+
+    #[test]
+    #[should_panic(expected = "emulation crashed")]
+    fn emulation_panics_carry_on() {
+        run(Box::new(RecordingFrontend::default()), |_| {
+            panic!("emulation crashed");
+        });
+    }
+}
@@ -0,0 +1,666 @@
+use std::io::{self, Write};
+use std::iter;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[cfg(feature = "egui")]
+use super::DebugView;
+use super::{AudioSink, Frontend, Input, Keypad};
+use crate::emu::chip8::FRAMES_PER_SECOND;
+use crate::emu::chip8x::ColorZones;
+use crate::emu::disasm::ListingLine;
+use crate::emu::io::{GRID_HEIGHT, GRID_WIDTH};
+use crate::emu::mega::ColorPalette;
+use crate::emu::screenshot::{Palette, WINDOW_PALETTE};
+use crate::emu::stats::FrameGraph;
+
+///
+/// The `QUEUED_FRAMES` constant is how many frames wait for the encoder before new ones are
+/// dropped, a few seconds' worth of hiccups at most.
+///
+pub const QUEUED_FRAMES: usize = 8;
+
+///
+/// Returns the `ffmpeg` command encoding the raw RGB frames of `size` read from its standard input,
+/// at the emulator's frame rate, into `out`, the container and codec picked from its extension.
+///
+pub fn ffmpeg_command(ffmpeg: &str, size: (u32, u32), out: &str) -> Command {
+    let mut command = Command::new(ffmpeg);
+    command
+        .args([
+            "-loglevel",
+            "error",
+            "-y",
+            "-f",
+            "rawvideo",
+            "-pixel_format",
+            "rgb24",
+        ])
+        .arg("-video_size")
+        .arg(format!("{}x{}", size.0, size.1))
+        .arg("-framerate")
+        .arg(FRAMES_PER_SECOND.to_string())
+        .args(["-i", "-", "-pix_fmt", "yuv420p"])
+        .arg(out);
+    command
+}
+
+///
+/// Checks that `ffmpeg` can be started, so a missing one is told before anything was recorded.
+///
+/// # Errors
+///
+/// Fails when it can't be started, `NotFound` most likely meaning it isn't installed.
+///
+pub fn find_encoder(ffmpeg: &str) -> io::Result<()> {
+    Command::new(ffmpeg)
+        .arg("-version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|_| ())
+}
+
+// Opens the encoder for frames of the given size, returning its input and its process if it has
+type Opener =
+    Box<dyn FnOnce((u32, u32)) -> io::Result<(Box<dyn Write + Send>, Option<Child>)> + Send>;
+
+///
+/// The `VideoStats` struct represents how a recording went: the frames the encoder was sent, and
+/// the ones dropped because it couldn't keep up.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VideoStats {
+    pub frames: u64,
+    pub dropped: u64,
+}
+
+///
+/// The `VideoPipe` struct represents an encoder fed raw RGB frames, one after the other without
+/// any framing, by a thread of its own.
+///
+/// Frames are queued for that thread rather than written right away, so a slow encoder never
+/// stalls the emulation: once `QUEUED_FRAMES` are waiting, new ones are dropped and counted. A
+/// lossless pipe waits for the encoder instead.
+///
+/// The video is as tall as the first screen pushed, 32 rows or the 64 of HiRes CHIP-8; the
+/// screens of another size after it are cut or padded to it.
+///
+#[derive(Debug)]
+pub struct VideoPipe {
+    frames: Option<SyncSender<((u32, u32), Vec<u8>)>>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+    scale: u32,
+    palette: Palette,
+    /// Whether a full queue waits for the encoder rather than dropping the frame
+    lossless: bool,
+    /// How many rows the screens are recorded with, the first one's
+    rows: Option<usize>,
+    /// Whether a screen of another size was pushed, only warned about once
+    resized: bool,
+    stats: VideoStats,
+}
+
+impl VideoPipe {
+    ///
+    /// Writes the frames, every pixel scaled up to a `scale`×`scale` square, into `sink`.
+    ///
+    pub fn new<W: Write + Send + 'static>(sink: W, scale: u32) -> Self {
+        Self::open(
+            Box::new(move |_| Ok((Box::new(sink) as Box<dyn Write + Send>, None))),
+            scale,
+        )
+    }
+
+    ///
+    /// Starts the command made for the size of the frames, once the first one is pushed, and
+    /// writes the frames into its standard input, e.g. the one of `ffmpeg_command`. A command
+    /// failing to start is reported by `finish`.
+    ///
+    pub fn spawn(command: impl FnOnce((u32, u32)) -> Command + Send + 'static, scale: u32) -> Self {
+        Self::open(
+            Box::new(move |size| {
+                let mut child = command(size).stdin(Stdio::piped()).spawn()?;
+                let stdin = child
+                    .stdin
+                    .take()
+                    .ok_or_else(|| io::Error::other("the encoder's input isn't a pipe"))?;
+                Ok((Box::new(stdin) as Box<dyn Write + Send>, Some(child)))
+            }),
+            scale,
+        )
+    }
+
+    fn open(opener: Opener, scale: u32) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(QUEUED_FRAMES);
+        Self {
+            frames: Some(sender),
+            writer: Some(thread::spawn(move || write_frames(opener, &receiver))),
+            scale: scale.max(1),
+            palette: WINDOW_PALETTE,
+            lossless: false,
+            rows: None,
+            resized: false,
+            stats: VideoStats::default(),
+        }
+    }
+
+    ///
+    /// Makes the pipe wait for the encoder to catch up rather than drop frames, for runs that
+    /// aren't paced to real time anyway.
+    ///
+    #[must_use]
+    pub const fn lossless(mut self) -> Self {
+        self.lossless = true;
+        self
+    }
+
+    ///
+    /// Returns the size of the frames of a screen of `rows` rows, in pixels.
+    ///
+    pub const fn size(scale: u32, rows: usize) -> (u32, u32) {
+        let scale = if scale == 0 { 1 } else { scale };
+        (GRID_WIDTH as u32 * scale, rows as u32 * scale)
+    }
+
+    ///
+    /// Queues the framebuffer (one byte, 0 or 1, per pixel, 64 pixels to a row) for the encoder,
+    /// or drops it if the encoder is behind and the pipe isn't lossless. Nothing is queued once the
+    /// encoder stopped reading.
+    ///
+    pub fn push(&mut self, framebuffer: &[u8]) {
+        let Some(frames) = &self.frames else {
+            return;
+        };
+        let height = (framebuffer.len() / GRID_WIDTH).max(1);
+        let rows = *self.rows.get_or_insert(height);
+        if height != rows && !self.resized {
+            self.resized = true;
+            log::warn!(
+                "The screen changed to {GRID_WIDTH}×{height} pixels, the video stays {GRID_WIDTH}×{rows}."
+            );
+        }
+        let frame = (
+            Self::size(self.scale, rows),
+            rgb_frame(framebuffer, rows, self.scale, self.palette),
+        );
+        let sent = if self.lossless {
+            frames
+                .send(frame)
+                .map_err(|err| TrySendError::Disconnected(err.0))
+        } else {
+            frames.try_send(frame)
+        };
+        match sent {
+            Ok(()) => self.stats.frames += 1,
+            Err(TrySendError::Full(_)) => self.stats.dropped += 1,
+            // The writer's error is for `finish` to report
+            Err(TrySendError::Disconnected(_)) => self.frames = None,
+        }
+    }
+
+    pub const fn stats(&self) -> VideoStats {
+        self.stats
+    }
+
+    ///
+    /// Writes the frames still queued, closes the encoder's input and waits for it to exit.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the frames couldn't all be written, or the encoder exited with an error.
+    ///
+    pub fn finish(mut self) -> io::Result<VideoStats> {
+        self.close()?;
+        Ok(self.stats)
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        // Hanging up ends the writer once it wrote the last queued frame, closing the pipe
+        self.frames = None;
+        self.writer.take().map_or(Ok(()), |writer| {
+            writer
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("the writer panicked")))
+        })
+    }
+}
+
+impl Drop for VideoPipe {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+///
+/// Opens the encoder for the size of the first frame and writes the frames into it, then waits
+/// for it to exit.
+///
+fn write_frames(opener: Opener, frames: &Receiver<((u32, u32), Vec<u8>)>) -> io::Result<()> {
+    let mut frames = frames.iter();
+    let Some((size, first)) = frames.next() else {
+        return Ok(());
+    };
+    let (mut sink, child) = opener(size)?;
+    let written = iter::once(first)
+        .chain(frames.map(|(_, frame)| frame))
+        .try_for_each(|frame| sink.write_all(&frame))
+        .and_then(|()| sink.flush());
+    // Closing the pipe lets the encoder finish
+    drop(sink);
+    if let Some(mut child) = child {
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "the encoder exited with {status}"
+            )));
+        }
+    }
+    written
+}
+
+///
+/// Returns the first `rows` rows of the framebuffer as RGB24 pixels in the colors of `palette`,
+/// the missing ones blank, every pixel scaled up to a `scale`×`scale` square.
+///
+fn rgb_frame(framebuffer: &[u8], rows: usize, scale: u32, palette: Palette) -> Vec<u8> {
+    let scale = scale as usize;
+    let mut frame = Vec::with_capacity(GRID_WIDTH * rows * scale * scale * 3);
+    let blank = [0; GRID_WIDTH];
+    let screen_rows = framebuffer
+        .chunks_exact(GRID_WIDTH)
+        .chain(iter::repeat(&blank[..]));
+    for row in screen_rows.take(rows) {
+        let start = frame.len();
+        for &pixel in row {
+            let color = palette[usize::from(pixel != 0)];
+            for _ in 0..scale {
+                frame.extend_from_slice(&color);
+            }
+        }
+        for _ in 1..scale {
+            frame.extend_from_within(start..start + GRID_WIDTH * scale * 3);
+        }
+    }
+    frame
+}
+
+///
+/// The `VideoRecorder` struct represents a frontend recording what another one shows.
+///
+/// The last screen presented is sent to the encoder every time a frame of the run loop ends, so
+/// the video plays at the emulator's frame rate. The frames ending before anything was presented
+/// are recorded blank, in the size of the first screen.
+///
+pub struct VideoRecorder {
+    frontend: Box<dyn Frontend>,
+    video: Option<VideoPipe>,
+    /// The last screen presented, none until the first one
+    framebuffer: Vec<u8>,
+    /// How many frames ended before any screen was presented, recorded blank in its size
+    blank_frames: u64,
+    /// Whether the screen is MegaChip's, colored by a palette
+    mega: bool,
+    /// Whether MegaChip's screens were warned about, only done once
+    warned_mega: bool,
+}
+
+impl VideoRecorder {
+    pub fn new(frontend: Box<dyn Frontend>, video: VideoPipe) -> Self {
+        Self {
+            frontend,
+            video: Some(video),
+            framebuffer: Vec::new(),
+            blank_frames: 0,
+            mega: false,
+            warned_mega: false,
+        }
+    }
+}
+
+impl Drop for VideoRecorder {
+    fn drop(&mut self) {
+        let Some(video) = self.video.take() else {
+            return;
+        };
+        match video.finish() {
+            Ok(VideoStats { frames, dropped: 0 }) => {
+                log::info!("Recorded {frames} frames.");
+            }
+            Ok(VideoStats { frames, dropped }) => log::warn!(
+                "Recorded {frames} frames, dropped {dropped} the encoder couldn't keep up with."
+            ),
+            Err(err) => log::error!("Failed to record the video. Error => `{err}`"),
+        }
+    }
+}
+
+impl Keypad for VideoRecorder {
+    fn poll_input(&mut self) -> Option<Input> {
+        self.frontend.poll_input()
+    }
+
+    fn wait_input(&mut self, timeout: Duration) -> Option<Input> {
+        self.frontend.wait_input(timeout)
+    }
+}
+
+impl AudioSink for VideoRecorder {
+    fn set_tone(&mut self, on: bool) {
+        self.frontend.set_tone(on);
+    }
+}
+
+impl Frontend for VideoRecorder {
+    fn present(&mut self, framebuffer: &[u8]) {
+        self.framebuffer.clear();
+        if self.mega {
+            // Only the screens of 64 columns are sent, MegaChip's showing as blank
+            self.framebuffer.resize(GRID_WIDTH * GRID_HEIGHT, 0);
+        } else {
+            self.framebuffer.extend_from_slice(framebuffer);
+        }
+        self.frontend.present(framebuffer);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.frontend.set_title(title);
+    }
+
+    fn set_overlay(&mut self, lines: &[String]) {
+        self.frontend.set_overlay(lines);
+    }
+
+    fn set_pane(&mut self, lines: &[ListingLine]) {
+        self.frontend.set_pane(lines);
+    }
+
+    #[cfg(feature = "egui")]
+    fn set_debug_view(&mut self, view: Option<&DebugView>) {
+        self.frontend.set_debug_view(view);
+    }
+
+    fn set_frame_graph(&mut self, graph: Option<&FrameGraph>) {
+        self.frontend.set_frame_graph(graph);
+    }
+
+    fn set_pixel_ages(&mut self, ages: Option<&[u8]>) {
+        self.frontend.set_pixel_ages(ages);
+    }
+
+    fn set_palette(&mut self, palette: Option<&ColorPalette>) {
+        if palette.is_some() && !self.warned_mega {
+            self.warned_mega = true;
+            log::warn!("MegaChip's screens can't be recorded, the video shows them blank.");
+        }
+        self.mega = palette.is_some();
+        self.frontend.set_palette(palette);
+    }
+
+    fn set_color_zones(&mut self, zones: Option<&ColorZones>) {
+        self.frontend.set_color_zones(zones);
+    }
+
+    fn set_help(&mut self, shown: bool) {
+        self.frontend.set_help(shown);
+    }
+
+    fn end_frame(&mut self) {
+        if let Some(video) = &mut self.video {
+            if self.framebuffer.is_empty() {
+                self.blank_frames += 1;
+            } else {
+                let blank = vec![0; self.framebuffer.len()];
+                for _ in 0..std::mem::take(&mut self.blank_frames) {
+                    video.push(&blank);
+                }
+                video.push(&self.framebuffer);
+            }
+        }
+        self.frontend.end_frame();
+    }
+}
+
+#[cfg(test)]
+mod video_tests {
+    use std::io::{self, Write};
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::sync::{Arc, Mutex};
+
+    use super::{ffmpeg_command, find_encoder, rgb_frame, VideoPipe, VideoRecorder, QUEUED_FRAMES};
+    use crate::emu::io::{GRID_HEIGHT, GRID_WIDTH, HIRES_GRID_HEIGHT};
+    use crate::emu::screenshot::WINDOW_PALETTE;
+    use crate::frontend::{Frontend, HeadlessFrontend};
+
+    // The frames of a scale of 4
+    const FRAME_BYTES: usize = GRID_WIDTH * 4 * GRID_HEIGHT * 4 * 3;
+
+    ///
+    /// An encoder that only takes a frame when the test lets it, keeping what it took.
+    ///
+    struct GatedSink {
+        gate: Receiver<()>,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for GatedSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.gate.recv().map_err(io::Error::other)?;
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn gated() -> (VideoPipe, Sender<()>, Arc<Mutex<Vec<u8>>>) {
+        let (gate, receiver) = mpsc::channel();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink = GatedSink {
+            gate: receiver,
+            written: Arc::clone(&written),
+        };
+        (VideoPipe::new(sink, 4), gate, written)
+    }
+
+    fn framebuffer(lit: usize) -> Vec<u8> {
+        let mut framebuffer = vec![0; GRID_WIDTH * GRID_HEIGHT];
+        framebuffer[lit] = 1;
+        framebuffer
+    }
+
+    #[test]
+    fn frames_are_scaled_rgb() {
+        let frame = rgb_frame(&framebuffer(GRID_WIDTH + 1), GRID_HEIGHT, 2, WINDOW_PALETTE);
+        assert_eq!(frame.len(), GRID_WIDTH * GRID_HEIGHT * 4 * 3);
+        let at = |x: usize, y: usize| {
+            let index = (y * GRID_WIDTH * 2 + x) * 3;
+            &frame[index..index + 3]
+        };
+        // The pixel at (1, 1) is the square at (2, 2)
+        assert_eq!(at(2, 2), [0x00, 0xFF, 0x00]);
+        assert_eq!(at(3, 3), [0x00, 0xFF, 0x00]);
+        assert_eq!(at(1, 2), [0x00, 0x00, 0x00]);
+        assert_eq!(at(2, 4), [0x00, 0x00, 0x00]);
+        assert_eq!(VideoPipe::size(2, GRID_HEIGHT), (128, 64));
+
+        // Screens shorter than the video are padded, taller ones cut
+        let short = rgb_frame(&framebuffer(0), HIRES_GRID_HEIGHT, 1, WINDOW_PALETTE);
+        assert_eq!(short.len(), GRID_WIDTH * HIRES_GRID_HEIGHT * 3);
+        assert!(short[GRID_WIDTH * GRID_HEIGHT * 3..]
+            .iter()
+            .all(|&byte| byte == 0));
+        let tall = vec![1; GRID_WIDTH * HIRES_GRID_HEIGHT];
+        assert_eq!(
+            rgb_frame(&tall, GRID_HEIGHT, 1, WINDOW_PALETTE).len(),
+            GRID_WIDTH * GRID_HEIGHT * 3
+        );
+    }
+
+    #[test]
+    fn records_the_64_rows_of_hires_screens() {
+        let (video, gate, written) = gated();
+        let mut recorder = VideoRecorder::new(Box::new(HeadlessFrontend), video);
+        // A frame ending before anything was shown is blank in the first screen's size
+        recorder.end_frame();
+        let mut tall = vec![0; GRID_WIDTH * HIRES_GRID_HEIGHT];
+        tall[GRID_WIDTH * HIRES_GRID_HEIGHT - 1] = 1;
+        recorder.present(&tall);
+        recorder.end_frame();
+        // A smaller screen later keeps the video's size
+        recorder.present(&framebuffer(0));
+        recorder.end_frame();
+        for _ in 0..3 {
+            gate.send(()).unwrap();
+        }
+        drop(recorder);
+
+        let frame_bytes = FRAME_BYTES * 2;
+        let written = written.lock().unwrap().clone();
+        assert_eq!(written.len(), 3 * frame_bytes);
+        let frames: Vec<_> = written.chunks(frame_bytes).collect();
+        assert!(frames[0].iter().all(|&byte| byte == 0));
+        assert_eq!(
+            frames[1],
+            rgb_frame(&tall, HIRES_GRID_HEIGHT, 4, WINDOW_PALETTE)
+        );
+        // The bottom right pixel is lit
+        assert_eq!(frames[1][frame_bytes - 2], 0xFF);
+        assert_eq!(
+            frames[2],
+            rgb_frame(&framebuffer(0), HIRES_GRID_HEIGHT, 4, WINDOW_PALETTE)
+        );
+    }
+
+    #[test]
+    fn slow_encoders_drop_frames() {
+        let (mut video, gate, written) = gated();
+        // The writer holds the first frame, the queue the next ones, and the rest is dropped
+        for frame in 0..QUEUED_FRAMES + 5 {
+            video.push(&framebuffer(frame));
+        }
+        let stats = video.stats();
+        assert!(stats.dropped >= 4, "{stats:?}");
+        assert_eq!(stats.frames + stats.dropped, QUEUED_FRAMES as u64 + 5);
+
+        for _ in 0..stats.frames {
+            gate.send(()).unwrap();
+        }
+        assert_eq!(video.finish().unwrap(), stats);
+        let written = written.lock().unwrap().clone();
+        assert_eq!(written.len() as u64, stats.frames * FRAME_BYTES as u64);
+        // The frames kept are the first ones, in order
+        assert_eq!(
+            written[..FRAME_BYTES],
+            rgb_frame(&framebuffer(0), GRID_HEIGHT, 4, WINDOW_PALETTE)
+        );
+    }
+
+    #[test]
+    fn failing_encoders_stop_the_recording() {
+        let (mut video, gate, _) = gated();
+        video.push(&framebuffer(0));
+        // Hanging up fails the write
+        drop(gate);
+        let err = video.finish().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn recorder_sends_a_frame_per_end_of_frame() {
+        let (video, gate, written) = gated();
+        let mut recorder = VideoRecorder::new(Box::new(HeadlessFrontend), video);
+        recorder.end_frame();
+        recorder.present(&framebuffer(5));
+        recorder.present(&framebuffer(6));
+        recorder.end_frame();
+        recorder.end_frame();
+        for _ in 0..3 {
+            gate.send(()).unwrap();
+        }
+        drop(recorder);
+
+        let written = written.lock().unwrap().clone();
+        let frames: Vec<_> = written.chunks(FRAME_BYTES).collect();
+        assert_eq!(frames.len(), 3);
+        // Blank until something was presented, then the last screen presented
+        assert_eq!(
+            frames[0],
+            rgb_frame(
+                &[0; GRID_WIDTH * GRID_HEIGHT],
+                GRID_HEIGHT,
+                4,
+                WINDOW_PALETTE
+            )
+        );
+        assert_eq!(
+            frames[1],
+            rgb_frame(&framebuffer(6), GRID_HEIGHT, 4, WINDOW_PALETTE)
+        );
+        assert_eq!(frames[1], frames[2]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn frames_reach_the_child_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("frames.rgb");
+        let target = out.clone();
+        let mut video = VideoPipe::spawn(
+            move |size| {
+                assert_eq!(size, (256, 128));
+                let mut command = std::process::Command::new("sh");
+                command
+                    .arg("-c")
+                    .arg(format!("cat > '{}'", target.display()));
+                command
+            },
+            4,
+        );
+        video.push(&framebuffer(0));
+        video.push(&framebuffer(1));
+        let stats = video.finish().unwrap();
+        assert_eq!(stats.frames + stats.dropped, 2);
+        let expected = stats.frames as usize * FRAME_BYTES;
+        assert_eq!(std::fs::read(&out).unwrap().len(), expected);
+
+        // An encoder failing is reported once it exited
+        let mut video = VideoPipe::spawn(
+            |_| {
+                let mut command = std::process::Command::new("sh");
+                command.arg("-c").arg("cat > /dev/null; exit 3");
+                command
+            },
+            4,
+        );
+        video.push(&framebuffer(0));
+        assert!(video.finish().is_err());
+    }
+
+    #[test]
+    fn missing_encoders_fail_to_start() {
+        let err = find_encoder("/nonexistent/ffmpeg").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let mut video = VideoPipe::spawn(
+            |size| ffmpeg_command("/nonexistent/ffmpeg", size, "out.mp4"),
+            8,
+        );
+        video.push(&framebuffer(0));
+        assert_eq!(video.finish().unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        let command = ffmpeg_command("/nonexistent/ffmpeg", VideoPipe::size(8, 32), "out.mp4");
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy())
+            .collect();
+        assert_eq!(
+            args.join(" "),
+            "-loglevel error -y -f rawvideo -pixel_format rgb24 -video_size 512x256 -framerate 60 -i - -pix_fmt yuv420p out.mp4"
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//!
+//! The CHIP-8 emulator core: the machine itself, ROM loading and the screens it can draw into.
+//!
+//! Everything the emulator needs from the outside world goes through a [`Frontend`]: showing the
+//! screen, taking input and sounding the buzzer. The SDL window and keyboard live in
+//! `frontend::sdl`; embedders wanting their own frontend pass one to [`Chip8Builder::frontend`], or
+//! start from [`Chip8::headless`] and either read the framebuffer back or attach [`Chip8Hooks`].
+//!
+//! The SDL frontend sits behind the default `sdl` feature and everything needing an OS (files,
+//! sleeping, the wall clock) behind `native`, so the core alone builds for the browser, where the
+//! `wasm` feature exports functions for a JavaScript shim to drive it. The `ffi` feature exports
+//! a C API (see `include/chip8_emu.h`) for frontends written in other languages.
+//!
+//! Without the `std` feature, the crate is `no_std` and allocation-free, down to [`Cpu`], the
+//! bare machine, with the screen, the random numbers and the timer ticks supplied by the host.
+//!
+// The unit tests link std even for the bare core, which itself never uses it
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![warn(clippy::all)]
+#![warn(clippy::pedantic)]
+#![warn(clippy::nursery)]
+#![warn(clippy::cargo)]
+// #![warn(clippy::restriction)]
+#![allow(clippy::missing_const_for_fn)]
+#![allow(clippy::multiple_crate_versions)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::must_use_candidate)]
+
+pub mod emu;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod frontend;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "std")]
+pub use emu::breakpoint::Breakpoint;
+#[cfg(feature = "std")]
+pub use emu::builder::Chip8Builder;
+#[cfg(feature = "std")]
+pub use emu::chip8::{Chip8, RunSummary, StepOutcome};
+pub use emu::cpu::{Chip8Error, Clock, Cpu, Instruction, RandomSource, SequenceRng};
+#[cfg(feature = "std")]
+pub use emu::hooks::Chip8Hooks;
+pub use emu::memory::{Ram, Registers};
+pub use emu::quirks::{Quirks, Variant};
+#[cfg(feature = "std")]
+pub use frontend::{AudioSink, Frontend, HeadlessFrontend, Input, Keypad};
@@ -3,27 +3,34 @@
 #![warn(clippy::nursery)]
 #![warn(clippy::cargo)]
 // #![warn(clippy::restriction)]
+#![allow(clippy::missing_const_for_fn)]
+#![allow(clippy::multiple_crate_versions)]
+#![allow(clippy::cast_possible_truncation)]
 
-mod emu;
 mod utl;
 
-use clap::Parser;
+use std::process::ExitCode;
 
-use emu::chip8::Chip8;
-use utl::config::Args;
+use utl::cli;
+use utl::config::{Args, Command};
+use utl::exit;
+use utl::logger::Logger;
 
-fn main() {
-    let args = Args::parse();
-    match Chip8::new(args.width, args.height) {
-        Ok(mut chip8) => {
-            if let Err(err) = chip8.load_rom(args.rom_path.as_str()) {
-                eprintln!("[-] Failed to load the ROM. Error => `{err}`");
-            } else if let Err(err) = chip8.run() {
-                eprintln!("[-] Failed to run the app. Error => `{err}`");
-            }
-        }
-        Err(err) => {
-            eprintln!("[-] Failed to run the CHIP8 emulator. Error => `{err}`");
-        }
-    }
+fn main() -> ExitCode {
+    let args = Args::parse_with_env();
+    Logger::init(args.log_level);
+    let result = match args.command {
+        Some(Command::Run(run)) => cli::run(&run),
+        Some(Command::Disasm(rom)) => cli::disasm(&rom),
+        Some(Command::Asm(asm)) => cli::asm(&asm),
+        Some(Command::Info(info)) => cli::info(&info),
+        Some(Command::Check(check)) => cli::check(&check),
+        Some(Command::Recent(recent)) => cli::recent(&recent),
+        Some(Command::Verify(verify)) => cli::verify(&verify),
+        Some(Command::Replay(replay)) => cli::replay(&replay),
+        Some(Command::TestSuite(suite)) => cli::test_suite(&suite),
+        Some(Command::State(state)) => cli::state(&state),
+        None => cli::run(&args.run),
+    };
+    exit::report(&result)
 }
@@ -0,0 +1,1690 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write as _};
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use chip8_emu::emu::analysis::{self, Analysis, Extension, ReportFormat};
+use chip8_emu::emu::chip8::{Chip8, Chip8Error, INSTRUCTIONS_PER_SECOND};
+use chip8_emu::emu::compare::{Comparison, QuirkSetting, Side};
+use chip8_emu::emu::coverage::Coverage;
+use chip8_emu::emu::disasm::{self, Syntax};
+use chip8_emu::emu::memory::{DEFAULT_PROGRAM_START_OFFSET, RAM_SIZE};
+use chip8_emu::emu::quirks::{Quirks, Variant};
+use chip8_emu::emu::remote::ControlServer;
+use chip8_emu::emu::replay::Replay;
+use chip8_emu::emu::rom;
+use chip8_emu::emu::schedule::{Pacer, Scheduler, SystemClock};
+use chip8_emu::emu::screenshot::{self, WINDOW_PALETTE};
+#[cfg(feature = "script")]
+use chip8_emu::emu::script::{RhaiScript, ScriptHost};
+use chip8_emu::emu::state::Chip8State;
+use chip8_emu::emu::stats::RunOutcome;
+use chip8_emu::emu::trace::TraceWriter;
+use chip8_emu::emu::verify::{self, Verdict};
+use chip8_emu::emu::{asm, crash};
+use chip8_emu::frontend::sdl::keymap::{self, Hotkeys, KeyMap};
+use chip8_emu::frontend::sdl::render::RenderOptions;
+use chip8_emu::frontend::sdl::SdlFrontend;
+use chip8_emu::frontend::stream::{Spectator, StreamFrontend, StreamSender};
+use chip8_emu::frontend::video::{self, VideoPipe, VideoRecorder};
+use chip8_emu::frontend::{threaded, Frontend, HeadlessFrontend, Input, Keypad};
+
+use super::config::{
+    AsmArgs, CheckArgs, DisasmArgs, InfoArgs, RecentAction, RecentArgs, ReplayArgs, RomArgs,
+    RunArgs, StateAction, StateArgs, TestSuiteArgs, VerifyArgs,
+};
+use super::debugger;
+use super::exit::{Failure, Status};
+use super::interrupt;
+use super::library::Library;
+use super::recent::{RecentRom, RecentRoms};
+use super::sidecar::{self, Sidecar};
+use super::suite::{self, Manifest};
+use super::window::SavedWindow;
+
+///
+/// How many of the hottest instructions and addresses `--profile` prints.
+///
+const PROFILE_TOP: usize = 20;
+
+///
+/// How many lines of the disassembly `info` previews.
+///
+const PREVIEW_LINES: usize = 16;
+
+///
+/// How often `--compare` looks for input while paused.
+///
+const COMPARE_PAUSED_POLL: Duration = Duration::from_millis(10);
+
+///
+/// How many frames `replay` plays between updates of its progress.
+///
+const REPLAY_PROGRESS_FRAMES: u32 = 30;
+
+///
+/// How long `--spectate` waits for a screen before looking for input again.
+///
+const SPECTATE_POLL: Duration = Duration::from_millis(10);
+
+pub fn run(args: &RunArgs) -> Result<Status, Failure> {
+    // Flushed when the run is over, whichever way it ends
+    #[cfg(feature = "profiling")]
+    let _trace = args
+        .tracing_output
+        .as_deref()
+        .map(start_tracing)
+        .transpose()?;
+    let mut keymap = KeyMap::default();
+    let hotkeys = Hotkeys::default();
+    if args.list_keys {
+        print!("{}", keymap::key_table(&keymap, &hotkeys));
+        return Ok(Status::Done);
+    }
+
+    let recent = RecentRoms::load_default();
+    if args.recent {
+        print!(
+            "{}",
+            recent.as_ref().map(RecentRoms::listing).unwrap_or_default()
+        );
+        return Ok(Status::Done);
+    }
+    if let Some(port) = args.spectate {
+        return spectate(args, &keymap, &hotkeys, port);
+    }
+    let library = args
+        .rom_dir
+        .as_deref()
+        .map(|dir| scan_library(args, dir))
+        .transpose()?;
+
+    // Nothing could resume a headless run once it stopped on a breakpoint
+    let stops = !args.breakpoints.is_empty()
+        || !args.break_on.is_empty()
+        || !args.watch_reg.is_empty()
+        || !args.watch_mem.is_empty();
+    if args.headless && !args.debug && stops {
+        return Err(Failure::Other(
+            "--break, --break-on and --watch-* need a window, or --debug, to resume from."
+                .to_string(),
+        ));
+    }
+
+    let max_run_time = args
+        .run_seconds
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .map_err(|err| Failure::Other(format!("Invalid --run-seconds value. Error => `{err}`")))?;
+
+    // Without a ROM, offer the library's or the recent ones, and fall back to the demo if there are
+    // none. There is nobody to ask in headless mode, so it goes straight to the demo.
+    let has_recent = !args.headless
+        && recent
+            .as_ref()
+            .is_some_and(|recent| recent.entries().iter().any(RecentRom::exists));
+    let demo = args.demo || (args.rom().is_none() && library.is_none() && !has_recent);
+    let rom_path = if demo {
+        None
+    } else if let Some(library) = &library {
+        browse(library)
+    } else {
+        args.rom()
+            .map(str::to_string)
+            .or_else(|| recent.as_ref().and_then(pick_recent))
+    };
+
+    let sidecar = match &rom_path {
+        Some(rom_path) => load_sidecar(Path::new(rom_path))?,
+        None => Sidecar::default(),
+    };
+    for &(hex_key, key) in &sidecar.keys {
+        keymap.bind(hex_key, key);
+    }
+    for &(hex_key, key) in &sidecar.second_keys {
+        keymap.bind_second(hex_key, key);
+    }
+    // A missing ffmpeg is better told before the game starts than once it's over
+    let video = args
+        .record_video
+        .as_deref()
+        .map(|path| start_recording(&args.ffmpeg, args.record_video_scale, path))
+        .transpose()?;
+    let stream = args.stream_to.as_deref().map(start_streaming).transpose()?;
+    let patch = args.patch.as_deref().map(read_patch).transpose()?;
+    let launch = Launch {
+        sidecar,
+        keymap,
+        hotkeys,
+        max_run_time,
+        demo,
+        rom_path,
+        recent,
+        video,
+        stream,
+        library,
+        patch,
+    };
+    if let [a, b] = args.compare.as_slice() {
+        return compare(args, &launch, [a, b]);
+    }
+    if args.headless {
+        return emulate(args, launch, None);
+    }
+
+    // The window stays on the main thread, where SDL wants it, and is kept responsive there
+    // however long the emulation takes over a frame
+    let window = open_window(args, &launch)?;
+    let args = args.clone();
+    threaded::run(window, move |frontend| {
+        emulate(&args, launch, Some(Box::new(frontend)))
+    })
+}
+
+///
+/// Starts writing the tracing spans of every thread to `path` as a Chrome trace, until the
+/// returned guard is dropped.
+///
+#[cfg(feature = "profiling")]
+fn start_tracing(path: &str) -> Result<tracing_chrome::FlushGuard, Failure> {
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    let file = fs::File::create(path).map_err(|err| {
+        Failure::Other(format!("Failed to create the trace file. Error => `{err}`"))
+    })?;
+    let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+        .writer(io::BufWriter::new(file))
+        .include_args(true)
+        .build();
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))
+        .map_err(|err| Failure::Other(format!("Failed to start tracing. Error => `{err}`")))?;
+    log::info!("Writing the trace to {path}.");
+    Ok(guard)
+}
+
+///
+/// The `Launch` struct represents what a run was started with, besides its options.
+///
+struct Launch {
+    sidecar: Sidecar,
+    keymap: KeyMap,
+    hotkeys: Hotkeys,
+    max_run_time: Option<Duration>,
+    demo: bool,
+    rom_path: Option<String>,
+    recent: Option<RecentRoms>,
+    video: Option<VideoPipe>,
+    stream: Option<StreamSender>,
+    /// The ROMs of `--rom-dir`, F2 going back to them
+    library: Option<Library>,
+    /// The IPS patch of `--patch`
+    patch: Option<Vec<u8>>,
+}
+
+///
+/// Starts `ffmpeg` encoding the video of `--record-video` or `--export` into `path`, sized by
+/// the first screen shown.
+///
+fn start_recording(ffmpeg: &str, scale: u32, path: &str) -> Result<VideoPipe, Failure> {
+    video::find_encoder(ffmpeg).map_err(|err| {
+        if err.kind() == io::ErrorKind::NotFound {
+            Failure::Other(format!(
+                "Failed to record the video, `{ffmpeg}` wasn't found. Install ffmpeg or point --ffmpeg to it."
+            ))
+        } else {
+            Failure::Other(format!("Failed to start `{ffmpeg}`. Error => `{err}`"))
+        }
+    })?;
+    let (ffmpeg, out) = (ffmpeg.to_string(), path.to_string());
+    let video = VideoPipe::spawn(
+        move |size| video::ffmpeg_command(&ffmpeg, size, &out),
+        scale,
+    );
+    log::info!("Recording the video to {path}.");
+    Ok(video)
+}
+
+///
+/// Starts streaming the screen of `--stream-to` to `address`, which is only resolved here: the
+/// spectator itself may come and go.
+///
+fn start_streaming(address: &str) -> Result<StreamSender, Failure> {
+    let resolved = address
+        .to_socket_addrs()
+        .map_err(|err| {
+            Failure::Other(format!(
+                "Failed to resolve the --stream-to address `{address}`. Error => `{err}`"
+            ))
+        })?
+        .next()
+        .ok_or_else(|| {
+            Failure::Other(format!(
+                "The --stream-to address `{address}` doesn't resolve to anything."
+            ))
+        })?;
+    Ok(StreamSender::connect(resolved))
+}
+
+fn read_patch(path: &str) -> Result<Vec<u8>, Failure> {
+    fs::read(path).map_err(|err| {
+        Failure::Other(format!(
+            "Failed to read the patch `{path}`. Error => `{err}`"
+        ))
+    })
+}
+
+fn open_window(args: &RunArgs, launch: &Launch) -> Result<Box<dyn Frontend>, Failure> {
+    Ok(Box::new(sdl_window(
+        args,
+        &launch.keymap,
+        &launch.hotkeys,
+        args.side_panel,
+    )?))
+}
+
+fn sdl_window(
+    args: &RunArgs,
+    keymap: &KeyMap,
+    hotkeys: &Hotkeys,
+    side_panel: bool,
+) -> Result<SdlFrontend, Failure> {
+    let render = RenderOptions {
+        video_driver: args.video_driver.clone(),
+        renderer: args.renderer,
+        vsync: args.vsync,
+        scale_filter: args.scale_filter,
+        visual_beep: args.visual_beep,
+    };
+    let (width, height) = args.window_size();
+    let mut window = SdlFrontend::new(
+        width,
+        height,
+        keymap.clone(),
+        hotkeys.clone(),
+        side_panel,
+        &render,
+    )
+    .map_err(|err| Failure::InitSdl(Chip8Error::FailedToInitDisplay(err)))?;
+
+    if let Some(mut saved) = SavedWindow::load_default() {
+        window.restore_geometry(&saved.restored(window.geometry(), args));
+        window.on_close(move |geometry| {
+            saved.set(*geometry);
+            if let Err(err) = saved.save() {
+                log::warn!("Failed to save the window's position. Error => `{err}`");
+            }
+        });
+    }
+    Ok(window)
+}
+
+///
+/// Loads the ROM and runs it, on `frontend` if there is one, until it's quit or crashes.
+///
+fn emulate(
+    args: &RunArgs,
+    mut launch: Launch,
+    frontend: Option<Box<dyn Frontend>>,
+) -> Result<Status, Failure> {
+    let max_run_time = launch.max_run_time;
+    let frontend = match launch.video.take() {
+        Some(video) => {
+            let frontend = frontend.unwrap_or_else(|| Box::new(HeadlessFrontend));
+            Some(Box::new(VideoRecorder::new(frontend, video)) as Box<dyn Frontend>)
+        }
+        None => frontend,
+    };
+    let frontend = match launch.stream.take() {
+        Some(stream) => {
+            let frontend = frontend.unwrap_or_else(|| Box::new(HeadlessFrontend));
+            Some(Box::new(StreamFrontend::new(frontend, stream)) as Box<dyn Frontend>)
+        }
+        None => frontend,
+    };
+    let mut chip8 = build_chip8(args, &launch, frontend)?;
+    load_launched_rom(&mut chip8, args, &launch)?;
+    if let Some(path) = &args.load_state {
+        load_state(&mut chip8, path)?;
+    }
+    #[cfg(feature = "script")]
+    if let Some(path) = &args.script {
+        let script = RhaiScript::load(path).map_err(|err| {
+            Failure::Other(format!("Failed to load the script. Error => `{err}`"))
+        })?;
+        chip8.set_script(ScriptHost::new(script));
+    }
+
+    remember_rom(launch.recent.as_mut(), &chip8);
+    chip8.set_stop_flag(interrupt::install());
+    let outcome = if args.debug {
+        debugger::run(&mut chip8)
+    } else if let Some(frames) = args.frames {
+        run_frames(&mut chip8, frames)
+    } else {
+        run_library(&mut chip8, args, launch.library.as_ref())
+    }
+    .map_err(|err| {
+        report_crash(&chip8, &err, args.crash_dump);
+        Failure::Runtime(err)
+    });
+    // The profile of a crashed run is as telling as any
+    report_profile(&chip8, args.profile_csv.as_deref());
+    if let Some(latency) = chip8.latency() {
+        log::info!("{latency}.");
+    }
+    if let Some(faults) = chip8.faults().filter(|faults| faults.total() > 0) {
+        log::info!("{faults}.");
+    }
+    if let Some(uninit) = chip8.uninit() {
+        log::info!(
+            "{} read(s) of registers and memory nothing wrote.",
+            uninit.reported()
+        );
+    }
+    report_coverage(&chip8, args.coverage.as_deref());
+    if let Some(path) = &args.screenshot {
+        save_screenshot(&chip8, path, args.screenshot_scale);
+    }
+    if let Some(path) = &args.save_state {
+        save_state(&chip8, path);
+    }
+    let outcome = outcome?;
+    // A different ROM may have been dropped onto the window in the meantime
+    remember_rom(launch.recent.as_mut(), &chip8);
+
+    let interrupted = outcome == RunOutcome::Interrupted;
+    if interrupted {
+        log::info!("Interrupted by Ctrl-C.");
+    }
+    if args.headless || args.max_cycles.is_some() || max_run_time.is_some() || interrupted {
+        log::info!("{}", chip8.stats());
+    }
+    if !chip8.pokes().is_empty() {
+        log::info!("Frozen pokes: {}.", chip8.pokes());
+    }
+    match outcome {
+        // `run_library` handles going back to the library and saving the settings
+        RunOutcome::Quit | RunOutcome::Browse | RunOutcome::SaveProfile => Ok(Status::Done),
+        RunOutcome::BudgetExhausted => Ok(Status::BudgetExhausted),
+        RunOutcome::Interrupted => Ok(Status::Interrupted),
+    }
+}
+
+///
+/// Runs the emulator, going back to the ROM library whenever F2 is pressed if there is one, and
+/// loading the ROM picked there on the reset machine. Ctrl+S saves the settings on the way.
+///
+fn run_library(
+    chip8: &mut Chip8,
+    args: &RunArgs,
+    library: Option<&Library>,
+) -> Result<RunOutcome, Chip8Error> {
+    loop {
+        let outcome = chip8.run()?;
+        if outcome == RunOutcome::SaveProfile {
+            match save_profile(chip8, args) {
+                Ok(path) => log::info!("Saved the settings to {}.", path.display()),
+                Err(err) => log::error!("Failed to save the settings. Error => `{err}`"),
+            }
+            continue;
+        }
+        if outcome != RunOutcome::Browse {
+            return Ok(outcome);
+        }
+        let Some(library) = library else {
+            continue;
+        };
+        // Nothing is emulated while the list waits for an answer
+        chip8.set_paused(true);
+        if let Some(rom_path) = browse(library) {
+            match chip8.load_rom(&rom_path, None) {
+                Ok(()) => log::info!("Loaded {rom_path} from the library."),
+                Err(err) => log::error!("Failed to load the picked ROM. Error => `{err}`"),
+            }
+        }
+        chip8.set_paused(false);
+    }
+}
+
+///
+/// Writes the settings the running ROM resolved to into its sidecar file, keeping its key
+/// bindings, so the next run starts with them. Returns the file's path.
+///
+fn save_profile(chip8: &Chip8, args: &RunArgs) -> Result<PathBuf, Failure> {
+    let rom_path = chip8.rom_path().map(Path::new).ok_or_else(|| {
+        Failure::Other("The demo has no ROM file to save the settings next to.".to_string())
+    })?;
+    let mut sidecar = load_sidecar(rom_path)?;
+    // Their values weren't kept, there's nothing to write back
+    sidecar.unknown.clear();
+    sidecar.set_profile(
+        args.variant.or(sidecar.variant),
+        chip8.cpu().quirks(),
+        chip8.ips(),
+        chip8.cpu().load_address(),
+    );
+    sidecar.save(rom_path)
+}
+
+///
+/// Loads the demo or the ROM the run was started with, patched by `--patch` if it was given.
+///
+fn load_launched_rom(chip8: &mut Chip8, args: &RunArgs, launch: &Launch) -> Result<(), Failure> {
+    let zip_entry = args.zip_entry.as_deref();
+    if launch.demo {
+        chip8.load_demo()
+    } else if let Some(rom_path) = &launch.rom_path {
+        match &launch.patch {
+            Some(patch) => chip8
+                .load_patched_rom(rom_path, zip_entry, patch)
+                .map(|_| ()),
+            None => chip8.load_rom(rom_path, zip_entry),
+        }
+    } else {
+        Ok(())
+    }
+    .map_err(Failure::LoadRom)
+}
+
+///
+/// Runs `frames` frames as fast as possible, or until a breakpoint or a watch stops them, ending
+/// every one for the video to record it.
+///
+fn run_frames(chip8: &mut Chip8, frames: u32) -> Result<RunOutcome, Chip8Error> {
+    for _ in 0..frames {
+        let summary = chip8.run_frame()?;
+        #[cfg(feature = "script")]
+        chip8.run_script();
+        chip8.end_frame();
+        if summary.stopped() {
+            break;
+        }
+    }
+    Ok(RunOutcome::Quit)
+}
+
+///
+/// Builds the two cores of `--compare`, only differing in their quirks, with the ROM loaded.
+///
+fn compared_cores(
+    args: &RunArgs,
+    launch: &Launch,
+    settings: [&QuirkSetting; 2],
+) -> Result<Comparison, Failure> {
+    // The random numbers must not tell the cores apart, seeded or not
+    let seed = args.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64
+    });
+    let sidecar = &launch.sidecar;
+    let base = args
+        .variant
+        .map_or_else(|| sidecar.quirks(Quirks::default()), Variant::quirks);
+    let core = |setting: &QuirkSetting| -> Result<Chip8, Failure> {
+        let mut builder = Chip8::builder()
+            .headless(true)
+            .ips(args.ips.or(sidecar.ips).unwrap_or(INSTRUCTIONS_PER_SECOND))
+            .seed(seed)
+            .quirks(setting.apply(base));
+        if let Some(address) = sidecar.load_address {
+            builder = builder.load_address(address);
+        }
+        let mut chip8 = builder
+            .build()
+            .map_err(|err| Failure::Other(format!("Invalid configuration. Error => `{err}`")))?;
+        load_launched_rom(&mut chip8, args, launch)?;
+        Ok(chip8)
+    };
+    log::info!(
+        "Comparing A: {} with B: {}, seeded with {seed}.",
+        settings[0],
+        settings[1]
+    );
+    Ok(Comparison::new(core(settings[0])?, core(settings[1])?))
+}
+
+///
+/// Runs the ROM on two cores side by side, with the quirks of `settings`, until the window is
+/// closed, logging the first frame their screens differ.
+///
+fn compare(
+    args: &RunArgs,
+    launch: &Launch,
+    settings: [&QuirkSetting; 2],
+) -> Result<Status, Failure> {
+    let mut comparison = compared_cores(args, launch, settings)?;
+    // The panes and panels show one core, not two
+    let mut window = sdl_window(args, &launch.keymap, &launch.hotkeys, false)?;
+    window.set_title(&format!("A: {} | B: {}", settings[0], settings[1]));
+
+    let interrupted = interrupt::install();
+    let mut scheduler = Scheduler::new(args.max_catch_up);
+    let mut pacer = Pacer::new(Duration::from_millis(args.spin_ms));
+    let mut paused = false;
+    let mut last = Instant::now();
+    let status = loop {
+        let mut step = false;
+        let mut quit = false;
+        while let Some(input) = window.poll_input() {
+            match input {
+                Input::Quit => quit = true,
+                Input::TogglePause => {
+                    paused = !paused;
+                    scheduler.reset();
+                }
+                Input::Step => step = paused,
+                Input::Key {
+                    hex_key, pressed, ..
+                } => comparison.set_key(hex_key, pressed),
+                Input::ReleaseKeys => {
+                    (0..16).for_each(|hex_key| comparison.set_key(hex_key, false));
+                }
+                _ => {}
+            }
+        }
+        if quit {
+            break Status::Done;
+        }
+        if interrupted.load(Ordering::Relaxed) {
+            log::info!("Interrupted by Ctrl-C.");
+            break Status::Interrupted;
+        }
+
+        let now = Instant::now();
+        let frames = if paused {
+            u32::from(step)
+        } else {
+            scheduler
+                .advance(now.saturating_duration_since(last))
+                .frames
+        };
+        last = now;
+        for _ in 0..frames {
+            let diverged = comparison.run_frame().map_err(|err| {
+                log::error!("Core {} crashed.", err.side);
+                report_crash(comparison.core(err.side), &err.error, args.crash_dump);
+                Failure::Runtime(err.error)
+            })?;
+            if diverged {
+                log::info!("{}.", comparison.status());
+            }
+        }
+        if frames > 0 {
+            window.set_overlay(&[
+                format!("A {}", settings[0]),
+                format!("B {}", settings[1]),
+                comparison.status(),
+            ]);
+            window.present_side_by_side(
+                comparison.core(Side::A).framebuffer(),
+                comparison.core(Side::B).framebuffer(),
+                comparison.divergence().is_some(),
+            );
+            window.end_frame();
+        }
+        let wait = if paused {
+            COMPARE_PAUSED_POLL
+        } else {
+            scheduler.until_next_frame()
+        };
+        pacer.wait_until(&mut SystemClock, now + wait);
+    };
+    log::info!("{}.", comparison.status());
+    Ok(status)
+}
+
+///
+/// Shows the screens streamed to `port` by another emulator's `--stream-to` until the window is
+/// closed, running nothing of its own.
+///
+fn spectate(
+    args: &RunArgs,
+    keymap: &KeyMap,
+    hotkeys: &Hotkeys,
+    port: u16,
+) -> Result<Status, Failure> {
+    // Spectating is mostly done from another machine than the one playing
+    let spectator =
+        Spectator::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))).map_err(|err| {
+            Failure::Other(format!(
+                "Failed to listen for a stream on port {port}. Error => `{err}`"
+            ))
+        })?;
+    let mut window = sdl_window(args, keymap, hotkeys, false)?;
+    window.set_title(&format!("Spectating port {}", spectator.address().port()));
+    log::info!(
+        "Waiting for a stream on port {}.",
+        spectator.address().port()
+    );
+
+    let interrupted = interrupt::install();
+    loop {
+        // The keys are the player's, not the spectator's
+        while let Some(input) = window.poll_input() {
+            if input == Input::Quit {
+                return Ok(Status::Done);
+            }
+        }
+        if interrupted.load(Ordering::Relaxed) {
+            log::info!("Interrupted by Ctrl-C.");
+            return Ok(Status::Interrupted);
+        }
+        if let Some(received) = spectator.recv_timeout(SPECTATE_POLL) {
+            window.present(&received.framebuffer);
+            window.end_frame();
+        }
+    }
+}
+
+///
+/// Prints the state the machine crashed in and, with `--crash-dump`, writes it with the whole
+/// memory to `crash-<timestamp>.txt`.
+///
+fn report_crash(chip8: &Chip8, err: &Chip8Error, dump: bool) {
+    let report = crash::crash_report(chip8, err);
+    log::error!("Crashed.\n{}", report.trim_end());
+    if !dump {
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = format!("crash-{timestamp}.txt");
+    let mut contents = report;
+    contents.push_str("\nMemory:\n");
+    crash::write_hex_dump(chip8.cpu().ram(), 0, RAM_SIZE, &mut contents);
+    match fs::write(&path, contents) {
+        Ok(()) => log::info!("Wrote the crash report to {path}."),
+        Err(err) => log::error!("Failed to write the crash report. Error => `{err}`"),
+    }
+}
+
+///
+/// Prints the hottest instructions and addresses of the profile, if any, and writes all of it to
+/// `csv` as CSV.
+///
+fn report_profile(chip8: &Chip8, csv: Option<&str>) {
+    let (Some(profile), Some(report)) = (chip8.profile(), chip8.profile_report(PROFILE_TOP)) else {
+        return;
+    };
+    eprint!("[+] Profile of {} instructions.\n{report}", profile.total());
+    let Some(path) = csv else {
+        return;
+    };
+    let written = fs::File::create(path).and_then(|file| {
+        let mut out = io::BufWriter::new(file);
+        profile.write_csv(&mut out)?;
+        out.flush()
+    });
+    match written {
+        Ok(()) => log::info!("Wrote the profile to {path}."),
+        Err(err) => log::error!("Failed to write the profile. Error => `{err}`"),
+    }
+}
+
+///
+/// Saves the screen to `path` as a PNG in the window's colors, or the palette's in MegaChip's mega
+/// mode.
+///
+fn save_screenshot(chip8: &Chip8, path: &str, scale: u32) {
+    let png = chip8.palette().map_or_else(
+        || screenshot::encode_palette_png(chip8.framebuffer(), scale, &WINDOW_PALETTE),
+        |palette| screenshot::encode_mega_png(chip8.framebuffer(), scale, palette),
+    );
+    match fs::write(path, png) {
+        Ok(()) => log::info!("Saved the screen to {path}."),
+        Err(err) => log::error!("Failed to save the screenshot. Error => `{err}`"),
+    }
+}
+
+///
+/// Saves the machine's state to `path`, for a later run to start from or `state dump` to print.
+///
+fn save_state(chip8: &Chip8, path: &str) {
+    let written = chip8
+        .save_state()
+        .map_err(|err| err.to_string())
+        .and_then(|state| fs::write(path, state.to_bytes()).map_err(|err| err.to_string()));
+    match written {
+        Ok(()) => log::info!("Saved the state to {path}."),
+        Err(err) => log::error!("Failed to save the state. Error => `{err}`"),
+    }
+}
+
+fn load_state(chip8: &mut Chip8, path: &str) -> Result<(), Failure> {
+    let state = read_state(path)?;
+    if state.rom_hash.is_some() && state.rom_hash.as_deref() != chip8.rom_hash() {
+        log::warn!("The save state {path} was saved from another ROM.");
+    }
+    chip8
+        .load_state(&state)
+        .map_err(|err| Failure::Other(format!("Failed to load `{path}`. Error => `{err}`")))
+}
+
+fn read_state(path: &str) -> Result<Chip8State, Failure> {
+    let error = |err: &dyn std::fmt::Display| {
+        Failure::Other(format!("Failed to read `{path}`. Error => `{err}`"))
+    };
+    let bytes = fs::read(path).map_err(|err| error(&err))?;
+    Chip8State::from_bytes(&bytes).map_err(|err| error(&err))
+}
+
+pub fn state(args: &StateArgs) -> Result<Status, Failure> {
+    match &args.action {
+        StateAction::Dump { state, json: true } => print!("{}", read_state(state)?.to_json()),
+        StateAction::Dump { state, json: false } => {
+            let saved = read_state(state)?;
+            let mut chip8 = Chip8::headless();
+            chip8.load_state(&saved).map_err(|err| {
+                Failure::Other(format!("Failed to load `{state}`. Error => `{err}`"))
+            })?;
+            if let Some(hash) = &saved.rom_hash {
+                println!("ROM SHA-1: {hash}");
+            }
+            println!("{}", chip8.dump_state());
+        }
+        StateAction::Build { json, output } => {
+            let text = fs::read_to_string(json).map_err(|err| {
+                Failure::Other(format!("Failed to read `{json}`. Error => `{err}`"))
+            })?;
+            let state = Chip8State::from_json(&text).map_err(|err| {
+                Failure::Other(format!("Failed to read `{json}`. Error => `{err}`"))
+            })?;
+            fs::write(output, state.to_bytes()).map_err(|err| {
+                Failure::Other(format!("Failed to write `{output}`. Error => `{err}`"))
+            })?;
+            println!("[+] Wrote the save state to {output}.");
+        }
+    }
+    Ok(Status::Done)
+}
+
+///
+/// Prints how much of the ROM was executed and writes the coverage report to `path`, if the run
+/// recorded it.
+///
+fn report_coverage(chip8: &Chip8, path: Option<&str>) {
+    let Some(path) = path else {
+        return;
+    };
+    let mut json = String::new();
+    let Some(summary) = chip8.coverage_report(&mut json) else {
+        return;
+    };
+    json.push('\n');
+    log::info!("Coverage: {summary}.");
+    match fs::write(path, json) {
+        Ok(()) => log::info!("Wrote the coverage report to {path}."),
+        Err(err) => log::error!("Failed to write the coverage report. Error => `{err}`"),
+    }
+}
+
+///
+/// Reads the ROM's sidecar file, warning about the settings in it that don't exist.
+///
+fn load_sidecar(rom: &Path) -> Result<Sidecar, Failure> {
+    let Some(sidecar) = Sidecar::load(rom)? else {
+        return Ok(Sidecar::default());
+    };
+    let path = sidecar::path_for(rom);
+    log::debug!("Read the settings from {}.", path.display());
+    for key in &sidecar.unknown {
+        log::warn!("Ignoring unknown setting `{key}` in {}.", path.display());
+    }
+    Ok(sidecar)
+}
+
+///
+/// Translates the run options into the emulator's configuration, the ones left out taken from
+/// the ROM's sidecar file, showing it on `frontend` if there is one.
+///
+fn build_chip8(
+    args: &RunArgs,
+    launch: &Launch,
+    frontend: Option<Box<dyn Frontend>>,
+) -> Result<Chip8, Failure> {
+    let sidecar = &launch.sidecar;
+    let (width, height) = args.window_size();
+    let mut builder = Chip8::builder()
+        .headless(args.headless)
+        .window_size(width, height)
+        .key_bindings(launch.keymap.clone(), launch.hotkeys.clone())
+        .side_panel(args.side_panel)
+        .renderer(args.renderer)
+        .vsync(args.vsync)
+        .scale_filter(args.scale_filter)
+        .visual_beep(args.visual_beep)
+        .profile(args.profile)
+        .measure_latency(args.measure_latency)
+        .detect_uninit(args.detect_uninit)
+        .fault_policy(args.fault_policy())
+        .coverage(args.coverage.is_some())
+        .debug_draw_age(args.debug_draw_age)
+        .ips(args.ips.or(sidecar.ips).unwrap_or(INSTRUCTIONS_PER_SECOND))
+        .max_catch_up_frames(args.max_catch_up)
+        .spin_threshold(Duration::from_millis(args.spin_ms))
+        .quirks(sidecar.quirks(Quirks::default()))
+        .detect_variant(args.variant.is_none() && !sidecar.picks_quirks());
+    if let Some(address) = sidecar.load_address {
+        builder = builder.load_address(address);
+    }
+    if let Some(max) = args.max_faults {
+        builder = builder.max_faults(max);
+    }
+    if let Some(driver) = &args.video_driver {
+        builder = builder.video_driver(driver.as_str());
+    }
+    if let Some(frontend) = frontend {
+        builder = builder.frontend(frontend);
+    }
+    // Runs of a given number of frames are the same every time
+    if let Some(seed) = args.seed.or_else(|| args.frames.map(|_| 0)) {
+        builder = builder.seed(seed);
+    }
+    if let Some(variant) = args.variant {
+        builder = builder.variant(variant);
+    } else if let Some(variant) = sidecar.variant {
+        // Its quirks are the sidecar's already
+        builder = builder
+            .hires(variant == Variant::HiRes)
+            .megachip(variant == Variant::MegaChip)
+            .chip8x(variant == Variant::Chip8X);
+    }
+
+    let mut chip8 = builder.build().map_err(|err| match err {
+        Chip8Error::FailedToInitDisplay(_) => Failure::InitSdl(err),
+        _ => Failure::Other(format!("Invalid configuration. Error => `{err}`")),
+    })?;
+    chip8.set_drop_requires_pause(args.drop_requires_pause);
+    chip8.set_budget(args.max_cycles, launch.max_run_time);
+    for breakpoint in &args.breakpoints {
+        chip8.add_breakpoint(breakpoint.clone());
+    }
+    for &instruction in &args.break_on {
+        chip8.add_break_on(instruction);
+    }
+    for &watch in args.watch_reg.iter().chain(&args.watch_mem) {
+        chip8.add_watch(watch);
+    }
+    for &key in &args.turbo_key {
+        chip8.add_turbo_key(key);
+    }
+    for &poke in &args.poke {
+        chip8.add_poke(poke);
+    }
+    if let Some(port) = args.control_port {
+        let server = ControlServer::bind((args.control_addr, port)).map_err(|err| {
+            Failure::Other(format!(
+                "Failed to start the control server. Error => `{err}`"
+            ))
+        })?;
+        log::info!("Listening for control commands on {}.", server.address());
+        chip8.set_control_server(server);
+    }
+    if let Some(format) = args.trace_format {
+        let out = io::BufWriter::new(io::stdout());
+        chip8.set_tracer(TraceWriter::new(out, format, args.trace_registers));
+    }
+    Ok(chip8)
+}
+
+pub fn verify(args: &VerifyArgs) -> Result<Status, Failure> {
+    let rom = read_rom(&args.rom)?;
+    let reference = fs::read_to_string(&args.against).map_err(|err| {
+        Failure::Other(format!(
+            "Failed to read the reference trace. Error => `{err}`"
+        ))
+    })?;
+    let reference =
+        verify::parse_reference(&reference).map_err(|err| Failure::Other(err.to_string()))?;
+
+    let mut builder = Chip8::builder()
+        .headless(true)
+        .seed(args.seed)
+        .ips(args.ips);
+    if let Some(variant) = args.variant {
+        builder = builder.variant(variant);
+    }
+    let mut chip8 = builder
+        .build()
+        .map_err(|err| Failure::Other(format!("Invalid configuration. Error => `{err}`")))?;
+    chip8.load_rom_bytes(&rom).map_err(Failure::LoadRom)?;
+
+    match verify::verify(&mut chip8, &reference).map_err(Failure::Runtime)? {
+        Verdict::Match(instructions) => {
+            println!("[+] All {instructions} instructions match the reference trace.");
+            Ok(Status::Done)
+        }
+        Verdict::Diverged(divergence) => Err(Failure::Diverged(divergence.to_string())),
+    }
+}
+
+pub fn replay(args: &ReplayArgs) -> Result<Status, Failure> {
+    let text = fs::read_to_string(&args.replay)
+        .map_err(|err| Failure::Other(format!("Failed to read the replay. Error => `{err}`")))?;
+    let replay = Replay::parse(&text).map_err(|err| Failure::Other(err.to_string()))?;
+    let rom_path = match (&args.rom, &replay.rom) {
+        (Some(rom), _) => PathBuf::from(rom),
+        (None, Some(rom)) => Path::new(&args.replay)
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(rom),
+        (None, None) => {
+            return Err(Failure::Other(
+                "The replay doesn't name its ROM, pass it with --rom.".to_string(),
+            ))
+        }
+    };
+    let rom = rom::read_rom(&rom_path.to_string_lossy(), None).map_err(Failure::LoadRom)?;
+    let video = args
+        .export
+        .as_deref()
+        .map(|path| start_recording(&args.ffmpeg, args.scale, path))
+        .transpose()?
+        .map(VideoPipe::lossless);
+
+    play_replay(&replay, &rom, video, io::stderr().is_terminal())?;
+    println!("[+] Played all {} frames of the replay.", replay.frames);
+    Ok(Status::Done)
+}
+
+pub fn test_suite(args: &TestSuiteArgs) -> Result<Status, Failure> {
+    let dir = Path::new(&args.dir);
+    let manifest_path = args
+        .manifest
+        .as_ref()
+        .map_or_else(|| dir.join(suite::MANIFEST_NAME), PathBuf::from);
+    let manifest = Manifest::load(&manifest_path)?;
+    let library = Library::scan(dir, args.recursive).map_err(|err| {
+        Failure::Other(format!(
+            "Failed to read the ROM directory `{}`. Error => `{err}`",
+            args.dir
+        ))
+    })?;
+    let jobs = args.jobs.map_or_else(
+        || thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        |jobs| jobs as usize,
+    );
+
+    let results = suite::run(library.roms(), &manifest, args.cycles, jobs);
+    print!("{}", suite::summary(&results));
+    if args.update {
+        fs::write(&manifest_path, suite::updated(&results).to_toml()).map_err(|err| {
+            Failure::Other(format!(
+                "Failed to write the manifest {}. Error => `{err}`",
+                manifest_path.display()
+            ))
+        })?;
+        println!("[+] Updated {}.", manifest_path.display());
+        return Ok(Status::Done);
+    }
+
+    let failures = results
+        .iter()
+        .filter(|result| result.outcome.is_failure())
+        .count();
+    if failures > 0 {
+        return Err(Failure::Regressed(format!(
+            "{failures} of {} ROMs didn't end on the manifest's screen",
+            results.len()
+        )));
+    }
+    Ok(Status::Done)
+}
+
+///
+/// Plays the replay on `rom` headless, recording every frame into `video` if there is one, and
+/// counting the frames on stderr with `progress`. The video is finished once this returns.
+///
+fn play_replay(
+    replay: &Replay,
+    rom: &[u8],
+    video: Option<VideoPipe>,
+    progress: bool,
+) -> Result<(), Failure> {
+    let frontend: Box<dyn Frontend> = match video {
+        Some(video) => Box::new(VideoRecorder::new(Box::new(HeadlessFrontend), video)),
+        None => Box::new(HeadlessFrontend),
+    };
+    let mut builder = Chip8::builder()
+        .headless(true)
+        .frontend(frontend)
+        .seed(replay.seed);
+    if let Some(ips) = replay.ips {
+        builder = builder.ips(ips);
+    }
+    if let Some(variant) = replay.variant {
+        builder = builder.variant(variant);
+    }
+    let mut chip8 = builder
+        .build()
+        .map_err(|err| Failure::Other(format!("Invalid configuration. Error => `{err}`")))?;
+    chip8.load_rom_bytes(rom).map_err(Failure::LoadRom)?;
+    if let (Some(expected), Some(actual)) = (&replay.sha1, chip8.rom_hash()) {
+        if expected != actual {
+            return Err(Failure::Other(format!(
+                "The replay was recorded on the ROM with SHA-1 {expected}, not this one's {actual}."
+            )));
+        }
+    }
+
+    let total = replay.frames;
+    replay
+        .play(&mut chip8, |frame| {
+            if progress && (frame % REPLAY_PROGRESS_FRAMES == 0 || frame == total) {
+                eprint!("\rFrame {frame}/{total}");
+            }
+        })
+        .map_err(|err| {
+            report_crash(&chip8, &err, false);
+            Failure::Runtime(err)
+        })?;
+    if progress && total > 0 {
+        eprintln!();
+    }
+    Ok(())
+}
+
+pub fn recent(args: &RecentArgs) -> Result<Status, Failure> {
+    let mut recent = RecentRoms::load_default()
+        .ok_or_else(|| Failure::Other("Failed to locate the user config directory.".to_string()))?;
+
+    match args.action {
+        Some(RecentAction::Clear) => {
+            recent.clear();
+            recent.save().map_err(|err| {
+                Failure::Other(format!("Failed to clear the recent ROMs. Error => `{err}`"))
+            })?;
+        }
+        None => print!("{}", recent.listing()),
+    }
+    Ok(Status::Done)
+}
+
+///
+/// Prints the recent ROMs and lets the user pick one by number, an empty answer opens the
+/// emulator without a ROM.
+///
+///
+/// Scans `--rom-dir` for ROMs, keeping the ones `--filter` matches.
+///
+fn scan_library(args: &RunArgs, dir: &str) -> Result<Library, Failure> {
+    let library = Library::scan(Path::new(dir), args.recursive).map_err(|err| {
+        Failure::Other(format!(
+            "Failed to read the ROM directory `{dir}`. Error => `{err}`"
+        ))
+    })?;
+    let library = match &args.filter {
+        Some(filter) => library.filtered(filter),
+        None => library,
+    };
+    if library.is_empty() {
+        return Err(Failure::Other(format!(
+            "No ROMs (.ch8, .c8 or .rom) in `{dir}`{}.",
+            args.filter
+                .as_ref()
+                .map(|filter| format!(" match `{filter}`"))
+                .unwrap_or_default()
+        )));
+    }
+    log::info!("Found {} ROM(s) in {dir}.", library.roms().len());
+    Ok(library)
+}
+
+///
+/// Shows the library a page at a time and asks for a ROM by number, `n` and `p` turning the pages.
+/// Pressing Enter picks none.
+///
+fn browse(library: &Library) -> Option<String> {
+    let pages = library.page_count();
+    let mut page = 0;
+    loop {
+        print!("{}", library.page(page));
+        if pages > 1 {
+            println!(
+                "Page {} of {pages}, n/p for the next or previous one.",
+                page + 1
+            );
+        }
+        print!("Pick a ROM by number (or press Enter to drop one onto the window): ");
+        let _ = io::stdout().flush();
+
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer).ok()?;
+        match answer.trim() {
+            "" => return None,
+            "n" => page = (page + 1) % pages,
+            "p" => page = (page + pages - 1) % pages,
+            answer => match answer.parse().ok().and_then(|number| library.pick(number)) {
+                Some(rom) => return Some(rom.path.display().to_string()),
+                None => log::warn!("`{answer}` is not one of the listed ROMs."),
+            },
+        }
+    }
+}
+
+fn pick_recent(recent: &RecentRoms) -> Option<String> {
+    print!("{}", recent.listing());
+    print!("Pick a ROM by number (or press Enter to drop one onto the window): ");
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).ok()?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return None;
+    }
+
+    let picked = answer.parse().ok().and_then(|number| recent.pick(number));
+    if picked.is_none() {
+        log::warn!("`{answer}` is not one of the available ROMs.");
+    }
+    picked.map(|entry| entry.path.display().to_string())
+}
+
+fn remember_rom(recent: Option<&mut RecentRoms>, chip8: &Chip8) {
+    let (Some(recent), Some(path), Some(hash)) = (recent, chip8.rom_path(), chip8.rom_hash())
+    else {
+        return;
+    };
+
+    recent.push(Path::new(path), hash);
+    if let Err(err) = recent.save() {
+        log::warn!("Failed to save the recent ROMs. Error => `{err}`");
+    }
+}
+
+pub fn disasm(args: &DisasmArgs) -> Result<Status, Failure> {
+    let rom = read_rom(&args.rom)?;
+    let syntax = if args.octo_syntax {
+        Syntax::Octo
+    } else {
+        Syntax::Classic
+    };
+    let listing = match &args.coverage {
+        Some(path) => {
+            let report = fs::read_to_string(path).map_err(|err| {
+                Failure::Other(format!("Failed to read `{path}`. Error => `{err}`"))
+            })?;
+            let coverage = Coverage::from_json(&report).map_err(|err| {
+                Failure::Other(format!("Failed to read `{path}`. Error => `{err}`"))
+            })?;
+            if args.only_executed {
+                disasm::executed_listing(&rom, args.start_addr, syntax, &coverage)
+            } else {
+                disasm::coverage_listing(&rom, args.start_addr, syntax, &coverage)
+            }
+        }
+        None => disasm::listing(&rom, args.start_addr, syntax),
+    };
+    print!("{listing}");
+    Ok(Status::Done)
+}
+
+pub fn asm(args: &AsmArgs) -> Result<Status, Failure> {
+    let source = fs::read_to_string(&args.source).map_err(|err| {
+        Failure::Other(format!(
+            "Failed to read `{}`. Error => `{err}`",
+            args.source
+        ))
+    })?;
+    let rom = asm::assemble(&source, args.start_addr)
+        .map_err(|err| Failure::Other(format!("Failed to assemble `{}`.\n{err}", args.source)))?;
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        Path::new(&args.source)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+    fs::write(&output, &rom)
+        .map_err(|err| Failure::Other(format!("Failed to write `{output}`. Error => `{err}`")))?;
+    println!("[+] Wrote {} bytes to {output}.", rom.len());
+    Ok(Status::Done)
+}
+
+pub fn info(args: &InfoArgs) -> Result<Status, Failure> {
+    let rom = read_rom(&args.rom)?;
+    print!("{}", rom_info(&rom, args.format));
+    if args.write_sidecar {
+        write_sidecar(Path::new(&args.rom.rom), &rom)?;
+    }
+    Ok(Status::Done)
+}
+
+///
+/// Writes a sidecar file next to the ROM with the settings it would run with by default, the
+/// analyzer picking the variant, unless it already has one.
+///
+fn write_sidecar(rom_path: &Path, rom: &[u8]) -> Result<(), Failure> {
+    let path = sidecar::path_for(rom_path);
+    if path.exists() {
+        return Err(Failure::Other(format!(
+            "`{}` already exists, remove it to write a new one.",
+            path.display()
+        )));
+    }
+
+    let variant = analysis::analyze(rom, DEFAULT_PROGRAM_START_OFFSET as u16).suggested_variant();
+    let quirks = variant.map_or_else(Quirks::default, Variant::quirks);
+    let name = rom_path.file_name().map_or_else(
+        || rom_path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let template = sidecar::template(
+        &name,
+        variant,
+        quirks,
+        INSTRUCTIONS_PER_SECOND,
+        DEFAULT_PROGRAM_START_OFFSET as u16,
+    );
+    fs::write(&path, template).map_err(|err| {
+        Failure::Other(format!(
+            "Failed to write `{}`. Error => `{err}`",
+            path.display()
+        ))
+    })?;
+    println!("[+] Wrote the settings to {}.", path.display());
+    Ok(())
+}
+
+pub fn check(args: &CheckArgs) -> Result<Status, Failure> {
+    let rom = read_rom(&args.rom)?;
+    let analysis = analysis::analyze(&rom, DEFAULT_PROGRAM_START_OFFSET as u16);
+    print!("{}", check_report(&analysis, args.format));
+    match analysis.issues.len() {
+        0 => Ok(Status::Done),
+        issues => Err(Failure::Other(format!("Found {issues} issue(s)."))),
+    }
+}
+
+fn read_rom(args: &RomArgs) -> Result<Vec<u8>, Failure> {
+    rom::read_rom(&args.rom, args.zip_entry.as_deref()).map_err(Failure::LoadRom)
+}
+
+fn rom_info(rom: &[u8], format: ReportFormat) -> String {
+    let start = DEFAULT_PROGRAM_START_OFFSET as u16;
+    let sha1 = sha1_smol::Sha1::from(rom).digest();
+    let crc32 = crc32fast::hash(rom);
+    let entry = match rom {
+        [hi, lo, ..] => Some(u16::from_be_bytes([*hi, *lo])),
+        _ => None,
+    };
+    let analysis = analysis::analyze(rom, start);
+    let extensions = extension_hints(&analysis);
+    let listing = disasm::listing(rom, start, Syntax::Classic);
+    let preview: Vec<&str> = listing.lines().take(PREVIEW_LINES).collect();
+
+    let mut info = String::new();
+    match format {
+        ReportFormat::Text => {
+            let _ = writeln!(info, "Size: {} bytes", rom.len());
+            let _ = writeln!(info, "SHA-1: {sha1}");
+            let _ = writeln!(info, "CRC32: {crc32:08X}");
+            let _ = match entry {
+                Some(opcode) => writeln!(
+                    info,
+                    "Entry: 0x{start:04X} {opcode:04X} ({})",
+                    entry_text(start, opcode)
+                ),
+                None => writeln!(info, "Entry: none"),
+            };
+            for (extension, names) in &extensions {
+                let _ = writeln!(info, "Uses {}: {}", extension.name(), names.join(", "));
+            }
+            let _ = writeln!(info, "Variant: {}", variant_hint(&analysis));
+            if !preview.is_empty() {
+                info.push_str("Preview:\n");
+            }
+            for line in &preview {
+                let _ = writeln!(info, "  {line}");
+            }
+        }
+        ReportFormat::Json => {
+            let _ = write!(
+                info,
+                "{{\"size\":{},\"sha1\":\"{sha1}\",\"crc32\":\"{crc32:08X}\",\"entry\":",
+                rom.len()
+            );
+            let _ = match entry {
+                Some(opcode) => write!(
+                    info,
+                    "{{\"address\":{start},\"opcode\":{opcode},\"instruction\":\"{}\"}}",
+                    entry_text(start, opcode)
+                ),
+                None => write!(info, "null"),
+            };
+            info.push_str(",\"extensions\":{");
+            for (n, (extension, names)) in extensions.iter().enumerate() {
+                let separator = if n == 0 { "" } else { "," };
+                let names: Vec<String> = names.iter().map(|name| format!("\"{name}\"")).collect();
+                let _ = write!(
+                    info,
+                    "{separator}\"{}\":[{}]",
+                    extension.name(),
+                    names.join(",")
+                );
+            }
+            let variant = match analysis.suggested_variant() {
+                Some(_) => "\"schip\"",
+                None => "null",
+            };
+            let _ = write!(info, "}},\"suggested_variant\":{variant},\"preview\":[");
+            for (n, line) in preview.iter().enumerate() {
+                let separator = if n == 0 { "" } else { "," };
+                let _ = write!(info, "{separator}\"{line}\"");
+            }
+            info.push_str("]}\n");
+        }
+    }
+    info
+}
+
+fn entry_text(start: u16, opcode: u16) -> String {
+    match Chip8::decode(opcode) {
+        Some(_) => disasm::disassemble(start, opcode),
+        None => "unknown opcode".to_string(),
+    }
+}
+
+///
+/// Returns the names of the instructions the ROM uses from every extension, once each.
+///
+fn extension_hints(analysis: &Analysis) -> Vec<(Extension, Vec<&'static str>)> {
+    let mut hints: Vec<(Extension, Vec<&'static str>)> = Vec::new();
+    for used in &analysis.extensions {
+        match hints
+            .iter_mut()
+            .find(|(extension, _)| *extension == used.extension)
+        {
+            Some((_, names)) if names.contains(&used.name) => {}
+            Some((_, names)) => names.push(used.name),
+            None => hints.push((used.extension, vec![used.name])),
+        }
+    }
+    hints.sort_by_key(|&(extension, _)| extension);
+    hints
+}
+
+fn variant_hint(analysis: &Analysis) -> &'static str {
+    if analysis
+        .extensions
+        .iter()
+        .any(|used| used.extension == Extension::XoChip)
+    {
+        "xochip, which isn't supported"
+    } else if analysis.suggested_variant().is_some() {
+        "schip, whose instructions aren't supported, --variant schip only follows its quirks"
+    } else {
+        "chip8"
+    }
+}
+
+fn check_report(analysis: &Analysis, format: ReportFormat) -> String {
+    let mut report = String::new();
+    match format {
+        ReportFormat::Text => {
+            analysis.write_text(&mut report);
+            if analysis.issues.is_empty() {
+                report.push_str("[+] No issues found.\n");
+            }
+        }
+        ReportFormat::Json => {
+            analysis.write_json(&mut report);
+            report.push('\n');
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use std::fs;
+    use std::io::{self, Write};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    use chip8_emu::emu::analysis::{analyze, ReportFormat};
+    use chip8_emu::emu::chip8::Chip8;
+    use chip8_emu::emu::io::{GRID_HEIGHT, GRID_WIDTH};
+    use chip8_emu::emu::replay::Replay;
+    use chip8_emu::emu::screenshot::{self, WINDOW_PALETTE};
+    use chip8_emu::frontend::sdl::keymap::{Hotkeys, KeyMap};
+    use chip8_emu::frontend::video::VideoPipe;
+
+    use super::{
+        build_chip8, check_report, load_launched_rom, load_sidecar, play_replay, rom_info, run,
+        save_profile, Launch,
+    };
+    use crate::utl::config::config_tests::parse;
+    use crate::utl::config::RunArgs;
+    use crate::utl::exit::Status;
+
+    // CLS; LD V1, 0x2A; JMP 0x200; unknown; trailing byte
+    const FIXTURE_ROM: [u8; 9] = [0x00, 0xE0, 0x61, 0x2A, 0x12, 0x00, 0xFF, 0xFF, 0x42];
+
+    #[test]
+    fn info_reports_size_hashes_and_preview() {
+        assert_eq!(
+            rom_info(&FIXTURE_ROM, ReportFormat::Text),
+            format!(
+                "Size: 9 bytes\n\
+                 SHA-1: {}\n\
+                 CRC32: {:08X}\n\
+                 Entry: 0x0200 00E0 (CLS)\n\
+                 Variant: chip8\n\
+                 Preview:\n\
+                 \x20 0x0200: 00E0  CLS\n\
+                 \x20 0x0202: 612A  LD V1, #0x2A\n\
+                 \x20 0x0204: 1200  JP #0x200\n\
+                 \x20 0x0206: FFFF  DATA\n\
+                 \x20 0x0208: 42    DATA\n",
+                sha1_smol::Sha1::from(FIXTURE_ROM).digest(),
+                crc32fast::hash(&FIXTURE_ROM)
+            )
+        );
+    }
+
+    #[test]
+    fn info_hints_the_variant() {
+        // HIGH; SCR; SCR; JMP 0x206
+        let rom = [0x00, 0xFF, 0x00, 0xFB, 0x00, 0xFB, 0x12, 0x06];
+        let info = rom_info(&rom, ReportFormat::Text);
+        assert!(info.contains(
+            "Uses SUPER-CHIP: hires, scroll right\nVariant: schip, whose instructions aren't supported, --variant schip only follows its quirks\n"
+        ));
+
+        // long i := 0x0000; JMP 0x204
+        let rom = [0xF0, 0x00, 0x00, 0x00, 0x12, 0x04];
+        assert!(
+            rom_info(&rom, ReportFormat::Text).contains("Variant: xochip, which isn't supported\n")
+        );
+    }
+
+    #[test]
+    fn info_as_json() {
+        assert_eq!(
+            rom_info(&FIXTURE_ROM, ReportFormat::Json),
+            format!(
+                "{{\"size\":9,\"sha1\":\"{}\",\"crc32\":\"{:08X}\",\
+                 \"entry\":{{\"address\":512,\"opcode\":224,\"instruction\":\"CLS\"}},\
+                 \"extensions\":{{}},\"suggested_variant\":null,\
+                 \"preview\":[\"0x0200: 00E0  CLS\",\"0x0202: 612A  LD V1, #0x2A\",\
+                 \"0x0204: 1200  JP #0x200\",\"0x0206: FFFF  DATA\",\"0x0208: 42    DATA\"]}}\n",
+                sha1_smol::Sha1::from(FIXTURE_ROM).digest(),
+                crc32fast::hash(&FIXTURE_ROM)
+            )
+        );
+
+        // HIGH; JMP 0x202
+        let info = rom_info(&[0x00, 0xFF, 0x12, 0x02], ReportFormat::Json);
+        assert!(info.contains(
+            "\"extensions\":{\"SUPER-CHIP\":[\"hires\"]},\"suggested_variant\":\"schip\""
+        ));
+    }
+
+    #[test]
+    fn info_on_empty_rom() {
+        let info = rom_info(&[], ReportFormat::Text);
+        assert!(info.contains("Entry: none\nVariant: chip8\n"));
+        assert!(!info.contains("Preview:"));
+        assert!(rom_info(&[], ReportFormat::Json).contains("\"entry\":null"));
+    }
+
+    #[test]
+    fn check_reports_the_issues() {
+        let analysis = analyze(&FIXTURE_ROM, 0x200);
+        assert_eq!(
+            check_report(&analysis, ReportFormat::Text),
+            "Reachable instructions: 3\nStack depth: at most 0 call(s)\n[+] No issues found.\n"
+        );
+
+        // CLS; JMP 0x100
+        let analysis = analyze(&[0x00, 0xE0, 0x11, 0x00], 0x200);
+        let report = check_report(&analysis, ReportFormat::Text);
+        assert!(report.starts_with("0x0202: 1100 targets 0x0100 outside the ROM\n"));
+        let report = check_report(&analysis, ReportFormat::Json);
+        assert!(report.starts_with("{\"issues\":[{\"kind\":\"target-outside-rom\""));
+        assert!(report.ends_with("}\n"));
+    }
+
+    ///
+    /// Builds the machine `args` launch the ROM with, reading its sidecar, and loads the ROM.
+    ///
+    fn launch(args: &RunArgs) -> Chip8 {
+        let rom_path = args.rom().unwrap().to_string();
+        let launch = Launch {
+            sidecar: load_sidecar(Path::new(&rom_path)).unwrap(),
+            keymap: KeyMap::default(),
+            hotkeys: Hotkeys::default(),
+            max_run_time: None,
+            demo: false,
+            rom_path: Some(rom_path),
+            recent: None,
+            video: None,
+            stream: None,
+            library: None,
+            patch: None,
+        };
+        let mut chip8 = build_chip8(args, &launch, None).unwrap();
+        load_launched_rom(&mut chip8, args, &launch).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn saved_settings_come_back_on_the_next_launch() {
+        let dir = tempfile::tempdir().unwrap();
+        let rom = dir.path().join("game.ch8");
+        fs::write(&rom, FIXTURE_ROM).unwrap();
+        let rom = rom.to_str().unwrap();
+
+        let args = parse([
+            "chip8-emu",
+            "--headless",
+            "--variant",
+            "schip",
+            "--ips",
+            "900",
+            rom,
+        ])
+        .unwrap()
+        .run;
+        let chip8 = launch(&args);
+        let path = save_profile(&chip8, &args).unwrap();
+        assert!(fs::read_to_string(path)
+            .unwrap()
+            .contains("variant = \"schip\"\nips = 900\n"));
+
+        // Without the options, the sidecar gives the same settings back
+        let relaunched = launch(&parse(["chip8-emu", "--headless", rom]).unwrap().run);
+        assert_eq!(relaunched.cpu().quirks(), chip8.cpu().quirks());
+        assert_eq!(relaunched.ips(), 900);
+        assert_eq!(relaunched.cpu().load_address(), chip8.cpu().load_address());
+
+        // The demo has nowhere to save them
+        let mut demo = Chip8::builder().headless(true).build().unwrap();
+        demo.load_demo().unwrap();
+        assert!(save_profile(&demo, &args).is_err());
+    }
+
+    ///
+    /// An encoder keeping what it's sent.
+    ///
+    struct SharedSink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn exports_every_frame_of_a_replay() {
+        let replay = Replay::parse(&format!(
+            r#"{{"sha1": "{}", "frames": 90, "events": [{{"frame": 30, "key": 5, "pressed": true}},
+                {{"frame": 40, "key": 5, "pressed": false}}]}}"#,
+            sha1_smol::Sha1::from(FIXTURE_ROM).digest()
+        ))
+        .unwrap();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let video = VideoPipe::new(SharedSink(Arc::clone(&written)), 1).lossless();
+        play_replay(&replay, &FIXTURE_ROM, Some(video), false).unwrap();
+        // Nothing dropped however fast the frames came
+        let frame_bytes = GRID_WIDTH * GRID_HEIGHT * 3;
+        assert_eq!(written.lock().unwrap().len(), 90 * frame_bytes);
+
+        // Another ROM than the one recorded on is refused
+        let err = play_replay(&replay, &[0x12, 0x00], None, false).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("The replay was recorded on the ROM"));
+    }
+
+    #[test]
+    fn headless_screenshot_of_the_demo() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("demo.png");
+        let args = parse([
+            "chip8-emu",
+            "--demo",
+            "--headless",
+            "--frames",
+            "600",
+            "--screenshot",
+            path.to_str().unwrap(),
+            "--screenshot-scale",
+            "4",
+        ])
+        .unwrap()
+        .run;
+        assert_eq!(run(&args).unwrap(), Status::Done);
+
+        let png = fs::read(&path).unwrap();
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..20], 256u32.to_be_bytes());
+        assert_eq!(png[20..24], 128u32.to_be_bytes());
+        // The same screen as a core seeded with 0 shows after as many frames, and not a blank one
+        let mut chip8 = Chip8::builder().headless(true).seed(0).build().unwrap();
+        chip8.load_demo().unwrap();
+        chip8.run_frames(600).unwrap();
+        assert!(chip8.framebuffer().contains(&1));
+        assert_eq!(
+            png,
+            screenshot::encode_palette_png(chip8.framebuffer(), 4, &WINDOW_PALETTE)
+        );
+    }
+}
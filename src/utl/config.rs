@@ -1,12 +1,1641 @@
-use clap_derive::Parser;
+use std::env;
+use std::error::Error as _;
+use std::ffi::OsString;
+use std::net::IpAddr;
+
+use chip8_emu::emu::analysis::ReportFormat;
+use chip8_emu::emu::breakpoint::{self, Breakpoint, Watch};
+use chip8_emu::emu::chip8::{Instruction, INSTRUCTIONS_PER_SECOND};
+use chip8_emu::emu::compare::QuirkSetting;
+use chip8_emu::emu::fault::{FaultPolicy, OnFault, Strictness};
+use chip8_emu::emu::poke::Poke;
+use chip8_emu::emu::quirks::Variant;
+use chip8_emu::emu::schedule::DEFAULT_MAX_CATCH_UP_FRAMES;
+use chip8_emu::emu::trace::{TraceFormat, TraceRegisters};
+use chip8_emu::emu::turbo::TurboKey;
+use chip8_emu::frontend::sdl::render::{Renderer, ScaleFilter, VisualBeep};
+use chip8_emu::frontend::sdl::window::MIN_WINDOW_SIZE;
+use clap::builder::BoolishValueParser;
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{ArgAction, CommandFactory, Parser as _};
+use clap_derive::{Args, Parser, Subcommand};
+use log::LevelFilter;
+
+use super::suite::DEFAULT_SUITE_CYCLES;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// The least severe diagnostics printed: off, error, warn, info, debug or trace. `RUST_LOG`
+    /// refines it per module, e.g. `RUST_LOG=chip8_emu::emu=trace`
+    #[arg(
+        long,
+        global = true,
+        env = "CHIP8_LOG_LEVEL",
+        value_name = "LEVEL",
+        default_value_t = LevelFilter::Info
+    )]
+    pub log_level: LevelFilter,
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+impl Args {
+    ///
+    /// Parses the command line, taking the options it doesn't set from the `CHIP8_*` environment
+    /// variables, and exits with an error naming the variable when one of them is invalid.
+    ///
+    pub fn parse_with_env() -> Self {
+        Self::try_parse_with_env(env::args_os()).unwrap_or_else(|err| err.exit())
+    }
+
+    pub fn try_parse_with_env<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Self::try_parse_from(args).map_err(blame_env_var)
+    }
+}
+
+///
+/// Rewrites an invalid value error to name the environment variable, when the value came from one.
+///
+fn blame_env_var(err: clap::Error) -> clap::Error {
+    let (Some(ContextValue::String(arg)), Some(ContextValue::String(value))) = (
+        err.get(ContextKind::InvalidArg),
+        err.get(ContextKind::InvalidValue),
+    ) else {
+        return err;
+    };
+
+    let mut command = Args::command();
+    let flag = arg.split(' ').next().unwrap_or(arg);
+    let Some(var) = command
+        .get_arguments()
+        .filter(|candidate| {
+            candidate
+                .get_long()
+                .is_some_and(|long| flag == format!("--{long}"))
+        })
+        .find_map(|candidate| candidate.get_env())
+        .filter(|var| env::var_os(var).is_some_and(|env_value| env_value == value.as_str()))
+    else {
+        return err;
+    };
+
+    let reason = err
+        .source()
+        .map(|source| format!(": {source}"))
+        .unwrap_or_default();
+    command.error(
+        ErrorKind::ValueValidation,
+        format!(
+            "invalid value '{value}' in the {} environment variable (for '{flag}'){reason}",
+            var.to_string_lossy()
+        ),
+    )
+}
+
+///
+/// The `Command` enum represents the subcommands supported by the emulator's CLI.
+///
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a ROM in the emulator (default)
+    Run(Box<RunArgs>),
+    /// Print a disassembly listing of a ROM
+    Disasm(DisasmArgs),
+    /// Assemble a source file, in the mnemonics `disasm` prints, into a ROM
+    Asm(AsmArgs),
+    /// Print a ROM's size, hashes, the variant it needs and the start of its disassembly
+    Info(InfoArgs),
+    /// Statically analyze a ROM's control flow for unknown opcodes, bad jumps and accesses,
+    /// stack overflows and the variant it needs
+    Check(CheckArgs),
+    /// Print the recently loaded ROMs
+    Recent(RecentArgs),
+    /// Run a ROM deterministically and compare every instruction against a reference trace
+    Verify(VerifyArgs),
+    /// Play a recorded run's key presses headless, as fast as possible, optionally exporting it
+    /// to a video
+    Replay(ReplayArgs),
+    /// Run every ROM of a directory headless for a budget of instructions and check the screens
+    /// they end on against the directory's suite.toml
+    TestSuite(TestSuiteArgs),
+    /// Convert save states, written by `--save-state`, to JSON to edit by hand and back
+    State(StateArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RunArgs {
+    #[arg(env = "CHIP8_ROM")]
+    pub rom: Option<String>,
+    #[arg(long, hide = true, conflicts_with = "rom")]
+    pub rom_path: Option<String>,
+    /// Width of the window, at least the screen's 64 pixels and at most the desktop's [default:
+    /// 800, or the width it was closed at]
+    #[arg(long, env = "CHIP8_WIDTH", value_parser = parse_window_width)]
+    pub width: Option<u32>,
+    /// Height of the window, at least the screen's 32 pixels and at most the desktop's [default:
+    /// 600, or the height it was closed at]
+    #[arg(long, env = "CHIP8_HEIGHT", value_parser = parse_window_height)]
+    pub height: Option<u32>,
+    /// Number of instructions executed per second [default: 450, or the ROM's sidecar file's]
+    #[arg(long, env = "CHIP8_IPS")]
+    pub ips: Option<u32>,
+    /// Frames to catch up on at most when the host falls behind, the emulated time of any more
+    /// being dropped
+    #[arg(long, env = "CHIP8_MAX_CATCH_UP", value_name = "FRAMES", default_value_t = DEFAULT_MAX_CATCH_UP_FRAMES)]
+    pub max_catch_up: u32,
+    /// Milliseconds before a frame is due to stop sleeping and spin instead, 0 only sleeping
+    #[arg(long, env = "CHIP8_SPIN_MS", value_name = "MS", default_value_t = 2)]
+    pub spin_ms: u64,
+    /// Seed for the random number generator, to make runs reproducible
+    #[arg(long, env = "CHIP8_SEED")]
+    pub seed: Option<u64>,
+    /// CHIP-8 dialect whose quirks to follow (chip8 or schip), hires for the 64×64 screen of
+    /// HiRes CHIP-8, megachip for MegaChip's 256×192 screen of colors or chip8x for the color
+    /// zones and second keypad of CHIP-8X [default: schip for ROMs running its instructions, hires
+    /// for ROMs starting with its 1260 header]
+    #[arg(long, env = "CHIP8_VARIANT")]
+    pub variant: Option<Variant>,
+    /// Name of the ROM to load when the ROM is a zip archive with several ROMs
+    #[arg(long, env = "CHIP8_ZIP_ENTRY")]
+    pub zip_entry: Option<String>,
+    /// IPS patch to apply to the ROM as it's loaded, e.g. a translation or a bugfix
+    #[arg(
+        long,
+        env = "CHIP8_PATCH",
+        value_name = "FILE",
+        requires = "rom",
+        // Clap drops the requirement once an argument the ROM conflicts with is given
+        conflicts_with_all = ["demo", "rom_dir"]
+    )]
+    pub patch: Option<String>,
+    /// Only load a ROM dropped onto the window while the emulator is paused
+    #[arg(long, env = "CHIP8_DROP_REQUIRES_PAUSE", value_parser = BoolishValueParser::new())]
+    pub drop_requires_pause: bool,
+    /// Run the demo ROM embedded in the emulator
+    #[arg(
+        long,
+        env = "CHIP8_DEMO",
+        value_parser = BoolishValueParser::new(),
+        conflicts_with_all = ["rom", "rom_path"]
+    )]
+    pub demo: bool,
+    /// Pick the ROM from a numbered list of the .ch8, .c8 and .rom files of this directory, going
+    /// back to it with F2
+    #[arg(
+        long,
+        env = "CHIP8_ROM_DIR",
+        value_name = "DIR",
+        conflicts_with_all = ["rom", "rom_path", "demo", "headless", "debug", "compare"]
+    )]
+    pub rom_dir: Option<String>,
+    /// Look for ROMs in the directories under --rom-dir too
+    #[arg(
+        long,
+        env = "CHIP8_RECURSIVE",
+        value_parser = BoolishValueParser::new(),
+        requires = "rom_dir",
+        // Clap drops the requirement once an argument --rom-dir conflicts with is given
+        conflicts_with_all = ["rom", "rom_path"]
+    )]
+    pub recursive: bool,
+    /// Only list the ROMs of --rom-dir whose file name contains this, whatever its case
+    #[arg(
+        long,
+        env = "CHIP8_FILTER",
+        value_name = "TEXT",
+        requires = "rom_dir",
+        conflicts_with_all = ["rom", "rom_path"]
+    )]
+    pub filter: Option<String>,
+    /// Widen the window to show the disassembly around the PC beside the screen
+    #[arg(long, env = "CHIP8_SIDE_PANEL", value_parser = BoolishValueParser::new())]
+    pub side_panel: bool,
+    /// Tint every pixel by how recently it changed, to follow the order things are drawn in
+    #[arg(long, env = "CHIP8_DEBUG_DRAW_AGE", value_parser = BoolishValueParser::new())]
+    pub debug_draw_age: bool,
+    /// SDL video driver to open the window through, e.g. dummy to draw nowhere
+    #[arg(long, env = "CHIP8_VIDEO_DRIVER", value_name = "NAME")]
+    pub video_driver: Option<String>,
+    /// Which SDL renderer draws the window, the software one for VMs and remote desktops
+    #[arg(
+        long,
+        env = "CHIP8_RENDERER",
+        value_name = "auto|accelerated|software",
+        default_value = "auto"
+    )]
+    pub renderer: Renderer,
+    /// Wait for the display's vertical sync to show every frame
+    #[arg(
+        long,
+        env = "CHIP8_VSYNC",
+        value_name = "on|off",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        default_value = "off",
+        default_missing_value = "on",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub vsync: bool,
+    /// Cover the whole display with the window [default: as it was closed]
+    #[arg(
+        long,
+        env = "CHIP8_FULLSCREEN",
+        value_name = "on|off",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        default_missing_value = "on",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub fullscreen: Option<bool>,
+    /// Only scale the screen up by whole multiples, leaving a border around it [default: as it
+    /// was closed]
+    #[arg(
+        long,
+        env = "CHIP8_INTEGER_SCALE",
+        value_name = "on|off",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        default_missing_value = "on",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub integer_scale: Option<bool>,
+    /// How the screen is sampled as it's scaled up: sharp pixels, or smoothed for scales that
+    /// aren't whole multiples. Integer scaling always keeps them sharp
+    #[arg(
+        long,
+        env = "CHIP8_SCALE_FILTER",
+        value_name = "nearest|linear",
+        default_value = "nearest"
+    )]
+    pub scale_filter: ScaleFilter,
+    /// Show the buzzer sounding on the window too: a border around the screen, the screen
+    /// flashing or a speaker in its corner
+    #[arg(long, env = "CHIP8_VISUAL_BEEP", value_name = "border|flash|icon")]
+    pub visual_beep: Option<VisualBeep>,
+    /// Run without a window, keyboard or real-time display
+    #[arg(long, env = "CHIP8_HEADLESS", value_parser = BoolishValueParser::new())]
+    pub headless: bool,
+    /// Stop after executing this many instructions
+    #[arg(long, env = "CHIP8_MAX_CYCLES", value_name = "N")]
+    pub max_cycles: Option<u64>,
+    /// Stop after running for this many seconds, time spent paused excluded
+    #[arg(long, env = "CHIP8_RUN_SECONDS", value_name = "N")]
+    pub run_seconds: Option<f64>,
+    /// Run this many frames as fast as possible, seeded with 0 unless --seed says otherwise, then
+    /// exit
+    #[arg(
+        long,
+        env = "CHIP8_FRAMES",
+        value_name = "N",
+        requires = "headless",
+        conflicts_with_all = ["debug", "max_cycles", "run_seconds"]
+    )]
+    pub frames: Option<u32>,
+    /// Save the screen as it was when the run ended to this PNG file, in the window's colors
+    #[arg(long, env = "CHIP8_SCREENSHOT", value_name = "FILE")]
+    pub screenshot: Option<String>,
+    /// How many pixels wide and high every pixel of the screenshot is
+    #[arg(
+        long,
+        env = "CHIP8_SCREENSHOT_SCALE",
+        value_name = "N",
+        default_value_t = 8,
+        requires = "screenshot"
+    )]
+    pub screenshot_scale: u32,
+    /// Save the machine's state as it was when the run ended to this file, for `state dump`
+    #[arg(long, value_name = "FILE")]
+    pub save_state: Option<String>,
+    /// Start the ROM from the save state in this file rather than from its beginning
+    #[arg(long, value_name = "FILE")]
+    pub load_state: Option<String>,
+    /// Record the screen to this video file, e.g. out.mp4, by piping it to ffmpeg
+    #[arg(long, env = "CHIP8_RECORD_VIDEO", value_name = "FILE")]
+    pub record_video: Option<String>,
+    /// How many pixels wide and high every pixel of the video is
+    #[arg(
+        long,
+        env = "CHIP8_RECORD_VIDEO_SCALE",
+        value_name = "N",
+        default_value_t = 8,
+        requires = "record_video"
+    )]
+    pub record_video_scale: u32,
+    /// The ffmpeg executable encoding the video
+    #[arg(
+        long,
+        env = "CHIP8_FFMPEG",
+        value_name = "PATH",
+        default_value = "ffmpeg",
+        requires = "record_video"
+    )]
+    pub ffmpeg: String,
+    /// Stream the screen to a spectator at this address, e.g. 192.168.1.20:7070, dropping frames
+    /// rather than slowing down when they can't keep up
+    #[arg(long, env = "CHIP8_STREAM_TO", value_name = "HOST:PORT")]
+    pub stream_to: Option<String>,
+    /// Only show the screens another emulator streams to this TCP port with --stream-to, running
+    /// nothing and ignoring the keys
+    #[arg(
+        long,
+        env = "CHIP8_SPECTATE",
+        value_name = "PORT",
+        conflicts_with_all = [
+            "rom",
+            "rom_path",
+            "demo",
+            "headless",
+            "debug",
+            "compare",
+            "stream_to",
+            "record_video",
+            "control_port",
+            "rom_dir"
+        ]
+    )]
+    pub spectate: Option<u16>,
+    /// Pause before executing the instructions at these addresses, e.g. 0x2A4,0x3F0, or only
+    /// when a condition holds, e.g. "0x2A4 if v5==3 && i>0x300"
+    #[arg(
+        long = "break",
+        env = "CHIP8_BREAK",
+        value_name = "ADDR [if COND]",
+        value_delimiter = ','
+    )]
+    pub breakpoints: Vec<Breakpoint>,
+    /// Pause before executing any instruction of these kinds, e.g. DRW,RND,CALL
+    #[arg(long, env = "CHIP8_BREAK_ON", value_name = "INSTR", value_delimiter = ',', value_parser = parse_break_on)]
+    pub break_on: Vec<Instruction>,
+    /// Pause whenever these general registers change, e.g. vA,vF
+    #[arg(long, env = "CHIP8_WATCH_REG", value_name = "REG", value_delimiter = ',', value_parser = parse_register_watch)]
+    pub watch_reg: Vec<Watch>,
+    /// Pause whenever the bytes of memory at these addresses change, e.g. 0x0345
+    #[arg(long, env = "CHIP8_WATCH_MEM", value_name = "ADDR", value_delimiter = ',', value_parser = parse_memory_watch)]
+    pub watch_mem: Vec<Watch>,
+    /// Press these hex keys over and over while they're held, e.g. 5:10 for key 5 ten times a
+    /// second, up to 30
+    #[arg(
+        long,
+        env = "CHIP8_TURBO_KEY",
+        value_name = "KEY:RATE",
+        value_delimiter = ','
+    )]
+    pub turbo_key: Vec<TurboKey>,
+    /// Force these bytes of RAM to a value after every frame, e.g. 0x3A2=0x63 for infinite lives
+    #[arg(
+        long,
+        env = "CHIP8_POKE",
+        value_name = "ADDR=VALUE",
+        value_delimiter = ','
+    )]
+    pub poke: Vec<Poke>,
+    /// Print every executed instruction to stdout, as aligned text or as JSON Lines
+    #[arg(long, env = "CHIP8_TRACE_FORMAT", value_name = "text|json")]
+    pub trace_format: Option<TraceFormat>,
+    /// Which registers every traced instruction lists: all of them, or the ones it changed
+    #[arg(
+        long,
+        env = "CHIP8_TRACE_REGISTERS",
+        value_name = "full|changed",
+        default_value = "changed",
+        requires = "trace_format"
+    )]
+    pub trace_registers: TraceRegisters,
+    /// Also write the crash report and a memory dump to crash-<timestamp>.txt on a runtime error
+    #[arg(long, env = "CHIP8_CRASH_DUMP", value_parser = BoolishValueParser::new())]
+    pub crash_dump: bool,
+    /// How the faults of the ROM's instructions are handled: strict crashes on every one, normal
+    /// skips unknown opcodes and accesses past the memory but crashes when the stack breaks,
+    /// lenient skips them all. Skipped faults are warned about once each and counted
+    #[arg(
+        long,
+        env = "CHIP8_STRICTNESS",
+        value_name = "strict|normal|lenient",
+        default_value = "normal"
+    )]
+    pub strictness: Strictness,
+    /// Skip or stop on unknown opcodes, whatever the strictness
+    #[arg(long, env = "CHIP8_ON_UNKNOWN_OPCODE", value_name = "stop|skip")]
+    pub on_unknown_opcode: Option<OnFault>,
+    /// Skip or stop on accesses through I past the end of the memory, whatever the strictness
+    #[arg(long, env = "CHIP8_ON_OUT_OF_BOUNDS", value_name = "stop|skip")]
+    pub on_out_of_bounds: Option<OnFault>,
+    /// Skip or stop on calls past the 16 levels of the stack and returns without a call, whatever
+    /// the strictness
+    #[arg(long, env = "CHIP8_ON_STACK_FAULT", value_name = "stop|skip")]
+    pub on_stack_fault: Option<OnFault>,
+    /// Crash on the fault past N of the ones skipped
+    #[arg(long, env = "CHIP8_MAX_FAULTS", value_name = "N")]
+    pub max_faults: Option<u64>,
+    /// Count the executions of every instruction and address, and print the hottest ones on exit
+    #[arg(long, env = "CHIP8_PROFILE", value_parser = BoolishValueParser::new())]
+    pub profile: bool,
+    /// Also write the whole profile to this file as CSV
+    #[arg(
+        long,
+        env = "CHIP8_PROFILE_CSV",
+        value_name = "FILE",
+        requires = "profile"
+    )]
+    pub profile_csv: Option<String>,
+    /// Measure how long key presses take to reach the game, from the window getting them to the
+    /// game checking the key, and print the latencies on exit
+    #[arg(long, env = "CHIP8_MEASURE_LATENCY", value_parser = BoolishValueParser::new())]
+    pub measure_latency: bool,
+    /// Warn the first time the ROM reads each V register or byte of RAM nothing wrote since the
+    /// reset, past the font and the ROM itself, with the value that happened to be there
+    #[arg(long, env = "CHIP8_DETECT_UNINIT", value_parser = BoolishValueParser::new())]
+    pub detect_uninit: bool,
+    /// Write the emulator's own tracing spans to this Chrome trace file, for Chrome's tracing page
+    /// or Perfetto to show where its time goes
+    #[cfg(feature = "profiling")]
+    #[arg(long, env = "CHIP8_TRACING_OUTPUT", value_name = "FILE")]
+    pub tracing_output: Option<String>,
+    /// Run this Rhai script along with the game, calling its `on_frame` between frames and its
+    /// `on_instruction`, if it has one, before every instruction
+    #[cfg(feature = "script")]
+    #[arg(long, env = "CHIP8_SCRIPT", value_name = "FILE")]
+    pub script: Option<String>,
+    /// Record which ROM addresses and instructions are executed, and write them to this file as
+    /// JSON on exit
+    #[arg(long, env = "CHIP8_COVERAGE", value_name = "FILE")]
+    pub coverage: Option<String>,
+    /// Take commands from scripts as line-delimited JSON on this TCP port
+    #[arg(long, env = "CHIP8_CONTROL_PORT", value_name = "PORT")]
+    pub control_port: Option<u16>,
+    /// The address the control server listens on
+    #[arg(
+        long,
+        env = "CHIP8_CONTROL_ADDR",
+        value_name = "IP",
+        default_value = "127.0.0.1",
+        requires = "control_port"
+    )]
+    pub control_addr: IpAddr,
+    /// Run two cores side by side with these quirks, e.g. "shift=on" "shift=off", the same seed
+    /// and keys, marking the first frame their screens differ
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["A", "B"],
+        conflicts_with_all = [
+            "headless",
+            "debug",
+            "control_port",
+            "max_cycles",
+            "run_seconds",
+            "record_video",
+            "stream_to"
+        ]
+    )]
+    pub compare: Vec<QuirkSetting>,
+    /// Start paused and take debugger commands (step, breakpoints, dumps) on stdin
+    #[arg(long)]
+    pub debug: bool,
+    /// Print the keypad and hotkey bindings and exit
+    #[arg(long)]
+    pub list_keys: bool,
+    /// Print the recently loaded ROMs and exit
+    #[arg(long)]
+    pub recent: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DisasmArgs {
+    #[command(flatten)]
+    pub rom: RomArgs,
+    /// Address the ROM is loaded at and starts executing from, in hex (0x200) or decimal
+    #[arg(long, value_name = "ADDR", default_value = "0x200", value_parser = parse_address)]
+    pub start_addr: u16,
+    /// Print Octo statements instead of the classic mnemonics
+    #[arg(long)]
+    pub octo_syntax: bool,
+    /// Mark every line by whether the run that wrote this coverage report executed it
+    #[arg(long, value_name = "FILE")]
+    pub coverage: Option<String>,
+    /// Only list the instructions the coverage report says were executed, in blocks, with labels
+    /// on the targets of the jumps and calls
+    #[arg(long, requires = "coverage")]
+    pub only_executed: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct InfoArgs {
+    #[command(flatten)]
+    pub rom: RomArgs,
+    /// Print the metadata as text or as a single JSON object
+    #[arg(long, value_name = "text|json", default_value = "text")]
+    pub format: ReportFormat,
+    /// Also write a sidecar file, <ROM>.toml, with the settings the ROM would run with
+    #[arg(long)]
+    pub write_sidecar: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub rom: RomArgs,
+    /// Print the report as text or as a single JSON object
+    #[arg(long, value_name = "text|json", default_value = "text")]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct AsmArgs {
+    pub source: String,
+    /// Where to write the ROM, by default the source with a .ch8 extension
+    #[arg(short, long, value_name = "ROM")]
+    pub output: Option<String>,
+    /// Address the ROM is loaded at, which the labels are relative to, in hex (0x200) or decimal
+    #[arg(long, value_name = "ADDR", default_value = "0x200", value_parser = parse_address)]
+    pub start_addr: u16,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    pub rom: RomArgs,
+    /// Reference trace in JSON Lines, e.g. written by `--trace-format json`
+    #[arg(long, value_name = "TRACE")]
+    pub against: String,
+    /// Seed for the random number generator, the one the reference was recorded with
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Number of instructions executed per second, the speed the reference was recorded at
+    #[arg(long, default_value_t = INSTRUCTIONS_PER_SECOND)]
+    pub ips: u32,
+    /// CHIP-8 dialect whose quirks to follow (chip8 or schip), hires for the 64×64 screen of
+    /// HiRes CHIP-8, megachip for MegaChip's 256×192 screen of colors or chip8x for the color
+    /// zones and second keypad of CHIP-8X [default: hires for ROMs starting with its 1260 header]
+    #[arg(long)]
+    pub variant: Option<Variant>,
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// Replay file in JSON, with the frames the run lasts and its key events
+    pub replay: String,
+    /// ROM to play the replay on [default: the replay's `rom`, relative to the replay file]
+    #[arg(long)]
+    pub rom: Option<String>,
+    /// Render the run into this video file, e.g. run.gif or run.mp4, by piping it to ffmpeg
+    #[arg(long, value_name = "FILE")]
+    pub export: Option<String>,
+    /// How many pixels wide and high every pixel of the video is
+    #[arg(long, value_name = "N", default_value_t = 8, requires = "export")]
+    pub scale: u32,
+    /// The ffmpeg executable encoding the video
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = "ffmpeg",
+        requires = "export"
+    )]
+    pub ffmpeg: String,
+}
+
+#[derive(Debug, Args)]
+pub struct TestSuiteArgs {
+    /// Directory of the ROMs to run
+    pub dir: String,
+    /// Manifest of the screens the ROMs should end on [default: suite.toml in the directory]
+    #[arg(long, value_name = "FILE")]
+    pub manifest: Option<String>,
+    /// Rewrite the manifest with the screens the ROMs ended on instead of checking them
+    #[arg(long)]
+    pub update: bool,
+    /// Instructions to run the ROMs the manifest gives no `cycles` for
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_SUITE_CYCLES)]
+    pub cycles: u64,
+    /// How many ROMs run at once [default: the number of CPUs]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub jobs: Option<u32>,
+    /// Look for ROMs in the directory's subdirectories too
     #[arg(long)]
-    pub rom_path: String,
-    #[arg(long, default_value_t = 800)]
-    pub width: u32,
-    #[arg(long, default_value_t = 600)]
-    pub height: u32,
+    pub recursive: bool,
+}
+
+fn parse_window_width(value: &str) -> Result<u32, String> {
+    parse_window_side(value, MIN_WINDOW_SIZE.0, "wide")
+}
+
+fn parse_window_height(value: &str) -> Result<u32, String> {
+    parse_window_side(value, MIN_WINDOW_SIZE.1, "tall")
+}
+
+///
+/// Parses a side of the window, which shows every pixel of the screen at least once.
+///
+fn parse_window_side(value: &str, min: u32, direction: &str) -> Result<u32, String> {
+    let side = value.parse().map_err(|err| format!("{err}"))?;
+    if side < min {
+        return Err(format!(
+            "the window must be at least {min} pixels {direction}, a pixel for every one of the \
+             {}×{} screen; scale it up with e.g. --width 640 --height 320",
+            MIN_WINDOW_SIZE.0, MIN_WINDOW_SIZE.1
+        ));
+    }
+    Ok(side)
+}
+
+fn parse_address(value: &str) -> Result<u16, String> {
+    breakpoint::parse_number(value).map_err(|err| format!("expected an address like 0x200: {err}"))
+}
+
+fn parse_break_on(value: &str) -> Result<Instruction, String> {
+    breakpoint::parse_instruction(value).map_err(|err| err.to_string())
+}
+
+fn parse_register_watch(value: &str) -> Result<Watch, String> {
+    match value.parse() {
+        Ok(watch @ Watch::Register(_)) => Ok(watch),
+        Ok(Watch::Memory(_)) => Err("expected a register like vA, see --watch-mem".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn parse_memory_watch(value: &str) -> Result<Watch, String> {
+    match value.parse() {
+        Ok(watch @ Watch::Memory(_)) => Ok(watch),
+        Ok(Watch::Register(_)) => {
+            Err("expected an address like 0x0345, see --watch-reg".to_string())
+        }
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct StateArgs {
+    #[command(subcommand)]
+    pub action: StateAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StateAction {
+    /// Print a save state's registers and screen, or all of it as JSON
+    Dump {
+        state: String,
+        /// Print the whole state as JSON, which `state build` reads back
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write the save state a JSON dump describes
+    Build {
+        json: String,
+        /// Where to write the save state
+        #[arg(short, long, value_name = "FILE")]
+        output: String,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct RecentArgs {
+    #[command(subcommand)]
+    pub action: Option<RecentAction>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RecentAction {
+    /// Forget all recently loaded ROMs
+    Clear,
+}
+
+impl RunArgs {
+    pub fn rom(&self) -> Option<&str> {
+        self.rom.as_deref().or(self.rom_path.as_deref())
+    }
+
+    ///
+    /// Returns the size the screen is laid out at, `--width` × `--height` or 800 × 600.
+    ///
+    pub fn window_size(&self) -> (u32, u32) {
+        (self.width.unwrap_or(800), self.height.unwrap_or(600))
+    }
+
+    ///
+    /// Returns the fault policy of `--strictness`, with the kinds of faults given their own option
+    /// set to it.
+    ///
+    pub fn fault_policy(&self) -> FaultPolicy {
+        let policy = self.strictness.policy();
+        FaultPolicy {
+            unknown_opcode: self.on_unknown_opcode.unwrap_or(policy.unknown_opcode),
+            out_of_bounds: self.on_out_of_bounds.unwrap_or(policy.out_of_bounds),
+            stack: self.on_stack_fault.unwrap_or(policy.stack),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct RomArgs {
+    pub rom: String,
+    /// Name of the ROM to read when the ROM is a zip archive with several ROMs
+    #[arg(long)]
+    pub zip_entry: Option<String>,
+}
+
+#[cfg(test)]
+pub mod config_tests {
+    use chip8_emu::emu::analysis::ReportFormat;
+    use std::env;
+    use std::net::IpAddr;
+    use std::sync::Mutex;
+
+    use chip8_emu::emu::breakpoint::Watch;
+    use chip8_emu::emu::chip8::Instruction;
+    use chip8_emu::emu::fault::{FaultPolicy, OnFault, Strictness};
+    use chip8_emu::emu::quirks::Variant;
+    use chip8_emu::emu::trace::{TraceFormat, TraceRegisters};
+    use chip8_emu::frontend::sdl::render::{Renderer, ScaleFilter, VisualBeep};
+    use log::LevelFilter;
+
+    use super::{Args, Command, RecentAction, StateAction};
+
+    // The environment is process-wide, so parsing must not overlap with tests changing it
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    pub fn parse<const N: usize>(args: [&str; N]) -> Result<Args, clap::Error> {
+        parse_with_env(&[], args)
+    }
+
+    fn parse_with_env<const N: usize>(
+        vars: &[(&str, &str)],
+        args: [&str; N],
+    ) -> Result<Args, clap::Error> {
+        let _guard = ENV_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        for (var, value) in vars {
+            env::set_var(var, value);
+        }
+        let result = Args::try_parse_with_env(args);
+        for (var, _) in vars {
+            env::remove_var(var);
+        }
+        result
+    }
+
+    #[test]
+    fn bare_rom_runs() {
+        let args = parse(["chip8-emu", "PONG"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.run.rom(), Some("PONG"));
+    }
+
+    #[test]
+    fn legacy_rom_path_flag_runs() {
+        let args = parse(["chip8-emu", "--rom-path", "PONG"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.run.rom(), Some("PONG"));
+    }
+
+    #[test]
+    fn run_subcommand() {
+        let args = parse(["chip8-emu", "run", "PONG", "--width", "640"]).unwrap();
+        let Some(Command::Run(run)) = args.command else {
+            panic!("expected the run subcommand");
+        };
+        assert_eq!(run.rom(), Some("PONG"));
+        assert_eq!(run.width, Some(640));
+    }
+
+    #[test]
+    fn tool_subcommands() {
+        let args = parse(["chip8-emu", "disasm", "PONG"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Disasm(disasm))
+                if disasm.rom.rom == "PONG" && disasm.start_addr == 0x200 && !disasm.octo_syntax
+        ));
+
+        let args = parse(["chip8-emu", "info", "PONG"]).unwrap();
+        assert!(
+            matches!(args.command, Some(Command::Info(info)) if info.rom.rom == "PONG" && info.format == ReportFormat::Text)
+        );
+        let args = parse(["chip8-emu", "info", "PONG", "--format", "json"]).unwrap();
+        assert!(
+            matches!(args.command, Some(Command::Info(info)) if info.format == ReportFormat::Json)
+        );
+
+        let args = parse(["chip8-emu", "check", "PONG"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Check(check)) if check.rom.rom == "PONG" && check.format == ReportFormat::Text
+        ));
+        let args = parse(["chip8-emu", "check", "PONG", "--format", "json"]).unwrap();
+        assert!(
+            matches!(args.command, Some(Command::Check(check)) if check.format == ReportFormat::Json)
+        );
+        assert!(parse(["chip8-emu", "check", "PONG", "--format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn asm_options() {
+        let args = parse(["chip8-emu", "asm", "pong.s", "-o", "pong.ch8"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Asm(asm))
+                if asm.source == "pong.s"
+                    && asm.output.as_deref() == Some("pong.ch8")
+                    && asm.start_addr == 0x200
+        ));
+        let args = parse(["chip8-emu", "asm", "pong.s", "--start-addr", "0x600"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Asm(asm)) if asm.output.is_none() && asm.start_addr == 0x600
+        ));
+        assert!(parse(["chip8-emu", "asm"]).is_err());
+    }
+
+    #[test]
+    fn verify_options() {
+        let args = parse(["chip8-emu", "verify", "PONG", "--against", "pong.jsonl"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Verify(verify))
+                if verify.rom.rom == "PONG" && verify.against == "pong.jsonl" && verify.seed == 0
+        ));
+        assert!(parse(["chip8-emu", "verify", "PONG"]).is_err());
+    }
+
+    #[test]
+    fn replay_options() {
+        let args = parse(["chip8-emu", "replay", "run.json", "--export", "run.gif"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Replay(replay))
+                if replay.replay == "run.json" && replay.rom.is_none()
+                    && replay.export.as_deref() == Some("run.gif") && replay.scale == 8
+        ));
+        // The video's settings need a video
+        assert!(parse(["chip8-emu", "replay", "run.json", "--scale", "4"]).is_err());
+        assert!(parse(["chip8-emu", "replay"]).is_err());
+    }
+
+    #[test]
+    fn test_suite_options() {
+        let args = parse(["chip8-emu", "test-suite", "roms", "--update", "--jobs", "3"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::TestSuite(suite))
+                if suite.dir == "roms" && suite.manifest.is_none() && suite.update
+                    && suite.cycles == 100_000 && suite.jobs == Some(3)
+        ));
+        assert!(parse(["chip8-emu", "test-suite", "roms", "--jobs", "0"]).is_err());
+        assert!(parse(["chip8-emu", "test-suite"]).is_err());
+    }
+
+    #[test]
+    fn disasm_options() {
+        let args = parse([
+            "chip8-emu",
+            "disasm",
+            "PONG",
+            "--start-addr",
+            "0x600",
+            "--octo-syntax",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Disasm(disasm)) if disasm.start_addr == 0x600 && disasm.octo_syntax
+        ));
+
+        let args = parse(["chip8-emu", "disasm", "PONG", "--start-addr", "512"]).unwrap();
+        assert!(
+            matches!(args.command, Some(Command::Disasm(disasm)) if disasm.start_addr == 0x200)
+        );
+        assert!(parse(["chip8-emu", "disasm", "PONG", "--start-addr", "0x10000"]).is_err());
+    }
+
+    #[test]
+    fn rom_is_optional() {
+        let args = parse(["chip8-emu"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.run.rom(), None);
+        assert!(!args.run.drop_requires_pause);
+    }
+
+    #[test]
+    fn zip_entry_option() {
+        let args = parse(["chip8-emu", "roms.zip", "--zip-entry", "PONG"]).unwrap();
+        assert_eq!(args.run.zip_entry.as_deref(), Some("PONG"));
+
+        let args = parse(["chip8-emu", "info", "roms.zip", "--zip-entry", "PONG"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Info(info)) if info.rom.zip_entry.as_deref() == Some("PONG")
+        ));
+    }
+
+    #[test]
+    fn patch_option() {
+        let args = parse(["chip8-emu", "PONG", "--patch", "fix.ips"]).unwrap();
+        assert_eq!(args.run.patch.as_deref(), Some("fix.ips"));
+        assert!(parse(["chip8-emu", "--patch", "fix.ips"]).is_err());
+        assert!(parse(["chip8-emu", "--demo", "--patch", "fix.ips"]).is_err());
+    }
+
+    #[test]
+    fn recent_options() {
+        let args = parse(["chip8-emu", "--recent"]).unwrap();
+        assert!(args.run.recent);
+
+        let args = parse(["chip8-emu", "recent"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Recent(recent)) if recent.action.is_none()));
+
+        let args = parse(["chip8-emu", "recent", "clear"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Recent(recent)) if matches!(recent.action, Some(RecentAction::Clear))
+        ));
+    }
+
+    #[test]
+    fn state_subcommands() {
+        let args = parse(["chip8-emu", "state", "dump", "slot0.sav", "--json"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::State(state))
+                if matches!(&state.action, StateAction::Dump { state, json: true } if state == "slot0.sav")
+        ));
+        let args = parse([
+            "chip8-emu",
+            "state",
+            "build",
+            "state.json",
+            "-o",
+            "slot0.sav",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::State(state))
+                if matches!(&state.action, StateAction::Build { json, output } if json == "state.json" && output == "slot0.sav")
+        ));
+        assert!(parse(["chip8-emu", "state", "build", "state.json"]).is_err());
+
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--load-state",
+            "in.sav",
+            "--save-state",
+            "out.sav",
+        ])
+        .unwrap();
+        assert_eq!(args.run.load_state.as_deref(), Some("in.sav"));
+        assert_eq!(args.run.save_state.as_deref(), Some("out.sav"));
+    }
+
+    #[test]
+    fn demo_flag() {
+        let args = parse(["chip8-emu", "--demo"]).unwrap();
+        assert!(args.run.demo);
+        assert!(parse(["chip8-emu", "--demo", "PONG"]).is_err());
+    }
+
+    #[test]
+    fn budget_options() {
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--headless",
+            "--max-cycles",
+            "1000",
+            "--run-seconds",
+            "2.5",
+        ])
+        .unwrap();
+        assert!(args.run.headless);
+        assert_eq!(args.run.max_cycles, Some(1000));
+        assert_eq!(args.run.run_seconds, Some(2.5));
+    }
+
+    #[test]
+    fn core_options() {
+        let args = parse(["chip8-emu", "PONG"]).unwrap();
+        assert_eq!(args.run.ips, None);
+        assert_eq!(args.run.max_catch_up, 5);
+        assert_eq!(args.run.spin_ms, 2);
+        assert_eq!(args.run.seed, None);
+        assert_eq!(args.run.variant, None);
+
+        let args = parse_with_env(
+            &[("CHIP8_IPS", "700")],
+            [
+                "chip8-emu",
+                "PONG",
+                "--seed",
+                "42",
+                "--variant",
+                "schip",
+                "--max-catch-up",
+                "0",
+                "--spin-ms",
+                "0",
+            ],
+        )
+        .unwrap();
+        assert_eq!(args.run.ips, Some(700));
+        assert_eq!(args.run.max_catch_up, 0);
+        assert_eq!(args.run.spin_ms, 0);
+        assert_eq!(args.run.seed, Some(42));
+        assert_eq!(args.run.variant, Some(Variant::SChip));
+
+        let err = parse_with_env(&[("CHIP8_VARIANT", "xochip")], ["chip8-emu"]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("invalid value 'xochip' in the CHIP8_VARIANT environment variable"));
+    }
+
+    #[test]
+    fn compare_option() {
+        assert!(parse(["chip8-emu", "PONG"]).unwrap().run.compare.is_empty());
+
+        let args = parse(["chip8-emu", "PONG", "--compare", "shift=on", "schip"]).unwrap();
+        let settings: Vec<String> = args.run.compare.iter().map(ToString::to_string).collect();
+        assert_eq!(settings, ["shift=on", "schip"]);
+
+        assert!(parse(["chip8-emu", "PONG", "--compare", "shift=on"]).is_err());
+        assert!(parse(["chip8-emu", "PONG", "--compare", "shift=on", "wrap=off"]).is_err());
+        assert!(parse([
+            "chip8-emu",
+            "PONG",
+            "--headless",
+            "--compare",
+            "shift=on",
+            "shift=off"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn list_keys_flag() {
+        let args = parse(["chip8-emu", "--list-keys"]).unwrap();
+        assert!(args.run.list_keys);
+    }
+
+    #[test]
+    fn break_option() {
+        assert!(parse(["chip8-emu", "PONG"])
+            .unwrap()
+            .run
+            .breakpoints
+            .is_empty());
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--break",
+            "0x2A4,0x3F0",
+            "--break",
+            "512",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.run.breakpoints,
+            [0x2A4.into(), 0x3F0.into(), 0x200.into()]
+        );
+        let args = parse_with_env(&[("CHIP8_BREAK", "0x2A4 if v5==3")], ["chip8-emu"]).unwrap();
+        assert_eq!(args.run.breakpoints, ["0x2A4 if v5 == 3".parse().unwrap()]);
+        assert!(parse(["chip8-emu", "--break", "0x2G4"]).is_err());
+        assert!(parse(["chip8-emu", "--break", "0x2A4 if v5"]).is_err());
+    }
+
+    #[test]
+    fn break_on_option() {
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--break-on",
+            "DRW,rnd",
+            "--break-on",
+            "CALL",
+        ]);
+        assert_eq!(
+            args.unwrap().run.break_on,
+            [Instruction::DRW, Instruction::RND, Instruction::CALL]
+        );
+        let args = parse_with_env(&[("CHIP8_BREAK_ON", "LDK")], ["chip8-emu"]).unwrap();
+        assert_eq!(args.run.break_on, [Instruction::LDK]);
+        assert!(parse(["chip8-emu", "--break-on", "DRAW"]).is_err());
+    }
+
+    #[test]
+    fn watch_options() {
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--watch-reg",
+            "vA,vf",
+            "--watch-mem",
+            "0x0345",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.run.watch_reg,
+            [Watch::Register(0xA), Watch::Register(0xF)]
+        );
+        assert_eq!(args.run.watch_mem, [Watch::Memory(0x345)]);
+        assert!(parse(["chip8-emu", "--watch-reg", "0x0345"]).is_err());
+        assert!(parse(["chip8-emu", "--watch-mem", "vA"]).is_err());
+    }
+
+    #[test]
+    fn trace_options() {
+        let args = parse(["chip8-emu", "PONG"]).unwrap();
+        assert_eq!(args.run.trace_format, None);
+        assert_eq!(args.run.trace_registers, TraceRegisters::Changed);
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--trace-format",
+            "json",
+            "--trace-registers",
+            "full",
+        ])
+        .unwrap();
+        assert_eq!(args.run.trace_format, Some(TraceFormat::Json));
+        assert_eq!(args.run.trace_registers, TraceRegisters::Full);
+        let args = parse_with_env(&[("CHIP8_TRACE_FORMAT", "text")], ["chip8-emu"]).unwrap();
+        assert_eq!(args.run.trace_format, Some(TraceFormat::Text));
+        assert!(parse(["chip8-emu", "--trace-format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn log_level_option() {
+        assert_eq!(parse(["chip8-emu"]).unwrap().log_level, LevelFilter::Info);
+        let args = parse(["chip8-emu", "PONG", "--log-level", "debug"]).unwrap();
+        assert_eq!(args.log_level, LevelFilter::Debug);
+        let args = parse(["chip8-emu", "info", "PONG", "--log-level", "off"]).unwrap();
+        assert_eq!(args.log_level, LevelFilter::Off);
+        let args = parse_with_env(&[("CHIP8_LOG_LEVEL", "TRACE")], ["chip8-emu"]).unwrap();
+        assert_eq!(args.log_level, LevelFilter::Trace);
+        assert!(parse(["chip8-emu", "--log-level", "loud"]).is_err());
+    }
+
+    #[test]
+    fn crash_dump_flag() {
+        assert!(!parse(["chip8-emu", "PONG"]).unwrap().run.crash_dump);
+        let args = parse(["chip8-emu", "PONG", "--crash-dump"]).unwrap();
+        assert!(args.run.crash_dump);
+        let args = parse_with_env(&[("CHIP8_CRASH_DUMP", "yes")], ["chip8-emu"]).unwrap();
+        assert!(args.run.crash_dump);
+    }
+
+    #[test]
+    fn side_panel_flag() {
+        assert!(!parse(["chip8-emu", "PONG"]).unwrap().run.side_panel);
+        let args = parse(["chip8-emu", "PONG", "--side-panel"]).unwrap();
+        assert!(args.run.side_panel);
+        let args = parse_with_env(&[("CHIP8_SIDE_PANEL", "1")], ["chip8-emu"]).unwrap();
+        assert!(args.run.side_panel);
+    }
+
+    #[test]
+    fn screenshot_options() {
+        let args = parse(["chip8-emu", "PONG", "--screenshot", "out.png"]).unwrap();
+        assert_eq!(args.run.screenshot.as_deref(), Some("out.png"));
+        assert_eq!(args.run.screenshot_scale, 8);
+        assert_eq!(args.run.frames, None);
+        let args = parse(["chip8-emu", "PONG", "--headless", "--frames", "600"]).unwrap();
+        assert_eq!(args.run.frames, Some(600));
+        // Frames are only run as fast as possible without a window
+        assert!(parse(["chip8-emu", "PONG", "--frames", "600"]).is_err());
+        assert!(parse([
+            "chip8-emu",
+            "PONG",
+            "--headless",
+            "--frames",
+            "6",
+            "--max-cycles",
+            "9"
+        ])
+        .is_err());
+        assert!(parse(["chip8-emu", "PONG", "--screenshot-scale", "2"]).is_err());
+    }
+
+    #[test]
+    fn turbo_keys() {
+        assert!(parse(["chip8-emu", "PONG"])
+            .unwrap()
+            .run
+            .turbo_key
+            .is_empty());
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--turbo-key",
+            "5:10,A:30",
+            "--turbo-key",
+            "6:2",
+        ])
+        .unwrap();
+        let keys: Vec<_> = args.run.turbo_key.iter().map(ToString::to_string).collect();
+        assert_eq!(keys, ["5:10", "A:30", "6:2"]);
+        assert!(parse(["chip8-emu", "PONG", "--turbo-key", "5"]).is_err());
+    }
+
+    #[test]
+    fn pokes() {
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--poke",
+            "0x3A2=0x63,0x3A3=1",
+            "--poke",
+            "0x3A2=9",
+        ])
+        .unwrap();
+        let pokes: Vec<_> = args.run.poke.iter().map(ToString::to_string).collect();
+        assert_eq!(pokes, ["0x3A2=0x63", "0x3A3=0x01", "0x3A2=0x09"]);
+        assert!(parse(["chip8-emu", "PONG", "--poke", "0x3A2"]).is_err());
+        assert!(parse(["chip8-emu", "PONG", "--poke", "0x1000=1"]).is_err());
+    }
+
+    #[test]
+    fn stream_options() {
+        let args = parse(["chip8-emu", "PONG", "--stream-to", "localhost:7070"]).unwrap();
+        assert_eq!(args.run.stream_to.as_deref(), Some("localhost:7070"));
+        let args = parse(["chip8-emu", "--spectate", "7070"]).unwrap();
+        assert_eq!(args.run.spectate, Some(7070));
+        assert!(parse(["chip8-emu", "--spectate", "http"]).is_err());
+        assert!(parse(["chip8-emu", "PONG", "--spectate", "7070"]).is_err());
+        assert!(parse([
+            "chip8-emu",
+            "--spectate",
+            "7070",
+            "--stream-to",
+            "localhost:7071"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn rom_dir_options() {
+        let args = parse([
+            "chip8-emu",
+            "--rom-dir",
+            "roms",
+            "--recursive",
+            "--filter",
+            "invaders",
+        ])
+        .unwrap();
+        assert_eq!(args.run.rom_dir.as_deref(), Some("roms"));
+        assert!(args.run.recursive);
+        assert_eq!(args.run.filter.as_deref(), Some("invaders"));
+        assert!(
+            !parse(["chip8-emu", "--rom-dir", "roms"])
+                .unwrap()
+                .run
+                .recursive
+        );
+        assert!(parse(["chip8-emu", "PONG", "--rom-dir", "roms"]).is_err());
+        assert!(parse(["chip8-emu", "--rom-dir", "roms", "--headless"]).is_err());
+        assert!(parse(["chip8-emu", "PONG", "--filter", "invaders"]).is_err());
+        assert!(parse(["chip8-emu", "PONG", "--recursive"]).is_err());
+    }
+
+    #[test]
+    fn record_video_options() {
+        let args = parse(["chip8-emu", "PONG", "--record-video", "out.mp4"]).unwrap();
+        assert_eq!(args.run.record_video.as_deref(), Some("out.mp4"));
+        assert_eq!(args.run.record_video_scale, 8);
+        assert_eq!(args.run.ffmpeg, "ffmpeg");
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--record-video",
+            "out.mp4",
+            "--ffmpeg",
+            "/opt/ffmpeg/bin/ffmpeg",
+            "--record-video-scale",
+            "4",
+        ])
+        .unwrap();
+        assert_eq!(args.run.ffmpeg, "/opt/ffmpeg/bin/ffmpeg");
+        assert_eq!(args.run.record_video_scale, 4);
+        assert!(parse(["chip8-emu", "PONG", "--ffmpeg", "ffmpeg"]).is_err());
+        assert!(parse([
+            "chip8-emu",
+            "PONG",
+            "--record-video",
+            "out.mp4",
+            "--compare",
+            "shift=on",
+            "shift=off",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn debug_draw_age_flag() {
+        assert!(!parse(["chip8-emu", "PONG"]).unwrap().run.debug_draw_age);
+        let args = parse(["chip8-emu", "PONG", "--debug-draw-age"]).unwrap();
+        assert!(args.run.debug_draw_age);
+        let args = parse_with_env(&[("CHIP8_DEBUG_DRAW_AGE", "1")], ["chip8-emu"]).unwrap();
+        assert!(args.run.debug_draw_age);
+    }
+
+    #[test]
+    fn render_options() {
+        let args = parse(["chip8-emu", "PONG"]).unwrap();
+        assert_eq!(args.run.renderer, Renderer::Auto);
+        assert!(!args.run.vsync);
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--renderer",
+            "software",
+            "--vsync",
+            "on",
+        ])
+        .unwrap();
+        assert_eq!(args.run.renderer, Renderer::Software);
+        assert!(args.run.vsync);
+        assert!(parse(["chip8-emu", "PONG", "--vsync"]).unwrap().run.vsync);
+        assert!(
+            !parse(["chip8-emu", "PONG", "--vsync", "off"])
+                .unwrap()
+                .run
+                .vsync
+        );
+        let args = parse_with_env(&[("CHIP8_RENDERER", "accelerated")], ["chip8-emu"]).unwrap();
+        assert_eq!(args.run.renderer, Renderer::Accelerated);
+        assert!(parse(["chip8-emu", "PONG", "--renderer", "metal"]).is_err());
+        let args = parse(["chip8-emu", "PONG", "--video-driver", "dummy"]).unwrap();
+        assert_eq!(args.run.video_driver.as_deref(), Some("dummy"));
+
+        assert_eq!(args.run.scale_filter, ScaleFilter::Nearest);
+        let args = parse(["chip8-emu", "PONG", "--scale-filter", "linear"]).unwrap();
+        assert_eq!(args.run.scale_filter, ScaleFilter::Linear);
+        let args = parse_with_env(&[("CHIP8_SCALE_FILTER", "Nearest")], ["chip8-emu"]).unwrap();
+        assert_eq!(args.run.scale_filter, ScaleFilter::Nearest);
+        assert!(parse(["chip8-emu", "PONG", "--scale-filter", "bicubic"]).is_err());
+
+        assert_eq!(args.run.visual_beep, None);
+        let args = parse(["chip8-emu", "PONG", "--visual-beep", "border"]).unwrap();
+        assert_eq!(args.run.visual_beep, Some(VisualBeep::Border));
+        let args = parse_with_env(&[("CHIP8_VISUAL_BEEP", "icon")], ["chip8-emu"]).unwrap();
+        assert_eq!(args.run.visual_beep, Some(VisualBeep::Icon));
+        assert!(parse(["chip8-emu", "PONG", "--visual-beep", "bell"]).is_err());
+    }
+
+    #[test]
+    fn profile_options() {
+        let args = parse(["chip8-emu", "PONG"]).unwrap();
+        assert!(!args.run.profile);
+        assert_eq!(args.run.profile_csv, None);
+        let args = parse(["chip8-emu", "PONG", "--profile", "--profile-csv", "p.csv"]).unwrap();
+        assert!(args.run.profile);
+        assert_eq!(args.run.profile_csv.as_deref(), Some("p.csv"));
+        let args = parse_with_env(&[("CHIP8_PROFILE", "1")], ["chip8-emu"]).unwrap();
+        assert!(args.run.profile);
+        assert!(parse(["chip8-emu", "PONG", "--profile-csv", "p.csv"]).is_err());
+    }
+
+    #[test]
+    fn measure_latency_option() {
+        assert!(!parse(["chip8-emu", "PONG"]).unwrap().run.measure_latency);
+        let args = parse(["chip8-emu", "PONG", "--measure-latency"]).unwrap();
+        assert!(args.run.measure_latency);
+        let args = parse_with_env(&[("CHIP8_MEASURE_LATENCY", "true")], ["chip8-emu"]).unwrap();
+        assert!(args.run.measure_latency);
+    }
+
+    #[test]
+    fn fault_policy_options() {
+        use OnFault::{Skip, Stop};
+        let args = parse(["chip8-emu", "PONG"]).unwrap();
+        assert_eq!(args.run.strictness, Strictness::Normal);
+        assert_eq!(args.run.fault_policy(), Strictness::Normal.policy());
+        for (name, strictness) in [
+            ("strict", Strictness::Strict),
+            ("normal", Strictness::Normal),
+            ("lenient", Strictness::Lenient),
+        ] {
+            let args = parse(["chip8-emu", "PONG", "--strictness", name]).unwrap();
+            assert_eq!(args.run.fault_policy(), strictness.policy());
+        }
+
+        // Every kind of fault can be set on its own
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--strictness",
+            "strict",
+            "--on-stack-fault",
+            "skip",
+        ])
+        .unwrap();
+        let expected = FaultPolicy {
+            unknown_opcode: Stop,
+            out_of_bounds: Stop,
+            stack: Skip,
+        };
+        assert_eq!(args.run.fault_policy(), expected);
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--strictness",
+            "lenient",
+            "--on-unknown-opcode",
+            "stop",
+            "--on-out-of-bounds",
+            "stop",
+        ])
+        .unwrap();
+        assert_eq!(args.run.fault_policy(), expected);
+        let args = parse_with_env(&[("CHIP8_STRICTNESS", "strict")], ["chip8-emu"]).unwrap();
+        assert!(!args.run.fault_policy().recovers());
+        assert!(parse(["chip8-emu", "PONG", "--strictness", "loose"]).is_err());
+        assert!(parse(["chip8-emu", "PONG", "--on-stack-fault", "nop"]).is_err());
+    }
+
+    #[cfg(feature = "script")]
+    #[test]
+    fn script_option() {
+        assert_eq!(parse(["chip8-emu", "PONG"]).unwrap().run.script, None);
+        let args = parse(["chip8-emu", "PONG", "--script", "hud.rhai"]).unwrap();
+        assert_eq!(args.run.script.as_deref(), Some("hud.rhai"));
+    }
+
+    #[test]
+    fn max_faults_option() {
+        assert_eq!(parse(["chip8-emu", "PONG"]).unwrap().run.max_faults, None);
+        let args = parse(["chip8-emu", "PONG", "--max-faults", "100"]).unwrap();
+        assert_eq!(args.run.max_faults, Some(100));
+        assert!(parse(["chip8-emu", "PONG", "--max-faults", "-1"]).is_err());
+    }
+
+    #[test]
+    fn detect_uninit_option() {
+        assert!(!parse(["chip8-emu", "PONG"]).unwrap().run.detect_uninit);
+        let args = parse(["chip8-emu", "PONG", "--detect-uninit"]).unwrap();
+        assert!(args.run.detect_uninit);
+    }
+
+    #[test]
+    fn coverage_options() {
+        assert_eq!(parse(["chip8-emu", "PONG"]).unwrap().run.coverage, None);
+        let args = parse(["chip8-emu", "PONG", "--coverage", "out.json"]).unwrap();
+        assert_eq!(args.run.coverage.as_deref(), Some("out.json"));
+
+        let args = parse(["chip8-emu", "disasm", "PONG", "--coverage", "in.json"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Disasm(disasm))
+                if disasm.coverage.as_deref() == Some("in.json") && !disasm.only_executed
+        ));
+        let args = parse([
+            "chip8-emu",
+            "disasm",
+            "PONG",
+            "--only-executed",
+            "--coverage",
+            "run.json",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Disasm(disasm)) if disasm.only_executed
+        ));
+        assert!(parse(["chip8-emu", "disasm", "PONG", "--only-executed"]).is_err());
+    }
+
+    #[test]
+    fn control_options() {
+        let args = parse(["chip8-emu", "PONG"]).unwrap();
+        assert_eq!(args.run.control_port, None);
+        assert_eq!(args.run.control_addr, IpAddr::from([127, 0, 0, 1]));
+        let args = parse(["chip8-emu", "PONG", "--control-port", "9999"]).unwrap();
+        assert_eq!(args.run.control_port, Some(9999));
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--control-port",
+            "9999",
+            "--control-addr",
+            "0.0.0.0",
+        ])
+        .unwrap();
+        assert_eq!(args.run.control_addr, IpAddr::from([0, 0, 0, 0]));
+        assert!(parse(["chip8-emu", "PONG", "--control-port", "99999"]).is_err());
+    }
+
+    #[test]
+    fn debug_flag() {
+        assert!(!parse(["chip8-emu", "PONG"]).unwrap().run.debug);
+        let args = parse(["chip8-emu", "PONG", "--debug"]).unwrap();
+        assert!(args.run.debug);
+    }
+
+    #[test]
+    fn drop_requires_pause_flag() {
+        let args = parse(["chip8-emu", "--drop-requires-pause"]).unwrap();
+        assert!(args.run.drop_requires_pause);
+    }
+
+    #[test]
+    fn options_from_env() {
+        let args = parse_with_env(
+            &[
+                ("CHIP8_ROM", "PONG"),
+                ("CHIP8_WIDTH", "640"),
+                ("CHIP8_ZIP_ENTRY", "PONG.ch8"),
+                ("CHIP8_HEADLESS", "yes"),
+                ("CHIP8_DROP_REQUIRES_PAUSE", "0"),
+                ("CHIP8_MAX_CYCLES", "1000"),
+                ("CHIP8_RUN_SECONDS", "2.5"),
+            ],
+            ["chip8-emu"],
+        )
+        .unwrap();
+        assert_eq!(args.run.rom(), Some("PONG"));
+        assert_eq!(args.run.width, Some(640));
+        assert_eq!(args.run.height, None);
+        assert_eq!(args.run.window_size(), (640, 600));
+        assert_eq!(args.run.zip_entry.as_deref(), Some("PONG.ch8"));
+        assert!(args.run.headless);
+        assert!(!args.run.drop_requires_pause);
+        assert_eq!(args.run.max_cycles, Some(1000));
+        assert_eq!(args.run.run_seconds, Some(2.5));
+    }
+
+    #[test]
+    fn cli_overrides_env() {
+        let args = parse_with_env(
+            &[("CHIP8_ROM", "PONG"), ("CHIP8_WIDTH", "640")],
+            ["chip8-emu", "TETRIS", "--width", "1024"],
+        )
+        .unwrap();
+        assert_eq!(args.run.rom(), Some("TETRIS"));
+        assert_eq!(args.run.width, Some(1024));
+    }
+
+    #[test]
+    fn window_sizes_show_the_whole_screen() {
+        let cases: [(&str, &str, Option<&str>); 8] = [
+            ("--width", "640", None),
+            ("--width", "64", None),
+            ("--height", "32", None),
+            ("--width", "20000", None),
+            ("--width", "0", Some("at least 64 pixels wide")),
+            ("--width", "63", Some("at least 64 pixels wide")),
+            ("--height", "3", Some("at least 32 pixels tall")),
+            ("--height", "tall", Some("invalid digit")),
+        ];
+        for (flag, value, error) in cases {
+            let result = parse(["chip8-emu", "PONG", flag, value]);
+            match error {
+                None => assert!(result.is_ok(), "{flag} {value}"),
+                Some(error) => {
+                    let message = result.unwrap_err().to_string();
+                    assert!(message.contains(error), "{flag} {value}: {message}");
+                }
+            }
+        }
+        // The message says how to make it bigger
+        let err = parse(["chip8-emu", "--width", "0"]).unwrap_err();
+        assert!(err.to_string().contains("--width 640 --height 320"));
+        let err = parse_with_env(&[("CHIP8_HEIGHT", "0")], ["chip8-emu"]).unwrap_err();
+        assert!(err.to_string().contains("CHIP8_HEIGHT"));
+    }
+
+    #[test]
+    fn env_applies_to_run_subcommand() {
+        let args =
+            parse_with_env(&[("CHIP8_HEIGHT", "480")], ["chip8-emu", "run", "PONG"]).unwrap();
+        let Some(Command::Run(run)) = args.command else {
+            panic!("expected the run subcommand");
+        };
+        assert_eq!(run.height, Some(480));
+    }
+
+    #[test]
+    fn invalid_env_values_name_the_variable() {
+        let err = parse_with_env(&[("CHIP8_WIDTH", "wide")], ["chip8-emu"]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("invalid value 'wide' in the CHIP8_WIDTH environment variable"));
+
+        let err = parse_with_env(&[("CHIP8_HEADLESS", "maybe")], ["chip8-emu"]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("invalid value 'maybe' in the CHIP8_HEADLESS environment variable"));
+
+        // Invalid values given on the command line keep clap's own message
+        let err = parse(["chip8-emu", "--width", "wide"]).unwrap_err();
+        assert!(!err.to_string().contains("CHIP8_WIDTH"));
+    }
 }
@@ -0,0 +1,749 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write as _};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::{thread, time};
+
+use chip8_emu::emu::breakpoint::{self, Breakpoint, BreakpointError, Watch};
+use chip8_emu::emu::chip8::{Chip8, Chip8Error, Instruction, FRAMES_PER_SECOND, WORD_SIZE};
+use chip8_emu::emu::crash;
+use chip8_emu::emu::disasm;
+use chip8_emu::emu::memory::RAM_SIZE;
+use chip8_emu::emu::poke::{Poke, PokeError};
+use chip8_emu::emu::stats::RunOutcome;
+
+const PROMPT: &str = "(chip8) ";
+
+///
+/// The `DIS_CONTEXT` constant is how many instructions `dis` shows before the address.
+///
+const DIS_CONTEXT: u16 = 4;
+
+///
+/// The `DIS_LINES` constant is how many instructions `dis` shows in total.
+///
+const DIS_LINES: u16 = 10;
+
+const HELP: &str = "\
+s, step [n]      execute n instructions (1 by default)
+n, next          step over a CALL, stopping once it returns
+finish           run until the current subroutine returns
+c, continue      run until a breakpoint, the pause hotkey or the end
+b <addr> [if c]  set a breakpoint, e.g. `b 0x2A4 if v5==3 && i>0x300`
+d [addr]         delete a breakpoint, or all of them
+bo [instr,...]   toggle breaking on kinds of instructions, e.g. `bo DRW,RND`, or list them
+watch <vX|addr>  break when the register or memory byte changes
+unwatch [target] delete a watch, or all of them
+poke <addr=val>  write a byte, e.g. `poke 0x3A2=0x63`, after every frame with `freeze` after it
+pokes            list the frozen bytes
+unpoke [addr]    let go of a frozen byte, or all of them
+r                print the registers
+bt, backtrace    print the pending calls, innermost first
+history [n]      disassemble the last n instructions executed (all kept by default)
+x <addr> [len]   hex-dump len bytes of memory (16 by default)
+dis [addr]       disassemble around addr (the PC by default)
+q, quit          quit the emulator
+";
+
+///
+/// The `Command` enum represents the commands the debugger accepts on stdin.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(u32),
+    Next,
+    Finish,
+    Continue,
+    Break(Breakpoint),
+    Delete(Option<u16>),
+    BreakOn(Vec<Instruction>),
+    Watch(Watch),
+    Unwatch(Option<Watch>),
+    Poke { poke: Poke, freeze: bool },
+    Pokes,
+    Unpoke(Option<u16>),
+    Registers,
+    Backtrace,
+    History(Option<u16>),
+    Examine { address: u16, len: u16 },
+    Disassemble(Option<u16>),
+    Help,
+    Quit,
+}
+
+fn parse_number(value: &str) -> Result<u16, String> {
+    breakpoint::parse_number(value)
+        .map_err(|_| format!("Invalid number `{value}`, expected e.g. `0x200` or `512`!"))
+}
+
+fn parse_address(value: Option<&str>) -> Result<u16, String> {
+    let address = parse_number(value.ok_or("Missing the address!")?)?;
+    if usize::from(address) < RAM_SIZE {
+        Ok(address)
+    } else {
+        Err(format!("Address 0x{address:X} is outside the memory!"))
+    }
+}
+
+fn optional_address(value: Option<&str>) -> Result<Option<u16>, String> {
+    value.map(|value| parse_address(Some(value))).transpose()
+}
+
+fn parse_watch(value: &str) -> Result<Watch, String> {
+    value
+        .parse()
+        .map_err(|err: BreakpointError| err.to_string())
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Err("Empty command!".to_string());
+        };
+        let command = match name {
+            "s" | "step" => Self::Step(words.next().map_or(Ok(1), parse_number)?.into()),
+            "n" | "next" => Self::Next,
+            "finish" => Self::Finish,
+            "c" | "continue" => Self::Continue,
+            "b" | "break" => {
+                // The condition may contain spaces, the breakpoint takes the rest of the line
+                let spec = line.trim_start()[name.len()..].trim();
+                if spec.is_empty() {
+                    return Err("Missing the address!".to_string());
+                }
+                return spec
+                    .parse()
+                    .map(Self::Break)
+                    .map_err(|err: BreakpointError| err.to_string());
+            }
+            "d" | "delete" => Self::Delete(optional_address(words.next())?),
+            "bo" | "break-on" => Self::BreakOn(
+                words
+                    .next()
+                    .map_or(Ok(Vec::new()), |names| {
+                        names
+                            .split(',')
+                            .map(breakpoint::parse_instruction)
+                            .collect()
+                    })
+                    .map_err(|err| err.to_string())?,
+            ),
+            "watch" => Self::Watch(parse_watch(
+                words.next().ok_or("Missing the register or address!")?,
+            )?),
+            "unwatch" => Self::Unwatch(words.next().map(parse_watch).transpose()?),
+            "poke" => Self::Poke {
+                poke: words
+                    .next()
+                    .ok_or("Missing the address and the value!")?
+                    .parse()
+                    .map_err(|err: PokeError| err.to_string())?,
+                freeze: match words.next() {
+                    Some("freeze") => true,
+                    Some(word) => return Err(format!("Unexpected `{word}`, try `freeze`!")),
+                    None => false,
+                },
+            },
+            "pokes" => Self::Pokes,
+            "unpoke" => Self::Unpoke(optional_address(words.next())?),
+            "r" | "registers" => Self::Registers,
+            "bt" | "backtrace" => Self::Backtrace,
+            "history" => Self::History(words.next().map(parse_number).transpose()?),
+            "x" => Self::Examine {
+                address: parse_address(words.next())?,
+                len: words.next().map_or(Ok(16), parse_number)?,
+            },
+            "dis" => Self::Disassemble(optional_address(words.next())?),
+            "h" | "help" => Self::Help,
+            "q" | "quit" => Self::Quit,
+            _ => return Err(format!("Unknown command `{name}`, try `help`!")),
+        };
+        if words.next().is_some() {
+            return Err(format!("Too many arguments for `{name}`!"));
+        }
+        Ok(command)
+    }
+}
+
+///
+/// The `Control` enum represents what the run loop does after a command.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Stay,
+    Quit,
+}
+
+///
+/// Runs the command against the emulator, writing what it prints into `out`.
+///
+pub fn execute(
+    chip8: &mut Chip8,
+    command: Command,
+    out: &mut String,
+) -> Result<Control, Chip8Error> {
+    match command {
+        Command::Next if next_opcode(chip8) & 0xF000 == 0x2000 => {
+            // Back from the call once the stack is as deep as before it, so recursion works too
+            chip8.set_temporary_breakpoint(chip8.pc() + WORD_SIZE, chip8.stack().len());
+            chip8.set_paused(false);
+        }
+        Command::Next => return execute(chip8, Command::Step(1), out),
+        Command::Finish => match chip8.stack() {
+            [.., return_address] => {
+                let depth = chip8.stack().len() - 1;
+                chip8.set_temporary_breakpoint(*return_address, depth);
+                chip8.set_paused(false);
+            }
+            [] => out.push_str("Not in a subroutine\n"),
+        },
+        Command::Step(count) => {
+            chip8.set_paused(true);
+            // Stepping stops early on a watch, like running does
+            for _ in 0..count {
+                if let Some(hit) = chip8.step()?.watch {
+                    let _ = writeln!(out, "Watch: {hit}");
+                    break;
+                }
+            }
+            let _ = writeln!(
+                out,
+                "{}",
+                disasm::instruction_at(chip8.cpu().ram(), chip8.pc())
+            );
+        }
+        Command::Continue => chip8.set_paused(false),
+        Command::Break(breakpoint) => {
+            let _ = writeln!(out, "Breakpoint at {breakpoint}");
+            chip8.add_breakpoint(breakpoint);
+        }
+        Command::Delete(Some(address)) => {
+            if !chip8.remove_breakpoint(address) {
+                let _ = writeln!(out, "No breakpoint at 0x{address:04X}");
+            }
+        }
+        Command::Delete(None) => chip8.clear_breakpoints(),
+        Command::BreakOn(instructions) => {
+            for instruction in instructions {
+                if !chip8.remove_break_on(instruction) {
+                    chip8.add_break_on(instruction);
+                }
+            }
+            let names: Vec<_> = chip8.break_on().map(|i| format!("{i:?}")).collect();
+            if names.is_empty() {
+                out.push_str("Not breaking on any instruction\n");
+            } else {
+                let _ = writeln!(out, "Breaking on {}", names.join(", "));
+            }
+        }
+        Command::Watch(watch) => {
+            chip8.add_watch(watch);
+            let _ = writeln!(out, "Watching {watch}");
+        }
+        Command::Unwatch(Some(watch)) => {
+            if !chip8.remove_watch(watch) {
+                let _ = writeln!(out, "Not watching {watch}");
+            }
+        }
+        Command::Unwatch(None) => chip8.clear_watches(),
+        Command::Poke {
+            poke,
+            freeze: false,
+        } => chip8.poke(poke),
+        Command::Poke { poke, freeze: true } => {
+            chip8.add_poke(poke);
+            let _ = writeln!(out, "Freezing {poke}");
+        }
+        Command::Pokes if chip8.pokes().is_empty() => out.push_str("No frozen bytes\n"),
+        Command::Pokes => {
+            let _ = writeln!(out, "Frozen: {}", chip8.pokes());
+        }
+        Command::Unpoke(Some(address)) => {
+            if !chip8.remove_poke(address) {
+                let _ = writeln!(out, "Not freezing 0x{address:03X}");
+            }
+        }
+        Command::Unpoke(None) => chip8.clear_pokes(),
+        Command::Registers => {
+            let _ = writeln!(out, "{}", chip8.registers());
+        }
+        Command::Backtrace => out.push_str(&chip8.backtrace()),
+        Command::History(count) => {
+            let kept = chip8.pc_history().count();
+            let skipped = count.map_or(0, |count| kept.saturating_sub(count.into()));
+            crash::write_history(chip8.pc_history().skip(skipped), out);
+        }
+        Command::Examine { address, len } => {
+            crash::write_hex_dump(chip8.cpu().ram(), address, usize::from(len), out);
+        }
+        Command::Disassemble(address) => {
+            let address = address.unwrap_or_else(|| chip8.pc());
+            write_listing(chip8, address.saturating_sub(DIS_CONTEXT * WORD_SIZE), out);
+        }
+        Command::Help => out.push_str(HELP),
+        Command::Quit => return Ok(Control::Quit),
+    }
+    Ok(Control::Stay)
+}
+
+fn next_opcode(chip8: &Chip8) -> u16 {
+    let ram = chip8.cpu().ram();
+    let byte = |address: u16| ram.read_byte(usize::from(address)).unwrap_or_default();
+    u16::from_be_bytes([byte(chip8.pc()), byte(chip8.pc() + 1)])
+}
+
+///
+/// Writes `DIS_LINES` instructions from `from`, pointing at the PC and the breakpoints.
+///
+fn write_listing(chip8: &Chip8, from: u16, out: &mut String) {
+    let breakpoints = chip8.breakpoint_addresses();
+    let ram = chip8.cpu().ram();
+    for line in disasm::memory_listing(ram, from, DIS_LINES, chip8.pc(), &breakpoints) {
+        let _ = writeln!(out, "{line}");
+    }
+}
+
+///
+/// Reads the commands typed on stdin on a thread of their own, so the emulator keeps running
+/// while nothing is typed.
+///
+fn read_commands() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn prompt() {
+    print!("{PROMPT}");
+    let _ = io::stdout().flush();
+}
+
+///
+/// Runs the emulator under the debugger: paused at first, taking commands on stdin while the
+/// window (if any) keeps showing the display. Closing stdin quits, like `q`.
+///
+pub fn run(chip8: &mut Chip8) -> Result<RunOutcome, Chip8Error> {
+    let commands = read_commands();
+    let frame = time::Duration::from_secs(1) / FRAMES_PER_SECOND;
+
+    chip8.set_paused(true);
+    println!(
+        "[+] Paused at 0x{:04X}, type `help` for the commands.",
+        chip8.pc()
+    );
+    prompt();
+    loop {
+        let frame_start = time::Instant::now();
+        match chip8.handle_inputs() {
+            // The prompt is the debugger's, there's no ROM library to go back to
+            None | Some(RunOutcome::Browse) => {}
+            Some(RunOutcome::SaveProfile) => {
+                log::warn!("The settings can't be saved from the debugger.");
+            }
+            Some(outcome) => return Ok(outcome),
+        }
+
+        loop {
+            let line = match commands.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(RunOutcome::Quit),
+            };
+            if line.trim().is_empty() {
+                prompt();
+                continue;
+            }
+
+            let mut out = String::new();
+            let control = match line.parse::<Command>() {
+                Ok(command) => execute(chip8, command, &mut out)?,
+                Err(err) => {
+                    let _ = writeln!(out, "[-] {err}");
+                    Control::Stay
+                }
+            };
+            print!("{out}");
+            if control == Control::Quit {
+                return Ok(RunOutcome::Quit);
+            }
+            if chip8.is_paused() {
+                prompt();
+            }
+        }
+
+        if !chip8.is_paused() {
+            let summary = chip8.run_frame()?;
+            match (summary.breakpoint, summary.break_on, summary.watch) {
+                (Some(address), Some(instruction), _) => {
+                    println!("[+] Break on {instruction:?} at 0x{address:04X}");
+                }
+                // Only a temporary breakpoint, where `next` or `finish` stopped
+                (Some(address), None, _) if chip8.breakpoints().all(|b| b.address != address) => {}
+                (Some(address), None, _) => println!("[+] Breakpoint hit at 0x{address:04X}"),
+                (None, _, Some(hit)) => println!("[+] Watch: {hit}"),
+                (None, _, None) => {}
+            }
+            if summary.stopped() {
+                chip8.set_paused(true);
+                println!("{}", disasm::instruction_at(chip8.cpu().ram(), chip8.pc()));
+                prompt();
+            }
+        }
+        chip8.end_frame();
+        thread::sleep(frame.saturating_sub(frame_start.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod debugger_tests {
+    use chip8_emu::emu::chip8::{Chip8, Instruction};
+
+    use chip8_emu::emu::breakpoint::Watch;
+
+    use super::{execute, Command, Control};
+
+    // LD V0, 0x01; ADD V0, 0x01; JMP 0x202
+    const COUNTER_ROM: [u8; 6] = [0x60, 0x01, 0x70, 0x01, 0x12, 0x02];
+
+    #[test]
+    fn parses_commands() {
+        let cases = [
+            ("s", Command::Step(1)),
+            ("step 10", Command::Step(10)),
+            ("n", Command::Next),
+            ("next", Command::Next),
+            ("finish", Command::Finish),
+            ("c", Command::Continue),
+            ("continue", Command::Continue),
+            ("b 0x2EA", Command::Break(0x2EA.into())),
+            ("b 512", Command::Break(0x200.into())),
+            (
+                "b 0x2A4 if v5 == 3 && i>0x300",
+                Command::Break("0x2A4 if v5==3 && i>0x300".parse().unwrap()),
+            ),
+            ("d", Command::Delete(None)),
+            ("d 0x200", Command::Delete(Some(0x200))),
+            ("bo", Command::BreakOn(Vec::new())),
+            (
+                "break-on DRW,rnd",
+                Command::BreakOn(vec![Instruction::DRW, Instruction::RND]),
+            ),
+            ("watch vA", Command::Watch(Watch::Register(0xA))),
+            ("watch 0x0345", Command::Watch(Watch::Memory(0x345))),
+            ("unwatch", Command::Unwatch(None)),
+            ("unwatch vf", Command::Unwatch(Some(Watch::Register(0xF)))),
+            (
+                "poke 0x3A2=0x63",
+                Command::Poke {
+                    poke: "0x3A2=0x63".parse().unwrap(),
+                    freeze: false,
+                },
+            ),
+            (
+                "poke 0x3A2=99 freeze",
+                Command::Poke {
+                    poke: "0x3A2=0x63".parse().unwrap(),
+                    freeze: true,
+                },
+            ),
+            ("pokes", Command::Pokes),
+            ("unpoke", Command::Unpoke(None)),
+            ("unpoke 0x3A2", Command::Unpoke(Some(0x3A2))),
+            ("r", Command::Registers),
+            ("bt", Command::Backtrace),
+            ("history", Command::History(None)),
+            ("history 5", Command::History(Some(5))),
+            (
+                "x 0x300",
+                Command::Examine {
+                    address: 0x300,
+                    len: 16,
+                },
+            ),
+            (
+                "x 0x300 0x20",
+                Command::Examine {
+                    address: 0x300,
+                    len: 32,
+                },
+            ),
+            ("dis", Command::Disassemble(None)),
+            ("  dis   0x204 ", Command::Disassemble(Some(0x204))),
+            ("q", Command::Quit),
+        ];
+        for (line, command) in cases {
+            assert_eq!(line.parse::<Command>(), Ok(command), "{line}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        assert_eq!(
+            "jump".parse::<Command>(),
+            Err("Unknown command `jump`, try `help`!".to_string())
+        );
+        assert_eq!(
+            "b".parse::<Command>(),
+            Err("Missing the address!".to_string())
+        );
+        assert_eq!(
+            "b 0x1000".parse::<Command>(),
+            Err("Address 0x1000 is outside the memory!".to_string())
+        );
+        assert_eq!(
+            "b 0x2A4 if v5 = 3".parse::<Command>(),
+            Err("Unexpected `=` in the condition!".to_string())
+        );
+        assert_eq!(
+            "s lots".parse::<Command>(),
+            Err("Invalid number `lots`, expected e.g. `0x200` or `512`!".to_string())
+        );
+        assert_eq!(
+            "watch i".parse::<Command>(),
+            Err("Unknown register `i`, expected V0-VF, I, DT or ST!".to_string())
+        );
+        assert_eq!(
+            "bo DRAW".parse::<Command>(),
+            Err("Unknown instruction `DRAW`, expected e.g. DRW, RND or CALL!".to_string())
+        );
+        assert_eq!(
+            "r 1".parse::<Command>(),
+            Err("Too many arguments for `r`!".to_string())
+        );
+        assert_eq!(
+            "poke 0x3A2 0x63".parse::<Command>(),
+            Err(
+                "Invalid poke `0x3A2`, expected an address below 0x1000 and a byte, e.g. `0x3A2=0x63`!"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            "poke 0x3A2=0x63 always".parse::<Command>(),
+            Err("Unexpected `always`, try `freeze`!".to_string())
+        );
+    }
+
+    #[test]
+    fn pokes_write_once_or_for_good() {
+        let mut chip8 = Chip8::headless();
+        // LD I, 0x300; LD [I], V0; JMP 0x200, writing 0 at 0x300 over and over
+        chip8
+            .load_rom_bytes(&[0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00])
+            .unwrap();
+        let ram = |chip8: &Chip8| chip8.cpu().ram().read_byte(0x300).unwrap();
+        let poke = |line: &str| line.parse::<Command>().unwrap();
+        let mut out = String::new();
+
+        execute(&mut chip8, poke("poke 0x300=7"), &mut out).unwrap();
+        assert_eq!(ram(&chip8), 7);
+        chip8.run_frame().unwrap();
+        assert_eq!(ram(&chip8), 0);
+        execute(&mut chip8, Command::Pokes, &mut out).unwrap();
+        assert_eq!(out, "No frozen bytes\n");
+
+        out.clear();
+        execute(&mut chip8, poke("poke 0x300=7 freeze"), &mut out).unwrap();
+        execute(&mut chip8, poke("poke 0x300=9 freeze"), &mut out).unwrap();
+        chip8.run_frame().unwrap();
+        assert_eq!(ram(&chip8), 9);
+        execute(&mut chip8, Command::Pokes, &mut out).unwrap();
+        assert_eq!(
+            out,
+            "Freezing 0x300=0x07\nFreezing 0x300=0x09\nFrozen: 0x300=0x09\n"
+        );
+
+        out.clear();
+        execute(&mut chip8, Command::Unpoke(Some(0x300)), &mut out).unwrap();
+        execute(&mut chip8, Command::Unpoke(Some(0x300)), &mut out).unwrap();
+        assert_eq!(out, "Not freezing 0x300\n");
+        chip8.run_frame().unwrap();
+        assert_eq!(ram(&chip8), 0);
+    }
+
+    #[test]
+    fn breakpoints_stop_the_run_until_deleted() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&COUNTER_ROM).unwrap();
+        let mut out = String::new();
+        execute(&mut chip8, Command::Break(0x202.into()), &mut out).unwrap();
+        assert_eq!(out, "Breakpoint at 0x0202\n");
+
+        assert_eq!(chip8.run_frame().unwrap().breakpoint, Some(0x202));
+        assert_eq!(chip8.v(0), 1);
+        // Continuing runs the instruction under the breakpoint and stops on it the next time
+        execute(&mut chip8, Command::Continue, &mut out).unwrap();
+        assert!(!chip8.is_paused());
+        assert_eq!(chip8.run_frame().unwrap().breakpoint, Some(0x202));
+        assert_eq!(chip8.v(0), 2);
+
+        out.clear();
+        execute(&mut chip8, Command::Delete(Some(0x202)), &mut out).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(chip8.run_frame().unwrap().breakpoint, None);
+        execute(&mut chip8, Command::Delete(Some(0x202)), &mut out).unwrap();
+        assert_eq!(out, "No breakpoint at 0x0202\n");
+    }
+
+    #[test]
+    fn break_on_toggles_kinds_of_instructions() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&COUNTER_ROM).unwrap();
+        let mut out = String::new();
+        let break_on = |names: &[Instruction]| Command::BreakOn(names.to_vec());
+        execute(&mut chip8, break_on(&[Instruction::JMP]), &mut out).unwrap();
+        execute(&mut chip8, break_on(&[Instruction::ADD]), &mut out).unwrap();
+        assert_eq!(out, "Breaking on JMP\nBreaking on JMP, ADD\n");
+
+        let summary = chip8.run_frame().unwrap();
+        assert_eq!(summary.breakpoint, Some(0x202));
+        assert_eq!(summary.break_on, Some(Instruction::ADD));
+        out.clear();
+        execute(&mut chip8, break_on(&[Instruction::ADD]), &mut out).unwrap();
+        let summary = chip8.run_frame().unwrap();
+        assert_eq!(summary.break_on, Some(Instruction::JMP));
+        assert_eq!(chip8.v(0), 2);
+
+        out.clear();
+        execute(&mut chip8, break_on(&[Instruction::JMP]), &mut out).unwrap();
+        assert_eq!(out, "Not breaking on any instruction\n");
+        assert_eq!(chip8.run_frame().unwrap().breakpoint, None);
+    }
+
+    // CALL 0x206; LD V2, 0x01; JMP 0x204
+    // 0x206: CALL 0x20C; ADD V0, 0x01; RET
+    // 0x20C: ADD V1, 0x01; RET
+    const NESTED_CALLS_ROM: [u8; 16] = [
+        0x22, 0x06, 0x62, 0x01, 0x12, 0x04, 0x22, 0x0C, 0x70, 0x01, 0x00, 0xEE, 0x71, 0x01, 0x00,
+        0xEE,
+    ];
+
+    // CALL 0x204; JMP 0x202
+    // 0x204: ADD V0, 0x01; SE V0, 0x03; CALL 0x204; RET
+    const RECURSIVE_ROM: [u8; 12] = [
+        0x22, 0x04, 0x12, 0x02, 0x70, 0x01, 0x30, 0x03, 0x22, 0x04, 0x00, 0xEE,
+    ];
+
+    #[test]
+    fn next_steps_over_nested_calls() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&NESTED_CALLS_ROM).unwrap();
+        let mut out = String::new();
+
+        execute(&mut chip8, Command::Next, &mut out).unwrap();
+        assert!(!chip8.is_paused());
+        assert!(out.is_empty());
+        assert_eq!(chip8.run_frames(10).unwrap().breakpoint, Some(0x202));
+        assert!(chip8.stack().is_empty());
+        assert_eq!((chip8.v(0), chip8.v(1), chip8.v(2)), (1, 1, 0));
+
+        // Anything but a CALL is a plain step
+        execute(&mut chip8, Command::Next, &mut out).unwrap();
+        assert!(chip8.is_paused());
+        assert_eq!(out, "0x0204: 1204  JP #0x204 ; halt\n");
+        assert_eq!(chip8.v(2), 1);
+    }
+
+    #[test]
+    fn next_steps_over_recursion() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&RECURSIVE_ROM).unwrap();
+        let mut out = String::new();
+        execute(&mut chip8, Command::Step(3), &mut out).unwrap();
+        assert_eq!((chip8.pc(), chip8.stack().len()), (0x208, 1));
+
+        // The recursive calls pass by 0x20A deeper in the stack first
+        execute(&mut chip8, Command::Next, &mut out).unwrap();
+        assert_eq!(chip8.run_frames(10).unwrap().breakpoint, Some(0x20A));
+        assert_eq!(chip8.stack(), [0x202]);
+        assert_eq!(chip8.v(0), 3);
+    }
+
+    #[test]
+    fn finish_runs_until_the_subroutine_returns() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&NESTED_CALLS_ROM).unwrap();
+        let mut out = String::new();
+        execute(&mut chip8, Command::Finish, &mut out).unwrap();
+        assert_eq!(out, "Not in a subroutine\n");
+
+        execute(&mut chip8, Command::Step(2), &mut out).unwrap();
+        out.clear();
+        execute(&mut chip8, Command::Backtrace, &mut out).unwrap();
+        assert_eq!(
+            out,
+            "#0 0x020C\n\
+             #1 0x020C called from 0x0206 (CALL #0x20C)\n\
+             #2 0x0206 called from 0x0200 (CALL #0x206)\n"
+        );
+        execute(&mut chip8, Command::Finish, &mut out).unwrap();
+        assert_eq!(chip8.run_frames(10).unwrap().breakpoint, Some(0x208));
+        assert_eq!(chip8.stack(), [0x202]);
+        assert_eq!((chip8.v(0), chip8.v(1)), (0, 1));
+
+        execute(&mut chip8, Command::Finish, &mut out).unwrap();
+        assert_eq!(chip8.run_frames(10).unwrap().breakpoint, Some(0x202));
+        assert!(chip8.stack().is_empty());
+        assert_eq!(chip8.v(0), 1);
+    }
+
+    #[test]
+    fn steps_and_inspects() {
+        let mut chip8 = Chip8::headless();
+        chip8.load_rom_bytes(&COUNTER_ROM).unwrap();
+
+        let mut out = String::new();
+        let control = execute(&mut chip8, Command::Step(3), &mut out).unwrap();
+        assert_eq!(control, Control::Stay);
+        assert!(chip8.is_paused());
+        assert_eq!(out, "0x0202: 7001  ADD V0, #0x01\n");
+
+        out.clear();
+        execute(&mut chip8, Command::Registers, &mut out).unwrap();
+        assert!(out.starts_with("PC=0x0202 I=0x0000 DT=0x00 ST=0x00\nV0=0x02 V1=0x00"));
+        assert!(out.ends_with("Stack: []\n"));
+
+        out.clear();
+        let examine = Command::Examine {
+            address: 0x200,
+            len: 6,
+        };
+        execute(&mut chip8, examine, &mut out).unwrap();
+        assert_eq!(out, "0x0200: 60 01 70 01 12 02\n");
+
+        out.clear();
+        execute(&mut chip8, Command::History(Some(2)), &mut out).unwrap();
+        assert_eq!(
+            out,
+            "0x0202: 7001  ADD V0, #0x01\n0x0204: 1202  JP #0x202\n"
+        );
+        out.clear();
+        execute(&mut chip8, Command::History(None), &mut out).unwrap();
+        assert!(out.starts_with("0x0200: 6001  LD V0, #0x01\n"));
+
+        execute(&mut chip8, Command::Break(0x204.into()), &mut out).unwrap();
+        out.clear();
+        execute(&mut chip8, Command::Disassemble(None), &mut out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "   0x01FA: 0000  DATA");
+        assert_eq!(lines[4], "=> 0x0202: 7001  ADD V0, #0x01");
+        assert_eq!(lines[5], " * 0x0204: 1202  JP #0x202");
+
+        assert_eq!(
+            execute(&mut chip8, Command::Quit, &mut out).unwrap(),
+            Control::Quit
+        );
+    }
+}
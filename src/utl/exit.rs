@@ -0,0 +1,137 @@
+use std::error::Error;
+use std::fmt;
+use std::process::ExitCode;
+
+use chip8_emu::Chip8Error;
+
+///
+/// The `Status` enum represents the ways a CLI command can finish successfully.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Done,
+    BudgetExhausted,
+    Interrupted,
+}
+
+///
+/// The `Failure` enum represents the ways a CLI command can fail, each with its own exit code.
+///
+#[derive(Debug)]
+pub enum Failure {
+    Other(String),
+    LoadRom(Chip8Error),
+    InitSdl(Chip8Error),
+    Runtime(Chip8Error),
+    Diverged(String),
+    Regressed(String),
+}
+
+impl Error for Failure {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::LoadRom(err) | Self::InitSdl(err) | Self::Runtime(err) => Some(err),
+            Self::Other(_) | Self::Diverged(_) | Self::Regressed(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Other(message) => write!(f, "{message}"),
+            Self::LoadRom(err) => write!(f, "Failed to load the ROM. Error => `{err}`"),
+            Self::InitSdl(err) => {
+                write!(f, "Failed to run the CHIP8 emulator. Error => `{err}`")
+            }
+            Self::Runtime(err) => write!(f, "Failed to run the app. Error => `{err}`"),
+            Self::Diverged(divergence) => {
+                write!(f, "The run diverged from the reference trace. {divergence}")
+            }
+            Self::Regressed(summary) => write!(f, "The test suite failed: {summary}."),
+        }
+    }
+}
+
+///
+/// Maps the result of a CLI command to the process exit code:
+///
+/// - `0`: the command succeeded, or the user quit the emulator
+/// - `1`: any other failure
+/// - `2`: the command line is invalid (reported by clap)
+/// - `3`: the emulator stopped because its `--max-cycles`/`--run-seconds` budget ran out
+/// - `4`: the ROM couldn't be loaded
+/// - `5`: SDL couldn't be initialized
+/// - `6`: the emulation failed at runtime
+/// - `7`: `verify` found the run diverging from the reference trace
+/// - `8`: `test-suite` found ROMs ending on other screens than the manifest's, or missing
+/// - `130`: the run was interrupted by Ctrl-C
+///
+pub fn exit_code(result: &Result<Status, Failure>) -> u8 {
+    match result {
+        Ok(Status::Done) => 0,
+        Ok(Status::BudgetExhausted) => 3,
+        Ok(Status::Interrupted) => 130,
+        Err(Failure::Other(_)) => 1,
+        Err(Failure::LoadRom(_)) => 4,
+        Err(Failure::InitSdl(_)) => 5,
+        Err(Failure::Runtime(_)) => 6,
+        Err(Failure::Diverged(_)) => 7,
+        Err(Failure::Regressed(_)) => 8,
+    }
+}
+
+///
+/// Prints the failure, if any, and returns the matching exit code.
+///
+pub fn report(result: &Result<Status, Failure>) -> ExitCode {
+    if let Err(failure) = result {
+        log::error!("{failure}");
+    }
+    ExitCode::from(exit_code(result))
+}
+
+#[cfg(test)]
+mod exit_tests {
+    use std::error::Error as _;
+    use std::io;
+
+    use chip8_emu::emu::io::DisplayError;
+    use chip8_emu::Chip8Error;
+
+    use super::{exit_code, Failure, Status};
+
+    #[test]
+    fn codes_are_distinct() {
+        let results = [
+            Ok(Status::Done),
+            Err(Failure::Other("oops".to_string())),
+            Ok(Status::BudgetExhausted),
+            Err(Failure::LoadRom(Chip8Error::EmptyRom)),
+            Err(Failure::InitSdl(DisplayError::FailedToCreateWindow.into())),
+            Err(Failure::Runtime(Chip8Error::UnknownOpcode {
+                pc: 0x200,
+                opcode: 0xF1A3,
+            })),
+            Err(Failure::Diverged("v0".to_string())),
+            Err(Failure::Regressed("1 failed".to_string())),
+            Ok(Status::Interrupted),
+        ];
+        let codes: Vec<u8> = results.iter().map(exit_code).collect();
+        assert_eq!(codes, vec![0, 1, 3, 4, 5, 6, 7, 8, 130]);
+    }
+
+    #[test]
+    fn messages_keep_the_error() {
+        let missing = io::Error::new(io::ErrorKind::NotFound, "No such file");
+        let failure = Failure::LoadRom(missing.into());
+        assert_eq!(
+            failure.to_string(),
+            "Failed to load the ROM. Error => `No such file`"
+        );
+        assert!(matches!(
+            failure.source().and_then(|err| err.downcast_ref()),
+            Some(Chip8Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::AtomicBool;
+#[cfg(unix)]
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, OnceLock};
+
+///
+/// The `STOP_REQUESTED` static is the flag the Ctrl-C handler sets, shared with the emulator.
+///
+static STOP_REQUESTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+///
+/// The `FORCED_EXIT_CODE` constant is what the process exits with on a second Ctrl-C, the shell's
+/// code for a process killed by `SIGINT`.
+///
+#[cfg(unix)]
+const FORCED_EXIT_CODE: i32 = 130;
+
+///
+/// Installs the Ctrl-C handler and returns the flag it sets, for `Chip8::set_stop_flag`, so the
+/// run ends as cleanly as by closing the window. A second Ctrl-C exits right away.
+///
+/// There is no handler outside of Unix, where Ctrl-C still kills the process.
+///
+pub fn install() -> Arc<AtomicBool> {
+    let flag = Arc::clone(STOP_REQUESTED.get_or_init(Arc::default));
+    #[cfg(unix)]
+    {
+        let handler = on_interrupt as extern "C" fn(libc::c_int);
+        // SAFETY: the handler only touches an atomic and calls `_exit`, both async-signal-safe
+        unsafe {
+            libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+        }
+    }
+    flag
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    let already_requested = STOP_REQUESTED
+        .get()
+        .is_some_and(|flag| flag.swap(true, Ordering::Relaxed));
+    if already_requested {
+        // SAFETY: `_exit` ends the process without running anything that isn't signal-safe
+        unsafe { libc::_exit(FORCED_EXIT_CODE) };
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chip8_emu::emu::quirks::Variant;
+
+use super::sidecar::{self, Sidecar};
+
+///
+/// The `ROM_EXTENSIONS` constant is the file extensions of the ROMs a library is made of, matched
+/// whatever their case.
+///
+pub const ROM_EXTENSIONS: [&str; 3] = ["ch8", "c8", "rom"];
+
+///
+/// The `PAGE_SIZE` constant is how many ROMs a page of the listing shows.
+///
+pub const PAGE_SIZE: usize = 20;
+
+///
+/// The `LibraryRom` struct represents a ROM found in the library, with what the listing tells of
+/// it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryRom {
+    pub path: PathBuf,
+    pub size: u64,
+    /// The variant its sidecar file picks, or the one its header asks for
+    pub variant: Option<Variant>,
+}
+
+impl LibraryRom {
+    fn read(path: PathBuf) -> io::Result<Self> {
+        let size = fs::metadata(&path)?.len();
+        let variant = Sidecar::load(&path)
+            .ok()
+            .flatten()
+            .and_then(|sidecar| sidecar.variant)
+            .or_else(|| {
+                let mut header = [0; 2];
+                let read = fs::File::open(&path)
+                    .and_then(|mut file| io::Read::read_exact(&mut file, &mut header));
+                read.ok().and_then(|()| Variant::detect(&header))
+            });
+        Ok(Self {
+            path,
+            size,
+            variant,
+        })
+    }
+
+    ///
+    /// Returns the name of the ROM's file, as the listing shows it.
+    ///
+    pub fn name(&self) -> String {
+        self.path.file_name().map_or_else(
+            || self.path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    }
+}
+
+///
+/// The `Library` struct represents the ROMs of a directory, sorted by name.
+///
+#[derive(Debug, Default, Clone)]
+pub struct Library {
+    roms: Vec<LibraryRom>,
+}
+
+impl Library {
+    ///
+    /// Finds the ROMs of `dir`, and of the directories under it when `recursive`, by their
+    /// extension. Files that can't be read are left out.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `dir` itself can't be read.
+    ///
+    pub fn scan(dir: &Path, recursive: bool) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        find_roms(dir, recursive, &mut paths)?;
+        let mut roms: Vec<_> = paths
+            .into_iter()
+            .filter_map(|path| LibraryRom::read(path).ok())
+            .collect();
+        roms.sort_by_cached_key(|rom| (rom.name().to_lowercase(), rom.path.clone()));
+        Ok(Self { roms })
+    }
+
+    ///
+    /// Keeps the ROMs whose file name contains `filter`, whatever its case.
+    ///
+    #[must_use]
+    pub fn filtered(mut self, filter: &str) -> Self {
+        let filter = filter.to_lowercase();
+        self.roms
+            .retain(|rom| rom.name().to_lowercase().contains(&filter));
+        self
+    }
+
+    pub fn roms(&self) -> &[LibraryRom] {
+        &self.roms
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.roms.len().div_ceil(PAGE_SIZE).max(1)
+    }
+
+    ///
+    /// Renders the numbered ROMs of the 0-based page, with their size and variant.
+    ///
+    pub fn page(&self, page: usize) -> String {
+        let start = page.saturating_mul(PAGE_SIZE);
+        let roms = self.roms.iter().enumerate().skip(start).take(PAGE_SIZE);
+        let width = roms
+            .clone()
+            .map(|(_, rom)| rom.name().chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut listing = String::new();
+        for (i, rom) in roms {
+            let _ = write!(
+                listing,
+                "{:>3}. {:<width$}  {:>5} B",
+                i + 1,
+                rom.name(),
+                rom.size
+            );
+            if let Some(variant) = rom.variant {
+                let _ = write!(listing, "  [{}]", sidecar::variant_name(variant));
+            }
+            listing.push('\n');
+        }
+        listing
+    }
+
+    ///
+    /// Returns the ROM with the given 1-based number, counted across all pages.
+    ///
+    pub fn pick(&self, number: usize) -> Option<&LibraryRom> {
+        number.checked_sub(1).and_then(|index| self.roms.get(index))
+    }
+}
+
+fn find_roms(dir: &Path, recursive: bool, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let path = entry.path();
+        if path.is_dir() {
+            // A subdirectory that can't be read doesn't spoil the rest
+            if recursive {
+                let _ = find_roms(&path, recursive, paths);
+            }
+        } else if is_rom(&path) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_rom(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        ROM_EXTENSIONS
+            .iter()
+            .any(|rom| extension.eq_ignore_ascii_case(rom))
+    })
+}
+
+#[cfg(test)]
+mod library_tests {
+    use std::fs;
+    use std::path::Path;
+
+    use chip8_emu::emu::quirks::Variant;
+
+    use super::{Library, LibraryRom, PAGE_SIZE};
+
+    fn write(path: &Path, bytes: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, bytes).unwrap();
+    }
+
+    fn names(library: &Library) -> Vec<String> {
+        library.roms().iter().map(LibraryRom::name).collect()
+    }
+
+    #[test]
+    fn scans_the_roms_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("tetris.ch8"), &[0x00, 0xE0]);
+        write(&dir.path().join("INVADERS.c8"), &[0x00, 0xE0, 0x12, 0x00]);
+        write(&dir.path().join("Pong.ROM"), &[0x00, 0xE0]);
+        write(&dir.path().join("notes.txt"), b"no");
+        write(
+            &dir.path().join("tetris.ch8.toml"),
+            b"variant = \"schip\"\n",
+        );
+        write(
+            &dir.path().join("more").join("hires worm.ch8"),
+            &[0x12, 0x60],
+        );
+
+        let library = Library::scan(dir.path(), false).unwrap();
+        assert_eq!(names(&library), ["INVADERS.c8", "Pong.ROM", "tetris.ch8"]);
+        assert_eq!(library.roms()[0].size, 4);
+        // The sidecar file picks the variant, or the header does
+        assert_eq!(library.roms()[2].variant, Some(Variant::SChip));
+        assert_eq!(library.roms()[0].variant, None);
+
+        let library = Library::scan(dir.path(), true).unwrap();
+        assert_eq!(
+            names(&library),
+            ["hires worm.ch8", "INVADERS.c8", "Pong.ROM", "tetris.ch8"]
+        );
+        assert_eq!(library.roms()[0].variant, Some(Variant::HiRes));
+
+        assert!(Library::scan(&dir.path().join("missing"), false).is_err());
+    }
+
+    #[test]
+    fn filters_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["Space Invaders.ch8", "INVADERS.c8", "Pong.ch8"] {
+            write(&dir.path().join(name), &[0x00, 0xE0]);
+        }
+        let library = Library::scan(dir.path(), false).unwrap();
+        assert_eq!(
+            names(&library.clone().filtered("invaders")),
+            ["INVADERS.c8", "Space Invaders.ch8"]
+        );
+        assert!(library.filtered("tetris").is_empty());
+    }
+
+    #[test]
+    fn lists_numbered_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..PAGE_SIZE + 3 {
+            write(&dir.path().join(format!("game{i:02}.ch8")), &[0; 10]);
+        }
+        write(
+            &dir.path().join("game00.ch8.toml"),
+            b"variant = \"chip8\"\n",
+        );
+        let library = Library::scan(dir.path(), false).unwrap();
+        assert_eq!(library.page_count(), 2);
+
+        let first = library.page(0);
+        assert_eq!(first.lines().count(), PAGE_SIZE);
+        assert_eq!(
+            first.lines().next(),
+            Some("  1. game00.ch8     10 B  [chip8]")
+        );
+        assert_eq!(first.lines().nth(1), Some("  2. game01.ch8     10 B"));
+        let second = library.page(1);
+        assert_eq!(second.lines().count(), 3);
+        assert!(second.starts_with(" 21. game20.ch8"));
+        assert_eq!(library.page(2), "");
+
+        assert_eq!(library.pick(21).unwrap().name(), "game20.ch8");
+        assert!(library.pick(0).is_none());
+        assert!(library.pick(24).is_none());
+        assert_eq!(Library::default().page_count(), 1);
+    }
+}
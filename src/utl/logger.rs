@@ -0,0 +1,129 @@
+use std::env;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+///
+/// The `LOG_ENV_VAR` constant is the variable whose directives refine `--log-level`, as in
+/// `RUST_LOG=chip8_emu::emu=trace`.
+///
+const LOG_ENV_VAR: &str = "RUST_LOG";
+
+///
+/// The `Logger` struct represents the diagnostics printed on stderr: errors and warnings as `[-]`,
+/// information as `[+]`, and everything below with its level and module.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Logger {
+    default: LevelFilter,
+    /// The level of every module given one, the longest matching one applying
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Logger {
+    ///
+    /// Returns a logger at `default`, refined by `directives`: a comma separated list of levels,
+    /// taking over the default, and of `module=level`. Invalid directives are ignored.
+    ///
+    pub fn new(default: LevelFilter, directives: &str) -> Self {
+        let mut logger = Self {
+            default,
+            targets: Vec::new(),
+        };
+        for directive in directives.split(',').map(str::trim) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    if let Ok(level) = level.parse() {
+                        logger.targets.push((target.to_string(), level));
+                    }
+                }
+                None => {
+                    if let Ok(level) = directive.parse() {
+                        logger.default = level;
+                    }
+                }
+            }
+        }
+        logger
+    }
+
+    ///
+    /// Installs the logger at `default`, refined by `RUST_LOG`.
+    ///
+    pub fn init(default: LevelFilter) {
+        let logger = Self::new(default, &env::var(LOG_ENV_VAR).unwrap_or_default());
+        log::set_max_level(logger.max_level());
+        // Only fails when a logger is already installed, which is then kept
+        let _ = log::set_boxed_logger(Box::new(logger));
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error | Level::Warn => eprintln!("[-] {}", record.args()),
+            Level::Info => eprintln!("[+] {}", record.args()),
+            level => eprintln!(
+                "[{}] {}: {}",
+                level.as_str().to_ascii_lowercase(),
+                record.target(),
+                record.args()
+            ),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod logger_tests {
+    use log::LevelFilter;
+
+    use super::Logger;
+
+    #[test]
+    fn directives_refine_the_default() {
+        let logger = Logger::new(LevelFilter::Info, "");
+        assert_eq!(logger.level_for("chip8_emu::emu::chip8"), LevelFilter::Info);
+        assert_eq!(logger.max_level(), LevelFilter::Info);
+
+        let logger = Logger::new(
+            LevelFilter::Info,
+            "warn, chip8_emu::emu=trace,chip8_emu::emu::rom=off,nonsense=loud",
+        );
+        assert_eq!(logger.level_for("chip8_emu::utl::cli"), LevelFilter::Warn);
+        assert_eq!(
+            logger.level_for("chip8_emu::emu::chip8"),
+            LevelFilter::Trace
+        );
+        assert_eq!(logger.level_for("chip8_emu::emu"), LevelFilter::Trace);
+        assert_eq!(logger.level_for("chip8_emu::emu::rom"), LevelFilter::Off);
+        assert_eq!(logger.level_for("chip8_emu::emulator"), LevelFilter::Warn);
+        assert_eq!(logger.max_level(), LevelFilter::Trace);
+    }
+}
@@ -1 +1,11 @@
+pub mod cli;
 pub mod config;
+pub mod debugger;
+pub mod exit;
+pub mod interrupt;
+pub mod library;
+pub mod logger;
+pub mod recent;
+pub mod sidecar;
+pub mod suite;
+pub mod window;
@@ -0,0 +1,228 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+///
+/// The `MAX_RECENT_ROMS` constant is the number of ROMs remembered in the recent list.
+///
+pub const MAX_RECENT_ROMS: usize = 10;
+
+///
+/// The `RECENT_FILE_NAME` constant is the name of the file storing the recent list.
+///
+const RECENT_FILE_NAME: &str = "recent.txt";
+
+///
+/// Returns the directory holding the emulator's user configuration files.
+///
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("chip8-emu"))
+}
+
+///
+/// The `RecentRom` struct represents a ROM that was successfully loaded in the past.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentRom {
+    pub path: PathBuf,
+    pub sha1: String,
+}
+
+impl RecentRom {
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+}
+
+///
+/// The `RecentRoms` struct represents the list of recently loaded ROMs, most recent first,
+/// persisted as one `<sha1>\t<path>` line per ROM.
+///
+#[derive(Debug)]
+pub struct RecentRoms {
+    file: PathBuf,
+    entries: Vec<RecentRom>,
+}
+
+impl RecentRoms {
+    ///
+    /// Loads the list stored in the user config directory, or an empty one if there is none.
+    ///
+    pub fn load_default() -> Option<Self> {
+        config_dir().map(|dir| Self::load(dir.join(RECENT_FILE_NAME)))
+    }
+
+    pub fn load(file: PathBuf) -> Self {
+        let entries = fs::read_to_string(&file)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| line.split_once('\t'))
+                    .map(|(sha1, path)| RecentRom {
+                        path: PathBuf::from(path),
+                        sha1: sha1.to_string(),
+                    })
+                    .take(MAX_RECENT_ROMS)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { file, entries }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut content = String::new();
+        for entry in &self.entries {
+            let _ = writeln!(content, "{}\t{}", entry.sha1, entry.path.display());
+        }
+        fs::write(&self.file, content)
+    }
+
+    pub fn entries(&self) -> &[RecentRom] {
+        &self.entries
+    }
+
+    ///
+    /// Moves the ROM to the front of the list, dropping the oldest entry if the list is full.
+    ///
+    pub fn push(&mut self, path: &Path, sha1: &str) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.entries.retain(|entry| entry.path != path);
+        self.entries.insert(
+            0,
+            RecentRom {
+                path,
+                sha1: sha1.to_string(),
+            },
+        );
+        self.entries.truncate(MAX_RECENT_ROMS);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    ///
+    /// Returns the ROM with the given 1-based number, unless it was moved or deleted.
+    ///
+    pub fn pick(&self, number: usize) -> Option<&RecentRom> {
+        number
+            .checked_sub(1)
+            .and_then(|index| self.entries.get(index))
+            .filter(|entry| entry.exists())
+    }
+
+    ///
+    /// Renders the numbered list, marking ROMs that were moved or deleted since.
+    ///
+    pub fn listing(&self) -> String {
+        let mut listing = String::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            let missing = if entry.exists() { "" } else { " (missing)" };
+            let _ = writeln!(listing, "{:>2}. {}{missing}", i + 1, entry.path.display());
+        }
+        listing
+    }
+}
+
+#[cfg(test)]
+mod recent_tests {
+    use std::fs;
+
+    use super::{RecentRoms, MAX_RECENT_ROMS};
+
+    #[test]
+    fn missing_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let recent = RecentRoms::load(dir.path().join("recent.txt"));
+        assert!(recent.entries().is_empty());
+        assert_eq!(recent.listing(), "");
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let rom = dir.path().join("PONG.ch8");
+        fs::write(&rom, [0x00, 0xE0]).unwrap();
+
+        let file = dir.path().join("config").join("recent.txt");
+        let mut recent = RecentRoms::load(file.clone());
+        recent.push(&rom, "abc123");
+        recent.save().unwrap();
+
+        let recent = RecentRoms::load(file);
+        assert_eq!(recent.entries().len(), 1);
+        assert_eq!(recent.entries()[0].path, rom.canonicalize().unwrap());
+        assert_eq!(recent.entries()[0].sha1, "abc123");
+    }
+
+    #[test]
+    fn push_moves_to_front_and_truncates() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recent = RecentRoms::load(dir.path().join("recent.txt"));
+        for i in 0..=MAX_RECENT_ROMS {
+            recent.push(&dir.path().join(format!("{i}.ch8")), &i.to_string());
+        }
+        assert_eq!(recent.entries().len(), MAX_RECENT_ROMS);
+        assert_eq!(recent.entries()[0].sha1, MAX_RECENT_ROMS.to_string());
+        assert!(!recent.entries().iter().any(|entry| entry.sha1 == "0"));
+
+        recent.push(&dir.path().join("5.ch8"), "5");
+        assert_eq!(recent.entries().len(), MAX_RECENT_ROMS);
+        assert_eq!(recent.entries()[0].sha1, "5");
+        assert_eq!(
+            recent
+                .entries()
+                .iter()
+                .filter(|entry| entry.sha1 == "5")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn missing_roms_are_marked_and_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let pong = dir.path().join("PONG.ch8");
+        let tetris = dir.path().join("TETRIS.ch8");
+        fs::write(&pong, [0x00, 0xE0]).unwrap();
+        fs::write(&tetris, [0x00, 0xE0]).unwrap();
+
+        let mut recent = RecentRoms::load(dir.path().join("recent.txt"));
+        recent.push(&pong, "1");
+        recent.push(&tetris, "2");
+        fs::remove_file(&tetris).unwrap();
+
+        let listing = recent.listing();
+        assert!(listing.starts_with(" 1. "));
+        assert!(listing
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with("TETRIS.ch8 (missing)"));
+        assert!(listing.lines().nth(1).unwrap().ends_with("PONG.ch8"));
+
+        assert!(recent.pick(0).is_none());
+        assert!(recent.pick(1).is_none());
+        assert_eq!(recent.pick(2).unwrap().sha1, "1");
+        assert!(recent.pick(3).is_none());
+    }
+
+    #[test]
+    fn clear_empties_the_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("recent.txt");
+        let mut recent = RecentRoms::load(file.clone());
+        recent.push(&dir.path().join("PONG.ch8"), "1");
+        recent.save().unwrap();
+
+        recent.clear();
+        recent.save().unwrap();
+        assert!(RecentRoms::load(file).entries().is_empty());
+    }
+}
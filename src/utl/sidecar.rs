@@ -0,0 +1,609 @@
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chip8_emu::emu::chip8::INSTRUCTIONS_PER_SECOND;
+use chip8_emu::emu::memory::DEFAULT_PROGRAM_START_OFFSET;
+use chip8_emu::emu::quirks::{Quirks, Variant};
+use sdl2::keyboard::Keycode;
+use toml::de::{DeTable, DeValue};
+
+use super::exit::Failure;
+
+///
+/// The `SIDECAR_EXTENSION` constant is the extension appended to a ROM's file name to name its
+/// sidecar file, as in `game.ch8.toml`.
+///
+const SIDECAR_EXTENSION: &str = "toml";
+
+///
+/// The `Sidecar` struct represents the settings of a single ROM, read from the TOML file next to
+/// it. Every setting it leaves out falls back to the default, and the command line takes over any
+/// of them.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Sidecar {
+    pub variant: Option<Variant>,
+    pub shift: Option<bool>,
+    pub memory: Option<bool>,
+    pub jump: Option<bool>,
+    pub ips: Option<u32>,
+    pub load_address: Option<u16>,
+    /// The physical keys bound to hex keys, on top of the default bindings
+    pub keys: Vec<(u8, Keycode)>,
+    /// The physical keys bound to hex keys of CHIP-8X's second keypad, under `[keys2]`
+    pub second_keys: Vec<(u8, Keycode)>,
+    /// The keys the emulator doesn't know, as `section.key`, ignored
+    pub unknown: Vec<String>,
+}
+
+///
+/// The `SidecarError` struct represents a line of a sidecar file that can't be understood, either
+/// invalid TOML or a setting with the wrong value.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SidecarError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Error for SidecarError {}
+
+impl fmt::Display for SidecarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}!", self.line, self.message)
+    }
+}
+
+///
+/// Returns the path of the ROM's sidecar file, its file name with `.toml` appended.
+///
+pub fn path_for(rom: &Path) -> PathBuf {
+    let mut name = rom.as_os_str().to_os_string();
+    name.push(".");
+    name.push(SIDECAR_EXTENSION);
+    PathBuf::from(name)
+}
+
+impl Sidecar {
+    ///
+    /// Reads the sidecar file of the ROM, if it has one.
+    ///
+    pub fn load(rom: &Path) -> Result<Option<Self>, Failure> {
+        let path = path_for(rom);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(Failure::Other(format!(
+                    "Failed to read `{}`. Error => `{err}`",
+                    path.display()
+                )))
+            }
+        };
+        Self::parse(&text).map(Some).map_err(|err| {
+            Failure::Other(format!("Invalid sidecar file `{}`.\n{err}", path.display()))
+        })
+    }
+
+    ///
+    /// Writes the sidecar file of the ROM, replacing the one it had, and returns its path.
+    ///
+    pub fn save(&self, rom: &Path) -> Result<PathBuf, Failure> {
+        let path = path_for(rom);
+        let name = rom.file_name().map_or_else(
+            || rom.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        fs::write(&path, self.to_toml(&name)).map_err(|err| {
+            Failure::Other(format!(
+                "Failed to write `{}`. Error => `{err}`",
+                path.display()
+            ))
+        })?;
+        Ok(path)
+    }
+
+    ///
+    /// Parses a sidecar file, its settings in the top-level table and `[quirks]`, and its key
+    /// bindings in `[keys]` and `[keys2]`.
+    ///
+    pub fn parse(text: &str) -> Result<Self, SidecarError> {
+        let mut sidecar = Self::default();
+        for (key, value) in parse_toml(text)?.get_ref() {
+            match value.get_ref() {
+                DeValue::Table(table) => {
+                    for (name, value) in table {
+                        sidecar
+                            .set(key.get_ref(), name.get_ref(), value.get_ref())
+                            .map_err(|message| error_at(text, name.span().start, message))?;
+                    }
+                }
+                value => sidecar
+                    .set("", key.get_ref(), value)
+                    .map_err(|message| error_at(text, key.span().start, message))?,
+            }
+        }
+        Ok(sidecar)
+    }
+
+    fn set(&mut self, section: &str, key: &str, value: &DeValue<'_>) -> Result<(), String> {
+        match (section, key) {
+            ("", "variant") => {
+                let variant = expect_string(key, value)?
+                    .parse::<Variant>()
+                    .map_err(|err| err.to_string().trim_end_matches('!').to_string())?;
+                self.variant = Some(variant);
+            }
+            ("", "ips") => {
+                let ips = expect_integer(key, value)?;
+                self.ips = Some(u32::try_from(ips).map_err(|_| format!("`ips` {ips} is too big"))?);
+            }
+            ("", "load_address") => {
+                let address = expect_integer(key, value)?;
+                self.load_address = Some(
+                    u16::try_from(address)
+                        .map_err(|_| format!("`load_address` {address} is too big"))?,
+                );
+            }
+            ("quirks", "shift") => self.shift = Some(expect_boolean(key, value)?),
+            ("quirks", "memory") => self.memory = Some(expect_boolean(key, value)?),
+            ("quirks", "jump") => self.jump = Some(expect_boolean(key, value)?),
+            ("keys" | "keys2", _) => {
+                let hex_key = u8::from_str_radix(key, 16)
+                    .ok()
+                    .filter(|&hex_key| key.len() == 1 && hex_key <= 0xF)
+                    .ok_or_else(|| format!("Expected a hex key from 0 to F, got `{key}`"))?;
+                let name = expect_string(key, value)?;
+                let keycode = Keycode::from_name(name)
+                    .ok_or_else(|| format!("Unknown key `{name}` for hex key {key}"))?;
+                if section == "keys" {
+                    self.keys.push((hex_key, keycode));
+                } else {
+                    self.second_keys.push((hex_key, keycode));
+                }
+            }
+            ("", _) => self.unknown.push(key.to_string()),
+            _ => self.unknown.push(format!("{section}.{key}")),
+        }
+        Ok(())
+    }
+
+    ///
+    /// Returns the quirks the sidecar picks: the ones of its variant, or `base`, with its
+    /// individual quirks applied on top.
+    ///
+    pub fn quirks(&self, base: Quirks) -> Quirks {
+        let quirks = self.variant.map_or(base, Variant::quirks);
+        Quirks {
+            shift: self.shift.unwrap_or(quirks.shift),
+            memory: self.memory.unwrap_or(quirks.memory),
+            jump: self.jump.unwrap_or(quirks.jump),
+        }
+    }
+
+    ///
+    /// Sets the sidecar to the settings a running ROM resolved to, leaving out the ones it would
+    /// get anyway: the quirks of `variant`, or the default ones, the default speed and load
+    /// address. The key bindings stay as they were.
+    ///
+    pub fn set_profile(
+        &mut self,
+        variant: Option<Variant>,
+        quirks: Quirks,
+        ips: u32,
+        load_address: u16,
+    ) {
+        let base = variant.map_or_else(Quirks::default, Variant::quirks);
+        self.variant = variant;
+        self.shift = (quirks.shift != base.shift).then_some(quirks.shift);
+        self.memory = (quirks.memory != base.memory).then_some(quirks.memory);
+        self.jump = (quirks.jump != base.jump).then_some(quirks.jump);
+        self.ips = (ips != INSTRUCTIONS_PER_SECOND).then_some(ips);
+        self.load_address =
+            (usize::from(load_address) != DEFAULT_PROGRAM_START_OFFSET).then_some(load_address);
+    }
+
+    ///
+    /// Writes the settings the sidecar sets as a sidecar file, the ones it leaves out left out
+    /// of it too.
+    ///
+    pub fn to_toml(&self, rom_name: &str) -> String {
+        let mut toml = String::new();
+        let _ = writeln!(
+            toml,
+            "# Settings for {rom_name}, the command line options taking over any of them"
+        );
+        if let Some(variant) = self.variant {
+            let _ = writeln!(toml, "variant = \"{}\"", variant_name(variant));
+        }
+        if let Some(ips) = self.ips {
+            let _ = writeln!(toml, "ips = {ips}");
+        }
+        if let Some(address) = self.load_address {
+            let _ = writeln!(toml, "load_address = 0x{address:03X}");
+        }
+        let quirks = [
+            ("shift", self.shift),
+            ("memory", self.memory),
+            ("jump", self.jump),
+        ];
+        if quirks.iter().any(|(_, quirk)| quirk.is_some()) {
+            toml.push_str("\n[quirks]\n");
+            for (name, quirk) in quirks {
+                if let Some(quirk) = quirk {
+                    let _ = writeln!(toml, "{name} = {quirk}");
+                }
+            }
+        }
+        for (section, keys) in [("keys", &self.keys), ("keys2", &self.second_keys)] {
+            if keys.is_empty() {
+                continue;
+            }
+            let _ = write!(toml, "\n[{section}]\n");
+            for (hex_key, key) in keys {
+                let _ = writeln!(toml, "{hex_key:X} = \"{}\"", key.name());
+            }
+        }
+        toml
+    }
+
+    ///
+    /// Returns whether the sidecar sets the variant or any quirk, leaving nothing to detect.
+    ///
+    pub const fn picks_quirks(&self) -> bool {
+        self.variant.is_some()
+            || self.shift.is_some()
+            || self.memory.is_some()
+            || self.jump.is_some()
+    }
+}
+
+///
+/// Writes a sidecar file setting every setting to the given value, the variant only if there is
+/// one, and with commented out examples of the key bindings.
+///
+pub fn template(
+    rom_name: &str,
+    variant: Option<Variant>,
+    quirks: Quirks,
+    ips: u32,
+    load_address: u16,
+) -> String {
+    let mut template = String::new();
+    let _ = writeln!(
+        template,
+        "# Settings for {rom_name}, the command line options taking over any of them"
+    );
+    let _ = match variant {
+        Some(variant) => writeln!(template, "variant = \"{}\"", variant_name(variant)),
+        None => writeln!(
+            template,
+            "# variant = \"chip8\", \"schip\", \"hires\", \"megachip\" or \"chip8x\""
+        ),
+    };
+    let _ = writeln!(template, "ips = {ips}");
+    let _ = writeln!(template, "load_address = 0x{load_address:03X}");
+    template.push_str("\n[quirks]\n");
+    let _ = writeln!(template, "shift = {}", quirks.shift);
+    let _ = writeln!(template, "memory = {}", quirks.memory);
+    let _ = writeln!(template, "jump = {}", quirks.jump);
+    template.push_str("\n[keys]\n# 5 = \"Space\"\n");
+    template
+}
+
+pub const fn variant_name(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Chip8 => "chip8",
+        Variant::SChip => "schip",
+        Variant::HiRes => "hires",
+        Variant::MegaChip => "megachip",
+        Variant::Chip8X => "chip8x",
+    }
+}
+
+///
+/// Parses TOML text into its top-level table, a syntax error reported on the line it's on.
+///
+pub(super) fn parse_toml(text: &str) -> Result<toml::Spanned<DeTable<'_>>, SidecarError> {
+    DeTable::parse(text).map_err(|err| {
+        let offset = err.span().map_or(0, |span| span.start);
+        let mut message = err.message().to_string();
+        if let Some(first) = message.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        error_at(text, offset, message)
+    })
+}
+
+///
+/// Returns the error for the line of `text` the byte at `offset` is on.
+///
+pub(super) fn error_at(text: &str, offset: usize, message: String) -> SidecarError {
+    let before = text.get(..offset).unwrap_or(text);
+    SidecarError {
+        line: before.matches('\n').count() + 1,
+        message,
+    }
+}
+
+pub(super) fn expect_string<'a>(key: &str, value: &'a DeValue<'_>) -> Result<&'a str, String> {
+    match value {
+        DeValue::String(string) => Ok(string),
+        _ => Err(format!("`{key}` should be a string")),
+    }
+}
+
+pub(super) fn expect_integer(key: &str, value: &DeValue<'_>) -> Result<u64, String> {
+    match value {
+        DeValue::Integer(integer) => u64::from_str_radix(integer.as_str(), integer.radix())
+            .map_err(|_| format!("`{key}` {integer} isn't a positive integer")),
+        _ => Err(format!("`{key}` should be an integer")),
+    }
+}
+
+fn expect_boolean(key: &str, value: &DeValue<'_>) -> Result<bool, String> {
+    match value {
+        DeValue::Boolean(boolean) => Ok(*boolean),
+        _ => Err(format!("`{key}` should be true or false")),
+    }
+}
+
+#[cfg(test)]
+mod sidecar_tests {
+    use std::fs;
+    use std::path::Path;
+
+    use chip8_emu::emu::quirks::{Quirks, Variant};
+    use sdl2::keyboard::Keycode;
+
+    use super::{path_for, template, Sidecar, SidecarError};
+
+    #[test]
+    fn sidecar_path() {
+        assert_eq!(
+            path_for(Path::new("roms/game.ch8")),
+            Path::new("roms/game.ch8.toml")
+        );
+    }
+
+    #[test]
+    fn parses_every_setting() {
+        let sidecar = Sidecar::parse(
+            "# My homebrew\n\
+             variant = \"schip\"\n\
+             ips = 1_000 # fast\n\
+             load_address = 0x600\n\
+             \n\
+             [quirks]\n\
+             memory = true\n\
+             \n\
+             [keys]\n\
+             5 = \"Space\"\n\
+             \"a\" = \"Return\"\n\
+             \n\
+             [keys2]\n\
+             1 = \"Keypad 7\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            sidecar,
+            Sidecar {
+                variant: Some(Variant::SChip),
+                memory: Some(true),
+                ips: Some(1000),
+                load_address: Some(0x600),
+                keys: vec![(0x5, Keycode::Space), (0xA, Keycode::Return)],
+                second_keys: vec![(0x1, Keycode::Kp7)],
+                ..Sidecar::default()
+            }
+        );
+        assert_eq!(
+            sidecar.quirks(Quirks::default()),
+            Quirks {
+                memory: true,
+                ..Variant::SChip.quirks()
+            }
+        );
+        assert_eq!(
+            Sidecar::default().quirks(Quirks::default()),
+            Quirks::default()
+        );
+        assert!(sidecar.picks_quirks());
+        assert!(!Sidecar::default().picks_quirks());
+    }
+
+    #[test]
+    fn parses_any_toml() {
+        let sidecar = Sidecar::parse(
+            "variant = 'chip8x'\n\
+             quirks = { jump = true }\n\
+             keys.5 = \"Space\"\n\
+             notes = \"\"\"\n\
+             [keys]\n\
+             \"\"\"\n\
+             [keys2]\n\
+             \"0\" = \"Keypad 0\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            sidecar,
+            Sidecar {
+                variant: Some(Variant::Chip8X),
+                jump: Some(true),
+                keys: vec![(0x5, Keycode::Space)],
+                second_keys: vec![(0x0, Keycode::Kp0)],
+                unknown: vec!["notes".to_string()],
+                ..Sidecar::default()
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_kept_aside() {
+        let sidecar = Sidecar::parse(
+            "palette = \"amber\"\nips = 600\n[quirks]\nwrap = true\n[sound]\nbeep = 440\n",
+        )
+        .unwrap();
+        assert_eq!(sidecar.ips, Some(600));
+        assert_eq!(sidecar.unknown, ["palette", "quirks.wrap", "sound.beep"]);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let error = |line, message: &str| {
+            Err(SidecarError {
+                line,
+                message: message.to_string(),
+            })
+        };
+        assert_eq!(
+            Sidecar::parse("ips = 700\nvariant = \"xochip\"\n"),
+            error(
+                2,
+                "Unknown variant `xochip`, expected `chip8`, `schip`, `hires`, `megachip` or `chip8x`"
+            )
+        );
+        assert_eq!(
+            Sidecar::parse("ips = fast"),
+            error(1, "Invalid boolean, expected `false`")
+        );
+        assert_eq!(
+            Sidecar::parse("ips = -1"),
+            error(1, "`ips` -1 isn't a positive integer")
+        );
+        assert_eq!(
+            Sidecar::parse("ips = true"),
+            error(1, "`ips` should be an integer")
+        );
+        assert_eq!(
+            Sidecar::parse("load_address = 0x10000"),
+            error(1, "`load_address` 65536 is too big")
+        );
+        assert_eq!(
+            Sidecar::parse("[quirks]\nshift = 1"),
+            error(2, "`shift` should be true or false")
+        );
+        assert_eq!(
+            Sidecar::parse("[keys]\n10 = \"Space\""),
+            error(2, "Expected a hex key from 0 to F, got `10`")
+        );
+        assert_eq!(
+            Sidecar::parse("[keys]\n5 = \"Nope\""),
+            error(2, "Unknown key `Nope` for hex key 5")
+        );
+        assert_eq!(
+            Sidecar::parse("[quirks\n"),
+            error(1, "Unclosed table, expected `]`")
+        );
+        assert_eq!(
+            Sidecar::parse("variant"),
+            error(1, "Key with no value, expected `=`")
+        );
+        assert_eq!(
+            Sidecar::parse("[quirks]\nshift = true\nshift = false"),
+            error(3, "Duplicate key")
+        );
+    }
+
+    #[test]
+    fn template_parses_back() {
+        let quirks = Quirks {
+            jump: false,
+            ..Variant::SChip.quirks()
+        };
+        let text = template("game.ch8", Some(Variant::SChip), quirks, 700, 0x200);
+        assert_eq!(
+            text,
+            "# Settings for game.ch8, the command line options taking over any of them\n\
+             variant = \"schip\"\n\
+             ips = 700\n\
+             load_address = 0x200\n\
+             \n\
+             [quirks]\n\
+             shift = true\n\
+             memory = false\n\
+             jump = false\n\
+             \n\
+             [keys]\n\
+             # 5 = \"Space\"\n"
+        );
+        let sidecar = Sidecar::parse(&text).unwrap();
+        assert_eq!(sidecar.quirks(Quirks::default()), quirks);
+        assert_eq!(sidecar.ips, Some(700));
+        assert_eq!(sidecar.load_address, Some(0x200));
+
+        let text = template("game.ch8", None, Quirks::default(), 450, 0x200);
+        assert!(text.contains(
+            "# variant = \"chip8\", \"schip\", \"hires\", \"megachip\" or \"chip8x\"\nips = 450\n"
+        ));
+        assert_eq!(
+            Sidecar::parse(&text)
+                .unwrap()
+                .quirks(Variant::SChip.quirks()),
+            Quirks::default()
+        );
+    }
+
+    #[test]
+    fn loads_the_file_next_to_the_rom() {
+        let dir = tempfile::tempdir().unwrap();
+        let rom = dir.path().join("game.ch8");
+        assert_eq!(Sidecar::load(&rom).unwrap(), None);
+
+        fs::write(path_for(&rom), "ips = 900\n").unwrap();
+        let sidecar = Sidecar::load(&rom).unwrap().unwrap();
+        assert_eq!(sidecar.ips, Some(900));
+
+        fs::write(path_for(&rom), "ips = -1\n").unwrap();
+        assert!(Sidecar::load(&rom).is_err());
+    }
+
+    #[test]
+    fn profiles_only_write_what_isnt_the_default() {
+        let mut sidecar = Sidecar {
+            keys: vec![(0x5, Keycode::Space)],
+            second_keys: vec![(0xA, Keycode::Kp0)],
+            ips: Some(1000),
+            ..Sidecar::default()
+        };
+        // SUPER-CHIP's quirks but for the jump, at the default speed
+        let quirks = Quirks {
+            jump: false,
+            ..Variant::SChip.quirks()
+        };
+        sidecar.set_profile(Some(Variant::SChip), quirks, 450, 0x200);
+        assert_eq!(
+            sidecar.to_toml("game.ch8"),
+            "# Settings for game.ch8, the command line options taking over any of them\n\
+             variant = \"schip\"\n\
+             \n\
+             [quirks]\n\
+             jump = false\n\
+             \n\
+             [keys]\n\
+             5 = \"Space\"\n\
+             \n\
+             [keys2]\n\
+             A = \"Keypad 0\"\n"
+        );
+
+        // Saved and read back, it resolves to the same settings
+        let dir = tempfile::tempdir().unwrap();
+        let rom = dir.path().join("game.ch8");
+        sidecar.set_profile(None, Variant::Chip8.quirks(), 900, 0x300);
+        assert_eq!(sidecar.save(&rom).unwrap(), path_for(&rom));
+        let saved = Sidecar::load(&rom).unwrap().unwrap();
+        assert_eq!(saved, sidecar);
+        assert_eq!(saved.quirks(Quirks::default()), Variant::Chip8.quirks());
+        assert_eq!((saved.ips, saved.load_address), (Some(900), Some(0x300)));
+
+        // Nothing but the header for the defaults
+        let mut sidecar = Sidecar::default();
+        sidecar.set_profile(None, Quirks::default(), 450, 0x200);
+        assert_eq!(sidecar, Sidecar::default());
+        assert_eq!(sidecar.to_toml("game.ch8").lines().count(), 1);
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use chip8_emu::emu::chip8::{Chip8, FRAMES_PER_SECOND, INSTRUCTIONS_PER_SECOND};
+use chip8_emu::emu::quirks::Variant;
+use chip8_emu::emu::rom;
+
+use super::exit::Failure;
+use super::library::LibraryRom;
+use super::sidecar::{self, SidecarError};
+use toml::de::DeValue;
+
+///
+/// The `MANIFEST_NAME` constant is the file name of the manifest `test-suite` looks for in the
+/// directory of the ROMs.
+///
+pub const MANIFEST_NAME: &str = "suite.toml";
+
+///
+/// The `DEFAULT_SUITE_CYCLES` constant is how many instructions the ROMs run for when the manifest
+/// doesn't say, almost four minutes at the default speed.
+///
+pub const DEFAULT_SUITE_CYCLES: u64 = 100_000;
+
+///
+/// How many hex digits of the hashes the summary shows.
+///
+const SHORT_HASH: usize = 12;
+
+///
+/// The `Entry` struct represents what the manifest expects of a ROM: the hash of the screen it
+/// ends on after `cycles` instructions, following the quirks of `variant`.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The SHA-1 of the framebuffer, in lowercase hex, none before the suite was updated
+    pub hash: Option<String>,
+    /// The instructions to run, the default budget when the manifest leaves them out
+    pub cycles: Option<u64>,
+    /// The variant to follow, the one the ROM's code tells when the manifest leaves it out
+    pub variant: Option<Variant>,
+}
+
+///
+/// The `Manifest` struct represents the checked-in expectations of a suite, by the ROMs' file
+/// names.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub entries: BTreeMap<String, Entry>,
+}
+
+impl Manifest {
+    ///
+    /// Reads the manifest at `path`, an empty one if there's no file yet.
+    ///
+    pub fn load(path: &Path) -> Result<Self, Failure> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|err| {
+                Failure::Other(format!("Invalid manifest {}. {err}", path.display()))
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(Failure::Other(format!(
+                "Failed to read the manifest {}. Error => `{err}`",
+                path.display()
+            ))),
+        }
+    }
+
+    ///
+    /// Parses a manifest, a table per ROM named after its file (quoted, as names with dots are in
+    /// TOML) holding its `hash`, `cycles` and `variant`, all optional.
+    ///
+    pub fn parse(text: &str) -> Result<Self, SidecarError> {
+        let mut manifest = Self::default();
+        for (rom, table) in sidecar::parse_toml(text)?.get_ref() {
+            let DeValue::Table(table) = table.get_ref() else {
+                return Err(sidecar::error_at(
+                    text,
+                    rom.span().start,
+                    format!("`{}` isn't under the table of a ROM", rom.get_ref()),
+                ));
+            };
+            let entry = manifest
+                .entries
+                .entry(rom.get_ref().to_string())
+                .or_default();
+            for (key, value) in table {
+                let error = |message| sidecar::error_at(text, key.span().start, message);
+                let (key, value) = (key.get_ref().as_ref(), value.get_ref());
+                match key {
+                    "hash" => {
+                        let hash = sidecar::expect_string(key, value).map_err(error)?;
+                        entry.hash = Some(hash.to_ascii_lowercase());
+                    }
+                    "cycles" => {
+                        entry.cycles = Some(sidecar::expect_integer(key, value).map_err(error)?);
+                    }
+                    "variant" => {
+                        let variant = sidecar::expect_string(key, value)
+                            .map_err(error)?
+                            .parse::<Variant>()
+                            .map_err(|err| {
+                                error(err.to_string().trim_end_matches('!').to_string())
+                            })?;
+                        entry.variant = Some(variant);
+                    }
+                    _ => return Err(error(format!("Unknown key `{key}`"))),
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut toml = String::from(
+            "# The screens the ROMs end on, written by `chip8-emu test-suite --update`\n",
+        );
+        for (name, entry) in &self.entries {
+            let _ = write!(toml, "\n[\"{name}\"]\n");
+            if let Some(hash) = &entry.hash {
+                let _ = writeln!(toml, "hash = \"{hash}\"");
+            }
+            if let Some(cycles) = entry.cycles {
+                let _ = writeln!(toml, "cycles = {cycles}");
+            }
+            if let Some(variant) = entry.variant {
+                let _ = writeln!(toml, "variant = \"{}\"", sidecar::variant_name(variant));
+            }
+        }
+        toml
+    }
+}
+
+///
+/// The `Outcome` enum represents how a ROM of the suite did against the manifest.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Pass(String),
+    Fail {
+        expected: String,
+        actual: String,
+    },
+    /// A ROM the manifest has no hash for yet
+    New(String),
+    /// A ROM of the manifest that isn't in the directory
+    Missing,
+    /// A ROM that couldn't be read or crashed
+    Error(String),
+}
+
+impl Outcome {
+    ///
+    /// Returns whether the suite fails because of it, new ROMs only asking for an update.
+    ///
+    pub const fn is_failure(&self) -> bool {
+        matches!(self, Self::Fail { .. } | Self::Missing | Self::Error(_))
+    }
+
+    ///
+    /// Returns the hash of the screen the ROM ended on, if it ran.
+    ///
+    pub fn hash(&self) -> Option<&str> {
+        match self {
+            Self::Pass(hash) | Self::New(hash) | Self::Fail { actual: hash, .. } => Some(hash),
+            Self::Missing | Self::Error(_) => None,
+        }
+    }
+}
+
+///
+/// The `RomResult` struct represents a ROM of the suite, the settings it ran with and how it did.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomResult {
+    pub name: String,
+    pub entry: Entry,
+    pub outcome: Outcome,
+}
+
+///
+/// Runs every ROM headless and deterministically for its budget, `jobs` at once, and checks the
+/// screens they end on against the manifest. The results are in the order of the ROMs, followed
+/// by the ROMs of the manifest that weren't found.
+///
+pub fn run(
+    roms: &[LibraryRom],
+    manifest: &Manifest,
+    default_cycles: u64,
+    jobs: usize,
+) -> Vec<RomResult> {
+    let next = AtomicUsize::new(0);
+    let done = Mutex::new(Vec::with_capacity(roms.len()));
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, roms.len().max(1)) {
+            scope.spawn(|| {
+                // Every worker takes the next ROM nobody took until there's none left
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(rom) = roms.get(index) else {
+                        break;
+                    };
+                    let result = run_rom(rom, manifest, default_cycles);
+                    done.lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push((index, result));
+                }
+            });
+        }
+    });
+
+    let mut done = done.into_inner().unwrap_or_else(PoisonError::into_inner);
+    done.sort_by_key(|(index, _)| *index);
+    let mut results: Vec<RomResult> = done.into_iter().map(|(_, result)| result).collect();
+    for (name, entry) in &manifest.entries {
+        if !results.iter().any(|result| &result.name == name) {
+            results.push(RomResult {
+                name: name.clone(),
+                entry: entry.clone(),
+                outcome: Outcome::Missing,
+            });
+        }
+    }
+    results
+}
+
+fn run_rom(rom: &LibraryRom, manifest: &Manifest, default_cycles: u64) -> RomResult {
+    let name = rom.name();
+    let expected = manifest.entries.get(&name).cloned().unwrap_or_default();
+    let entry = Entry {
+        cycles: Some(expected.cycles.unwrap_or(default_cycles)),
+        // A new ROM follows what its sidecar or header picks, as the library lists it
+        variant: expected.variant.or(rom.variant),
+        ..expected.clone()
+    };
+    let outcome = match screen_hash(&rom.path, &entry) {
+        Err(err) => Outcome::Error(err),
+        Ok(actual) => match expected.hash {
+            None => Outcome::New(actual),
+            Some(hash) if hash == actual => Outcome::Pass(actual),
+            Some(hash) => Outcome::Fail {
+                expected: hash,
+                actual,
+            },
+        },
+    };
+    RomResult {
+        name,
+        entry,
+        outcome,
+    }
+}
+
+///
+/// Returns the SHA-1 of the screen the ROM ends on after the entry's cycles, run as whole frames
+/// at the default speed with the random numbers of seed 0.
+///
+fn screen_hash(path: &Path, entry: &Entry) -> Result<String, String> {
+    let bytes = rom::read_rom(&path.to_string_lossy(), None).map_err(|err| err.to_string())?;
+    let mut builder = Chip8::builder()
+        .headless(true)
+        .seed(0)
+        .detect_variant(entry.variant.is_none());
+    if let Some(variant) = entry.variant {
+        builder = builder.variant(variant);
+    }
+    let mut chip8 = builder.build().map_err(|err| err.to_string())?;
+    chip8
+        .load_rom_bytes(&bytes)
+        .map_err(|err| err.to_string())?;
+
+    let cycles_per_frame = u64::from(INSTRUCTIONS_PER_SECOND / FRAMES_PER_SECOND).max(1);
+    let frames = entry.cycles.unwrap_or_default().div_ceil(cycles_per_frame);
+    chip8
+        .run_frames(u32::try_from(frames).unwrap_or(u32::MAX))
+        .map_err(|err| err.to_string())?;
+    Ok(sha1_smol::Sha1::from(chip8.framebuffer())
+        .digest()
+        .to_string())
+}
+
+///
+/// Returns the manifest the results make: every ROM that ran with the hash it ended on, the ones
+/// that didn't keeping their entry, and the missing ones left out.
+///
+pub fn updated(results: &[RomResult]) -> Manifest {
+    let entries = results
+        .iter()
+        .filter(|result| result.outcome != Outcome::Missing)
+        .map(|result| {
+            let hash = result.outcome.hash().map(str::to_string);
+            let entry = Entry {
+                hash: hash.or_else(|| result.entry.hash.clone()),
+                ..result.entry.clone()
+            };
+            (result.name.clone(), entry)
+        })
+        .collect();
+    Manifest { entries }
+}
+
+///
+/// Returns the table of the results, a line per ROM and the counts, e.g.
+/// `2 passed, 1 failed, 1 new, 0 missing, 0 errors`.
+///
+pub fn summary(results: &[RomResult]) -> String {
+    let short = |hash: &str| hash.chars().take(SHORT_HASH).collect::<String>();
+    let width = results
+        .iter()
+        .map(|result| result.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    let mut table = String::new();
+    let mut counts = [0; 5];
+    for result in results {
+        let (kind, label, detail) = match &result.outcome {
+            Outcome::Pass(hash) => (0, "pass", short(hash)),
+            Outcome::Fail { expected, actual } => (
+                1,
+                "FAIL",
+                format!("expected {}, got {}", short(expected), short(actual)),
+            ),
+            Outcome::New(hash) => (2, "new", short(hash)),
+            Outcome::Missing => (3, "MISSING", String::new()),
+            Outcome::Error(err) => (4, "ERROR", err.clone()),
+        };
+        counts[kind] += 1;
+        let _ = writeln!(table, "{:width$}  {label:7}  {detail}", result.name);
+    }
+    let [passed, failed, new, missing, errors] = counts;
+    let _ = writeln!(
+        table,
+        "{passed} passed, {failed} failed, {new} new, {missing} missing, {errors} errors"
+    );
+    table
+}
+
+#[cfg(test)]
+mod suite_tests {
+    use std::fs;
+
+    use chip8_emu::emu::quirks::Variant;
+
+    use super::{run, summary, updated, Entry, Manifest, Outcome, MANIFEST_NAME};
+    use crate::utl::library::Library;
+
+    // LD I, 0x000 (the font's 0); DRW V0, V0, 5; JMP 0x204
+    const ZERO_ROM: [u8; 6] = [0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04];
+    // CLS; JMP 0x202
+    const BLANK_ROM: [u8; 4] = [0x00, 0xE0, 0x12, 0x02];
+
+    #[test]
+    fn manifests_parse_back() {
+        let manifest = Manifest::parse(
+            "# ROMs\n[\"pong.ch8\"]\nhash = \"ABC\"\ncycles = 500\nvariant = \"schip\"\n\n[\"new.ch8\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.entries["pong.ch8"],
+            Entry {
+                hash: Some("abc".to_string()),
+                cycles: Some(500),
+                variant: Some(Variant::SChip),
+            }
+        );
+        assert_eq!(manifest.entries["new.ch8"], Entry::default());
+        assert_eq!(Manifest::parse(&manifest.to_toml()).unwrap(), manifest);
+
+        let err = Manifest::parse("hash = \"abc\"").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Line 1: `hash` isn't under the table of a ROM!"
+        );
+        let err = Manifest::parse("[\"a.ch8\"]\nframes = 3").unwrap_err();
+        assert_eq!(err.to_string(), "Line 2: Unknown key `frames`!");
+    }
+
+    #[test]
+    fn runs_the_roms_against_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("zero.ch8"), ZERO_ROM).unwrap();
+        fs::write(dir.path().join("blank.ch8"), BLANK_ROM).unwrap();
+        let roms = Library::scan(dir.path(), false).unwrap();
+
+        // Nothing is expected yet
+        let results = run(roms.roms(), &Manifest::default(), 100, 2);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result.outcome, Outcome::New(_))));
+        let mut manifest = updated(&results);
+        assert_eq!(manifest.entries["zero.ch8"].cycles, Some(100));
+        let blank_hash = manifest.entries["blank.ch8"].hash.clone().unwrap();
+        assert_eq!(
+            blank_hash,
+            sha1_smol::Sha1::from([0; 64 * 32]).digest().to_string()
+        );
+
+        // The same screens come back however many run at once
+        let results = run(roms.roms(), &manifest, 100, 1);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result.outcome, Outcome::Pass(_))));
+
+        // A deliberately wrong entry, and one for a ROM that isn't there
+        manifest.entries.get_mut("zero.ch8").unwrap().hash = Some(blank_hash.clone());
+        manifest
+            .entries
+            .insert("gone.ch8".to_string(), Entry::default());
+        fs::write(dir.path().join(MANIFEST_NAME), manifest.to_toml()).unwrap();
+        let manifest = Manifest::load(&dir.path().join(MANIFEST_NAME)).unwrap();
+        let results = run(roms.roms(), &manifest, 100, 4);
+        let outcomes: Vec<(&str, bool)> = results
+            .iter()
+            .map(|result| (result.name.as_str(), result.outcome.is_failure()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [("blank.ch8", false), ("zero.ch8", true), ("gone.ch8", true)]
+        );
+        assert!(matches!(
+            &results[1].outcome,
+            Outcome::Fail { expected, actual } if *expected == blank_hash && *actual != blank_hash
+        ));
+        let table = summary(&results);
+        assert!(table.contains(&format!(
+            "zero.ch8   FAIL     expected {}",
+            &blank_hash[..12]
+        )));
+        assert!(table.ends_with("1 passed, 1 failed, 0 new, 1 missing, 0 errors\n"));
+
+        // Updating takes the new screen and forgets the missing ROM
+        let manifest = updated(&results);
+        assert_eq!(manifest.entries.len(), 2);
+        let results = run(roms.roms(), &manifest, 100, 2);
+        assert!(results.iter().all(|result| !result.outcome.is_failure()));
+    }
+}
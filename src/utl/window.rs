@@ -0,0 +1,205 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use chip8_emu::frontend::sdl::window::WindowGeometry;
+
+use super::config::RunArgs;
+use super::recent::config_dir;
+
+///
+/// The `WINDOW_FILE_NAME` constant is the name of the file storing the window's geometry.
+///
+const WINDOW_FILE_NAME: &str = "window.txt";
+
+///
+/// The `SavedWindow` struct represents the window's geometry as it was last closed, persisted as
+/// `key=value` lines: `x` and `y` unless it was centered, `width`, `height`, `fullscreen` and
+/// `integer_scale`.
+///
+#[derive(Debug)]
+pub struct SavedWindow {
+    file: PathBuf,
+    geometry: Option<WindowGeometry>,
+}
+
+impl SavedWindow {
+    ///
+    /// Loads the geometry stored in the user config directory, or none if there is none.
+    ///
+    pub fn load_default() -> Option<Self> {
+        config_dir().map(|dir| Self::load(dir.join(WINDOW_FILE_NAME)))
+    }
+
+    ///
+    /// Loads the geometry stored in `file`, none if it's missing or its size is.
+    ///
+    pub fn load(file: PathBuf) -> Self {
+        let geometry = fs::read_to_string(&file)
+            .ok()
+            .and_then(|content| parse(&content));
+        Self { file, geometry }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(geometry) = &self.geometry else {
+            return Ok(());
+        };
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut content = String::new();
+        if let Some((x, y)) = geometry.position {
+            let _ = writeln!(content, "x={x}\ny={y}");
+        }
+        let _ = writeln!(
+            content,
+            "width={}\nheight={}\nfullscreen={}\ninteger_scale={}",
+            geometry.size.0, geometry.size.1, geometry.fullscreen, geometry.integer_scale
+        );
+        fs::write(&self.file, content)
+    }
+
+    pub const fn set(&mut self, geometry: WindowGeometry) {
+        self.geometry = Some(geometry);
+    }
+
+    ///
+    /// Returns the geometry the window opens with: the saved one, its size replaced by `opened`'s
+    /// when `--width` or `--height` is given, and its fullscreen state and scaling by the flags
+    /// setting them. `opened` is the window as it was created, when nothing was saved.
+    ///
+    pub fn restored(&self, opened: WindowGeometry, args: &RunArgs) -> WindowGeometry {
+        let mut geometry = self.geometry.unwrap_or(opened);
+        if args.width.is_some() || args.height.is_some() {
+            geometry.size = opened.size;
+        }
+        geometry.fullscreen = args.fullscreen.unwrap_or(geometry.fullscreen);
+        geometry.integer_scale = args.integer_scale.unwrap_or(geometry.integer_scale);
+        geometry
+    }
+}
+
+///
+/// Parses the lines `save` wrote, ignoring the ones it doesn't know.
+///
+fn parse(content: &str) -> Option<WindowGeometry> {
+    let (mut x, mut y, mut width, mut height) = (None, None, None, None);
+    let (mut fullscreen, mut integer_scale) = (false, false);
+    for (key, value) in content.lines().filter_map(|line| line.split_once('=')) {
+        let value = value.trim();
+        match key.trim() {
+            "x" => x = value.parse().ok(),
+            "y" => y = value.parse().ok(),
+            "width" => width = value.parse().ok().filter(|&width| width > 0),
+            "height" => height = value.parse().ok().filter(|&height| height > 0),
+            "fullscreen" => fullscreen = value == "true",
+            "integer_scale" => integer_scale = value == "true",
+            _ => {}
+        }
+    }
+    Some(WindowGeometry {
+        position: x.zip(y),
+        size: (width?, height?),
+        fullscreen,
+        integer_scale,
+    })
+}
+
+#[cfg(test)]
+mod window_tests {
+    use std::fs;
+
+    use chip8_emu::frontend::sdl::window::WindowGeometry;
+
+    use super::SavedWindow;
+    use crate::utl::config::config_tests::parse;
+
+    fn saved(geometry: WindowGeometry) -> SavedWindow {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config").join("window.txt");
+        let mut window = SavedWindow::load(file.clone());
+        window.set(geometry);
+        window.save().unwrap();
+        SavedWindow::load(file)
+    }
+
+    #[test]
+    fn missing_file_is_nothing_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let window = SavedWindow::load(dir.path().join("window.txt"));
+        assert_eq!(window.geometry.as_ref(), None);
+        // Nothing to save either
+        window.save().unwrap();
+        assert!(!dir.path().join("window.txt").exists());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let geometry = WindowGeometry {
+            position: Some((2200, -20)),
+            size: (1024, 768),
+            fullscreen: true,
+            integer_scale: true,
+        };
+        assert_eq!(saved(geometry).geometry.as_ref(), Some(&geometry));
+
+        let centered = WindowGeometry::centered((800, 600));
+        assert_eq!(saved(centered).geometry.as_ref(), Some(&centered));
+    }
+
+    #[test]
+    fn unreadable_geometry_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("window.txt");
+        fs::write(&file, "x=10\ny=20\nwidth=wide\nheight=600\n").unwrap();
+        assert_eq!(SavedWindow::load(file.clone()).geometry.as_ref(), None);
+
+        // A position half there centers the window, and unknown keys are skipped
+        fs::write(&file, "x=10\nwidth=640\nheight=480\ntheme=dark\n").unwrap();
+        assert_eq!(
+            SavedWindow::load(file).geometry.as_ref(),
+            Some(&WindowGeometry::centered((640, 480)))
+        );
+    }
+
+    #[test]
+    fn flags_override_the_saved_geometry() {
+        let opened = WindowGeometry::centered((800, 600));
+        let dir = tempfile::tempdir().unwrap();
+        let nothing_saved = SavedWindow::load(dir.path().join("window.txt"));
+        let args = parse(["chip8-emu", "PONG"]).unwrap().run;
+        assert_eq!(nothing_saved.restored(opened, &args), opened);
+
+        let geometry = WindowGeometry {
+            position: Some((100, 80)),
+            size: (1280, 960),
+            fullscreen: true,
+            integer_scale: false,
+        };
+        let window = saved(geometry);
+        assert_eq!(window.restored(opened, &args), geometry);
+        let args = parse([
+            "chip8-emu",
+            "PONG",
+            "--width",
+            "800",
+            "--fullscreen",
+            "off",
+            "--integer-scale",
+        ])
+        .unwrap()
+        .run;
+        assert_eq!(
+            window.restored(opened, &args),
+            WindowGeometry {
+                position: Some((100, 80)),
+                size: (800, 600),
+                fullscreen: false,
+                integer_scale: true,
+            }
+        );
+    }
+}
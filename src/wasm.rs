@@ -0,0 +1,134 @@
+use std::cell::RefCell;
+
+use crate::emu::chip8::Chip8;
+use crate::emu::io::BufferScreen;
+
+///
+/// The `TICK_FAILED` constant is what `tick` returns once the emulator can't go any further.
+///
+const TICK_FAILED: i32 = -1;
+
+thread_local! {
+    static ROM: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    static CHIP8: RefCell<Option<Chip8>> = const { RefCell::new(None) };
+}
+
+///
+/// Returns a buffer of `len` bytes for the JavaScript side to copy the ROM into before `init`.
+///
+#[no_mangle]
+pub extern "C" fn rom_buffer(len: usize) -> *mut u8 {
+    ROM.with_borrow_mut(|rom| {
+        rom.clear();
+        rom.resize(len, 0);
+        rom.as_mut_ptr()
+    })
+}
+
+///
+/// Starts a new emulator running the ROM copied into `rom_buffer`, with `RND` seeded by `seed`.
+/// Returns `false`, keeping the running emulator, when the ROM is invalid.
+///
+#[no_mangle]
+pub extern "C" fn init(seed: u32) -> bool {
+    let Ok(mut chip8) = Chip8::builder()
+        .screen(Box::new(BufferScreen::new()))
+        .seed(u64::from(seed))
+        .build()
+    else {
+        return false;
+    };
+    if ROM.with_borrow(|rom| chip8.load_rom_bytes(rom)).is_err() {
+        return false;
+    }
+
+    CHIP8.set(Some(chip8));
+    true
+}
+
+///
+/// Runs the emulator for `frames` 60 Hz frames. Returns `1` when the screen changed, `0` when it
+/// didn't, and `TICK_FAILED` when there's no emulator or it hit an invalid instruction.
+///
+#[no_mangle]
+pub extern "C" fn tick(frames: u32) -> i32 {
+    CHIP8.with_borrow_mut(|chip8| {
+        let Some(chip8) = chip8 else {
+            return TICK_FAILED;
+        };
+
+        chip8
+            .run_frames(frames)
+            .map_or(TICK_FAILED, |summary| i32::from(summary.display_changed))
+    })
+}
+
+///
+/// Returns where the 64×32 framebuffer lives in the module's memory, one byte (0 or 1) per pixel,
+/// or null before `init`. The pointer stays valid until the next successful `init`.
+///
+#[no_mangle]
+pub extern "C" fn framebuffer_ptr() -> *const u8 {
+    CHIP8.with_borrow(|chip8| {
+        chip8
+            .as_ref()
+            .map_or(std::ptr::null(), |chip8| chip8.framebuffer().as_ptr())
+    })
+}
+
+///
+/// Presses or releases the hex key `key`.
+///
+#[no_mangle]
+pub extern "C" fn key_event(key: u8, pressed: bool) {
+    CHIP8.with_borrow_mut(|chip8| {
+        if let Some(chip8) = chip8 {
+            chip8.set_key(key, pressed);
+        }
+    });
+}
+
+#[cfg(test)]
+mod wasm_tests {
+    use super::{framebuffer_ptr, init, key_event, rom_buffer, tick, TICK_FAILED};
+    use crate::emu::io::{GRID_HEIGHT, GRID_WIDTH};
+
+    fn load(rom: &[u8]) -> bool {
+        let buffer = rom_buffer(rom.len());
+        // SAFETY: `rom_buffer` returned room for exactly `rom.len()` bytes
+        unsafe { std::ptr::copy_nonoverlapping(rom.as_ptr(), buffer, rom.len()) };
+        init(0)
+    }
+
+    #[test]
+    fn draws_through_the_exports() {
+        assert!(framebuffer_ptr().is_null());
+        assert_eq!(tick(1), TICK_FAILED);
+
+        // LD I, 0x000 (font sprite of 0); DRW V0, V0, 5; JMP 0x204
+        assert!(load(&[0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04]));
+        assert!(!load(&[]));
+        assert_eq!(tick(1), 1);
+        assert_eq!(tick(1), 0);
+
+        // SAFETY: the framebuffer is GRID_WIDTH × GRID_HEIGHT bytes and lives until the next init
+        let framebuffer =
+            unsafe { std::slice::from_raw_parts(framebuffer_ptr(), GRID_WIDTH * GRID_HEIGHT) };
+        assert_eq!(&framebuffer[..4], [1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn keys_reach_the_keypad() {
+        // LD V0, K; JMP 0x202
+        assert!(load(&[0xF0, 0x0A, 0x12, 0x02]));
+        assert_eq!(tick(1), 0);
+        key_event(0x7, true);
+        key_event(0x7, false);
+        key_event(0x5, true);
+        assert_eq!(tick(1), 0);
+        assert_eq!(
+            super::CHIP8.with_borrow(|chip8| chip8.as_ref().map(|chip8| chip8.v(0))),
+            Some(0x5)
+        );
+    }
+}
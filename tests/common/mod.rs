@@ -0,0 +1,111 @@
+// Each test crate only uses some of the helpers
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8_emu::emu::io::GRID_WIDTH;
+use chip8_emu::emu::screenshot::ascii_art;
+use chip8_emu::Chip8;
+
+///
+/// Asserts that the framebuffer of the emulator looks as the ASCII art in
+/// `tests/snapshots/<name>.txt`, printing both when it doesn't. With `CHIP8_BLESS=1` the snapshot
+/// is rewritten instead.
+///
+#[macro_export]
+macro_rules! assert_framebuffer_snapshot {
+    ($chip8:expr, $name:expr) => {
+        if let Err(failure) =
+            $crate::common::Snapshots::from_env().check_framebuffer(&$chip8, $name)
+        {
+            panic!("{failure}");
+        }
+    };
+}
+
+///
+/// Draws the screen of the emulator as ASCII art at the width it's shown at, the 256 pixels of
+/// MegaChip's as well as the 64 of the others.
+///
+pub fn screen_art(chip8: &Chip8) -> String {
+    let width = chip8
+        .framebuffer_rows()
+        .next()
+        .map_or(GRID_WIDTH, <[u8]>::len);
+    ascii_art(chip8.framebuffer(), width)
+}
+
+///
+/// The `Snapshots` struct represents a directory of expected screens, drawn as ASCII art.
+///
+pub struct Snapshots {
+    dir: PathBuf,
+    bless: bool,
+}
+
+impl Snapshots {
+    pub fn new(dir: impl Into<PathBuf>, bless: bool) -> Self {
+        Self {
+            dir: dir.into(),
+            bless,
+        }
+    }
+
+    ///
+    /// Opens `tests/snapshots`, rewriting the snapshots if `CHIP8_BLESS` is set to 1.
+    ///
+    pub fn from_env() -> Self {
+        Self::in_tests(Path::new("snapshots"))
+    }
+
+    ///
+    /// Opens the directory at `path` under `tests`, rewriting the snapshots if `CHIP8_BLESS` is
+    /// set to 1.
+    ///
+    pub fn in_tests(path: &Path) -> Self {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join(path);
+        let bless = env::var_os("CHIP8_BLESS").is_some_and(|value| value == "1");
+        Self::new(dir, bless)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.txt"))
+    }
+
+    pub fn check_framebuffer(&self, chip8: &Chip8, name: &str) -> Result<(), String> {
+        self.check(name, &screen_art(chip8))
+    }
+
+    ///
+    /// Compares `actual` with the snapshot called `name`, or writes it there when blessing.
+    ///
+    pub fn check(&self, name: &str, actual: &str) -> Result<(), String> {
+        let path = self.path(name);
+        let expected = fs::read_to_string(&path).ok();
+        if expected.as_deref() == Some(actual) {
+            return Ok(());
+        }
+        if self.bless {
+            return fs::write(&path, actual)
+                .map_err(|err| format!("Failed to write {}: {err}", path.display()));
+        }
+
+        Err(match expected {
+            Some(expected) => format!(
+                "{name} doesn't match its snapshot, rerun with CHIP8_BLESS=1 to update it.\n\
+                 Expected:\n{expected}Actual:\n{actual}"
+            ),
+            None => format!(
+                "{name} has no snapshot, rerun with CHIP8_BLESS=1 to write it.\nActual:\n{actual}"
+            ),
+        })
+    }
+}
@@ -0,0 +1,31 @@
+; Bounces a dot between the edges of the screen, in both syntaxes
+
+        CLS
+        LD VA, 0                ; x
+        LD VB, 0x10             ; y
+        LD VC, 1                ; dx
+        LD I, dot
+
+loop:   DRW VA, VB, 1
+        LD V0, 2
+        LD DT, V0
+wait:   LD V0, DT
+        SE V0, 0
+        JP wait
+        DRW VA, VB, 1           ; erase the dot
+        ADD VA, VC
+        SNE VA, 63
+        CALL turn
+        SNE VA, 0
+        CALL turn
+        JP loop
+
+# Octo from here on
+: turn
+        v0 := 0b11111110
+        vc ^= v0                # 1 <-> -1, as 0xFF
+        sub_done:
+        return
+
+dot:    db 0b10000000
+        dw 0x1234, turn
@@ -0,0 +1,174 @@
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+
+use chip8_emu::emu::memory::{DEFAULT_PROGRAM_START_OFFSET, RAM_SIZE};
+use chip8_emu::{Chip8, Instruction, Variant};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+///
+/// How many inputs each target tries, unless `CHIP8_FUZZ_ITERATIONS` asks for a longer run.
+///
+const SMOKE_ITERATIONS: u64 = 200;
+
+///
+/// How many instructions a fuzzed ROM runs for at most.
+///
+const MAX_STEPS: u64 = 2_000;
+
+///
+/// Returns the seed of the first input and how many inputs to try. `CHIP8_FUZZ_SEED` replays a
+/// failing run.
+///
+fn fuzz_run() -> (u64, u64) {
+    let var = |name| env::var(name).ok().and_then(|value| value.parse().ok());
+    (
+        var("CHIP8_FUZZ_SEED").unwrap_or_default(),
+        var("CHIP8_FUZZ_ITERATIONS").unwrap_or(SMOKE_ITERATIONS),
+    )
+}
+
+///
+/// Returns an opcode that decodes, its fixed nibbles from the pattern of a random instruction and
+/// its operands random.
+///
+fn random_opcode(rng: &mut StdRng) -> u16 {
+    let pattern = Instruction::ALL[rng.gen_range(0..Instruction::ALL.len())].pattern();
+    pattern.chars().fold(0, |opcode, nibble| {
+        let value = nibble
+            .to_digit(16)
+            .filter(|_| nibble.is_ascii_digit() || nibble.is_ascii_uppercase())
+            .map_or_else(|| rng.gen_range(0..16), |digit| digit as u16);
+        opcode << 4 | value
+    })
+}
+
+///
+/// Returns a ROM of random bytes, or of random decodable instructions to get further into it.
+///
+fn random_rom(rng: &mut StdRng) -> Vec<u8> {
+    let len = rng.gen_range(1..=RAM_SIZE - DEFAULT_PROGRAM_START_OFFSET);
+    if rng.gen() {
+        (0..len).map(|_| rng.gen()).collect()
+    } else {
+        (0..len.div_ceil(2))
+            .flat_map(|_| random_opcode(rng).to_be_bytes())
+            .take(len)
+            .collect()
+    }
+}
+
+fn run_rom(rom: &[u8], variant: Variant, seed: u64, rng: &mut StdRng) {
+    let mut chip8 = Chip8::builder()
+        .headless(true)
+        .variant(variant)
+        .seed(seed)
+        .build()
+        .unwrap();
+    chip8.load_rom_bytes(rom).unwrap();
+    for _ in 0..MAX_STEPS {
+        if rng.gen_ratio(1, 64) {
+            chip8.set_keys(rng.gen());
+        }
+        // Any error of the emulator is fine, it just has to be one
+        if chip8.step().is_err() {
+            break;
+        }
+    }
+}
+
+#[test]
+fn random_roms_never_panic() {
+    let (first, iterations) = fuzz_run();
+    for seed in first..first + iterations {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let rom = random_rom(&mut rng);
+        let variant = if rng.gen() {
+            Variant::Chip8
+        } else {
+            Variant::SChip
+        };
+        let ran = panic::catch_unwind(AssertUnwindSafe(|| run_rom(&rom, variant, seed, &mut rng)));
+        assert!(
+            ran.is_ok(),
+            "The ROM of CHIP8_FUZZ_SEED={seed} panicked the emulator"
+        );
+    }
+}
+
+#[cfg(feature = "debug")]
+mod single_opcodes {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use chip8_emu::emu::io::BufferScreen;
+    use chip8_emu::emu::memory::{DEFAULT_PROGRAM_START_OFFSET, RAM_SIZE, STACK_SIZE};
+    use chip8_emu::{Cpu, Quirks, RandomSource};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{fuzz_run, random_opcode};
+
+    struct Random(StdRng);
+
+    impl RandomSource for Random {
+        fn random_byte(&mut self) -> u8 {
+            self.0.gen()
+        }
+    }
+
+    ///
+    /// Returns a machine with random memory above the load address, random registers, timers,
+    /// stack and quirks, and a PC anywhere in memory.
+    ///
+    fn random_cpu(rng: &mut StdRng) -> Cpu {
+        let quirks = Quirks {
+            shift: rng.gen(),
+            memory: rng.gen(),
+            jump: rng.gen(),
+        };
+        let mut cpu = Cpu::new(quirks, DEFAULT_PROGRAM_START_OFFSET as u16);
+        let mut memory = vec![0; RAM_SIZE - DEFAULT_PROGRAM_START_OFFSET];
+        rng.fill(&mut memory[..]);
+        cpu.load_rom(&memory).unwrap();
+        if rng.gen() {
+            cpu.set_key(rng.gen_range(0..16), true);
+        }
+
+        let registers = cpu.registers_mut();
+        registers.pc = rng.gen_range(0..RAM_SIZE as u16);
+        registers.i = rng.gen();
+        registers.dt = rng.gen();
+        registers.st = rng.gen();
+        rng.fill(&mut registers.v);
+        for _ in 0..rng.gen_range(0..=STACK_SIZE) {
+            registers.push(rng.gen()).unwrap();
+        }
+        cpu
+    }
+
+    #[test]
+    fn single_opcodes_never_panic() {
+        let (first, iterations) = fuzz_run();
+        // Each input is cheap, so try many more of them
+        for seed in first..first + iterations * 20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut cpu = random_cpu(&mut rng);
+            let opcode = if rng.gen() {
+                random_opcode(&mut rng)
+            } else {
+                rng.gen()
+            };
+            let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+                let Some(instruction) = Cpu::decode(opcode) else {
+                    return;
+                };
+                let mut screen = BufferScreen::new();
+                let _ = cpu.execute(instruction, opcode, &mut screen, &mut Random(rng.clone()));
+            }));
+            assert!(
+                ran.is_ok(),
+                "Opcode {opcode:04X} of CHIP8_FUZZ_SEED={seed} panicked the emulator"
+            );
+        }
+    }
+}
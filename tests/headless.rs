@@ -0,0 +1,382 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chip8_emu::emu::io::{BufferScreen, Screen};
+use chip8_emu::emu::remote::ControlServer;
+use chip8_emu::emu::rom::DEMO_ROM;
+use chip8_emu::emu::stats::RunOutcome;
+use chip8_emu::emu::trace::{TraceFormat, TraceRegisters, TraceWriter};
+use chip8_emu::emu::verify::{self, Verdict};
+use chip8_emu::{Chip8, Clock, Instruction};
+
+#[macro_use]
+mod common;
+
+///
+/// The SHA-1 hash of the demo's framebuffer after two seconds of emulation, seeded with 0.
+///
+const DEMO_GOLDEN_FRAMEBUFFER: &str = "d4598c296d5884a621d3fb2bc9461a308710fcfa";
+
+fn demo_framebuffer_hash(mut chip8: Chip8) -> String {
+    chip8.load_demo().unwrap();
+    chip8.run_frames(120).unwrap();
+    sha1_smol::Sha1::from(chip8.framebuffer())
+        .digest()
+        .to_string()
+}
+
+#[test]
+fn demo_runs_to_the_budget() {
+    let mut chip8 = Chip8::headless();
+    chip8.load_demo().unwrap();
+    chip8.set_budget(Some(200), None);
+
+    assert_eq!(chip8.run().unwrap(), RunOutcome::BudgetExhausted);
+    assert_eq!(chip8.stats().cycles, 200);
+    assert_eq!(
+        chip8.rom_hash(),
+        Some(
+            sha1_smol::Sha1::from(DEMO_ROM)
+                .digest()
+                .to_string()
+                .as_str()
+        )
+    );
+}
+
+#[test]
+fn rom_from_bytes_runs() {
+    // LD V0, 0x2A; JMP 0x202
+    let mut chip8 = Chip8::headless();
+    chip8.load_rom_bytes(&[0x60, 0x2A, 0x12, 0x02]).unwrap();
+    chip8.set_budget(Some(10), None);
+
+    assert_eq!(chip8.run().unwrap(), RunOutcome::BudgetExhausted);
+    assert_eq!(chip8.stats().cycles, 10);
+}
+
+#[test]
+fn patched_roms_run_patched() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("answer.ch8");
+    // LD V0, 0x2A; JMP 0x202
+    std::fs::write(&path, [0x60, 0x2A, 0x12, 0x02]).unwrap();
+    let path = path.to_str().unwrap();
+    // LD V0, 0x2B; LD V1, 0x2C; JMP 0x204
+    let patch = b"PATCH\x00\x00\x01\x00\x01\x2B\x00\x00\x02\x00\x04\x61\x2C\x12\x04EOF";
+
+    let mut chip8 = Chip8::headless();
+    assert_eq!(chip8.load_patched_rom(path, None, patch).unwrap(), 2);
+    chip8.run_cycles(3).unwrap();
+    assert_eq!((chip8.v(0), chip8.v(1)), (0x2B, 0x2C));
+    assert_eq!(chip8.rom_path(), Some(path));
+    let patched = [0x60, 0x2B, 0x61, 0x2C, 0x12, 0x04];
+    assert_eq!(
+        chip8.rom_hash(),
+        Some(sha1_smol::Sha1::from(patched).digest().to_string().as_str())
+    );
+
+    // A bad patch leaves the machine as it was
+    let err = chip8.load_patched_rom(path, None, b"PATCH").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Failed to apply the IPS patch: The patch ends without its `EOF` marker!"
+    );
+    assert_eq!(chip8.v(1), 0x2C);
+}
+
+#[test]
+fn breakpoints_stop_the_demo_exactly_there() {
+    let mut chip8 = Chip8::headless();
+    chip8.load_demo().unwrap();
+    chip8.add_breakpoint(0x208);
+
+    let summary = chip8.run_frames(60).unwrap();
+    assert_eq!(summary.breakpoint, Some(0x208));
+    assert_eq!(summary.cycles, 4);
+    // CLS; LD I, 0x22A; LD V0, 0x0C; LD V1, 0x08 ran, DRW V0, V1, 15 didn't
+    assert_eq!(chip8.pc(), 0x208);
+    assert_eq!(chip8.i(), 0x22A);
+    assert_eq!((chip8.v(0), chip8.v(1)), (0x0C, 0x08));
+    assert!(chip8.framebuffer().iter().all(|&pixel| pixel == 0));
+}
+
+#[test]
+fn break_on_drw_stops_before_the_first_draw() {
+    let mut chip8 = Chip8::headless();
+    chip8.load_demo().unwrap();
+    chip8.add_break_on(Instruction::DRW);
+
+    let summary = chip8.run_frames(60).unwrap();
+    assert_eq!(summary.breakpoint, Some(0x208));
+    assert_eq!(summary.break_on, Some(Instruction::DRW));
+    assert_eq!(summary.cycles, 4);
+    assert_eq!(chip8.cpu().fetch().unwrap() & 0xF000, 0xD000);
+    assert_eq!((chip8.pc(), chip8.i()), (0x208, 0x22A));
+    assert_eq!((chip8.v(0), chip8.v(1)), (0x0C, 0x08));
+    assert!(chip8.framebuffer().iter().all(|&pixel| pixel == 0));
+
+    // Resuming draws, then stops on the next DRW
+    let summary = chip8.run_frame().unwrap();
+    assert_eq!(summary.breakpoint, Some(0x20E));
+    assert_eq!(summary.cycles, 3);
+    assert!(chip8.framebuffer().contains(&1));
+}
+
+#[test]
+fn screens_are_usable_on_their_own() {
+    let mut screen = BufferScreen::new();
+    screen.set_pixel(3, 2, 1);
+    assert_eq!(screen.get_pixel(3, 2), 1);
+    assert_eq!(
+        screen
+            .framebuffer()
+            .iter()
+            .filter(|&&pixel| pixel == 1)
+            .count(),
+        1
+    );
+
+    screen.clear();
+    assert!(screen.framebuffer().iter().all(|&pixel| pixel == 0));
+}
+
+#[test]
+fn external_driver_steps_the_core() {
+    // CLS; JMP 0x202
+    let mut chip8 = Chip8::headless();
+    chip8.load_rom_bytes(&[0x00, 0xE0, 0x12, 0x02]).unwrap();
+
+    let first = chip8.step().unwrap();
+    assert!(first.display_changed);
+    assert_eq!(first.cycles, 1);
+    for _ in 0..10 {
+        assert!(!chip8.step().unwrap().display_changed);
+    }
+    assert_eq!(chip8.stats().cycles, 11);
+}
+
+///
+/// The `SharedBuffer` struct represents a trace output the test can still read once the emulator
+/// owns the writer.
+///
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+///
+/// The `StoppedClock` struct represents a clock that never ticks, keeping the timers in the
+/// traces still.
+///
+struct StoppedClock;
+
+impl Clock for StoppedClock {
+    fn ticks(&mut self) -> u32 {
+        0
+    }
+}
+
+fn trace(format: TraceFormat, registers: TraceRegisters) -> String {
+    // LD I, 0x300; LD V0, 0x05; LD V1, 0xFF; ADD V0, V1; LD DT, V0; CALL 0x20C; RET
+    let rom = [
+        0xA3, 0x00, 0x60, 0x05, 0x61, 0xFF, 0x80, 0x14, 0xF0, 0x15, 0x22, 0x0C, 0x00, 0xEE,
+    ];
+    let mut chip8 = Chip8::builder()
+        .headless(true)
+        .clock(Box::new(StoppedClock))
+        .build()
+        .unwrap();
+    chip8.load_rom_bytes(&rom).unwrap();
+    let out = SharedBuffer::default();
+    chip8.set_tracer(TraceWriter::new(out.clone(), format, registers));
+    for _ in 0..7 {
+        chip8.step().unwrap();
+    }
+
+    let trace = out.0.borrow();
+    String::from_utf8(trace.clone()).unwrap()
+}
+
+#[test]
+fn stop_flag_ends_the_run_between_frames() {
+    let mut chip8 = Chip8::headless();
+    chip8.load_demo().unwrap();
+    let out = SharedBuffer::default();
+    chip8.set_tracer(TraceWriter::new(
+        out.clone(),
+        TraceFormat::Json,
+        TraceRegisters::Changed,
+    ));
+    let stop = Arc::new(AtomicBool::new(false));
+    chip8.set_stop_flag(Arc::clone(&stop));
+    let interrupt = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        stop.store(true, Ordering::Relaxed);
+    });
+
+    assert_eq!(chip8.run().unwrap(), RunOutcome::Interrupted);
+    interrupt.join().unwrap();
+    // Every instruction run up to the stop made it to the trace
+    let cycles = chip8.stats().cycles;
+    assert!(cycles > 0);
+    let trace = String::from_utf8(out.0.borrow().clone()).unwrap();
+    assert_eq!(trace.lines().count() as u64, cycles);
+}
+
+#[test]
+fn json_traces_match_the_fixtures() {
+    assert_eq!(
+        trace(TraceFormat::Json, TraceRegisters::Changed),
+        include_str!("fixtures/trace_changed.jsonl")
+    );
+    assert_eq!(
+        trace(TraceFormat::Json, TraceRegisters::Full),
+        include_str!("fixtures/trace_full.jsonl")
+    );
+}
+
+#[test]
+fn demo_verifies_against_its_own_trace() {
+    let demo = || {
+        let mut chip8 = Chip8::builder().seed(0).headless(true).build().unwrap();
+        chip8.load_demo().unwrap();
+        chip8
+    };
+    let out = SharedBuffer::default();
+    let mut chip8 = demo();
+    chip8.set_tracer(TraceWriter::new(
+        out.clone(),
+        TraceFormat::Json,
+        TraceRegisters::Changed,
+    ));
+    chip8.run_frames(30).unwrap();
+    let trace = String::from_utf8(out.0.borrow().clone()).unwrap();
+
+    let reference = verify::parse_reference(&trace).unwrap();
+    assert_eq!(
+        verify::verify(&mut demo(), &reference).unwrap(),
+        Verdict::Match(chip8.stats().cycles as usize)
+    );
+
+    // A reference skipping an instruction stops agreeing right there
+    let mut lines: Vec<&str> = trace.lines().collect();
+    lines.remove(9);
+    let reference = verify::parse_reference(&lines.join("\n")).unwrap();
+    assert!(matches!(
+        verify::verify(&mut demo(), &reference).unwrap(),
+        Verdict::Diverged(divergence) if divergence.index == 9 && divergence.field == "pc"
+    ));
+}
+
+#[test]
+fn state_is_inspectable() {
+    // LD I, 0x300; LD V0, 0x3C; LD DT, V0; LD ST, V0
+    let mut chip8 = Chip8::headless();
+    chip8
+        .load_rom_bytes(&[0xA3, 0x00, 0x60, 0x3C, 0xF0, 0x15, 0xF0, 0x18])
+        .unwrap();
+    for _ in 0..3 {
+        chip8.step().unwrap();
+    }
+
+    assert_eq!(chip8.pc(), 0x206);
+    assert_eq!(chip8.i(), 0x300);
+    assert_eq!(chip8.v(0), 0x3C);
+    assert_eq!(chip8.registers().v[0], 0x3C);
+    assert!(chip8.delay_timer() > 0 && chip8.delay_timer() <= 0x3C);
+    assert_eq!(chip8.sound_timer(), 0);
+    assert!(chip8.stack().is_empty());
+    assert!(chip8.framebuffer().iter().all(|&pixel| pixel == 0));
+}
+
+#[cfg(feature = "debug")]
+#[test]
+fn debug_mutators() {
+    let mut chip8 = Chip8::headless();
+    chip8.set_v(0x1F, 7);
+    chip8.set_pc(0x300);
+    chip8.set_i(0x123);
+    assert_eq!(chip8.v(0xF), 7);
+    assert_eq!(chip8.pc(), 0x300);
+    assert_eq!(chip8.i(), 0x123);
+}
+
+#[test]
+fn headless_frontend_matches_the_golden_framebuffer() {
+    let chip8 = Chip8::builder().seed(0).headless(true).build().unwrap();
+    assert_eq!(demo_framebuffer_hash(chip8), DEMO_GOLDEN_FRAMEBUFFER);
+}
+
+#[test]
+fn control_server_presses_keys_and_reads_registers() {
+    // LD V0, K; JMP 0x202
+    let mut chip8 = Chip8::headless();
+    chip8.load_rom_bytes(&[0xF0, 0x0A, 0x12, 0x02]).unwrap();
+    // Stops the run should the client fail
+    chip8.set_budget(None, Some(Duration::from_secs(10)));
+    let server = ControlServer::bind("127.0.0.1:0").unwrap();
+    let address = server.address();
+    chip8.set_control_server(server);
+
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        let mut answers = BufReader::new(stream.try_clone().unwrap()).lines();
+        let mut send = |command: &str| {
+            writeln!(stream, "{command}").unwrap();
+            answers.next().unwrap().unwrap()
+        };
+
+        assert_eq!(send(r#"{"cmd":"key_down","key":5}"#), r#"{"ok":true}"#);
+        // The key is read by the next frame
+        let mut v0 = send(r#"{"cmd":"read_reg","reg":"v0"}"#);
+        for _ in 0..100 {
+            if v0 != r#"{"ok":true,"value":0}"# {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+            v0 = send(r#"{"cmd":"read_reg","reg":"v0"}"#);
+        }
+        assert_eq!(v0, r#"{"ok":true,"value":5}"#);
+        assert_eq!(
+            send(r#"{"cmd":"jump"}"#),
+            r#"{"ok":false,"error":"Unknown command `jump`"}"#
+        );
+        assert_eq!(send(r#"{"cmd":"quit"}"#), r#"{"ok":true}"#);
+    });
+
+    assert_eq!(chip8.run().unwrap(), RunOutcome::Quit);
+    client.join().unwrap();
+}
+
+#[test]
+fn demo_matches_its_snapshot() {
+    let mut chip8 = Chip8::builder().seed(0).headless(true).build().unwrap();
+    chip8.load_demo().unwrap();
+    chip8.run_frames(120).unwrap();
+    assert_framebuffer_snapshot!(chip8, "demo_after_120_frames");
+}
+
+#[cfg(feature = "sdl")]
+#[test]
+fn sdl_frontend_matches_the_golden_framebuffer() {
+    // No display needed, SDL renders into nothing
+    std::env::set_var("SDL_VIDEODRIVER", "dummy");
+    let chip8 = Chip8::builder().seed(0).headless(false).build().unwrap();
+    assert_eq!(demo_framebuffer_hash(chip8), DEMO_GOLDEN_FRAMEBUFFER);
+}
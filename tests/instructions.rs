@@ -0,0 +1,495 @@
+use std::collections::BTreeSet;
+
+use chip8_emu::emu::io::{GRID_WIDTH, MEGA_GRID_HEIGHT, MEGA_GRID_WIDTH};
+use chip8_emu::emu::mega::Blend;
+use chip8_emu::{Chip8, Clock, Instruction};
+
+///
+/// Keeps the timers where the instructions put them.
+///
+struct StoppedClock;
+
+impl Clock for StoppedClock {
+    fn ticks(&mut self) -> u32 {
+        0
+    }
+}
+
+///
+/// A ROM exercising `instruction`, the keys held while it runs, whether it runs as MegaChip or
+/// CHIP-8X and what the machine looks like after `cycles` instructions.
+///
+struct Case {
+    instruction: Instruction,
+    rom: &'static [u8],
+    keys: u16,
+    /// The hex key held on CHIP-8X's second keypad
+    second_key: Option<u8>,
+    megachip: bool,
+    chip8x: bool,
+    cycles: u64,
+    check: fn(&Chip8),
+}
+
+const fn case(
+    instruction: Instruction,
+    rom: &'static [u8],
+    cycles: u64,
+    check: fn(&Chip8),
+) -> Case {
+    Case {
+        instruction,
+        rom,
+        keys: 0,
+        second_key: None,
+        megachip: false,
+        chip8x: false,
+        cycles,
+        check,
+    }
+}
+
+const fn mega_case(
+    instruction: Instruction,
+    rom: &'static [u8],
+    cycles: u64,
+    check: fn(&Chip8),
+) -> Case {
+    Case {
+        megachip: true,
+        ..case(instruction, rom, cycles, check)
+    }
+}
+
+const fn chip8x_case(
+    instruction: Instruction,
+    rom: &'static [u8],
+    cycles: u64,
+    check: fn(&Chip8),
+) -> Case {
+    Case {
+        chip8x: true,
+        ..case(instruction, rom, cycles, check)
+    }
+}
+
+fn pixel(chip8: &Chip8, x: usize, y: usize) -> u8 {
+    chip8.framebuffer()[y * GRID_WIDTH + x]
+}
+
+const CASES: &[Case] = &[
+    // LD F, V0; DRW V0, V0, 5; CLS
+    case(
+        Instruction::CLS,
+        &[0xF0, 0x29, 0xD0, 0x05, 0x00, 0xE0],
+        3,
+        |chip8| {
+            assert!(chip8.framebuffer().iter().all(|&pixel| pixel == 0));
+        },
+    ),
+    // CALL 0x204; DATA; RET
+    case(
+        Instruction::CALL,
+        &[0x22, 0x04, 0x00, 0x00, 0x00, 0xEE],
+        1,
+        |chip8| {
+            assert_eq!(chip8.pc(), 0x204);
+            assert_eq!(chip8.stack(), [0x202]);
+        },
+    ),
+    case(
+        Instruction::RET,
+        &[0x22, 0x04, 0x00, 0x00, 0x00, 0xEE],
+        2,
+        |chip8| {
+            assert_eq!(chip8.pc(), 0x202);
+            assert!(chip8.stack().is_empty());
+        },
+    ),
+    // JMP 0x246
+    case(Instruction::JMP, &[0x12, 0x46], 1, |chip8| {
+        assert_eq!(chip8.pc(), 0x246);
+    }),
+    // LD V0, 0x05; SE V0, 0x05
+    case(Instruction::SE, &[0x60, 0x05, 0x30, 0x05], 2, |chip8| {
+        assert_eq!(chip8.pc(), 0x206);
+    }),
+    // LD V0, 0x05; SNE V0, 0x05
+    case(Instruction::SNE, &[0x60, 0x05, 0x40, 0x05], 2, |chip8| {
+        assert_eq!(chip8.pc(), 0x204);
+    }),
+    // LD V0, 0x05; LD V1, 0x05; SE V0, V1
+    case(
+        Instruction::SER,
+        &[0x60, 0x05, 0x61, 0x05, 0x50, 0x10],
+        3,
+        |chip8| {
+            assert_eq!(chip8.pc(), 0x208);
+        },
+    ),
+    // LD V0, 0x05; LD V1, 0x06; SNE V0, V1
+    case(
+        Instruction::SNER,
+        &[0x60, 0x05, 0x61, 0x06, 0x90, 0x10],
+        3,
+        |chip8| {
+            assert_eq!(chip8.pc(), 0x208);
+        },
+    ),
+    // LD VA, 0x42
+    case(Instruction::LD, &[0x6A, 0x42], 1, |chip8| {
+        assert_eq!(chip8.v(0xA), 0x42);
+    }),
+    // LD VF, 0x07; LD V0, 0xFF; ADD V0, 0x02 wrapping without a carry
+    case(
+        Instruction::ADD,
+        &[0x6F, 0x07, 0x60, 0xFF, 0x70, 0x02],
+        3,
+        |chip8| {
+            assert_eq!(chip8.v(0x0), 0x01);
+            assert_eq!(chip8.v(0xF), 0x07);
+        },
+    ),
+    // LD V1, 0x33; LD V0, V1
+    case(Instruction::LDR, &[0x61, 0x33, 0x80, 0x10], 2, |chip8| {
+        assert_eq!(chip8.v(0x0), 0x33);
+    }),
+    // LD V0, 0xF0; LD V1, 0x0F; OR V0, V1
+    case(
+        Instruction::OR,
+        &[0x60, 0xF0, 0x61, 0x0F, 0x80, 0x11],
+        3,
+        |chip8| {
+            assert_eq!(chip8.v(0x0), 0xFF);
+        },
+    ),
+    // LD V0, 0xF0; LD V1, 0x3C; AND V0, V1
+    case(
+        Instruction::AND,
+        &[0x60, 0xF0, 0x61, 0x3C, 0x80, 0x12],
+        3,
+        |chip8| {
+            assert_eq!(chip8.v(0x0), 0x30);
+        },
+    ),
+    // LD V0, 0xF0; LD V1, 0xFF; XOR V0, V1
+    case(
+        Instruction::XOR,
+        &[0x60, 0xF0, 0x61, 0xFF, 0x80, 0x13],
+        3,
+        |chip8| {
+            assert_eq!(chip8.v(0x0), 0x0F);
+        },
+    ),
+    // LD V0, 0xF0; LD V1, 0x20; ADD V0, V1 with a carry
+    case(
+        Instruction::ADDR,
+        &[0x60, 0xF0, 0x61, 0x20, 0x80, 0x14],
+        3,
+        |chip8| {
+            assert_eq!((chip8.v(0x0), chip8.v(0xF)), (0x10, 1));
+        },
+    ),
+    // LD V0, 0x10; LD V1, 0x20; SUB V0, V1 with a borrow
+    case(
+        Instruction::SUB,
+        &[0x60, 0x10, 0x61, 0x20, 0x80, 0x15],
+        3,
+        |chip8| {
+            assert_eq!((chip8.v(0x0), chip8.v(0xF)), (0xF0, 0));
+        },
+    ),
+    // LD V0, 0x05; SHR V0
+    case(Instruction::SHR, &[0x60, 0x05, 0x80, 0x06], 2, |chip8| {
+        assert_eq!((chip8.v(0x0), chip8.v(0xF)), (0x02, 1));
+    }),
+    // LD V0, 0x10; LD V1, 0x20; SUBN V0, V1 without a borrow
+    case(
+        Instruction::SUBN,
+        &[0x60, 0x10, 0x61, 0x20, 0x80, 0x17],
+        3,
+        |chip8| {
+            assert_eq!((chip8.v(0x0), chip8.v(0xF)), (0x10, 1));
+        },
+    ),
+    // LD V0, 0x81; SHL V0
+    case(Instruction::SHL, &[0x60, 0x81, 0x80, 0x0E], 2, |chip8| {
+        assert_eq!((chip8.v(0x0), chip8.v(0xF)), (0x02, 1));
+    }),
+    // LD I, 0x345
+    case(Instruction::LDRI, &[0xA3, 0x45], 1, |chip8| {
+        assert_eq!(chip8.i(), 0x345);
+    }),
+    // LD V0, 0x04; JMP V0, 0x300
+    case(Instruction::JMPV0, &[0x60, 0x04, 0xB3, 0x00], 2, |chip8| {
+        assert_eq!(chip8.pc(), 0x304);
+    }),
+    // RND V0, 0x0F
+    case(Instruction::RND, &[0xC0, 0x0F], 1, |chip8| {
+        assert_eq!(chip8.v(0x0) & 0xF0, 0);
+        assert_eq!(chip8.pc(), 0x202);
+    }),
+    // LD F, V0; DRW V0, V0, 5, the 0 of the font in the top left corner
+    case(Instruction::DRW, &[0xF0, 0x29, 0xD0, 0x05], 2, |chip8| {
+        let row = |y| (0..5).map(|x| pixel(chip8, x, y)).collect::<Vec<_>>();
+        assert_eq!(row(0), [1, 1, 1, 1, 0]);
+        assert_eq!(row(1), [1, 0, 0, 1, 0]);
+        assert_eq!(row(4), [1, 1, 1, 1, 0]);
+        assert_eq!(chip8.v(0xF), 0);
+    }),
+    // LD V0, 0x05; SKP V0 with 5 held
+    Case {
+        keys: 0x0020,
+        ..case(Instruction::SKP, &[0x60, 0x05, 0xE0, 0x9E], 2, |chip8| {
+            assert_eq!(chip8.pc(), 0x206);
+        })
+    },
+    // LD V0, 0x05; SKNP V0 with nothing held, hex key 0 standing for no key at all
+    case(Instruction::SKNP, &[0x60, 0x05, 0xE0, 0xA1], 2, |chip8| {
+        assert_eq!(chip8.pc(), 0x206);
+    }),
+    // LD V0, 0x09; LD DT, V0; LD V1, DT
+    case(
+        Instruction::LDRDT,
+        &[0x60, 0x09, 0xF0, 0x15, 0xF1, 0x07],
+        3,
+        |chip8| {
+            assert_eq!(chip8.v(0x1), 0x09);
+        },
+    ),
+    // LD V1, K with 7 held
+    Case {
+        keys: 0x0080,
+        ..case(Instruction::LDK, &[0xF1, 0x0A], 1, |chip8| {
+            assert_eq!(chip8.v(0x1), 0x07);
+            assert_eq!(chip8.pc(), 0x202);
+        })
+    },
+    // LD V0, 0x09; LD DT, V0
+    case(Instruction::LDDTR, &[0x60, 0x09, 0xF0, 0x15], 2, |chip8| {
+        assert_eq!(chip8.delay_timer(), 0x09);
+    }),
+    // LD V0, 0x09; LD ST, V0
+    case(Instruction::LDRST, &[0x60, 0x09, 0xF0, 0x18], 2, |chip8| {
+        assert_eq!(chip8.sound_timer(), 0x09);
+    }),
+    // LD I, 0x100; LD V0, 0x05; ADD I, V0
+    case(
+        Instruction::ADDRI,
+        &[0xA1, 0x00, 0x60, 0x05, 0xF0, 0x1E],
+        3,
+        |chip8| {
+            assert_eq!(chip8.i(), 0x105);
+        },
+    ),
+    // LD V0, 0x0A; LD F, V0
+    case(Instruction::LDSR, &[0x60, 0x0A, 0xF0, 0x29], 2, |chip8| {
+        assert_eq!(chip8.i(), 0x0A * 5);
+    }),
+    // LD I, 0x300; LD V0, 234; LD B, V0
+    case(
+        Instruction::LDB,
+        &[0xA3, 0x00, 0x60, 0xEA, 0xF0, 0x33],
+        3,
+        |chip8| {
+            let ram = chip8.cpu().ram();
+            let digits: Vec<_> = (0x300..0x303)
+                .map(|at| ram.read_byte(at).unwrap())
+                .collect();
+            assert_eq!(digits, [2, 3, 4]);
+        },
+    ),
+    // LD I, 0x300; LD V0, 0x11; LD V1, 0x22; LD [I], V1
+    case(
+        Instruction::LDRIR,
+        &[0xA3, 0x00, 0x60, 0x11, 0x61, 0x22, 0xF1, 0x55],
+        4,
+        |chip8| {
+            let ram = chip8.cpu().ram();
+            assert_eq!(ram.read_byte(0x300), Ok(0x11));
+            assert_eq!(ram.read_byte(0x301), Ok(0x22));
+            assert_eq!(chip8.i(), 0x302);
+        },
+    ),
+    // LD I, 0x206; LD V1, [I]; JMP 0x204; DATA 0xABCD
+    case(
+        Instruction::LDRRI,
+        &[0xA2, 0x06, 0xF1, 0x65, 0x12, 0x04, 0xAB, 0xCD],
+        2,
+        |chip8| {
+            assert_eq!((chip8.v(0x0), chip8.v(0x1)), (0xAB, 0xCD));
+            assert_eq!(chip8.i(), 0x208);
+        },
+    ),
+    // MEGAON
+    mega_case(Instruction::MEGAON, &[0x00, 0x11], 1, |chip8| {
+        assert!(chip8.palette().is_some());
+        assert_eq!(
+            chip8.framebuffer().len(),
+            MEGA_GRID_WIDTH * MEGA_GRID_HEIGHT
+        );
+    }),
+    // MEGAON; MEGAOFF
+    mega_case(
+        Instruction::MEGAOFF,
+        &[0x00, 0x11, 0x00, 0x10],
+        2,
+        |chip8| {
+            assert!(chip8.palette().is_none());
+            assert_eq!(chip8.framebuffer().len(), GRID_WIDTH * 32);
+        },
+    ),
+    // LDHI I, 0x000345
+    mega_case(Instruction::LDHI, &[0x01, 0x00, 0x03, 0x45], 1, |chip8| {
+        assert_eq!(chip8.i(), 0x345);
+        assert_eq!(chip8.pc(), 0x204);
+    }),
+    // LD I, 0x206; LDPAL 2; JMP 0x204; DATA two ARGB colors
+    mega_case(
+        Instruction::LDPAL,
+        &[
+            0xA2, 0x06, 0x02, 0x02, 0x12, 0x04, 0xFF, 0x10, 0x20, 0x30, 0x80, 0xFF, 0x00, 0x00,
+        ],
+        2,
+        |chip8| {
+            let palette = &chip8.cpu().mega().palette;
+            assert_eq!(palette.color(1), [0x10, 0x20, 0x30, 0xFF]);
+            assert_eq!(palette.color(2), [0xFF, 0x00, 0x00, 0x80]);
+        },
+    ),
+    // SPRW 0, standing for 256
+    mega_case(Instruction::SPRW, &[0x03, 0x00], 1, |chip8| {
+        assert_eq!(chip8.cpu().mega().sprite_width, 256);
+    }),
+    // SPRH 16
+    mega_case(Instruction::SPRH, &[0x04, 0x10], 1, |chip8| {
+        assert_eq!(chip8.cpu().mega().sprite_height, 16);
+    }),
+    // ALPHA 0x80
+    mega_case(Instruction::ALPHA, &[0x05, 0x80], 1, |chip8| {
+        assert_eq!(chip8.cpu().mega().palette.alpha(), 0x80);
+    }),
+    // DIGISND 0, without digitised sound to play
+    mega_case(Instruction::DIGISND, &[0x06, 0x00], 1, |chip8| {
+        assert_eq!(chip8.pc(), 0x202);
+    }),
+    // STOPSND
+    mega_case(Instruction::STOPSND, &[0x07, 0x00], 1, |chip8| {
+        assert_eq!(chip8.pc(), 0x202);
+    }),
+    // BMODE 4
+    mega_case(Instruction::BMODE, &[0x08, 0x04], 1, |chip8| {
+        assert_eq!(chip8.cpu().mega().blend, Blend::Add);
+    }),
+    // CCOL 7
+    mega_case(Instruction::CCOL, &[0x09, 0x07], 1, |chip8| {
+        assert_eq!(chip8.cpu().mega().collision, 7);
+    }),
+    // MEGAON; SPRW 2; SPRH 1; LD I, 0x20C; LD V0, 3; DRW V0, V0, 0; DATA a sprite of 2 pixels
+    mega_case(
+        Instruction::DRW,
+        &[
+            0x00, 0x11, 0x03, 0x02, 0x04, 0x01, 0xA2, 0x0C, 0x60, 0x03, 0xD0, 0x00, 0x05, 0x06,
+        ],
+        6,
+        |chip8| {
+            let row = &chip8.framebuffer()[3 * MEGA_GRID_WIDTH..4 * MEGA_GRID_WIDTH];
+            assert_eq!(row[2..6], [0, 5, 6, 0]);
+            assert_eq!(chip8.v(0xF), 0);
+        },
+    ),
+    // BGCOL
+    chip8x_case(Instruction::BGCOL, &[0x02, 0xA0], 1, |chip8| {
+        assert_eq!(chip8.color_zones().unwrap().background(), 1);
+    }),
+    // LD V0, 0x11; LD V1, 0x00; LD V2, 2; COL V0, V2: the second and third zones of the first row
+    chip8x_case(
+        Instruction::COLZONE,
+        &[0x60, 0x11, 0x61, 0x00, 0x62, 0x02, 0xB0, 0x20],
+        4,
+        |chip8| {
+            let zones = chip8.color_zones().unwrap();
+            assert_eq!(zones.foreground(8, 3), 2);
+            assert_eq!(zones.foreground(23, 0), 2);
+            assert_eq!(zones.foreground(24, 0), 1);
+            assert_eq!(zones.foreground(8, 4), 1);
+        },
+    ),
+    // LD V0, 15; LD V1, 4; LD V2, 7; COL V0, V2, 1
+    chip8x_case(
+        Instruction::COLROWS,
+        &[0x60, 0x0F, 0x61, 0x04, 0x62, 0x07, 0xB0, 0x21],
+        4,
+        |chip8| {
+            let zones = chip8.color_zones().unwrap();
+            assert_eq!(zones.foreground(8, 4), 7);
+            assert_eq!(zones.foreground(8, 5), 1);
+        },
+    ),
+    // LD V0, 0x05; SKP2 V0 with 5 held on the second keypad
+    Case {
+        second_key: Some(0x5),
+        ..chip8x_case(Instruction::SKP2, &[0x60, 0x05, 0xE0, 0xF2], 2, |chip8| {
+            assert_eq!(chip8.pc(), 0x206);
+        })
+    },
+    // LD V0, 0x05; SKNP2 V0 with 5 held on the first keypad only
+    Case {
+        keys: 0x0020,
+        ..chip8x_case(Instruction::SKNP2, &[0x60, 0x05, 0xE0, 0xF5], 2, |chip8| {
+            assert_eq!(chip8.pc(), 0x206);
+        })
+    },
+    // LD V0, 0x80; OUT V0, without a tone generator to set
+    chip8x_case(Instruction::OUT, &[0x60, 0x80, 0xF0, 0xF8], 2, |chip8| {
+        assert_eq!(chip8.pc(), 0x204);
+    }),
+    // LD V0, 0x05; IN V0, with nothing plugged into the port
+    chip8x_case(Instruction::IN, &[0x60, 0x05, 0xF0, 0xFB], 2, |chip8| {
+        assert_eq!(chip8.v(0), 0);
+        assert_eq!(chip8.pc(), 0x204);
+    }),
+];
+
+#[test]
+fn every_instruction_runs_end_to_end() {
+    for case in CASES {
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .seed(0)
+            .clock(Box::new(StoppedClock))
+            .megachip(case.megachip)
+            .chip8x(case.chip8x)
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(case.rom).unwrap();
+        chip8.set_keys(case.keys);
+        if let Some(hex_key) = case.second_key {
+            chip8.set_second_key(hex_key, true);
+        }
+        chip8.run_cycles(case.cycles - 1).unwrap();
+
+        let ram = chip8.cpu().ram();
+        let pc = usize::from(chip8.pc());
+        let opcode =
+            u16::from_be_bytes([ram.read_byte(pc).unwrap(), ram.read_byte(pc + 1).unwrap()]);
+        assert_eq!(
+            chip8.cpu().decode_opcode(opcode),
+            Some(case.instruction),
+            "The ROM of {:?} ends with another instruction",
+            case.instruction
+        );
+        chip8.step().unwrap();
+        (case.check)(&chip8);
+    }
+}
+
+#[test]
+fn the_cases_cover_the_instruction_set() {
+    let covered: BTreeSet<_> = CASES.iter().map(|case| case.instruction as usize).collect();
+    let missing: Vec<_> = Instruction::ALL
+        .into_iter()
+        .filter(|&instruction| !covered.contains(&(instruction as usize)))
+        .collect();
+    assert!(missing.is_empty(), "No case for {missing:?}");
+}
@@ -0,0 +1,53 @@
+use std::sync::Mutex;
+
+use chip8_emu::Chip8;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+///
+/// Keeps every record, as `(level, target, message)`.
+///
+struct CapturingLogger(Mutex<Vec<(Level, String, String)>>);
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.0.lock().unwrap().push((
+            record.level(),
+            record.target().to_string(),
+            record.args().to_string(),
+        ));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+
+// The logger is process-wide, so everything is checked in a single test
+#[test]
+fn loading_and_tracing_are_logged() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let mut chip8 = Chip8::headless();
+    // LD V0, 0x05
+    chip8.load_rom_bytes(&[0x60, 0x05]).unwrap();
+    chip8.step().unwrap();
+
+    let records = LOGGER.0.lock().unwrap();
+    let logged = |level, message: &str| {
+        records.iter().any(|(record_level, target, text)| {
+            *record_level == level && target.starts_with("chip8_emu::emu") && text.contains(message)
+        })
+    };
+    assert!(logged(
+        Level::Debug,
+        "Quirks: shift true, memory true, jump false"
+    ));
+    assert!(logged(Level::Debug, "Loaded a ROM of 2 bytes at 0x0200"));
+    assert!(logged(Level::Trace, "0x0200: 6005"));
+    assert!(logged(Level::Trace, "V0 0x00 -> 0x05"));
+}
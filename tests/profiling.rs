@@ -0,0 +1,58 @@
+use std::fs;
+
+use chip8_emu::emu::stats::RunOutcome;
+use chip8_emu::Chip8;
+use tracing_subscriber::layer::SubscriberExt;
+
+///
+/// Runs the demo for a few frames with the spans written to a Chrome trace, and reads the trace
+/// back.
+///
+#[test]
+fn short_runs_make_a_chrome_trace() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chrome_trace.json");
+    let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+        .file(&path)
+        .include_args(true)
+        .build();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+        let mut chip8 = Chip8::builder()
+            .headless(true)
+            .seed(0)
+            .ips(45_000)
+            .build()
+            .unwrap();
+        chip8.load_demo().unwrap();
+        // Four frames of 750 instructions
+        chip8.set_budget(Some(3000), None);
+        assert_eq!(chip8.run().unwrap(), RunOutcome::BudgetExhausted);
+    });
+    drop(guard);
+
+    let trace: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).expect("the trace isn't JSON");
+    let events = trace
+        .as_array()
+        .expect("the trace isn't an array of events");
+    assert!(!events.is_empty());
+    let spans = |name: &str| {
+        events
+            .iter()
+            .filter(|event| event["name"] == name && event["ph"] == "B")
+            .count()
+    };
+    assert!(spans("frame") >= 4, "{events:?}");
+    assert!(spans("poll_input") >= 4);
+    assert!(spans("end_frame") >= 4);
+    assert!(spans("present") >= 1);
+    assert_eq!(spans("instructions"), 4);
+
+    // The instruction batches end with how many instructions ran, written as strings
+    let instructions: u64 = events
+        .iter()
+        .filter(|event| event["name"] == "instructions" && event["ph"] == "E")
+        .filter_map(|event| event["args"]["instructions"].as_str()?.parse::<u64>().ok())
+        .sum();
+    assert_eq!(instructions, 3000);
+}
@@ -0,0 +1,31 @@
+use chip8_emu::emu::stats::RunOutcome;
+use chip8_emu::frontend::sdl::render::Renderer;
+use chip8_emu::Chip8;
+
+///
+/// Runs the demo in a real SDL window under the dummy video driver, which draws nowhere, so the
+/// glue between the emulator and SDL is exercised without a display. SDL only takes one context
+/// per process at a time, hence a single test.
+///
+#[test]
+fn demo_runs_in_an_sdl_window() {
+    let mut chip8 = Chip8::builder()
+        .video_driver("dummy")
+        .renderer(Renderer::Software)
+        .vsync(true)
+        .side_panel(true)
+        .seed(0)
+        .build()
+        .unwrap();
+    chip8.load_demo().unwrap();
+
+    let summary = chip8.run_frames(300).unwrap();
+    assert!(summary.cycles >= 300 * 7);
+    assert!(chip8.framebuffer().contains(&1));
+
+    chip8.set_budget(Some(summary.cycles + 150), None);
+    assert_eq!(chip8.run().unwrap(), RunOutcome::BudgetExhausted);
+    let stats = chip8.stats();
+    assert_eq!(stats.cycles, summary.cycles + 150);
+    assert!(stats.to_string().starts_with("Executed "), "{stats}");
+}
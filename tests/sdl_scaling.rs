@@ -0,0 +1,117 @@
+use chip8_emu::emu::io::{GRID_HEIGHT, GRID_WIDTH};
+use chip8_emu::frontend::sdl::keymap::{Hotkeys, KeyMap};
+use chip8_emu::frontend::sdl::render::{RenderOptions, Renderer, ScaleFilter};
+use chip8_emu::frontend::sdl::window::WindowGeometry;
+use chip8_emu::frontend::sdl::SdlFrontend;
+use chip8_emu::frontend::Frontend;
+#[cfg(feature = "egui")]
+use chip8_emu::frontend::{DebugView, MEMORY_COLUMNS};
+
+///
+/// Returns whether every pixel of the capture is black or the green of a lit pixel, none of them
+/// blended.
+///
+fn is_sharp(pixels: &[u8]) -> bool {
+    pixels
+        .chunks_exact(3)
+        .all(|rgb| rgb == [0, 0, 0] || rgb == [0, 255, 0])
+}
+
+///
+/// Draws a single lit pixel in a window under the dummy video driver and reads the output back,
+/// scaled with each filter, and at the smallest size. SDL only takes one context per process at a
+/// time, hence a single test.
+///
+#[test]
+fn scale_filters_sample_the_screen() {
+    let render = RenderOptions {
+        video_driver: Some("dummy".to_string()),
+        renderer: Renderer::Software,
+        vsync: false,
+        scale_filter: ScaleFilter::Nearest,
+        visual_beep: None,
+    };
+    let mut window = SdlFrontend::new(
+        640,
+        320,
+        KeyMap::default(),
+        Hotkeys::default(),
+        false,
+        &render,
+    )
+    .unwrap();
+    let mut framebuffer = [0; GRID_WIDTH * GRID_HEIGHT];
+    framebuffer[5 * GRID_WIDTH + 10] = 1;
+
+    window.present(&framebuffer);
+    let (size, pixels) = window.capture().unwrap();
+    assert_eq!(size, (640, 320));
+    assert!(is_sharp(&pixels));
+    // The pixel is a 10×10 square at (100, 50)
+    let at = |pixels: &[u8], x: usize, y: usize| {
+        let index = (y * 640 + x) * 3;
+        [pixels[index], pixels[index + 1], pixels[index + 2]]
+    };
+    assert_eq!(at(&pixels, 100, 50), [0, 255, 0]);
+    assert_eq!(at(&pixels, 109, 59), [0, 255, 0]);
+    assert_eq!(at(&pixels, 110, 59), [0, 0, 0]);
+    assert_eq!(at(&pixels, 99, 50), [0, 0, 0]);
+
+    // The debug panels are drawn over the screen, and gone once they're hidden
+    #[cfg(feature = "egui")]
+    {
+        window.set_debug_view(Some(&DebugView {
+            registers: vec!["PC: 0x200".to_string()],
+            memory: vec![0; 8 * MEMORY_COLUMNS],
+            ..DebugView::default()
+        }));
+        // egui lays new windows out unseen on their first pass
+        window.present(&framebuffer);
+        window.present(&framebuffer);
+        let (_, pixels) = window.capture().unwrap();
+        assert!(!is_sharp(&pixels));
+        window.set_debug_view(None);
+        window.present(&framebuffer);
+        let (_, pixels) = window.capture().unwrap();
+        assert!(is_sharp(&pixels));
+    }
+
+    window.set_scale_filter(ScaleFilter::Linear);
+    assert_eq!(window.scale_filter(), ScaleFilter::Linear);
+    window.present(&framebuffer);
+    let (_, pixels) = window.capture().unwrap();
+    assert!(!is_sharp(&pixels));
+
+    // Integer scaling keeps the pixels sharp whatever the filter, even at a scale of 1.5625
+    window.restore_geometry(&WindowGeometry {
+        integer_scale: true,
+        ..WindowGeometry::centered((1000, 500))
+    });
+    assert_eq!(window.scale_filter(), ScaleFilter::Nearest);
+    window.present(&framebuffer);
+    let (size, pixels) = window.capture().unwrap();
+    assert_eq!(size, (1000, 500));
+    assert!(is_sharp(&pixels));
+    drop(window);
+
+    // The smallest window has a pixel for every pixel of the screen, and smaller ones are grown to
+    // it rather than drawing nothing
+    for (width, height) in [(64, 32), (0, 3)] {
+        let mut window = SdlFrontend::new(
+            width,
+            height,
+            KeyMap::default(),
+            Hotkeys::default(),
+            false,
+            &render,
+        )
+        .unwrap();
+        window.present(&framebuffer);
+        let (size, pixels) = window.capture().unwrap();
+        assert_eq!(size, (64, 32), "{width}×{height}");
+        let lit: Vec<usize> = (0..GRID_WIDTH * GRID_HEIGHT)
+            .filter(|&index| pixels[index * 3 + 1] == 255)
+            .collect();
+        assert_eq!(lit, [5 * GRID_WIDTH + 10], "{width}×{height}");
+    }
+}
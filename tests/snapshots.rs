@@ -0,0 +1,124 @@
+use std::fs;
+
+use chip8_emu::emu::quirks::Variant;
+use chip8_emu::Chip8;
+
+#[macro_use]
+mod common;
+
+use common::{screen_art, Snapshots};
+
+///
+/// Runs the program until it loops on its last instruction, a `JMP` to itself.
+///
+fn run(program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::builder().headless(true).seed(0).build().unwrap();
+    chip8.load_rom_bytes(program).unwrap();
+    chip8.run_cycles(100).unwrap();
+    chip8
+}
+
+// A 4×4 box, drawn right after the programs below
+const BOX: [u8; 4] = [0xF0, 0x90, 0x90, 0xF0];
+
+#[test]
+fn drw_wraps_around_the_edges() {
+    // LD I, 0x20A; LD V0, 62; LD V1, 30; DRW V0, V1, 4; JMP 0x208
+    let mut program = vec![0xA2, 0x0A, 0x60, 0x3E, 0x61, 0x1E, 0xD0, 0x14, 0x12, 0x08];
+    program.extend(BOX);
+    let chip8 = run(&program);
+
+    assert_framebuffer_snapshot!(chip8, "drw_wraps_around_the_edges");
+}
+
+#[test]
+fn drw_xors_overlapping_sprites() {
+    // LD I, 0x20E; DRW V0, V0, 4; LD V0, 2; LD V1, 1; DRW V0, V1, 4; JMP 0x20A
+    let mut program = vec![
+        0xA2, 0x0E, 0xD0, 0x04, 0x60, 0x02, 0x61, 0x01, 0xD0, 0x14, 0x12, 0x0A,
+    ];
+    program.extend([0, 0]);
+    program.extend(BOX);
+    let chip8 = run(&program);
+
+    assert_eq!(chip8.v(0xF), 1);
+    assert_framebuffer_snapshot!(chip8, "drw_xors_overlapping_sprites");
+}
+
+// SUPER-CHIP's scrolling (00Cn, 00FB and 00FC) has no snapshots: the emulator doesn't run those
+// instructions, `analysis` only recognizes them to suggest the variant
+
+#[test]
+fn megachip_screens_are_drawn_at_their_width() {
+    // MEGAON; JMP 0x202
+    let mut chip8 = Chip8::builder()
+        .headless(true)
+        .variant(Variant::MegaChip)
+        .build()
+        .unwrap();
+    chip8.load_rom_bytes(&[0x00, 0x11, 0x12, 0x02]).unwrap();
+    chip8.run_cycles(10).unwrap();
+
+    let art = screen_art(&chip8);
+    assert_eq!(art.lines().count(), 192);
+    assert!(art.lines().all(|line| line.len() == 256));
+}
+
+#[test]
+fn snapshot_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("screen.txt"), "#.\n.#\n").unwrap();
+
+    assert_eq!(
+        Snapshots::new(dir.path(), false).check("screen", "#.\n.#\n"),
+        Ok(())
+    );
+}
+
+#[test]
+fn snapshot_mismatch_shows_both_screens() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("screen.txt"), "#.\n.#\n").unwrap();
+
+    let failure = Snapshots::new(dir.path(), false)
+        .check("screen", "..\n##\n")
+        .unwrap_err();
+    assert_eq!(
+        failure,
+        "screen doesn't match its snapshot, rerun with CHIP8_BLESS=1 to update it.\n\
+         Expected:\n#.\n.#\nActual:\n..\n##\n"
+    );
+    // Nothing is written without blessing
+    assert_eq!(
+        fs::read_to_string(dir.path().join("screen.txt")).unwrap(),
+        "#.\n.#\n"
+    );
+}
+
+#[test]
+fn missing_snapshot_fails() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let failure = Snapshots::new(dir.path(), false)
+        .check("screen", "#\n")
+        .unwrap_err();
+    assert!(failure.starts_with("screen has no snapshot"), "{failure}");
+    assert!(!dir.path().join("screen.txt").exists());
+}
+
+#[test]
+fn blessing_writes_and_rewrites_snapshots() {
+    let dir = tempfile::tempdir().unwrap();
+    let snapshots = Snapshots::new(dir.path(), true);
+
+    assert_eq!(snapshots.check("screen", "#.\n"), Ok(()));
+    assert_eq!(
+        fs::read_to_string(snapshots.path("screen")).unwrap(),
+        "#.\n"
+    );
+    assert_eq!(snapshots.check("screen", ".#\n"), Ok(()));
+    assert_eq!(
+        fs::read_to_string(snapshots.path("screen")).unwrap(),
+        ".#\n"
+    );
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8_emu::Chip8;
+
+mod common;
+
+use common::{screen_art, Snapshots};
+
+///
+/// The ROMs checked into the repository, the IBM logo and corax89's opcode test in `tests/roms`
+/// and games of `assets`, with how many instructions they run before their screen is compared to
+/// the ASCII art of the same name in `tests/roms`.
+///
+const SUITE: &[(&str, &str, u64)] = &[
+    ("ibm-logo", "tests/roms/ibm-logo.ch8", 1_000),
+    ("test_opcode", "tests/roms/test_opcode.ch8", 5_000),
+    ("maze", "assets/MAZE", 5_000),
+    ("brix", "assets/BRIX", 5_000),
+    ("tetris", "assets/TETRIS", 5_000),
+    ("invaders", "assets/INVADERS", 20_000),
+    ("pong", "assets/PONG", 5_000),
+    ("ufo", "assets/UFO", 5_000),
+    ("blinky", "assets/BLINKY", 20_000),
+];
+
+///
+/// Runs the ROM deterministically, without a window, and returns its screen as ASCII art.
+///
+fn final_screen(rom: &[u8], cycles: u64) -> String {
+    let mut chip8 = Chip8::builder().headless(true).seed(0).build().unwrap();
+    chip8.load_rom_bytes(rom).unwrap();
+    chip8.run_cycles(cycles).unwrap();
+    screen_art(&chip8)
+}
+
+///
+/// Runs every ROM and compares its screen with its snapshot, returning what went wrong. A missing
+/// ROM is a failure like a wrong screen.
+///
+fn run_suite<'a>(
+    snapshots: &Snapshots,
+    roms: impl IntoIterator<Item = (&'a str, PathBuf, u64)>,
+) -> Vec<String> {
+    let mut failures = Vec::new();
+    for (name, path, cycles) in roms {
+        let rom = match fs::read(&path) {
+            Ok(rom) => rom,
+            Err(err) => {
+                failures.push(format!("Failed to read {}: {err}", path.display()));
+                continue;
+            }
+        };
+        if let Err(failure) = snapshots.check(name, &final_screen(&rom, cycles)) {
+            failures.push(format!("After {cycles} instructions, {failure}"));
+        }
+    }
+    failures
+}
+
+#[test]
+fn test_roms_end_on_the_expected_screens() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let roms = SUITE
+        .iter()
+        .map(|&(name, path, cycles)| (name, root.join(path), cycles));
+    let failures = run_suite(&Snapshots::in_tests(Path::new("roms")), roms);
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,117 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Instant;
+
+use chip8_emu::emu::stats::RunOutcome;
+use chip8_emu::frontend::{threaded, AudioSink, Frontend, Input, Keypad};
+use chip8_emu::Chip8;
+
+///
+/// The inputs the hammering frontend cycles through, covering every way of stopping, stepping,
+/// restarting and drawing over the run.
+///
+fn hammered_input(n: u32) -> Input {
+    #[allow(unused_mut)]
+    let mut inputs = vec![
+        Input::TogglePause,
+        Input::Step,
+        Input::Key {
+            hex_key: 5,
+            pressed: true,
+            at: Instant::now(),
+        },
+        Input::TogglePause,
+        Input::ToggleOverlay,
+        Input::Reset,
+        Input::TogglePane,
+        Input::Key {
+            hex_key: 5,
+            pressed: false,
+            at: Instant::now(),
+        },
+        Input::ToggleBreakpoint(0x202),
+        Input::ToggleBreakpoint(0x202),
+        Input::ReleaseKeys,
+    ];
+    #[cfg(feature = "egui")]
+    inputs.extend([Input::ToggleDebugView, Input::ScrollMemory(1)]);
+    inputs[n as usize % inputs.len()].clone()
+}
+
+///
+/// The `HammeringFrontend` struct represents a user mashing every control as fast as the main
+/// thread polls, until it closes the window after `polls` polls.
+///
+struct HammeringFrontend {
+    polls: u32,
+    sent: u32,
+    presents: Rc<Cell<u32>>,
+}
+
+impl Keypad for HammeringFrontend {
+    fn poll_input(&mut self) -> Option<Input> {
+        // A burst of inputs per poll of the main thread, and none in between
+        if self.sent % 4 == 3 {
+            self.sent += 1;
+            return None;
+        }
+        self.sent += 1;
+        if self.sent / 4 >= self.polls {
+            return Some(Input::Quit);
+        }
+        Some(hammered_input(self.sent))
+    }
+}
+
+impl AudioSink for HammeringFrontend {
+    fn set_tone(&mut self, _on: bool) {}
+}
+
+impl Frontend for HammeringFrontend {
+    fn present(&mut self, framebuffer: &[u8]) {
+        assert_eq!(framebuffer.len(), 64 * 32);
+        self.presents.set(self.presents.get() + 1);
+    }
+}
+
+#[test]
+fn commands_hammered_during_emulation() {
+    let presents = Rc::new(Cell::new(0));
+    let frontend = HammeringFrontend {
+        polls: 300,
+        sent: 0,
+        presents: Rc::clone(&presents),
+    };
+    let (outcome, cycles) = threaded::run(Box::new(frontend), |frontend| {
+        let mut chip8 = Chip8::builder()
+            .frontend(Box::new(frontend))
+            .build()
+            .unwrap();
+        chip8.load_demo().unwrap();
+        let outcome = chip8.run().unwrap();
+        (outcome, chip8.stats().cycles)
+    });
+    assert_eq!(outcome, RunOutcome::Quit);
+    assert!(cycles > 0);
+    assert!(presents.get() > 0);
+}
+
+#[test]
+fn closing_the_window_ends_a_busy_run() {
+    let presents = Rc::new(Cell::new(0));
+    let frontend = HammeringFrontend {
+        polls: 0,
+        sent: 0,
+        presents,
+    };
+    let outcome = threaded::run(Box::new(frontend), |frontend| {
+        let mut chip8 = Chip8::builder()
+            .frontend(Box::new(frontend))
+            .ips(1_000_000)
+            .build()
+            .unwrap();
+        chip8.load_demo().unwrap();
+        chip8.run().unwrap()
+    });
+    assert_eq!(outcome, RunOutcome::Quit);
+}